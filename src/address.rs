@@ -0,0 +1,493 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::encoding::base58::Base58Error;
+use crate::encoding::base58check;
+use crate::encoding::bech32;
+use crate::encoding::bech32::{decode_witness_program, encode_witness_program, Bech32Error};
+use crate::network::Network;
+use crate::script::standard::ScriptType;
+use crate::script::Script;
+
+// The output types that have an address, each with the hash or key its script_pubkey commits to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressKind {
+    P2pkh([u8; 20]),
+    P2sh([u8; 20]),
+    P2wpkh([u8; 20]),
+    P2wsh([u8; 32]),
+    // The BIP341 output key, x-only
+    P2tr([u8; 32]),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+    Base58(Base58Error),
+    Bech32(Bech32Error),
+    // A base58check version byte that is not P2PKH or P2SH on any network
+    UnknownVersion(u8),
+    // A base58check payload that is not a 20-byte hash
+    InvalidPayloadLength(usize),
+    // A valid bech32 string under a human-readable part no network uses
+    UnknownHrp(String),
+    // A valid witness program that is none of P2WPKH, P2WSH and P2TR, such as one for a future version
+    UnsupportedWitnessProgram { version: u8, length: usize },
+}
+
+impl Display for AddressError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddressError::Base58(error) => write!(f, "{}", error),
+            AddressError::Bech32(error) => write!(f, "{}", error),
+            AddressError::UnknownVersion(version) => write!(f, "Unknown address version byte {:#04x}.", version),
+            AddressError::InvalidPayloadLength(length) => {
+                write!(f, "Address payload of {} bytes is not a 20-byte hash.", length)
+            }
+            AddressError::UnknownHrp(hrp) => write!(f, "No network uses the human-readable part '{}'.", hrp),
+            AddressError::UnsupportedWitnessProgram { version, length } => {
+                write!(f, "Witness version {} program of {} bytes has no address type.", version, length)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+impl From<Base58Error> for AddressError {
+    fn from(error: Base58Error) -> Self {
+        AddressError::Base58(error)
+    }
+}
+
+impl From<Bech32Error> for AddressError {
+    fn from(error: Bech32Error) -> Self {
+        AddressError::Bech32(error)
+    }
+}
+
+// An output type together with the network it is meant for, which only shows in the encoded string
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Address {
+    kind: AddressKind,
+    network: Network,
+}
+
+impl Address {
+    pub fn new(kind: AddressKind, network: Network) -> Self {
+        Self { kind, network }
+    }
+
+    pub fn kind(&self) -> AddressKind {
+        self.kind
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn address_type(&self) -> ScriptType {
+        match self.kind {
+            AddressKind::P2pkh(_) => ScriptType::P2pkh,
+            AddressKind::P2sh(_) => ScriptType::P2sh,
+            AddressKind::P2wpkh(_) => ScriptType::P2wpkh,
+            AddressKind::P2wsh(_) => ScriptType::P2wsh,
+            AddressKind::P2tr(_) => ScriptType::P2tr,
+        }
+    }
+
+    // The hash or witness program the address encodes, without its version byte or witness version
+    pub fn payload_bytes(&self) -> &[u8] {
+        match &self.kind {
+            AddressKind::P2pkh(hash) | AddressKind::P2sh(hash) | AddressKind::P2wpkh(hash) => hash,
+            AddressKind::P2wsh(hash) => hash,
+            AddressKind::P2tr(output_key) => output_key,
+        }
+    }
+
+    // The output template paying to this address, which is the same on every network
+    pub fn script_pubkey(&self) -> Script {
+        match &self.kind {
+            AddressKind::P2pkh(hash) => Script::p2pkh(hash),
+            AddressKind::P2sh(hash) => Script::p2sh(hash),
+            AddressKind::P2wpkh(hash) => Script::p2wpkh(hash),
+            AddressKind::P2wsh(hash) => Script::p2wsh(hash),
+            AddressKind::P2tr(output_key) => Script::p2tr(output_key),
+        }
+    }
+
+    fn segwit_address(&self, version: u8, program: &[u8]) -> String {
+        encode_witness_program(self.network.bech32_hrp(), version, program)
+            .expect("Every address kind holds a program of a valid length.")
+    }
+
+    fn from_segwit(s: &str, network: Network) -> Result<Self, AddressError> {
+        let (version, program) = decode_witness_program(network.bech32_hrp(), s)?;
+        let kind = match (version, program.len()) {
+            (0, 20) => AddressKind::P2wpkh(program.try_into().expect("The program is 20 bytes.")),
+            (0, 32) => AddressKind::P2wsh(program.try_into().expect("The program is 32 bytes.")),
+            (1, 32) => AddressKind::P2tr(program.try_into().expect("The program is 32 bytes.")),
+            (version, length) => return Err(AddressError::UnsupportedWitnessProgram { version, length }),
+        };
+
+        Ok(Self::new(kind, network))
+    }
+
+    fn from_base58check(s: &str) -> Result<Self, AddressError> {
+        let (version, payload) = base58check::decode(s)?;
+        let hash: [u8; 20] =
+            payload.as_slice().try_into().map_err(|_| AddressError::InvalidPayloadLength(payload.len()))?;
+
+        for network in [Network::Mainnet, Network::Testnet] {
+            if version == network.p2pkh_version() {
+                return Ok(Self::new(AddressKind::P2pkh(hash), network));
+            }
+            if version == network.p2sh_version() {
+                return Ok(Self::new(AddressKind::P2sh(hash), network));
+            }
+        }
+
+        Err(AddressError::UnknownVersion(version))
+    }
+}
+
+impl Display for Address {
+    // Base58check with the network's version byte for P2PKH and P2SH, bech32 or bech32m for witness programs
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let address = match &self.kind {
+            AddressKind::P2pkh(hash) => base58check::encode(self.network.p2pkh_version(), hash),
+            AddressKind::P2sh(hash) => base58check::encode(self.network.p2sh_version(), hash),
+            AddressKind::P2wpkh(hash) => self.segwit_address(0, hash),
+            AddressKind::P2wsh(hash) => self.segwit_address(0, hash),
+            AddressKind::P2tr(output_key) => self.segwit_address(1, output_key),
+        };
+
+        write!(f, "{}", address)
+    }
+}
+
+/*
+    Parses any of the five address types. Testnet and signet share every prefix, and regtest shares testnet's
+    base58check version bytes, so those addresses come back as Testnet.
+ */
+impl FromStr for Address {
+    type Err = AddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        let segwit_network = [Network::Mainnet, Network::Testnet, Network::Regtest]
+            .into_iter()
+            .find(|network| lower.starts_with(&format!("{}1", network.bech32_hrp())));
+
+        match segwit_network {
+            Some(network) => Self::from_segwit(s, network),
+            // A base58 string can hold a '1' as well, so only one with a bech32 checksum is taken for bech32
+            None => match bech32::decode(s) {
+                Ok((hrp, _, _)) => Err(AddressError::UnknownHrp(hrp)),
+                Err(_) => Self::from_base58check(s),
+            },
+        }
+    }
+}
+
+impl Script {
+    // The address a block explorer would show for this script_pubkey, or None when it is not one of the five types
+    pub fn to_address(&self, network: Network) -> Option<Address> {
+        let program = || self.witness_program().map(|(_, program)| program);
+        let kind = match self.classify() {
+            ScriptType::P2pkh => AddressKind::P2pkh(self.p2pkh_hash()?),
+            ScriptType::P2sh => AddressKind::P2sh(self.redeem_script_hash()?),
+            ScriptType::P2wpkh => AddressKind::P2wpkh(program()?.try_into().ok()?),
+            ScriptType::P2wsh => AddressKind::P2wsh(program()?.try_into().ok()?),
+            ScriptType::P2tr => AddressKind::P2tr(program()?.try_into().ok()?),
+            _ => return None,
+        };
+
+        Some(Address::new(kind, network))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use crate::address::{Address, AddressError, AddressKind};
+    use crate::encoding::base58::Base58Error;
+    use crate::encoding::base58check;
+    use crate::encoding::bech32::{Bech32Error, Variant};
+    use crate::encoding::hex;
+    use crate::network::Network;
+    use crate::script::opcodes::{OP_1, OP_2, OP_CHECKMULTISIG};
+    use crate::script::standard::ScriptType;
+    use crate::script::{Cmd, Script};
+
+    fn address_of(script_pubkey_hex: &str, network: Network) -> Option<String> {
+        let script = Script::from_bytes(&hex::decode(script_pubkey_hex).unwrap()).unwrap();
+
+        script.to_address(network).map(|address| address.to_string())
+    }
+
+    #[test]
+    fn standard_outputs_map_to_their_addresses() {
+        let cases = [
+            // The genesis block's pubkey hash, which people still pay to
+            ("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac", "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"),
+            // The Programming Bitcoin chapter 8 multisig
+            ("a91474d691da1574e6b3c192ecfb52cc8984ee7b6c5687", "3CLoMMyuoDQTPRD3XYZtCvgvkadrAdvdXh"),
+            // BIP173
+            ("0014751e76e8199196d454941c45d1b3a323f1433bd6", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"),
+            (
+                "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+                "bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3",
+            ),
+            // BIP86, the first receiving address of the test mnemonic
+            (
+                "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+                "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+            ),
+        ];
+
+        for (script_pubkey, address) in cases {
+            assert_eq!(address_of(script_pubkey, Network::Mainnet).as_deref(), Some(address), "{}", script_pubkey);
+        }
+    }
+
+    #[test]
+    fn the_network_only_changes_the_encoding() {
+        let script = Script::p2wsh(&[0x18; 32]);
+
+        let mainnet = script.to_address(Network::Mainnet).unwrap();
+        let testnet = script.to_address(Network::Testnet).unwrap();
+
+        assert_eq!(mainnet.kind(), testnet.kind());
+        assert_eq!(mainnet.kind(), AddressKind::P2wsh([0x18; 32]));
+        assert!(testnet.to_string().starts_with("tb1q"));
+        assert_eq!(
+            address_of("0014751e76e8199196d454941c45d1b3a323f1433bd6", Network::Regtest).as_deref(),
+            Some("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080")
+        );
+        let p2pkh = AddressKind::P2pkh([0x62; 20]);
+        assert_eq!(Address::new(p2pkh, Network::Signet).to_string(), Address::new(p2pkh, Network::Testnet).to_string());
+    }
+
+    #[test]
+    fn nonstandard_scripts_have_no_address() {
+        let keys = vec![Cmd::push(vec![2; 33]), Cmd::push(vec![3; 33])];
+        let bare_multisig =
+            Script::new([vec![Cmd::Op(OP_1)], keys, vec![Cmd::Op(OP_2), Cmd::Op(OP_CHECKMULTISIG)]].concat());
+        let future_version = Script::new(vec![Cmd::Op(OP_2), Cmd::push(vec![0; 32])]);
+        let short_taproot = Script::new(vec![Cmd::Op(OP_1), Cmd::push(vec![0; 20])]);
+
+        for script in [Script::op_return(b"data").unwrap(), bare_multisig, future_version, short_taproot] {
+            assert_eq!(script.to_address(Network::Mainnet), None, "{:?}", script);
+        }
+    }
+
+    #[test]
+    fn addresses_give_back_the_script_they_came_from() {
+        let cases = [
+            "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac",
+            "a91474d691da1574e6b3c192ecfb52cc8984ee7b6c5687",
+            "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+            "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+            "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+        ];
+
+        for script_pubkey in cases {
+            let script = Script::from_bytes(&hex::decode(script_pubkey).unwrap()).unwrap();
+
+            for network in Network::ALL {
+                let address = script.to_address(network).unwrap();
+
+                assert_eq!(address.network(), network);
+                assert_eq!(&hex::encode(&address.script_pubkey().to_bytes()), script_pubkey);
+            }
+        }
+    }
+
+    #[test]
+    fn script_pubkeys_use_the_exact_templates() {
+        let script_pubkey = |kind| hex::encode(&Address::new(kind, Network::Testnet).script_pubkey().to_bytes());
+
+        assert_eq!(script_pubkey(AddressKind::P2pkh([0x11; 20])), format!("76a914{}88ac", "11".repeat(20)));
+        assert_eq!(script_pubkey(AddressKind::P2sh([0x22; 20])), format!("a914{}87", "22".repeat(20)));
+        assert_eq!(script_pubkey(AddressKind::P2wpkh([0x33; 20])), format!("0014{}", "33".repeat(20)));
+        assert_eq!(script_pubkey(AddressKind::P2wsh([0x44; 32])), format!("0020{}", "44".repeat(32)));
+        assert_eq!(script_pubkey(AddressKind::P2tr([0x55; 32])), format!("5120{}", "55".repeat(32)));
+    }
+
+    #[test]
+    fn addresses_parse_into_network_type_and_payload() {
+        // The testnet P2PKH is the book's key 5002, uncompressed, and the testnet P2SH is BIP49's vector
+        let cases = [
+            (
+                "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa",
+                Network::Mainnet,
+                ScriptType::P2pkh,
+                "62e907b15cbf27d5425399ebf6f0fb50ebb88f18",
+            ),
+            (
+                "mmTPbXQFxboEtNRkwfh6K51jvdtHLxGeMA",
+                Network::Testnet,
+                ScriptType::P2pkh,
+                "41243614aecd13819d7a7f348a4a07fbcb29d8e5",
+            ),
+            (
+                "3CLoMMyuoDQTPRD3XYZtCvgvkadrAdvdXh",
+                Network::Mainnet,
+                ScriptType::P2sh,
+                "74d691da1574e6b3c192ecfb52cc8984ee7b6c56",
+            ),
+            (
+                "2Mww8dCYPUpKHofjgcXcBCEGmniw9CoaiD2",
+                Network::Testnet,
+                ScriptType::P2sh,
+                "336caa13e08b96080a32b5d818d59b4ab3b36742",
+            ),
+            (
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+                Network::Mainnet,
+                ScriptType::P2wpkh,
+                "751e76e8199196d454941c45d1b3a323f1433bd6",
+            ),
+            (
+                "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+                Network::Testnet,
+                ScriptType::P2wpkh,
+                "751e76e8199196d454941c45d1b3a323f1433bd6",
+            ),
+            (
+                "bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3",
+                Network::Mainnet,
+                ScriptType::P2wsh,
+                "1863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+            ),
+            (
+                "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+                Network::Testnet,
+                ScriptType::P2wsh,
+                "1863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+            ),
+            (
+                "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+                Network::Mainnet,
+                ScriptType::P2tr,
+                "a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+            ),
+            (
+                "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c",
+                Network::Testnet,
+                ScriptType::P2tr,
+                "000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433",
+            ),
+        ];
+
+        for (string, network, address_type, payload) in cases {
+            let address = Address::from_str(string).unwrap_or_else(|error| panic!("{}: {}", string, error));
+
+            assert_eq!(address.network(), network, "{}", string);
+            assert_eq!(address.address_type(), address_type, "{}", string);
+            assert_eq!(hex::encode(address.payload_bytes()), payload, "{}", string);
+            assert_eq!(address.to_string(), string);
+            assert_eq!(address.script_pubkey().classify(), address_type, "{}", string);
+        }
+    }
+
+    #[test]
+    fn upper_case_segwit_and_regtest_addresses_parse() {
+        let upper = Address::from_str("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4").unwrap();
+        let regtest = Address::from_str("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080").unwrap();
+
+        assert_eq!(upper.to_string(), "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4");
+        assert_eq!(regtest.network(), Network::Regtest);
+        assert_eq!(regtest.kind(), upper.kind());
+    }
+
+    #[test]
+    fn bip173_and_bip350_invalid_addresses_are_rejected() {
+        let bech32 = AddressError::Bech32;
+        let vectors = [
+            // BIP173
+            ("tc1qw508d6qejxtdg4y5r3zarvary0c5xw7kg3g4ty", AddressError::UnknownHrp("tc".to_string())),
+            ("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5", bech32(Bech32Error::InvalidChecksum)),
+            ("BC13W508D6QEJXTDG4Y5R3ZARVARY0C5XW7KN40WF2", bech32(Bech32Error::InvalidWitnessVersion(17))),
+            ("bc1rw5uspcuh", bech32(Bech32Error::InvalidProgramLength(1))),
+            (
+                "bc10w508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kw5rljs90",
+                bech32(Bech32Error::InvalidProgramLength(41)),
+            ),
+            ("BC1QR508D6QEJXTDG4Y5R3ZARVARYV98GJ9P", bech32(Bech32Error::InvalidProgramLength(16))),
+            ("tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sL5k7", bech32(Bech32Error::MixedCase)),
+            ("tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3pjxtptv", bech32(Bech32Error::InvalidPadding)),
+            ("bc1gmk9yu", bech32(Bech32Error::EmptyData)),
+            // BIP350
+            (
+                "tc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq5zuyut",
+                AddressError::UnknownHrp("tc".to_string()),
+            ),
+            (
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd",
+                bech32(Bech32Error::WrongVariant(Variant::Bech32)),
+            ),
+            (
+                "tb1z0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqglt7rf",
+                bech32(Bech32Error::WrongVariant(Variant::Bech32)),
+            ),
+            (
+                "BC1S0XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ54WELL",
+                bech32(Bech32Error::WrongVariant(Variant::Bech32)),
+            ),
+            ("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh", bech32(Bech32Error::WrongVariant(Variant::Bech32m))),
+            (
+                "tb1q0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq24jc47",
+                bech32(Bech32Error::WrongVariant(Variant::Bech32m)),
+            ),
+            (
+                "bc1p38j9r5y49hruaue7wxjce0updqjuyyx0kh56v8s25huc6995vvpql3jow4",
+                bech32(Bech32Error::InvalidCharacter { character: 'o', position: 59 }),
+            ),
+            (
+                "BC130XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ7ZWS8R",
+                bech32(Bech32Error::InvalidWitnessVersion(17)),
+            ),
+            ("bc1pw5dgrnzv", bech32(Bech32Error::InvalidProgramLength(1))),
+            (
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v8n0nx0muaewav253zgeav",
+                bech32(Bech32Error::InvalidProgramLength(41)),
+            ),
+            ("tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq47Zagq", bech32(Bech32Error::MixedCase)),
+            ("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v07qwwzcrf", bech32(Bech32Error::InvalidPadding)),
+            ("tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vpggkg4j", bech32(Bech32Error::InvalidPadding)),
+        ];
+
+        for (address, expected) in vectors {
+            assert_eq!(Address::from_str(address), Err(expected), "{}", address);
+        }
+    }
+
+    #[test]
+    fn witness_programs_without_an_address_type_are_rejected() {
+        // Valid BIP350 addresses, but for programs none of the five types pays to
+        let long_v1 = "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y";
+
+        assert_eq!(
+            Address::from_str(long_v1),
+            Err(AddressError::UnsupportedWitnessProgram { version: 1, length: 40 })
+        );
+        assert_eq!(
+            Address::from_str("BC1SW50QGDZ25J"),
+            Err(AddressError::UnsupportedWitnessProgram { version: 16, length: 2 })
+        );
+    }
+
+    #[test]
+    fn invalid_base58check_addresses_are_rejected() {
+        assert_eq!(
+            Address::from_str("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb"),
+            Err(AddressError::Base58(Base58Error::InvalidChecksum))
+        );
+        // Litecoin's P2PKH version byte
+        assert_eq!(Address::from_str(&base58check::encode(0x30, &[0; 20])), Err(AddressError::UnknownVersion(0x30)));
+        assert_eq!(
+            Address::from_str(&base58check::encode(0x00, &[0; 21])),
+            Err(AddressError::InvalidPayloadLength(21))
+        );
+    }
+}
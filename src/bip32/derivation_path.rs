@@ -0,0 +1,296 @@
+use std::fmt::{Display, Formatter};
+use std::slice::Iter;
+use std::str::FromStr;
+
+// Indices from 2^31 onwards are reserved for hardened children
+const HARDENED_OFFSET: u32 = 0x8000_0000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DerivationPathError {
+    MissingMasterPrefix,
+    EmptyComponent,
+    InvalidIndex(String),
+    IndexOutOfRange(String),
+}
+
+impl Display for DerivationPathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DerivationPathError::MissingMasterPrefix => write!(f, "Derivation path must start with 'm'."),
+            DerivationPathError::EmptyComponent => write!(f, "Derivation path contains an empty component."),
+            DerivationPathError::InvalidIndex(component) => write!(f, "Invalid child index '{}'.", component),
+            DerivationPathError::IndexOutOfRange(component) => {
+                write!(f, "Child index '{}' is out of range 0 to {}.", component, HARDENED_OFFSET - 1)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DerivationPathError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChildNumber {
+    Normal(u32),
+    Hardened(u32),
+}
+
+impl ChildNumber {
+    pub fn normal(index: u32) -> Result<Self, DerivationPathError> {
+        if index >= HARDENED_OFFSET {
+            return Err(DerivationPathError::IndexOutOfRange(index.to_string()));
+        }
+
+        Ok(ChildNumber::Normal(index))
+    }
+
+    pub fn hardened(index: u32) -> Result<Self, DerivationPathError> {
+        if index >= HARDENED_OFFSET {
+            return Err(DerivationPathError::IndexOutOfRange(index.to_string()));
+        }
+
+        Ok(ChildNumber::Hardened(index))
+    }
+
+    pub fn is_hardened(&self) -> bool {
+        matches!(self, ChildNumber::Hardened(_))
+    }
+
+    pub fn index(&self) -> u32 {
+        match self {
+            ChildNumber::Normal(index) | ChildNumber::Hardened(index) => *index,
+        }
+    }
+
+    // The 32-bit value that is serialized and fed into CKD
+    pub fn to_u32(&self) -> u32 {
+        match self {
+            ChildNumber::Normal(index) => *index,
+            ChildNumber::Hardened(index) => index + HARDENED_OFFSET,
+        }
+    }
+}
+
+impl From<u32> for ChildNumber {
+    fn from(value: u32) -> Self {
+        match value >= HARDENED_OFFSET {
+            true => ChildNumber::Hardened(value - HARDENED_OFFSET),
+            false => ChildNumber::Normal(value),
+        }
+    }
+}
+
+impl Display for ChildNumber {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChildNumber::Normal(index) => write!(f, "{}", index),
+            ChildNumber::Hardened(index) => write!(f, "{}'", index),
+        }
+    }
+}
+
+impl FromStr for ChildNumber {
+    type Err = DerivationPathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(DerivationPathError::EmptyComponent);
+        }
+
+        let (digits, hardened) = match s.strip_suffix(['\'', 'h', 'H']) {
+            Some(digits) => (digits, true),
+            None => (s, false),
+        };
+
+        if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(DerivationPathError::InvalidIndex(s.to_string()));
+        }
+
+        let index = match digits.parse::<u32>() {
+            Ok(index) if index < HARDENED_OFFSET => index,
+            _ => return Err(DerivationPathError::IndexOutOfRange(s.to_string())),
+        };
+
+        match hardened {
+            true => Ok(ChildNumber::Hardened(index)),
+            false => Ok(ChildNumber::Normal(index)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DerivationPath {
+    children: Vec<ChildNumber>,
+}
+
+impl DerivationPath {
+    pub fn master() -> Self {
+        Self::default()
+    }
+
+    pub fn is_master(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.children.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    pub fn iter(&self) -> Iter<'_, ChildNumber> {
+        self.children.iter()
+    }
+
+    pub fn child(&self, child: ChildNumber) -> Self {
+        let mut children = self.children.clone();
+        children.push(child);
+
+        Self { children }
+    }
+}
+
+impl From<Vec<ChildNumber>> for DerivationPath {
+    fn from(children: Vec<ChildNumber>) -> Self {
+        Self { children }
+    }
+}
+
+impl<'a> IntoIterator for &'a DerivationPath {
+    type Item = &'a ChildNumber;
+    type IntoIter = Iter<'a, ChildNumber>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.children.iter()
+    }
+}
+
+impl Display for DerivationPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "m")?;
+        for child in &self.children {
+            write!(f, "/{}", child)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = DerivationPathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut components = s.split('/');
+
+        if components.next() != Some("m") {
+            return Err(DerivationPathError::MissingMasterPrefix);
+        }
+
+        let children = components
+            .map(ChildNumber::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { children })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use crate::bip32::derivation_path::{ChildNumber, DerivationPath, DerivationPathError};
+
+    #[test]
+    fn master_path_can_be_parsed() {
+        let subject = DerivationPath::from_str("m").unwrap();
+
+        assert_eq!(subject, DerivationPath::master());
+        assert!(subject.is_master());
+        assert_eq!(&subject.to_string(), "m");
+    }
+
+    #[test]
+    fn bip84_path_can_be_parsed() {
+        let subject = DerivationPath::from_str("m/84'/0'/0'/0/0").unwrap();
+
+        let children: Vec<ChildNumber> = subject.iter().copied().collect();
+        assert_eq!(children, vec![
+            ChildNumber::Hardened(84),
+            ChildNumber::Hardened(0),
+            ChildNumber::Hardened(0),
+            ChildNumber::Normal(0),
+            ChildNumber::Normal(0),
+        ]);
+    }
+
+    #[test]
+    fn all_hardened_markers_are_accepted_and_displayed_canonically() {
+        let paths = ["m/44'/1h/2H/3", "m/44h/1'/2'/3", "m/44H/1H/2h/3"];
+
+        for path in paths {
+            let subject = DerivationPath::from_str(path).unwrap();
+
+            assert_eq!(&subject.to_string(), "m/44'/1'/2'/3");
+        }
+    }
+
+    #[test]
+    fn paths_round_trip_through_display() {
+        let paths = [
+            "m",
+            "m/0",
+            "m/0'",
+            "m/0'/1/2'/2/1000000000",
+            "m/2147483647'/0/2147483647",
+        ];
+
+        for path in paths {
+            let subject = DerivationPath::from_str(path).unwrap();
+
+            assert_eq!(&subject.to_string(), path);
+        }
+    }
+
+    #[test]
+    fn child_numbers_convert_to_and_from_raw_index() {
+        assert_eq!(ChildNumber::Hardened(0).to_u32(), 0x8000_0000);
+        assert_eq!(ChildNumber::Hardened(2147483647).to_u32(), u32::MAX);
+        assert_eq!(ChildNumber::Normal(5).to_u32(), 5);
+        assert_eq!(ChildNumber::from(0x8000_0001), ChildNumber::Hardened(1));
+        assert_eq!(ChildNumber::from(7), ChildNumber::Normal(7));
+    }
+
+    #[test]
+    fn index_past_two_to_the_thirty_one_is_rejected() {
+        let result = DerivationPath::from_str("m/2147483648");
+        let hardened_result = DerivationPath::from_str("m/2147483648'");
+        let huge_result = DerivationPath::from_str("m/99999999999");
+
+        assert_eq!(result, Err(DerivationPathError::IndexOutOfRange("2147483648".to_string())));
+        assert_eq!(hardened_result, Err(DerivationPathError::IndexOutOfRange("2147483648'".to_string())));
+        assert_eq!(huge_result, Err(DerivationPathError::IndexOutOfRange("99999999999".to_string())));
+        assert_eq!(ChildNumber::hardened(0x8000_0000), Err(DerivationPathError::IndexOutOfRange("2147483648".to_string())));
+    }
+
+    #[test]
+    fn malformed_paths_are_rejected() {
+        assert_eq!(DerivationPath::from_str(""), Err(DerivationPathError::MissingMasterPrefix));
+        assert_eq!(DerivationPath::from_str("84'/0'"), Err(DerivationPathError::MissingMasterPrefix));
+        assert_eq!(DerivationPath::from_str("m/"), Err(DerivationPathError::EmptyComponent));
+        assert_eq!(DerivationPath::from_str("m//1"), Err(DerivationPathError::EmptyComponent));
+        assert_eq!(DerivationPath::from_str("m/'"), Err(DerivationPathError::InvalidIndex("'".to_string())));
+        assert_eq!(DerivationPath::from_str("m/-1"), Err(DerivationPathError::InvalidIndex("-1".to_string())));
+        assert_eq!(DerivationPath::from_str("m/1''"), Err(DerivationPathError::InvalidIndex("1''".to_string())));
+        assert_eq!(DerivationPath::from_str("m/+1"), Err(DerivationPathError::InvalidIndex("+1".to_string())));
+    }
+
+    #[test]
+    fn child_extends_the_path() {
+        let path = DerivationPath::from_str("m/84'").unwrap();
+
+        let subject = path.child(ChildNumber::Hardened(0));
+
+        assert_eq!(&subject.to_string(), "m/84'/0'");
+        assert_eq!(subject.len(), 2);
+    }
+}
@@ -0,0 +1,792 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::bip32::derivation_path::{ChildNumber, DerivationPath};
+use crate::ecc::private_key::PrivateKey;
+use crate::ecc::scalar::Scalar;
+use crate::ecc::secp256k1::{PointError, S256Point};
+use crate::encoding::base58::Base58Error;
+use crate::encoding::base58check;
+use crate::encoding::hex;
+use crate::hashing::hash160::hash160;
+use crate::hashing::hmac::{hmac_sha512, HmacSha512};
+use crate::network::Network;
+
+// The HMAC key BIP32 derives the master key from the seed with
+const MASTER_KEY: &[u8] = b"Bitcoin seed";
+// Seeds are from 128 to 512 bits
+const MIN_SEED_LENGTH: usize = 16;
+const MAX_SEED_LENGTH: usize = 64;
+// version, depth, parent fingerprint, child number, chain code and key
+const SERIALIZED_LENGTH: usize = 4 + 1 + 4 + 4 + 32 + 33;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bip32Error {
+    // The number of seed bytes, which has to be from 16 to 64
+    InvalidSeedLength(usize),
+    // A seed whose HMAC is zero or not below N as a key, which no known seed gives
+    InvalidMasterKey,
+    // Hardened children commit to the parent's secret, so a public key cannot derive them
+    HardenedFromPublic(ChildNumber),
+    // Every index from the one asked for to the end of its range gave an invalid key
+    NoValidChild(ChildNumber),
+    // A child below depth 255, the most the serialization has room for
+    MaxDepth,
+    Base58(Base58Error),
+    // The number of bytes inside the base58check, which has to be 78
+    InvalidLength(usize),
+    // Version bytes that are not this kind of key's on any network, such as an xpub's read as an xprv
+    UnknownVersion([u8; 4]),
+    // A private key envelope whose key does not start with 0x00, such as one holding a public key
+    InvalidPrivateKeyPrefix(u8),
+    // A private key of zero or not below N
+    InvalidPrivateKey,
+    InvalidPublicKey(PointError),
+    // A depth of zero with a parent fingerprint or child number, which only a derived key has
+    ZeroDepthNotMaster,
+}
+
+impl Display for Bip32Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Bip32Error::InvalidSeedLength(length) => {
+                write!(f, "Seeds are {} to {} bytes, not {}.", MIN_SEED_LENGTH, MAX_SEED_LENGTH, length)
+            }
+            Bip32Error::InvalidMasterKey => write!(f, "Seed gives an invalid master key."),
+            Bip32Error::HardenedFromPublic(child) => {
+                write!(f, "Hardened child {} cannot be derived from a public key.", child)
+            }
+            Bip32Error::NoValidChild(child) => write!(f, "No valid child key from index {} on.", child),
+            Bip32Error::MaxDepth => write!(f, "Extended keys cannot be deeper than 255."),
+            Bip32Error::Base58(error) => write!(f, "{}", error),
+            Bip32Error::InvalidLength(length) => {
+                write!(f, "Extended keys are {} bytes, not {}.", SERIALIZED_LENGTH, length)
+            }
+            Bip32Error::UnknownVersion(version) => {
+                write!(f, "Unknown extended key version {}.", hex::encode(version))
+            }
+            Bip32Error::InvalidPrivateKeyPrefix(prefix) => {
+                write!(f, "Extended private key starts with {:#04x} instead of 0x00.", prefix)
+            }
+            Bip32Error::InvalidPrivateKey => write!(f, "Extended private key is not between 1 and N - 1."),
+            Bip32Error::InvalidPublicKey(error) => write!(f, "{}", error),
+            Bip32Error::ZeroDepthNotMaster => {
+                write!(f, "Extended key of depth 0 has a parent fingerprint or child number.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Bip32Error {}
+
+impl From<Base58Error> for Bip32Error {
+    fn from(error: Base58Error) -> Self {
+        Bip32Error::Base58(error)
+    }
+}
+
+impl From<PointError> for Bip32Error {
+    fn from(error: PointError) -> Self {
+        Bip32Error::InvalidPublicKey(error)
+    }
+}
+
+/*
+    A private key and chain code at some node of a BIP32 tree, together with where that node hangs:
+
+    depth               0 for the master key, one more for each derivation
+    parent_fingerprint  the first 4 bytes of the hash160 of the parent's compressed public key, zero for the master
+    child_number        the index this key was derived at, Normal(0) for the master
+
+    The network only picks the version bytes of the serialization, xprv or tprv.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedPrivateKey {
+    network: Network,
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: ChildNumber,
+    chain_code: [u8; 32],
+    private_key: PrivateKey,
+}
+
+// The public half of an ExtendedPrivateKey, which can derive the same normal children's public keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtendedPublicKey {
+    network: Network,
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: ChildNumber,
+    chain_code: [u8; 32],
+    public_key: S256Point,
+}
+
+impl ExtendedPrivateKey {
+    /*
+        The master key: the left half of HMAC-SHA512("Bitcoin seed", seed) as the key, the right half as chain code.
+        It is for mainnet until set_network says otherwise.
+     */
+    pub fn from_seed(seed: &[u8]) -> Result<Self, Bip32Error> {
+        if !(MIN_SEED_LENGTH..=MAX_SEED_LENGTH).contains(&seed.len()) {
+            return Err(Bip32Error::InvalidSeedLength(seed.len()));
+        }
+
+        let (key, chain_code) = split(hmac_sha512(MASTER_KEY, seed));
+        let private_key = Scalar::from_be_bytes(key)
+            .and_then(|key| PrivateKey::new(key.value()))
+            .ok_or(Bip32Error::InvalidMasterKey)?;
+
+        Ok(Self {
+            network: Network::Mainnet,
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_number: ChildNumber::Normal(0),
+            chain_code,
+            private_key,
+        })
+    }
+
+    // The same key serialized for another network, which its children inherit
+    pub fn set_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self
+    }
+
+    /*
+        CKDpriv: I = HMAC-SHA512(chain code, data || index), where data is 0x00 and the secret for a hardened
+        child and the compressed public key otherwise. The child's key is the left half of I plus the parent's
+        secret and its chain code is the right half. When the left half is not below N or the sum is zero, which
+        happens with a chance below 2^-127, BIP32 moves on to the next index.
+     */
+    pub fn derive_child(&self, child: ChildNumber) -> Result<Self, Bip32Error> {
+        let depth = self.depth.checked_add(1).ok_or(Bip32Error::MaxDepth)?;
+        let mut index = child;
+
+        loop {
+            let mut hmac = HmacSha512::new(&self.chain_code);
+            match index.is_hardened() {
+                true => {
+                    hmac.update(&[0]);
+                    hmac.update(&self.private_key.secret().to_be_bytes());
+                }
+                false => hmac.update(&self.private_key.point().sec(true)),
+            }
+            hmac.update(&index.to_u32().to_be_bytes());
+            let (tweak, chain_code) = split(hmac.finalize());
+
+            let private_key = Scalar::from_be_bytes(tweak)
+                .and_then(|tweak| PrivateKey::new((tweak + self.private_key.secret()).value()));
+            match private_key {
+                Some(private_key) => {
+                    return Ok(Self {
+                        network: self.network,
+                        depth,
+                        parent_fingerprint: self.fingerprint(),
+                        child_number: index,
+                        chain_code,
+                        private_key,
+                    });
+                }
+                None => index = next_index(child, index)?,
+            }
+        }
+    }
+
+    // Derives each child of the path in turn from this key, so the master path gives the key back
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, Bip32Error> {
+        path.iter().try_fold(self.clone(), |key, child| key.derive_child(*child))
+    }
+
+    pub fn to_public(&self) -> ExtendedPublicKey {
+        ExtendedPublicKey {
+            network: self.network,
+            depth: self.depth,
+            parent_fingerprint: self.parent_fingerprint,
+            child_number: self.child_number,
+            chain_code: self.chain_code,
+            public_key: self.private_key.point(),
+        }
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    pub fn parent_fingerprint(&self) -> [u8; 4] {
+        self.parent_fingerprint
+    }
+
+    pub fn child_number(&self) -> ChildNumber {
+        self.child_number
+    }
+
+    pub fn chain_code(&self) -> [u8; 32] {
+        self.chain_code
+    }
+
+    pub fn private_key(&self) -> &PrivateKey {
+        &self.private_key
+    }
+
+    // The fingerprint this key's children record as their parent's
+    pub fn fingerprint(&self) -> [u8; 4] {
+        fingerprint(&self.private_key.point())
+    }
+}
+
+impl ExtendedPublicKey {
+    pub fn set_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self
+    }
+
+    /*
+        CKDpub: I = HMAC-SHA512(chain code, compressed public key || index), and the child's public key is the
+        parent's plus the left half of I times G. This gives the public key of the child CKDpriv gives, and like
+        it moves on to the next index for a left half not below N or a sum at infinity.
+     */
+    pub fn derive_child(&self, child: ChildNumber) -> Result<Self, Bip32Error> {
+        if child.is_hardened() {
+            return Err(Bip32Error::HardenedFromPublic(child));
+        }
+        let depth = self.depth.checked_add(1).ok_or(Bip32Error::MaxDepth)?;
+        let mut index = child;
+
+        loop {
+            let mut hmac = HmacSha512::new(&self.chain_code);
+            hmac.update(&self.public_key.sec(true));
+            hmac.update(&index.to_u32().to_be_bytes());
+            let (tweak, chain_code) = split(hmac.finalize());
+
+            let public_key = Scalar::from_be_bytes(tweak)
+                .and_then(|tweak| S256Point::generator().mul(&tweak.value()))
+                .and_then(|tweak_point| tweak_point.add(&self.public_key));
+            match public_key {
+                Some(public_key) => {
+                    return Ok(Self {
+                        network: self.network,
+                        depth,
+                        parent_fingerprint: self.fingerprint(),
+                        child_number: index,
+                        chain_code,
+                        public_key,
+                    });
+                }
+                None => index = next_index(child, index)?,
+            }
+        }
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    // Fails with HardenedFromPublic at the first hardened child of the path
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, Bip32Error> {
+        path.iter().try_fold(*self, |key, child| key.derive_child(*child))
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    pub fn parent_fingerprint(&self) -> [u8; 4] {
+        self.parent_fingerprint
+    }
+
+    pub fn child_number(&self) -> ChildNumber {
+        self.child_number
+    }
+
+    pub fn chain_code(&self) -> [u8; 32] {
+        self.chain_code
+    }
+
+    pub fn public_key(&self) -> S256Point {
+        self.public_key
+    }
+
+    pub fn fingerprint(&self) -> [u8; 4] {
+        fingerprint(&self.public_key)
+    }
+}
+
+// xprv or tprv followed by base58check of the 78 bytes, the key being 0x00 and the secret
+impl Display for ExtendedPrivateKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut key = [0; 33];
+        key[1..].copy_from_slice(&self.private_key.secret().to_be_bytes());
+        let fields = Fields {
+            version: self.network.xprv_version(),
+            depth: self.depth,
+            parent_fingerprint: self.parent_fingerprint,
+            child_number: self.child_number,
+            chain_code: self.chain_code,
+            key,
+        };
+
+        write!(f, "{}", fields.encode())
+    }
+}
+
+impl FromStr for ExtendedPrivateKey {
+    type Err = Bip32Error;
+
+    // Testnet, signet and regtest share version bytes, so a tprv comes back for Testnet
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = Fields::decode(s)?;
+        let network = [Network::Mainnet, Network::Testnet]
+            .into_iter()
+            .find(|network| network.xprv_version() == fields.version)
+            .ok_or(Bip32Error::UnknownVersion(fields.version))?;
+        if fields.key[0] != 0 {
+            return Err(Bip32Error::InvalidPrivateKeyPrefix(fields.key[0]));
+        }
+        let secret = fields.key[1..].try_into().expect("The secret is 32 bytes.");
+        let private_key = Scalar::from_be_bytes(secret)
+            .and_then(|secret| PrivateKey::new(secret.value()))
+            .ok_or(Bip32Error::InvalidPrivateKey)?;
+
+        Ok(Self {
+            network,
+            depth: fields.depth,
+            parent_fingerprint: fields.parent_fingerprint,
+            child_number: fields.child_number,
+            chain_code: fields.chain_code,
+            private_key,
+        })
+    }
+}
+
+// xpub or tpub followed by base58check of the 78 bytes, the key being the compressed SEC encoding
+impl Display for ExtendedPublicKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let fields = Fields {
+            version: self.network.xpub_version(),
+            depth: self.depth,
+            parent_fingerprint: self.parent_fingerprint,
+            child_number: self.child_number,
+            chain_code: self.chain_code,
+            key: self.public_key.sec(true).try_into().expect("Compressed SEC is 33 bytes."),
+        };
+
+        write!(f, "{}", fields.encode())
+    }
+}
+
+impl FromStr for ExtendedPublicKey {
+    type Err = Bip32Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = Fields::decode(s)?;
+        let network = [Network::Mainnet, Network::Testnet]
+            .into_iter()
+            .find(|network| network.xpub_version() == fields.version)
+            .ok_or(Bip32Error::UnknownVersion(fields.version))?;
+        let public_key = S256Point::parse_sec(&fields.key)?;
+
+        Ok(Self {
+            network,
+            depth: fields.depth,
+            parent_fingerprint: fields.parent_fingerprint,
+            child_number: fields.child_number,
+            chain_code: fields.chain_code,
+            public_key,
+        })
+    }
+}
+
+// The 78 serialized bytes, before the key in them is read as private or public
+struct Fields {
+    version: [u8; 4],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: ChildNumber,
+    chain_code: [u8; 32],
+    key: [u8; 33],
+}
+
+impl Fields {
+    fn encode(&self) -> String {
+        let mut payload = Vec::with_capacity(SERIALIZED_LENGTH - 1);
+        payload.extend_from_slice(&self.version[1..]);
+        payload.push(self.depth);
+        payload.extend_from_slice(&self.parent_fingerprint);
+        payload.extend_from_slice(&self.child_number.to_u32().to_be_bytes());
+        payload.extend_from_slice(&self.chain_code);
+        payload.extend_from_slice(&self.key);
+
+        // base58check's version byte is just the first of the four
+        base58check::encode(self.version[0], &payload)
+    }
+
+    fn decode(s: &str) -> Result<Self, Bip32Error> {
+        let (first, rest) = base58check::decode(s)?;
+        if rest.len() != SERIALIZED_LENGTH - 1 {
+            return Err(Bip32Error::InvalidLength(rest.len() + 1));
+        }
+        let bytes = [&[first][..], &rest].concat();
+
+        let fields = Self {
+            version: bytes[..4].try_into().expect("The version is 4 bytes."),
+            depth: bytes[4],
+            parent_fingerprint: bytes[5..9].try_into().expect("The fingerprint is 4 bytes."),
+            child_number: ChildNumber::from(u32::from_be_bytes(bytes[9..13].try_into().expect("4 bytes."))),
+            chain_code: bytes[13..45].try_into().expect("The chain code is 32 bytes."),
+            key: bytes[45..].try_into().expect("The key is 33 bytes."),
+        };
+        if fields.depth == 0 && (fields.parent_fingerprint != [0; 4] || fields.child_number.to_u32() != 0) {
+            return Err(Bip32Error::ZeroDepthNotMaster);
+        }
+
+        Ok(fields)
+    }
+}
+
+fn fingerprint(public_key: &S256Point) -> [u8; 4] {
+    hash160(&public_key.sec(true))[..4].try_into().expect("A hash160 is longer than 4 bytes.")
+}
+
+// The left and right 32 bytes of an HMAC-SHA512
+fn split(hmac: [u8; 64]) -> ([u8; 32], [u8; 32]) {
+    (hmac[..32].try_into().expect("Halves are 32 bytes."), hmac[32..].try_into().expect("Halves are 32 bytes."))
+}
+
+// The index after one that gave an invalid key, staying on the same side of the hardened boundary
+fn next_index(requested: ChildNumber, index: ChildNumber) -> Result<ChildNumber, Bip32Error> {
+    let next = index.index().checked_add(1).ok_or(Bip32Error::NoValidChild(requested))?;
+    let next = match index {
+        ChildNumber::Normal(_) => ChildNumber::normal(next),
+        ChildNumber::Hardened(_) => ChildNumber::hardened(next),
+    };
+
+    next.map_err(|_| Bip32Error::NoValidChild(requested))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use crate::bip32::derivation_path::{ChildNumber, DerivationPath};
+    use crate::bip32::extended_key::{Bip32Error, ExtendedPrivateKey, ExtendedPublicKey};
+    use crate::ecc::secp256k1::PointError;
+    use crate::encoding::base58::Base58Error;
+    use crate::encoding::hex;
+    use crate::network::Network;
+
+    // Each step of a chain: the child to derive and the xpub and xprv BIP32 gives for the result
+    type Chain<'a> = [(Option<ChildNumber>, &'a str, &'a str)];
+
+    // BIP32 test vector 1
+    const VECTOR_1_SEED: &str = "000102030405060708090a0b0c0d0e0f";
+    const VECTOR_1: [(Option<ChildNumber>, &str, &str); 6] = [
+        (
+            None,
+            "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8",
+            "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi",
+        ),
+        (
+            Some(ChildNumber::Hardened(0)),
+            "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw",
+            "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7",
+        ),
+        (
+            Some(ChildNumber::Normal(1)),
+            "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ",
+            "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs",
+        ),
+        (
+            Some(ChildNumber::Hardened(2)),
+            "xpub6D4BDPcP2GT577Vvch3R8wDkScZWzQzMMUm3PWbmWvVJrZwQY4VUNgqFJPMM3No2dFDFGTsxxpG5uJh7n7epu4trkrX7x7DogT5Uv6fcLW5",
+            "xprv9z4pot5VBttmtdRTWfWQmoH1taj2axGVzFqSb8C9xaxKymcFzXBDptWmT7FwuEzG3ryjH4ktypQSAewRiNMjANTtpgP4mLTj34bhnZX7UiM",
+        ),
+        (
+            Some(ChildNumber::Normal(2)),
+            "xpub6FHa3pjLCk84BayeJxFW2SP4XRrFd1JYnxeLeU8EqN3vDfZmbqBqaGJAyiLjTAwm6ZLRQUMv1ZACTj37sR62cfN7fe5JnJ7dh8zL4fiyLHV",
+            "xprvA2JDeKCSNNZky6uBCviVfJSKyQ1mDYahRjijr5idH2WwLsEd4Hsb2Tyh8RfQMuPh7f7RtyzTtdrbdqqsunu5Mm3wDvUAKRHSC34sJ7in334",
+        ),
+        (
+            Some(ChildNumber::Normal(1000000000)),
+            "xpub6H1LXWLaKsWFhvm6RVpEL9P4KfRZSW7abD2ttkWP3SSQvnyA8FSVqNTEcYFgJS2UaFcxupHiYkro49S8yGasTvXEYBVPamhGW6cFJodrTHy",
+            "xprvA41z7zogVVwxVSgdKUHDy1SKmdb533PjDz7J6N6mV6uS3ze1ai8FHa8kmHScGpWmj4WggLyQjgPie1rFSruoUihUZREPSL39UNdE3BBDu76",
+        ),
+    ];
+
+    // BIP32 test vector 2
+    const VECTOR_2_SEED: &str = "fffcf9f6f3f0edeae7e4e1dedbd8d5d2cfccc9c6c3c0bdbab7b4b1aeaba8a5a2\
+                                 9f9c999693908d8a8784817e7b7875726f6c696663605d5a5754514e4b484542";
+    const VECTOR_2: [(Option<ChildNumber>, &str, &str); 6] = [
+        (
+            None,
+            "xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB",
+            "xprv9s21ZrQH143K31xYSDQpPDxsXRTUcvj2iNHm5NUtrGiGG5e2DtALGdso3pGz6ssrdK4PFmM8NSpSBHNqPqm55Qn3LqFtT2emdEXVYsCzC2U",
+        ),
+        (
+            Some(ChildNumber::Normal(0)),
+            "xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH",
+            "xprv9vHkqa6EV4sPZHYqZznhT2NPtPCjKuDKGY38FBWLvgaDx45zo9WQRUT3dKYnjwih2yJD9mkrocEZXo1ex8G81dwSM1fwqWpWkeS3v86pgKt",
+        ),
+        (
+            Some(ChildNumber::Hardened(2147483647)),
+            "xpub6ASAVgeehLbnwdqV6UKMHVzgqAG8Gr6riv3Fxxpj8ksbH9ebxaEyBLZ85ySDhKiLDBrQSARLq1uNRts8RuJiHjaDMBU4Zn9h8LZNnBC5y4a",
+            "xprv9wSp6B7kry3Vj9m1zSnLvN3xH8RdsPP1Mh7fAaR7aRLcQMKTR2vidYEeEg2mUCTAwCd6vnxVrcjfy2kRgVsFawNzmjuHc2YmYRmagcEPdU9",
+        ),
+        (
+            Some(ChildNumber::Normal(1)),
+            "xpub6DF8uhdarytz3FWdA8TvFSvvAh8dP3283MY7p2V4SeE2wyWmG5mg5EwVvmdMVCQcoNJxGoWaU9DCWh89LojfZ537wTfunKau47EL2dhHKon",
+            "xprv9zFnWC6h2cLgpmSA46vutJzBcfJ8yaJGg8cX1e5StJh45BBciYTRXSd25UEPVuesF9yog62tGAQtHjXajPPdbRCHuWS6T8XA2ECKADdw4Ef",
+        ),
+        (
+            Some(ChildNumber::Hardened(2147483646)),
+            "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL",
+            "xprvA1RpRA33e1JQ7ifknakTFpgNXPmW2YvmhqLQYMmrj4xJXXWYpDPS3xz7iAxn8L39njGVyuoseXzU6rcxFLJ8HFsTjSyQbLYnMpCqE2VbFWc",
+        ),
+        (
+            Some(ChildNumber::Normal(2)),
+            "xpub6FnCn6nSzZAw5Tw7cgR9bi15UV96gLZhjDstkXXxvCLsUXBGXPdSnLFbdpq8p9HmGsApME5hQTZ3emM2rnY5agb9rXpVGyy3bdW6EEgAtqt",
+            "xprvA2nrNbFZABcdryreWet9Ea4LvTJcGsqrMzxHx98MMrotbir7yrKCEXw7nadnHM8Dq38EGfSh6dqA9QWTyefMLEcBYJUuekgW4BYPJcr9E7j",
+        ),
+    ];
+
+    // BIP32 test vector 3, whose master secret starts with a zero byte
+    const VECTOR_3_SEED: &str = "4b381541583be4423346c643850da4b320e46a87ae3d2a4e6da11eba819cd4ac\
+                                 ba45d239319ac14f863b8d5ab5a0d0c64d2e8a1e7d1457df2e5a3c51c73235be";
+    const VECTOR_3: [(Option<ChildNumber>, &str, &str); 2] = [
+        (
+            None,
+            "xpub661MyMwAqRbcEZVB4dScxMAdx6d4nFc9nvyvH3v4gJL378CSRZiYmhRoP7mBy6gSPSCYk6SzXPTf3ND1cZAceL7SfJ1Z3GC8vBgp2epUt13",
+            "xprv9s21ZrQH143K25QhxbucbDDuQ4naNntJRi4KUfWT7xo4EKsHt2QJDu7KXp1A3u7Bi1j8ph3EGsZ9Xvz9dGuVrtHHs7pXeTzjuxBrCmmhgC6",
+        ),
+        (
+            Some(ChildNumber::Hardened(0)),
+            "xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y",
+            "xprv9uPDJpEQgRQfDcW7BkF7eTya6RPxXeJCqCJGHuCJ4GiRVLzkTXBAJMu2qaMWPrS7AANYqdq6vcBcBUdJCVVFceUvJFjaPdGZ2y9WACViL4L",
+        ),
+    ];
+
+    // BIP32 test vector 4, whose hardened child secret starts with a zero byte
+    const VECTOR_4_SEED: &str = "3ddd5602285899a946114506157c7997e5444528f3003f6134712147db19b678";
+    const VECTOR_4: [(Option<ChildNumber>, &str, &str); 3] = [
+        (
+            None,
+            "xpub661MyMwAqRbcGczjuMoRm6dXaLDEhW1u34gKenbeYqAix21mdUKJyuyu5F1rzYGVxyL6tmgBUAEPrEz92mBXjByMRiJdba9wpnN37RLLAXa",
+            "xprv9s21ZrQH143K48vGoLGRPxgo2JNkJ3J3fqkirQC2zVdk5Dgd5w14S7fRDyHH4dWNHUgkvsvNDCkvAwcSHNAQwhwgNMgZhLtQC63zxwhQmRv",
+        ),
+        (
+            Some(ChildNumber::Hardened(0)),
+            "xpub69AUMk3qDBi3uW1sXgjCmVjJ2G6WQoYSnNHyzkmdCHEhSZ4tBok37xfFEqHd2AddP56Tqp4o56AePAgCjYdvpW2PU2jbUPFKsav5ut6Ch1m",
+            "xprv9vB7xEWwNp9kh1wQRfCCQMnZUEG21LpbR9NPCNN1dwhiZkjjeGRnaALmPXCX7SgjFTiCTT6bXes17boXtjq3xLpcDjzEuGLQBM5ohqkao9G",
+        ),
+        (
+            Some(ChildNumber::Hardened(1)),
+            "xpub6BJA1jSqiukeaesWfxe6sNK9CCGaujFFSJLomWHprUL9DePQ4JDkM5d88n49sMGJxrhpjazuXYWdMf17C9T5XnxkopaeS7jGk1GyyVziaMt",
+            "xprv9xJocDuwtYCMNAo3Zw76WENQeAS6WGXQ55RCy7tDJ8oALr4FWkuVoHJeHVAcAqiZLE7Je3vZJHxspZdFHfnBEjHqU5hG1Jaj32dVoS6XLT1",
+        ),
+    ];
+
+    // Derives down the chain privately, and publicly from the parent's xpub wherever the child is normal
+    fn check_chain(seed: &str, chain: &Chain) {
+        let mut key = ExtendedPrivateKey::from_seed(&hex::decode(seed).unwrap()).unwrap();
+
+        for (child, xpub, xprv) in chain {
+            if let Some(child) = child {
+                let parent = key.to_public();
+                key = key.derive_child(*child).unwrap();
+                if !child.is_hardened() {
+                    assert_eq!(parent.derive_child(*child), Ok(key.to_public()), "{}", xpub);
+                }
+            }
+
+            assert_eq!(&key.to_string(), xprv);
+            assert_eq!(&key.to_public().to_string(), xpub);
+            assert_eq!(ExtendedPrivateKey::from_str(xprv).as_ref(), Ok(&key));
+            assert_eq!(ExtendedPublicKey::from_str(xpub), Ok(key.to_public()));
+        }
+    }
+
+    #[test]
+    fn bip32_test_vector_1() {
+        check_chain(VECTOR_1_SEED, &VECTOR_1);
+    }
+
+    #[test]
+    fn bip32_test_vector_2() {
+        check_chain(VECTOR_2_SEED, &VECTOR_2);
+    }
+
+    #[test]
+    fn bip32_test_vector_3() {
+        check_chain(VECTOR_3_SEED, &VECTOR_3);
+    }
+
+    #[test]
+    fn bip32_test_vector_4() {
+        check_chain(VECTOR_4_SEED, &VECTOR_4);
+    }
+
+    #[test]
+    fn paths_derive_the_same_keys_as_each_step() {
+        let master = ExtendedPrivateKey::from_seed(&hex::decode(VECTOR_1_SEED).unwrap()).unwrap();
+
+        let key = master.derive_path(&DerivationPath::from_str("m/0h/1/2h/2/1000000000").unwrap()).unwrap();
+        let public = master.derive_path(&DerivationPath::from_str("m/0h/1/2h").unwrap()).unwrap().to_public();
+
+        assert_eq!(master.derive_path(&DerivationPath::master()), Ok(master.clone()));
+        assert_eq!(key.to_string(), VECTOR_1[5].2);
+        assert_eq!(public.derive_path(&DerivationPath::from_str("m/2/1000000000").unwrap()), Ok(key.to_public()));
+    }
+
+    #[test]
+    fn bip84_first_receive_key() {
+        // The BIP39 seed of "abandon abandon ... about" with no passphrase, which BIP84's test vectors start from
+        let seed = hex::decode(
+            "5eb00bbddcf069084889a8ab9155568165f5c453ccb85e70811aaed6f6da5fc1\
+             9a5ac40b389cd370d086206dec8aa6c43daea6690f20ad3d8d48b2d2ce9e38e4",
+        )
+        .unwrap();
+        let master = ExtendedPrivateKey::from_seed(&seed).unwrap();
+
+        let key = master.derive_path(&DerivationPath::from_str("m/84'/0'/0'/0/0").unwrap()).unwrap();
+
+        let point = key.private_key().point();
+        assert_eq!(point.to_hex(true), "0330d54fd0dd420a6e5f8d3624f5f3482cae350f79d5f0753bf5beef9c2d91af3c");
+        assert_eq!(point.p2wpkh_address(Network::Mainnet), "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu");
+    }
+
+    #[test]
+    fn hardened_paths_cannot_be_derived_from_a_public_key() {
+        let master = ExtendedPrivateKey::from_seed(&hex::decode(VECTOR_1_SEED).unwrap()).unwrap().to_public();
+
+        let result = master.derive_path(&DerivationPath::from_str("m/0/1h/2").unwrap());
+
+        assert_eq!(result, Err(Bip32Error::HardenedFromPublic(ChildNumber::Hardened(1))));
+    }
+
+    #[test]
+    fn hardened_children_cannot_be_derived_from_a_public_key() {
+        let master = ExtendedPrivateKey::from_seed(&hex::decode(VECTOR_1_SEED).unwrap()).unwrap().to_public();
+
+        assert_eq!(
+            master.derive_child(ChildNumber::Hardened(0)),
+            Err(Bip32Error::HardenedFromPublic(ChildNumber::Hardened(0)))
+        );
+    }
+
+    #[test]
+    fn seeds_must_be_128_to_512_bits() {
+        assert_eq!(ExtendedPrivateKey::from_seed(&[0; 15]), Err(Bip32Error::InvalidSeedLength(15)));
+        assert_eq!(ExtendedPrivateKey::from_seed(&[0; 65]), Err(Bip32Error::InvalidSeedLength(65)));
+        assert!(ExtendedPrivateKey::from_seed(&[0; 16]).is_ok());
+        assert!(ExtendedPrivateKey::from_seed(&[0; 64]).is_ok());
+    }
+
+    #[test]
+    fn testnet_keys_use_tprv_and_tpub() {
+        let master = ExtendedPrivateKey::from_seed(&hex::decode(VECTOR_1_SEED).unwrap()).unwrap();
+        let tprv = "tprv8ZgxMBicQKsPeDgjzdC36fs6bMjGApWDNLR9erAXMs5skhMv36j9MV5ecvfavji5khqjWaWSFhN3YcCUUdiKH6isR4Pwy3U5y5egddBr16m";
+
+        let testnet = master.clone().set_network(Network::Testnet);
+        let child = testnet.derive_child(ChildNumber::Hardened(0)).unwrap();
+
+        assert_eq!(testnet.to_string(), tprv);
+        assert_eq!(ExtendedPrivateKey::from_str(tprv), Ok(testnet.clone()));
+        assert!(testnet.to_public().to_string().starts_with("tpub"));
+        assert_eq!(ExtendedPublicKey::from_str(&testnet.to_public().to_string()), Ok(testnet.to_public()));
+        assert_eq!(child.network(), Network::Testnet);
+        assert_eq!(child.set_network(Network::Mainnet).to_string(), VECTOR_1[1].2);
+        // Signet and regtest share testnet's version bytes
+        assert_eq!(master.set_network(Network::Regtest).to_string(), tprv);
+    }
+
+    #[test]
+    fn bip32_test_vector_5_is_rejected() {
+        let vectors = [
+            // An xpub version around a private key, and an xprv version around a public key
+            (
+                "xpub661MyMwAqRbcEYS8w7XLSVeEsBXy79zSzH1J8vCdxAZningWLdN3zgtU6LBpB85b3D2yc8sfvZU521AAwdZafEz7mnzBBsz4wKY5fTtTQBm",
+                Bip32Error::InvalidPublicKey(PointError::InvalidSecPrefix(0x00)),
+            ),
+            (
+                "xprv9s21ZrQH143K24Mfq5zL5MhWK9hUhhGbd45hLXo2Pq2oqzMMo63oStZzFGTQQD3dC4H2D5GBj7vWvSQaaBv5cxi9gafk7NF3pnBju6dwKvH",
+                Bip32Error::InvalidPrivateKeyPrefix(0x03),
+            ),
+            // Invalid key prefixes
+            (
+                "xpub661MyMwAqRbcEYS8w7XLSVeEsBXy79zSzH1J8vCdxAZningWLdN3zgtU6Txnt3siSujt9RCVYsx4qHZGc62TG4McvMGcAUjeuwZdduYEvFn",
+                Bip32Error::InvalidPublicKey(PointError::InvalidSecPrefix(0x04)),
+            ),
+            (
+                "xprv9s21ZrQH143K24Mfq5zL5MhWK9hUhhGbd45hLXo2Pq2oqzMMo63oStZzFGpWnsj83BHtEy5Zt8CcDr1UiRXuWCmTQLxEK9vbz5gPstX92JQ",
+                Bip32Error::InvalidPrivateKeyPrefix(0x04),
+            ),
+            (
+                "xpub661MyMwAqRbcEYS8w7XLSVeEsBXy79zSzH1J8vCdxAZningWLdN3zgtU6N8ZMMXctdiCjxTNq964yKkwrkBJJwpzZS4HS2fxvyYUA4q2Xe4",
+                Bip32Error::InvalidPublicKey(PointError::InvalidSecPrefix(0x01)),
+            ),
+            (
+                "xprv9s21ZrQH143K24Mfq5zL5MhWK9hUhhGbd45hLXo2Pq2oqzMMo63oStZzFAzHGBP2UuGCqWLTAPLcMtD9y5gkZ6Eq3Rjuahrv17fEQ3Qen6J",
+                Bip32Error::InvalidPrivateKeyPrefix(0x01),
+            ),
+            // Depth 0 with a parent fingerprint, then with a child number
+            (
+                "xprv9s2SPatNQ9Vc6GTbVMFPFo7jsaZySyzk7L8n2uqKXJen3KUmvQNTuLh3fhZMBoG3G4ZW1N2kZuHEPY53qmbZzCHshoQnNf4GvELZfqTUrcv",
+                Bip32Error::ZeroDepthNotMaster,
+            ),
+            (
+                "xpub661no6RGEX3uJkY4bNnPcw4URcQTrSibUZ4NqJEw5eBkv7ovTwgiT91XX27VbEXGENhYRCf7hyEbWrR3FewATdCEebj6znwMfQkhRYHRLpJ",
+                Bip32Error::ZeroDepthNotMaster,
+            ),
+            (
+                "xprv9s21ZrQH4r4TsiLvyLXqM9P7k1K3EYhA1kkD6xuquB5i39AU8KF42acDyL3qsDbU9NmZn6MsGSUYZEsuoePmjzsB3eFKSUEh3Gu1N3cqVUN",
+                Bip32Error::ZeroDepthNotMaster,
+            ),
+            (
+                "xpub661MyMwAuDcm6CRQ5N4qiHKrJ39Xe1R1NyfouMKTTWcguwVcfrZJaNvhpebzGerh7gucBvzEQWRugZDuDXjNDRmXzSZe4c7mnTK97pTvGS8",
+                Bip32Error::ZeroDepthNotMaster,
+            ),
+            // Private keys of 0 and N, and a public key off the curve
+            (
+                "xprv9s21ZrQH143K24Mfq5zL5MhWK9hUhhGbd45hLXo2Pq2oqzMMo63oStZzF93Y5wvzdUayhgkkFoicQZcP3y52uPPxFnfoLZB21Teqt1VvEHx",
+                Bip32Error::InvalidPrivateKey,
+            ),
+            (
+                "xprv9s21ZrQH143K24Mfq5zL5MhWK9hUhhGbd45hLXo2Pq2oqzMMo63oStZzFAzHGBP2UuGCqWLTAPLcMtD5SDKr24z3aiUvKr9bJpdrcLg1y3G",
+                Bip32Error::InvalidPrivateKey,
+            ),
+            (
+                "xpub661MyMwAqRbcEYS8w7XLSVeEsBXy79zSzH1J8vCdxAZningWLdN3zgtU6Q5JXayek4PRsn35jii4veMimro1xefsM58PgBMrvdYre8QyULY",
+                Bip32Error::InvalidPublicKey(PointError::NotOnCurve),
+            ),
+            (
+                "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHL",
+                Bip32Error::Base58(Base58Error::InvalidChecksum),
+            ),
+        ];
+
+        for (serialized, expected) in vectors {
+            match serialized.starts_with("xprv") {
+                true => assert_eq!(ExtendedPrivateKey::from_str(serialized), Err(expected), "{}", serialized),
+                false => assert_eq!(ExtendedPublicKey::from_str(serialized), Err(expected), "{}", serialized),
+            }
+        }
+
+        // Unknown version bytes, which neither kind of key takes
+        for serialized in [
+            "DMwo58pR1QLEFihHiXPVykYB6fJmsTeHvyTp7hRThAtCX8CvYzgPcn8XnmdfHGMQzT7ayAmfo4z3gY5KfbrZWZ6St24UVf2Qgo6oujFktLHdHY4",
+            "DMwo58pR1QLEFihHiXPVykYB6fJmsTeHvyTp7hRThAtCX8CvYzgPcn8XnmdfHPmHJiEDXkTiJTVV9rHEBUem2mwVbbNfvT2MTcAqj3nesx8uBf9",
+        ] {
+            assert_eq!(ExtendedPrivateKey::from_str(serialized), Err(Bip32Error::UnknownVersion([1; 4])));
+            assert_eq!(ExtendedPublicKey::from_str(serialized), Err(Bip32Error::UnknownVersion([1; 4])));
+        }
+    }
+
+    #[test]
+    fn xprvs_and_xpubs_are_not_read_as_each_other() {
+        let (_, xpub, xprv) = VECTOR_1[0];
+
+        assert_eq!(ExtendedPublicKey::from_str(xprv), Err(Bip32Error::UnknownVersion([0x04, 0x88, 0xad, 0xe4])));
+        assert_eq!(ExtendedPrivateKey::from_str(xpub), Err(Bip32Error::UnknownVersion([0x04, 0x88, 0xb2, 0x1e])));
+    }
+}
@@ -0,0 +1,2 @@
+pub mod derivation_path;
+pub mod extended_key;
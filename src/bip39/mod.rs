@@ -0,0 +1,2 @@
+pub mod mnemonic;
+pub mod wordlist;
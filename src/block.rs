@@ -0,0 +1,330 @@
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+use crate::encoding::encodable::{Decodable, Encodable, VarInt};
+use crate::encoding::parse_error::ParseError;
+use crate::encoding::varint::read_varint;
+use crate::hashing::hash256::{Hash256, Hash256Engine};
+use crate::merkle::merkle_proof::merkle_root;
+use crate::tx::transaction::Tx;
+
+// The start of a BIP141 witness commitment output: OP_RETURN, a 36-byte push and the commitment header
+const WITNESS_COMMITMENT_PREFIX: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockError {
+    NoTransactions,
+    FirstNotCoinbase,
+    // A transaction carries witness data while the coinbase commits to none
+    UnexpectedWitness(usize),
+    // The coinbase witness must be a single 32-byte witness reserved value
+    InvalidWitnessReservedValue,
+    WitnessCommitmentMismatch { committed: Hash256, computed: Hash256 },
+}
+
+impl Display for BlockError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockError::NoTransactions => write!(f, "Block has no transactions."),
+            BlockError::FirstNotCoinbase => write!(f, "The first transaction of the block is not a coinbase."),
+            BlockError::UnexpectedWitness(index) => {
+                write!(f, "Transaction {} has witness data but the block has no witness commitment.", index)
+            }
+            BlockError::InvalidWitnessReservedValue => {
+                write!(f, "Coinbase witness is not a single 32-byte witness reserved value.")
+            }
+            BlockError::WitnessCommitmentMismatch { committed, computed } => {
+                write!(f, "Coinbase commits to witnesses {} but the block's hash to {}.", committed, computed)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BlockError {}
+
+/*
+    Wire format:
+
+    version      4 bytes little-endian
+    prev_block   32 bytes, in wire order
+    merkle_root  32 bytes, in wire order
+    timestamp    4 bytes little-endian
+    bits         4 bytes little-endian, the compact proof of work target
+    nonce        4 bytes little-endian
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockHeader {
+    pub version: u32,
+    pub prev_block: Hash256,
+    pub merkle_root: Hash256,
+    pub timestamp: u32,
+    pub bits: u32,
+    pub nonce: u32,
+}
+
+impl BlockHeader {
+    pub fn hash(&self) -> Hash256 {
+        Hash256::hash(&self.serialize_to_vec())
+    }
+}
+
+impl Decodable for BlockHeader {
+    fn parse(reader: &mut impl Read) -> Result<Self, ParseError> {
+        let parse_fields = |reader: &mut _| -> Result<Self, ParseError> {
+            Ok(Self {
+                version: u32::parse(reader)?,
+                prev_block: Hash256::parse(reader)?,
+                merkle_root: Hash256::parse(reader)?,
+                timestamp: u32::parse(reader)?,
+                bits: u32::parse(reader)?,
+                nonce: u32::parse(reader)?,
+            })
+        };
+
+        parse_fields(reader).map_err(|error| error.in_type("BlockHeader"))
+    }
+}
+
+impl Encodable for BlockHeader {
+    fn serialize(&self, writer: &mut impl Write) -> Result<(), ParseError> {
+        self.version.serialize(writer)?;
+        self.prev_block.serialize(writer)?;
+        self.merkle_root.serialize(writer)?;
+        self.timestamp.serialize(writer)?;
+        self.bits.serialize(writer)?;
+        self.nonce.serialize(writer)
+    }
+}
+
+/*
+    Wire format:
+
+    header        80 bytes
+    transactions  varint count followed by each Tx
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub header: BlockHeader,
+    pub transactions: Vec<Tx>,
+}
+
+impl Block {
+    // The count is untrusted, so the list grows as transactions parse rather than being preallocated
+    pub fn parse(reader: &mut impl Read, testnet: bool) -> Result<Self, ParseError> {
+        let parse_fields = |reader: &mut _| -> Result<Self, ParseError> {
+            let header = BlockHeader::parse(reader)?;
+            let count = read_varint(reader)?;
+            let transactions = (0..count).map(|_| Tx::parse(reader, testnet)).collect::<Result<_, _>>()?;
+
+            Ok(Self { header, transactions })
+        };
+
+        parse_fields(reader).map_err(|error| error.in_type("Block"))
+    }
+
+    // Parses a complete block, rejecting any bytes left over
+    pub fn parse_exact(bytes: &[u8], testnet: bool) -> Result<Self, ParseError> {
+        let mut reader = bytes;
+        let block = Self::parse(&mut reader, testnet)?;
+
+        match reader.len() {
+            0 => Ok(block),
+            leftover => Err(ParseError::TrailingBytes(leftover)),
+        }
+    }
+
+    // The root of the txid tree, which the header commits to
+    pub fn merkle_root(&self) -> Result<Hash256, BlockError> {
+        let txids: Vec<Hash256> = self.transactions.iter().map(Tx::id).collect();
+
+        merkle_root(&txids).map_err(|_| BlockError::NoTransactions)
+    }
+
+    // The root of the BIP141 wtxid tree, in which the coinbase counts as all zeros
+    pub fn witness_merkle_root(&self) -> Result<Hash256, BlockError> {
+        let wtxids: Vec<Hash256> = self.transactions.iter().map(Tx::wtxid).collect();
+
+        merkle_root(&wtxids).map_err(|_| BlockError::NoTransactions)
+    }
+
+    // The commitment in the last coinbase output that carries one, the one BIP141 says counts
+    pub fn witness_commitment(&self) -> Option<Hash256> {
+        let coinbase = self.transactions.first()?;
+
+        coinbase.outputs.iter().rev().find_map(|output| {
+            let script_pubkey = &output.script_pubkey;
+            match script_pubkey.len() >= 38 && script_pubkey.starts_with(&WITNESS_COMMITMENT_PREFIX) {
+                true => Some(Hash256::from_wire_bytes(script_pubkey[6..38].try_into().unwrap())),
+                false => None,
+            }
+        })
+    }
+
+    /*
+        BIP141: with a commitment, the coinbase witness must be the 32-byte witness reserved value and the
+        commitment must be hash256(witness root || reserved value). Without one, no transaction may carry
+        witness data, since nothing would stop it being changed.
+     */
+    pub fn validate_witness_commitment(&self) -> Result<(), BlockError> {
+        let coinbase = self.transactions.first().ok_or(BlockError::NoTransactions)?;
+        if !coinbase.is_coinbase() {
+            return Err(BlockError::FirstNotCoinbase);
+        }
+
+        let Some(committed) = self.witness_commitment() else {
+            return match self.transactions.iter().position(Tx::has_witness) {
+                Some(index) => Err(BlockError::UnexpectedWitness(index)),
+                None => Ok(()),
+            };
+        };
+
+        let reserved_value = match coinbase.inputs[0].witness.as_slice() {
+            [value] if value.len() == 32 => value,
+            _ => return Err(BlockError::InvalidWitnessReservedValue),
+        };
+        let mut engine = Hash256Engine::new();
+        engine.update(&self.witness_merkle_root()?.to_wire_bytes());
+        engine.update(reserved_value);
+        let computed = Hash256::from_wire_bytes(engine.finalize());
+
+        match computed == committed {
+            true => Ok(()),
+            false => Err(BlockError::WitnessCommitmentMismatch { committed, computed }),
+        }
+    }
+}
+
+impl Encodable for Block {
+    fn serialize(&self, writer: &mut impl Write) -> Result<(), ParseError> {
+        self.header.serialize(writer)?;
+        VarInt(self.transactions.len() as u64).serialize(writer)?;
+        for tx in &self.transactions {
+            tx.serialize(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use crate::block::{Block, BlockError};
+    use crate::encoding::encodable::Encodable;
+    use crate::encoding::hex;
+    use crate::hashing::hash256::Hash256;
+
+    /*
+        Not a block from any chain: a synthetic block around the two BIP143 example transactions, with a segwit
+        coinbase committing to their witnesses, then the native P2WPKH and the P2SH-P2WPKH spends, under a
+        header with regtest's minimum difficulty. The merkle root, witness root and commitment were recomputed
+        outside this crate from the raw bytes.
+     */
+    const SEGWIT_BLOCK_HEX: &str = "00000020801b81629334be8e7af5ebfb9df09c18e1f833b5f0efcb000000000000000000089316457d8570fc7e\
+                                    e7b85c17bd3f19bdd69f20ec2ed31a68808652b1b6101e91329e59ffff7f200000000003010000000001010000\
+                                    000000000000000000000000000000000000000000000000000000000000ffffffff0403204e07ffffffff0208\
+                                    90814a0000000016001411111111111111111111111111111111111111110000000000000000266a24aa21a9ed\
+                                    d5232abf74199897d4d7e6b39c255c816a0f698986bf7df236a56ed4a21a756d01200000000000000000000000\
+                                    0000000000000000000000000000000000000000000000000001000000000102fff7f7881a8099afa6940d42d1\
+                                    e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9\
+                                    d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecba\
+                                    b4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100\
+                                    000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac909351\
+                                    0d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7\
+                                    d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f5\
+                                    1928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07\
+                                    aeee63571100000001000000000101db6b1b20aa0fd7b23880be2ecbd4a98130974cf4748fb66092ac4d3ceb1a\
+                                    5477010000001716001479091972186c449eb1ded22b78e40d009bdf0089feffffff02b8b4eb0b000000001976\
+                                    a914a457b684d7f0d539a46a45bbc043f35b59d0d96388ac0008af2f000000001976a914fd270b1ee6abcaea97\
+                                    fea7ad0402e8bd8ad6d77c88ac02473044022047ac8e878352d3ebbde1c94ce3a10d057c24175747116f8288e5\
+                                    d794d12d482f0220217f36a485cae903c713331d877c1f64677e3622ad4010726870540656fe9dcb012103ad1d\
+                                    8e89212f0b92c74d23bb710c00662ad1470198ac48c43f7d6f93a2a2687392040000";
+
+    const WITNESS_ROOT: &str = "89e2a26c8b6e186d05c2c5c9133c5b9880497dcb90f2dd4e39154f4d0a20bfb4";
+    const MERKLE_ROOT: &str = "1e10b6b1528680681ad32eec209fd6bd193fbd175cb8e77efc70857d45169308";
+
+    fn segwit_block() -> Block {
+        Block::parse_exact(&hex::decode(SEGWIT_BLOCK_HEX).unwrap(), false).unwrap()
+    }
+
+    #[test]
+    fn segwit_block_round_trips() {
+        let bytes = hex::decode(SEGWIT_BLOCK_HEX).unwrap();
+
+        let subject = Block::parse_exact(&bytes, false).unwrap();
+
+        assert_eq!(subject.transactions.len(), 3);
+        assert!(subject.transactions[0].is_coinbase());
+        assert_eq!(subject.serialize_to_vec(), bytes);
+    }
+
+    #[test]
+    fn roots_match_the_header_and_the_commitment() {
+        let subject = segwit_block();
+
+        assert_eq!(subject.merkle_root(), Ok(subject.header.merkle_root));
+        assert_eq!(subject.header.merkle_root, Hash256::from_str(MERKLE_ROOT).unwrap());
+        assert_eq!(subject.witness_merkle_root(), Ok(Hash256::from_str(WITNESS_ROOT).unwrap()));
+        assert_eq!(subject.validate_witness_commitment(), Ok(()));
+    }
+
+    #[test]
+    fn mutated_witness_breaks_the_commitment() {
+        let mut subject = segwit_block();
+        let committed = subject.witness_commitment().unwrap();
+
+        subject.transactions[1].inputs[1].witness[0][10] ^= 0x01;
+
+        // The txids, and so the header's merkle root, do not cover witnesses
+        assert_eq!(subject.merkle_root(), Ok(subject.header.merkle_root));
+        let Err(BlockError::WitnessCommitmentMismatch { committed: found, computed }) =
+            subject.validate_witness_commitment()
+        else {
+            panic!("the commitment still validates");
+        };
+        assert_eq!(found, committed);
+        assert_ne!(computed, committed);
+    }
+
+    #[test]
+    fn witness_reserved_value_must_be_one_32_byte_item() {
+        let mut changed_value = segwit_block();
+        changed_value.transactions[0].inputs[0].witness[0][0] = 0x01;
+        let mut two_items = segwit_block();
+        two_items.transactions[0].inputs[0].witness.push(vec![]);
+        let mut short_value = segwit_block();
+        short_value.transactions[0].inputs[0].witness[0].pop();
+
+        assert!(matches!(
+            changed_value.validate_witness_commitment(),
+            Err(BlockError::WitnessCommitmentMismatch { .. })
+        ));
+        assert_eq!(two_items.validate_witness_commitment(), Err(BlockError::InvalidWitnessReservedValue));
+        assert_eq!(short_value.validate_witness_commitment(), Err(BlockError::InvalidWitnessReservedValue));
+    }
+
+    #[test]
+    fn witnesses_need_a_commitment() {
+        let mut subject = segwit_block();
+        subject.transactions[0].outputs.pop();
+        subject.transactions[0].inputs[0].witness.clear();
+
+        assert_eq!(subject.witness_commitment(), None);
+        assert_eq!(subject.validate_witness_commitment(), Err(BlockError::UnexpectedWitness(1)));
+
+        for tx in &mut subject.transactions {
+            for input in &mut tx.inputs {
+                input.witness.clear();
+            }
+        }
+        assert_eq!(subject.validate_witness_commitment(), Ok(()));
+    }
+
+    #[test]
+    fn the_first_transaction_must_be_the_coinbase() {
+        let mut subject = segwit_block();
+        subject.transactions.swap(0, 1);
+
+        assert_eq!(subject.validate_witness_commitment(), Err(BlockError::FirstNotCoinbase));
+        assert_eq!(Block { transactions: vec![], ..subject }.witness_merkle_root(), Err(BlockError::NoTransactions));
+    }
+}
@@ -0,0 +1,174 @@
+use std::hint::black_box;
+
+/*
+    Compares two byte strings without returning early on the first difference, so the time
+    taken does not reveal how much of a checksum or MAC an attacker guessed correctly.
+
+    Lengths are treated as public: a mismatch is folded into the result, but the content
+    loop still runs over every byte of `a`.
+ */
+pub fn ct_eq_bytes(a: &[u8], b: &[u8]) -> bool {
+    let mut difference = (a.len() ^ b.len()) as u64;
+
+    for (i, &byte) in a.iter().enumerate() {
+        let other = match b.get(i) {
+            Some(&other) => other,
+            None => !byte,
+        };
+        difference |= black_box((byte ^ other) as u64);
+    }
+
+    black_box(difference) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constant_time::ct_eq_bytes;
+    use crate::ecc::private_key::PrivateKey;
+    use crate::ecc::scalar::Scalar;
+    use crate::ecc::secp256k1::{S256Point, MULTIPLICATIONS, N};
+    use crate::ecc::u256::U256;
+    use crate::hashing::sha256::sha256;
+
+    // Scalars of Hamming weight 1, 255 and in between
+    fn extreme_scalars() -> [U256; 5] {
+        [
+            U256::ONE,
+            U256::from_limbs([0, 0, 0, 1 << 63]),
+            U256::from_limbs([u64::MAX, u64::MAX, u64::MAX, u64::MAX >> 1]),
+            N.value().wrapping_sub(&U256::ONE),
+            U256::from_u64(0xdeadbeef),
+        ]
+    }
+
+    // The field multiplications the closure does, counted on this thread
+    fn multiplications<T>(work: impl FnOnce() -> T) -> (T, usize) {
+        MULTIPLICATIONS.with(|count| count.set(0));
+        let result = work();
+
+        (result, MULTIPLICATIONS.with(|count| count.get()))
+    }
+
+    fn all_equal(counts: &[usize]) -> bool {
+        counts.iter().all(|count| *count == counts[0])
+    }
+
+    #[test]
+    fn equal_inputs_compare_equal() {
+        assert!(ct_eq_bytes(b"", b""));
+        assert!(ct_eq_bytes(&[0x12, 0x34, 0x56, 0x78], &[0x12, 0x34, 0x56, 0x78]));
+    }
+
+    #[test]
+    fn single_bit_differences_are_detected() {
+        let a = [0u8; 32];
+
+        for bit in 0..256 {
+            let mut b = a;
+            b[bit / 8] ^= 1 << (bit % 8);
+
+            assert!(!ct_eq_bytes(&a, &b), "bit {}", bit);
+        }
+    }
+
+    #[test]
+    fn different_lengths_never_compare_equal() {
+        assert!(!ct_eq_bytes(&[1, 2, 3], &[1, 2, 3, 4]));
+        assert!(!ct_eq_bytes(&[1, 2, 3, 4], &[1, 2, 3]));
+        assert!(!ct_eq_bytes(&[], &[0]));
+        assert!(!ct_eq_bytes(&[0], &[]));
+    }
+
+    #[test]
+    fn checksum_and_mac_paths_use_constant_time_comparison() {
+        // Guards against a later refactor quietly going back to == on secret-dependent bytes
+        let audited_sources = [
+            ("base58check.rs", include_str!("encoding/base58check.rs"), "fn decode"),
+            ("hmac.rs", include_str!("hashing/hmac.rs"), "fn verify"),
+        ];
+
+        for (file, source, function) in audited_sources {
+            let code = source.split("#[cfg(test)]").next().unwrap();
+            let start = code.find(function).unwrap_or_else(|| panic!("{} has no {}", file, function));
+
+            assert!(code[start..].contains("ct_eq_bytes("), "{} {} does not call ct_eq_bytes", file, function);
+        }
+    }
+
+    #[test]
+    fn ladder_matches_double_and_add_with_the_same_work_for_any_scalar() {
+        let generator = S256Point::generator();
+        let mut scalars = extreme_scalars().to_vec();
+        scalars.extend([U256::from_u64(2), N.value().wrapping_add(&U256::from_u64(3))]);
+
+        let mut counts = vec![];
+        for scalar in scalars {
+            let (point, count) = multiplications(|| generator.mul_ct(&scalar));
+            counts.push(count);
+
+            assert_eq!(point, generator.mul(&scalar), "scalar {}", scalar);
+        }
+        assert!(all_equal(&counts), "{:?}", counts);
+        assert_eq!(generator.mul_ct(&N.value()), None);
+    }
+
+    #[test]
+    fn generator_table_takes_the_same_work_for_any_scalar() {
+        // Built outside the count, since only the first call anywhere pays for it
+        S256Point::mul_generator_ct(&U256::ONE);
+
+        let counts = extreme_scalars().map(|scalar| {
+            let (point, count) = multiplications(|| S256Point::mul_generator_ct(&scalar));
+
+            assert_eq!(point, S256Point::generator().mul(&scalar), "scalar {}", scalar);
+            count
+        });
+
+        assert!(all_equal(&counts), "{:?}", counts);
+    }
+
+    #[test]
+    fn inverses_take_the_same_work_for_any_scalar() {
+        let counts = extreme_scalars().map(|value| {
+            let scalar = Scalar::new(value).unwrap();
+            let (inverse, count) = multiplications(|| scalar.inverse());
+
+            assert_eq!(inverse * scalar, Scalar::ONE);
+            count
+        });
+
+        assert!(all_equal(&counts), "{:?}", counts);
+    }
+
+    #[test]
+    fn signing_work_does_not_depend_on_the_secret() {
+        // Each secret gets its own nonces, and about half the keys, nonces and s values need negating
+        let z = sha256(b"constant time");
+        let mut ecdsa_counts = vec![];
+        let mut schnorr_counts = vec![];
+        for secret in extreme_scalars() {
+            let private_key = PrivateKey::new(secret).unwrap();
+
+            let (signature, count) = multiplications(|| private_key.sign(&z));
+            ecdsa_counts.push(count);
+            assert!(private_key.point().verify(&z, &signature));
+
+            let (signature, count) = multiplications(|| private_key.sign_schnorr(&z, &[0; 32]));
+            schnorr_counts.push(count);
+            assert!(private_key.point().verify_schnorr(&z, &signature));
+        }
+
+        assert!(all_equal(&ecdsa_counts), "{:?}", ecdsa_counts);
+        assert!(all_equal(&schnorr_counts), "{:?}", schnorr_counts);
+    }
+
+    #[test]
+    fn private_keys_compare_in_constant_time() {
+        let source = include_str!("ecc/private_key.rs").split("#[cfg(test)]").next().unwrap();
+        let start = source.find("impl PartialEq for PrivateKey").unwrap();
+
+        assert!(source[start..].contains("ct_eq_bytes("));
+        assert_eq!(PrivateKey::new(U256::ONE), PrivateKey::new(U256::ONE));
+        assert_ne!(PrivateKey::new(U256::ONE), PrivateKey::new(U256::from_u64(2)));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::ecc::private_key::PrivateKey;
+use crate::ecc::secp256k1::S256Point;
+use crate::ecc::signature::Signature;
+use crate::ecc::x_only_public_key::{Parity, XOnlyPublicKey};
+use crate::script::taproot::{tweak_private_key, TaprootError};
+
+/*
+    A secret with its public key, for code that needs both: Schnorr signing and taproot tweaking depend on the
+    parity of the public key's y, and MuSig2 on the public key itself. The public key is derived once, when the
+    keypair is made, so the two halves cannot drift apart.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keypair {
+    private_key: PrivateKey,
+}
+
+impl Keypair {
+    pub fn from_secret(private_key: PrivateKey) -> Self {
+        Self { private_key }
+    }
+
+    pub fn private_key(&self) -> &PrivateKey {
+        &self.private_key
+    }
+
+    pub fn public_key(&self) -> S256Point {
+        self.private_key.point()
+    }
+
+    // The BIP340 key Schnorr signatures from this keypair verify against, and the parity of the full point's y
+    pub fn x_only_public_key(&self) -> (XOnlyPublicKey, Parity) {
+        let point = self.public_key();
+
+        (XOnlyPublicKey::from_point(&point), Parity::of(&point))
+    }
+
+    pub fn sign_ecdsa(&self, z: &[u8; 32]) -> Signature {
+        self.private_key.sign(z)
+    }
+
+    pub fn sign_schnorr(&self, msg: &[u8; 32], aux_rand: &[u8; 32]) -> [u8; 64] {
+        self.private_key.sign_schnorr(msg, aux_rand)
+    }
+
+    /*
+        The keypair of the BIP341 output key for this keypair's x-only key as the internal key, committing to the
+        script tree with the given merkle root or to none. Its Schnorr signatures are the key path spends of the
+        output XOnlyPublicKey::tap_tweak gives for the same internal key and root.
+     */
+    pub fn tap_tweak(&self, merkle_root: Option<[u8; 32]>) -> Result<Self, TaprootError> {
+        Ok(Self::from_secret(tweak_private_key(&self.private_key, merkle_root.as_ref())?))
+    }
+}
+
+impl From<PrivateKey> for Keypair {
+    fn from(private_key: PrivateKey) -> Self {
+        Self::from_secret(private_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ecc::keypair::Keypair;
+    use crate::ecc::private_key::PrivateKey;
+    use crate::ecc::secp256k1::S256Point;
+    use crate::ecc::u256::U256;
+    use crate::ecc::x_only_public_key::{Parity, XOnlyPublicKey};
+    use crate::hashing::sha256::sha256;
+
+    fn keypairs() -> impl Iterator<Item = Keypair> {
+        [1, 2, 3, 7, 5002, 0x12345deadbeef, u64::MAX]
+            .into_iter()
+            .map(|secret| Keypair::from_secret(PrivateKey::new(U256::from_u64(secret)).unwrap()))
+    }
+
+    #[test]
+    fn public_halves_match_an_independent_derivation() {
+        for keypair in keypairs() {
+            // mul is the variable-time path, separate from the ladder PrivateKey::new uses
+            let expected = S256Point::generator().mul(&keypair.private_key().secret().value()).unwrap();
+            let (x_only, parity) = keypair.x_only_public_key();
+
+            assert_eq!(keypair.public_key(), expected);
+            assert_eq!(x_only.to_bytes(), expected.x().to_be_bytes());
+            assert_eq!(parity.is_odd(), expected.y().is_odd());
+        }
+    }
+
+    #[test]
+    fn both_parities_occur_and_sign_for_the_x_only_key() {
+        let msg = sha256(b"keypair");
+        let mut seen = Vec::new();
+
+        for keypair in keypairs() {
+            let (x_only, parity) = keypair.x_only_public_key();
+            seen.push(parity);
+
+            assert!(x_only.to_point().verify_schnorr(&msg, &keypair.sign_schnorr(&msg, &[0; 32])));
+            assert!(keypair.public_key().verify(&msg, &keypair.sign_ecdsa(&msg)));
+        }
+        assert!(seen.contains(&Parity::Even) && seen.contains(&Parity::Odd));
+    }
+
+    #[test]
+    fn tweaked_keypairs_sign_for_the_output_key() {
+        let msg = sha256(b"key path spend");
+        let merkle_root = sha256(b"script tree");
+
+        for keypair in keypairs() {
+            let (internal_key, _) = keypair.x_only_public_key();
+
+            for root in [None, Some(merkle_root)] {
+                let tweaked = keypair.tap_tweak(root).unwrap();
+                let (output_key, odd_y) = internal_key.tap_tweak(root).unwrap();
+
+                assert_eq!(tweaked.x_only_public_key(), (output_key, if odd_y { Parity::Odd } else { Parity::Even }));
+                let signature = tweaked.sign_schnorr(&msg, &[7; 32]);
+                assert!(output_key.to_point().verify_schnorr(&msg, &signature));
+                assert!(!internal_key.to_point().verify_schnorr(&msg, &signature));
+            }
+        }
+    }
+
+    #[test]
+    fn tweaking_is_by_the_x_only_key() {
+        // A secret and its negation share an x-only key, so they tweak to the same output keypair
+        let keypair = Keypair::from_secret(PrivateKey::new(U256::from_u64(5)).unwrap());
+        let negated = Keypair::from_secret(PrivateKey::new((-keypair.private_key().secret()).value()).unwrap());
+
+        assert_eq!(XOnlyPublicKey::from_point(&negated.public_key()), keypair.x_only_public_key().0);
+        assert_ne!(negated.x_only_public_key().1, keypair.x_only_public_key().1);
+        assert_eq!(negated.tap_tweak(None).unwrap(), keypair.tap_tweak(None).unwrap());
+    }
+}
@@ -0,0 +1,156 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::address::{Address, AddressError, AddressKind};
+use crate::ecc::private_key::PrivateKey;
+use crate::ecc::secp256k1::S256Point;
+use crate::ecc::signature::{RecoverableSignature, SignatureError};
+use crate::encoding::base64;
+use crate::encoding::base64::Base64Error;
+use crate::encoding::varint::encode_varint;
+use crate::hashing::hash160::hash160;
+use crate::hashing::hash256::hash256;
+
+// Prefixed to every signed message, its first byte being the length of the rest
+const MESSAGE_MAGIC: &[u8] = b"\x18Bitcoin Signed Message:\n";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageError {
+    Address(AddressError),
+    // signmessage only signs with the key behind a P2PKH address, so no other kind can be checked
+    AddressNotP2pkh,
+    Base64(Base64Error),
+    // The number of decoded bytes, which has to be 65
+    InvalidSignatureLength(usize),
+    Signature(SignatureError),
+}
+
+impl Display for MessageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MessageError::Address(error) => write!(f, "{}", error),
+            MessageError::AddressNotP2pkh => write!(f, "Signed messages can only be checked against P2PKH addresses."),
+            MessageError::Base64(error) => write!(f, "{}", error),
+            MessageError::InvalidSignatureLength(length) => {
+                write!(f, "Message signatures are 65 bytes, not {}.", length)
+            }
+            MessageError::Signature(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for MessageError {}
+
+impl From<AddressError> for MessageError {
+    fn from(error: AddressError) -> Self {
+        MessageError::Address(error)
+    }
+}
+
+impl From<Base64Error> for MessageError {
+    fn from(error: Base64Error) -> Self {
+        MessageError::Base64(error)
+    }
+}
+
+impl From<SignatureError> for MessageError {
+    fn from(error: SignatureError) -> Self {
+        MessageError::Signature(error)
+    }
+}
+
+// hash256 of the magic prefix, the message's varint length and the message, which is what gets signed
+pub fn signed_message_hash(message: &str) -> [u8; 32] {
+    let mut data = MESSAGE_MAGIC.to_vec();
+    data.extend(encode_varint(message.len() as u64));
+    data.extend_from_slice(message.as_bytes());
+
+    hash256(&data)
+}
+
+impl PrivateKey {
+    // What bitcoin-cli signmessage gives: the base64 of the recoverable signature, for the compressed key
+    pub fn sign_message(&self, message: &str) -> String {
+        base64::encode(&self.sign_recoverable(&signed_message_hash(message)).serialize_compact(true))
+    }
+}
+
+/*
+    What bitcoin-cli verifymessage does: recovers the public key from the signature and checks that it hashes to
+    the address's, compressed or not as the header says. Errors are for inputs that cannot be read, false for a
+    signature that is well formed but not by this address's key.
+ */
+pub fn verify_message(address: &str, signature: &str, message: &str) -> Result<bool, MessageError> {
+    let AddressKind::P2pkh(pubkey_hash) = Address::from_str(address)?.kind() else {
+        return Err(MessageError::AddressNotP2pkh);
+    };
+    let bytes = base64::decode(signature)?;
+    let bytes: [u8; 65] = bytes.as_slice().try_into().map_err(|_| MessageError::InvalidSignatureLength(bytes.len()))?;
+    let (signature, compressed) = RecoverableSignature::parse(&bytes)?;
+
+    Ok(S256Point::recover(&signed_message_hash(message), &signature)
+        .is_some_and(|point| hash160(&point.sec(compressed)) == pubkey_hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::address::AddressError;
+    use crate::ecc::message::{verify_message, MessageError};
+    use crate::ecc::private_key::PrivateKey;
+    use crate::ecc::u256::U256;
+    use crate::network::Network;
+
+    // Bitcoin Core's rpc_signmessagewithprivkey.py, whose key is the WIF
+    // cUeKHd5orzT3mz8P9pxyREHfsWtVfgsfDjiZZBcjUBAaGk1BTj7N
+    const CORE_SECRET: &str = "d2b8a0116d641fe7d3036f8464628fb595b480414c13a301b3d4038c811c28b0";
+    const CORE_ADDRESS: &str = "mpLQjfK79b7CCV4VMJWEWAj5Mpx8Up5zxB";
+    const CORE_MESSAGE: &str = "This is just a test message";
+    const CORE_SIGNATURE: &str =
+        "INbVnW4e6PeRmsv2Qgu8NuopvrVjkcxob+sX8OcZG0SALhWybUjzMLPdAsXI46YZGb0KQTRii+wWIQzRpG/U+S0=";
+
+    #[test]
+    fn signing_matches_bitcoin_core() {
+        let private_key = PrivateKey::from_hex(CORE_SECRET).unwrap();
+
+        assert_eq!(private_key.point().address(true, Network::Testnet), CORE_ADDRESS);
+        assert_eq!(private_key.sign_message(CORE_MESSAGE), CORE_SIGNATURE);
+        assert_eq!(verify_message(CORE_ADDRESS, CORE_SIGNATURE, CORE_MESSAGE), Ok(true));
+    }
+
+    #[test]
+    fn other_messages_and_addresses_do_not_verify() {
+        let other_address = PrivateKey::new(U256::from_u64(5002)).unwrap().point().address(true, Network::Testnet);
+
+        assert_eq!(verify_message(CORE_ADDRESS, CORE_SIGNATURE, "This is just a test message."), Ok(false));
+        assert_eq!(verify_message(&other_address, CORE_SIGNATURE, CORE_MESSAGE), Ok(false));
+    }
+
+    #[test]
+    fn own_signatures_round_trip() {
+        for (secret, message) in [(1, ""), (5002, "hello"), (0x12345deadbeef, &"long message ".repeat(40))] {
+            let private_key = PrivateKey::new(U256::from_u64(secret)).unwrap();
+
+            let signature = private_key.sign_message(message);
+
+            for network in [Network::Mainnet, Network::Testnet] {
+                let address = private_key.point().address(true, network);
+                assert_eq!(verify_message(&address, &signature, message), Ok(true), "secret {}", secret);
+            }
+            // The header says the key is compressed, so the uncompressed key's address does not match
+            let uncompressed = private_key.point().address(false, Network::Mainnet);
+            assert_eq!(verify_message(&uncompressed, &signature, message), Ok(false));
+        }
+    }
+
+    #[test]
+    fn malformed_inputs_are_errors() {
+        let segwit = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+        assert_eq!(verify_message(segwit, CORE_SIGNATURE, CORE_MESSAGE), Err(MessageError::AddressNotP2pkh));
+        assert!(matches!(
+            verify_message("not an address", CORE_SIGNATURE, CORE_MESSAGE),
+            Err(MessageError::Address(AddressError::Base58(_)))
+        ));
+        assert_eq!(verify_message(CORE_ADDRESS, "AAAA", CORE_MESSAGE), Err(MessageError::InvalidSignatureLength(3)));
+        assert!(matches!(verify_message(CORE_ADDRESS, "!!!!", CORE_MESSAGE), Err(MessageError::Base64(_))));
+    }
+}
@@ -1,2 +1,16 @@
+// The small-prime field and integer curve worked through in Programming Bitcoin chapters 1 and 2. Their types stay
+// private, as only their own tests use them so far.
+#[allow(dead_code)]
 pub mod finite_field;
-pub mod point;
\ No newline at end of file
+pub mod keypair;
+pub mod message;
+pub mod musig;
+#[allow(dead_code)]
+pub mod point;
+pub mod private_key;
+pub mod scalar;
+pub mod schnorr;
+pub mod secp256k1;
+pub mod signature;
+pub mod u256;
+pub mod x_only_public_key;
@@ -0,0 +1,256 @@
+use std::fmt::{Display, Formatter};
+use crate::ecc::scalar::Scalar;
+use crate::ecc::secp256k1::{PointError, S256Point};
+use crate::ecc::u256::U256;
+use crate::ecc::x_only_public_key::XOnlyPublicKey;
+use crate::hashing::tagged_hash::{tagged_hash, tagged_hash_engine, KEYAGG_COEFFICIENT, KEYAGG_LIST};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MusigError {
+    NoKeys,
+    // The index of the signer whose key could not be read
+    InvalidPublicKey { signer: usize, error: PointError },
+    // A tweak not below N
+    InvalidTweak,
+    // The keys, or the key and a tweak, cancel out
+    InfiniteKey,
+}
+
+impl Display for MusigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MusigError::NoKeys => write!(f, "Key aggregation needs at least one public key."),
+            MusigError::InvalidPublicKey { signer, error } => {
+                write!(f, "Public key of signer {} is invalid: {}", signer, error)
+            }
+            MusigError::InvalidTweak => write!(f, "Tweak is not below N."),
+            MusigError::InfiniteKey => write!(f, "Aggregate key is the point at infinity."),
+        }
+    }
+}
+
+impl std::error::Error for MusigError {}
+
+/*
+    BIP327 (MuSig2) key aggregation. Each key is weighted by a coefficient, the KeyAgg coefficient tagged hash of
+    the whole key list's hash and the key, which stops a signer from picking its key to cancel out the others'.
+    The first key that differs from the first in the list gets a coefficient of 1 instead, which saves a
+    multiplication when signing.
+
+    gacc and tacc track tweaks, so that signing can later account for the negations and additions they made.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyAggContext {
+    aggregate: S256Point,
+    gacc: Scalar,
+    tacc: Scalar,
+}
+
+impl KeyAggContext {
+    // Aggregates x-only keys, each standing for its point with an even y, as a 0x02 plain key does
+    pub fn new(public_keys: &[XOnlyPublicKey]) -> Result<Self, MusigError> {
+        let plain_keys: Vec<[u8; 33]> = public_keys
+            .iter()
+            .map(|key| {
+                let mut plain_key = [0x02; 33];
+                plain_key[1..].copy_from_slice(&key.to_bytes());
+                plain_key
+            })
+            .collect();
+
+        Self::from_plain_keys(&plain_keys)
+    }
+
+    // Aggregates 33-byte compressed keys, which is what BIP327 takes, in the order given
+    pub fn from_plain_keys(public_keys: &[[u8; 33]]) -> Result<Self, MusigError> {
+        let first = public_keys.first().ok_or(MusigError::NoKeys)?;
+        let second = public_keys.iter().find(|key| *key != first);
+
+        let mut engine = tagged_hash_engine(KEYAGG_LIST);
+        for key in public_keys {
+            engine.update(key);
+        }
+        let list_hash = engine.finalize();
+
+        let mut aggregate: Option<S256Point> = None;
+        for (signer, key) in public_keys.iter().enumerate() {
+            let point = S256Point::parse_sec(key).map_err(|error| MusigError::InvalidPublicKey { signer, error })?;
+            let term = match Some(key) == second {
+                true => Some(point),
+                false => point.mul(&coefficient(&list_hash, key).value()),
+            };
+            aggregate = match (aggregate, term) {
+                (Some(sum), Some(term)) => sum.add(&term),
+                (sum, term) => sum.or(term),
+            };
+        }
+
+        Ok(Self {
+            aggregate: aggregate.ok_or(MusigError::InfiniteKey)?,
+            gacc: Scalar::ONE,
+            tacc: Scalar::ZERO,
+        })
+    }
+
+    // The aggregate as a full point, whose y parity tweaking and signing need
+    pub fn aggregate_point(&self) -> S256Point {
+        self.aggregate
+    }
+
+    // The aggregate key that a P2TR output or a BIP340 verifier takes
+    pub fn aggregate_key(&self) -> XOnlyPublicKey {
+        XOnlyPublicKey::from_point(&self.aggregate)
+    }
+
+    /*
+        BIP327 ApplyTweak: adds tweak * G to the aggregate. An x-only tweak, such as a taproot tweak, is applied to
+        the even y point of the aggregate, so the aggregate is negated first when its y is odd. A plain tweak, such
+        as a BIP32 one, is added to the aggregate as it is.
+     */
+    pub fn tweak(&self, tweak: &[u8; 32], x_only: bool) -> Result<Self, MusigError> {
+        let tweak = Scalar::from_be_bytes(*tweak).ok_or(MusigError::InvalidTweak)?;
+        let negate = x_only && self.aggregate.y().is_odd();
+        let aggregate = match negate {
+            true => S256Point::lift_x(self.aggregate.x(), false).expect("The aggregate is on the curve."),
+            false => self.aggregate,
+        };
+        let aggregate = match S256Point::generator().mul(&tweak.value()) {
+            Some(tweak_point) => aggregate.add(&tweak_point),
+            None => Some(aggregate),
+        };
+
+        Ok(Self {
+            aggregate: aggregate.ok_or(MusigError::InfiniteKey)?,
+            gacc: self.gacc.negate_if(negate),
+            tacc: tweak + self.tacc.negate_if(negate),
+        })
+    }
+
+    // The product of the negations tweaking has made, 1 or N - 1
+    pub fn gacc(&self) -> Scalar {
+        self.gacc
+    }
+
+    // The sum of the tweaks, with the same negations applied
+    pub fn tacc(&self) -> Scalar {
+        self.tacc
+    }
+}
+
+// The KeyAgg coefficient of a key in the list with the given KeyAgg list hash
+fn coefficient(list_hash: &[u8; 32], key: &[u8; 33]) -> Scalar {
+    let mut data = list_hash.to_vec();
+    data.extend_from_slice(key);
+
+    Scalar::reduce(&U256::from_be_bytes(tagged_hash(KEYAGG_COEFFICIENT, &data)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ecc::musig::{KeyAggContext, MusigError};
+    use crate::ecc::scalar::Scalar;
+    use crate::ecc::secp256k1::{PointError, S256Point};
+    use crate::ecc::u256::U256;
+    use crate::ecc::x_only_public_key::XOnlyPublicKey;
+    use crate::encoding::hex;
+
+    // The pubkeys and tweaks of BIP327's key_agg_vectors.json
+    const PUBKEYS: [&str; 7] = [
+        "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+        "03dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659",
+        "023590a94e768f8e1815c2f24b4d80a8e3149316c3518ce7b7ad338368d038ca66",
+        "020000000000000000000000000000000000000000000000000000000000000005",
+        "02fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc30",
+        "04f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+        "03935f972da013f80ae011890fa89b67a27b7be6ccb24d3274d18b2d4067f261a9",
+    ];
+    const TWEAKS: [&str; 2] = [
+        "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141",
+        "252e4bd67410a76cdf933d30eaa1608214037f1b105a013eccd3c5c184a6110b",
+    ];
+
+    fn keys(indices: &[usize]) -> Vec<[u8; 33]> {
+        indices.iter().map(|&index| hex::decode(PUBKEYS[index]).unwrap().try_into().unwrap()).collect()
+    }
+
+    fn bytes32(hex_str: &str) -> [u8; 32] {
+        hex::decode(hex_str).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn aggregate_keys_match_bip327_vectors() {
+        let cases: [(&[usize], &str); 4] = [
+            (&[0, 1, 2], "90539eede565f5d054f32cc0c220126889ed1e5d193baf15aef344fe59d4610c"),
+            (&[2, 1, 0], "6204de8b083426dc6eaf9502d27024d53fc826bf7d2012148a0575435df54b2b"),
+            (&[0, 0, 0], "b436e3bad62b8cd409969a224731c193d051162d8c5ae8b109306127da3aa935"),
+            (&[0, 0, 1, 1], "69bc22bfa5d106306e48a20679de1d7389386124d07571d0d872686028c26a3e"),
+        ];
+
+        for (indices, expected) in cases {
+            let context = KeyAggContext::from_plain_keys(&keys(indices)).unwrap();
+
+            assert_eq!(context.aggregate_key().to_string(), expected, "keys {:?}", indices);
+        }
+    }
+
+    #[test]
+    fn invalid_keys_name_their_signer() {
+        let cases = [
+            (&[0, 3], 1, PointError::NotOnCurve),
+            (&[0, 4], 1, PointError::CoordinateOutOfRange),
+            (&[5, 0], 0, PointError::InvalidSecPrefix(0x04)),
+        ];
+
+        for (indices, signer, error) in cases {
+            assert_eq!(
+                KeyAggContext::from_plain_keys(&keys(indices)),
+                Err(MusigError::InvalidPublicKey { signer, error }),
+                "keys {:?}",
+                indices
+            );
+        }
+        assert_eq!(KeyAggContext::from_plain_keys(&[]), Err(MusigError::NoKeys));
+    }
+
+    #[test]
+    fn invalid_tweaks_match_bip327_error_vectors() {
+        let context = KeyAggContext::from_plain_keys(&keys(&[0, 1])).unwrap();
+        assert_eq!(context.tweak(&bytes32(TWEAKS[0]), true), Err(MusigError::InvalidTweak));
+
+        // Key 6 is -tweak_1 * G, so adding the tweak as a plain tweak leaves the point at infinity
+        let context = KeyAggContext::from_plain_keys(&keys(&[6])).unwrap();
+        assert_eq!(context.tweak(&bytes32(TWEAKS[1]), false), Err(MusigError::InfiniteKey));
+    }
+
+    #[test]
+    fn x_only_keys_aggregate_as_even_y_plain_keys() {
+        let x_only = |indices: &[usize]| -> Vec<XOnlyPublicKey> {
+            keys(indices).iter().map(|key| XOnlyPublicKey::from_bytes(key[1..].try_into().unwrap()).unwrap()).collect()
+        };
+
+        // Keys 0 and 2 have an even y, so their x-only forms give the same aggregate
+        assert_eq!(KeyAggContext::new(&x_only(&[0, 2, 0])), KeyAggContext::from_plain_keys(&keys(&[0, 2, 0])));
+        // Key 1 has an odd y, which its x-only form drops, so the aggregate changes
+        assert_ne!(
+            KeyAggContext::new(&x_only(&[0, 1])).unwrap().aggregate_key(),
+            KeyAggContext::from_plain_keys(&keys(&[0, 1])).unwrap().aggregate_key()
+        );
+    }
+
+    #[test]
+    fn tweaks_are_added_to_the_even_or_actual_aggregate() {
+        let context = KeyAggContext::from_plain_keys(&keys(&[0, 1, 2])).unwrap();
+        let tweak = bytes32(TWEAKS[1]);
+        let tweak_point = S256Point::generator().mul(&U256::from_be_bytes(tweak)).unwrap();
+        let odd_y = context.aggregate_point().y().is_odd();
+
+        let x_only = context.tweak(&tweak, true).unwrap();
+        let plain = context.tweak(&tweak, false).unwrap();
+
+        assert_eq!(Some(x_only.aggregate_point()), context.aggregate_key().to_point().add(&tweak_point));
+        assert_eq!(Some(plain.aggregate_point()), context.aggregate_point().add(&tweak_point));
+        assert_eq!(x_only.gacc(), Scalar::ONE.negate_if(odd_y));
+        assert_eq!(x_only.tacc(), Scalar::from_be_bytes(tweak).unwrap());
+        assert_eq!(plain.gacc(), Scalar::ONE);
+    }
+}
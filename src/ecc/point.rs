@@ -56,9 +56,9 @@ impl Add for Point {
         }
 
         if self.x_opt.is_none() {
-            return rhs;
+            rhs
         } else if rhs.x_opt.is_none() {
-            return self;
+            self
         } else {
             todo!()
         }
@@ -0,0 +1,317 @@
+use std::fmt::{Debug, Display, Formatter};
+use crate::constant_time::ct_eq_bytes;
+use crate::ecc::scalar::Scalar;
+use crate::ecc::secp256k1::{S256Point, N};
+use crate::ecc::signature::{RecoverableSignature, Signature};
+use crate::ecc::u256::U256;
+use crate::encoding::hex;
+use crate::encoding::hex::HexError;
+use crate::hashing::hmac::HmacSha256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyError {
+    // The number of hex digits, which has to be 64
+    InvalidLength(usize),
+    Hex(HexError),
+    // Zero, or not below N
+    OutOfRange,
+}
+
+impl Display for KeyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyError::InvalidLength(length) => write!(f, "Private keys are 64 hex digits, not {}.", length),
+            KeyError::Hex(error) => write!(f, "{}", error),
+            KeyError::OutOfRange => write!(f, "Private key is not between 1 and N - 1."),
+        }
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+impl From<HexError> for KeyError {
+    fn from(error: HexError) -> Self {
+        KeyError::Hex(error)
+    }
+}
+
+// A secp256k1 secret, a scalar from 1 to N - 1, along with the public key it gives
+#[derive(Clone)]
+pub struct PrivateKey {
+    secret: Scalar,
+    point: S256Point,
+}
+
+impl PrivateKey {
+    // None for zero or anything not below N
+    pub fn new(secret: U256) -> Option<Self> {
+        let secret = Scalar::new(secret).filter(|secret| !secret.is_zero())?;
+        let point = S256Point::mul_generator_ct(&secret.value())?;
+
+        Some(Self { secret, point })
+    }
+
+    // For callers that already hold secret * G, such as the vanity search stepping from one key to the next
+    pub(crate) fn from_parts(secret: Scalar, point: S256Point) -> Self {
+        debug_assert!(!secret.is_zero());
+
+        Self { secret, point }
+    }
+
+    // The secret as 64 big-endian hex digits
+    pub fn from_hex(s: &str) -> Result<Self, KeyError> {
+        if s.len() != 64 {
+            return Err(KeyError::InvalidLength(s.len()));
+        }
+        let bytes: [u8; 32] = hex::decode(s)?.try_into().expect("64 hex digits are 32 bytes.");
+
+        Self::new(U256::from_be_bytes(bytes)).ok_or(KeyError::OutOfRange)
+    }
+
+    pub fn secret(&self) -> Scalar {
+        self.secret
+    }
+
+    // The hex from_hex reads, named so that writing the secret anywhere is a visible choice
+    pub fn expose_secret_hex(&self) -> String {
+        self.secret.to_string()
+    }
+
+    pub fn point(&self) -> S256Point {
+        self.point
+    }
+
+    /*
+        ECDSA over the 32-byte message hash z: with R = kG, r is R.x and s is (z + r * secret) / k modulo N. The
+        nonce k comes from RFC 6979, so signing the same z twice gives the same signature and never leaks the
+        secret through a bad random number. s is always the lower of s and N - s, as BIP62 asks for.
+
+        kG goes through the constant-time generator table and 1 / k through the fixed-length Fermat inverse, so the work
+        done depends on neither the secret nor the nonce.
+     */
+    pub fn sign(&self, z: &[u8; 32]) -> Signature {
+        self.sign_recoverable(z).signature()
+    }
+
+    /*
+        sign, keeping the recovery id as well. Taking N - s signs with -k instead, whose R has the other parity,
+        so the parity bit is flipped along with s.
+     */
+    pub fn sign_recoverable(&self, z: &[u8; 32]) -> RecoverableSignature {
+        let z = Scalar::reduce(&U256::from_be_bytes(*z));
+        let mut nonces = Rfc6979::new(&self.secret, &z);
+
+        loop {
+            let k = nonces.next();
+            let Some(r_point) = S256Point::mul_generator_ct(&k.value()) else { continue };
+            let r = Scalar::from_x_coordinate(&r_point);
+            if r.is_zero() {
+                continue;
+            }
+
+            let s = (z + r * self.secret) * k.inverse();
+            if s.is_zero() {
+                continue;
+            }
+
+            let high = s.is_high();
+            let s = s.negate_if(high);
+            let odd_y = r_point.y().is_odd() ^ high;
+            let x_overflow = !r_point.x().overflowing_sub(&N.value()).1;
+
+            return RecoverableSignature::new(Signature::new(r, s), odd_y as u8 | (x_overflow as u8) << 1)
+                .expect("The recovery id is two bits.");
+        }
+    }
+}
+
+// Compares the secrets without stopping at the first byte that differs
+impl PartialEq for PrivateKey {
+    fn eq(&self, other: &Self) -> bool {
+        ct_eq_bytes(&self.secret.to_be_bytes(), &other.secret.to_be_bytes())
+    }
+}
+
+impl Eq for PrivateKey {}
+
+// Only the public key, so that logging a PrivateKey does not log the secret
+impl Debug for PrivateKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrivateKey").field("point", &self.point).finish_non_exhaustive()
+    }
+}
+
+// The HMAC-SHA256 DRBG of RFC 6979 section 3.2, seeded with the secret and the message hash
+struct Rfc6979 {
+    k: [u8; 32],
+    v: [u8; 32],
+    started: bool,
+}
+
+impl Rfc6979 {
+    fn new(secret: &Scalar, z: &Scalar) -> Self {
+        let mut drbg = Self { k: [0; 32], v: [1; 32], started: false };
+        for separator in [0x00, 0x01] {
+            drbg.k = drbg.hmac(&[&[separator], &secret.to_be_bytes(), &z.to_be_bytes()]);
+            drbg.v = drbg.hmac(&[]);
+        }
+
+        drbg
+    }
+
+    fn hmac(&self, parts: &[&[u8]]) -> [u8; 32] {
+        let mut hmac = HmacSha256::new(&self.k);
+        hmac.update(&self.v);
+        for part in parts {
+            hmac.update(part);
+        }
+
+        hmac.finalize()
+    }
+
+    // The next candidate nonce in 1..N; the caller asks again if one turns out unusable
+    fn next(&mut self) -> Scalar {
+        loop {
+            if self.started {
+                self.k = self.hmac(&[&[0x00]]);
+                self.v = self.hmac(&[]);
+            }
+            self.started = true;
+
+            self.v = self.hmac(&[]);
+            if let Some(candidate) = Scalar::from_be_bytes(self.v).filter(|candidate| !candidate.is_zero()) {
+                return candidate;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use crate::ecc::private_key::{KeyError, PrivateKey};
+    use crate::ecc::scalar::Scalar;
+    use crate::ecc::secp256k1::{S256Point, N, P};
+    use crate::ecc::signature::Signature;
+    use crate::ecc::u256::U256;
+    use crate::encoding::hex;
+    use crate::encoding::hex::HexError;
+    use crate::hashing::sha256::sha256;
+
+    #[test]
+    fn secrets_must_be_below_the_order() {
+        assert_eq!(PrivateKey::new(U256::ZERO), None);
+        assert_eq!(PrivateKey::new(N.value()), None);
+        assert!(PrivateKey::new(N.value().wrapping_sub(&U256::ONE)).is_some());
+    }
+
+    #[test]
+    fn secrets_round_trip_through_hex() {
+        let secret = "0000000000000000000000000000000000000000000000000000000000000539";
+
+        let private_key = PrivateKey::from_hex(secret).unwrap();
+
+        assert_eq!(private_key.secret().value(), U256::from_u64(1337));
+        assert_eq!(private_key.expose_secret_hex(), secret);
+        assert_eq!(PrivateKey::from_hex(&secret.to_uppercase()), Ok(private_key));
+    }
+
+    #[test]
+    fn malformed_hex_secrets_are_rejected() {
+        let order = hex::encode(&N.value().to_be_bytes());
+        let not_hex = format!("{}g", &order[..63]);
+
+        assert_eq!(PrivateKey::from_hex(&order[..63]), Err(KeyError::InvalidLength(63)));
+        assert_eq!(PrivateKey::from_hex(&format!("0x{}", order)), Err(KeyError::InvalidLength(66)));
+        assert_eq!(
+            PrivateKey::from_hex(&not_hex),
+            Err(KeyError::Hex(HexError::InvalidCharacter { character: 'g', position: 63 }))
+        );
+        assert_eq!(PrivateKey::from_hex(&order), Err(KeyError::OutOfRange));
+        assert_eq!(PrivateKey::from_hex(&"0".repeat(64)), Err(KeyError::OutOfRange));
+    }
+
+    #[test]
+    fn debug_shows_no_secret() {
+        let private_key = PrivateKey::from_hex(&"ab".repeat(32)).unwrap();
+
+        let debug = format!("{:?}", private_key);
+
+        assert!(debug.starts_with("PrivateKey { point: S256Point"), "{}", debug);
+        assert!(!debug.contains("abab") && !debug.contains(&format!("{:?}", private_key.secret())), "{}", debug);
+    }
+
+    #[test]
+    fn signing_is_deterministic_and_verifies() {
+        // The widely used RFC 6979 secp256k1 vector for secret 1
+        let private_key = PrivateKey::new(U256::ONE).unwrap();
+        let z = sha256(b"Satoshi Nakamoto");
+
+        let signature = private_key.sign(&z);
+
+        assert_eq!(
+            hex::encode(&signature.der()),
+            "3045022100934b1ea10a4b3c1757e2b0c017d0b6143ce3c9a7e6a4a49860d7a6ab210ee3d802202442ce9d2b916064108014783e\
+             923ec36b49743e2ffa1c4496f01a512aafd9e5"
+        );
+        assert_eq!(private_key.sign(&z), signature);
+        assert!(private_key.point().verify(&z, &signature));
+    }
+
+    #[test]
+    fn s_taken_modulo_the_field_prime_does_not_verify() {
+        let private_key = PrivateKey::new(U256::from_u64(12345)).unwrap();
+        let z = sha256(b"modulo n");
+        let (k, secret, z_value) = (U256::from_u64(1234567), private_key.secret().value(), U256::from_be_bytes(z));
+        let r = Scalar::from_x_coordinate(&S256Point::generator().mul(&k).unwrap());
+
+        let s = (Scalar::reduce(&z_value) + r * private_key.secret()) * Scalar::new(k).unwrap().inverse();
+        let field_s = P.mul(&P.add(&P.reduce(&z_value), &P.mul(&r.value(), &secret)), &P.inv(&k));
+
+        assert!(private_key.point().verify(&z, &Signature::new(r, s)));
+        assert_ne!(Scalar::reduce(&field_s), s);
+        assert!(!private_key.point().verify(&z, &Signature::new(r, Scalar::reduce(&field_s))));
+    }
+
+    #[test]
+    fn signatures_have_low_s() {
+        let private_key = PrivateKey::new(U256::from_u64(12345)).unwrap();
+
+        for message in [&b"a"[..], b"b", b"c", b"d"] {
+            let signature = private_key.sign(&sha256(message));
+
+            assert!(signature.s().value() < N.value().wrapping_sub(&signature.s().value()));
+        }
+    }
+
+    #[test]
+    fn keys_can_be_shared_between_signing_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<PrivateKey>();
+
+        let private_key = Arc::new(PrivateKey::new(U256::from_u64(0x5160)).unwrap());
+        let threads: Vec<_> = (0..4u8)
+            .map(|thread_index| {
+                let private_key = Arc::clone(&private_key);
+                thread::spawn(move || {
+                    (0..8u8)
+                        .map(|message| {
+                            let z = sha256(&[thread_index, message]);
+                            (z, private_key.sign(&z), private_key.sign_schnorr(&z, &[0; 32]))
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            for (z, signature, schnorr_signature) in thread.join().unwrap() {
+                assert!(private_key.point().verify(&z, &signature));
+                assert!(private_key.point().verify_schnorr(&z, &schnorr_signature));
+                // Signing is deterministic, so another thread's signature is the same as this one's
+                assert_eq!(private_key.sign(&z), signature);
+            }
+        }
+    }
+}
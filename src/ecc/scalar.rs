@@ -0,0 +1,158 @@
+use std::fmt::{Display, Formatter};
+use std::ops::{Add, Mul, Neg, Sub};
+use crate::ecc::secp256k1::{S256Point, N};
+use crate::ecc::u256::U256;
+
+// (N - 1) / 2, the largest low s
+const HALF_ORDER: U256 =
+    U256::from_limbs([0xdfe92f46681b20a0, 0x5d576e7357a4501d, 0xffffffffffffffff, 0x7fffffffffffffff]);
+
+/*
+    An integer modulo N, the order of the generator: secrets, nonces, message hashes and the r and s of a signature
+    all live here, while coordinates stay integers modulo P. Keeping the two apart in the types means an inverse
+    taken modulo the wrong prime does not compile, rather than giving signatures that do not verify.
+
+    The arithmetic is that of N, so it does the same work whatever the values.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Scalar(U256);
+
+impl Scalar {
+    pub const ZERO: Self = Self(U256::ZERO);
+    pub const ONE: Self = Self(U256::ONE);
+
+    // None for anything not below N
+    pub fn new(value: U256) -> Option<Self> {
+        (value < N.value()).then_some(Self(value))
+    }
+
+    // value modulo N, for a message hash, which any 256-bit value can be
+    pub fn reduce(value: &U256) -> Self {
+        Self(N.reduce(value))
+    }
+
+    // The x coordinate of a point modulo N, which is how ECDSA turns the nonce point into r
+    pub fn from_x_coordinate(point: &S256Point) -> Self {
+        Self::reduce(&point.x())
+    }
+
+    pub fn from_be_bytes(bytes: [u8; 32]) -> Option<Self> {
+        Self::new(U256::from_be_bytes(bytes))
+    }
+
+    pub fn to_be_bytes(&self) -> [u8; 32] {
+        self.0.to_be_bytes()
+    }
+
+    pub fn value(&self) -> U256 {
+        self.0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+
+    // Above N / 2, so that the negation is the low s BIP62 asks for. Read off a borrow rather than a comparison
+    // that stops at the first differing limb, as s comes from the secret and the nonce
+    pub fn is_high(&self) -> bool {
+        HALF_ORDER.overflowing_sub(&self.0).1
+    }
+
+    // The negation when choice is true, picked by selection so that a choice made from secret data does not branch
+    pub fn negate_if(&self, choice: bool) -> Self {
+        Self(U256::select(&self.0, &N.neg(&self.0), choice))
+    }
+
+    // Zero has no inverse and gives zero
+    pub fn inverse(&self) -> Self {
+        Self(N.inv(&self.0))
+    }
+
+    pub fn pow(&self, exponent: &U256) -> Self {
+        Self(N.pow(&self.0, exponent))
+    }
+}
+
+impl Add for Scalar {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self(N.add(&self.0, &rhs.0))
+    }
+}
+
+impl Sub for Scalar {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(N.sub(&self.0, &rhs.0))
+    }
+}
+
+impl Mul for Scalar {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self(N.mul(&self.0, &rhs.0))
+    }
+}
+
+impl Neg for Scalar {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self(N.neg(&self.0))
+    }
+}
+
+// 64 hex digits, most significant first
+impl Display for Scalar {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ecc::scalar::Scalar;
+    use crate::ecc::secp256k1::{N, P};
+    use crate::ecc::u256::U256;
+
+    #[test]
+    fn arithmetic_wraps_around_the_order() {
+        let minus_one = -Scalar::ONE;
+        let seven = Scalar::new(U256::from_u64(7)).unwrap();
+
+        assert_eq!(minus_one.value(), N.value().wrapping_sub(&U256::ONE));
+        assert_eq!(minus_one + Scalar::new(U256::from_u64(2)).unwrap(), Scalar::ONE);
+        assert_eq!(Scalar::ZERO - Scalar::ONE, minus_one);
+        assert_eq!(minus_one * minus_one, Scalar::ONE);
+        assert_eq!(seven * seven.inverse(), Scalar::ONE);
+        assert_eq!(seven.pow(&U256::from_u64(3)), seven * seven * seven);
+        assert!(minus_one.is_high() && !Scalar::ONE.is_high());
+        assert_eq!(seven.negate_if(true), -seven);
+        assert_eq!(seven.negate_if(false), seven);
+    }
+
+    #[test]
+    fn values_must_be_below_the_order() {
+        let mut bytes = N.value().to_be_bytes();
+
+        assert_eq!(Scalar::from_be_bytes(bytes), None);
+        assert_eq!(Scalar::reduce(&N.value()), Scalar::ZERO);
+        bytes[31] -= 1;
+        assert_eq!(Scalar::from_be_bytes(bytes).map(|scalar| scalar.to_be_bytes()), Some(bytes));
+        assert_eq!(Scalar::reduce(&P.value()).value(), P.value().wrapping_sub(&N.value()));
+    }
+
+    #[test]
+    fn inverses_modulo_the_order_and_the_field_prime_differ() {
+        // 7 inverted modulo P is not its inverse modulo N, the mix-up Scalar keeps out of the signing math
+        let seven = U256::from_u64(7);
+        let field_inverse = P.inv(&seven);
+
+        assert_eq!(P.mul(&field_inverse, &seven), U256::ONE);
+        assert_ne!(Scalar::reduce(&field_inverse), Scalar::new(seven).unwrap().inverse());
+        assert_ne!(Scalar::reduce(&field_inverse) * Scalar::new(seven).unwrap(), Scalar::ONE);
+    }
+}
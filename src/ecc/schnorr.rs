@@ -0,0 +1,231 @@
+use crate::ecc::private_key::PrivateKey;
+use crate::ecc::scalar::Scalar;
+use crate::ecc::secp256k1::{S256Point, P};
+use crate::ecc::u256::U256;
+use crate::hashing::tagged_hash::{tagged_hash, tagged_hash_engine, BIP340_AUX, BIP340_CHALLENGE, BIP340_NONCE};
+use crate::rng::CryptoRng;
+
+/*
+    BIP340 Schnorr signatures, which taproot spends use in place of ECDSA. A public key is x-only, 32 bytes
+    standing for the point with that x and an even y, and a signature is 64 bytes with no DER around them:
+
+    r  32 bytes, the x of the nonce point R, whose y is even
+    s  32 bytes, with sG = R + eP, where e is the challenge hash of r, the public key and the message
+ */
+impl PrivateKey {
+    /*
+        Signs the 32-byte message as BIP340 does. The secret is negated when its point has an odd y, so that it
+        matches the x-only public key, and the same goes for the nonce and R. aux_rand is masked into the nonce
+        derivation: fresh random bytes guard against side channels, while any fixed value still gives a valid
+        signature, the same one each time.
+     */
+    pub fn sign_schnorr(&self, msg: &[u8; 32], aux_rand: &[u8; 32]) -> [u8; 64] {
+        let point = self.point();
+        let secret = self.secret().negate_if(point.y().is_odd());
+        let public_key = point.x().to_be_bytes();
+
+        let mut masked_secret = secret.to_be_bytes();
+        for (byte, mask) in masked_secret.iter_mut().zip(tagged_hash(BIP340_AUX, aux_rand)) {
+            *byte ^= mask;
+        }
+        let mut engine = tagged_hash_engine(BIP340_NONCE);
+        engine.update(&masked_secret);
+        engine.update(&public_key);
+        engine.update(msg);
+        let k = Scalar::reduce(&U256::from_be_bytes(engine.finalize()));
+
+        let r_point = S256Point::mul_generator_ct(&k.value()).expect("A hash is never a multiple of N.");
+        let k = k.negate_if(r_point.y().is_odd());
+        let r = r_point.x().to_be_bytes();
+        let s = k + challenge(&r, &public_key, msg) * secret;
+
+        let mut signature = [0; 64];
+        signature[..32].copy_from_slice(&r);
+        signature[32..].copy_from_slice(&s.to_be_bytes());
+
+        signature
+    }
+
+    // sign_schnorr with aux_rand drawn from the generator, as BIP340 recommends outside of reproducing vectors
+    pub fn sign_schnorr_with_rng(&self, msg: &[u8; 32], rng: &mut impl CryptoRng) -> [u8; 64] {
+        let mut aux_rand = [0; 32];
+        rng.fill_bytes(&mut aux_rand);
+
+        self.sign_schnorr(msg, &aux_rand)
+    }
+}
+
+impl S256Point {
+    /*
+        BIP340 verification against this point's x-only public key, so the parity of its y plays no part. Fails
+        for an r not below P or an s not below N, and otherwise checks that sG - eP is a point with an even y
+        whose x is r.
+     */
+    pub fn verify_schnorr(&self, msg: &[u8; 32], signature: &[u8; 64]) -> bool {
+        let r: [u8; 32] = signature[..32].try_into().expect("r is 32 bytes.");
+        let Some(s) = Scalar::from_be_bytes(signature[32..].try_into().expect("s is 32 bytes.")) else {
+            return false;
+        };
+        if U256::from_be_bytes(r) >= P.value() {
+            return false;
+        }
+
+        // The key stands for the even y point, the negation of this one when its y is odd, which turns -eP into eP
+        let e = challenge(&r, &self.x().to_be_bytes(), msg);
+        let e = if self.y().is_odd() { e } else { -e };
+        let total = match (Self::generator().mul(&s.value()), self.mul(&e.value())) {
+            (Some(s_g), Some(e_p)) => s_g.add(&e_p),
+            (s_g, e_p) => s_g.or(e_p),
+        };
+
+        total.is_some_and(|point| !point.y().is_odd() && point.x().to_be_bytes() == r)
+    }
+}
+
+// e, the BIP0340/challenge tagged hash of r, the x-only public key and the message, modulo N
+fn challenge(r: &[u8; 32], public_key: &[u8; 32], msg: &[u8; 32]) -> Scalar {
+    let mut engine = tagged_hash_engine(BIP340_CHALLENGE);
+    engine.update(r);
+    engine.update(public_key);
+    engine.update(msg);
+
+    Scalar::reduce(&U256::from_be_bytes(engine.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ecc::private_key::PrivateKey;
+    use crate::ecc::secp256k1::{S256Point, N, P};
+    use crate::ecc::u256::U256;
+    use crate::encoding::hex;
+    use crate::rng::{OsRng, SeededRng};
+
+    fn bytes32(hex_str: &str) -> [u8; 32] {
+        hex::decode(hex_str).unwrap().try_into().unwrap()
+    }
+
+    fn bytes64(hex_str: &str) -> [u8; 64] {
+        hex::decode(hex_str).unwrap().try_into().unwrap()
+    }
+
+    // (secret, x-only public key, aux_rand, message, signature), vectors 0 to 2 of BIP340's test-vectors.csv
+    const SIGNING_VECTORS: [(&str, &str, &str, &str, &str); 3] = [
+        (
+            "0000000000000000000000000000000000000000000000000000000000000003",
+            "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca8215\
+             25f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0",
+        ),
+        (
+            "b7e151628aed2a6abf7158809cf4f3c762e7160f38b4da56a784d9045190cfef",
+            "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659",
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+            "6896bd60eeae296db48a229ff71dfe071bde413e6d43f917dc8dcf8c78de3341\
+             8906d11ac976abccb20b091292bff4ea897efcb639ea871cfa95f6de339e4b0a",
+        ),
+        (
+            "c90fdaa22168c234c4c6628b80dc1cd129024e088a67cc74020bbea63b14e5c9",
+            "dd308afec5777e13121fa72b9cc1b7cc0139715309b086c960e18fd969774eb8",
+            "c87aa53824b4d7ae2eb035a2b5bbbccc080e76cdc6d1692c4b0b62d798e6d906",
+            "7e2d58d8b3bcdf1abadec7829054f90dda9805aab56c77333024b9d0a508b75c",
+            "5831aaeed7b44bb74e5eab94ba9d4294c49bcf2a60728d8b4c200f50dd313c1b\
+             ab745879a5ad954a72c45a91c3a51d3c7adea98d82f8481e0e1e03674a6f3fb7",
+        ),
+    ];
+
+    #[test]
+    fn signing_matches_bip340_vectors() {
+        for (secret, public_key, aux_rand, msg, signature) in SIGNING_VECTORS {
+            let private_key = PrivateKey::from_hex(secret).unwrap();
+
+            let result = private_key.sign_schnorr(&bytes32(msg), &bytes32(aux_rand));
+
+            assert_eq!(private_key.point().x().to_be_bytes(), bytes32(public_key));
+            assert_eq!(hex::encode(&result), signature);
+        }
+    }
+
+    #[test]
+    fn zero_aux_rand_matches_bip340_vector_0() {
+        let (secret, _, aux_rand, msg, signature) = SIGNING_VECTORS[0];
+        let private_key = PrivateKey::from_hex(secret).unwrap();
+
+        assert_eq!(bytes32(aux_rand), [0; 32]);
+        for _ in 0..2 {
+            assert_eq!(hex::encode(&private_key.sign_schnorr(&bytes32(msg), &[0; 32])), signature);
+        }
+    }
+
+    #[test]
+    fn aux_rand_changes_the_signature_but_not_its_validity() {
+        let private_key = PrivateKey::new(U256::from_u64(0x5c4e)).unwrap();
+        let msg = [0x42; 32];
+
+        let zero_aux = private_key.sign_schnorr(&msg, &[0; 32]);
+        let other_aux = private_key.sign_schnorr(&msg, &[1; 32]);
+        let seeded = private_key.sign_schnorr_with_rng(&msg, &mut SeededRng::from_u64(340));
+        let os = private_key.sign_schnorr_with_rng(&msg, &mut OsRng);
+
+        assert_ne!(zero_aux, other_aux);
+        assert_ne!(seeded, os);
+        for signature in [zero_aux, other_aux, seeded, os] {
+            assert!(private_key.point().verify_schnorr(&msg, &signature));
+        }
+        // The same generator state gives the same aux_rand, and so the same signature
+        assert_eq!(private_key.sign_schnorr_with_rng(&msg, &mut SeededRng::from_u64(340)), seeded);
+    }
+
+    #[test]
+    fn either_point_with_the_key_as_x_verifies() {
+        for (_, public_key, _, msg, signature) in SIGNING_VECTORS {
+            let x = U256::from_be_bytes(bytes32(public_key));
+
+            for odd_y in [false, true] {
+                let point = S256Point::lift_x(x, odd_y).unwrap();
+
+                assert!(point.verify_schnorr(&bytes32(msg), &bytes64(signature)));
+            }
+        }
+    }
+
+    #[test]
+    fn changed_messages_and_signatures_fail() {
+        let (_, public_key, _, msg, signature) = SIGNING_VECTORS[1];
+        let point = S256Point::lift_x(U256::from_be_bytes(bytes32(public_key)), false).unwrap();
+        let (msg, signature) = (bytes32(msg), bytes64(signature));
+
+        let mut other_msg = msg;
+        other_msg[31] ^= 0x01;
+        assert!(!point.verify_schnorr(&other_msg, &signature));
+
+        for index in [0, 31, 32, 63] {
+            let mut flipped = signature;
+            flipped[index] ^= 0x01;
+            assert!(!point.verify_schnorr(&msg, &flipped), "byte {}", index);
+        }
+
+        // Out of range values are rejected rather than reduced
+        let mut r_of_p = signature;
+        r_of_p[..32].copy_from_slice(&P.value().to_be_bytes());
+        let mut s_of_n = signature;
+        s_of_n[32..].copy_from_slice(&N.value().to_be_bytes());
+        assert!(!point.verify_schnorr(&msg, &r_of_p));
+        assert!(!point.verify_schnorr(&msg, &s_of_n));
+    }
+
+    #[test]
+    fn signatures_from_keys_with_an_odd_y_verify() {
+        // Whichever parity each key has, the signature has to verify against its own point
+        let msg = [0x5a; 32];
+        for secret in 1..=8 {
+            let private_key = PrivateKey::new(U256::from_u64(secret)).unwrap();
+
+            let signature = private_key.sign_schnorr(&msg, &[0; 32]);
+
+            assert!(private_key.point().verify_schnorr(&msg, &signature), "secret {}", secret);
+        }
+    }
+}
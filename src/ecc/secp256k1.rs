@@ -0,0 +1,1101 @@
+#[cfg(test)]
+use std::cell::Cell;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use crate::address::{Address, AddressKind};
+use crate::ecc::scalar::Scalar;
+use crate::ecc::signature::{DerError, RecoverableSignature, Signature};
+use crate::ecc::u256::U256;
+use crate::encoding::hex;
+use crate::encoding::hex::HexError;
+use crate::hashing::hash160::hash160;
+use crate::network::Network;
+use crate::script::Script;
+
+/*
+    Arithmetic modulo a prime just below 2^256. Since 2^256 = c (mod m) for c = 2^256 - m, the high half of a
+    512-bit product can be folded back in as high * c. Both secp256k1 moduli have a c of at most 129 bits, so four
+    folds bring any product under 2^256.
+
+    Nothing here branches on the values: carries pick results through U256::select and every loop runs a fixed
+    number of times, so signing can use the same arithmetic on secrets.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Modulus {
+    m: U256,
+    c: U256,
+}
+
+impl Modulus {
+    const fn new(m: U256, c: U256) -> Self {
+        Self { m, c }
+    }
+
+    pub fn value(&self) -> U256 {
+        self.m
+    }
+
+    // Brings any 256-bit value into range, which takes at most one subtraction for these moduli
+    pub fn reduce(&self, value: &U256) -> U256 {
+        let (reduced, borrow) = value.overflowing_sub(&self.m);
+        U256::select(&reduced, value, borrow)
+    }
+
+    pub fn add(&self, a: &U256, b: &U256) -> U256 {
+        let (sum, carry) = a.overflowing_add(b);
+        let (reduced, borrow) = sum.overflowing_sub(&self.m);
+        U256::select(&sum, &reduced, carry | !borrow)
+    }
+
+    pub fn sub(&self, a: &U256, b: &U256) -> U256 {
+        let (difference, borrow) = a.overflowing_sub(b);
+        difference.wrapping_add(&U256::select(&U256::ZERO, &self.m, borrow))
+    }
+
+    pub fn neg(&self, a: &U256) -> U256 {
+        self.sub(&U256::ZERO, a)
+    }
+
+    /*
+        The high half shrinks to under 130 bits after the first fold, under 3 bits after the second and to at most
+        a carry after the third, which the fourth clears.
+     */
+    pub fn mul(&self, a: &U256, b: &U256) -> U256 {
+        #[cfg(test)]
+        MULTIPLICATIONS.with(|count| count.set(count.get() + 1));
+
+        let (mut low, mut high) = a.widening_mul(b);
+        for _ in 0..4 {
+            let (folded_low, folded_high) = high.widening_mul(&self.c);
+            let (sum, carry) = low.overflowing_add(&folded_low);
+            low = sum;
+            high = folded_high.wrapping_add(&U256::from_u64(carry as u64));
+        }
+
+        self.reduce(&low)
+    }
+
+    // Square and always multiply over all 256 exponent bits, keeping the product only where the bit is set
+    pub fn pow(&self, base: &U256, exponent: &U256) -> U256 {
+        let mut result = U256::ONE;
+        for index in (0..256).rev() {
+            result = self.mul(&result, &result);
+            let multiplied = self.mul(&result, base);
+            result = U256::select(&result, &multiplied, exponent.bit(index));
+        }
+
+        result
+    }
+
+    // By Fermat's little theorem, a^(m - 2) is the inverse of a non-zero a
+    pub fn inv(&self, a: &U256) -> U256 {
+        self.pow(a, &self.m.wrapping_sub(&U256::from_u64(2)))
+    }
+}
+
+// Field multiplications done on this thread, which tests compare to check the work does not depend on secrets
+#[cfg(test)]
+thread_local! {
+    pub(crate) static MULTIPLICATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+// The field prime, 2^256 - 2^32 - 977
+pub const P: Modulus = Modulus::new(
+    U256::from_limbs([0xfffffffefffffc2f, 0xffffffffffffffff, 0xffffffffffffffff, 0xffffffffffffffff]),
+    U256::from_limbs([0x1000003d1, 0, 0, 0]),
+);
+
+// The order of the generator, and so the modulus for scalars
+pub const N: Modulus = Modulus::new(
+    U256::from_limbs([0xbfd25e8cd0364141, 0xbaaedce6af48a03b, 0xfffffffffffffffe, 0xffffffffffffffff]),
+    U256::from_limbs([0x402da1732fc9bebf, 0x4551231950b75fc4, 0x1, 0]),
+);
+
+const GX: U256 = U256::from_limbs([0x59f2815b16f81798, 0x029bfcdb2dce28d9, 0x55a06295ce870b07, 0x79be667ef9dcbbac]);
+const GY: U256 = U256::from_limbs([0x9c47d08ffb10d4b8, 0xfd17b448a6855419, 0x5da4fbfc0e1108a8, 0x483ada7726a3c465]);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PointError {
+    Hex(HexError),
+    InvalidSecLength(usize),
+    InvalidSecPrefix(u8),
+    // A coordinate that is not below the field prime
+    CoordinateOutOfRange,
+    NotOnCurve,
+}
+
+impl Display for PointError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PointError::Hex(error) => write!(f, "{}", error),
+            PointError::InvalidSecLength(length) => write!(f, "SEC public keys are 33 or 65 bytes, not {}.", length),
+            PointError::InvalidSecPrefix(prefix) => write!(f, "Invalid SEC prefix {:#04x}.", prefix),
+            PointError::CoordinateOutOfRange => write!(f, "Coordinate is not below the field prime."),
+            PointError::NotOnCurve => write!(f, "Point is not on secp256k1."),
+        }
+    }
+}
+
+impl std::error::Error for PointError {}
+
+impl From<HexError> for PointError {
+    fn from(error: HexError) -> Self {
+        PointError::Hex(error)
+    }
+}
+
+// The first item of a batch whose signature does not verify, by its index in the batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchError {
+    InvalidSignature(usize),
+}
+
+impl Display for BatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BatchError::InvalidSignature(index) => write!(f, "Signature {} of the batch is invalid.", index),
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+// Why verify_strict turned a signature down, in the order it checks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    // Not strict DER, as BIP66 requires
+    Der(DerError),
+    // r or s is zero, or was not below N, which DER parsing reads as zero
+    ZeroR,
+    ZeroS,
+    // s is above N / 2, which BIP62 makes non-standard since N - s verifies too
+    HighS,
+    // Well formed, but not a signature of z by this key
+    InvalidSignature,
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyError::Der(error) => write!(f, "{}", error),
+            VerifyError::ZeroR => write!(f, "Signature r is zero."),
+            VerifyError::ZeroS => write!(f, "Signature s is zero."),
+            VerifyError::HighS => write!(f, "Signature s is above half the group order."),
+            VerifyError::InvalidSignature => write!(f, "Signature does not verify."),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+impl From<DerError> for VerifyError {
+    fn from(error: DerError) -> Self {
+        VerifyError::Der(error)
+    }
+}
+
+// A point on secp256k1, y^2 = x^3 + 7 over the field of P, other than the point at infinity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct S256Point {
+    x: U256,
+    y: U256,
+}
+
+impl S256Point {
+    pub fn new(x: U256, y: U256) -> Result<Self, PointError> {
+        if x >= P.value() || y >= P.value() {
+            return Err(PointError::CoordinateOutOfRange);
+        }
+        if P.mul(&y, &y) != curve_rhs(&x) {
+            return Err(PointError::NotOnCurve);
+        }
+
+        Ok(Self { x, y })
+    }
+
+    pub fn generator() -> Self {
+        Self { x: GX, y: GY }
+    }
+
+    pub fn x(&self) -> U256 {
+        self.x
+    }
+
+    pub fn y(&self) -> U256 {
+        self.y
+    }
+
+    /*
+        SEC1 encoding:
+
+        uncompressed  0x04, then x and y as 32 bytes each
+        compressed    0x02 when y is even or 0x03 when it is odd, then x as 32 bytes
+     */
+    pub fn parse_sec(bytes: &[u8]) -> Result<Self, PointError> {
+        let coordinate = |bytes: &[u8]| U256::from_be_bytes(bytes.try_into().expect("Coordinates are 32 bytes."));
+
+        match (bytes.len(), bytes.first()) {
+            (65, Some(0x04)) => Self::new(coordinate(&bytes[1..33]), coordinate(&bytes[33..])),
+            (33, Some(prefix @ (0x02 | 0x03))) => Self::lift_x(coordinate(&bytes[1..]), *prefix == 0x03),
+            (33 | 65, Some(prefix)) => Err(PointError::InvalidSecPrefix(*prefix)),
+            (length, _) => Err(PointError::InvalidSecLength(length)),
+        }
+    }
+
+    // The point with this x and the y of the given parity, if x is on the curve
+    pub fn lift_x(x: U256, odd_y: bool) -> Result<Self, PointError> {
+        if x >= P.value() {
+            return Err(PointError::CoordinateOutOfRange);
+        }
+
+        // P = 3 (mod 4), so a square root of a is a^((P + 1) / 4)
+        let rhs = curve_rhs(&x);
+        let exponent =
+            U256::from_limbs([0xffffffffbfffff0c, 0xffffffffffffffff, 0xffffffffffffffff, 0x3fffffffffffffff]);
+        let y = P.pow(&rhs, &exponent);
+        if P.mul(&y, &y) != rhs {
+            return Err(PointError::NotOnCurve);
+        }
+
+        let y = if y.is_odd() == odd_y { y } else { P.neg(&y) };
+
+        Ok(Self { x, y })
+    }
+
+    pub fn sec(&self, compressed: bool) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(65);
+        match compressed {
+            true => bytes.push(if self.y.is_odd() { 0x03 } else { 0x02 }),
+            false => bytes.push(0x04),
+        }
+        bytes.extend(self.x.to_be_bytes());
+        if !compressed {
+            bytes.extend(self.y.to_be_bytes());
+        }
+
+        bytes
+    }
+
+    // The hex of a SEC encoding, with surrounding whitespace and a 0x prefix allowed
+    pub fn from_hex(s: &str) -> Result<Self, PointError> {
+        Self::parse_sec(&hex::decode(s.trim())?)
+    }
+
+    pub fn to_hex(&self, compressed: bool) -> String {
+        hex::encode(&self.sec(compressed))
+    }
+
+    // The P2PKH address of the SEC encoding: its hash160 under the network's version byte, in base58check
+    pub fn address(&self, compressed: bool, network: Network) -> String {
+        Address::new(AddressKind::P2pkh(hash160(&self.sec(compressed))), network).to_string()
+    }
+
+    // The BIP173 P2WPKH address, which always hashes the compressed encoding since segwit v0 takes no other
+    pub fn p2wpkh_address(&self, network: Network) -> String {
+        Address::new(AddressKind::P2wpkh(hash160(&self.sec(true))), network).to_string()
+    }
+
+    // The P2WPKH script that a BIP49 P2SH-P2WPKH output wraps, which the spending scriptSig pushes
+    pub fn p2sh_p2wpkh_redeem_script(&self) -> Script {
+        Script::p2wpkh(&hash160(&self.sec(true)))
+    }
+
+    // The BIP49 P2SH-P2WPKH address, the P2SH address of the redeem script above
+    pub fn p2sh_p2wpkh_address(&self, network: Network) -> String {
+        let redeem_script_hash = hash160(&self.p2sh_p2wpkh_redeem_script().to_bytes());
+
+        Address::new(AddressKind::P2sh(redeem_script_hash), network).to_string()
+    }
+
+    // None for the point at infinity, which is what adding a point to its negation gives
+    pub fn add(&self, other: &Self) -> Option<Self> {
+        JacobianPoint::from(self).add(&JacobianPoint::from(other)).to_affine()
+    }
+
+    // None when the scalar is a multiple of N
+    pub fn mul(&self, scalar: &U256) -> Option<Self> {
+        JacobianPoint::from(self).mul(scalar).to_affine()
+    }
+
+    // mul for a secret scalar, doing the same work whatever its bits
+    pub fn mul_ct(&self, scalar: &U256) -> Option<Self> {
+        JacobianPoint::from(self).mul_ct(scalar).to_affine()
+    }
+
+    /*
+        mul_ct with the generator as the point, which is what making a public key or a signing nonce needs. It
+        goes through a table of multiples of G that is built on first use and then shared by every thread, and
+        costs about a fifth of the ladder from then on.
+     */
+    pub fn mul_generator_ct(scalar: &U256) -> Option<Self> {
+        GeneratorTable::get().mul_ct(scalar).to_affine()
+    }
+
+    /*
+        ECDSA verification of a signature over the 32-byte message hash z, read as a big-endian integer: with
+        u = z / s and v = r / s modulo N, the signature is valid when the x coordinate of uG + vP is r modulo N.
+     */
+    pub fn verify(&self, z: &[u8; 32], signature: &Signature) -> bool {
+        let (r, s) = (signature.r(), signature.s());
+        if r.is_zero() || s.is_zero() {
+            return false;
+        }
+
+        let s_inv = s.inverse();
+        let u = Scalar::reduce(&U256::from_be_bytes(*z)) * s_inv;
+        let v = r * s_inv;
+
+        let total =
+            JacobianPoint::from(&Self::generator()).mul(&u.value()).add(&JacobianPoint::from(self).mul(&v.value()));
+        match total.to_affine() {
+            Some(point) => Scalar::from_x_coordinate(&point) == r,
+            None => false,
+        }
+    }
+
+    /*
+        The public key a recoverable signature over z verifies against: Q = (sR - zG) / r, where R is the point the
+        recovery id gives back from r. None when that R is not on the curve or r or s is zero.
+     */
+    pub fn recover(z: &[u8; 32], signature: &RecoverableSignature) -> Option<Self> {
+        let (r, s) = (signature.signature().r(), signature.signature().s());
+        if r.is_zero() || s.is_zero() {
+            return None;
+        }
+
+        let x = match signature.recovery_id() & 2 {
+            0 => r.value(),
+            _ => match r.value().overflowing_add(&N.value()) {
+                (x, false) => x,
+                (_, true) => return None,
+            },
+        };
+        let r_point = Self::lift_x(x, signature.recovery_id() & 1 == 1).ok()?;
+
+        let r_inv = r.inverse();
+        let u = -(Scalar::reduce(&U256::from_be_bytes(*z)) * r_inv);
+        let v = s * r_inv;
+
+        let u_g = JacobianPoint::from(&Self::generator()).mul(&u.value());
+
+        u_g.add(&JacobianPoint::from(&r_point).mul(&v.value())).to_affine()
+    }
+
+    /*
+        verify for a signature still in its DER encoding, without the hash type byte a script adds, holding it to
+        the encoding rules as well as the math: strict DER, non-zero r and s, and a low s.
+     */
+    pub fn verify_strict(&self, z: &[u8; 32], der: &[u8]) -> Result<(), VerifyError> {
+        let signature = Signature::parse_der(der)?;
+        if signature.r().is_zero() {
+            return Err(VerifyError::ZeroR);
+        }
+        if signature.s().is_zero() {
+            return Err(VerifyError::ZeroS);
+        }
+        if signature.s().is_high() {
+            return Err(VerifyError::HighS);
+        }
+
+        match self.verify(z, &signature) {
+            true => Ok(()),
+            false => Err(VerifyError::InvalidSignature),
+        }
+    }
+}
+
+impl FromStr for S256Point {
+    type Err = PointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(s)
+    }
+}
+
+/*
+    Verifies (public key, z, signature) items with the same outcome as calling verify on each, but with work shared
+    across the batch: every s is inverted by one field inversion through Montgomery's trick, uG goes through the
+    generator table, and the x of uG + vP is compared to r without converting the point to affine coordinates.
+
+    The usual random-weighted sum of all the equations, checked with one multi-scalar multiplication, does not
+    apply to ECDSA: a signature gives only the x of R, so which of the two points with that x goes into the sum is
+    not known without computing each uG + vP on its own. Each item's own check then says whether it failed, so the
+    first failure is reported without searching for it.
+ */
+pub fn verify_batch(items: &[(S256Point, U256, Signature)]) -> Result<(), BatchError> {
+    let s_inverses = batch_inverse(items.iter().map(|(_, _, signature)| signature.s()).collect());
+    let table = GeneratorTable::get();
+
+    for (index, ((point, z, signature), s_inv)) in items.iter().zip(s_inverses).enumerate() {
+        let r = signature.r();
+        let valid = !r.is_zero() && !signature.s().is_zero() && {
+            let u = Scalar::reduce(z) * s_inv;
+            let v = r * s_inv;
+            table.mul_ct(&u.value()).add(&JacobianPoint::from(point).mul(&v.value())).x_matches(&r)
+        };
+        if !valid {
+            return Err(BatchError::InvalidSignature(index));
+        }
+    }
+
+    Ok(())
+}
+
+/*
+    The inverses of all the scalars for one inversion and three multiplications each: invert the product of them
+    all, then peel the prefix products off one at a time. Zeros are left as they are, so they do not spoil the rest.
+ */
+fn batch_inverse(scalars: Vec<Scalar>) -> Vec<Scalar> {
+    let mut prefixes = Vec::with_capacity(scalars.len());
+    let mut product = Scalar::ONE;
+    for scalar in &scalars {
+        prefixes.push(product);
+        if !scalar.is_zero() {
+            product = product * *scalar;
+        }
+    }
+
+    let mut inverse = product.inverse();
+    let mut inverses = vec![Scalar::ZERO; scalars.len()];
+    for (index, scalar) in scalars.iter().enumerate().rev() {
+        if !scalar.is_zero() {
+            inverses[index] = inverse * prefixes[index];
+            inverse = inverse * *scalar;
+        }
+    }
+
+    inverses
+}
+
+// x^3 + 7
+fn curve_rhs(x: &U256) -> U256 {
+    P.add(&P.mul(&P.mul(x, x), x), &U256::from_u64(7))
+}
+
+// (X, Y, Z) stands for the affine point (X / Z^2, Y / Z^3), which lets additions skip the field inversion
+#[derive(Debug, Clone, Copy)]
+struct JacobianPoint {
+    x: U256,
+    y: U256,
+    z: U256,
+}
+
+impl JacobianPoint {
+    const INFINITY: Self = Self { x: U256::ONE, y: U256::ONE, z: U256::ZERO };
+
+    fn is_infinity(&self) -> bool {
+        self.z.is_zero()
+    }
+
+    /*
+        Whether the affine x of this point, reduced modulo N, is r, checked as X = x * Z^2 so that no inversion is
+        needed. An x from N up to P reduces to an r below P - N, which is the second candidate.
+     */
+    fn x_matches(&self, r: &Scalar) -> bool {
+        if self.is_infinity() {
+            return false;
+        }
+
+        let z_squared = P.mul(&self.z, &self.z);
+        let (r_plus_n, overflow) = r.value().overflowing_add(&N.value());
+
+        if P.mul(&r.value(), &z_squared) == self.x {
+            return true;
+        }
+
+        !overflow && r_plus_n < P.value() && P.mul(&r_plus_n, &z_squared) == self.x
+    }
+
+    fn to_affine(self) -> Option<S256Point> {
+        if self.is_infinity() {
+            return None;
+        }
+
+        let z_inv = P.inv(&self.z);
+        let z_inv_squared = P.mul(&z_inv, &z_inv);
+
+        Some(S256Point {
+            x: P.mul(&self.x, &z_inv_squared),
+            y: P.mul(&self.y, &P.mul(&z_inv_squared, &z_inv)),
+        })
+    }
+
+    // The point at infinity doubles to a Z of zero again, and secp256k1 has no point with Y zero to double
+    fn double(&self) -> Self {
+        let y_squared = P.mul(&self.y, &self.y);
+        let s = P.mul(&U256::from_u64(4), &P.mul(&self.x, &y_squared));
+        let m = P.mul(&U256::from_u64(3), &P.mul(&self.x, &self.x));
+        let x = P.sub(&P.mul(&m, &m), &P.add(&s, &s));
+        let y = P.sub(&P.mul(&m, &P.sub(&s, &x)), &P.mul(&U256::from_u64(8), &P.mul(&y_squared, &y_squared)));
+        let z = P.mul(&U256::from_u64(2), &P.mul(&self.y, &self.z));
+
+        Self { x, y, z }
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        if self.is_infinity() {
+            return *other;
+        }
+        if other.is_infinity() {
+            return *self;
+        }
+
+        let (z1_squared, z2_squared) = (P.mul(&self.z, &self.z), P.mul(&other.z, &other.z));
+        let u1 = P.mul(&self.x, &z2_squared);
+        let u2 = P.mul(&other.x, &z1_squared);
+        let s1 = P.mul(&self.y, &P.mul(&z2_squared, &other.z));
+        let s2 = P.mul(&other.y, &P.mul(&z1_squared, &self.z));
+
+        let h = P.sub(&u2, &u1);
+        let r = P.sub(&s2, &s1);
+        if h.is_zero() {
+            return match r.is_zero() {
+                true => self.double(),
+                false => Self::INFINITY,
+            };
+        }
+
+        let h_squared = P.mul(&h, &h);
+        let h_cubed = P.mul(&h_squared, &h);
+        let u1_h_squared = P.mul(&u1, &h_squared);
+        let x = P.sub(&P.sub(&P.mul(&r, &r), &h_cubed), &P.add(&u1_h_squared, &u1_h_squared));
+        let y = P.sub(&P.mul(&r, &P.sub(&u1_h_squared, &x)), &P.mul(&s1, &h_cubed));
+        let z = P.mul(&h, &P.mul(&self.z, &other.z));
+
+        Self { x, y, z }
+    }
+
+    /*
+        add without the early returns: the general formula, which already gives a Z of zero for a point and its
+        negation, with the doubling and either side being infinity picked in afterwards by selection.
+     */
+    fn add_ct(&self, other: &Self) -> Self {
+        let (z1_squared, z2_squared) = (P.mul(&self.z, &self.z), P.mul(&other.z, &other.z));
+        let u1 = P.mul(&self.x, &z2_squared);
+        let u2 = P.mul(&other.x, &z1_squared);
+        let s1 = P.mul(&self.y, &P.mul(&z2_squared, &other.z));
+        let s2 = P.mul(&other.y, &P.mul(&z1_squared, &self.z));
+        let h = P.sub(&u2, &u1);
+        let r = P.sub(&s2, &s1);
+
+        let h_squared = P.mul(&h, &h);
+        let h_cubed = P.mul(&h_squared, &h);
+        let u1_h_squared = P.mul(&u1, &h_squared);
+        let x = P.sub(&P.sub(&P.mul(&r, &r), &h_cubed), &P.add(&u1_h_squared, &u1_h_squared));
+        let y = P.sub(&P.mul(&r, &P.sub(&u1_h_squared, &x)), &P.mul(&s1, &h_cubed));
+        let z = P.mul(&h, &P.mul(&self.z, &other.z));
+
+        let sum = Self { x, y, z };
+        let sum = Self::select(&sum, &self.double(), h.is_zero() & r.is_zero());
+        let sum = Self::select(&sum, self, other.is_infinity());
+        Self::select(&sum, other, self.is_infinity())
+    }
+
+    // Double and add, which only ever sees public scalars in verification
+    fn mul(&self, scalar: &U256) -> Self {
+        let mut result = Self::INFINITY;
+        for index in (0..scalar.bits()).rev() {
+            result = result.double();
+            if scalar.bit(index) {
+                result = result.add(self);
+            }
+        }
+
+        result
+    }
+
+    /*
+        A Montgomery ladder over a fixed 257 bits. The scalar is replaced by k + N or k + 2N, whichever has bit 256
+        set, which names the same point and lets the ladder start from P and 2P instead of the point at infinity.
+        Each step is one addition and one doubling, with the bit only choosing which of R0 and R1 they land in.
+     */
+    fn mul_ct(&self, scalar: &U256) -> Self {
+        let scalar = N.reduce(scalar);
+        let (once, carry) = scalar.overflowing_add(&N.value());
+        let twice = once.wrapping_add(&N.value());
+        let scalar = U256::select(&twice, &once, carry);
+
+        let (mut r0, mut r1) = (*self, self.double());
+        for index in (0..256).rev() {
+            let bit = scalar.bit(index);
+            Self::swap(&mut r0, &mut r1, bit);
+            r1 = r0.add_ct(&r1);
+            r0 = r0.double();
+            Self::swap(&mut r0, &mut r1, bit);
+        }
+
+        r0
+    }
+
+    fn select(a: &Self, b: &Self, choice: bool) -> Self {
+        Self {
+            x: U256::select(&a.x, &b.x, choice),
+            y: U256::select(&a.y, &b.y, choice),
+            z: U256::select(&a.z, &b.z, choice),
+        }
+    }
+
+    fn swap(a: &mut Self, b: &mut Self, choice: bool) {
+        (*a, *b) = (Self::select(a, b, choice), Self::select(b, a, choice));
+    }
+}
+
+/*
+    Row w holds j * 16^w * G for j from 1 to 15, so kG is the sum of one entry per row, picked by the w-th nibble of
+    k. Every entry of a row is read for each lookup and the wanted one kept by selection, with the point at infinity
+    standing in for a zero nibble, so neither the rows' memory nor the work shows which entry was used.
+ */
+const GENERATOR_TABLE_ROWS: usize = 64;
+
+struct GeneratorTable {
+    rows: Vec<[JacobianPoint; 15]>,
+}
+
+impl GeneratorTable {
+    fn new() -> Self {
+        let mut rows = Vec::with_capacity(GENERATOR_TABLE_ROWS);
+        let mut base = JacobianPoint::from(&S256Point::generator());
+        for _ in 0..GENERATOR_TABLE_ROWS {
+            let mut row = [base; 15];
+            for j in 1..row.len() {
+                row[j] = row[j - 1].add(&base);
+            }
+            base = row[14].add(&base);
+            rows.push(row);
+        }
+
+        Self { rows }
+    }
+
+    fn get() -> &'static Self {
+        static TABLE: OnceLock<GeneratorTable> = OnceLock::new();
+        TABLE.get_or_init(Self::new)
+    }
+
+    fn mul_ct(&self, scalar: &U256) -> JacobianPoint {
+        let scalar = N.reduce(scalar);
+
+        let mut result = JacobianPoint::INFINITY;
+        for (window, row) in self.rows.iter().enumerate() {
+            let nibble = (0..4).fold(0, |nibble, bit| nibble | (scalar.bit(4 * window + bit) as usize) << bit);
+            let mut entry = JacobianPoint::INFINITY;
+            for (index, point) in row.iter().enumerate() {
+                entry = JacobianPoint::select(&entry, point, nibble == index + 1);
+            }
+            result = result.add_ct(&entry);
+        }
+
+        result
+    }
+}
+
+impl From<&S256Point> for JacobianPoint {
+    fn from(point: &S256Point) -> Self {
+        Self { x: point.x, y: point.y, z: U256::ONE }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use crate::ecc::message::signed_message_hash;
+    use crate::ecc::private_key::PrivateKey;
+    use crate::ecc::scalar::Scalar;
+    use crate::ecc::secp256k1::{
+        verify_batch, BatchError, GeneratorTable, PointError, S256Point, VerifyError, MULTIPLICATIONS, N, P,
+    };
+    use crate::ecc::signature::{DerError, RecoverableSignature, Signature};
+    use crate::ecc::u256::U256;
+    use crate::encoding::base64;
+    use crate::encoding::hex;
+    use crate::encoding::hex::HexError;
+    use crate::hashing::hash160::hash160;
+    use crate::network::Network;
+    use crate::rng::{CryptoRng, SeededRng};
+
+    fn u256(hex_str: &str) -> U256 {
+        U256::from_be_slice(&hex::decode(hex_str).unwrap()).unwrap()
+    }
+
+    fn scalar(hex_str: &str) -> Scalar {
+        Scalar::new(u256(hex_str)).unwrap()
+    }
+
+    #[test]
+    fn modular_arithmetic_wraps_around_the_prime() {
+        let minus_one = P.value().wrapping_sub(&U256::ONE);
+
+        assert_eq!(P.add(&minus_one, &U256::from_u64(2)), U256::ONE);
+        assert_eq!(P.sub(&U256::ONE, &U256::from_u64(2)), minus_one);
+        assert_eq!(P.mul(&minus_one, &minus_one), U256::ONE);
+        assert_eq!(N.mul(&N.inv(&U256::from_u64(7)), &U256::from_u64(7)), U256::ONE);
+    }
+
+    #[test]
+    fn generator_has_order_n() {
+        let generator = S256Point::generator();
+
+        assert_eq!(S256Point::new(generator.x(), generator.y()), Ok(generator));
+        assert_eq!(generator.mul(&N.value()), None);
+        assert_eq!(generator.mul(&N.value().wrapping_sub(&U256::ONE)).map(|point| point.x()), Some(generator.x()));
+    }
+
+    #[test]
+    fn public_keys_of_known_secrets() {
+        // Programming Bitcoin chapter 4 exercise 1
+        let vectors = [
+            (
+                U256::from_u64(5000),
+                "04ffe558e388852f0120e46af2d1b370f85854a8eb0841811ece0e3e03d282d57c315dc72890a4f10a1481c031b03b351b0dc7\
+                 9901ca18a00cf009dbdb157a1d10",
+            ),
+            (
+                U256::from_u64(2018u64.pow(5)),
+                "04027f3da1918455e03c46f659266a1bb5204e959db7364d2f473bdf8f0a13cc9dff87647fd023c13b4a4994f17691895806e1\
+                 b40b57f4fd22581a4f46851f3b06",
+            ),
+            (
+                U256::from_u64(0xdeadbeef12345),
+                "04d90cd625ee87dd38656dd95cf79f65f60f7273b67d3096e68bd81e4f5342691f842efa762fd59961d0e99803c61edba8b3e3\
+                 f7dc3a341836f97733aebf987121",
+            ),
+        ];
+
+        for (secret, sec) in vectors {
+            let point = S256Point::generator().mul(&secret).unwrap();
+
+            assert_eq!(hex::encode(&point.sec(false)), sec, "secret {}", secret);
+        }
+    }
+
+    #[test]
+    fn sec_encodings_round_trip() {
+        // Programming Bitcoin chapter 4 exercise 2, the public key of 5001
+        let point = S256Point::generator().mul(&U256::from_u64(5001)).unwrap();
+
+        let compressed = point.sec(true);
+        let uncompressed = point.sec(false);
+
+        assert_eq!(&hex::encode(&compressed), "0357a4f368868a8a6d572991e484e664810ff14c05c0fa023275251151fe0e53d1");
+        assert_eq!(uncompressed.len(), 65);
+        assert_eq!(S256Point::parse_sec(&compressed), Ok(point));
+        assert_eq!(S256Point::parse_sec(&uncompressed), Ok(point));
+    }
+
+    #[test]
+    fn p2pkh_addresses_match_the_book() {
+        // Programming Bitcoin chapter 4 exercise 5
+        let cases = [
+            (U256::from_u64(5002), false, Network::Testnet, "mmTPbXQFxboEtNRkwfh6K51jvdtHLxGeMA"),
+            (U256::from_u64(2020u64.pow(5)), true, Network::Testnet, "mopVkxp8UhXqRYbCYJsbeE1h1fiF64jcoH"),
+            (U256::from_u64(0x12345deadbeef), true, Network::Mainnet, "1F1Pn2y6pDb68E5nYJJeba4TLg2U7B6KF1"),
+        ];
+
+        for (secret, compressed, network, address) in cases {
+            let point = S256Point::generator().mul(&secret).unwrap();
+
+            assert_eq!(point.address(compressed, network), address);
+        }
+    }
+
+    #[test]
+    fn p2wpkh_addresses_match_bip173() {
+        // BIP173's example key, which is the generator
+        let cases = [
+            (Network::Mainnet, "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"),
+            (Network::Testnet, "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"),
+            (Network::Signet, "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"),
+            (Network::Regtest, "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080"),
+        ];
+
+        for (network, address) in cases {
+            assert_eq!(S256Point::generator().p2wpkh_address(network), address);
+        }
+    }
+
+    #[test]
+    fn p2sh_p2wpkh_address_matches_bip49() {
+        // BIP49's test vector, the first receive key of its testnet account
+        let private_key =
+            PrivateKey::from_hex("c9bdb49cfbaedca21c4b1f3a7803c34636b1d7dc55a717132443fc3f4c5867e8").unwrap();
+        let point = private_key.point();
+        assert_eq!(point.to_hex(true), "03a1af804ac108a8a51782198c2d034b28bf90c8803f5a53f76276fa69a4eae77f");
+
+        let redeem_script = point.p2sh_p2wpkh_redeem_script().to_bytes();
+
+        assert_eq!(hex::encode(&redeem_script), "001438971f73930f6c141d977ac4fd4a727c854935b3");
+        assert_eq!(hex::encode(&hash160(&redeem_script)), "336caa13e08b96080a32b5d818d59b4ab3b36742");
+        assert_eq!(point.p2sh_p2wpkh_address(Network::Testnet), "2Mww8dCYPUpKHofjgcXcBCEGmniw9CoaiD2");
+    }
+
+    #[test]
+    fn malformed_sec_encodings_are_rejected() {
+        let mut off_curve = S256Point::generator().sec(false);
+        off_curve[64] ^= 1;
+        let mut bad_prefix = S256Point::generator().sec(true);
+        bad_prefix[0] = 0x05;
+
+        assert_eq!(S256Point::parse_sec(&off_curve), Err(PointError::NotOnCurve));
+        assert_eq!(S256Point::parse_sec(&bad_prefix), Err(PointError::InvalidSecPrefix(0x05)));
+        assert_eq!(S256Point::parse_sec(&[0x02; 32]), Err(PointError::InvalidSecLength(32)));
+    }
+
+    #[test]
+    fn hex_public_keys_parse_and_round_trip() {
+        let generator = S256Point::generator();
+        let compressed = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+        let uncompressed = "0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798483ada7726a3c4655da4fbfc0e110\
+                            8a8fd17b448a68554199c47d08ffb10d4b8";
+
+        assert_eq!(S256Point::from_hex(compressed), Ok(generator));
+        assert_eq!(S256Point::from_hex(uncompressed), Ok(generator));
+        assert_eq!(S256Point::from_str(&format!(" 0x{}\n", compressed.to_uppercase())), Ok(generator));
+        assert_eq!(generator.to_hex(true), compressed);
+        assert_eq!(generator.to_hex(false), uncompressed);
+    }
+
+    #[test]
+    fn hex_without_a_sec_prefix_is_rejected() {
+        // The bare x coordinate leaves the parity of y unknown
+        let bare_x = "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+        assert_eq!(S256Point::from_hex(bare_x), Err(PointError::InvalidSecLength(32)));
+        assert_eq!(
+            S256Point::from_hex("02 79"),
+            Err(PointError::Hex(HexError::InvalidCharacter { character: ' ', position: 2 }))
+        );
+    }
+
+    #[test]
+    fn signatures_verify_against_their_message() {
+        // Programming Bitcoin chapter 3, a signature by the public key below
+        let point = S256Point::new(
+            u256("887387e452b8eacc4acfde10d9aaf7f6d9a0f975aabb10d006e4da568744d06c"),
+            u256("61de6d95231cd89026e286df3b6ae4a894a3378e393e93a0f45b666329a0ae34"),
+        )
+        .unwrap();
+        let z = hex::decode("ec208baa0fc1c19f708a9ca96fdeff3ac3f230bb4a7ba4aede4942ad003c0f60").unwrap();
+        let signature = Signature::new(
+            scalar("ac8d1c87e51d0d441be8b3dd5b05c8795b48875dffe00b7ffcfac23010d3a395"),
+            scalar("068342ceff8935ededd102dd876ffd6ba72d6a427a3edb13d26eb0781cb423c4"),
+        );
+
+        let z: [u8; 32] = z.try_into().unwrap();
+        let mut other_z = z;
+        other_z[31] ^= 1;
+
+        assert!(point.verify(&z, &signature));
+        assert!(!point.verify(&other_z, &signature));
+        assert!(!S256Point::generator().verify(&z, &signature));
+    }
+
+    #[test]
+    fn strict_verification_names_each_failure() {
+        // The Programming Bitcoin chapter 3 signature again
+        let point = S256Point::new(
+            u256("887387e452b8eacc4acfde10d9aaf7f6d9a0f975aabb10d006e4da568744d06c"),
+            u256("61de6d95231cd89026e286df3b6ae4a894a3378e393e93a0f45b666329a0ae34"),
+        )
+        .unwrap();
+        let z: [u8; 32] = hex::decode("ec208baa0fc1c19f708a9ca96fdeff3ac3f230bb4a7ba4aede4942ad003c0f60")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let signature = Signature::new(
+            scalar("ac8d1c87e51d0d441be8b3dd5b05c8795b48875dffe00b7ffcfac23010d3a395"),
+            scalar("068342ceff8935ededd102dd876ffd6ba72d6a427a3edb13d26eb0781cb423c4"),
+        );
+        let high_s = Signature::new(signature.r(), -signature.s());
+        let mut other_z = z;
+        other_z[0] ^= 1;
+
+        assert_eq!(point.verify_strict(&z, &signature.der()), Ok(()));
+        assert!(point.verify(&z, &high_s));
+        assert_eq!(point.verify_strict(&z, &high_s.der()), Err(VerifyError::HighS));
+        assert_eq!(point.verify_strict(&other_z, &signature.der()), Err(VerifyError::InvalidSignature));
+        assert_eq!(
+            point.verify_strict(&z, &[0x30, 7, 2, 2, 0, 1, 2, 1, 1]),
+            Err(VerifyError::Der(DerError::InvalidInteger { position: 2 }))
+        );
+        assert_eq!(point.verify_strict(&z, &[0x30, 6, 2, 1, 0, 2, 1, 1]), Err(VerifyError::ZeroR));
+        assert_eq!(point.verify_strict(&z, &[0x30, 6, 2, 1, 1, 2, 1, 0]), Err(VerifyError::ZeroS));
+    }
+
+    #[test]
+    fn recovery_finds_the_signing_key() {
+        for secret in [1, 2, 3, 0xdeadbeef, 0x12345deadbeef] {
+            let private_key = PrivateKey::new(U256::from_u64(secret)).unwrap();
+            for z in [[0; 32], [0x5a; 32], [0xff; 32]] {
+                let signature = private_key.sign_recoverable(&z);
+
+                assert_eq!(S256Point::recover(&z, &signature), Some(private_key.point()), "secret {}", secret);
+                assert!(private_key.point().verify(&z, &signature.signature()));
+            }
+        }
+    }
+
+    #[test]
+    fn recovery_with_another_id_gives_another_key() {
+        let private_key = PrivateKey::new(U256::from_u64(0xdeadbeef)).unwrap();
+        let z = [0x5a; 32];
+        let signature = private_key.sign_recoverable(&z);
+
+        let flipped = RecoverableSignature::new(signature.signature(), signature.recovery_id() ^ 1).unwrap();
+        // An r far below P - N, so no R has an x of r + N
+        let overflowed = RecoverableSignature::new(signature.signature(), signature.recovery_id() | 2).unwrap();
+
+        assert_ne!(S256Point::recover(&z, &flipped), Some(private_key.point()));
+        assert_eq!(S256Point::recover(&z, &overflowed), None);
+    }
+
+    #[test]
+    fn bitcoin_core_signmessage_signature_recovers() {
+        // Bitcoin Core's rpc_signmessagewithprivkey.py, whose key is the WIF
+        // cUeKHd5orzT3mz8P9pxyREHfsWtVfgsfDjiZZBcjUBAaGk1BTj7N
+        let private_key =
+            PrivateKey::from_hex("d2b8a0116d641fe7d3036f8464628fb595b480414c13a301b3d4038c811c28b0").unwrap();
+        let signature = base64::decode(
+            "INbVnW4e6PeRmsv2Qgu8NuopvrVjkcxob+sX8OcZG0SALhWybUjzMLPdAsXI46YZGb0KQTRii+wWIQzRpG/U+S0=",
+        )
+        .unwrap();
+        let z = signed_message_hash("This is just a test message");
+
+        let (recoverable, compressed) = RecoverableSignature::parse(&signature.try_into().unwrap()).unwrap();
+        let recovered = S256Point::recover(&z, &recoverable).unwrap();
+
+        assert!(compressed);
+        assert_eq!(recovered.address(compressed, Network::Testnet), "mpLQjfK79b7CCV4VMJWEWAj5Mpx8Up5zxB");
+        // RFC 6979 nonces make our signature the same bytes as Core's
+        assert_eq!(private_key.sign_recoverable(&z), recoverable);
+    }
+
+    #[test]
+    fn generator_table_matches_double_and_add() {
+        let generator = S256Point::generator();
+        let mut scalars: Vec<U256> = [1, 2, 15, 16, 17, 255, 256, 0xdeadbeef12345].map(U256::from_u64).to_vec();
+        scalars.extend([
+            N.value().wrapping_sub(&U256::ONE),
+            N.value().wrapping_add(&U256::from_u64(3)),
+            U256::from_limbs([0, 0, 0, 1 << 63]),
+            U256::from_limbs([u64::MAX; 4]),
+        ]);
+
+        for scalar in scalars {
+            assert_eq!(S256Point::mul_generator_ct(&scalar), generator.mul(&scalar), "scalar {}", scalar);
+        }
+        assert_eq!(S256Point::mul_generator_ct(&U256::ZERO), None);
+        assert_eq!(S256Point::mul_generator_ct(&N.value()), None);
+    }
+
+    #[test]
+    fn generator_table_costs_once_and_then_saves_work_on_every_call() {
+        // Counted in field multiplications, which is where the time goes, rather than timed
+        fn multiplications<T>(work: impl FnOnce() -> T) -> usize {
+            MULTIPLICATIONS.with(|count| count.set(0));
+            work();
+            MULTIPLICATIONS.with(|count| count.get())
+        }
+        let scalar = U256::from_u64(0xdeadbeef12345);
+        GeneratorTable::get();
+
+        let build = multiplications(GeneratorTable::new);
+        let ladder = multiplications(|| S256Point::generator().mul_ct(&scalar));
+        let table = multiplications(|| S256Point::mul_generator_ct(&scalar));
+
+        assert!(table * 4 < ladder, "table {}, ladder {}", table, ladder);
+        // Building is repaid by the second call
+        assert!(build < 2 * (ladder - table), "build {}, saved per call {}", build, ladder - table);
+    }
+
+    // (public key, z, signature) items signed by a handful of keys, with about one in fifty spoiled in one of three
+    // ways; the second value lists the spoiled indices
+    fn batch(count: usize, seed: u64) -> (Vec<(S256Point, U256, Signature)>, Vec<usize>) {
+        let mut rng = SeededRng::from_u64(seed);
+        let keys: Vec<PrivateKey> =
+            (1..=5).map(|secret| PrivateKey::new(U256::from_u64(secret * 7919)).unwrap()).collect();
+        let mut items = Vec::with_capacity(count);
+        let mut spoiled = vec![];
+
+        for index in 0..count {
+            let mut bytes = [0; 34];
+            rng.fill_bytes(&mut bytes);
+            let z: [u8; 32] = bytes[..32].try_into().unwrap();
+            let key = &keys[bytes[32] as usize % keys.len()];
+            let signature = key.sign(&z);
+            let other_s = Signature::new(signature.r(), -signature.s() + Scalar::ONE);
+            let other_key = keys[(bytes[32] as usize + 1) % keys.len()].point();
+
+            let item = match bytes[33] {
+                0..=1 => (key.point(), U256::from_be_bytes(z).wrapping_add(&U256::ONE), signature),
+                2..=3 => (key.point(), U256::from_be_bytes(z), other_s),
+                4..=5 => (other_key, U256::from_be_bytes(z), signature),
+                _ => (key.point(), U256::from_be_bytes(z), signature),
+            };
+            if bytes[33] <= 5 {
+                spoiled.push(index);
+            }
+            items.push(item);
+        }
+
+        (items, spoiled)
+    }
+
+    fn verify_one((point, z, signature): &(S256Point, U256, Signature)) -> bool {
+        point.verify(&z.to_be_bytes(), signature)
+    }
+
+    #[test]
+    fn batches_agree_with_verifying_one_by_one() {
+        let (items, spoiled) = batch(1000, 109);
+        assert!(spoiled.len() > 10, "{:?}", spoiled);
+
+        // Each failure is found in turn by verifying the rest of the batch after the last one
+        let mut failures = vec![];
+        let mut start = 0;
+        while let Err(BatchError::InvalidSignature(index)) = verify_batch(&items[start..]) {
+            failures.push(start + index);
+            start += index + 1;
+        }
+
+        let sequential: Vec<usize> = (0..items.len()).filter(|&index| !verify_one(&items[index])).collect();
+        assert_eq!(failures, sequential);
+        assert_eq!(failures, spoiled);
+    }
+
+    #[test]
+    fn valid_batches_pass_and_one_bad_signature_is_named() {
+        let (items, _) = batch(1000, 110);
+        let valid: Vec<_> = items.into_iter().filter(verify_one).collect();
+        assert!(valid.len() > 950);
+
+        assert_eq!(verify_batch(&valid), Ok(()));
+        assert_eq!(verify_batch(&[]), Ok(()));
+
+        let mut corrupted = valid.clone();
+        let (point, z, signature) = corrupted[617];
+        corrupted[617] = (point, z, Signature::new(signature.s(), signature.r()));
+        assert_eq!(verify_batch(&corrupted), Err(BatchError::InvalidSignature(617)));
+
+        let mut zero_s = valid[..3].to_vec();
+        zero_s[1].2 = Signature::new(zero_s[1].2.r(), Scalar::ZERO);
+        assert_eq!(verify_batch(&zero_s), Err(BatchError::InvalidSignature(1)));
+    }
+
+    #[test]
+    fn batches_do_less_work_per_signature() {
+        fn multiplications<T>(work: impl FnOnce() -> T) -> usize {
+            MULTIPLICATIONS.with(|count| count.set(0));
+            work();
+            MULTIPLICATIONS.with(|count| count.get())
+        }
+        let (items, _) = batch(20, 111);
+        let valid: Vec<_> = items.into_iter().filter(verify_one).collect();
+        GeneratorTable::get();
+
+        let sequential = multiplications(|| valid.iter().all(verify_one));
+        let batched = multiplications(|| verify_batch(&valid));
+
+        assert!(batched * 3 < sequential * 2, "batched {}, sequential {}", batched, sequential);
+    }
+}
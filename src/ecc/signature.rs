@@ -0,0 +1,490 @@
+use std::fmt::{Display, Formatter, LowerHex};
+use std::str::FromStr;
+use crate::ecc::scalar::Scalar;
+use crate::ecc::u256::U256;
+use crate::encoding::hex;
+use crate::encoding::hex::HexError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DerError {
+    // The outer or an integer length runs past the end, or bytes are left over after the signature
+    InvalidLength,
+    // A byte that should have been 0x30 (sequence) or 0x02 (integer)
+    UnexpectedTag { position: usize, tag: u8 },
+    // Negative, empty, padded with a needless zero byte, or wider than 256 bits
+    InvalidInteger { position: usize },
+}
+
+impl Display for DerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DerError::InvalidLength => write!(f, "DER signature lengths are inconsistent."),
+            DerError::UnexpectedTag { position, tag } => {
+                write!(f, "Unexpected DER tag {:#04x} at position {}.", tag, position)
+            }
+            DerError::InvalidInteger { position } => write!(f, "Invalid DER integer at position {}.", position),
+        }
+    }
+}
+
+impl std::error::Error for DerError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureError {
+    Hex(HexError),
+    Der(DerError),
+    // A compact r or s that is not below N
+    OutOfRange,
+    // A recoverable signature's first byte, which has to be from 27 to 34
+    InvalidRecoveryHeader(u8),
+}
+
+impl Display for SignatureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureError::Hex(error) => write!(f, "{}", error),
+            SignatureError::Der(error) => write!(f, "{}", error),
+            SignatureError::OutOfRange => write!(f, "Compact signature r or s is not below the group order."),
+            SignatureError::InvalidRecoveryHeader(header) => {
+                write!(f, "Recoverable signature header {} is outside 27 to 34.", header)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+impl From<HexError> for SignatureError {
+    fn from(error: HexError) -> Self {
+        SignatureError::Hex(error)
+    }
+}
+
+impl From<DerError> for SignatureError {
+    fn from(error: DerError) -> Self {
+        SignatureError::Der(error)
+    }
+}
+
+// An ECDSA signature over secp256k1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Signature {
+    r: Scalar,
+    s: Scalar,
+}
+
+impl Signature {
+    pub fn new(r: Scalar, s: Scalar) -> Self {
+        Self { r, s }
+    }
+
+    pub fn r(&self) -> Scalar {
+        self.r
+    }
+
+    pub fn s(&self) -> Scalar {
+        self.s
+    }
+
+    /*
+        Strict DER, as BIP66 requires for anything in a script:
+
+        0x30 [total length] 0x02 [length of r] [r] 0x02 [length of s] [s]
+
+        Each integer is big-endian with no needless leading zero, and starts with 0x00 when its top bit is set so
+        it does not read as negative. The sighash type byte that follows in a script is not part of the DER.
+
+        An r or s that is not below N is still strict DER. Like libsecp256k1, it is read as zero, which no
+        signature verifies with.
+     */
+    pub fn parse_der(bytes: &[u8]) -> Result<Self, DerError> {
+        match bytes.first() {
+            Some(0x30) => {}
+            Some(tag) => return Err(DerError::UnexpectedTag { position: 0, tag: *tag }),
+            None => return Err(DerError::InvalidLength),
+        }
+        if bytes.len() < 2 || bytes[1] as usize != bytes.len() - 2 {
+            return Err(DerError::InvalidLength);
+        }
+
+        let (r, position) = parse_integer(bytes, 2)?;
+        let (s, position) = parse_integer(bytes, position)?;
+        if position != bytes.len() {
+            return Err(DerError::InvalidLength);
+        }
+
+        Ok(Self { r: Scalar::new(r).unwrap_or(Scalar::ZERO), s: Scalar::new(s).unwrap_or(Scalar::ZERO) })
+    }
+
+    /*
+        What consensus accepted before BIP66, following Bitcoin Core's lax parser: lengths may take the long form,
+        integers may be padded with zeros or have their top bit set, the sequence length is not checked and bytes
+        after s are ignored. None when the structure cannot be read at all. An r or s that is not below N is read
+        as zero, which no signature verifies with, rather than as a failure to parse.
+     */
+    pub fn parse_der_lax(bytes: &[u8]) -> Option<Self> {
+        let mut rest = bytes;
+        if take_byte(&mut rest)? != 0x30 {
+            return None;
+        }
+        let sequence_length = take_byte(&mut rest)?;
+        if sequence_length & 0x80 != 0 {
+            take(&mut rest, (sequence_length & 0x7f) as usize)?;
+        }
+
+        let mut integers = [Scalar::ZERO; 2];
+        let mut overflow = false;
+        for integer in &mut integers {
+            if take_byte(&mut rest)? != 0x02 {
+                return None;
+            }
+            let length_byte = take_byte(&mut rest)?;
+            let length = match length_byte & 0x80 {
+                0 => length_byte as usize,
+                _ => take(&mut rest, (length_byte & 0x7f) as usize)?
+                    .iter()
+                    .try_fold(0usize, |length, byte| length.checked_mul(256)?.checked_add(*byte as usize))?,
+            };
+
+            let value = take(&mut rest, length)?;
+            let first_nonzero = value.iter().position(|byte| *byte != 0).unwrap_or(value.len());
+            match U256::from_be_slice(&value[first_nonzero..]).and_then(Scalar::new) {
+                Some(value) => *integer = value,
+                None => overflow = true,
+            }
+        }
+
+        match overflow {
+            true => Some(Self { r: Scalar::ZERO, s: Scalar::ZERO }),
+            false => Some(Self { r: integers[0], s: integers[1] }),
+        }
+    }
+
+    pub fn der(&self) -> Vec<u8> {
+        let r = encode_integer(&self.r.value());
+        let s = encode_integer(&self.s.value());
+
+        let mut bytes = vec![0x30, (r.len() + s.len()) as u8];
+        bytes.extend(r);
+        bytes.extend(s);
+
+        bytes
+    }
+
+    // r and then s, 32 big-endian bytes each
+    pub fn compact(&self) -> [u8; 64] {
+        let mut bytes = [0; 64];
+        bytes[..32].copy_from_slice(&self.r.to_be_bytes());
+        bytes[32..].copy_from_slice(&self.s.to_be_bytes());
+
+        bytes
+    }
+}
+
+// The hex of the DER encoding
+impl Display for Signature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(&self.der()))
+    }
+}
+
+impl LowerHex for Signature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/*
+    DER hex, or the 128 hex digits of the compact encoding. Both can be 64 bytes long: 64 bytes that start with
+    the 0x30 sequence tag and parse as strict DER are read as DER, and any other 64 bytes as compact.
+ */
+impl FromStr for Signature {
+    type Err = SignatureError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(s)?;
+        let der = Self::parse_der(&bytes);
+        if der.is_err() && bytes.len() == 64 {
+            let integer = |bytes: &[u8]| Scalar::from_be_bytes(bytes.try_into().expect("Compact halves are 32 bytes."));
+            let (r, s) = (integer(&bytes[..32]), integer(&bytes[32..]));
+            return r.zip(s).map(|(r, s)| Self::new(r, s)).ok_or(SignatureError::OutOfRange);
+        }
+
+        Ok(der?)
+    }
+}
+
+// The header byte of a recoverable signature with recovery id 0 and an uncompressed key
+const RECOVERY_HEADER_BASE: u8 = 27;
+// Added to the header when the key the signature recovers to is meant to be compressed
+const RECOVERY_HEADER_COMPRESSED: u8 = 4;
+
+/*
+    A signature along with the recovery id that picks out its public key among the up to four that verify it: bit 0
+    is the parity of R's y, and bit 1 is set when R's x was N or more, so that r alone lost it.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RecoverableSignature {
+    signature: Signature,
+    recovery_id: u8,
+}
+
+impl RecoverableSignature {
+    // None for a recovery id above 3
+    pub fn new(signature: Signature, recovery_id: u8) -> Option<Self> {
+        (recovery_id <= 3).then_some(Self { signature, recovery_id })
+    }
+
+    pub fn signature(&self) -> Signature {
+        self.signature
+    }
+
+    pub fn recovery_id(&self) -> u8 {
+        self.recovery_id
+    }
+
+    /*
+        The 65 bytes Bitcoin Core's signmessage base64-encodes:
+
+        header  1 byte, 27 + recovery id, plus 4 when the key is compressed
+        r       32 bytes
+        s       32 bytes
+     */
+    pub fn serialize_compact(&self, compressed: bool) -> [u8; 65] {
+        let mut bytes = [0; 65];
+        bytes[0] = RECOVERY_HEADER_BASE + self.recovery_id + if compressed { RECOVERY_HEADER_COMPRESSED } else { 0 };
+        bytes[1..].copy_from_slice(&self.signature.compact());
+
+        bytes
+    }
+
+    // The signature and whether its header marks the key as compressed
+    pub fn parse(bytes: &[u8; 65]) -> Result<(Self, bool), SignatureError> {
+        let header = bytes[0];
+        if !(RECOVERY_HEADER_BASE..RECOVERY_HEADER_BASE + 2 * RECOVERY_HEADER_COMPRESSED).contains(&header) {
+            return Err(SignatureError::InvalidRecoveryHeader(header));
+        }
+        let integer = |bytes: &[u8]| Scalar::from_be_bytes(bytes.try_into().expect("Compact halves are 32 bytes."));
+        let (r, s) = integer(&bytes[1..33]).zip(integer(&bytes[33..])).ok_or(SignatureError::OutOfRange)?;
+
+        let recovery_id = (header - RECOVERY_HEADER_BASE) % RECOVERY_HEADER_COMPRESSED;
+        let compressed = header - RECOVERY_HEADER_BASE >= RECOVERY_HEADER_COMPRESSED;
+
+        Ok((Self { signature: Signature::new(r, s), recovery_id }, compressed))
+    }
+}
+
+// Reads the integer whose tag is at position, returning it and the position just past it
+fn parse_integer(bytes: &[u8], position: usize) -> Result<(U256, usize), DerError> {
+    match bytes.get(position) {
+        Some(0x02) => {}
+        Some(tag) => return Err(DerError::UnexpectedTag { position, tag: *tag }),
+        None => return Err(DerError::InvalidLength),
+    }
+
+    let length = *bytes.get(position + 1).ok_or(DerError::InvalidLength)? as usize;
+    let start = position + 2;
+    let value = bytes.get(start..start + length).ok_or(DerError::InvalidLength)?;
+
+    let empty_or_negative = value.first().is_none_or(|byte| byte & 0x80 != 0);
+    let padded = value.len() > 1 && value[0] == 0 && value[1] & 0x80 == 0;
+    if empty_or_negative || padded {
+        return Err(DerError::InvalidInteger { position });
+    }
+
+    match U256::from_be_slice(value) {
+        Some(integer) => Ok((integer, start + length)),
+        None => Err(DerError::InvalidInteger { position }),
+    }
+}
+
+// Splits count bytes off the front of rest, for the lax parser
+fn take<'a>(rest: &mut &'a [u8], count: usize) -> Option<&'a [u8]> {
+    let (taken, remaining) = rest.split_at_checked(count)?;
+    *rest = remaining;
+
+    Some(taken)
+}
+
+fn take_byte(rest: &mut &[u8]) -> Option<u8> {
+    take(rest, 1).map(|taken| taken[0])
+}
+
+fn encode_integer(integer: &U256) -> Vec<u8> {
+    let bytes = integer.to_be_bytes();
+    let first_nonzero = bytes.iter().position(|byte| *byte != 0).unwrap_or(31);
+
+    let mut value = Vec::with_capacity(33);
+    if bytes[first_nonzero] & 0x80 != 0 {
+        value.push(0);
+    }
+    value.extend(&bytes[first_nonzero..]);
+
+    let mut encoded = vec![0x02, value.len() as u8];
+    encoded.extend(value);
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use crate::ecc::scalar::Scalar;
+    use crate::ecc::secp256k1::N;
+    use crate::ecc::signature::{DerError, RecoverableSignature, Signature, SignatureError};
+    use crate::ecc::u256::U256;
+    use crate::encoding::hex;
+    use crate::encoding::hex::HexError;
+
+    // Programming Bitcoin chapter 4, the DER example
+    const DER_HEX: &str = "3045022037206a0610995c58074999cb9767b87af4c4978db68c06e8e6e81d282047a7c60221008ca63759c1157ebeaec0d03ce\
+                           cca119fc9a75bf8e6d0fa65c841c8e2738cdaec";
+
+    fn scalar(value: U256) -> Scalar {
+        Scalar::new(value).unwrap()
+    }
+
+    #[test]
+    fn der_round_trips() {
+        let bytes = hex::decode(DER_HEX).unwrap();
+
+        let signature = Signature::parse_der(&bytes).unwrap();
+
+        assert_eq!(signature.r().to_string(), "37206a0610995c58074999cb9767b87af4c4978db68c06e8e6e81d282047a7c6");
+        assert_eq!(signature.der(), bytes);
+        let high_bit = Signature::new(Scalar::ONE, scalar(U256::from_u64(0x80)));
+        assert_eq!(high_bit.der(), vec![0x30, 7, 2, 1, 1, 2, 2, 0, 0x80]);
+    }
+
+    #[test]
+    fn non_canonical_der_is_rejected() {
+        let bytes = hex::decode(DER_HEX).unwrap();
+        let mut wrong_tag = bytes.clone();
+        wrong_tag[2] = 0x03;
+        let mut trailing = bytes.clone();
+        trailing.push(0x01);
+
+        assert_eq!(Signature::parse_der(&wrong_tag), Err(DerError::UnexpectedTag { position: 2, tag: 0x03 }));
+        assert_eq!(Signature::parse_der(&trailing), Err(DerError::InvalidLength));
+        assert_eq!(Signature::parse_der(&bytes[..bytes.len() - 1]), Err(DerError::InvalidLength));
+        assert_eq!(
+            Signature::parse_der(&[0x30, 6, 2, 1, 0x80, 2, 1, 1]),
+            Err(DerError::InvalidInteger { position: 2 })
+        );
+        assert_eq!(
+            Signature::parse_der(&[0x30, 7, 2, 2, 0, 1, 2, 1, 1]),
+            Err(DerError::InvalidInteger { position: 2 })
+        );
+    }
+
+    #[test]
+    fn lax_der_reads_what_strict_der_rejects() {
+        let strict = hex::decode(DER_HEX).unwrap();
+        let signature = Signature::parse_der(&strict).unwrap();
+        // r padded with two zero bytes, the sequence length given in the long form, and a trailing byte
+        let mut padded = vec![0x30, 0x81, 0x49, 0x02, 0x22, 0x00, 0x00];
+        padded.extend_from_slice(&strict[4..]);
+        padded.push(0x01);
+        let negative_s = [0x30, 6, 2, 1, 1, 2, 1, 0x81];
+
+        assert!(Signature::parse_der(&padded).is_err());
+        assert_eq!(Signature::parse_der_lax(&padded), Some(signature));
+        assert_eq!(Signature::parse_der_lax(&strict), Some(signature));
+        assert_eq!(
+            Signature::parse_der_lax(&negative_s),
+            Some(Signature::new(Scalar::ONE, scalar(U256::from_u64(0x81))))
+        );
+        assert_eq!(Signature::parse_der_lax(&[0x30, 6, 2, 1, 1, 2, 9, 1]), None);
+        assert_eq!(Signature::parse_der_lax(&[0x31, 6, 2, 1, 1, 2, 1, 1]), None);
+
+        let mut wide = vec![0x30, 0x26, 0x02, 0x21, 0x01];
+        wide.extend([0; 32]);
+        wide.extend([0x02, 0x01, 0x01]);
+        assert_eq!(Signature::parse_der_lax(&wide), Some(Signature::new(Scalar::ZERO, Scalar::ZERO)));
+    }
+
+    #[test]
+    fn hex_round_trips_as_der_and_compact() {
+        let signature = Signature::parse_der(&hex::decode(DER_HEX).unwrap()).unwrap();
+        let compact = hex::encode(&signature.compact());
+
+        assert_eq!(signature.to_string(), DER_HEX);
+        assert_eq!(format!("{:x}", signature), DER_HEX);
+        assert_eq!(Signature::from_str(DER_HEX), Ok(signature));
+        assert_eq!(compact.len(), 128);
+        assert_eq!(&compact[..64], "37206a0610995c58074999cb9767b87af4c4978db68c06e8e6e81d282047a7c6");
+        assert_eq!(Signature::from_str(&compact), Ok(signature));
+    }
+
+    #[test]
+    fn sixty_four_bytes_read_as_der_only_when_they_are_der() {
+        // A DER signature with a 28-byte r and a 30-byte s, 64 bytes in all
+        let short = Signature::new(
+            scalar(U256::from_limbs([1, 2, 3, 0x7f << 24])),
+            scalar(U256::from_limbs([1, 2, 3, 0x7f << 40])),
+        );
+        // A compact signature whose r starts with the DER sequence tag
+        let tagged = Signature::new(scalar(U256::from_limbs([1, 2, 3, 0x30 << 56])), Scalar::ONE);
+
+        assert_eq!(short.der().len(), 64);
+        assert_eq!(Signature::from_str(&short.to_string()), Ok(short));
+        assert_eq!(Signature::from_str(&hex::encode(&tagged.compact())), Ok(tagged));
+    }
+
+    #[test]
+    fn malformed_hex_signatures_are_rejected() {
+        let odd_length = &DER_HEX[1..];
+        // r padded with a needless zero byte, which BIP66 does not allow
+        let padded = "300702020001020101";
+
+        assert_eq!(
+            Signature::from_str(odd_length),
+            Err(SignatureError::Hex(HexError::OddLength(odd_length.len())))
+        );
+        assert_eq!(Signature::from_str(padded), Err(SignatureError::Der(DerError::InvalidInteger { position: 2 })));
+    }
+
+    #[test]
+    fn integers_not_below_the_order_read_as_zero() {
+        let order = N.value().to_be_bytes();
+        let mut der = vec![0x30, 0x26, 0x02, 0x01, 0x01, 0x02, 0x21, 0x00];
+        der.extend(order);
+        let compact = format!("{}{}", hex::encode(&[1; 32]), hex::encode(&order));
+
+        assert_eq!(Signature::parse_der(&der), Ok(Signature::new(Scalar::ONE, Scalar::ZERO)));
+        assert_eq!(Signature::from_str(&compact), Err(SignatureError::OutOfRange));
+    }
+
+    #[test]
+    fn recoverable_signatures_round_trip_with_every_header() {
+        let signature = Signature::from_str(DER_HEX).unwrap();
+
+        for recovery_id in 0..4 {
+            for compressed in [false, true] {
+                let recoverable = RecoverableSignature::new(signature, recovery_id).unwrap();
+
+                let bytes = recoverable.serialize_compact(compressed);
+
+                assert_eq!(bytes[0], 27 + recovery_id + if compressed { 4 } else { 0 });
+                assert_eq!(bytes[1..], signature.compact());
+                assert_eq!(RecoverableSignature::parse(&bytes), Ok((recoverable, compressed)));
+            }
+        }
+        assert_eq!(RecoverableSignature::new(signature, 4), None);
+    }
+
+    #[test]
+    fn recoverable_signatures_with_a_bad_header_or_range_are_rejected() {
+        let signature = Signature::from_str(DER_HEX).unwrap();
+        let mut bytes = RecoverableSignature::new(signature, 0).unwrap().serialize_compact(false);
+
+        for header in [0, 26, 35, 0xff] {
+            bytes[0] = header;
+            assert_eq!(RecoverableSignature::parse(&bytes), Err(SignatureError::InvalidRecoveryHeader(header)));
+        }
+
+        bytes[0] = 31;
+        bytes[33..].copy_from_slice(&N.value().to_be_bytes());
+        assert_eq!(RecoverableSignature::parse(&bytes), Err(SignatureError::OutOfRange));
+    }
+}
@@ -0,0 +1,211 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use crate::encoding::hex;
+
+// An unsigned 256-bit integer, as 64-bit limbs from least to most significant
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct U256([u64; 4]);
+
+impl U256 {
+    pub const ZERO: Self = Self([0; 4]);
+    pub const ONE: Self = Self([1, 0, 0, 0]);
+
+    pub const fn from_limbs(limbs: [u64; 4]) -> Self {
+        Self(limbs)
+    }
+
+    pub const fn from_u64(value: u64) -> Self {
+        Self([value, 0, 0, 0])
+    }
+
+    pub fn from_be_bytes(bytes: [u8; 32]) -> Self {
+        let mut limbs = [0; 4];
+        for (index, chunk) in bytes.chunks_exact(8).rev().enumerate() {
+            limbs[index] = u64::from_be_bytes(chunk.try_into().expect("Chunks are 8 bytes."));
+        }
+
+        Self(limbs)
+    }
+
+    // None when the value is longer than 32 bytes once leading zeros are dropped
+    pub fn from_be_slice(bytes: &[u8]) -> Option<Self> {
+        let first_nonzero = bytes.iter().position(|byte| *byte != 0).unwrap_or(bytes.len());
+        let significant = &bytes[first_nonzero..];
+        if significant.len() > 32 {
+            return None;
+        }
+
+        let mut padded = [0; 32];
+        padded[32 - significant.len()..].copy_from_slice(significant);
+
+        Some(Self::from_be_bytes(padded))
+    }
+
+    pub fn to_be_bytes(&self) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        for (index, limb) in self.0.iter().rev().enumerate() {
+            bytes[8 * index..8 * index + 8].copy_from_slice(&limb.to_be_bytes());
+        }
+
+        bytes
+    }
+
+    // ORs the limbs together rather than stopping at the first non-zero one
+    pub fn is_zero(&self) -> bool {
+        self.0.iter().fold(0, |bits, limb| bits | limb) == 0
+    }
+
+    pub fn is_odd(&self) -> bool {
+        self.0[0] & 1 == 1
+    }
+
+    // Bit 0 is the least significant
+    pub fn bit(&self, index: usize) -> bool {
+        index < 256 && (self.0[index / 64] >> (index % 64)) & 1 == 1
+    }
+
+    // The number of bits up to and including the highest set one
+    pub fn bits(&self) -> usize {
+        match self.0.iter().rposition(|limb| *limb != 0) {
+            Some(index) => 64 * index + 64 - self.0[index].leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    // a when choice is false and b when it is true, picked with a mask rather than a branch
+    pub fn select(a: &Self, b: &Self, choice: bool) -> Self {
+        let mask = 0u64.wrapping_sub(choice as u64);
+        let mut limbs = [0; 4];
+        for (index, limb) in limbs.iter_mut().enumerate() {
+            *limb = a.0[index] ^ (mask & (a.0[index] ^ b.0[index]));
+        }
+
+        Self(limbs)
+    }
+
+    pub fn overflowing_add(&self, rhs: &Self) -> (Self, bool) {
+        let mut limbs = [0; 4];
+        let mut carry = false;
+        for (index, limb) in limbs.iter_mut().enumerate() {
+            let (sum, first) = self.0[index].overflowing_add(rhs.0[index]);
+            let (sum, second) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = first || second;
+        }
+
+        (Self(limbs), carry)
+    }
+
+    pub fn overflowing_sub(&self, rhs: &Self) -> (Self, bool) {
+        let mut limbs = [0; 4];
+        let mut borrow = false;
+        for (index, limb) in limbs.iter_mut().enumerate() {
+            let (difference, first) = self.0[index].overflowing_sub(rhs.0[index]);
+            let (difference, second) = difference.overflowing_sub(borrow as u64);
+            *limb = difference;
+            borrow = first || second;
+        }
+
+        (Self(limbs), borrow)
+    }
+
+    pub fn wrapping_add(&self, rhs: &Self) -> Self {
+        self.overflowing_add(rhs).0
+    }
+
+    pub fn wrapping_sub(&self, rhs: &Self) -> Self {
+        self.overflowing_sub(rhs).0
+    }
+
+    // The full 512-bit product, as its low and high halves
+    pub fn widening_mul(&self, rhs: &Self) -> (Self, Self) {
+        let mut product = [0u64; 8];
+        for (i, left) in self.0.iter().enumerate() {
+            let mut carry = 0u128;
+            for (j, right) in rhs.0.iter().enumerate() {
+                let partial = (*left as u128) * (*right as u128) + product[i + j] as u128 + carry;
+                product[i + j] = partial as u64;
+                carry = partial >> 64;
+            }
+            product[i + 4] = carry as u64;
+        }
+
+        (
+            Self(product[..4].try_into().expect("The product has 8 limbs.")),
+            Self(product[4..].try_into().expect("The product has 8 limbs.")),
+        )
+    }
+}
+
+impl Ord for U256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// 64 hex digits, most significant first
+impl Display for U256 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(&self.to_be_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ecc::u256::U256;
+
+    const MAX: U256 = U256::from_limbs([u64::MAX; 4]);
+
+    #[test]
+    fn bytes_round_trip_most_significant_first() {
+        let mut bytes = [0; 32];
+        bytes[0] = 0x80;
+        bytes[31] = 0x01;
+
+        let value = U256::from_be_bytes(bytes);
+
+        assert_eq!(value, U256::from_limbs([1, 0, 0, 1 << 63]));
+        assert_eq!(value.to_be_bytes(), bytes);
+        assert_eq!(value.bits(), 256);
+        assert!(value.bit(0) && value.bit(255) && !value.bit(1));
+        assert_eq!(U256::from_be_slice(&[0, 0, 1, 2]), Some(U256::from_u64(0x0102)));
+        assert_eq!(U256::from_be_slice(&[1; 33]), None);
+    }
+
+    #[test]
+    fn addition_and_subtraction_carry_across_limbs() {
+        assert_eq!(MAX.overflowing_add(&U256::ONE), (U256::ZERO, true));
+        assert_eq!(U256::ZERO.overflowing_sub(&U256::ONE), (MAX, true));
+        assert_eq!(
+            U256::from_limbs([u64::MAX, 0, 0, 0]).overflowing_add(&U256::ONE),
+            (U256::from_limbs([0, 1, 0, 0]), false)
+        );
+    }
+
+    #[test]
+    fn widening_multiplication_keeps_the_high_half() {
+        // (2^256 - 1)^2 = 2^512 - 2^257 + 1
+        let (low, high) = MAX.widening_mul(&MAX);
+
+        assert_eq!(low, U256::ONE);
+        assert_eq!(high, U256::from_limbs([u64::MAX - 1, u64::MAX, u64::MAX, u64::MAX]));
+    }
+
+    #[test]
+    fn select_picks_by_the_choice() {
+        assert_eq!(U256::select(&U256::ONE, &MAX, false), U256::ONE);
+        assert_eq!(U256::select(&U256::ONE, &MAX, true), MAX);
+    }
+
+    #[test]
+    fn ordering_starts_from_the_most_significant_limb() {
+        assert!(U256::from_limbs([0, 0, 0, 1]) > U256::from_limbs([u64::MAX, u64::MAX, u64::MAX, 0]));
+        assert_eq!(format!("{}", U256::from_u64(0xff)), format!("{:0>64}", "ff"));
+    }
+}
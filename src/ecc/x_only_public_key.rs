@@ -0,0 +1,165 @@
+use std::fmt::{Display, Formatter};
+use crate::address::{Address, AddressKind};
+use crate::ecc::secp256k1::{PointError, S256Point};
+use crate::ecc::u256::U256;
+use crate::encoding::hex;
+use crate::network::Network;
+use crate::script::taproot::{tweak_public_key, TaprootError};
+
+// Whether the y of the point behind an x-only key is even or odd
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Parity {
+    Even,
+    Odd,
+}
+
+impl Parity {
+    pub fn of(point: &S256Point) -> Self {
+        match point.y().is_odd() {
+            true => Parity::Odd,
+            false => Parity::Even,
+        }
+    }
+
+    pub fn is_odd(&self) -> bool {
+        matches!(self, Parity::Odd)
+    }
+}
+
+/*
+    A BIP340 public key: the 32-byte x coordinate of a point, standing for the point with that x and an even y.
+    Taproot internal and output keys, and the keys Schnorr signatures verify against, are all of this kind.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct XOnlyPublicKey([u8; 32]);
+
+impl XOnlyPublicKey {
+    // Fails unless the bytes are the x of a point on the curve
+    pub fn from_bytes(bytes: [u8; 32]) -> Result<Self, PointError> {
+        S256Point::lift_x(U256::from_be_bytes(bytes), false)?;
+
+        Ok(Self(bytes))
+    }
+
+    // Drops the parity of the point's y, so a point and its negation give the same key
+    pub fn from_point(point: &S256Point) -> Self {
+        Self(point.x().to_be_bytes())
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0
+    }
+
+    // The point with an even y that the key stands for
+    pub fn to_point(&self) -> S256Point {
+        S256Point::lift_x(U256::from_be_bytes(self.0), false).expect("The key was checked to be on the curve.")
+    }
+
+    /*
+        The BIP341 output key for this internal key, committing to the script tree with the given merkle root, or
+        to no scripts at all for None, and whether its point has an odd y.
+     */
+    pub fn tap_tweak(&self, merkle_root: Option<[u8; 32]>) -> Result<(Self, bool), TaprootError> {
+        let (output_key, odd_y) = tweak_public_key(&self.0, merkle_root.as_ref())?;
+
+        Ok((Self(output_key), odd_y))
+    }
+
+    // The P2TR address of the output key this internal key tweaks into, in bech32m
+    pub fn p2tr_address(&self, merkle_root: Option<[u8; 32]>, network: Network) -> String {
+        let (output_key, _) = self.tap_tweak(merkle_root).expect("No tweak hash is known to be invalid.");
+
+        output_key.raw_p2tr_address(network)
+    }
+
+    // The P2TR address paying to this key as the output key as it is, with no tweak
+    pub fn raw_p2tr_address(&self, network: Network) -> String {
+        Address::new(AddressKind::P2tr(self.0), network).to_string()
+    }
+}
+
+impl Display for XOnlyPublicKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(&self.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ecc::secp256k1::{PointError, S256Point, P};
+    use crate::ecc::x_only_public_key::XOnlyPublicKey;
+    use crate::encoding::hex;
+    use crate::network::Network;
+
+    fn key(hex_str: &str) -> XOnlyPublicKey {
+        XOnlyPublicKey::from_bytes(hex::decode(hex_str).unwrap().try_into().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn key_path_addresses_match_bip86() {
+        // (internal key, address) for m/86'/0'/0'/0/0, m/86'/0'/0'/0/1 and the change key m/86'/0'/0'/1/0
+        let cases = [
+            (
+                "cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115",
+                "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+            ),
+            (
+                "83dfe85a3151d2517290da461fe2815591ef69f2b18a2ce63f01697a8b313145",
+                "bc1p4qhjn9zdvkux4e44uhx8tc55attvtyu358kutcqkudyccelu0was9fqzwh",
+            ),
+            (
+                "399f1b2f4393f29a18c937859c5dd8a77350103157eb880f02e8c08214277cef",
+                "bc1p3qkhfews2uk44qtvauqyr2ttdsw7svhkl9nkm9s9c3x4ax5h60wqwruhk7",
+            ),
+        ];
+
+        for (internal_key, address) in cases {
+            assert_eq!(key(internal_key).p2tr_address(None, Network::Mainnet), address);
+        }
+    }
+
+    #[test]
+    fn key_path_output_matches_bip341_wallet_vector() {
+        // The first scriptPubKey vector of BIP341's wallet-test-vectors.json, which has no script tree
+        let internal_key = key("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d");
+
+        let (output_key, _) = internal_key.tap_tweak(None).unwrap();
+
+        assert_eq!(output_key.to_string(), "53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343");
+        assert_eq!(
+            internal_key.p2tr_address(None, Network::Mainnet),
+            "bc1p2wsldez5mud2yam29q22wgfh9439spgduvct83k3pm50fcxa5dps59h4z5"
+        );
+    }
+
+    #[test]
+    fn raw_output_key_matches_bip350() {
+        // BIP350's valid v1 example, whose 32-byte program is the generator's x
+        let generator = XOnlyPublicKey::from_point(&S256Point::generator());
+
+        assert_eq!(
+            generator.raw_p2tr_address(Network::Mainnet),
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0"
+        );
+    }
+
+    #[test]
+    fn keys_off_the_curve_are_rejected() {
+        assert_eq!(XOnlyPublicKey::from_bytes(P.value().to_be_bytes()), Err(PointError::CoordinateOutOfRange));
+        // x = 5 gives x^3 + 7 = 132, which is not a square modulo P
+        let mut x = [0; 32];
+        x[31] = 5;
+        assert_eq!(XOnlyPublicKey::from_bytes(x), Err(PointError::NotOnCurve));
+    }
+
+    #[test]
+    fn a_point_and_its_negation_give_the_same_key() {
+        let generator = S256Point::generator();
+        let negation = S256Point::lift_x(generator.x(), true).unwrap();
+        let key = XOnlyPublicKey::from_point(&generator);
+
+        assert_ne!(negation, generator);
+        assert_eq!(XOnlyPublicKey::from_point(&negation), key);
+        assert_eq!(key.to_point(), generator);
+    }
+}
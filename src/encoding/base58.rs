@@ -0,0 +1,195 @@
+use std::fmt::{Display, Formatter};
+
+// The Bitcoin alphabet leaves out 0, O, I and l since they are easy to confuse
+const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Base58Error {
+    InvalidCharacter { character: char, position: usize },
+    TooShort(usize),
+    InvalidChecksum,
+}
+
+impl Display for Base58Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Base58Error::InvalidCharacter { character, position } => {
+                write!(f, "Invalid base58 character '{}' at position {}.", character, position)
+            }
+            Base58Error::TooShort(length) => {
+                write!(f, "Base58Check data of {} bytes is too short for a version byte and checksum.", length)
+            }
+            Base58Error::InvalidChecksum => write!(f, "Base58Check checksum does not match."),
+        }
+    }
+}
+
+impl std::error::Error for Base58Error {}
+
+pub fn encode(data: &[u8]) -> String {
+    /*
+        Every leading zero byte becomes a leading '1', the rest of the input is
+        treated as a big-endian number and repeatedly divided by 58.
+     */
+    let leading_zeros = data.iter().take_while(|&&byte| byte == 0).count();
+
+    // Base58 digits, least significant first
+    let mut digits: Vec<u8> = Vec::with_capacity(data.len() * 138 / 100 + 1);
+    for &byte in &data[leading_zeros..] {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    let mut result = String::with_capacity(leading_zeros + digits.len());
+    result.extend(std::iter::repeat_n('1', leading_zeros));
+    result.extend(digits.iter().rev().map(|&digit| ALPHABET[digit as usize] as char));
+
+    result
+}
+
+pub fn decode(s: &str) -> Result<Vec<u8>, Base58Error> {
+    let leading_ones = s.chars().take_while(|&character| character == '1').count();
+
+    // Bytes, least significant first
+    let mut bytes: Vec<u8> = Vec::with_capacity(s.len() * 733 / 1000 + 1);
+    for (position, character) in s.chars().enumerate().skip(leading_ones) {
+        let value = digit_value(character)
+            .ok_or(Base58Error::InvalidCharacter { character, position })?;
+
+        let mut carry = value as u32;
+        for byte in bytes.iter_mut() {
+            carry += (*byte as u32) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+
+    let mut result = vec![0u8; leading_ones];
+    result.extend(bytes.iter().rev());
+
+    Ok(result)
+}
+
+pub fn is_base58_character(character: char) -> bool {
+    digit_value(character).is_some()
+}
+
+fn digit_value(character: char) -> Option<u8> {
+    if !character.is_ascii() {
+        return None;
+    }
+
+    ALPHABET
+        .iter()
+        .position(|&symbol| symbol == character as u8)
+        .map(|position| position as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::base58::{decode, encode, Base58Error};
+    use crate::encoding::hex;
+
+    // From Bitcoin Core's base58_encode_decode.json
+    const VECTORS: [(&str, &str); 14] = [
+        ("", ""),
+        ("61", "2g"),
+        ("626262", "a3gV"),
+        ("636363", "aPEr"),
+        ("73696d706c792061206c6f6e6720737472696e67", "2cFupjhnEsSn59qHXstmK2ffpLv2"),
+        ("00eb15231dfceb60925886b67d065299925915aeb172c06647", "1NS17iag9jJgTHD1VXjvLCEnZuQ3rJDE9L"),
+        ("516b6fcd0f", "ABnLTmg"),
+        ("bf4f89001e670274dd", "3SEo3LWLoPntC"),
+        ("572e4794", "3EFU7m"),
+        ("ecac89cad93923c02321", "EJDM8drfXA6uyA"),
+        ("10c8511e", "Rt5zm"),
+        ("00000000000000000000", "1111111111"),
+        (
+            "000111d38e5fc9071ffcd20b4a763cc9ae4f252bb4e48fd66a835e252ada93ff480d6dd43dc62a641155a5",
+            "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz",
+        ),
+        (
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f\
+             202122232425262728292a2b2c2d2e2f303132333435363738393a3b3c3d3e3f\
+             404142434445464748494a4b4c4d4e4f505152535455565758595a5b5c5d5e5f\
+             606162636465666768696a6b6c6d6e6f707172737475767778797a7b7c7d7e7f\
+             808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f\
+             a0a1a2a3a4a5a6a7a8a9aaabacadaeafb0b1b2b3b4b5b6b7b8b9babbbcbdbebf\
+             c0c1c2c3c4c5c6c7c8c9cacbcccdcecfd0d1d2d3d4d5d6d7d8d9dadbdcdddedf\
+             e0e1e2e3e4e5e6e7e8e9eaebecedeeeff0f1f2f3f4f5f6f7f8f9fafbfcfdfeff",
+            "1cWB5HCBdLjAuqGGReWE3R3CguuwSjw6RHn39s2yuDRTS5NsBgNiFpWgAnEx6VQi8csexkgYw3mdYrMHr8x9i7aEw\
+             P8kZ7vccXWqKDvGv3u1GxFKPuAkn8JCPPGDMf3vMMnbzm6Nh9zh1gcNsMvH3ZNLmP5fSG6DGbbi2tuwMWPthr4boW\
+             wCxf7ewSgNQeacyozhKDDQQ1qL5fQFUW52QKUZDZ5fw3KXNQJMcNTcaB723LchjeKun7MuGW5qyCBZYzA1KjofN1g\
+             YBV3NqyhQJ3Ns746GNuf9N2pQPmHz4xpnSrrfCvy6TVVz5d4PdrjeshsWQwpZsZGzvbdAdN8MKV5QsBDY",
+        ),
+    ];
+
+    #[test]
+    fn known_vectors_encode() {
+        for (bytes_hex, expected) in VECTORS {
+            assert_eq!(&encode(&hex::decode(bytes_hex).unwrap()), expected);
+        }
+    }
+
+    #[test]
+    fn known_vectors_decode() {
+        for (expected_hex, base58) in VECTORS {
+            assert_eq!(decode(base58), Ok(hex::decode(expected_hex).unwrap()));
+        }
+    }
+
+    #[test]
+    fn leading_zero_bytes_map_to_leading_ones() {
+        assert_eq!(&encode(&[0]), "1");
+        assert_eq!(&encode(&[0, 0, 1]), "112");
+        assert_eq!(decode("1"), Ok(vec![0]));
+        assert_eq!(decode("112"), Ok(vec![0, 0, 1]));
+    }
+
+    #[test]
+    fn random_byte_strings_round_trip() {
+        // Small xorshift generator so the test is deterministic without pulling in a dependency
+        let mut state: u64 = 0x2545f4914f6cdd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for length in 0..64 {
+            let zeros = (next() % 4) as usize;
+            let mut data = vec![0u8; usize::min(zeros, length)];
+            while data.len() < length {
+                data.push(next() as u8);
+            }
+
+            assert_eq!(decode(&encode(&data)), Ok(data.clone()), "input {:?}", data);
+        }
+
+        for length in 0..10 {
+            let zeros = vec![0u8; length];
+
+            assert_eq!(decode(&encode(&zeros)), Ok(zeros));
+        }
+    }
+
+    #[test]
+    fn ambiguous_and_non_alphabet_characters_are_rejected() {
+        for (input, character, position) in [("0", '0', 0), ("1O", 'O', 1), ("2gI", 'I', 2), ("a3lV", 'l', 2), ("3+", '+', 1), ("1é", 'é', 1)] {
+            assert_eq!(decode(input), Err(Base58Error::InvalidCharacter { character, position }));
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::constant_time::ct_eq_bytes;
+use crate::encoding::base58;
+use crate::encoding::base58::Base58Error;
+use crate::hashing::hash256::hash256;
+
+// version byte + payload + first 4 bytes of hash256(version byte + payload)
+const CHECKSUM_LENGTH: usize = 4;
+
+pub fn encode(version: u8, payload: &[u8]) -> String {
+    let mut data = Vec::with_capacity(1 + payload.len() + CHECKSUM_LENGTH);
+    data.push(version);
+    data.extend_from_slice(payload);
+
+    let checksum = hash256(&data);
+    data.extend_from_slice(&checksum[..CHECKSUM_LENGTH]);
+
+    base58::encode(&data)
+}
+
+pub fn decode(s: &str) -> Result<(u8, Vec<u8>), Base58Error> {
+    let data = base58::decode(s)?;
+
+    if data.len() < 1 + CHECKSUM_LENGTH {
+        return Err(Base58Error::TooShort(data.len()));
+    }
+
+    let (body, checksum) = data.split_at(data.len() - CHECKSUM_LENGTH);
+    if !ct_eq_bytes(&hash256(body)[..CHECKSUM_LENGTH], checksum) {
+        return Err(Base58Error::InvalidChecksum);
+    }
+
+    Ok((body[0], body[1..].to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::base58::Base58Error;
+    use crate::encoding::base58check::{decode, encode};
+    use crate::encoding::hex;
+
+    const P2PKH_ADDRESS: &str = "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2";
+    const P2PKH_HASH: &str = "77bff20c60e522dfaa3350c39b030a5d004e839a";
+    const WIF: &str = "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ";
+    const WIF_SECRET: &str = "0c28fca386c7a227600b2fe50b7cae11ec86d3bf1fbe471be89827e19d72aa1d";
+
+    #[test]
+    fn p2pkh_address_round_trips() {
+        let hash = hex::decode(P2PKH_HASH).unwrap();
+
+        assert_eq!(&encode(0x00, &hash), P2PKH_ADDRESS);
+        assert_eq!(decode(P2PKH_ADDRESS), Ok((0x00, hash)));
+    }
+
+    #[test]
+    fn wif_round_trips() {
+        let secret = hex::decode(WIF_SECRET).unwrap();
+
+        assert_eq!(&encode(0x80, &secret), WIF);
+        assert_eq!(decode(WIF), Ok((0x80, secret)));
+    }
+
+    #[test]
+    fn single_character_changes_fail_the_checksum() {
+        for position in [1, 5, 12, 20, 33] {
+            let mut characters: Vec<char> = P2PKH_ADDRESS.chars().collect();
+            characters[position] = match characters[position] {
+                'z' => 'y',
+                _ => 'z',
+            };
+            let corrupted: String = characters.into_iter().collect();
+
+            assert_eq!(decode(&corrupted), Err(Base58Error::InvalidChecksum), "position {}", position);
+        }
+    }
+
+    #[test]
+    fn errors_distinguish_bad_character_from_short_input() {
+        assert_eq!(decode("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN0"), Err(Base58Error::InvalidCharacter { character: '0', position: 33 }));
+        assert_eq!(decode(""), Err(Base58Error::TooShort(0)));
+        assert_eq!(decode("1111"), Err(Base58Error::TooShort(4)));
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt::{Display, Formatter};
+
+// Standard RFC 4648 alphabet, as used by signmessage
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const PADDING: char = '=';
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Base64Error {
+    InvalidCharacter { character: char, position: usize },
+    InvalidLength(usize),
+    InvalidPadding,
+}
+
+impl Display for Base64Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Base64Error::InvalidCharacter { character, position } => {
+                write!(f, "Invalid base64 character {:?} at position {}.", character, position)
+            }
+            Base64Error::InvalidLength(length) => write!(f, "Base64 length {} is not a multiple of 4.", length),
+            Base64Error::InvalidPadding => write!(f, "Invalid base64 padding."),
+        }
+    }
+}
+
+impl std::error::Error for Base64Error {}
+
+pub fn encode(data: &[u8]) -> String {
+    let mut result = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let group = match chunk {
+            [a, b, c] => (*a as u32) << 16 | (*b as u32) << 8 | *c as u32,
+            [a, b] => (*a as u32) << 16 | (*b as u32) << 8,
+            [a] => (*a as u32) << 16,
+            _ => unreachable!(),
+        };
+
+        // Each 3-byte group becomes four 6-bit digits, short groups are padded with '='
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => result.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char),
+                false => result.push(PADDING),
+            }
+        }
+    }
+
+    result
+}
+
+// Whitespace (such as line breaks in pasted signatures) is ignored. Positions index into `s` as given.
+pub fn decode(s: &str) -> Result<Vec<u8>, Base64Error> {
+    let characters: Vec<(usize, char)> = s
+        .chars()
+        .enumerate()
+        .filter(|(_, character)| !character.is_whitespace())
+        .collect();
+
+    if !characters.len().is_multiple_of(4) {
+        return Err(Base64Error::InvalidLength(characters.len()));
+    }
+
+    let mut result = Vec::with_capacity(characters.len() / 4 * 3);
+    let quad_count = characters.len() / 4;
+
+    for (quad_index, quad) in characters.chunks_exact(4).enumerate() {
+        let padding = quad.iter().rev().take_while(|(_, character)| *character == PADDING).count();
+        if padding > 2 || (padding > 0 && quad_index + 1 != quad_count) {
+            return Err(Base64Error::InvalidPadding);
+        }
+
+        let mut group: u32 = 0;
+        for &(position, character) in &quad[..4 - padding] {
+            let value = ALPHABET
+                .iter()
+                .position(|&symbol| character.is_ascii() && symbol == character as u8)
+                .ok_or(Base64Error::InvalidCharacter { character, position })?;
+            group = group << 6 | value as u32;
+        }
+        group <<= 6 * padding;
+
+        // The bits dropped by padding must be zero, otherwise the encoding is not canonical
+        if padding > 0 && group & ((1 << (8 * padding)) - 1) != 0 {
+            return Err(Base64Error::InvalidPadding);
+        }
+
+        result.extend_from_slice(&group.to_be_bytes()[1..4 - padding]);
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::base64::{decode, encode, Base64Error};
+
+    const RFC4648_VECTORS: [(&str, &str); 7] = [
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ];
+
+    #[test]
+    fn rfc4648_vectors_encode() {
+        for (plain, encoded) in RFC4648_VECTORS {
+            assert_eq!(&encode(plain.as_bytes()), encoded);
+        }
+    }
+
+    #[test]
+    fn rfc4648_vectors_decode() {
+        for (plain, encoded) in RFC4648_VECTORS {
+            assert_eq!(decode(encoded), Ok(plain.as_bytes().to_vec()));
+        }
+    }
+
+    #[test]
+    fn recoverable_signature_round_trips() {
+        // Header byte for a compressed key with recovery id 0, then compact r || s
+        let mut signature = vec![0x1f];
+        signature.extend((0..64).map(|i| (i * 37 + 11) as u8));
+
+        let encoded = encode(&signature);
+
+        assert_eq!(encoded.len(), 88);
+        assert!(encoded.ends_with('='));
+        assert_eq!(decode(&encoded), Ok(signature));
+    }
+
+    #[test]
+    fn whitespace_is_ignored_on_decode() {
+        assert_eq!(decode(" Zm9v\nYmFy\r\n"), Ok(b"foobar".to_vec()));
+        assert_eq!(decode("Zm9v YmE="), Ok(b"fooba".to_vec()));
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        assert_eq!(decode("Zm9"), Err(Base64Error::InvalidLength(3)));
+        assert_eq!(decode("Zm9v!mFy"), Err(Base64Error::InvalidCharacter { character: '!', position: 4 }));
+        assert_eq!(decode("Zm-v"), Err(Base64Error::InvalidCharacter { character: '-', position: 2 }));
+        assert_eq!(decode("Z==="), Err(Base64Error::InvalidPadding));
+        assert_eq!(decode("Zg==Zm9v"), Err(Base64Error::InvalidPadding));
+        assert_eq!(decode("Zh=="), Err(Base64Error::InvalidPadding));
+        assert_eq!(decode("Zm9=Zg=="), Err(Base64Error::InvalidPadding));
+    }
+}
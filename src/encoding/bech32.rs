@@ -0,0 +1,504 @@
+use std::fmt::{Display, Formatter};
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const SEPARATOR: char = '1';
+const CHECKSUM_LENGTH: usize = 6;
+const MAX_LENGTH: usize = 90;
+
+// The checksum residue each variant's polymod must produce
+const BECH32_CONSTANT: u32 = 1;
+const BECH32M_CONSTANT: u32 = 0x2bc830a3;
+
+// Witness version 0 programs use bech32 (BIP173), versions 1 to 16 use bech32m (BIP350)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    Bech32,
+    Bech32m,
+}
+
+impl Variant {
+    fn constant(self) -> u32 {
+        match self {
+            Variant::Bech32 => BECH32_CONSTANT,
+            Variant::Bech32m => BECH32M_CONSTANT,
+        }
+    }
+
+    fn from_residue(residue: u32) -> Option<Self> {
+        match residue {
+            BECH32_CONSTANT => Some(Variant::Bech32),
+            BECH32M_CONSTANT => Some(Variant::Bech32m),
+            _ => None,
+        }
+    }
+}
+
+// Generator coefficients of the BCH code, one per bit shifted out of the top of the checksum
+const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bech32Error {
+    MissingSeparator,
+    EmptyHrp,
+    InvalidHrpCharacter(char),
+    InvalidCharacter { character: char, position: usize },
+    MixedCase,
+    TooShortChecksum,
+    TooLong(usize),
+    InvalidChecksum,
+    InvalidData(u8),
+    InvalidPadding,
+    HrpMismatch(String),
+    EmptyData,
+    InvalidWitnessVersion(u8),
+    InvalidProgramLength(usize),
+    WrongVariant(Variant),
+}
+
+impl Display for Bech32Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Bech32Error::MissingSeparator => write!(f, "Bech32 string has no '1' separator."),
+            Bech32Error::EmptyHrp => write!(f, "Bech32 human-readable part is empty."),
+            Bech32Error::InvalidHrpCharacter(character) => {
+                write!(f, "Invalid character {:?} in bech32 human-readable part.", character)
+            }
+            Bech32Error::InvalidCharacter { character, position } => {
+                write!(f, "Invalid bech32 character {:?} at position {}.", character, position)
+            }
+            Bech32Error::MixedCase => write!(f, "Bech32 string mixes upper and lower case."),
+            Bech32Error::TooShortChecksum => write!(f, "Bech32 data part is shorter than the checksum."),
+            Bech32Error::TooLong(length) => write!(f, "Bech32 string of {} characters exceeds {}.", length, MAX_LENGTH),
+            Bech32Error::InvalidChecksum => write!(f, "Bech32 checksum does not match."),
+            Bech32Error::InvalidData(value) => write!(f, "Value {} does not fit in the source bit width.", value),
+            Bech32Error::InvalidPadding => write!(f, "Invalid padding in bit conversion."),
+            Bech32Error::HrpMismatch(hrp) => write!(f, "Unexpected human-readable part '{}'.", hrp),
+            Bech32Error::EmptyData => write!(f, "Segwit address has no witness version."),
+            Bech32Error::InvalidWitnessVersion(version) => write!(f, "Invalid witness version {}.", version),
+            Bech32Error::InvalidProgramLength(length) => write!(f, "Invalid witness program length {}.", length),
+            Bech32Error::WrongVariant(variant) => {
+                write!(f, "Checksum variant {:?} does not match the witness version.", variant)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Bech32Error {}
+
+// Encodes 5-bit values (0..32) under the human-readable part, appending the variant's checksum
+pub fn encode(hrp: &str, data: &[u8], variant: Variant) -> Result<String, Bech32Error> {
+    validate_hrp(hrp)?;
+    if let Some(&value) = data.iter().find(|&&value| value >= 32) {
+        return Err(Bech32Error::InvalidData(value));
+    }
+
+    let length = hrp.len() + 1 + data.len() + CHECKSUM_LENGTH;
+    if length > MAX_LENGTH {
+        return Err(Bech32Error::TooLong(length));
+    }
+    if hrp.chars().any(|c| c.is_ascii_uppercase()) && hrp.chars().any(|c| c.is_ascii_lowercase()) {
+        return Err(Bech32Error::MixedCase);
+    }
+
+    let hrp = hrp.to_ascii_lowercase();
+    let checksum = create_checksum(&hrp, data, variant);
+
+    let mut result = String::with_capacity(length);
+    result.push_str(&hrp);
+    result.push(SEPARATOR);
+    result.extend(data.iter().chain(&checksum).map(|&value| CHARSET[value as usize] as char));
+
+    Ok(result)
+}
+
+// Returns the lower case human-readable part, the 5-bit data values without the checksum, and
+// which variant's checksum matched
+pub fn decode(s: &str) -> Result<(String, Vec<u8>, Variant), Bech32Error> {
+    if s.len() > MAX_LENGTH {
+        return Err(Bech32Error::TooLong(s.len()));
+    }
+    if s.chars().any(|c| c.is_ascii_uppercase()) && s.chars().any(|c| c.is_ascii_lowercase()) {
+        return Err(Bech32Error::MixedCase);
+    }
+
+    let separator = s.rfind(SEPARATOR).ok_or(Bech32Error::MissingSeparator)?;
+    let (hrp, data_part) = (&s[..separator], &s[separator + 1..]);
+    validate_hrp(hrp)?;
+
+    if data_part.len() < CHECKSUM_LENGTH {
+        return Err(Bech32Error::TooShortChecksum);
+    }
+
+    let data = data_part
+        .chars()
+        .enumerate()
+        .map(|(offset, character)| {
+            let position = separator + 1 + offset;
+            let lower = character.to_ascii_lowercase();
+            CHARSET
+                .iter()
+                .position(|&symbol| lower.is_ascii() && symbol == lower as u8)
+                .map(|value| value as u8)
+                .ok_or(Bech32Error::InvalidCharacter { character, position })
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+
+    let hrp = hrp.to_ascii_lowercase();
+    let variant = Variant::from_residue(polymod_with_hrp(&hrp, &data)).ok_or(Bech32Error::InvalidChecksum)?;
+
+    let payload_length = data.len() - CHECKSUM_LENGTH;
+
+    Ok((hrp, data[..payload_length].to_vec(), variant))
+}
+
+// Encodes a witness program as a segwit address, picking the variant from the witness version
+pub fn encode_witness_program(hrp: &str, version: u8, program: &[u8]) -> Result<String, Bech32Error> {
+    validate_witness_program(version, program.len())?;
+
+    let mut data = vec![version];
+    data.extend(convert_bits(program, 8, 5, true)?);
+
+    encode(hrp, &data, witness_variant(version))
+}
+
+// Decodes a segwit address for the expected hrp into its witness version and program
+pub fn decode_witness_program(hrp: &str, address: &str) -> Result<(u8, Vec<u8>), Bech32Error> {
+    let (decoded_hrp, data, variant) = decode(address)?;
+
+    if decoded_hrp != hrp.to_ascii_lowercase() {
+        return Err(Bech32Error::HrpMismatch(decoded_hrp));
+    }
+
+    let (&version, program) = data.split_first().ok_or(Bech32Error::EmptyData)?;
+    let program = convert_bits(program, 5, 8, false)?;
+    validate_witness_program(version, program.len())?;
+
+    if variant != witness_variant(version) {
+        return Err(Bech32Error::WrongVariant(variant));
+    }
+
+    Ok((version, program))
+}
+
+fn witness_variant(version: u8) -> Variant {
+    match version {
+        0 => Variant::Bech32,
+        _ => Variant::Bech32m,
+    }
+}
+
+fn validate_witness_program(version: u8, length: usize) -> Result<(), Bech32Error> {
+    if version > 16 {
+        return Err(Bech32Error::InvalidWitnessVersion(version));
+    }
+
+    // v0 programs are either a 20-byte key hash or a 32-byte script hash
+    match (version, length) {
+        (0, 20) | (0, 32) => Ok(()),
+        (0, _) => Err(Bech32Error::InvalidProgramLength(length)),
+        (_, 2..=40) => Ok(()),
+        (_, _) => Err(Bech32Error::InvalidProgramLength(length)),
+    }
+}
+
+// Regroups bits, e.g. 8-bit bytes into 5-bit bech32 values and back
+pub fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Result<Vec<u8>, Bech32Error> {
+    /*
+        Bits are accumulated most significant first. When padding, leftover bits are
+        flushed with zeros. When not padding (decoding), leftovers must be fewer than
+        `from` bits and all zero, otherwise the encoding was not canonical.
+     */
+    let mut accumulator: u32 = 0;
+    let mut bits: u32 = 0;
+    let max_value: u32 = (1 << to) - 1;
+    let mut result = Vec::with_capacity(data.len() * from as usize / to as usize + 1);
+
+    for &value in data {
+        if (value as u32) >> from != 0 {
+            return Err(Bech32Error::InvalidData(value));
+        }
+        accumulator = (accumulator << from) | value as u32;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            result.push(((accumulator >> bits) & max_value) as u8);
+        }
+    }
+
+    if pad {
+        if bits > 0 {
+            result.push(((accumulator << (to - bits)) & max_value) as u8);
+        }
+    } else if bits >= from || (accumulator << (to - bits)) & max_value != 0 {
+        return Err(Bech32Error::InvalidPadding);
+    }
+
+    Ok(result)
+}
+
+// Whether the character can appear after the separator, in either case
+pub fn is_data_character(character: char) -> bool {
+    let lower = character.to_ascii_lowercase();
+
+    lower.is_ascii() && CHARSET.contains(&(lower as u8))
+}
+
+fn validate_hrp(hrp: &str) -> Result<(), Bech32Error> {
+    if hrp.is_empty() {
+        return Err(Bech32Error::EmptyHrp);
+    }
+
+    match hrp.chars().find(|&c| !(33..=126).contains(&(c as u32))) {
+        Some(character) => Err(Bech32Error::InvalidHrpCharacter(character)),
+        None => Ok(()),
+    }
+}
+
+fn polymod(values: impl Iterator<Item = u8>) -> u32 {
+    let mut checksum: u32 = 1;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x1ffffff) << 5) ^ value as u32;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+
+    checksum
+}
+
+// The hrp is expanded to its high bits, a zero, then its low bits so that it is covered by the checksum
+fn hrp_expand(hrp: &str) -> impl Iterator<Item = u8> + '_ {
+    hrp.bytes()
+        .map(|byte| byte >> 5)
+        .chain(std::iter::once(0))
+        .chain(hrp.bytes().map(|byte| byte & 31))
+}
+
+fn polymod_with_hrp(hrp: &str, data: &[u8]) -> u32 {
+    polymod(hrp_expand(hrp).chain(data.iter().copied()))
+}
+
+fn create_checksum(hrp: &str, data: &[u8], variant: Variant) -> [u8; CHECKSUM_LENGTH] {
+    let residue = polymod(hrp_expand(hrp).chain(data.iter().copied()).chain([0; CHECKSUM_LENGTH])) ^ variant.constant();
+
+    let mut checksum = [0u8; CHECKSUM_LENGTH];
+    for (i, value) in checksum.iter_mut().enumerate() {
+        *value = ((residue >> (5 * (5 - i))) & 31) as u8;
+    }
+
+    checksum
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::bech32::{
+        convert_bits, decode, decode_witness_program, encode, encode_witness_program, Bech32Error, Variant,
+    };
+    use crate::encoding::hex;
+
+    const VALID: [&str; 7] = [
+        "A12UEL5L",
+        "a12uel5l",
+        "an83characterlonghumanreadablepartthatcontainsthenumber1andtheexcludedcharactersbio1tt5tgs",
+        "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw",
+        "11qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqc8247j",
+        "split1checkupstagehandshakeupstreamerranterredcaperred2y9e3w",
+        "?1ezyfcl",
+    ];
+
+    #[test]
+    fn bip173_valid_strings_decode_and_re_encode() {
+        for valid in VALID {
+            let (hrp, data, variant) = decode(valid).unwrap_or_else(|error| panic!("{}: {}", valid, error));
+
+            let encoded = encode(&hrp, &data, Variant::Bech32).unwrap();
+
+            assert_eq!(variant, Variant::Bech32);
+            assert_eq!(encoded, valid.to_ascii_lowercase());
+        }
+    }
+
+    #[test]
+    fn uppercase_strings_round_trip_through_lower_case() {
+        let (hrp, data, _) = decode("A12UEL5L").unwrap();
+
+        assert_eq!(&hrp, "a");
+        assert!(data.is_empty());
+        assert_eq!(encode("A", &data, Variant::Bech32), Ok("a12uel5l".to_string()));
+    }
+
+    #[test]
+    fn bip173_invalid_strings_are_rejected() {
+        let vectors = [
+            ("\u{20}1nwldj5", Bech32Error::InvalidHrpCharacter('\u{20}')),
+            ("\u{7f}1axkwrx", Bech32Error::InvalidHrpCharacter('\u{7f}')),
+            ("\u{80}1eym55h", Bech32Error::InvalidHrpCharacter('\u{80}')),
+            (
+                "an84characterslonghumanreadablepartthatcontainsthenumber1andtheexcludedcharactersbio1569pvx",
+                Bech32Error::TooLong(91),
+            ),
+            ("pzry9x0s0muk", Bech32Error::MissingSeparator),
+            ("1pzry9x0s0muk", Bech32Error::EmptyHrp),
+            ("x1b4n0q5v", Bech32Error::InvalidCharacter { character: 'b', position: 2 }),
+            ("li1dgmt3", Bech32Error::TooShortChecksum),
+            ("de1lg7wt\u{ff}", Bech32Error::InvalidCharacter { character: '\u{ff}', position: 8 }),
+            ("A1G7SGD8", Bech32Error::InvalidChecksum),
+            ("10a06t8", Bech32Error::EmptyHrp),
+            ("1qzzfhee", Bech32Error::EmptyHrp),
+        ];
+
+        for (invalid, expected) in vectors {
+            assert_eq!(decode(invalid), Err(expected), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn mixed_case_is_rejected() {
+        assert_eq!(decode("A12uEL5L"), Err(Bech32Error::MixedCase));
+        assert_eq!(encode("aB", &[], Variant::Bech32), Err(Bech32Error::MixedCase));
+    }
+
+    #[test]
+    fn single_character_changes_fail_the_checksum() {
+        let valid = "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw";
+
+        for position in 7..valid.len() {
+            let mut characters: Vec<char> = valid.chars().collect();
+            characters[position] = match characters[position] {
+                'q' => 'p',
+                _ => 'q',
+            };
+            let corrupted: String = characters.into_iter().collect();
+
+            assert_eq!(decode(&corrupted), Err(Bech32Error::InvalidChecksum), "position {}", position);
+        }
+    }
+
+    #[test]
+    fn bits_convert_between_bytes_and_five_bit_groups() {
+        let bytes = [0xff, 0x00, 0xab];
+
+        let five_bit = convert_bits(&bytes, 8, 5, true).unwrap();
+
+        assert_eq!(five_bit, vec![31, 28, 0, 10, 22]);
+        assert_eq!(convert_bits(&five_bit, 5, 8, false), Ok(bytes.to_vec()));
+    }
+
+    #[test]
+    fn strict_padding_rules_are_enforced_when_not_padding() {
+        // 5 five-bit groups = 25 bits: 24 data bits plus 1 padding bit, which must be zero
+        assert_eq!(convert_bits(&[31, 28, 0, 10, 23], 5, 8, false), Err(Bech32Error::InvalidPadding));
+        // A whole extra group of padding is not allowed
+        assert_eq!(convert_bits(&[31, 28, 0, 10, 22, 0], 5, 8, false), Err(Bech32Error::InvalidPadding));
+        assert_eq!(convert_bits(&[32], 5, 8, false), Err(Bech32Error::InvalidData(32)));
+    }
+
+
+    #[test]
+    fn bip350_valid_bech32m_strings_decode_and_re_encode() {
+        let vectors = [
+            "A1LQFN3A",
+            "a1lqfn3a",
+            "an83characterlonghumanreadablepartthatcontainsthetheexcludedcharactersbioandnumber11sg7hg6",
+            "abcdef1l7aum6echk45nj3s0wdvt2fg8x9yrzpqzd3ryx",
+            "11llllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllllludsr8",
+            "split1checkupstagehandshakeupstreamerranterredcaperredlc445v",
+            "?1v759aa",
+        ];
+
+        for valid in vectors {
+            let (hrp, data, variant) = decode(valid).unwrap_or_else(|error| panic!("{}: {}", valid, error));
+
+            assert_eq!(variant, Variant::Bech32m);
+            assert_eq!(encode(&hrp, &data, Variant::Bech32m), Ok(valid.to_ascii_lowercase()));
+        }
+    }
+
+    #[test]
+    fn bip350_valid_segwit_addresses() {
+        // (address, scriptPubKey): OP_n followed by a push of the witness program
+        let vectors = [
+            ("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4", "0014751e76e8199196d454941c45d1b3a323f1433bd6"),
+            (
+                "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+                "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+            ),
+            (
+                "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y",
+                "5128751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6",
+            ),
+            ("BC1SW50QGDZ25J", "6002751e"),
+            ("bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs", "5210751e76e8199196d454941c45d1b3a323"),
+            (
+                "tb1qqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesrxh6hy",
+                "0020000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433",
+            ),
+            (
+                "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c",
+                "5120000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433",
+            ),
+            (
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+                "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            ),
+        ];
+
+        for (address, script_pubkey) in vectors {
+            let hrp = &address[..2];
+            let script_pubkey = hex::decode(script_pubkey).unwrap();
+            let expected_version = match script_pubkey[0] {
+                0 => 0,
+                op_n => op_n - 0x50,
+            };
+
+            let (version, program) = decode_witness_program(hrp, address).unwrap_or_else(|error| panic!("{}: {}", address, error));
+
+            assert_eq!(version, expected_version);
+            assert_eq!(program, script_pubkey[2..]);
+            assert_eq!(encode_witness_program(hrp, version, &program), Ok(address.to_ascii_lowercase()));
+        }
+    }
+
+    #[test]
+    fn bip350_invalid_segwit_addresses() {
+        let vectors = [
+            ("bc", "tc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq5zuyut", Bech32Error::HrpMismatch("tc".to_string())),
+            ("bc", "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd", Bech32Error::WrongVariant(Variant::Bech32)),
+            ("tb", "tb1z0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqglt7rf", Bech32Error::WrongVariant(Variant::Bech32)),
+            ("bc", "BC1S0XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ54WELL", Bech32Error::WrongVariant(Variant::Bech32)),
+            ("bc", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh", Bech32Error::WrongVariant(Variant::Bech32m)),
+            ("tb", "tb1q0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq24jc47", Bech32Error::WrongVariant(Variant::Bech32m)),
+            (
+                "bc",
+                "bc1p38j9r5y49hruaue7wxjce0updqjuyyx0kh56v8s25huc6995vvpql3jow4",
+                Bech32Error::InvalidCharacter { character: 'o', position: 59 },
+            ),
+            ("bc", "BC130XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ7ZWS8R", Bech32Error::InvalidWitnessVersion(17)),
+            ("bc", "bc1pw5dgrnzv", Bech32Error::InvalidProgramLength(1)),
+            (
+                "bc",
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v8n0nx0muaewav253zgeav",
+                Bech32Error::InvalidProgramLength(41),
+            ),
+            ("bc", "BC1QR508D6QEJXTDG4Y5R3ZARVARYV98GJ9P", Bech32Error::InvalidProgramLength(16)),
+            ("tb", "tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq47Zagq", Bech32Error::MixedCase),
+            ("bc", "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v07qwwzcrf", Bech32Error::InvalidPadding),
+            ("tb", "tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vpggkg4j", Bech32Error::InvalidPadding),
+            ("bc", "bc1gmk9yu", Bech32Error::EmptyData),
+        ];
+
+        for (hrp, address, expected) in vectors {
+            assert_eq!(decode_witness_program(hrp, address), Err(expected), "{}", address);
+        }
+    }
+
+    #[test]
+    fn witness_programs_with_invalid_lengths_are_not_encoded() {
+        assert_eq!(encode_witness_program("bc", 0, &[0; 21]), Err(Bech32Error::InvalidProgramLength(21)));
+        assert_eq!(encode_witness_program("bc", 1, &[0; 41]), Err(Bech32Error::InvalidProgramLength(41)));
+        assert_eq!(encode_witness_program("bc", 17, &[0; 32]), Err(Bech32Error::InvalidWitnessVersion(17)));
+    }
+}
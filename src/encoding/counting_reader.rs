@@ -0,0 +1,60 @@
+use std::io::{self, Read};
+
+// Passes reads through while counting the bytes they return, so a parser can report how far it got
+#[derive(Debug)]
+pub struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R: Read> CountingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, count: 0 }
+    }
+
+    // Bytes read so far, including those of a read_exact that ran out partway
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count += read as u64;
+
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use crate::encoding::counting_reader::CountingReader;
+
+    #[test]
+    fn counts_bytes_as_they_are_read() {
+        let bytes = [1, 2, 3, 4, 5];
+        let mut subject = CountingReader::new(&bytes[..]);
+        let mut buf = [0; 2];
+
+        subject.read_exact(&mut buf).unwrap();
+
+        assert_eq!(subject.count(), 2);
+        assert_eq!(subject.into_inner(), &[3, 4, 5]);
+    }
+
+    #[test]
+    fn short_read_counts_what_was_there() {
+        let bytes = [1, 2, 3];
+        let mut subject = CountingReader::new(&bytes[..]);
+        let mut buf = [0; 8];
+
+        assert!(subject.read_exact(&mut buf).is_err());
+        assert_eq!(subject.count(), 3);
+    }
+}
@@ -0,0 +1,160 @@
+use std::io::{Read, Write};
+use crate::encoding::little_endian::{
+    read_u16_le, read_u256_le, read_u32_le, read_u64_le, read_u8, write_u16_le, write_u256_le, write_u32_le,
+    write_u64_le, write_u8,
+};
+use crate::encoding::parse_error::ParseError;
+use crate::encoding::varint::{encode_varint, read_varint};
+use crate::hashing::hash256::Hash256;
+
+// Wire types parse from and serialize to streams so they can be composed without buffering whole messages
+pub trait Decodable: Sized {
+    fn parse(reader: &mut impl Read) -> Result<Self, ParseError>;
+
+    // Parses a complete value, rejecting any bytes left over
+    fn parse_exact(bytes: &[u8]) -> Result<Self, ParseError> {
+        let mut reader = bytes;
+        let value = Self::parse(&mut reader)?;
+
+        match reader.len() {
+            0 => Ok(value),
+            leftover => Err(ParseError::TrailingBytes(leftover)),
+        }
+    }
+}
+
+pub trait Encodable {
+    fn serialize(&self, writer: &mut impl Write) -> Result<(), ParseError>;
+
+    fn serialize_to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.serialize(&mut bytes).expect("Writing to a Vec cannot fail.");
+
+        bytes
+    }
+}
+
+// A CompactSize integer, distinct from u64 which is serialized as a fixed 8 bytes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct VarInt(pub u64);
+
+impl Decodable for VarInt {
+    fn parse(reader: &mut impl Read) -> Result<Self, ParseError> {
+        read_varint(reader).map(VarInt).map_err(|error| error.in_type("VarInt"))
+    }
+}
+
+impl Encodable for VarInt {
+    fn serialize(&self, writer: &mut impl Write) -> Result<(), ParseError> {
+        writer.write_all(&encode_varint(self.0))?;
+
+        Ok(())
+    }
+}
+
+macro_rules! impl_integer_encoding {
+    ($type:ty, $read:ident, $write:ident) => {
+        impl Decodable for $type {
+            fn parse(reader: &mut impl Read) -> Result<Self, ParseError> {
+                $read(reader).map_err(|error| error.in_type(stringify!($type)))
+            }
+        }
+
+        impl Encodable for $type {
+            fn serialize(&self, writer: &mut impl Write) -> Result<(), ParseError> {
+                $write(writer, *self)
+            }
+        }
+    };
+}
+
+impl_integer_encoding!(u8, read_u8, write_u8);
+impl_integer_encoding!(u16, read_u16_le, write_u16_le);
+impl_integer_encoding!(u32, read_u32_le, write_u32_le);
+impl_integer_encoding!(u64, read_u64_le, write_u64_le);
+
+// 256-bit values held big-endian, reversed on the wire
+impl Decodable for [u8; 32] {
+    fn parse(reader: &mut impl Read) -> Result<Self, ParseError> {
+        read_u256_le(reader).map_err(|error| error.in_type("u256"))
+    }
+}
+
+impl Encodable for [u8; 32] {
+    fn serialize(&self, writer: &mut impl Write) -> Result<(), ParseError> {
+        write_u256_le(writer, self)
+    }
+}
+
+// Hashes are already kept in wire order, so their bytes are copied as-is
+impl Decodable for Hash256 {
+    fn parse(reader: &mut impl Read) -> Result<Self, ParseError> {
+        let mut bytes = [0u8; 32];
+        reader
+            .read_exact(&mut bytes)
+            .map_err(|error| ParseError::from(error).in_type("Hash256"))?;
+
+        Ok(Hash256::from_wire_bytes(bytes))
+    }
+}
+
+impl Encodable for Hash256 {
+    fn serialize(&self, writer: &mut impl Write) -> Result<(), ParseError> {
+        writer.write_all(&self.to_wire_bytes())?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use crate::encoding::encodable::{Decodable, Encodable, VarInt};
+    use crate::encoding::hex;
+    use crate::encoding::parse_error::ParseError;
+    use crate::hashing::hash256::Hash256;
+
+    const TXID: &str = "d1c789a9c60383bf715f3f6ad9d14b91fe55f3deb369fe5d9280cb1a01793f81";
+
+    #[test]
+    fn several_structures_parse_from_one_reader() {
+        let mut bytes = Vec::new();
+        Hash256::from_str(TXID).unwrap().serialize(&mut bytes).unwrap();
+        7u32.serialize(&mut bytes).unwrap();
+        VarInt(300).serialize(&mut bytes).unwrap();
+        0x1234u16.serialize(&mut bytes).unwrap();
+        u64::MAX.serialize(&mut bytes).unwrap();
+        bytes.extend_from_slice(&[0xaa, 0xbb]);
+
+        let mut reader = bytes.as_slice();
+
+        assert_eq!(Hash256::parse(&mut reader), Ok(Hash256::from_str(TXID).unwrap()));
+        assert_eq!(u32::parse(&mut reader), Ok(7));
+        assert_eq!(VarInt::parse(&mut reader), Ok(VarInt(300)));
+        assert_eq!(u16::parse(&mut reader), Ok(0x1234));
+        assert_eq!(u64::parse(&mut reader), Ok(u64::MAX));
+        assert_eq!(reader, &[0xaa, 0xbb]);
+    }
+
+    #[test]
+    fn serialization_uses_wire_encodings() {
+        assert_eq!(&hex::encode(&0x01020304u32.serialize_to_vec()), "04030201");
+        assert_eq!(&hex::encode(&VarInt(0xfd).serialize_to_vec()), "fdfd00");
+        assert_eq!(&hex::encode(&Hash256::from_str(TXID).unwrap().serialize_to_vec()[..4]), "813f7901");
+    }
+
+    #[test]
+    fn parse_exact_accounts_for_leftover_bytes() {
+        assert_eq!(u32::parse_exact(&[1, 0, 0, 0]), Ok(1));
+        assert_eq!(u32::parse_exact(&[1, 0, 0, 0, 9, 9]), Err(ParseError::TrailingBytes(2)));
+    }
+
+    #[test]
+    fn short_reads_carry_the_type_being_parsed() {
+        assert_eq!(u32::parse(&mut [1, 2].as_slice()), Err(ParseError::Truncated("u32")));
+        assert_eq!(u64::parse(&mut [].as_slice()), Err(ParseError::Truncated("u64")));
+        assert_eq!(VarInt::parse(&mut [0xfe, 1].as_slice()), Err(ParseError::Truncated("VarInt")));
+        assert_eq!(Hash256::parse(&mut [0; 31].as_slice()), Err(ParseError::Truncated("Hash256")));
+        assert_eq!(<[u8; 32]>::parse(&mut [0; 3].as_slice()), Err(ParseError::Truncated("u256")));
+    }
+}
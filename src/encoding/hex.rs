@@ -0,0 +1,110 @@
+use std::fmt::{Display, Formatter};
+
+const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HexError {
+    OddLength(usize),
+    InvalidCharacter { character: char, position: usize },
+}
+
+impl Display for HexError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HexError::OddLength(length) => write!(f, "Hex string has odd length {}.", length),
+            HexError::InvalidCharacter { character, position } => {
+                write!(f, "Invalid hex character {:?} at position {}.", character, position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HexError {}
+
+pub fn encode(data: &[u8]) -> String {
+    let mut result = String::with_capacity(data.len() * 2);
+    for byte in data {
+        result.push(DIGITS[(byte >> 4) as usize] as char);
+        result.push(DIGITS[(byte & 0x0f) as usize] as char);
+    }
+
+    result
+}
+
+// Accepts upper and lower case digits and an optional 0x prefix. Error positions index into `s` as given.
+pub fn decode(s: &str) -> Result<Vec<u8>, HexError> {
+    let (offset, digits) = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(digits) => (2, digits),
+        None => (0, s),
+    };
+
+    let nibbles = digits
+        .chars()
+        .enumerate()
+        .map(|(index, character)| {
+            character
+                .to_digit(16)
+                .map(|nibble| nibble as u8)
+                .ok_or(HexError::InvalidCharacter { character, position: offset + index })
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+
+    if nibbles.len() % 2 != 0 {
+        return Err(HexError::OddLength(nibbles.len()));
+    }
+
+    Ok(nibbles.chunks_exact(2).map(|pair| (pair[0] << 4) | pair[1]).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::hex::{decode, encode, HexError};
+
+    #[test]
+    fn bytes_encode_to_lower_case_hex() {
+        assert_eq!(&encode(&[]), "");
+        assert_eq!(&encode(&[0x00, 0x0f, 0xab, 0xff]), "000fabff");
+    }
+
+    #[test]
+    fn upper_lower_and_prefixed_hex_decode() {
+        assert_eq!(decode(""), Ok(vec![]));
+        assert_eq!(decode("000fabff"), Ok(vec![0x00, 0x0f, 0xab, 0xff]));
+        assert_eq!(decode("000FABFF"), Ok(vec![0x00, 0x0f, 0xab, 0xff]));
+        assert_eq!(decode("0xdeadBEEF"), Ok(vec![0xde, 0xad, 0xbe, 0xef]));
+        assert_eq!(decode("0X01"), Ok(vec![0x01]));
+    }
+
+    #[test]
+    fn random_byte_strings_round_trip() {
+        let mut state: u64 = 0x9e3779b97f4a7c15;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for length in 0..100 {
+            let data: Vec<u8> = (0..length).map(|_| next() as u8).collect();
+
+            assert_eq!(decode(&encode(&data)), Ok(data.clone()));
+            assert_eq!(decode(&encode(&data).to_uppercase()), Ok(data));
+        }
+    }
+
+    #[test]
+    fn odd_length_is_rejected() {
+        assert_eq!(decode("abc"), Err(HexError::OddLength(3)));
+        assert_eq!(decode("0xabc"), Err(HexError::OddLength(3)));
+    }
+
+    #[test]
+    fn invalid_characters_report_their_position() {
+        assert_eq!(decode("zz"), Err(HexError::InvalidCharacter { character: 'z', position: 0 }));
+        assert_eq!(decode("00g0"), Err(HexError::InvalidCharacter { character: 'g', position: 2 }));
+        assert_eq!(decode("0x00 0"), Err(HexError::InvalidCharacter { character: ' ', position: 4 }));
+        assert_eq!(decode("00é0"), Err(HexError::InvalidCharacter { character: 'é', position: 2 }));
+        assert_eq!(decode("0x0x"), Err(HexError::InvalidCharacter { character: 'x', position: 3 }));
+    }
+}
@@ -0,0 +1,160 @@
+use std::io::{Read, Write};
+use crate::encoding::parse_error::ParseError;
+
+pub fn read_u8(reader: &mut impl Read) -> Result<u8, ParseError> {
+    let mut bytes = [0u8; 1];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(bytes[0])
+}
+
+pub fn read_u16_le(reader: &mut impl Read) -> Result<u16, ParseError> {
+    let mut bytes = [0u8; 2];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u16::from_le_bytes(bytes))
+}
+
+pub fn read_u32_le(reader: &mut impl Read) -> Result<u32, ParseError> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub fn read_u64_le(reader: &mut impl Read) -> Result<u64, ParseError> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u64::from_le_bytes(bytes))
+}
+
+// 256-bit values are kept big-endian in memory, so the 32 wire bytes are reversed on the way in
+pub fn read_u256_le(reader: &mut impl Read) -> Result<[u8; 32], ParseError> {
+    let mut bytes = [0u8; 32];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u256_from_le(bytes))
+}
+
+pub fn write_u8(writer: &mut impl Write, value: u8) -> Result<(), ParseError> {
+    writer.write_all(&[value])?;
+
+    Ok(())
+}
+
+pub fn write_u16_le(writer: &mut impl Write, value: u16) -> Result<(), ParseError> {
+    writer.write_all(&value.to_le_bytes())?;
+
+    Ok(())
+}
+
+pub fn write_u32_le(writer: &mut impl Write, value: u32) -> Result<(), ParseError> {
+    writer.write_all(&value.to_le_bytes())?;
+
+    Ok(())
+}
+
+pub fn write_u64_le(writer: &mut impl Write, value: u64) -> Result<(), ParseError> {
+    writer.write_all(&value.to_le_bytes())?;
+
+    Ok(())
+}
+
+pub fn write_u256_le(writer: &mut impl Write, value: &[u8; 32]) -> Result<(), ParseError> {
+    writer.write_all(&u256_to_le(*value))?;
+
+    Ok(())
+}
+
+pub fn u256_from_le(mut bytes: [u8; 32]) -> [u8; 32] {
+    bytes.reverse();
+
+    bytes
+}
+
+pub fn u256_to_le(mut bytes: [u8; 32]) -> [u8; 32] {
+    bytes.reverse();
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::little_endian::{
+        read_u16_le, read_u256_le, read_u32_le, read_u64_le, read_u8, u256_from_le, u256_to_le, write_u16_le,
+        write_u256_le, write_u32_le, write_u64_le, write_u8,
+    };
+    use crate::encoding::parse_error::ParseError;
+
+    #[test]
+    fn integers_are_read_from_hand_written_little_endian_bytes() {
+        assert_eq!(read_u8(&mut [0xab].as_slice()), Ok(0xab));
+        assert_eq!(read_u16_le(&mut [0x34, 0x12].as_slice()), Ok(0x1234));
+        assert_eq!(read_u32_le(&mut [0x78, 0x56, 0x34, 0x12].as_slice()), Ok(0x12345678));
+        assert_eq!(
+            read_u64_le(&mut [0xef, 0xcd, 0xab, 0x90, 0x78, 0x56, 0x34, 0x12].as_slice()),
+            Ok(0x1234567890abcdef)
+        );
+    }
+
+    #[test]
+    fn integers_are_written_as_little_endian_bytes() {
+        let mut buffer = Vec::new();
+
+        write_u8(&mut buffer, 0xab).unwrap();
+        write_u16_le(&mut buffer, 0x1234).unwrap();
+        write_u32_le(&mut buffer, 0x12345678).unwrap();
+        write_u64_le(&mut buffer, 0x1234567890abcdef).unwrap();
+
+        assert_eq!(buffer, vec![
+            0xab,
+            0x34, 0x12,
+            0x78, 0x56, 0x34, 0x12,
+            0xef, 0xcd, 0xab, 0x90, 0x78, 0x56, 0x34, 0x12,
+        ]);
+    }
+
+    #[test]
+    fn boundary_values_round_trip() {
+        for value in [0, 1, u16::MAX] {
+            let mut buffer = Vec::new();
+            write_u16_le(&mut buffer, value).unwrap();
+            assert_eq!(read_u16_le(&mut buffer.as_slice()), Ok(value));
+        }
+        for value in [0, 1, u32::MAX] {
+            let mut buffer = Vec::new();
+            write_u32_le(&mut buffer, value).unwrap();
+            assert_eq!(read_u32_le(&mut buffer.as_slice()), Ok(value));
+        }
+        for value in [0, 1, u64::MAX] {
+            let mut buffer = Vec::new();
+            write_u64_le(&mut buffer, value).unwrap();
+            assert_eq!(read_u64_le(&mut buffer.as_slice()), Ok(value));
+        }
+    }
+
+    #[test]
+    fn u256_values_are_reversed_on_the_wire() {
+        let mut value = [0u8; 32];
+        value[31] = 0x01;
+        value[0] = 0xff;
+
+        let mut buffer = Vec::new();
+        write_u256_le(&mut buffer, &value).unwrap();
+
+        assert_eq!(buffer[0], 0x01);
+        assert_eq!(buffer[31], 0xff);
+        assert_eq!(read_u256_le(&mut buffer.as_slice()), Ok(value));
+        assert_eq!(u256_from_le(u256_to_le(value)), value);
+    }
+
+    #[test]
+    fn short_input_is_an_error() {
+        assert_eq!(read_u8(&mut [].as_slice()), Err(ParseError::UnexpectedEof));
+        assert_eq!(read_u16_le(&mut [0x01].as_slice()), Err(ParseError::UnexpectedEof));
+        assert_eq!(read_u32_le(&mut [0x01, 0x02, 0x03].as_slice()), Err(ParseError::UnexpectedEof));
+        assert_eq!(read_u64_le(&mut [0x01; 7].as_slice()), Err(ParseError::UnexpectedEof));
+        assert_eq!(read_u256_le(&mut [0x01; 31].as_slice()), Err(ParseError::UnexpectedEof));
+    }
+}
@@ -0,0 +1,10 @@
+pub mod base58;
+pub mod base58check;
+pub mod base64;
+pub mod bech32;
+pub mod counting_reader;
+pub mod encodable;
+pub mod hex;
+pub mod little_endian;
+pub mod parse_error;
+pub mod varint;
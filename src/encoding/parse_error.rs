@@ -0,0 +1,71 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    UnexpectedEof,
+    // Input ended while parsing the named type
+    Truncated(&'static str),
+    // Input ended this many bytes into a top-level structure, while parsing the named type
+    TruncatedAt { type_name: &'static str, offset: u64 },
+    TrailingBytes(usize),
+    Io(io::ErrorKind),
+    NonCanonicalVarInt(u64),
+    // The segwit flag byte had bits set that no upgrade has defined
+    UnknownSegwitFlag(u8),
+    // Marked as segwit, but every witness was empty
+    SuperfluousWitness,
+    // The push starting at this byte of a script claims more data than the script has left
+    PushPastScriptEnd { position: usize },
+}
+
+impl ParseError {
+    // Attaches the type being parsed to a bare end-of-input error, keeping any more specific context
+    pub fn in_type(self, type_name: &'static str) -> Self {
+        match self {
+            ParseError::UnexpectedEof => ParseError::Truncated(type_name),
+            error => error,
+        }
+    }
+
+    // Records where in the structure being parsed a truncation happened
+    pub fn at_offset(self, offset: u64) -> Self {
+        match self {
+            ParseError::Truncated(type_name) => ParseError::TruncatedAt { type_name, offset },
+            error => error,
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnexpectedEof => write!(f, "Unexpected end of input."),
+            ParseError::Truncated(type_name) => write!(f, "Unexpected end of input while parsing {}.", type_name),
+            ParseError::TruncatedAt { type_name, offset } => {
+                write!(f, "Unexpected end of input at byte {} while parsing {}.", offset, type_name)
+            }
+            ParseError::TrailingBytes(count) => write!(f, "{} bytes left over after parsing.", count),
+            ParseError::Io(kind) => write!(f, "IO error while parsing: {}.", kind),
+            ParseError::NonCanonicalVarInt(value) => {
+                write!(f, "Varint {} is not encoded in its shortest form.", value)
+            }
+            ParseError::UnknownSegwitFlag(flag) => write!(f, "Unknown segwit flag {:#04x}.", flag),
+            ParseError::SuperfluousWitness => write!(f, "Segwit transaction has no witness data."),
+            ParseError::PushPastScriptEnd { position } => {
+                write!(f, "Push at byte {} of the script runs past its end.", position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<io::Error> for ParseError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::UnexpectedEof => ParseError::UnexpectedEof,
+            kind => ParseError::Io(kind),
+        }
+    }
+}
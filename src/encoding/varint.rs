@@ -0,0 +1,166 @@
+use std::io::{Read, Write};
+use crate::encoding::little_endian::{read_u16_le, read_u32_le, read_u64_le, read_u8};
+use crate::encoding::parse_error::ParseError;
+
+/*
+    CompactSize integers:
+
+    value < 0xfd        -> 1 byte, the value itself
+    value <= 0xffff     -> 0xfd followed by 2 bytes little-endian
+    value <= 0xffffffff -> 0xfe followed by 4 bytes little-endian
+    otherwise           -> 0xff followed by 8 bytes little-endian
+ */
+pub fn encode_varint(value: u64) -> Vec<u8> {
+    match value {
+        0..=0xfc => vec![value as u8],
+        0xfd..=0xffff => {
+            let mut bytes = vec![0xfd];
+            bytes.extend_from_slice(&(value as u16).to_le_bytes());
+            bytes
+        }
+        0x10000..=0xffff_ffff => {
+            let mut bytes = vec![0xfe];
+            bytes.extend_from_slice(&(value as u32).to_le_bytes());
+            bytes
+        }
+        _ => {
+            let mut bytes = vec![0xff];
+            bytes.extend_from_slice(&value.to_le_bytes());
+            bytes
+        }
+    }
+}
+
+pub fn read_varint(reader: &mut impl Read) -> Result<u64, ParseError> {
+    let value = match read_u8(reader)? {
+        0xfd => read_u16_le(reader)? as u64,
+        0xfe => read_u32_le(reader)? as u64,
+        0xff => read_u64_le(reader)?,
+        value => value as u64,
+    };
+
+    Ok(value)
+}
+
+// Consensus rejects varints that could have been encoded in fewer bytes, e.g. 0xfd 0x05 0x00 for 5
+pub fn read_varint_strict(reader: &mut impl Read) -> Result<u64, ParseError> {
+    let prefix = read_u8(reader)?;
+
+    let value = read_varint(&mut [prefix].as_slice().chain(reader))?;
+
+    let minimum = match prefix {
+        0xfd => 0xfd,
+        0xfe => 0x10000,
+        0xff => 0x1_0000_0000,
+        _ => 0,
+    };
+    if value < minimum {
+        return Err(ParseError::NonCanonicalVarInt(value));
+    }
+
+    Ok(value)
+}
+
+// A varint length followed by that many bytes, as used for scripts
+pub fn read_var_bytes(reader: &mut impl Read) -> Result<Vec<u8>, ParseError> {
+    let length = read_varint(reader)?;
+
+    // Read through take() so a bogus length cannot make us allocate more than the input holds
+    let mut bytes = Vec::new();
+    reader.take(length).read_to_end(&mut bytes)?;
+    if (bytes.len() as u64) < length {
+        return Err(ParseError::UnexpectedEof);
+    }
+
+    Ok(bytes)
+}
+
+pub fn write_var_bytes(writer: &mut impl Write, bytes: &[u8]) -> Result<(), ParseError> {
+    writer.write_all(&encode_varint(bytes.len() as u64))?;
+    writer.write_all(bytes)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::parse_error::ParseError;
+    use crate::encoding::varint::{encode_varint, read_var_bytes, read_varint, read_varint_strict, write_var_bytes};
+
+    #[test]
+    fn boundary_values_encode_to_the_expected_bytes() {
+        let vectors: [(u64, &[u8]); 8] = [
+            (0, &[0x00]),
+            (252, &[0xfc]),
+            (253, &[0xfd, 0xfd, 0x00]),
+            (65535, &[0xfd, 0xff, 0xff]),
+            (65536, &[0xfe, 0x00, 0x00, 0x01, 0x00]),
+            (0xffff_ffff, &[0xfe, 0xff, 0xff, 0xff, 0xff]),
+            (0x1_0000_0000, &[0xff, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]),
+            (u64::MAX, &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+        ];
+
+        for (value, expected) in vectors {
+            assert_eq!(encode_varint(value), expected, "value {}", value);
+        }
+    }
+
+    #[test]
+    fn boundary_values_round_trip() {
+        for value in [0, 1, 252, 253, 254, 65535, 65536, 0xffff_ffff, 0x1_0000_0000, u64::MAX] {
+            let bytes = encode_varint(value);
+
+            assert_eq!(read_varint(&mut bytes.as_slice()), Ok(value));
+            assert_eq!(read_varint_strict(&mut bytes.as_slice()), Ok(value));
+        }
+    }
+
+    #[test]
+    fn reading_consumes_only_the_varint() {
+        let bytes = [0xfd, 0x00, 0x01, 0xaa];
+        let mut reader = bytes.as_slice();
+
+        let result = read_varint(&mut reader);
+
+        assert_eq!(result, Ok(256));
+        assert_eq!(reader, &[0xaa]);
+    }
+
+    #[test]
+    fn truncated_streams_are_an_error() {
+        assert_eq!(read_varint(&mut [].as_slice()), Err(ParseError::UnexpectedEof));
+        assert_eq!(read_varint(&mut [0xfd, 0x01].as_slice()), Err(ParseError::UnexpectedEof));
+        assert_eq!(read_varint(&mut [0xfe, 0x01, 0x02, 0x03].as_slice()), Err(ParseError::UnexpectedEof));
+        assert_eq!(read_varint(&mut [0xff, 0x01].as_slice()), Err(ParseError::UnexpectedEof));
+    }
+
+    #[test]
+    fn strict_mode_rejects_non_canonical_encodings() {
+        assert_eq!(read_varint(&mut [0xfd, 0x05, 0x00].as_slice()), Ok(5));
+        assert_eq!(read_varint_strict(&mut [0xfd, 0x05, 0x00].as_slice()), Err(ParseError::NonCanonicalVarInt(5)));
+        assert_eq!(read_varint_strict(&mut [0xfd, 0xfc, 0x00].as_slice()), Err(ParseError::NonCanonicalVarInt(252)));
+        assert_eq!(read_varint_strict(&mut [0xfe, 0xff, 0xff, 0x00, 0x00].as_slice()), Err(ParseError::NonCanonicalVarInt(65535)));
+        assert_eq!(
+            read_varint_strict(&mut [0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00].as_slice()),
+            Err(ParseError::NonCanonicalVarInt(0xffff_ffff))
+        );
+    }
+
+    #[test]
+    fn var_bytes_round_trip() {
+        let mut buffer = Vec::new();
+        write_var_bytes(&mut buffer, &[0xab; 300]).unwrap();
+
+        assert_eq!(&buffer[..3], &[0xfd, 0x2c, 0x01]);
+        assert_eq!(read_var_bytes(&mut buffer.as_slice()), Ok(vec![0xab; 300]));
+    }
+
+    #[test]
+    fn var_bytes_longer_than_the_input_are_an_error() {
+        assert_eq!(read_var_bytes(&mut [0x03, 0x01, 0x02].as_slice()), Err(ParseError::UnexpectedEof));
+        assert_eq!(
+            read_var_bytes(&mut [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01].as_slice()),
+            Err(ParseError::UnexpectedEof)
+        );
+    }
+}
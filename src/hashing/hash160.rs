@@ -0,0 +1,73 @@
+use std::io;
+use std::io::Read;
+use crate::hashing::ripemd160::ripemd160;
+use crate::hashing::sha256::{sha256, sha256_reader};
+
+// hash160 is ripemd160(sha256(x)), the 20-byte hash inside P2PKH and P2WPKH outputs
+pub fn hash160(data: &[u8]) -> [u8; 20] {
+    ripemd160(&sha256(data))
+}
+
+pub fn hash160_reader(reader: &mut impl Read) -> io::Result<[u8; 20]> {
+    Ok(ripemd160(&sha256_reader(reader)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::io::Read;
+    use crate::encoding::hex;
+    use crate::hashing::hash160::{hash160, hash160_reader};
+
+    #[test]
+    fn hash160_of_empty_input() {
+        let result = hash160(b"");
+
+        assert_eq!(&hex::encode(&result), "b472a266d0bd89c13706a4132ccfb16f7c3b9fcb");
+    }
+
+    #[test]
+    fn hash160_of_uncompressed_generator_point() {
+        let sec = hex::decode(
+            "0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798\
+             483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8"
+        ).unwrap();
+
+        let result = hash160(&sec);
+
+        assert_eq!(&hex::encode(&result), "91b24bf9f5288532960ac687abb035127b1d28a5");
+    }
+
+    #[test]
+    fn hash160_of_compressed_generator_point() {
+        let sec = hex::decode("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap();
+
+        let result = hash160(&sec);
+
+        assert_eq!(&hex::encode(&result), "751e76e8199196d454941c45d1b3a323f1433bd6");
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk on fire"))
+        }
+    }
+
+    #[test]
+    fn streaming_a_multi_megabyte_reader_matches_one_shot_hash() {
+        let data: Vec<u8> = (0..=255u8).cycle().take(2 * 1024 * 1024 + 5).collect();
+
+        let result = hash160_reader(&mut data.as_slice()).unwrap();
+
+        assert_eq!(result, hash160(&data));
+    }
+
+    #[test]
+    fn reader_errors_propagate() {
+        let result = hash160_reader(&mut FailingReader);
+
+        assert_eq!(result.unwrap_err().to_string(), "disk on fire");
+    }
+}
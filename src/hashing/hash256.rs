@@ -0,0 +1,266 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::Read;
+use std::str::FromStr;
+use crate::encoding::hex;
+use crate::encoding::hex::HexError;
+use crate::hashing::sha256::Sha256;
+
+// hash256 is sha256 applied twice: txids, block hashes, sighashes and base58check checksums all use it
+pub fn hash256(data: &[u8]) -> [u8; 32] {
+    let mut engine = Hash256Engine::new();
+    engine.update(data);
+
+    engine.finalize()
+}
+
+pub fn hash256_reader(reader: &mut impl Read) -> io::Result<[u8; 32]> {
+    let mut engine = Hash256Engine::new();
+    engine.inner.update_from_reader(reader)?;
+
+    Ok(engine.finalize())
+}
+
+// Streams input into the inner sha256, the outer one only ever sees the 32-byte inner digest
+#[derive(Debug, Clone, Default)]
+pub struct Hash256Engine {
+    inner: Sha256,
+}
+
+impl Hash256Engine {
+    pub fn new() -> Self {
+        Self {
+            inner: Sha256::new(),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    pub fn finalize(self) -> [u8; 32] {
+        let mut outer = Sha256::new();
+        outer.update(&self.inner.finalize());
+
+        outer.finalize()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Hash256Error {
+    Hex(HexError),
+    InvalidLength(usize),
+}
+
+impl Display for Hash256Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Hash256Error::Hex(error) => write!(f, "{}", error),
+            Hash256Error::InvalidLength(length) => write!(f, "Hash must be 32 bytes, got {}.", length),
+        }
+    }
+}
+
+impl std::error::Error for Hash256Error {}
+
+impl From<HexError> for Hash256Error {
+    fn from(error: HexError) -> Self {
+        Hash256Error::Hex(error)
+    }
+}
+
+/*
+    A hash256 output, stored in wire order (the order hash256 produces and the order it is
+    serialized in). Txids, block hashes and merkle roots are conventionally displayed
+    byte-reversed, which is what Display and FromStr use, so the genesis block hash shows
+    as 000000000019d6... while its wire bytes end in zeros.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Hash256([u8; 32]);
+
+impl Hash256 {
+    pub fn hash(data: &[u8]) -> Self {
+        Self(hash256(data))
+    }
+
+    pub fn from_wire_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn to_wire_bytes(&self) -> [u8; 32] {
+        self.0
+    }
+
+    pub fn from_display_bytes(mut bytes: [u8; 32]) -> Self {
+        bytes.reverse();
+
+        Self(bytes)
+    }
+
+    pub fn to_display_bytes(&self) -> [u8; 32] {
+        let mut bytes = self.0;
+        bytes.reverse();
+
+        bytes
+    }
+
+    pub fn reversed(&self) -> Self {
+        Self(self.to_display_bytes())
+    }
+}
+
+impl AsRef<[u8]> for Hash256 {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Display for Hash256 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(&self.to_display_bytes()))
+    }
+}
+
+impl FromStr for Hash256 {
+    type Err = Hash256Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(s)?;
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|bytes: Vec<u8>| Hash256Error::InvalidLength(bytes.len()))?;
+
+        Ok(Self::from_display_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::io::Read;
+    use std::str::FromStr;
+    use crate::encoding::hex;
+    use crate::encoding::hex::HexError;
+    use crate::encoding::little_endian::write_u32_le;
+    use crate::hashing::hash256::{hash256, hash256_reader, Hash256, Hash256Engine, Hash256Error};
+
+    #[test]
+    fn hash256_of_empty_input() {
+        let result = hash256(b"");
+
+        assert_eq!(&hex::encode(&result), "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456");
+    }
+
+    #[test]
+    fn hash256_of_hello_world() {
+        let result = hash256(b"hello world");
+
+        assert_eq!(&hex::encode(&result), "bc62d4b80d9e36da29c16c5d4d9f11731f36052c72401a76c23c0fb5a9b74423");
+    }
+
+    #[test]
+    fn hash256_of_genesis_block_header_is_the_genesis_block_hash() {
+        let header = hex::decode(
+            "01000000000000000000000000000000000000000000000000000000000000000000000\
+             03ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f\
+             49ffff001d1dac2b7c"
+        ).unwrap();
+
+        let mut result = hash256(&header);
+        result.reverse();
+
+        assert_eq!(&hex::encode(&result), "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f");
+    }
+
+    #[test]
+    fn engine_matches_one_shot_hash_when_input_is_split() {
+        let data = [0xabu8; 200];
+
+        for split in [0, 1, 55, 56, 63, 64, 65, 128, 199, 200] {
+            let mut engine = Hash256Engine::new();
+            engine.update(&data[..split]);
+            engine.update(&data[split..]);
+
+            assert_eq!(engine.finalize(), hash256(&data), "split at {}", split);
+        }
+    }
+
+    const GENESIS_HEADER: &str = "01000000000000000000000000000000000000000000000000000000000000000000000\
+                                  03ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f\
+                                  49ffff001d1dac2b7c";
+    const GENESIS_HASH: &str = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+
+    #[test]
+    fn genesis_block_hash_displays_with_leading_zeros() {
+        let subject = Hash256::hash(&hex::decode(GENESIS_HEADER).unwrap());
+
+        assert_eq!(&subject.to_string(), GENESIS_HASH);
+        assert_eq!(&subject.to_wire_bytes()[27..], &[0; 5]);
+    }
+
+    #[test]
+    fn displayed_form_parses_back_to_the_wire_bytes() {
+        let wire_bytes = hash256(&hex::decode(GENESIS_HEADER).unwrap());
+
+        let subject = Hash256::from_str(GENESIS_HASH).unwrap();
+
+        assert_eq!(subject.to_wire_bytes(), wire_bytes);
+        assert_eq!(subject, Hash256::from_wire_bytes(wire_bytes));
+    }
+
+    #[test]
+    fn prevout_serialization_uses_wire_order() {
+        // An outpoint is serialized as the txid's wire bytes followed by the output index
+        let txid = Hash256::from_str("d1c789a9c60383bf715f3f6ad9d14b91fe55f3deb369fe5d9280cb1a01793f81").unwrap();
+        let mut prevout = Vec::new();
+        prevout.extend_from_slice(&txid.to_wire_bytes());
+        write_u32_le(&mut prevout, 0).unwrap();
+
+        assert_eq!(
+            &hex::encode(&prevout),
+            "813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d100000000"
+        );
+    }
+
+    #[test]
+    fn reversal_helpers_are_inverses() {
+        let subject = Hash256::hash(b"hello world");
+
+        assert_eq!(subject.reversed().reversed(), subject);
+        assert_eq!(subject.reversed().to_wire_bytes(), subject.to_display_bytes());
+        assert_eq!(Hash256::from_display_bytes(subject.to_display_bytes()), subject);
+    }
+
+    #[test]
+    fn malformed_strings_are_rejected() {
+        assert_eq!(Hash256::from_str("00"), Err(Hash256Error::InvalidLength(1)));
+        assert_eq!(
+            Hash256::from_str(&"zz".repeat(32)),
+            Err(Hash256Error::Hex(HexError::InvalidCharacter { character: 'z', position: 0 }))
+        );
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk on fire"))
+        }
+    }
+
+    #[test]
+    fn streaming_a_multi_megabyte_reader_matches_one_shot_hash() {
+        let data: Vec<u8> = (0..=255u8).cycle().take(5 * 1024 * 1024 + 3).collect();
+
+        let result = hash256_reader(&mut data.as_slice()).unwrap();
+
+        assert_eq!(result, hash256(&data));
+    }
+
+    #[test]
+    fn reader_errors_propagate() {
+        let result = hash256_reader(&mut FailingReader);
+
+        assert_eq!(result.unwrap_err().to_string(), "disk on fire");
+    }
+}
@@ -0,0 +1,270 @@
+use crate::constant_time::ct_eq_bytes;
+use crate::hashing::sha256::{sha256, Sha256};
+use crate::hashing::sha512::{sha512, Sha512};
+
+// HMAC (RFC 2104): H((K ^ opad) || H((K ^ ipad) || message)), with K padded to the hash's block size
+const SHA256_BLOCK_SIZE: usize = 64;
+const SHA512_BLOCK_SIZE: usize = 128;
+const IPAD: u8 = 0x36;
+const OPAD: u8 = 0x5c;
+
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut hmac = HmacSha256::new(key);
+    hmac.update(message);
+
+    hmac.finalize()
+}
+
+#[derive(Debug, Clone)]
+pub struct HmacSha256 {
+    inner: Sha256,
+    outer: Sha256,
+}
+
+impl HmacSha256 {
+    pub fn new(key: &[u8]) -> Self {
+        // Keys longer than a block are hashed first, shorter keys are zero padded
+        let mut block_key = [0u8; SHA256_BLOCK_SIZE];
+        match key.len() > SHA256_BLOCK_SIZE {
+            true => block_key[..32].copy_from_slice(&sha256(key)),
+            false => block_key[..key.len()].copy_from_slice(key),
+        }
+
+        let mut inner = Sha256::new();
+        inner.update(&block_key.map(|byte| byte ^ IPAD));
+
+        let mut outer = Sha256::new();
+        outer.update(&block_key.map(|byte| byte ^ OPAD));
+
+        Self { inner, outer }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    pub fn finalize(self) -> [u8; 32] {
+        let mut outer = self.outer;
+        outer.update(&self.inner.finalize());
+
+        outer.finalize()
+    }
+
+    // Checks a received tag (possibly truncated) without leaking how many bytes matched
+    pub fn verify(self, tag: &[u8]) -> bool {
+        let expected = self.finalize();
+
+        !tag.is_empty() && tag.len() <= expected.len() && ct_eq_bytes(&expected[..tag.len()], tag)
+    }
+}
+
+pub fn hmac_sha512(key: &[u8], message: &[u8]) -> [u8; 64] {
+    let mut hmac = HmacSha512::new(key);
+    hmac.update(message);
+
+    hmac.finalize()
+}
+
+#[derive(Debug, Clone)]
+pub struct HmacSha512 {
+    inner: Sha512,
+    outer: Sha512,
+}
+
+impl HmacSha512 {
+    pub fn new(key: &[u8]) -> Self {
+        let mut block_key = [0u8; SHA512_BLOCK_SIZE];
+        match key.len() > SHA512_BLOCK_SIZE {
+            true => block_key[..64].copy_from_slice(&sha512(key)),
+            false => block_key[..key.len()].copy_from_slice(key),
+        }
+
+        let mut inner = Sha512::new();
+        inner.update(&block_key.map(|byte| byte ^ IPAD));
+
+        let mut outer = Sha512::new();
+        outer.update(&block_key.map(|byte| byte ^ OPAD));
+
+        Self { inner, outer }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    pub fn finalize(self) -> [u8; 64] {
+        let mut outer = self.outer;
+        outer.update(&self.inner.finalize());
+
+        outer.finalize()
+    }
+
+    // Checks a received tag (possibly truncated) without leaking how many bytes matched
+    pub fn verify(self, tag: &[u8]) -> bool {
+        let expected = self.finalize();
+
+        !tag.is_empty() && tag.len() <= expected.len() && ct_eq_bytes(&expected[..tag.len()], tag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::hex;
+    use crate::hashing::hmac::{hmac_sha256, hmac_sha512, HmacSha256, HmacSha512};
+
+    #[test]
+    fn rfc4231_test_case_1() {
+        let result = hmac_sha256(&[0x0b; 20], b"Hi There");
+
+        assert_eq!(&hex::encode(&result), "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7");
+    }
+
+    #[test]
+    fn rfc4231_test_case_2_key_shorter_than_output() {
+        let result = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+
+        assert_eq!(&hex::encode(&result), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    }
+
+    #[test]
+    fn rfc4231_test_case_3() {
+        let result = hmac_sha256(&[0xaa; 20], &[0xdd; 50]);
+
+        assert_eq!(&hex::encode(&result), "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe");
+    }
+
+    #[test]
+    fn rfc4231_test_case_4() {
+        let key: Vec<u8> = (1..=25).collect();
+
+        let result = hmac_sha256(&key, &[0xcd; 50]);
+
+        assert_eq!(&hex::encode(&result), "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b");
+    }
+
+    #[test]
+    fn rfc4231_test_case_5_truncated_output() {
+        let result = hmac_sha256(&[0x0c; 20], b"Test With Truncation");
+
+        assert_eq!(&hex::encode(&result[..16]), "a3b6167473100ee06e0c796c2955552b");
+    }
+
+    #[test]
+    fn rfc4231_test_case_6_key_larger_than_block_size() {
+        let result = hmac_sha256(&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First");
+
+        assert_eq!(&hex::encode(&result), "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54");
+    }
+
+    #[test]
+    fn rfc4231_test_case_7_key_and_data_larger_than_block_size() {
+        let message = "This is a test using a larger than block-size key and a larger than block-size data. \
+                       The key needs to be hashed before being used by the HMAC algorithm.";
+
+        let result = hmac_sha256(&[0xaa; 131], message.as_bytes());
+
+        assert_eq!(&hex::encode(&result), "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2");
+    }
+
+    #[test]
+    fn tags_are_verified_including_truncated_ones() {
+        let tag = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        let mut corrupted = tag;
+        corrupted[31] ^= 0x01;
+
+        let verify = |candidate: &[u8]| {
+            let mut hmac = HmacSha256::new(b"Jefe");
+            hmac.update(b"what do ya want for nothing?");
+            hmac.verify(candidate)
+        };
+
+        assert!(verify(&tag));
+        assert!(verify(&tag[..16]));
+        assert!(!verify(&corrupted));
+        assert!(!verify(&[]));
+
+        let tag = hmac_sha512(b"Jefe", b"what do ya want for nothing?");
+        let mut hmac = HmacSha512::new(b"Jefe");
+        hmac.update(b"what do ya want for nothing?");
+        assert!(hmac.verify(&tag));
+    }
+
+    #[test]
+    fn multiple_updates_match_one_shot_hmac() {
+        let mut hmac = HmacSha256::new(b"Jefe");
+        hmac.update(b"what do ya ");
+        hmac.update(b"want ");
+        hmac.update(b"for nothing?");
+
+        assert_eq!(hmac.finalize(), hmac_sha256(b"Jefe", b"what do ya want for nothing?"));
+    }
+
+    #[test]
+    fn rfc4231_hmac_sha512_test_cases() {
+        let long_message = "This is a test using a larger than block-size key and a larger than block-size data. \
+                            The key needs to be hashed before being used by the HMAC algorithm.";
+        let case_4_key: Vec<u8> = (1..=25).collect();
+        let vectors: [(&[u8], &[u8], &str); 6] = [
+            (
+                &[0x0b; 20],
+                b"Hi There",
+                "87aa7cdea5ef619d4ff0b4241a1d6cb02379f4e2ce4ec2787ad0b30545e17cde\
+                 daa833b7d6b8a702038b274eaea3f4e4be9d914eeb61f1702e696c203a126854",
+            ),
+            (
+                b"Jefe",
+                b"what do ya want for nothing?",
+                "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea250554\
+                 9758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737",
+            ),
+            (
+                &[0xaa; 20],
+                &[0xdd; 50],
+                "fa73b0089d56a284efb0f0756c890be9b1b5dbdd8ee81a3655f83e33b2279d39\
+                 bf3e848279a722c806b485a47e67c807b946a337bee8942674278859e13292fb",
+            ),
+            (
+                &case_4_key,
+                &[0xcd; 50],
+                "b0ba465637458c6990e5a8c5f61d4af7e576d97ff94b872de76f8050361ee3db\
+                 a91ca5c11aa25eb4d679275cc5788063a5f19741120c4f2de2adebeb10a298dd",
+            ),
+            (
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+                "80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f352\
+                 6b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598",
+            ),
+            (
+                &[0xaa; 131],
+                long_message.as_bytes(),
+                "e37b6a775dc87dbaa4dfa9f96e5e3ffddebd71f8867289865df5a32d20cdc944\
+                 b6022cac3c4982b10d5eeb55c3e4de15134676fb6de0446065c97440fa8c6a58",
+            ),
+        ];
+
+        for (key, message, expected) in vectors {
+            assert_eq!(&hex::encode(&hmac_sha512(key, message)), expected);
+        }
+    }
+
+    #[test]
+    fn rfc4231_hmac_sha512_test_case_5_truncated_output() {
+        let result = hmac_sha512(&[0x0c; 20], b"Test With Truncation");
+
+        assert_eq!(&hex::encode(&result[..16]), "415fad6271580a531d4179bc891d87a6");
+    }
+
+    #[test]
+    fn bip32_master_key_from_test_vector_1_seed() {
+        // BIP32 splits HMAC-SHA512("Bitcoin seed", seed) into the master secret and chain code
+        let seed: Vec<u8> = (0..16).collect();
+
+        let mut hmac = HmacSha512::new(b"Bitcoin seed");
+        hmac.update(&seed);
+        let result = hmac.finalize();
+
+        assert_eq!(&hex::encode(&result[..32]), "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35");
+        assert_eq!(&hex::encode(&result[32..]), "873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508");
+    }
+}
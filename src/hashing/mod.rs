@@ -0,0 +1,9 @@
+pub mod hash160;
+pub mod hash256;
+pub mod hmac;
+pub mod murmur3;
+pub mod pbkdf2;
+pub mod ripemd160;
+pub mod sha256;
+pub mod sha512;
+pub mod tagged_hash;
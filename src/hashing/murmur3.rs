@@ -0,0 +1,101 @@
+// MurmurHash3 x86 32-bit, as used by BIP37 bloom filters
+const C1: u32 = 0xcc9e2d51;
+const C2: u32 = 0x1b873593;
+
+// BIP37 seeds hash function i with i * 0xfba4c795 + nTweak
+pub const BIP37_SEED_MULTIPLIER: u32 = 0xfba4c795;
+
+pub fn murmur3_32(data: &[u8], seed: u32) -> u32 {
+    let mut hash = seed;
+
+    let mut blocks = data.chunks_exact(4);
+    for block in &mut blocks {
+        let k = u32::from_le_bytes(block.try_into().unwrap());
+
+        hash ^= scramble(k);
+        hash = hash.rotate_left(13).wrapping_mul(5).wrapping_add(0xe6546b64);
+    }
+
+    // The 1 to 3 trailing bytes are packed little-endian and mixed in without the rotate-multiply-add step
+    let tail = blocks.remainder();
+    if !tail.is_empty() {
+        let k = tail
+            .iter()
+            .enumerate()
+            .fold(0u32, |k, (i, &byte)| k | (byte as u32) << (8 * i));
+
+        hash ^= scramble(k);
+    }
+
+    hash ^= data.len() as u32;
+    finalize(hash)
+}
+
+fn scramble(k: u32) -> u32 {
+    k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2)
+}
+
+// Final avalanche so every input bit affects every output bit
+fn finalize(mut hash: u32) -> u32 {
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85ebca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2ae35);
+    hash ^= hash >> 16;
+
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::hex;
+    use crate::hashing::murmur3::{murmur3_32, BIP37_SEED_MULTIPLIER};
+
+    fn bip37_bit_indexes(item: &[u8], function_count: u32, tweak: u32, bit_field_size: u32) -> Vec<u32> {
+        (0..function_count)
+            .map(|i| {
+                let seed = i.wrapping_mul(BIP37_SEED_MULTIPLIER).wrapping_add(tweak);
+                murmur3_32(item, seed) % bit_field_size
+            })
+            .collect()
+    }
+
+    #[test]
+    fn standard_test_vectors() {
+        let vectors: [(&[u8], u32, u32); 10] = [
+            (b"", 0, 0),
+            (b"", 1, 0x514e28b7),
+            (b"", 0xffffffff, 0x81f16f39),
+            (&[0, 0, 0, 0], 0, 0x2362f9de),
+            (b"aaaa", 0x9747b28c, 0x5a97808a),
+            (b"a", 0, 0x3c2569b2),
+            (b"ab", 0, 0x9bbfd75f),
+            (b"abc", 0, 0xb3dd93fa),
+            (b"Hello, world!", 0x9747b28c, 0x24884cba),
+            (b"The quick brown fox jumps over the lazy dog", 0x9747b28c, 0x2fa826cd),
+        ];
+
+        for (data, seed, expected) in vectors {
+            assert_eq!(murmur3_32(data, seed), expected, "data {:?} seed {:#x}", data, seed);
+        }
+    }
+
+    #[test]
+    fn hello_world_into_a_two_function_filter() {
+        let result = bip37_bit_indexes(b"hello world", 2, 99, 80);
+
+        assert_eq!(result, vec![79, 39]);
+    }
+
+    #[test]
+    fn bip37_filter_from_two_items_and_five_functions() {
+        let mut bit_field = [0u8; 10];
+        for item in [&b"Hello World"[..], b"Goodbye!"] {
+            for bit in bip37_bit_indexes(item, 5, 99, 80) {
+                bit_field[bit as usize / 8] |= 1 << (bit % 8);
+            }
+        }
+
+        assert_eq!(&hex::encode(&bit_field), "4000600a080000010940");
+    }
+}
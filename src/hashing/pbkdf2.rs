@@ -0,0 +1,207 @@
+use crate::hashing::hmac::{HmacSha256, HmacSha512};
+
+// The pseudorandom function PBKDF2 iterates. Cloning a keyed PRF lets the key schedule be done once
+// instead of on every iteration.
+pub trait Prf: Clone {
+    const OUTPUT_SIZE: usize;
+
+    fn new(key: &[u8]) -> Self;
+
+    fn update(&mut self, data: &[u8]);
+
+    fn finalize_into(self, out: &mut [u8]);
+}
+
+impl Prf for HmacSha256 {
+    const OUTPUT_SIZE: usize = 32;
+
+    fn new(key: &[u8]) -> Self {
+        HmacSha256::new(key)
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        HmacSha256::update(self, data)
+    }
+
+    fn finalize_into(self, out: &mut [u8]) {
+        out.copy_from_slice(&self.finalize())
+    }
+}
+
+impl Prf for HmacSha512 {
+    const OUTPUT_SIZE: usize = 64;
+
+    fn new(key: &[u8]) -> Self {
+        HmacSha512::new(key)
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        HmacSha512::update(self, data)
+    }
+
+    fn finalize_into(self, out: &mut [u8]) {
+        out.copy_from_slice(&self.finalize())
+    }
+}
+
+pub fn pbkdf2_hmac_sha256(password: &[u8], salt: &[u8], iterations: u32, out: &mut [u8]) {
+    pbkdf2::<HmacSha256>(password, salt, iterations, out)
+}
+
+// BIP39 uses this with 2048 iterations to stretch a mnemonic into a 64-byte seed
+pub fn pbkdf2_hmac_sha512(password: &[u8], salt: &[u8], iterations: u32, out: &mut [u8]) {
+    pbkdf2::<HmacSha512>(password, salt, iterations, out)
+}
+
+pub fn pbkdf2<P: Prf>(password: &[u8], salt: &[u8], iterations: u32, out: &mut [u8]) {
+    /*
+        RFC 8018 section 5.2: the output is split into blocks T_1, T_2, ... of the PRF's size.
+
+        T_i = U_1 ^ U_2 ^ ... ^ U_c
+        U_1 = PRF(P, S || INT(i))
+        U_j = PRF(P, U_{j-1})
+
+        The last block is truncated to fill the requested length.
+     */
+    if iterations == 0 {
+        panic!("PBKDF2 requires at least one iteration.");
+    }
+
+    let keyed_prf = P::new(password);
+    let mut u = vec![0u8; P::OUTPUT_SIZE];
+    let mut block = vec![0u8; P::OUTPUT_SIZE];
+
+    for (index, chunk) in out.chunks_mut(P::OUTPUT_SIZE).enumerate() {
+        let block_number = index as u32 + 1;
+
+        let mut prf = keyed_prf.clone();
+        prf.update(salt);
+        prf.update(&block_number.to_be_bytes());
+        prf.finalize_into(&mut u);
+        block.copy_from_slice(&u);
+
+        for _ in 1..iterations {
+            let mut prf = keyed_prf.clone();
+            prf.update(&u);
+            prf.finalize_into(&mut u);
+
+            for (accumulated, byte) in block.iter_mut().zip(&u) {
+                *accumulated ^= byte;
+            }
+        }
+
+        chunk.copy_from_slice(&block[..chunk.len()]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::hex;
+    use crate::hashing::pbkdf2::{pbkdf2_hmac_sha256, pbkdf2_hmac_sha512};
+
+    #[test]
+    fn pbkdf2_hmac_sha256_vectors() {
+        let vectors = [
+            ("password", "salt", 1, 32, "120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b"),
+            ("password", "salt", 4096, 32, "c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a"),
+            (
+                "passwordPASSWORDpassword",
+                "saltSALTsaltSALTsaltSALTsaltSALTsalt",
+                4096,
+                40,
+                "348c89dbcbd32b2f32d814b8116e84cf2b17347ebc1800181c4e2a1fb8dd53e1c635518c7dac47e9",
+            ),
+            ("pass\0word", "sa\0lt", 4096, 16, "89b69d0516f829893c696226650a8687"),
+        ];
+
+        for (password, salt, iterations, length, expected) in vectors {
+            let mut out = vec![0u8; length];
+
+            pbkdf2_hmac_sha256(password.as_bytes(), salt.as_bytes(), iterations, &mut out);
+
+            assert_eq!(&hex::encode(&out), expected);
+        }
+    }
+
+    #[test]
+    fn pbkdf2_hmac_sha512_vectors() {
+        let vectors: [(&[u8], &[u8], u32, &str); 3] = [
+            (
+                b"password",
+                b"salt",
+                1,
+                "867f70cf1ade02cff3752599a3a53dc4af34c7a669815ae5d513554e1c8cf252\
+                 c02d470a285a0501bad999bfe943c08f050235d7d68b1da55e63f73b60a57fce",
+            ),
+            (
+                b"password",
+                b"salt",
+                2,
+                "e1d9c16aa681708a45f5c7c4e215ceb66e011a2e9f0040713f18aefdb866d53c\
+                 f76cab2868a39b9f7840edce4fef5a82be67335c77a6068e04112754f27ccf4e",
+            ),
+            (
+                b"passwordPASSWORDpassword",
+                b"saltSALTsaltSALTsaltSALTsaltSALTsalt",
+                4096,
+                "8c0511f4c6e597c6ac6315d8f0362e225f3c501495ba23b868c005174dc4ee71\
+                 115b59f9e60cd9532fa33e0f75aefe30225c583a186cd82bd4daea9724a3d3b8",
+            ),
+        ];
+
+        for (password, salt, iterations, expected) in vectors {
+            let mut out = [0u8; 64];
+
+            pbkdf2_hmac_sha512(password, salt, iterations, &mut out);
+
+            assert_eq!(&hex::encode(&out), expected);
+        }
+    }
+
+    #[test]
+    fn multi_block_output_is_truncated_to_requested_length() {
+        let mut out = [0u8; 100];
+
+        pbkdf2_hmac_sha512(b"password", b"salt", 3, &mut out);
+
+        assert_eq!(
+            &hex::encode(&out),
+            "b6b07cb2cebf4ad84468391a543824fccffe0e0769dbe6bddf10a65673c4b648\
+             e612d44918f9ce9a19a1294cf5140628084ba994c3b21a4ef4741220b811c633\
+             cfc0641fccbcc4164f1bbfcb1f33f595ae9aa4a33ddcce570157775980362c0e\
+             e28aa340"
+        );
+    }
+
+    #[test]
+    fn bip39_trezor_seed_vectors() {
+        let vectors = [
+            (
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+                "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e5349553\
+                 1f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+            ),
+            (
+                "legal winner thank year wave sausage worth useful legal winner thank yellow",
+                "2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6f\
+                 a457fe1296106559a3c80937a1c1069be3a3a5bd381ee6260e8d9739fce1f607",
+            ),
+        ];
+
+        for (mnemonic, expected) in vectors {
+            let mut seed = [0u8; 64];
+
+            pbkdf2_hmac_sha512(mnemonic.as_bytes(), b"mnemonicTREZOR", 2048, &mut seed);
+
+            assert_eq!(&hex::encode(&seed), expected);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "PBKDF2 requires at least one iteration.")]
+    fn zero_iterations_are_rejected() {
+        let mut out = [0u8; 32];
+
+        pbkdf2_hmac_sha256(b"password", b"salt", 0, &mut out);
+    }
+}
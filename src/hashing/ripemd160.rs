@@ -0,0 +1,243 @@
+// RIPEMD-160 as specified by Dobbertin, Bosselaers and Preneel. Like SHA-256 it works on
+// 64-byte blocks, but words and the length encoding are little-endian and every block runs
+// through two parallel lines of 80 rounds.
+const BLOCK_SIZE: usize = 64;
+
+const INITIAL_STATE: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+const K_LEFT: [u32; 5] = [0x00000000, 0x5a827999, 0x6ed9eba1, 0x8f1bbcdc, 0xa953fd4e];
+const K_RIGHT: [u32; 5] = [0x50a28be6, 0x5c4dd124, 0x6d703ef3, 0x7a6d76e9, 0x00000000];
+
+// Message word selection for each round
+const R_LEFT: [usize; 80] = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+    7, 4, 13, 1, 10, 6, 15, 3, 12, 0, 9, 5, 2, 14, 11, 8,
+    3, 10, 14, 4, 9, 15, 8, 1, 2, 7, 0, 6, 13, 11, 5, 12,
+    1, 9, 11, 10, 0, 8, 12, 4, 13, 3, 7, 15, 14, 5, 6, 2,
+    4, 0, 5, 9, 7, 12, 2, 10, 14, 1, 3, 8, 11, 6, 15, 13,
+];
+const R_RIGHT: [usize; 80] = [
+    5, 14, 7, 0, 9, 2, 11, 4, 13, 6, 15, 8, 1, 10, 3, 12,
+    6, 11, 3, 7, 0, 13, 5, 10, 14, 15, 8, 12, 4, 9, 1, 2,
+    15, 5, 1, 3, 7, 14, 6, 9, 11, 8, 12, 2, 10, 0, 4, 13,
+    8, 6, 4, 1, 3, 11, 15, 0, 5, 12, 2, 13, 9, 7, 10, 14,
+    12, 15, 10, 4, 1, 5, 8, 7, 6, 2, 13, 14, 0, 3, 9, 11,
+];
+
+// Left rotation amounts for each round
+const S_LEFT: [u32; 80] = [
+    11, 14, 15, 12, 5, 8, 7, 9, 11, 13, 14, 15, 6, 7, 9, 8,
+    7, 6, 8, 13, 11, 9, 7, 15, 7, 12, 15, 9, 11, 7, 13, 12,
+    11, 13, 6, 7, 14, 9, 13, 15, 14, 8, 13, 6, 5, 12, 7, 5,
+    11, 12, 14, 15, 14, 15, 9, 8, 9, 14, 5, 6, 8, 6, 5, 12,
+    9, 15, 5, 11, 6, 8, 13, 12, 5, 12, 13, 14, 11, 8, 5, 6,
+];
+const S_RIGHT: [u32; 80] = [
+    8, 9, 9, 11, 13, 15, 15, 5, 7, 7, 8, 11, 14, 14, 12, 6,
+    9, 13, 15, 7, 12, 8, 9, 11, 7, 7, 12, 7, 6, 15, 13, 11,
+    9, 7, 15, 11, 8, 6, 6, 14, 12, 13, 5, 14, 13, 13, 7, 5,
+    15, 5, 8, 11, 14, 14, 6, 14, 6, 9, 12, 9, 12, 5, 15, 8,
+    8, 5, 12, 9, 12, 5, 14, 6, 8, 13, 6, 5, 15, 13, 11, 11,
+];
+
+fn f(round: usize, x: u32, y: u32, z: u32) -> u32 {
+    match round / 16 {
+        0 => x ^ y ^ z,
+        1 => (x & y) | (!x & z),
+        2 => (x | !y) ^ z,
+        3 => (x & z) | (y & !z),
+        _ => x ^ (y | !z),
+    }
+}
+
+pub fn ripemd160(data: &[u8]) -> [u8; 20] {
+    let mut hasher = Ripemd160::new();
+    hasher.update(data);
+
+    hasher.finalize()
+}
+
+#[derive(Debug, Clone)]
+pub struct Ripemd160 {
+    state: [u32; 5],
+    buffer: [u8; BLOCK_SIZE],
+    buffer_len: usize,
+    length: u64,
+}
+
+impl Ripemd160 {
+    pub fn new() -> Self {
+        Self {
+            state: INITIAL_STATE,
+            buffer: [0; BLOCK_SIZE],
+            buffer_len: 0,
+            length: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.length = self.length.wrapping_add(data.len() as u64);
+
+        if self.buffer_len > 0 {
+            let take = usize::min(BLOCK_SIZE - self.buffer_len, data.len());
+            self.buffer[self.buffer_len..self.buffer_len + take].copy_from_slice(&data[..take]);
+            self.buffer_len += take;
+            data = &data[take..];
+
+            if self.buffer_len < BLOCK_SIZE {
+                return;
+            }
+
+            let block = self.buffer;
+            self.compress(&block);
+            self.buffer_len = 0;
+        }
+
+        let mut blocks = data.chunks_exact(BLOCK_SIZE);
+        for block in &mut blocks {
+            self.compress(block.try_into().unwrap());
+        }
+
+        let remainder = blocks.remainder();
+        self.buffer[..remainder.len()].copy_from_slice(remainder);
+        self.buffer_len = remainder.len();
+    }
+
+    pub fn finalize(mut self) -> [u8; 20] {
+        // Same padding as SHA-256, except the bit length is appended little-endian
+        let bit_length = self.length.wrapping_mul(8);
+
+        let mut padding = [0u8; BLOCK_SIZE + 8];
+        padding[0] = 0x80;
+        let padding_len = match self.buffer_len < 56 {
+            true => 56 - self.buffer_len,
+            false => BLOCK_SIZE + 56 - self.buffer_len,
+        };
+
+        self.update(&padding[..padding_len]);
+        self.update(&bit_length.to_le_bytes());
+
+        let mut digest = [0u8; 20];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+
+        digest
+    }
+
+    fn compress(&mut self, block: &[u8; BLOCK_SIZE]) {
+        let mut x = [0u32; 16];
+        for (i, chunk) in block.chunks_exact(4).enumerate() {
+            x[i] = u32::from_le_bytes(chunk.try_into().unwrap());
+        }
+
+        let [mut al, mut bl, mut cl, mut dl, mut el] = self.state;
+        let [mut ar, mut br, mut cr, mut dr, mut er] = self.state;
+
+        for j in 0..80 {
+            let t = al
+                .wrapping_add(f(j, bl, cl, dl))
+                .wrapping_add(x[R_LEFT[j]])
+                .wrapping_add(K_LEFT[j / 16])
+                .rotate_left(S_LEFT[j])
+                .wrapping_add(el);
+            al = el;
+            el = dl;
+            dl = cl.rotate_left(10);
+            cl = bl;
+            bl = t;
+
+            // The right line runs the boolean functions in reverse order
+            let t = ar
+                .wrapping_add(f(79 - j, br, cr, dr))
+                .wrapping_add(x[R_RIGHT[j]])
+                .wrapping_add(K_RIGHT[j / 16])
+                .rotate_left(S_RIGHT[j])
+                .wrapping_add(er);
+            ar = er;
+            er = dr;
+            dr = cr.rotate_left(10);
+            cr = br;
+            br = t;
+        }
+
+        let [h0, h1, h2, h3, h4] = self.state;
+        self.state = [
+            h1.wrapping_add(cl).wrapping_add(dr),
+            h2.wrapping_add(dl).wrapping_add(er),
+            h3.wrapping_add(el).wrapping_add(ar),
+            h4.wrapping_add(al).wrapping_add(br),
+            h0.wrapping_add(bl).wrapping_add(cr),
+        ];
+    }
+}
+
+impl Default for Ripemd160 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::hex;
+    use crate::hashing::ripemd160::{ripemd160, Ripemd160};
+
+    #[test]
+    fn official_test_vectors() {
+        let vectors = [
+            ("", "9c1185a5c5e9fc54612808977ee8f548b2258d31"),
+            ("a", "0bdc9d2d256b3ee9daae347be6f4dc835a467ffe"),
+            ("abc", "8eb208f7e05d987a9b044a8e98c6b087f15a0bfc"),
+            ("message digest", "5d0689ef49d2fae572b881b123a85ffa21595f36"),
+            ("abcdefghijklmnopqrstuvwxyz", "f71c27109c692c1b56bbdceb5b9d2865b3708dbc"),
+            (
+                "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "12a053384a9c0c88e405a06c27dcf49ada62eb2b",
+            ),
+            (
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+                "b0e20b6e3116640286ed3a87a5713079b21f5189",
+            ),
+        ];
+
+        for (message, expected) in vectors {
+            assert_eq!(&hex::encode(&ripemd160(message.as_bytes())), expected, "message {:?}", message);
+        }
+    }
+
+    #[test]
+    fn eight_times_repeated_digits_vector() {
+        let message = "1234567890".repeat(8);
+
+        let result = ripemd160(message.as_bytes());
+
+        assert_eq!(&hex::encode(&result), "9b752e45573d4b39f4dbd3323cab82bf63326bfb");
+    }
+
+    #[test]
+    fn one_million_a_vector() {
+        let mut hasher = Ripemd160::new();
+        for _ in 0..1000 {
+            hasher.update(&[b'a'; 1000]);
+        }
+
+        let result = hasher.finalize();
+
+        assert_eq!(&hex::encode(&result), "52783243c1697bdbe16d37f97f68f08325dc1528");
+    }
+
+    #[test]
+    fn incremental_updates_match_one_shot_hash() {
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+
+        for chunk_size in [1, 3, 55, 56, 63, 64, 65, 127, 1000] {
+            let mut hasher = Ripemd160::new();
+            for chunk in data.chunks(chunk_size) {
+                hasher.update(chunk);
+            }
+
+            assert_eq!(hasher.finalize(), ripemd160(&data), "chunk size {}", chunk_size);
+        }
+    }
+}
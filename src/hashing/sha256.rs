@@ -0,0 +1,301 @@
+use std::io;
+use std::io::Read;
+
+// SHA-256 as specified in FIPS 180-4. Messages are processed in 64-byte blocks,
+// so the hasher buffers partial input until a full block is available.
+const BLOCK_SIZE: usize = 64;
+
+// Chunk size used when streaming from a reader
+const READ_BUFFER_SIZE: usize = 8192;
+
+// Initial hash value: first 32 bits of the fractional parts of the square roots of the first 8 primes
+const INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a,
+    0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+// Round constants: first 32 bits of the fractional parts of the cube roots of the first 64 primes
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+
+    hasher.finalize()
+}
+
+pub fn sha256_reader(reader: &mut impl Read) -> io::Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    hasher.update_from_reader(reader)?;
+
+    Ok(hasher.finalize())
+}
+
+#[derive(Debug, Clone)]
+pub struct Sha256 {
+    state: [u32; 8],
+    buffer: [u8; BLOCK_SIZE],
+    buffer_len: usize,
+    // Total message length in bytes, needed for the final length encoding
+    length: u64,
+}
+
+impl Sha256 {
+    pub fn new() -> Self {
+        Self {
+            state: INITIAL_STATE,
+            buffer: [0; BLOCK_SIZE],
+            buffer_len: 0,
+            length: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.length = self.length.wrapping_add(data.len() as u64);
+
+        if self.buffer_len > 0 {
+            let take = usize::min(BLOCK_SIZE - self.buffer_len, data.len());
+            self.buffer[self.buffer_len..self.buffer_len + take].copy_from_slice(&data[..take]);
+            self.buffer_len += take;
+            data = &data[take..];
+
+            if self.buffer_len < BLOCK_SIZE {
+                return;
+            }
+
+            let block = self.buffer;
+            self.compress(&block);
+            self.buffer_len = 0;
+        }
+
+        let mut blocks = data.chunks_exact(BLOCK_SIZE);
+        for block in &mut blocks {
+            self.compress(block.try_into().unwrap());
+        }
+
+        let remainder = blocks.remainder();
+        self.buffer[..remainder.len()].copy_from_slice(remainder);
+        self.buffer_len = remainder.len();
+    }
+
+    // Feeds the reader to the hasher until it is exhausted, returning the number of bytes read
+    pub fn update_from_reader(&mut self, reader: &mut impl Read) -> io::Result<u64> {
+        let mut buffer = [0u8; READ_BUFFER_SIZE];
+        let mut total = 0;
+
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => return Ok(total),
+                Ok(read) => {
+                    self.update(&buffer[..read]);
+                    total += read as u64;
+                }
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    pub fn finalize(mut self) -> [u8; 32] {
+        /*
+            Padding: a single 1 bit, then zeros until the length is 56 mod 64,
+            then the message length in bits as a 64-bit big-endian integer.
+         */
+        let bit_length = self.length.wrapping_mul(8);
+
+        let mut padding = [0u8; BLOCK_SIZE + 8];
+        padding[0] = 0x80;
+        let padding_len = match self.buffer_len < 56 {
+            true => 56 - self.buffer_len,
+            false => BLOCK_SIZE + 56 - self.buffer_len,
+        };
+
+        self.update(&padding[..padding_len]);
+        self.update(&bit_length.to_be_bytes());
+
+        let mut digest = [0u8; 32];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+
+        digest
+    }
+
+    fn compress(&mut self, block: &[u8; BLOCK_SIZE]) {
+        let mut w = [0u32; 64];
+        for (i, chunk) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes(chunk.try_into().unwrap());
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let temp1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+        }
+
+        for (word, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::hex;
+    use crate::hashing::hash256::hash256;
+    use std::io;
+    use std::io::Read;
+    use crate::hashing::sha256::{sha256, sha256_reader, Sha256};
+
+    #[test]
+    fn nist_short_message_vectors() {
+        let vectors: [(&[u8], &str); 4] = [
+            (b"", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+            (&[0xbd], "68325720aabd7c82f30f554b313d0570c95accbb7dc4b5aae11204c08ffe732b"),
+            (&[0xc9, 0x8c, 0x8e, 0x55], "7abc22c0ae5af26ce93dbb94433a0e0b2e119d014f8e7f65bd56c61ccccd9504"),
+            (b"abc", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+        ];
+
+        for (message, expected) in vectors {
+            assert_eq!(&hex::encode(&sha256(message)), expected);
+        }
+    }
+
+    #[test]
+    fn nist_multi_block_message_vectors() {
+        let vectors = [
+            (
+                "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            ),
+            (
+                "abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmno\
+                 ijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+                "cf5b16a778af8380036ce59e7b0492370b249b11e8f07a51afac45037afee9d1",
+            ),
+        ];
+
+        for (message, expected) in vectors {
+            assert_eq!(&hex::encode(&sha256(message.as_bytes())), expected);
+        }
+    }
+
+    #[test]
+    fn one_million_a_vector() {
+        let mut hasher = Sha256::new();
+        for _ in 0..1000 {
+            hasher.update(&[b'a'; 1000]);
+        }
+
+        let result = hasher.finalize();
+
+        assert_eq!(&hex::encode(&result), "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0");
+    }
+
+    #[test]
+    fn incremental_updates_match_one_shot_hash() {
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+
+        for chunk_size in [1, 3, 55, 56, 63, 64, 65, 127, 1000] {
+            let mut hasher = Sha256::new();
+            for chunk in data.chunks(chunk_size) {
+                hasher.update(chunk);
+            }
+
+            assert_eq!(hasher.finalize(), sha256(&data), "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn hash256_is_sha256_applied_twice() {
+        let message = b"hello world";
+
+        assert_eq!(hash256(message), sha256(&sha256(message)));
+    }
+
+    // Yields a deterministic byte pattern without holding it in memory
+    struct PatternReader {
+        remaining: usize,
+        counter: u8,
+    }
+
+    impl Read for PatternReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let count = usize::min(self.remaining, usize::min(buf.len(), 1000));
+            for byte in &mut buf[..count] {
+                *byte = self.counter;
+                self.counter = self.counter.wrapping_mul(31).wrapping_add(7);
+            }
+            self.remaining -= count;
+
+            Ok(count)
+        }
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk on fire"))
+        }
+    }
+
+    #[test]
+    fn streaming_a_multi_megabyte_reader_matches_one_shot_hash() {
+        let length = 3 * 1024 * 1024 + 17;
+        let mut expected = Vec::with_capacity(length);
+        PatternReader { remaining: length, counter: 1 }.read_to_end(&mut expected).unwrap();
+
+        let result = sha256_reader(&mut PatternReader { remaining: length, counter: 1 }).unwrap();
+
+        assert_eq!(result, sha256(&expected));
+    }
+
+    #[test]
+    fn reader_errors_propagate() {
+        let result = sha256_reader(&mut FailingReader);
+
+        assert_eq!(result.unwrap_err().to_string(), "disk on fire");
+    }
+}
@@ -0,0 +1,115 @@
+use std::sync::OnceLock;
+use crate::hashing::sha256::{sha256, Sha256};
+
+/*
+    BIP340 tagged hash: sha256(sha256(tag) || sha256(tag) || msg)
+
+    The tag prefix is exactly one 64-byte block, so a hasher that has absorbed it
+    can be cloned as a midstate. This is cached for the tags Schnorr, Taproot and MuSig2 use.
+ */
+pub const BIP340_AUX: &str = "BIP0340/aux";
+pub const BIP340_NONCE: &str = "BIP0340/nonce";
+pub const BIP340_CHALLENGE: &str = "BIP0340/challenge";
+pub const TAP_TWEAK: &str = "TapTweak";
+pub const TAP_LEAF: &str = "TapLeaf";
+pub const TAP_BRANCH: &str = "TapBranch";
+pub const KEYAGG_LIST: &str = "KeyAgg list";
+pub const KEYAGG_COEFFICIENT: &str = "KeyAgg coefficient";
+
+pub fn tagged_hash(tag: &str, msg: &[u8]) -> [u8; 32] {
+    let mut engine = tagged_hash_engine(tag);
+    engine.update(msg);
+
+    engine.finalize()
+}
+
+// A Sha256 that has already absorbed the tag prefix, ready for the message
+pub fn tagged_hash_engine(tag: &str) -> Sha256 {
+    static AUX: OnceLock<Sha256> = OnceLock::new();
+    static NONCE: OnceLock<Sha256> = OnceLock::new();
+    static CHALLENGE: OnceLock<Sha256> = OnceLock::new();
+    static TWEAK: OnceLock<Sha256> = OnceLock::new();
+    static LEAF: OnceLock<Sha256> = OnceLock::new();
+    static BRANCH: OnceLock<Sha256> = OnceLock::new();
+    static LIST: OnceLock<Sha256> = OnceLock::new();
+    static COEFFICIENT: OnceLock<Sha256> = OnceLock::new();
+
+    let cache = match tag {
+        BIP340_AUX => &AUX,
+        BIP340_NONCE => &NONCE,
+        BIP340_CHALLENGE => &CHALLENGE,
+        TAP_TWEAK => &TWEAK,
+        TAP_LEAF => &LEAF,
+        TAP_BRANCH => &BRANCH,
+        KEYAGG_LIST => &LIST,
+        KEYAGG_COEFFICIENT => &COEFFICIENT,
+        _ => return uncached_tagged_hash_engine(tag),
+    };
+
+    cache.get_or_init(|| uncached_tagged_hash_engine(tag)).clone()
+}
+
+fn uncached_tagged_hash_engine(tag: &str) -> Sha256 {
+    let tag_hash = sha256(tag.as_bytes());
+
+    let mut engine = Sha256::new();
+    engine.update(&tag_hash);
+    engine.update(&tag_hash);
+
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::hex;
+    use crate::hashing::sha256::sha256;
+    use crate::hashing::tagged_hash::{
+        tagged_hash, uncached_tagged_hash_engine, BIP340_AUX, BIP340_CHALLENGE, BIP340_NONCE, TAP_BRANCH, TAP_LEAF,
+        TAP_TWEAK,
+    };
+
+    #[test]
+    fn tap_tweak_matches_bip341_wallet_vector() {
+        // Key-path-only output: the tweak is TapTweak(internal key) with no merkle root
+        let internal_key = hex::decode("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d").unwrap();
+
+        let result = tagged_hash(TAP_TWEAK, &internal_key);
+
+        assert_eq!(&hex::encode(&result), "b86e7be8f39bab32a6f2c0443abbc210f0edac0e2c53d501b36b64437d9c6c70");
+    }
+
+    #[test]
+    fn tap_leaf_matches_bip341_wallet_vector() {
+        // leaf version 0xc0, compact size 0x22, then <32-byte key> OP_CHECKSIG
+        let leaf = hex::decode("c02220d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac").unwrap();
+
+        let result = tagged_hash(TAP_LEAF, &leaf);
+
+        assert_eq!(&hex::encode(&result), "5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21");
+    }
+
+    #[test]
+    fn tagged_hash_follows_the_definition() {
+        let tag_hash = sha256(b"custom");
+        let mut preimage = Vec::new();
+        preimage.extend_from_slice(&tag_hash);
+        preimage.extend_from_slice(&tag_hash);
+        preimage.extend_from_slice(b"abc");
+
+        assert_eq!(tagged_hash("custom", b"abc"), sha256(&preimage));
+    }
+
+    #[test]
+    fn cached_and_uncached_paths_agree() {
+        for tag in [BIP340_AUX, BIP340_NONCE, BIP340_CHALLENGE, TAP_TWEAK, TAP_LEAF, TAP_BRANCH] {
+            for msg in [&b""[..], &[0u8; 64], b"hello world"] {
+                let mut uncached = uncached_tagged_hash_engine(tag);
+                uncached.update(msg);
+
+                // Twice, so the second call is served from the cache
+                assert_eq!(tagged_hash(tag, msg), uncached.clone().finalize());
+                assert_eq!(tagged_hash(tag, msg), uncached.finalize());
+            }
+        }
+    }
+}
@@ -1 +1,14 @@
-pub mod ecc;
\ No newline at end of file
+pub mod address;
+pub mod bip32;
+pub mod bip39;
+pub mod block;
+pub mod constant_time;
+pub mod ecc;
+pub mod encoding;
+pub mod hashing;
+pub mod merkle;
+pub mod network;
+pub mod rng;
+pub mod script;
+pub mod tx;
+pub mod vanity;
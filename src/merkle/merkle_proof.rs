@@ -0,0 +1,200 @@
+use std::fmt::{Display, Formatter};
+use crate::hashing::hash256::{Hash256, Hash256Engine};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MerkleError {
+    EmptyTree,
+    IndexOutOfRange { index: usize, leaf_count: usize },
+}
+
+impl Display for MerkleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MerkleError::EmptyTree => write!(f, "Cannot build a merkle tree without leaves."),
+            MerkleError::IndexOutOfRange { index, leaf_count } => {
+                write!(f, "Leaf index {} is out of range for {} leaves.", index, leaf_count)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MerkleError {}
+
+// parent = hash256(left || right), over the wire bytes of both children
+pub fn merkle_parent(left: &Hash256, right: &Hash256) -> Hash256 {
+    let mut engine = Hash256Engine::new();
+    engine.update(&left.to_wire_bytes());
+    engine.update(&right.to_wire_bytes());
+
+    Hash256::from_wire_bytes(engine.finalize())
+}
+
+// Levels with an odd number of hashes pair their last hash with itself
+pub fn merkle_parent_level(level: &[Hash256]) -> Vec<Hash256> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => merkle_parent(left, right),
+            [single] => merkle_parent(single, single),
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+pub fn merkle_root(leaves: &[Hash256]) -> Result<Hash256, MerkleError> {
+    if leaves.is_empty() {
+        return Err(MerkleError::EmptyTree);
+    }
+
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = merkle_parent_level(&level);
+    }
+
+    Ok(level[0])
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleProof {
+    pub leaf_index: usize,
+    // Sibling hashes from the leaf level up to just below the root
+    pub siblings: Vec<Hash256>,
+}
+
+impl MerkleProof {
+    pub fn generate(leaves: &[Hash256], index: usize) -> Result<Self, MerkleError> {
+        if leaves.is_empty() {
+            return Err(MerkleError::EmptyTree);
+        }
+        if index >= leaves.len() {
+            return Err(MerkleError::IndexOutOfRange { index, leaf_count: leaves.len() });
+        }
+
+        let mut siblings = Vec::new();
+        let mut level = leaves.to_vec();
+        let mut position = index;
+
+        while level.len() > 1 {
+            // A last node without a partner is its own sibling
+            let sibling = match position ^ 1 {
+                sibling if sibling < level.len() => sibling,
+                _ => position,
+            };
+            siblings.push(level[sibling]);
+
+            level = merkle_parent_level(&level);
+            position /= 2;
+        }
+
+        Ok(Self {
+            leaf_index: index,
+            siblings,
+        })
+    }
+
+    pub fn verify(&self, leaf: &Hash256, root: &Hash256) -> bool {
+        if self.siblings.len() < usize::BITS as usize && self.leaf_index >> self.siblings.len() != 0 {
+            return false;
+        }
+
+        let mut hash = *leaf;
+        let mut position = self.leaf_index;
+
+        for sibling in &self.siblings {
+            hash = match position % 2 {
+                0 => merkle_parent(&hash, sibling),
+                _ => merkle_parent(sibling, &hash),
+            };
+            position /= 2;
+        }
+
+        hash == *root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use crate::hashing::hash256::Hash256;
+    use crate::merkle::merkle_proof::{merkle_parent, merkle_root, MerkleError, MerkleProof};
+
+    fn leaves(count: usize) -> Vec<Hash256> {
+        (0..count).map(|i| Hash256::hash(&(i as u32).to_le_bytes())).collect()
+    }
+
+    #[test]
+    fn merkle_root_of_block_100000() {
+        let txids = [
+            "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+            "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+            "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+            "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+        ]
+        .map(|txid| Hash256::from_str(txid).unwrap());
+
+        let result = merkle_root(&txids).unwrap();
+
+        assert_eq!(&result.to_string(), "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766");
+    }
+
+    #[test]
+    fn proofs_verify_for_every_leaf_of_trees_with_1_to_13_leaves() {
+        for count in 1..=13 {
+            let leaves = leaves(count);
+            let root = merkle_root(&leaves).unwrap();
+
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = MerkleProof::generate(&leaves, index).unwrap();
+
+                assert!(proof.verify(leaf, &root), "leaf {} of {}", index, count);
+                assert!(!proof.verify(&Hash256::hash(b"other"), &root), "leaf {} of {}", index, count);
+            }
+        }
+    }
+
+    #[test]
+    fn odd_levels_pair_the_last_hash_with_itself() {
+        let leaves = leaves(3);
+
+        let proof = MerkleProof::generate(&leaves, 2).unwrap();
+
+        assert_eq!(proof.siblings[0], leaves[2]);
+        assert_eq!(proof.siblings[1], merkle_parent(&leaves[0], &leaves[1]));
+    }
+
+    #[test]
+    fn corrupted_sibling_fails_verification() {
+        let leaves = leaves(7);
+        let root = merkle_root(&leaves).unwrap();
+        let mut proof = MerkleProof::generate(&leaves, 4).unwrap();
+
+        let mut corrupted = proof.siblings[1].to_wire_bytes();
+        corrupted[0] ^= 0x01;
+        proof.siblings[1] = Hash256::from_wire_bytes(corrupted);
+
+        assert!(!proof.verify(&leaves[4], &root));
+    }
+
+    #[test]
+    fn proof_with_a_mismatched_index_fails_verification() {
+        let leaves = leaves(8);
+        let root = merkle_root(&leaves).unwrap();
+        let mut proof = MerkleProof::generate(&leaves, 3).unwrap();
+
+        proof.leaf_index = 2;
+        assert!(!proof.verify(&leaves[3], &root));
+
+        proof.leaf_index = 3 + 8;
+        assert!(!proof.verify(&leaves[3], &root));
+    }
+
+    #[test]
+    fn out_of_range_indexes_and_empty_trees_are_rejected() {
+        assert_eq!(
+            MerkleProof::generate(&leaves(5), 5),
+            Err(MerkleError::IndexOutOfRange { index: 5, leaf_count: 5 })
+        );
+        assert_eq!(MerkleProof::generate(&[], 0), Err(MerkleError::EmptyTree));
+        assert_eq!(merkle_root(&[]), Err(MerkleError::EmptyTree));
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+// Per-network constants, as defined in Bitcoin Core's chainparams
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Signet,
+    Regtest,
+}
+
+impl Network {
+    pub const ALL: [Network; 4] = [Network::Mainnet, Network::Testnet, Network::Signet, Network::Regtest];
+
+    pub fn p2pkh_version(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x00,
+            Network::Testnet | Network::Signet | Network::Regtest => 0x6f,
+        }
+    }
+
+    pub fn p2sh_version(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x05,
+            Network::Testnet | Network::Signet | Network::Regtest => 0xc4,
+        }
+    }
+
+    pub fn wif_prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => 0x80,
+            Network::Testnet | Network::Signet | Network::Regtest => 0xef,
+        }
+    }
+
+    pub fn bech32_hrp(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "bc",
+            Network::Testnet | Network::Signet => "tb",
+            Network::Regtest => "bcrt",
+        }
+    }
+
+    pub fn xpub_version(&self) -> [u8; 4] {
+        match self {
+            Network::Mainnet => [0x04, 0x88, 0xb2, 0x1e],
+            Network::Testnet | Network::Signet | Network::Regtest => [0x04, 0x35, 0x87, 0xcf],
+        }
+    }
+
+    pub fn xprv_version(&self) -> [u8; 4] {
+        match self {
+            Network::Mainnet => [0x04, 0x88, 0xad, 0xe4],
+            Network::Testnet | Network::Signet | Network::Regtest => [0x04, 0x35, 0x83, 0x94],
+        }
+    }
+
+    // The pchMessageStart bytes that prefix every P2P message
+    pub fn magic(&self) -> [u8; 4] {
+        match self {
+            Network::Mainnet => [0xf9, 0xbe, 0xb4, 0xd9],
+            Network::Testnet => [0x0b, 0x11, 0x09, 0x07],
+            Network::Signet => [0x0a, 0x03, 0xcf, 0x40],
+            Network::Regtest => [0xfa, 0xbf, 0xb5, 0xda],
+        }
+    }
+
+    pub fn from_magic(magic: [u8; 4]) -> Option<Self> {
+        Network::ALL.into_iter().find(|network| network.magic() == magic)
+    }
+}
+
+impl Display for Network {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // Same names as Bitcoin Core's -chain option
+        match self {
+            Network::Mainnet => write!(f, "main"),
+            Network::Testnet => write!(f, "test"),
+            Network::Signet => write!(f, "signet"),
+            Network::Regtest => write!(f, "regtest"),
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "main" | "mainnet" | "bitcoin" => Ok(Network::Mainnet),
+            "test" | "testnet" => Ok(Network::Testnet),
+            "signet" => Ok(Network::Signet),
+            "regtest" => Ok(Network::Regtest),
+            _ => Err(format!("Unknown network '{}'.", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use crate::encoding::base58check;
+    use crate::encoding::bech32::{decode_witness_program, encode_witness_program, Bech32Error};
+    use crate::network::Network;
+
+    #[test]
+    fn mainnet_constants_match_chainparams() {
+        let network = Network::Mainnet;
+
+        assert_eq!(network.p2pkh_version(), 0);
+        assert_eq!(network.p2sh_version(), 5);
+        assert_eq!(network.wif_prefix(), 128);
+        assert_eq!(network.bech32_hrp(), "bc");
+        assert_eq!(network.xpub_version(), [0x04, 0x88, 0xb2, 0x1e]);
+        assert_eq!(network.xprv_version(), [0x04, 0x88, 0xad, 0xe4]);
+        assert_eq!(network.magic(), [0xf9, 0xbe, 0xb4, 0xd9]);
+    }
+
+    #[test]
+    fn test_networks_share_base58_prefixes_but_not_magic() {
+        for network in [Network::Testnet, Network::Signet, Network::Regtest] {
+            assert_eq!(network.p2pkh_version(), 111);
+            assert_eq!(network.p2sh_version(), 196);
+            assert_eq!(network.wif_prefix(), 239);
+            assert_eq!(network.xpub_version(), [0x04, 0x35, 0x87, 0xcf]);
+            assert_eq!(network.xprv_version(), [0x04, 0x35, 0x83, 0x94]);
+        }
+
+        assert_eq!(Network::Testnet.bech32_hrp(), "tb");
+        assert_eq!(Network::Signet.bech32_hrp(), "tb");
+        assert_eq!(Network::Regtest.bech32_hrp(), "bcrt");
+        assert_eq!(Network::Testnet.magic(), [0x0b, 0x11, 0x09, 0x07]);
+        assert_eq!(Network::Signet.magic(), [0x0a, 0x03, 0xcf, 0x40]);
+        assert_eq!(Network::Regtest.magic(), [0xfa, 0xbf, 0xb5, 0xda]);
+    }
+
+    #[test]
+    fn networks_are_recovered_from_magic_and_name() {
+        for network in Network::ALL {
+            assert_eq!(Network::from_magic(network.magic()), Some(network));
+            assert_eq!(Network::from_str(&network.to_string()), Ok(network));
+        }
+
+        assert_eq!(Network::from_magic([0; 4]), None);
+        assert!(Network::from_str("mainnet2").is_err());
+    }
+
+    #[test]
+    fn testnet_addresses_do_not_parse_as_mainnet() {
+        let program = [0x75; 20];
+
+        let segwit = encode_witness_program(Network::Testnet.bech32_hrp(), 0, &program).unwrap();
+        let legacy = base58check::encode(Network::Testnet.p2pkh_version(), &program);
+
+        assert_eq!(
+            decode_witness_program(Network::Mainnet.bech32_hrp(), &segwit),
+            Err(Bech32Error::HrpMismatch("tb".to_string()))
+        );
+        let (version, _) = base58check::decode(&legacy).unwrap();
+        assert_ne!(version, Network::Mainnet.p2pkh_version());
+        assert_eq!(version, Network::Testnet.p2pkh_version());
+    }
+}
@@ -0,0 +1,103 @@
+use std::fs::File;
+use std::io::Read;
+use crate::hashing::sha256::Sha256;
+
+/*
+    A source of random bytes for key and mnemonic generation and for signing nonces. Anything that takes one is
+    only as good as the bytes it is given, so only OsRng should back real keys; SeededRng is for reproducing a
+    run from a known seed.
+ */
+pub trait CryptoRng {
+    fn fill_bytes(&mut self, dest: &mut [u8]);
+}
+
+// The operating system's generator, read from /dev/urandom
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsRng;
+
+impl CryptoRng for OsRng {
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        // There is nothing safe to fall back to, so a system without the device cannot generate keys at all
+        File::open("/dev/urandom")
+            .and_then(|mut urandom| urandom.read_exact(dest))
+            .expect("The operating system's random number generator could not be read.");
+    }
+}
+
+// Deterministic bytes, the SHA-256 of the seed and a block counter, so the same seed always gives the same stream
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    seed: [u8; 32],
+    counter: u64,
+    block: [u8; 32],
+    // How much of the current block has been handed out
+    used: usize,
+}
+
+impl SeededRng {
+    pub fn new(seed: [u8; 32]) -> Self {
+        Self {
+            seed,
+            counter: 0,
+            block: [0; 32],
+            used: 32,
+        }
+    }
+
+    pub fn from_u64(seed: u64) -> Self {
+        let mut bytes = [0; 32];
+        bytes[24..].copy_from_slice(&seed.to_be_bytes());
+
+        Self::new(bytes)
+    }
+}
+
+impl CryptoRng for SeededRng {
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest.iter_mut() {
+            if self.used == self.block.len() {
+                let mut hasher = Sha256::new();
+                hasher.update(&self.seed);
+                hasher.update(&self.counter.to_be_bytes());
+                self.block = hasher.finalize();
+                self.counter += 1;
+                self.used = 0;
+            }
+            *byte = self.block[self.used];
+            self.used += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rng::{CryptoRng, OsRng, SeededRng};
+
+    #[test]
+    fn seeded_streams_repeat_and_do_not_depend_on_chunking() {
+        let mut whole = [0; 100];
+        SeededRng::from_u64(7).fill_bytes(&mut whole);
+
+        let mut pieces = [0; 100];
+        let mut rng = SeededRng::from_u64(7);
+        for chunk in pieces.chunks_mut(13) {
+            rng.fill_bytes(chunk);
+        }
+
+        let mut other = [0; 100];
+        SeededRng::from_u64(8).fill_bytes(&mut other);
+
+        assert_eq!(whole, pieces);
+        assert_ne!(whole, other);
+    }
+
+    #[test]
+    fn os_bytes_differ_between_reads() {
+        let (mut first, mut second) = ([0; 32], [0; 32]);
+
+        OsRng.fill_bytes(&mut first);
+        OsRng.fill_bytes(&mut second);
+
+        assert_ne!(first, second);
+    }
+}
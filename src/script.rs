@@ -0,0 +1,353 @@
+use std::io::{Read, Write};
+use std::ops::Add;
+use crate::encoding::encodable::{Decodable, Encodable};
+use crate::encoding::little_endian::{read_u16_le, read_u32_le, read_u8};
+use crate::encoding::parse_error::ParseError;
+use crate::encoding::varint::{read_var_bytes, write_var_bytes};
+use crate::script::opcodes::{OP_0, OP_PUSHDATA1, OP_PUSHDATA2, OP_PUSHDATA4};
+
+pub mod asm;
+pub mod interpreter;
+pub mod opcodes;
+pub mod standard;
+pub mod taproot;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cmd {
+    // Any opcode that does not push data
+    Op(u8),
+    /*
+        Data together with the opcode that pushed it:
+
+        OP_0           an empty push
+        0x01 to 0x4b   the opcode is the length of the data that follows
+        OP_PUSHDATA1   1 byte length, then the data
+        OP_PUSHDATA2   2 bytes little-endian length, then the data
+        OP_PUSHDATA4   4 bytes little-endian length, then the data
+
+        The opcode is kept so a script with a needlessly long push parses back to the same bytes.
+     */
+    Push { opcode: u8, data: Vec<u8> },
+}
+
+impl Cmd {
+    // Pushes the data with the shortest of the push opcodes that fits it
+    pub fn push(data: Vec<u8>) -> Self {
+        let opcode = match data.len() {
+            0..=0x4b => data.len() as u8,
+            0x4c..=0xff => OP_PUSHDATA1,
+            0x100..=0xffff => OP_PUSHDATA2,
+            _ => OP_PUSHDATA4,
+        };
+
+        Cmd::Push { opcode, data }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Script {
+    pub cmds: Vec<Cmd>,
+}
+
+impl Script {
+    pub fn new(cmds: Vec<Cmd>) -> Self {
+        Self { cmds }
+    }
+
+    // Parses the script's bytes without a length prefix, as they appear in a script_sig or script_pubkey field
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ParseError> {
+        let mut reader = bytes;
+        let mut cmds = Vec::new();
+
+        while let Ok(opcode) = read_u8(&mut reader) {
+            let position = bytes.len() - reader.len() - 1;
+            let overrun = |_| ParseError::PushPastScriptEnd { position };
+
+            let length = match opcode {
+                OP_0..=0x4b => opcode as usize,
+                OP_PUSHDATA1 => read_u8(&mut reader).map_err(overrun)? as usize,
+                OP_PUSHDATA2 => read_u16_le(&mut reader).map_err(overrun)? as usize,
+                OP_PUSHDATA4 => read_u32_le(&mut reader).map_err(overrun)? as usize,
+                _ => {
+                    cmds.push(Cmd::Op(opcode));
+                    continue;
+                }
+            };
+            if length > reader.len() {
+                return Err(ParseError::PushPastScriptEnd { position });
+            }
+
+            let (data, rest) = reader.split_at(length);
+            cmds.push(Cmd::Push { opcode, data: data.to_vec() });
+            reader = rest;
+        }
+
+        Ok(Self { cmds })
+    }
+
+    // The script's bytes without a length prefix, each push written with the opcode it holds
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        for cmd in &self.cmds {
+            match cmd {
+                Cmd::Op(opcode) => bytes.push(*opcode),
+                Cmd::Push { opcode, data } => {
+                    bytes.push(*opcode);
+                    match *opcode {
+                        OP_PUSHDATA1 => bytes.push(data.len() as u8),
+                        OP_PUSHDATA2 => bytes.extend((data.len() as u16).to_le_bytes()),
+                        OP_PUSHDATA4 => bytes.extend((data.len() as u32).to_le_bytes()),
+                        _ => {}
+                    }
+                    bytes.extend(data);
+                }
+            }
+        }
+
+        bytes
+    }
+}
+
+/*
+    script_sig + script_pubkey, the combined script evaluate runs. The commands are joined as they are, so every
+    push keeps the opcode it was parsed with and the result serializes to the two scripts' bytes back to back.
+ */
+impl Add for Script {
+    type Output = Script;
+
+    fn add(mut self, other: Script) -> Script {
+        self.cmds.extend(other.cmds);
+        self
+    }
+}
+
+impl Add<&Script> for &Script {
+    type Output = Script;
+
+    fn add(self, other: &Script) -> Script {
+        Script::new([self.cmds.as_slice(), other.cmds.as_slice()].concat())
+    }
+}
+
+// A varint length followed by the script bytes, as a script is serialized inside a transaction
+impl Decodable for Script {
+    fn parse(reader: &mut impl Read) -> Result<Self, ParseError> {
+        let bytes = read_var_bytes(reader).map_err(|error| error.in_type("Script"))?;
+
+        Self::from_bytes(&bytes)
+    }
+}
+
+impl Encodable for Script {
+    fn serialize(&self, writer: &mut impl Write) -> Result<(), ParseError> {
+        write_var_bytes(writer, &self.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::encodable::{Decodable, Encodable};
+    use crate::encoding::hex;
+    use crate::encoding::parse_error::ParseError;
+    use crate::script::opcodes::{OP_PUSHDATA1, OP_PUSHDATA2, OP_PUSHDATA4};
+    use crate::script::{Cmd, Script};
+    use crate::tx::fixtures::TX_HEX;
+    use crate::tx::transaction::Tx;
+
+    fn push_hex(data_hex: &str) -> Cmd {
+        Cmd::push(hex::decode(data_hex).unwrap())
+    }
+
+    #[test]
+    fn p2pkh_script_sig_is_a_signature_and_a_public_key() {
+        let tx = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+
+        let script_sig = Script::from_bytes(&tx.inputs[0].script_sig).unwrap();
+
+        assert_eq!(script_sig.cmds, vec![
+            push_hex("3045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb3\
+                      5d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01"),
+            push_hex("0349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278a"),
+        ]);
+    }
+
+    #[test]
+    fn p2pkh_script_pubkey_mixes_opcodes_and_a_push() {
+        let tx = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+
+        let script_pubkey = Script::from_bytes(&tx.outputs[0].script_pubkey).unwrap();
+
+        assert_eq!(script_pubkey.cmds, vec![
+            Cmd::Op(0x76),
+            Cmd::Op(0xa9),
+            push_hex("bc3b654dca7e56b04dca18f2566cdaf02e8d9ada"),
+            Cmd::Op(0x88),
+            Cmd::Op(0xac),
+        ]);
+    }
+
+    #[test]
+    fn every_push_opcode_parses() {
+        let mut bytes = vec![0x00, 0x01, 0xaa, OP_PUSHDATA1, 0x02, 0xbb, 0xbb, OP_PUSHDATA2, 0x2c, 0x01];
+        bytes.extend([0xcc; 300]);
+        bytes.extend([OP_PUSHDATA4, 0x01, 0x00, 0x00, 0x00, 0xdd, 0x51]);
+
+        let script = Script::from_bytes(&bytes).unwrap();
+
+        assert_eq!(script.cmds, vec![
+            Cmd::Push { opcode: 0x00, data: vec![] },
+            Cmd::Push { opcode: 0x01, data: vec![0xaa] },
+            Cmd::Push { opcode: OP_PUSHDATA1, data: vec![0xbb; 2] },
+            Cmd::Push { opcode: OP_PUSHDATA2, data: vec![0xcc; 300] },
+            Cmd::Push { opcode: OP_PUSHDATA4, data: vec![0xdd] },
+            Cmd::Op(0x51),
+        ]);
+    }
+
+    #[test]
+    fn push_picks_the_shortest_opcode() {
+        let opcode = |length| match Cmd::push(vec![0; length]) {
+            Cmd::Push { opcode, .. } => opcode,
+            Cmd::Op(_) => unreachable!(),
+        };
+
+        assert_eq!(opcode(0), 0x00);
+        assert_eq!(opcode(75), 0x4b);
+        assert_eq!(opcode(76), OP_PUSHDATA1);
+        assert_eq!(opcode(255), OP_PUSHDATA1);
+        assert_eq!(opcode(256), OP_PUSHDATA2);
+        assert_eq!(opcode(65536), OP_PUSHDATA4);
+    }
+
+    #[test]
+    fn pushes_past_the_end_of_the_script_are_an_error() {
+        let cases: [(&[u8], usize); 5] = [
+            (&[0x02, 0xaa], 0),
+            (&[0x51, 0x4b], 1),
+            (&[0x51, OP_PUSHDATA1], 1),
+            (&[0x00, OP_PUSHDATA2, 0x01], 1),
+            (&[OP_PUSHDATA4, 0xff, 0xff, 0xff, 0xff, 0xaa], 0),
+        ];
+
+        for (bytes, position) in cases {
+            assert_eq!(Script::from_bytes(bytes), Err(ParseError::PushPastScriptEnd { position }), "{:02x?}", bytes);
+        }
+    }
+
+    #[test]
+    fn length_prefixed_script_parses() {
+        let bytes = hex::decode("1976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac").unwrap();
+
+        let script = Script::parse_exact(&bytes).unwrap();
+
+        assert_eq!(script.cmds.len(), 5);
+        assert_eq!(Script::parse_exact(&bytes[..10]), Err(ParseError::Truncated("Script")));
+        // The length prefix stops a push from reading into whatever follows the script
+        assert_eq!(
+            Script::parse(&mut [0x02, 0x02, 0xaa, 0xbb].as_slice()),
+            Err(ParseError::PushPastScriptEnd { position: 0 })
+        );
+    }
+
+    #[test]
+    fn real_scripts_serialize_to_their_original_bytes() {
+        let tx = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+        let scripts = [&tx.inputs[0].script_sig, &tx.outputs[0].script_pubkey, &tx.outputs[1].script_pubkey];
+
+        for bytes in scripts {
+            let script = Script::from_bytes(bytes).unwrap();
+
+            assert_eq!(&script.to_bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn large_pushes_use_pushdata_opcodes() {
+        let script = Script::new(vec![Cmd::push(vec![0xaa; 300]), Cmd::Op(0x87)]);
+
+        let bytes = script.serialize_to_vec();
+
+        assert_eq!(&hex::encode(&bytes[..6]), "fd30014d2c01");
+        assert_eq!(bytes.len(), 3 + 3 + 300 + 1);
+        assert_eq!(bytes.last(), Some(&0x87));
+        assert_eq!(&hex::encode(&Script::new(vec![Cmd::push(vec![0xbb; 76])]).to_bytes()[..2]), "4c4c");
+    }
+
+    #[test]
+    fn non_minimal_pushes_keep_their_encoding() {
+        let bytes = [OP_PUSHDATA1, 0x01, 0xaa, OP_PUSHDATA2, 0x00, 0x00, OP_PUSHDATA4, 0x01, 0x00, 0x00, 0x00, 0xbb];
+
+        let script = Script::from_bytes(&bytes).unwrap();
+
+        assert_eq!(script.to_bytes(), bytes);
+    }
+
+    // A small xorshift generator keeps the property test deterministic and dependency-free
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: u64) -> u64 {
+            self.next() % bound
+        }
+    }
+
+    #[test]
+    fn random_command_lists_round_trip() {
+        let mut rng = XorShift(0x2545f4914f6cdd1d);
+
+        for _ in 0..500 {
+            let cmds = (0..rng.below(12))
+                .map(|_| match rng.below(4) {
+                    // Any opcode other than the pushes
+                    0 => Cmd::Op(OP_PUSHDATA4 + 1 + rng.below(0xff - OP_PUSHDATA4 as u64) as u8),
+                    1 => Cmd::push((0..rng.below(80)).map(|_| rng.next() as u8).collect()),
+                    2 => Cmd::push(vec![rng.next() as u8; rng.below(600) as usize]),
+                    _ => {
+                        let opcode = [OP_PUSHDATA1, OP_PUSHDATA2, OP_PUSHDATA4][rng.below(3) as usize];
+                        Cmd::Push { opcode, data: vec![rng.next() as u8; rng.below(0x100) as usize] }
+                    }
+                })
+                .collect();
+            let script = Script::new(cmds);
+
+            let bytes = script.serialize_to_vec();
+
+            assert_eq!(Script::parse_exact(&bytes), Ok(script));
+        }
+    }
+
+    #[test]
+    fn adding_scripts_joins_their_bytes() {
+        let tx = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+        let script_sig = Script::from_bytes(&tx.inputs[0].script_sig).unwrap();
+        let script_pubkey = Script::from_bytes(&tx.outputs[0].script_pubkey).unwrap();
+
+        let combined = &script_sig + &script_pubkey;
+
+        let bytes = [tx.inputs[0].script_sig.clone(), tx.outputs[0].script_pubkey.clone()].concat();
+        assert_eq!(combined.to_bytes(), bytes);
+        assert_eq!(combined.serialize_to_vec().len(), 1 + 0x6b + 0x19);
+        assert_eq!(combined, script_sig.clone() + script_pubkey.clone());
+        assert_eq!(script_sig.to_bytes(), tx.inputs[0].script_sig);
+        assert_eq!(script_pubkey.to_bytes(), tx.outputs[0].script_pubkey);
+    }
+
+    #[test]
+    fn adding_scripts_keeps_non_minimal_pushes() {
+        let long_push = Script::new(vec![Cmd::Push { opcode: OP_PUSHDATA2, data: vec![0x01] }]);
+        let empty = Script::new(vec![]);
+
+        let combined = &long_push + &empty;
+
+        assert_eq!(combined.to_bytes(), vec![OP_PUSHDATA2, 0x01, 0x00, 0x01]);
+        assert_eq!(empty + long_push.clone(), long_push);
+    }
+}
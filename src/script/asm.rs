@@ -0,0 +1,305 @@
+use std::fmt::{Display, Formatter};
+use crate::encoding::hex;
+use crate::encoding::hex::HexError;
+use crate::script::interpreter::{decode_num, encode_num};
+use crate::script::opcodes::{
+    opcode_from_name, opcode_name, OP_0, OP_1, OP_16, OP_1NEGATE, OP_PUSHDATA1, OP_PUSHDATA2, OP_PUSHDATA4,
+};
+use crate::script::{Cmd, Script};
+
+const PUSHBYTES_PREFIX: &str = "OP_PUSHBYTES_";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmError {
+    UnknownToken(String),
+    // A push opcode at the end of the asm, with no data after it
+    MissingData(String),
+    InvalidData { opcode: String, error: HexError },
+    // Data the push opcode before it cannot push
+    DataLength { opcode: String, length: usize },
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AsmError::UnknownToken(token) => write!(f, "Unknown asm token '{}'.", token),
+            AsmError::MissingData(opcode) => write!(f, "{} is not followed by data.", opcode),
+            AsmError::InvalidData { opcode, error } => write!(f, "Invalid data after {}: {}", opcode, error),
+            AsmError::DataLength { opcode, length } => write!(f, "{} cannot push {} bytes.", opcode, length),
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/*
+    The asm Bitcoin Core's decodescript prints, except that each push names its opcode so the asm parses back to
+    the same bytes:
+
+    0, -1, 1 to 16           OP_0, OP_1NEGATE and OP_1 to OP_16, printed as numbers like Core does
+    OP_PUSHBYTES_n <data>    a direct push of n bytes, which Core prints as just the data
+    OP_PUSHDATA1 <data>      and the same for OP_PUSHDATA2 and OP_PUSHDATA4
+    0xbb                     an opcode without a name
+
+    Like Core, data of up to 4 bytes is printed as the decimal script number it encodes, so a BIP34 height reads
+    as 500000, and longer data as hex. Where Core's number would not give the bytes back, as for a push of 0x00 or
+    of a number with needless padding, the data is printed as hex with a 0x prefix instead. Longer data that would
+    read as a number is given the prefix too.
+ */
+impl Display for Script {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let tokens: Vec<String> = self.cmds.iter().map(asm_token).collect();
+
+        write!(f, "{}", tokens.join(" "))
+    }
+}
+
+fn asm_token(cmd: &Cmd) -> String {
+    match cmd {
+        Cmd::Push { opcode: OP_0, .. } => "0".to_string(),
+        Cmd::Push { opcode: opcode @ (OP_PUSHDATA1 | OP_PUSHDATA2 | OP_PUSHDATA4), data } => {
+            format!("{} {}", opcode_name(*opcode).expect("The OP_PUSHDATA opcodes are named."), data_token(data))
+        }
+        Cmd::Push { opcode, data } => format!("{}{} {}", PUSHBYTES_PREFIX, opcode, data_token(data)),
+        Cmd::Op(OP_1NEGATE) => "-1".to_string(),
+        Cmd::Op(opcode @ OP_1..=OP_16) => (opcode - (OP_1 - 1)).to_string(),
+        Cmd::Op(opcode) => opcode_name(*opcode).map_or_else(|| format!("{:#04x}", opcode), str::to_string),
+    }
+}
+
+fn data_token(data: &[u8]) -> String {
+    let number = decode_num(data, 4).ok().filter(|number| encode_num(*number) == data);
+    if let Some(number) = number {
+        return number.to_string();
+    }
+
+    let data_hex = hex::encode(data);
+    match parse_number(&data_hex) {
+        Some(_) => format!("0x{}", data_hex),
+        None => data_hex,
+    }
+}
+
+// The minimal encoding of a decimal token in the range of a 4-byte script number
+fn parse_number(token: &str) -> Option<Vec<u8>> {
+    let number = token.parse::<i64>().ok().filter(|number| number.unsigned_abs() <= i32::MAX as u64)?;
+
+    Some(encode_num(number))
+}
+
+impl Script {
+    // Parses the asm Display prints. Opcode names have to be given in full, OP_DUP rather than DUP.
+    pub fn from_asm(asm: &str) -> Result<Self, AsmError> {
+        let mut tokens = asm.split_whitespace();
+        let mut cmds = Vec::new();
+
+        while let Some(token) = tokens.next() {
+            let push_opcode = match token {
+                "OP_PUSHDATA1" => Some(OP_PUSHDATA1),
+                "OP_PUSHDATA2" => Some(OP_PUSHDATA2),
+                "OP_PUSHDATA4" => Some(OP_PUSHDATA4),
+                _ => token
+                    .strip_prefix(PUSHBYTES_PREFIX)
+                    .and_then(|length| length.parse().ok())
+                    .filter(|length| (1..OP_PUSHDATA1).contains(length)),
+            };
+            if let Some(opcode) = push_opcode {
+                cmds.push(parse_push(token, opcode, tokens.next())?);
+                continue;
+            }
+
+            let cmd = match token {
+                "0" | "OP_0" => Cmd::push(vec![]),
+                "-1" => Cmd::Op(OP_1NEGATE),
+                _ => Cmd::Op(parse_opcode(token).ok_or_else(|| AsmError::UnknownToken(token.to_string()))?),
+            };
+            cmds.push(cmd);
+        }
+
+        Ok(Self::new(cmds))
+    }
+}
+
+fn parse_push(token: &str, opcode: u8, data: Option<&str>) -> Result<Cmd, AsmError> {
+    let data = data.ok_or_else(|| AsmError::MissingData(token.to_string()))?;
+    let data = match parse_number(data) {
+        Some(number) => number,
+        None => hex::decode(data).map_err(|error| AsmError::InvalidData { opcode: token.to_string(), error })?,
+    };
+
+    let fits = match opcode {
+        OP_PUSHDATA1 => data.len() <= 0xff,
+        OP_PUSHDATA2 => data.len() <= 0xffff,
+        OP_PUSHDATA4 => data.len() <= u32::MAX as usize,
+        length => data.len() == length as usize,
+    };
+    if !fits {
+        return Err(AsmError::DataLength { opcode: token.to_string(), length: data.len() });
+    }
+
+    Ok(Cmd::Push { opcode, data })
+}
+
+// A number from 1 to 16, an opcode name, or the hex of an opcode without one. Push opcodes are never bare.
+fn parse_opcode(token: &str) -> Option<u8> {
+    if let Ok(number @ 1..=16) = token.parse::<u8>() {
+        return Some(OP_1 - 1 + number);
+    }
+    if let Some(opcode) = opcode_from_name(token).filter(|opcode| *opcode > OP_PUSHDATA4) {
+        return Some(opcode);
+    }
+
+    let digits = token.strip_prefix("0x").filter(|digits| digits.len() == 2)?;
+    let opcode = u8::from_str_radix(digits, 16).ok()?;
+
+    opcode_name(opcode).is_none().then_some(opcode).filter(|opcode| *opcode > OP_PUSHDATA4)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::hex;
+    use crate::encoding::hex::HexError;
+    use crate::script::asm::AsmError;
+    use crate::script::opcodes::{opcode_name, OP_CHECKSIG, OP_PUSHDATA1, OP_PUSHDATA2};
+    use crate::script::{Cmd, Script};
+
+    // decodescript's asm for the same script: the OP_PUSHBYTES_n markers are all that is added, as long as no
+    // short push is a number with needless padding
+    fn core_asm(script: &Script) -> String {
+        let asm = script.to_string();
+
+        asm.split(' ').filter(|token| !token.starts_with("OP_PUSHBYTES_")).collect::<Vec<_>>().join(" ")
+    }
+
+    #[test]
+    fn asm_matches_bitcoin_core_decodescript() {
+        let cases = [
+            // The Programming Bitcoin chapter 5 script_pubkey and script_sig
+            (
+                "76a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac",
+                "OP_DUP OP_HASH160 bc3b654dca7e56b04dca18f2566cdaf02e8d9ada OP_EQUALVERIFY OP_CHECKSIG",
+            ),
+            (
+                "483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a\
+                 89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e21\
+                 3bf016b278a",
+                "3045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89\
+                 d3f56100f4d7f67801c31967743a9c8e10615bed01 0349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf01\
+                 6b278a",
+            ),
+            // The chapter 8 redeem script
+            (
+                "5221022626e955ea6ea6d98850c994f9107b036b1334f18ca8830bfff1295d21cfdb702103b287eaf122eea69030a0e9feed\
+                 096bed8045c8b98bec453e1ffac7fbdbd4bb7152ae",
+                "2 022626e955ea6ea6d98850c994f9107b036b1334f18ca8830bfff1295d21cfdb70 03b287eaf122eea69030a0e9feed096be\
+                 d8045c8b98bec453e1ffac7fbdbd4bb71 2 OP_CHECKMULTISIG",
+            ),
+            (
+                "a91474d691da1574e6b3c192ecfb52cc8984ee7b6c5687",
+                "OP_HASH160 74d691da1574e6b3c192ecfb52cc8984ee7b6c56 OP_EQUAL",
+            ),
+            ("0014751e76e8199196d454941c45d1b3a323f1433bd6", "0 751e76e8199196d454941c45d1b3a323f1433bd6"),
+            (
+                "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+                "1 a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+            ),
+            (
+                "6a24aa21a9edd5232abf74199897d4d7e6b39c255c816a0f698986bf7df236a56ed4a21a756d",
+                "OP_RETURN aa21a9edd5232abf74199897d4d7e6b39c255c816a0f698986bf7df236a56ed4a21a756d",
+            ),
+            // The BIP34 height that starts the coinbase script_sig of block 500000
+            ("0320a107", "500000"),
+            // The genesis coinbase script_sig, whose first push is the block's bits
+            (
+                "04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f\
+                 66207365636f6e64206261696c6f757420666f722062616e6b73",
+                "486604799 4 5468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f662\
+                 07365636f6e64206261696c6f757420666f722062616e6b73",
+            ),
+        ];
+
+        for (script_hex, asm) in cases {
+            let bytes = hex::decode(script_hex).unwrap();
+            let script = Script::from_bytes(&bytes).unwrap();
+
+            assert_eq!(core_asm(&script), asm);
+            assert_eq!(Script::from_asm(&script.to_string()).unwrap().to_bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn pushes_name_their_opcode() {
+        let script = Script::new(vec![
+            Cmd::push(vec![0xab; 5]),
+            Cmd::Push { opcode: OP_PUSHDATA1, data: vec![0xcd] },
+            Cmd::Push { opcode: OP_PUSHDATA2, data: vec![] },
+            Cmd::push(vec![]),
+            Cmd::Op(0xbb),
+            Cmd::Op(OP_CHECKSIG),
+        ]);
+
+        assert_eq!(
+            script.to_string(),
+            "OP_PUSHBYTES_5 ababababab OP_PUSHDATA1 -77 OP_PUSHDATA2 0 0 0xbb OP_CHECKSIG"
+        );
+        assert_eq!(Script::from_asm(&script.to_string()), Ok(script));
+    }
+
+    #[test]
+    fn pushes_that_are_not_minimal_numbers_keep_their_bytes() {
+        let script = Script::new(vec![
+            Cmd::push(vec![0x00]),
+            Cmd::push(vec![0x80]),
+            Cmd::push(vec![0x05, 0x00]),
+            Cmd::push(vec![0xff, 0xff, 0xff, 0x7f]),
+            Cmd::push(vec![0x01, 0x00, 0x00, 0x00, 0x80]),
+            Cmd::push(hex::decode("1234567890").unwrap()),
+            Cmd::push(hex::decode("9999999999").unwrap()),
+        ]);
+
+        assert_eq!(
+            script.to_string(),
+            "OP_PUSHBYTES_1 0x00 OP_PUSHBYTES_1 0x80 OP_PUSHBYTES_2 0x0500 OP_PUSHBYTES_4 2147483647 \
+             OP_PUSHBYTES_5 0x0100000080 OP_PUSHBYTES_5 0x1234567890 OP_PUSHBYTES_5 9999999999"
+        );
+        assert_eq!(Script::from_asm(&script.to_string()), Ok(script));
+        assert_eq!(
+            Script::from_asm("OP_PUSHBYTES_1 500000"),
+            Err(AsmError::DataLength { opcode: "OP_PUSHBYTES_1".to_string(), length: 3 })
+        );
+    }
+
+    #[test]
+    fn every_opcode_round_trips() {
+        let bytes: Vec<u8> = (0x4f..=0xff).collect();
+        let script = Script::from_bytes(&bytes).unwrap();
+
+        let asm = script.to_string();
+
+        assert_eq!(Script::from_asm(&asm).unwrap().to_bytes(), bytes);
+        assert!(asm.starts_with("-1 OP_RESERVED 1 2 3"));
+        assert!(asm.contains("OP_NOP1 OP_CHECKLOCKTIMEVERIFY OP_CHECKSEQUENCEVERIFY OP_NOP4"));
+        assert!(asm.contains("OP_CAT OP_SUBSTR OP_LEFT OP_RIGHT"));
+        assert!(asm.contains("OP_CHECKSIGADD 0xbb 0xbc") && asm.ends_with("0xfe OP_INVALIDOPCODE"));
+        assert_eq!((0x4f..=0xff).filter(|opcode| opcode_name(*opcode).is_some()).count(), 0xba - 0x4f + 2);
+    }
+
+    #[test]
+    fn malformed_asm_is_rejected() {
+        let invalid_hex = HexError::InvalidCharacter { character: 'z', position: 0 };
+
+        assert_eq!(Script::from_asm("OP_DUPE"), Err(AsmError::UnknownToken("OP_DUPE".to_string())));
+        assert_eq!(Script::from_asm("17"), Err(AsmError::UnknownToken("17".to_string())));
+        assert_eq!(Script::from_asm("0x4c"), Err(AsmError::UnknownToken("0x4c".to_string())));
+        assert_eq!(Script::from_asm("OP_PUSHDATA1"), Err(AsmError::MissingData("OP_PUSHDATA1".to_string())));
+        assert_eq!(
+            Script::from_asm("OP_PUSHBYTES_2 ab"),
+            Err(AsmError::DataLength { opcode: "OP_PUSHBYTES_2".to_string(), length: 1 })
+        );
+        assert_eq!(Script::from_asm("OP_PUSHBYTES_76 ab"), Err(AsmError::UnknownToken("OP_PUSHBYTES_76".to_string())));
+        assert_eq!(
+            Script::from_asm("OP_PUSHBYTES_1 zz"),
+            Err(AsmError::InvalidData { opcode: "OP_PUSHBYTES_1".to_string(), error: invalid_hex })
+        );
+    }
+}
@@ -0,0 +1,1992 @@
+use std::fmt::{Display, Formatter};
+use std::ops::BitOr;
+use std::slice;
+use crate::ecc::secp256k1::S256Point;
+use crate::ecc::signature::Signature;
+use crate::ecc::u256::U256;
+use crate::hashing::hash160::hash160;
+use crate::hashing::hash256::hash256;
+use crate::hashing::ripemd160::ripemd160;
+use crate::hashing::sha256::sha256;
+use crate::script::opcodes::{
+    OP_0NOTEQUAL, OP_1, OP_16, OP_1ADD, OP_1NEGATE, OP_1SUB, OP_2DIV, OP_2DROP, OP_2DUP, OP_2MUL, OP_2OVER, OP_2ROT,
+    OP_2SWAP, OP_3DUP, OP_ABS, OP_ADD, OP_AND, OP_BOOLAND, OP_BOOLOR, OP_CAT, OP_CHECKLOCKTIMEVERIFY,
+    OP_CHECKMULTISIG, OP_CHECKMULTISIGVERIFY, OP_CHECKSEQUENCEVERIFY, OP_CHECKSIG, OP_CHECKSIGVERIFY,
+    OP_CODESEPARATOR, OP_DEPTH, OP_DIV, OP_DROP, OP_DUP, OP_ELSE, OP_ENDIF, OP_EQUAL, OP_EQUALVERIFY,
+    OP_FROMALTSTACK, OP_GREATERTHAN, OP_GREATERTHANOREQUAL, OP_HASH160, OP_HASH256, OP_IF, OP_IFDUP, OP_INVERT,
+    OP_LEFT, OP_LESSTHAN, OP_LESSTHANOREQUAL, OP_LSHIFT, OP_MAX, OP_MIN, OP_MOD, OP_MUL, OP_NEGATE, OP_NIP, OP_NOP,
+    OP_NOP1, OP_NOP10, OP_NOP4, OP_NOT, OP_NOTIF, OP_NUMEQUAL, OP_NUMEQUALVERIFY, OP_NUMNOTEQUAL, OP_OR, OP_OVER,
+    OP_PICK, OP_RETURN, OP_RIGHT, OP_RIPEMD160, OP_ROLL, OP_ROT, OP_RSHIFT, OP_SHA256, OP_SIZE, OP_SUB, OP_SUBSTR,
+    OP_SWAP, OP_TOALTSTACK, OP_TUCK, OP_VERIFY, OP_WITHIN, OP_XOR,
+};
+use crate::script::standard::MAX_OP_RETURN_DATA;
+use crate::script::taproot::ANNEX_TAG;
+use crate::script::{Cmd, Script};
+use crate::tx::locktime::{
+    LOCKTIME_THRESHOLD, SEQUENCE_FINAL, SEQUENCE_LOCKTIME_DISABLE_FLAG, SEQUENCE_LOCKTIME_MASK,
+    SEQUENCE_LOCKTIME_TYPE_FLAG,
+};
+use crate::tx::sighash::SighashType;
+use crate::tx::transaction::Tx;
+use crate::tx::tx_out::TxOut;
+
+// Bitcoin Core's consensus limits
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+// Counting every opcode above OP_16, whether or not it is executed
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+// Across the stack and the altstack together
+pub const MAX_STACK_SIZE: usize = 1000;
+pub const MAX_PUBKEYS_PER_MULTISIG: usize = 20;
+// Arithmetic opcodes only accept numbers of up to 4 bytes, though they can produce longer ones
+pub const MAX_NUM_SIZE: usize = 4;
+// The locktime opcodes take 5 bytes, enough for any unsigned 32-bit value
+pub const LOCKTIME_NUM_SIZE: usize = 5;
+
+// Opcodes that fail the script wherever they appear, even in a branch that is not taken
+const DISABLED_OPCODES: [u8; 15] = [
+    OP_CAT, OP_SUBSTR, OP_LEFT, OP_RIGHT, OP_INVERT, OP_AND, OP_OR, OP_XOR, OP_2MUL, OP_2DIV, OP_MUL, OP_DIV,
+    OP_MOD, OP_LSHIFT, OP_RSHIFT,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptError {
+    ScriptSize(usize),
+    PushSize(usize),
+    OpCount,
+    StackSize,
+    // The opcode needed more items than the stack held
+    InvalidStackOperation(u8),
+    InvalidAltstackOperation,
+    UnbalancedConditional,
+    DisabledOpcode(u8),
+    // A reserved or unassigned opcode was executed
+    BadOpcode(u8),
+    OpReturn,
+    // The VERIFY opcode that found false on the stack
+    Verify(u8),
+    // A number operand longer than the opcode accepts
+    NumberOverflow(usize),
+    // A non-empty signature that is not strict DER, which BIP66 made invalid
+    SignatureDer,
+    // A push or number operand with a shorter encoding, under VERIFY_MINIMALDATA
+    MinimalData,
+    // A multisig key count above the limit, or a signature count above the key count
+    PubkeyCount(i64),
+    SigCount(i64),
+    // The element OP_CHECKMULTISIG consumes beyond its signatures was not empty
+    NullDummy,
+    // A script_sig spending P2SH that does more than push data
+    SigPushOnly,
+    // The last push of a P2SH script_sig does not parse as a script
+    InvalidRedeemScript,
+    // The last witness item of a P2WSH spend does not parse as a script
+    InvalidWitnessScript,
+    // A version 0 witness program that is neither 20 nor 32 bytes
+    WitnessProgramWrongLength(usize),
+    WitnessProgramWitnessEmpty,
+    // The witness does not match the program: the wrong item count for P2WPKH, or the wrong script for P2WSH
+    WitnessProgramMismatch,
+    // A native witness program spent with a non-empty script_sig
+    WitnessMalleated,
+    // A P2SH-wrapped witness program whose script_sig does more than push the redeem script
+    WitnessMalleatedP2sh,
+    // A witness on an input that does not spend a witness program
+    WitnessUnexpected,
+    // A witness script has to leave exactly one item on the stack
+    CleanStack,
+    // A witness version above 1, or a version 1 program other than a native 32-byte one, which the engine has no
+    // rules for
+    UnsupportedWitnessVersion(u8),
+    // A taproot spend with more than one witness item, which reveals a script this engine cannot run
+    UnsupportedTaprootScriptPath,
+    // A taproot key path signature that is neither 64 bytes nor 65 with a hash type byte
+    SchnorrSignatureSize(usize),
+    // A 65-byte taproot signature whose hash type byte is not one BIP341 defines, or is the implied default
+    SchnorrSignatureHashType(u8),
+    NegativeLocktime,
+    // The transaction's locktime or the input's sequence does not meet the one the script requires
+    UnsatisfiedLocktime,
+    // OP_RETURN data longer than a standard data carrier output holds
+    DataCarrierSize(usize),
+    // An m-of-n multisig to build with no keys, more than OP_CHECKMULTISIG takes, or m outside 1 to n
+    MultisigCounts { m: u8, n: usize },
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScriptError::ScriptSize(size) => {
+                write!(f, "Script is {} bytes, over the {} byte limit.", size, MAX_SCRIPT_SIZE)
+            }
+            ScriptError::PushSize(size) => {
+                write!(f, "Push of {} bytes is over the {} byte limit.", size, MAX_SCRIPT_ELEMENT_SIZE)
+            }
+            ScriptError::OpCount => write!(f, "Script has more than {} opcodes.", MAX_OPS_PER_SCRIPT),
+            ScriptError::StackSize => write!(f, "Stack grew past {} items.", MAX_STACK_SIZE),
+            ScriptError::InvalidStackOperation(opcode) => {
+                write!(f, "Opcode {:#04x} needs more items than the stack holds.", opcode)
+            }
+            ScriptError::InvalidAltstackOperation => write!(f, "OP_FROMALTSTACK with an empty altstack."),
+            ScriptError::UnbalancedConditional => write!(f, "OP_IF, OP_ELSE and OP_ENDIF are not balanced."),
+            ScriptError::DisabledOpcode(opcode) => write!(f, "Opcode {:#04x} is disabled.", opcode),
+            ScriptError::BadOpcode(opcode) => write!(f, "Opcode {:#04x} cannot be executed.", opcode),
+            ScriptError::OpReturn => write!(f, "OP_RETURN was executed."),
+            ScriptError::Verify(opcode) => write!(f, "Opcode {:#04x} found false on the stack.", opcode),
+            ScriptError::NumberOverflow(size) => {
+                write!(f, "Number of {} bytes is longer than {} bytes.", size, MAX_NUM_SIZE)
+            }
+            ScriptError::SignatureDer => write!(f, "Signature is not strict DER."),
+            ScriptError::MinimalData => write!(f, "Push or number is not minimally encoded."),
+            ScriptError::PubkeyCount(count) => {
+                write!(f, "Multisig with {} keys, outside 0 to {}.", count, MAX_PUBKEYS_PER_MULTISIG)
+            }
+            ScriptError::SigCount(count) => write!(f, "Multisig needing {} signatures, more than its keys.", count),
+            ScriptError::NullDummy => write!(f, "OP_CHECKMULTISIG dummy element is not empty."),
+            ScriptError::SigPushOnly => write!(f, "P2SH script_sig has opcodes other than pushes."),
+            ScriptError::InvalidRedeemScript => write!(f, "P2SH redeem script does not parse."),
+            ScriptError::InvalidWitnessScript => write!(f, "P2WSH witness script does not parse."),
+            ScriptError::WitnessProgramWrongLength(length) => {
+                write!(f, "Version 0 witness program of {} bytes, not 20 or 32.", length)
+            }
+            ScriptError::WitnessProgramWitnessEmpty => write!(f, "Witness program spent with an empty witness."),
+            ScriptError::WitnessProgramMismatch => write!(f, "Witness does not match the witness program."),
+            ScriptError::WitnessMalleated => write!(f, "Witness program spent with a non-empty script_sig."),
+            ScriptError::WitnessMalleatedP2sh => {
+                write!(f, "P2SH-wrapped witness program with a script_sig other than the redeem script push.")
+            }
+            ScriptError::WitnessUnexpected => write!(f, "Witness given for an input without a witness program."),
+            ScriptError::CleanStack => write!(f, "Witness script left more than one item on the stack."),
+            ScriptError::UnsupportedWitnessVersion(version) => {
+                write!(f, "Witness version {} is not supported.", version)
+            }
+            ScriptError::UnsupportedTaprootScriptPath => write!(f, "Taproot script path spends are not supported."),
+            ScriptError::SchnorrSignatureSize(size) => write!(f, "Schnorr signature of {} bytes is invalid.", size),
+            ScriptError::SchnorrSignatureHashType(value) => {
+                write!(f, "Schnorr signature hash type {:#04x} is invalid.", value)
+            }
+            ScriptError::NegativeLocktime => write!(f, "Locktime operand is negative."),
+            ScriptError::UnsatisfiedLocktime => write!(f, "Locktime requirement not satisfied."),
+            ScriptError::DataCarrierSize(size) => {
+                write!(f, "OP_RETURN data of {} bytes is over the {} byte limit.", size, MAX_OP_RETURN_DATA)
+            }
+            ScriptError::MultisigCounts { m, n } => {
+                write!(f, "A {}-of-{} multisig is outside 1 <= m <= n <= {}.", m, n, MAX_PUBKEYS_PER_MULTISIG)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+// The fields of the spending transaction that OP_CHECKLOCKTIMEVERIFY and OP_CHECKSEQUENCEVERIFY compare against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxContext {
+    pub locktime: u32,
+    pub sequence_of_input: u32,
+    pub version: u32,
+}
+
+impl TxContext {
+    pub fn new(tx: &Tx, input_index: usize) -> Option<Self> {
+        let input = tx.inputs.get(input_index)?;
+
+        Some(Self { locktime: tx.locktime, sequence_of_input: input.sequence, version: tx.version })
+    }
+}
+
+/*
+    The transaction whose input a script spends, and the outputs its inputs spend, one per input and in order.
+    With one, each signature is checked against the digest for its own hash type byte: the legacy digest with the
+    script being run as the scriptCode, the script_pubkey or the redeem script of a P2SH spend; the BIP143 digest
+    of the prevout's amount in a version 0 witness script; the BIP341 digest of every prevout for a taproot key
+    path spend.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxChecker<'a> {
+    pub tx: &'a Tx,
+    pub input_index: usize,
+    pub prevouts: &'a [TxOut],
+}
+
+impl<'a> TxChecker<'a> {
+    pub fn new(tx: &'a Tx, input_index: usize, prevouts: &'a [TxOut]) -> Self {
+        Self { tx, input_index, prevouts }
+    }
+}
+
+/*
+    The rules a script is checked under. Soft forks added them one at a time, so a historical spend has to be
+    checked with the ones in force when it was mined. The values are Bitcoin Core's SCRIPT_VERIFY_ bits, and flags
+    combine with |.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ScriptFlags(u32);
+
+impl ScriptFlags {
+    pub const NONE: ScriptFlags = ScriptFlags(0);
+    // BIP16: a P2SH spend also runs the redeem script its script_sig reveals
+    pub const VERIFY_P2SH: ScriptFlags = ScriptFlags(1 << 0);
+    // BIP66: signatures have to be strict DER, not whatever OpenSSL used to accept
+    pub const VERIFY_DERSIG: ScriptFlags = ScriptFlags(1 << 2);
+    // BIP147: the extra element OP_CHECKMULTISIG pops has to be empty
+    pub const VERIFY_NULLDUMMY: ScriptFlags = ScriptFlags(1 << 4);
+    // Pushes and number operands have to use their shortest encoding, which is relay policy and not consensus
+    pub const VERIFY_MINIMALDATA: ScriptFlags = ScriptFlags(1 << 6);
+    // BIP65: OP_CHECKLOCKTIMEVERIFY in place of OP_NOP2
+    pub const VERIFY_CLTV: ScriptFlags = ScriptFlags(1 << 9);
+    // BIP112: OP_CHECKSEQUENCEVERIFY in place of OP_NOP3
+    pub const VERIFY_CSV: ScriptFlags = ScriptFlags(1 << 10);
+    // BIP141: witness programs are checked against the witness, which no other input may have
+    pub const VERIFY_WITNESS: ScriptFlags = ScriptFlags(1 << 11);
+    // BIP341: native version 1 programs of 32 bytes are taproot outputs, spent with a Schnorr signature
+    pub const VERIFY_TAPROOT: ScriptFlags = ScriptFlags(1 << 17);
+    // Every rule above that consensus enforces today
+    pub const CONSENSUS: ScriptFlags = ScriptFlags(
+        Self::VERIFY_P2SH.0
+            | Self::VERIFY_DERSIG.0
+            | Self::VERIFY_NULLDUMMY.0
+            | Self::VERIFY_CLTV.0
+            | Self::VERIFY_CSV.0
+            | Self::VERIFY_WITNESS.0
+            | Self::VERIFY_TAPROOT.0,
+    );
+
+    pub fn from_bits(bits: u32) -> Self {
+        ScriptFlags(bits)
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    // Whether every flag set in other is set here
+    pub fn contains(&self, other: ScriptFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for ScriptFlags {
+    type Output = ScriptFlags;
+
+    fn bitor(self, rhs: ScriptFlags) -> ScriptFlags {
+        ScriptFlags(self.0 | rhs.0)
+    }
+}
+
+// The state a script runs against, which the opcode handlers read and change
+#[derive(Debug, Clone)]
+pub struct Machine<'a> {
+    pub stack: Vec<Vec<u8>>,
+    pub altstack: Vec<Vec<u8>>,
+    // The message signatures commit to, unless the checker works one out for each signature
+    pub z: [u8; 32],
+    pub checker: Option<TxChecker<'a>>,
+    // The serialization of the script being run, which the checker's digests commit to
+    script_code: Vec<u8>,
+    // Whether the script is a version 0 witness script, whose signatures the checker hashes with BIP143
+    witness_v0: bool,
+    // The rules the script is checked under
+    pub flags: ScriptFlags,
+    /*
+        The transaction the script is spent in. Without one, OP_CHECKLOCKTIMEVERIFY and OP_CHECKSEQUENCEVERIFY
+        cannot be satisfied once their flags make them more than NOP2 and NOP3.
+     */
+    pub context: Option<TxContext>,
+    // For each OP_IF being executed, whether its current branch runs
+    conditions: Vec<bool>,
+    // Opcodes counted towards MAX_OPS_PER_SCRIPT in the script being run
+    op_count: usize,
+}
+
+impl Machine<'_> {
+    pub fn new(stack: Vec<Vec<u8>>, z: [u8; 32]) -> Self {
+        Self {
+            stack,
+            altstack: vec![],
+            z,
+            checker: None,
+            script_code: vec![],
+            witness_v0: false,
+            flags: ScriptFlags::NONE,
+            context: None,
+            conditions: vec![],
+            op_count: 0,
+        }
+    }
+
+    fn executing(&self) -> bool {
+        self.conditions.iter().all(|branch_runs| *branch_runs)
+    }
+
+    pub fn pop(&mut self, opcode: u8) -> Result<Vec<u8>, ScriptError> {
+        self.stack.pop().ok_or(ScriptError::InvalidStackOperation(opcode))
+    }
+
+    // The item this many places below the top, 0 being the top itself
+    pub fn peek(&self, depth: usize, opcode: u8) -> Result<&Vec<u8>, ScriptError> {
+        self.stack
+            .len()
+            .checked_sub(depth + 1)
+            .map(|index| &self.stack[index])
+            .ok_or(ScriptError::InvalidStackOperation(opcode))
+    }
+
+    pub fn pop_num(&mut self, opcode: u8) -> Result<i64, ScriptError> {
+        let item = self.pop(opcode)?;
+        self.decode_num(&item, MAX_NUM_SIZE)
+    }
+
+    // decode_num, which under VERIFY_MINIMALDATA also wants the encoding encode_num would give
+    fn decode_num(&self, bytes: &[u8], max_size: usize) -> Result<i64, ScriptError> {
+        if self.flags.contains(ScriptFlags::VERIFY_MINIMALDATA) && !is_minimal_num(bytes) {
+            return Err(ScriptError::MinimalData);
+        }
+
+        decode_num(bytes, max_size)
+    }
+
+    pub fn push_num(&mut self, number: i64) {
+        self.stack.push(encode_num(number));
+    }
+
+    pub fn push_bool(&mut self, value: bool) {
+        self.push_num(value as i64);
+    }
+
+    pub fn pop_bool(&mut self, opcode: u8) -> Result<bool, ScriptError> {
+        Ok(cast_to_bool(&self.pop(opcode)?))
+    }
+
+    fn check_depth(&self, depth: usize, opcode: u8) -> Result<(), ScriptError> {
+        match self.stack.len() < depth {
+            true => Err(ScriptError::InvalidStackOperation(opcode)),
+            false => Ok(()),
+        }
+    }
+
+    fn top_is_true(&self) -> bool {
+        self.stack.last().is_some_and(|top| cast_to_bool(top))
+    }
+
+    /*
+        Checks a spend the way consensus does, where Script::evaluate takes the two scripts joined into one. The
+        script_sig runs first and the script_pubkey then runs on the stack it leaves, each with its own opcode
+        limit and conditionals that have to balance within it. Only the script_pubkey decides whether the spend is
+        P2SH or a witness program.
+     */
+    pub fn verify_spend(
+        mut self,
+        script_sig: &Script,
+        script_pubkey: &Script,
+        witness: &[Vec<u8>],
+    ) -> Result<bool, ScriptError> {
+        let flags = self.flags;
+        if let Some((version, program)) = script_pubkey.witness_program() {
+            if flags.contains(ScriptFlags::VERIFY_WITNESS) {
+                if !script_sig.cmds.is_empty() {
+                    return Err(ScriptError::WitnessMalleated);
+                }
+                return execute_witness_program(version, &program, false, witness, &self);
+            }
+        }
+
+        script_sig.run(&mut self)?;
+        let stack_after_script_sig = self.stack.clone();
+        script_pubkey.run(&mut self)?;
+        if !self.top_is_true() {
+            return Ok(false);
+        }
+        if !flags.contains(ScriptFlags::VERIFY_P2SH) || !script_pubkey.is_p2sh() {
+            return legacy_result(&self, witness);
+        }
+
+        if !script_sig.is_push_only() {
+            return Err(ScriptError::SigPushOnly);
+        }
+        self.stack = stack_after_script_sig;
+        let serialized_redeem_script = self.stack.pop().unwrap_or_default();
+        let redeem_script =
+            Script::from_bytes(&serialized_redeem_script).map_err(|_| ScriptError::InvalidRedeemScript)?;
+        let witness_program = redeem_script.witness_program().filter(|_| flags.contains(ScriptFlags::VERIFY_WITNESS));
+        if let Some((version, program)) = witness_program {
+            if !is_single_push_of(script_sig, &serialized_redeem_script) {
+                return Err(ScriptError::WitnessMalleatedP2sh);
+            }
+            return execute_witness_program(version, &program, true, witness, &self);
+        }
+        redeem_script.run(&mut self)?;
+
+        legacy_result(&self, witness)
+    }
+
+    fn add_ops(&mut self, count: usize) -> Result<(), ScriptError> {
+        self.op_count += count;
+        match self.op_count > MAX_OPS_PER_SCRIPT {
+            true => Err(ScriptError::OpCount),
+            false => Ok(()),
+        }
+    }
+}
+
+/*
+    Script numbers are little-endian and variable length, with the sign in the top bit of the last byte. Zero
+    is the empty vector, and a byte is only added when the magnitude needs the top bit of the last one.
+ */
+pub fn encode_num(number: i64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut magnitude = number.unsigned_abs();
+    while magnitude > 0 {
+        bytes.push(magnitude as u8);
+        magnitude >>= 8;
+    }
+
+    if let Some(last) = bytes.last_mut() {
+        match (*last & 0x80 != 0, number < 0) {
+            (true, negative) => bytes.push(if negative { 0x80 } else { 0x00 }),
+            (false, true) => *last |= 0x80,
+            (false, false) => {}
+        }
+    }
+
+    bytes
+}
+
+// Any encoding is accepted, including those with needless zero bytes, as long as it fits in max_size bytes
+pub fn decode_num(bytes: &[u8], max_size: usize) -> Result<i64, ScriptError> {
+    if bytes.len() > max_size {
+        return Err(ScriptError::NumberOverflow(bytes.len()));
+    }
+    let Some((&last, _)) = bytes.split_last() else {
+        return Ok(0);
+    };
+
+    let magnitude = bytes
+        .iter()
+        .enumerate()
+        .fold(0i64, |value, (index, byte)| value | (*byte as i64) << (8 * index))
+        & !(0x80i64 << (8 * (bytes.len() - 1)));
+
+    Ok(if last & 0x80 != 0 { -magnitude } else { magnitude })
+}
+
+// No zero byte at the end, unless the byte before it needs its top bit kept clear of the sign
+fn is_minimal_num(bytes: &[u8]) -> bool {
+    match bytes {
+        [] => true,
+        [.., last] if last & 0x7f != 0 => true,
+        [.., before, _] => before & 0x80 != 0,
+        [_] => false,
+    }
+}
+
+/*
+    Bitcoin Core's CheckMinimalPush: the numbers -1 and 1 to 16 have to be pushed with their own opcodes, and
+    anything else, empty data included, with the shortest push opcode that fits it.
+ */
+fn is_minimal_push(opcode: u8, data: &[u8]) -> bool {
+    match (data, Cmd::push(data.to_vec())) {
+        ([1..=16 | 0x81], _) => false,
+        (_, Cmd::Push { opcode: shortest, .. }) => opcode == shortest,
+        (_, Cmd::Op(_)) => unreachable!("Cmd::push always gives a push."),
+    }
+}
+
+// False for any encoding of zero, including negative zero
+pub fn cast_to_bool(bytes: &[u8]) -> bool {
+    match bytes.split_last() {
+        Some((&last, rest)) => rest.iter().any(|byte| *byte != 0) || (last != 0 && last != 0x80),
+        None => false,
+    }
+}
+
+// Every handler is given the opcode it was registered for, so one handler can serve a family of opcodes
+type Handler = fn(&mut Machine, u8) -> Result<(), ScriptError>;
+
+// Opcodes without a handler fail as bad opcodes when executed
+static HANDLERS: [Option<Handler>; 256] = handlers();
+
+const fn handlers() -> [Option<Handler>; 256] {
+    let mut table: [Option<Handler>; 256] = [None; 256];
+
+    table[OP_1NEGATE as usize] = Some(op_small_int);
+    let mut opcode = OP_1;
+    while opcode <= OP_16 {
+        table[opcode as usize] = Some(op_small_int);
+        opcode += 1;
+    }
+
+    table[OP_NOP as usize] = Some(op_nop);
+    table[OP_NOP1 as usize] = Some(op_nop);
+    // Without the BIP65 and BIP112 rules, the timelock opcodes are the NOPs they replaced
+    table[OP_CHECKLOCKTIMEVERIFY as usize] = Some(op_checklocktimeverify);
+    table[OP_CHECKSEQUENCEVERIFY as usize] = Some(op_checksequenceverify);
+    let mut opcode = OP_NOP4;
+    while opcode <= OP_NOP10 {
+        table[opcode as usize] = Some(op_nop);
+        opcode += 1;
+    }
+
+    table[OP_IF as usize] = Some(op_if);
+    table[OP_NOTIF as usize] = Some(op_if);
+    table[OP_ELSE as usize] = Some(op_else);
+    table[OP_ENDIF as usize] = Some(op_endif);
+    table[OP_VERIFY as usize] = Some(op_verify);
+    table[OP_RETURN as usize] = Some(op_return);
+
+    table[OP_TOALTSTACK as usize] = Some(op_altstack);
+    table[OP_FROMALTSTACK as usize] = Some(op_altstack);
+    let mut opcode = OP_2DROP;
+    while opcode <= OP_TUCK {
+        table[opcode as usize] = Some(op_stack);
+        opcode += 1;
+    }
+    table[OP_SIZE as usize] = Some(op_size);
+    table[OP_EQUAL as usize] = Some(op_equal);
+    table[OP_EQUALVERIFY as usize] = Some(op_equal);
+
+    let mut opcode = OP_1ADD;
+    while opcode <= OP_0NOTEQUAL {
+        if opcode != OP_2MUL && opcode != OP_2DIV {
+            table[opcode as usize] = Some(op_unary_num);
+        }
+        opcode += 1;
+    }
+    let mut opcode = OP_ADD;
+    while opcode <= OP_MAX {
+        if opcode < OP_MUL || opcode > OP_RSHIFT {
+            table[opcode as usize] = Some(op_binary_num);
+        }
+        opcode += 1;
+    }
+    table[OP_WITHIN as usize] = Some(op_within);
+
+    // OP_SHA1 is left out, as the crate has no SHA-1
+    table[OP_RIPEMD160 as usize] = Some(op_hash);
+    table[OP_SHA256 as usize] = Some(op_hash);
+    table[OP_HASH160 as usize] = Some(op_hash);
+    table[OP_HASH256 as usize] = Some(op_hash);
+    // Script::run moves the start of the scriptCode past it, which only the checker's digests look at
+    table[OP_CODESEPARATOR as usize] = Some(op_nop);
+    table[OP_CHECKSIG as usize] = Some(op_checksig);
+    table[OP_CHECKSIGVERIFY as usize] = Some(op_checksig);
+    table[OP_CHECKMULTISIG as usize] = Some(op_checkmultisig);
+    table[OP_CHECKMULTISIGVERIFY as usize] = Some(op_checkmultisig);
+
+    table
+}
+
+fn op_small_int(machine: &mut Machine, opcode: u8) -> Result<(), ScriptError> {
+    machine.push_num(opcode as i64 - (OP_1 - 1) as i64);
+    Ok(())
+}
+
+fn op_nop(_machine: &mut Machine, _opcode: u8) -> Result<(), ScriptError> {
+    Ok(())
+}
+
+// OP_IF and OP_NOTIF are reached in branches that are not taken too, where they only open a nested branch
+fn op_if(machine: &mut Machine, opcode: u8) -> Result<(), ScriptError> {
+    let branch_runs = match machine.executing() {
+        true => machine.pop_bool(opcode).map_err(|_| ScriptError::UnbalancedConditional)? == (opcode == OP_IF),
+        false => false,
+    };
+    machine.conditions.push(branch_runs);
+    Ok(())
+}
+
+fn op_else(machine: &mut Machine, _opcode: u8) -> Result<(), ScriptError> {
+    let branch_runs = machine.conditions.last_mut().ok_or(ScriptError::UnbalancedConditional)?;
+    *branch_runs = !*branch_runs;
+    Ok(())
+}
+
+fn op_endif(machine: &mut Machine, _opcode: u8) -> Result<(), ScriptError> {
+    machine.conditions.pop().ok_or(ScriptError::UnbalancedConditional)?;
+    Ok(())
+}
+
+fn op_verify(machine: &mut Machine, opcode: u8) -> Result<(), ScriptError> {
+    match machine.pop_bool(opcode)? {
+        true => Ok(()),
+        false => Err(ScriptError::Verify(opcode)),
+    }
+}
+
+fn op_return(_machine: &mut Machine, _opcode: u8) -> Result<(), ScriptError> {
+    Err(ScriptError::OpReturn)
+}
+
+fn op_altstack(machine: &mut Machine, opcode: u8) -> Result<(), ScriptError> {
+    match opcode {
+        OP_TOALTSTACK => {
+            let item = machine.pop(opcode)?;
+            machine.altstack.push(item);
+        }
+        _ => {
+            let item = machine.altstack.pop().ok_or(ScriptError::InvalidAltstackOperation)?;
+            machine.stack.push(item);
+        }
+    }
+    Ok(())
+}
+
+fn op_stack(machine: &mut Machine, opcode: u8) -> Result<(), ScriptError> {
+    let stack_len = machine.stack.len();
+
+    match opcode {
+        OP_2DROP => {
+            machine.check_depth(2, opcode)?;
+            machine.stack.truncate(stack_len - 2);
+        }
+        OP_2DUP | OP_3DUP => {
+            let count = if opcode == OP_2DUP { 2 } else { 3 };
+            machine.check_depth(count, opcode)?;
+            machine.stack.extend_from_within(stack_len - count..);
+        }
+        OP_2OVER => {
+            machine.check_depth(4, opcode)?;
+            machine.stack.extend_from_within(stack_len - 4..stack_len - 2);
+        }
+        OP_2ROT => {
+            machine.check_depth(6, opcode)?;
+            machine.stack[stack_len - 6..].rotate_left(2);
+        }
+        OP_2SWAP => {
+            machine.check_depth(4, opcode)?;
+            machine.stack[stack_len - 4..].rotate_left(2);
+        }
+        OP_IFDUP => {
+            let top = machine.peek(0, opcode)?.clone();
+            if cast_to_bool(&top) {
+                machine.stack.push(top);
+            }
+        }
+        OP_DEPTH => machine.push_num(stack_len as i64),
+        OP_DROP => {
+            machine.pop(opcode)?;
+        }
+        OP_DUP => machine.stack.push(machine.peek(0, opcode)?.clone()),
+        OP_NIP => {
+            machine.check_depth(2, opcode)?;
+            machine.stack.remove(stack_len - 2);
+        }
+        OP_OVER => machine.stack.push(machine.peek(1, opcode)?.clone()),
+        OP_PICK | OP_ROLL => {
+            let depth = machine.pop_num(opcode)?;
+            let depth = usize::try_from(depth).map_err(|_| ScriptError::InvalidStackOperation(opcode))?;
+            let item = machine.peek(depth, opcode)?.clone();
+            if opcode == OP_ROLL {
+                machine.stack.remove(machine.stack.len() - 1 - depth);
+            }
+            machine.stack.push(item);
+        }
+        OP_ROT => {
+            machine.check_depth(3, opcode)?;
+            machine.stack[stack_len - 3..].rotate_left(1);
+        }
+        OP_SWAP => {
+            machine.check_depth(2, opcode)?;
+            machine.stack.swap(stack_len - 1, stack_len - 2);
+        }
+        OP_TUCK => {
+            machine.check_depth(2, opcode)?;
+            let top = machine.stack[stack_len - 1].clone();
+            machine.stack.insert(stack_len - 2, top);
+        }
+        _ => unreachable!("op_stack is only registered for the stack opcodes."),
+    }
+    Ok(())
+}
+
+fn op_size(machine: &mut Machine, opcode: u8) -> Result<(), ScriptError> {
+    let size = machine.peek(0, opcode)?.len();
+    machine.push_num(size as i64);
+    Ok(())
+}
+
+fn op_equal(machine: &mut Machine, opcode: u8) -> Result<(), ScriptError> {
+    machine.check_depth(2, opcode)?;
+    let (b, a) = (machine.pop(opcode)?, machine.pop(opcode)?);
+    machine.push_bool(a == b);
+
+    match opcode {
+        OP_EQUALVERIFY => op_verify(machine, opcode),
+        _ => Ok(()),
+    }
+}
+
+fn op_unary_num(machine: &mut Machine, opcode: u8) -> Result<(), ScriptError> {
+    let a = machine.pop_num(opcode)?;
+
+    let result = match opcode {
+        OP_1ADD => a + 1,
+        OP_1SUB => a - 1,
+        OP_NEGATE => -a,
+        OP_ABS => a.abs(),
+        OP_NOT => (a == 0) as i64,
+        OP_0NOTEQUAL => (a != 0) as i64,
+        _ => unreachable!("op_unary_num is only registered for the one-operand arithmetic opcodes."),
+    };
+    machine.push_num(result);
+    Ok(())
+}
+
+// Operands are popped in reverse, so `a b OP_SUB` computes a - b
+fn op_binary_num(machine: &mut Machine, opcode: u8) -> Result<(), ScriptError> {
+    machine.check_depth(2, opcode)?;
+    let (b, a) = (machine.pop_num(opcode)?, machine.pop_num(opcode)?);
+
+    let result = match opcode {
+        OP_ADD => a + b,
+        OP_SUB => a - b,
+        OP_BOOLAND => (a != 0 && b != 0) as i64,
+        OP_BOOLOR => (a != 0 || b != 0) as i64,
+        OP_NUMEQUAL | OP_NUMEQUALVERIFY => (a == b) as i64,
+        OP_NUMNOTEQUAL => (a != b) as i64,
+        OP_LESSTHAN => (a < b) as i64,
+        OP_GREATERTHAN => (a > b) as i64,
+        OP_LESSTHANOREQUAL => (a <= b) as i64,
+        OP_GREATERTHANOREQUAL => (a >= b) as i64,
+        OP_MIN => a.min(b),
+        OP_MAX => a.max(b),
+        _ => unreachable!("op_binary_num is only registered for the two-operand arithmetic opcodes."),
+    };
+    machine.push_num(result);
+
+    match opcode {
+        OP_NUMEQUALVERIFY => op_verify(machine, opcode),
+        _ => Ok(()),
+    }
+}
+
+// x min max OP_WITHIN is true when min <= x < max
+fn op_within(machine: &mut Machine, opcode: u8) -> Result<(), ScriptError> {
+    machine.check_depth(3, opcode)?;
+    let (max, min, x) = (machine.pop_num(opcode)?, machine.pop_num(opcode)?, machine.pop_num(opcode)?);
+    machine.push_bool(min <= x && x < max);
+    Ok(())
+}
+
+fn op_hash(machine: &mut Machine, opcode: u8) -> Result<(), ScriptError> {
+    let data = machine.pop(opcode)?;
+
+    let digest = match opcode {
+        OP_RIPEMD160 => ripemd160(&data).to_vec(),
+        OP_SHA256 => sha256(&data).to_vec(),
+        OP_HASH160 => hash160(&data).to_vec(),
+        OP_HASH256 => hash256(&data).to_vec(),
+        _ => unreachable!("op_hash is only registered for the hashing opcodes."),
+    };
+    machine.stack.push(digest);
+    Ok(())
+}
+
+// <sig> <pubkey> OP_CHECKSIG pushes whether sig is a valid signature of z by pubkey
+/*
+    BIP65: fails unless the transaction's locktime is of the same kind, height or time, as the number on top of
+    the stack and at least as large, and the input's sequence leaves the locktime in force. The number is left on
+    the stack, and may be 5 bytes long since locktimes use all 32 bits.
+ */
+fn op_checklocktimeverify(machine: &mut Machine, opcode: u8) -> Result<(), ScriptError> {
+    if !machine.flags.contains(ScriptFlags::VERIFY_CLTV) {
+        return Ok(());
+    }
+    let locktime = machine.decode_num(machine.peek(0, opcode)?, LOCKTIME_NUM_SIZE)?;
+    if locktime < 0 {
+        return Err(ScriptError::NegativeLocktime);
+    }
+    let context = machine.context.ok_or(ScriptError::UnsatisfiedLocktime)?;
+
+    let threshold = LOCKTIME_THRESHOLD as i64;
+    let tx_locktime = context.locktime as i64;
+    let same_kind = (locktime < threshold) == (tx_locktime < threshold);
+    if !same_kind || locktime > tx_locktime || context.sequence_of_input == SEQUENCE_FINAL {
+        return Err(ScriptError::UnsatisfiedLocktime);
+    }
+
+    Ok(())
+}
+
+/*
+    BIP112: fails unless the input's BIP68 relative locktime is of the same kind, blocks or time, as the number on
+    top of the stack and at least as large. A number with the disable flag set makes it a NOP, while an input
+    whose own sequence has it set, or a transaction version below 2, cannot satisfy it.
+ */
+fn op_checksequenceverify(machine: &mut Machine, opcode: u8) -> Result<(), ScriptError> {
+    if !machine.flags.contains(ScriptFlags::VERIFY_CSV) {
+        return Ok(());
+    }
+    let sequence = machine.decode_num(machine.peek(0, opcode)?, LOCKTIME_NUM_SIZE)?;
+    if sequence < 0 {
+        return Err(ScriptError::NegativeLocktime);
+    }
+    if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG as i64 != 0 {
+        return Ok(());
+    }
+    let context = machine.context.ok_or(ScriptError::UnsatisfiedLocktime)?;
+    if context.version < 2 || context.sequence_of_input & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+        return Err(ScriptError::UnsatisfiedLocktime);
+    }
+
+    let mask = SEQUENCE_LOCKTIME_TYPE_FLAG | SEQUENCE_LOCKTIME_MASK;
+    let (required, available) = (sequence as u32 & mask, context.sequence_of_input & mask);
+    let same_kind = (required & SEQUENCE_LOCKTIME_TYPE_FLAG) == (available & SEQUENCE_LOCKTIME_TYPE_FLAG);
+    if !same_kind || required > available {
+        return Err(ScriptError::UnsatisfiedLocktime);
+    }
+
+    Ok(())
+}
+
+fn op_checksig(machine: &mut Machine, opcode: u8) -> Result<(), ScriptError> {
+    machine.check_depth(2, opcode)?;
+    let (pubkey, signature) = (machine.pop(opcode)?, machine.pop(opcode)?);
+
+    let valid = check_signature(&signature, &pubkey, slice::from_ref(&signature), machine)?;
+    machine.push_bool(valid);
+
+    match opcode {
+        OP_CHECKSIGVERIFY => op_verify(machine, opcode),
+        _ => Ok(()),
+    }
+}
+
+/*
+    <dummy> <sig 1> ... <sig m> m <pubkey 1> ... <pubkey n> n OP_CHECKMULTISIG
+
+    Signatures have to appear in the same order as their pubkeys. Each signature is tried against the pubkeys
+    that remain after the last match, so a mismatch uses up a pubkey and the check fails as soon as there are
+    fewer pubkeys left than signatures. An off-by-one in the original implementation pops one element more than
+    it needs, which spends have to supply; BIP147 requires it to be empty.
+ */
+fn op_checkmultisig(machine: &mut Machine, opcode: u8) -> Result<(), ScriptError> {
+    let key_count = machine.pop_num(opcode)?;
+    if !(0..=MAX_PUBKEYS_PER_MULTISIG as i64).contains(&key_count) {
+        return Err(ScriptError::PubkeyCount(key_count));
+    }
+    // Each key counts towards the opcode limit, as if it were checked by its own OP_CHECKSIG
+    machine.add_ops(key_count as usize)?;
+    machine.check_depth(key_count as usize + 1, opcode)?;
+    let pubkeys = machine.stack.split_off(machine.stack.len() - key_count as usize);
+
+    let sig_count = machine.pop_num(opcode)?;
+    if !(0..=key_count).contains(&sig_count) {
+        return Err(ScriptError::SigCount(sig_count));
+    }
+    machine.check_depth(sig_count as usize + 1, opcode)?;
+    let signatures = machine.stack.split_off(machine.stack.len() - sig_count as usize);
+
+    let dummy = machine.pop(opcode)?;
+    if machine.flags.contains(ScriptFlags::VERIFY_NULLDUMMY) && !dummy.is_empty() {
+        return Err(ScriptError::NullDummy);
+    }
+
+    let mut remaining_pubkeys = pubkeys.iter();
+    let mut valid = true;
+    for (index, signature) in signatures.iter().enumerate() {
+        let mut matched = false;
+        while !matched && remaining_pubkeys.len() >= signatures.len() - index {
+            let pubkey = remaining_pubkeys.next().expect("At least one pubkey remains.");
+            matched = check_signature(signature, pubkey, &signatures, machine)?;
+        }
+        if !matched {
+            valid = false;
+            break;
+        }
+    }
+    machine.push_bool(valid);
+
+    match opcode {
+        OP_CHECKMULTISIGVERIFY => op_verify(machine, opcode),
+        _ => Ok(()),
+    }
+}
+
+/*
+    Whether the signature, DER with the sighash type appended as one byte, is a valid signature by the SEC pubkey.
+    Without a checker the message is the machine's z, which already commits to the sighash type; with one it is
+    the digest for that type over the script being run, BIP143's in a version 0 witness script and the legacy
+    one otherwise. The legacy scriptCode has every signature in signed_over deleted from it first, as Bitcoin
+    Core's FindAndDelete does, so a script can contain the signature that signs it. An empty signature or a pubkey
+    that is not a point on the curve is just false. Under VERIFY_DERSIG a signature that is not strict DER fails
+    the script, while without it the lax DER consensus accepted before BIP66 is read, and one that is not even
+    that is just false.
+ */
+fn check_signature(
+    signature: &[u8],
+    pubkey: &[u8],
+    signed_over: &[Vec<u8>],
+    machine: &Machine,
+) -> Result<bool, ScriptError> {
+    let Some((sighash_type, der)) = signature.split_last() else {
+        return Ok(false);
+    };
+    let signature = match machine.flags.contains(ScriptFlags::VERIFY_DERSIG) {
+        true => Signature::parse_der(der).map_err(|_| ScriptError::SignatureDer)?,
+        false => match Signature::parse_der_lax(der) {
+            Some(signature) => signature,
+            None => return Ok(false),
+        },
+    };
+
+    let z = match &machine.checker {
+        Some(checker) => {
+            let sighash_type = SighashType::from_consensus(*sighash_type as u32);
+            let (tx, input_index, script_code) = (checker.tx, checker.input_index, &machine.script_code);
+            let z = match machine.witness_v0 {
+                true => match checker.prevouts.get(input_index) {
+                    Some(prevout) => tx.sig_hash_bip143(input_index, script_code, prevout.amount, sighash_type),
+                    None => return Ok(false),
+                },
+                false => tx.sig_hash(input_index, &find_and_delete(script_code, signed_over), sighash_type),
+            };
+            match z {
+                Ok(z) => z,
+                Err(_) => return Ok(false),
+            }
+        }
+        None => machine.z,
+    };
+
+    Ok(S256Point::parse_sec(pubkey).is_ok_and(|point| point.verify(&z, &signature)))
+}
+
+/*
+    The legacy scriptCode with every push of one of the signatures removed, along with any OP_CODESEPARATOR, which
+    the legacy digest also leaves out. The pushes matched are the minimal ones, as Bitcoin Core builds them.
+ */
+fn find_and_delete(script_code: &[u8], signatures: &[Vec<u8>]) -> Vec<u8> {
+    let script = Script::from_bytes(script_code).expect("The script being run has already been parsed.");
+    let pushes: Vec<Cmd> = signatures.iter().map(|signature| Cmd::push(signature.clone())).collect();
+    let cmds = script.cmds.into_iter().filter(|cmd| *cmd != Cmd::Op(OP_CODESEPARATOR) && !pushes.contains(cmd));
+
+    Script::new(cmds.collect()).to_bytes()
+}
+
+impl Script {
+    /*
+        Runs the script and reports whether it left a true value on top of the stack. A script that fails
+        outright, such as on a VERIFY of false or too many opcodes, is an error rather than false.
+
+        This is the book's way of checking a spend: the script_sig's commands followed by the script_pubkey's,
+        with the input's witness if it has one. Joining them lets a conditional or the opcode limit span both
+        scripts, and takes any script_pubkey ending in the P2SH template for P2SH, so Machine::verify_spend is the
+        one that matches consensus.
+
+        BIP16, under VERIFY_P2SH: when the commands end in the P2SH template, the ones before it are taken as the
+        script_sig. Once the template has checked the hash of the last push, that push is parsed as the redeem
+        script and run on what the rest of the script_sig left on the stack.
+
+        BIP141, under VERIFY_WITNESS: when the commands, or a P2SH redeem script, are a witness program, the
+        witness is what gets checked. z then has to be the BIP143 digest rather than the legacy one, or under
+        VERIFY_TAPROOT the BIP341 one for the hash type of a taproot key path signature.
+
+        The context is the spending transaction's locktime, version and input sequence, which the BIP65 and BIP112
+        timelock opcodes check under VERIFY_CLTV and VERIFY_CSV. Without those flags the opcodes do nothing, as
+        before those soft forks, while with them a missing context leaves the timelock unsatisfied.
+
+        The flags pick the rules the spend is checked under, ScriptFlags::CONSENSUS for today's.
+     */
+    pub fn evaluate(
+        &self,
+        z: [u8; 32],
+        witness: Option<&[Vec<u8>]>,
+        context: Option<&TxContext>,
+        flags: ScriptFlags,
+    ) -> Result<bool, ScriptError> {
+        let witness = witness.unwrap_or_default();
+        let mut machine = Machine::new(vec![], z);
+        machine.flags = flags;
+        machine.context = context.copied();
+        let witness_program = self.witness_program().filter(|_| flags.contains(ScriptFlags::VERIFY_WITNESS));
+        if let Some((version, program)) = witness_program {
+            return execute_witness_program(version, &program, false, witness, &machine);
+        }
+
+        let (script_sig, script_pubkey) = self.cmds.split_at(self.cmds.len().saturating_sub(3));
+        let script_pubkey = Script::new(script_pubkey.to_vec());
+        if script_sig.is_empty() || !script_pubkey.is_p2sh() || !flags.contains(ScriptFlags::VERIFY_P2SH) {
+            self.run(&mut machine)?;
+            return legacy_result(&machine, witness);
+        }
+
+        let script_sig = Script::new(script_sig.to_vec());
+        if !script_sig.is_push_only() {
+            return Err(ScriptError::SigPushOnly);
+        }
+        script_sig.run(&mut machine)?;
+        let serialized_redeem_script = machine.stack.last().cloned().unwrap_or_default();
+
+        script_pubkey.run(&mut machine)?;
+        if !machine.top_is_true() {
+            return Ok(false);
+        }
+        machine.stack.pop();
+
+        let redeem_script =
+            Script::from_bytes(&serialized_redeem_script).map_err(|_| ScriptError::InvalidRedeemScript)?;
+        let witness_program = redeem_script.witness_program().filter(|_| flags.contains(ScriptFlags::VERIFY_WITNESS));
+        if let Some((version, program)) = witness_program {
+            if !is_single_push_of(&script_sig, &serialized_redeem_script) {
+                return Err(ScriptError::WitnessMalleatedP2sh);
+            }
+            return execute_witness_program(version, &program, true, witness, &machine);
+        }
+        redeem_script.run(&mut machine)?;
+
+        legacy_result(&machine, witness)
+    }
+
+    // Executes the commands against the machine, leaving the result on its stack
+    pub fn run(&self, machine: &mut Machine) -> Result<(), ScriptError> {
+        let serialized = self.to_bytes();
+        if serialized.len() > MAX_SCRIPT_SIZE {
+            return Err(ScriptError::ScriptSize(serialized.len()));
+        }
+        machine.script_code = serialized;
+
+        // Each script starts with an empty altstack, as in Bitcoin Core, so nothing is passed between them that way
+        machine.altstack.clear();
+        machine.op_count = 0;
+        for (index, cmd) in self.cmds.iter().enumerate() {
+            let executing = machine.executing();
+
+            match cmd {
+                Cmd::Push { opcode, data } => {
+                    if data.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                        return Err(ScriptError::PushSize(data.len()));
+                    }
+                    let minimal_data = machine.flags.contains(ScriptFlags::VERIFY_MINIMALDATA);
+                    if executing && minimal_data && !is_minimal_push(*opcode, data) {
+                        return Err(ScriptError::MinimalData);
+                    }
+                    if executing {
+                        machine.stack.push(data.clone());
+                    }
+                }
+                Cmd::Op(opcode) => {
+                    if *opcode > OP_16 {
+                        machine.add_ops(1)?;
+                    }
+                    if DISABLED_OPCODES.contains(opcode) {
+                        return Err(ScriptError::DisabledOpcode(*opcode));
+                    }
+
+                    // Flow control runs in untaken branches too, to keep track of where they end
+                    if executing || (OP_IF..=OP_ENDIF).contains(opcode) {
+                        let handler = HANDLERS[*opcode as usize].ok_or(ScriptError::BadOpcode(*opcode))?;
+                        handler(machine, *opcode)?;
+                    }
+                    // Signatures after an executed OP_CODESEPARATOR only commit to the script that follows it
+                    if executing && *opcode == OP_CODESEPARATOR {
+                        machine.script_code = Script::new(self.cmds[index + 1..].to_vec()).to_bytes();
+                    }
+                }
+            }
+
+            if machine.stack.len() + machine.altstack.len() > MAX_STACK_SIZE {
+                return Err(ScriptError::StackSize);
+            }
+        }
+
+        match machine.conditions.is_empty() {
+            true => Ok(()),
+            false => Err(ScriptError::UnbalancedConditional),
+        }
+    }
+}
+
+/*
+    BIP141 has a P2SH-wrapped witness program spent by a script_sig that is exactly the push of the program, in its
+    shortest encoding, so nothing else in the script_sig can be changed without invalidating the spend.
+ */
+fn is_single_push_of(script_sig: &Script, redeem_script: &[u8]) -> bool {
+    script_sig.to_bytes() == Script::new(vec![Cmd::push(redeem_script.to_vec())]).to_bytes()
+}
+
+// Under VERIFY_WITNESS a spend without a witness program cannot carry a witness, but failing outright comes first
+fn legacy_result(machine: &Machine, witness: &[Vec<u8>]) -> Result<bool, ScriptError> {
+    match machine.top_is_true() {
+        true if !witness.is_empty() && machine.flags.contains(ScriptFlags::VERIFY_WITNESS) => {
+            Err(ScriptError::WitnessUnexpected)
+        }
+        result => Ok(result),
+    }
+}
+
+/*
+    Version 0 programs are BIP141's two templates:
+
+    20 bytes  P2WPKH, the hash160 of a pubkey. The witness is <sig> <pubkey>, run against the P2PKH script of
+              the hash.
+    32 bytes  P2WSH, the sha256 of a witness script. The witness script is the last witness item, and runs on
+              the items before it.
+
+    Under VERIFY_TAPROOT a native version 1 program of 32 bytes is BIP341's P2TR, an x-only output key. Once an
+    annex, a last item starting with 0x50, is set aside, a single item left is a key path spend: a Schnorr
+    signature by the output key, with a hash type byte unless it is SIGHASH_DEFAULT. z has to be the digest for
+    that hash type, committing to the annex if there is one.
+
+    The machine that ran the script_pubkey, or the script_sig of a P2SH spend, hands on its flags, timelock
+    context and z or checker, and with a checker each signature is checked against its own digest instead of z.
+ */
+fn execute_witness_program(
+    version: u8,
+    program: &[u8],
+    is_p2sh: bool,
+    witness: &[Vec<u8>],
+    machine: &Machine,
+) -> Result<bool, ScriptError> {
+    let flags = machine.flags;
+    match (version, program.len()) {
+        (0, 20) => {
+            if witness.len() != 2 {
+                return Err(ScriptError::WitnessProgramMismatch);
+            }
+            let pubkey_hash = program.try_into().expect("The program is 20 bytes.");
+
+            execute_witness_script(&Script::p2pkh(pubkey_hash), witness.to_vec(), machine)
+        }
+        (0, 32) => {
+            let (witness_script, stack) = witness.split_last().ok_or(ScriptError::WitnessProgramWitnessEmpty)?;
+            if sha256(witness_script) != program {
+                return Err(ScriptError::WitnessProgramMismatch);
+            }
+            let witness_script = Script::from_bytes(witness_script).map_err(|_| ScriptError::InvalidWitnessScript)?;
+
+            execute_witness_script(&witness_script, stack.to_vec(), machine)
+        }
+        (0, length) => Err(ScriptError::WitnessProgramWrongLength(length)),
+        (1, 32) if !is_p2sh && flags.contains(ScriptFlags::VERIFY_TAPROOT) => {
+            let (stack, annex) = match witness {
+                [stack @ .., annex] if !stack.is_empty() && annex.first() == Some(&ANNEX_TAG) => {
+                    (stack, Some(annex.as_slice()))
+                }
+                _ => (witness, None),
+            };
+            match stack {
+                [] => Err(ScriptError::WitnessProgramWitnessEmpty),
+                [signature] => {
+                    let output_key: &[u8; 32] = program.try_into().expect("The program is 32 bytes.");
+                    let (signature, sighash_type) = parse_schnorr_signature(signature)?;
+                    let z = match &machine.checker {
+                        Some(checker) => {
+                            let tx = checker.tx;
+                            match tx.sig_hash_taproot(checker.input_index, checker.prevouts, sighash_type, annex) {
+                                Ok(z) => z,
+                                Err(_) => return Ok(false),
+                            }
+                        }
+                        None => machine.z,
+                    };
+
+                    Ok(S256Point::lift_x(U256::from_be_bytes(*output_key), false)
+                        .is_ok_and(|output_point| output_point.verify_schnorr(&z, signature)))
+                }
+                _ => Err(ScriptError::UnsupportedTaprootScriptPath),
+            }
+        }
+        (version, _) => Err(ScriptError::UnsupportedWitnessVersion(version)),
+    }
+}
+
+/*
+    The 64-byte signature and its hash type, SIGHASH_DEFAULT unless a 65th byte names another. A key whose x is not
+    on the curve can never be signed for, so verifying against it just fails.
+ */
+fn parse_schnorr_signature(signature: &[u8]) -> Result<(&[u8; 64], SighashType), ScriptError> {
+    match signature.len() {
+        64 => Ok((signature.try_into().expect("The signature is 64 bytes."), SighashType::DEFAULT)),
+        65 if matches!(signature[64], 0x01..=0x03 | 0x81..=0x83) => Ok((
+            signature[..64].try_into().expect("The signature is 64 bytes."),
+            SighashType::from_consensus(signature[64] as u32),
+        )),
+        65 => Err(ScriptError::SchnorrSignatureHashType(signature[64])),
+        size => Err(ScriptError::SchnorrSignatureSize(size)),
+    }
+}
+
+fn execute_witness_script(script: &Script, stack: Vec<Vec<u8>>, parent: &Machine) -> Result<bool, ScriptError> {
+    if let Some(item) = stack.iter().find(|item| item.len() > MAX_SCRIPT_ELEMENT_SIZE) {
+        return Err(ScriptError::PushSize(item.len()));
+    }
+
+    let mut machine = Machine::new(stack, parent.z);
+    machine.flags = parent.flags;
+    machine.context = parent.context;
+    machine.checker = parent.checker;
+    machine.witness_v0 = true;
+    script.run(&mut machine)?;
+    if machine.stack.len() != 1 {
+        return Err(ScriptError::CleanStack);
+    }
+
+    Ok(machine.top_is_true())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ecc::private_key::PrivateKey;
+    use crate::ecc::secp256k1::S256Point;
+    use crate::ecc::u256::U256;
+    use crate::encoding::hex;
+    use crate::hashing::hash160::hash160;
+    use crate::hashing::sha256::sha256;
+    use crate::script::interpreter::{
+        cast_to_bool, decode_num, encode_num, Machine, ScriptError, ScriptFlags, TxContext, MAX_OPS_PER_SCRIPT,
+    };
+    use crate::script::opcodes::{
+        OP_0NOTEQUAL, OP_1, OP_10, OP_16, OP_1ADD, OP_1NEGATE, OP_2, OP_2DROP, OP_2DUP, OP_3, OP_4, OP_5, OP_6,
+        OP_9, OP_ADD, OP_CHECKLOCKTIMEVERIFY, OP_CHECKMULTISIG, OP_CHECKMULTISIGVERIFY, OP_CHECKSEQUENCEVERIFY,
+        OP_CHECKSIG, OP_CHECKSIGVERIFY, OP_DEPTH, OP_DROP, OP_DUP, OP_ELSE, OP_ENDIF, OP_EQUAL, OP_EQUALVERIFY,
+        OP_FROMALTSTACK, OP_HASH160, OP_HASH256, OP_IF, OP_MUL, OP_NOP, OP_NOT, OP_NOTIF, OP_NUMEQUAL,
+        OP_NUMEQUALVERIFY, OP_OVER, OP_PICK, OP_PUSHDATA1, OP_RESERVED, OP_RETURN, OP_ROT, OP_SHA256, OP_SUB,
+        OP_SWAP, OP_TOALTSTACK, OP_VERIF, OP_VERIFY, OP_WITHIN,
+    };
+    use crate::script::taproot::{tweak_private_key, ANNEX_TAG};
+    use crate::script::{Cmd, Script};
+    use crate::tx::amount::Amount;
+    use crate::tx::fixtures::{PREV_SCRIPT_PUBKEY, TX_HEX};
+    use crate::tx::locktime::{RelativeLockTime, SEQUENCE_FINAL, SEQUENCE_LOCKTIME_DISABLE_FLAG};
+    use crate::tx::sighash::SighashType;
+    use crate::tx::transaction::Tx;
+
+    // The 2-of-2 multisig spent by the first input of the Programming Bitcoin chapter 8 transaction, mainnet
+    // 46df1a9484d0a81d03ce0ee543ab6e1a23ed06175c104a178268fad381216c2b, with its signatures and z
+    const MULTISIG_Z_HEX: &str = "e71bfa115715d6fd33796948126f40a8cdd39f187e4afb03896795189fe1423c";
+    const MULTISIG_PUBKEY_HEXES: [&str; 2] = [
+        "022626e955ea6ea6d98850c994f9107b036b1334f18ca8830bfff1295d21cfdb70",
+        "03b287eaf122eea69030a0e9feed096bed8045c8b98bec453e1ffac7fbdbd4bb71",
+    ];
+    const MULTISIG_SIGNATURE_HEXES: [&str; 2] = [
+        "3045022100dc92655fe37036f47756db8102e0d7d5e28b3beb83a8fef4f5dc0559bddfb94e02205a36d4e4e6c7fcd16658c50783e0\
+         0c341609977aed3ad00937bf4ee942a8993701",
+        "3045022100da6bee3c93766232079a01639d07fa869598749729ae323eab8eef53577d611b02207bef15429dcadce2121ea07f2331\
+         15c6f09034c0be68db99980b9a6c5e75402201",
+    ];
+    // OP_2 <pubkey 1> <pubkey 2> OP_2 OP_CHECKMULTISIG, and its hash160 the P2SH output pays to
+    const MULTISIG_REDEEM_SCRIPT_HEX: &str = "5221022626e955ea6ea6d98850c994f9107b036b1334f18ca8830bfff1295d21cfdb70\
+                                              2103b287eaf122eea69030a0e9feed096bed8045c8b98bec453e1ffac7fbdbd4bb7152ae";
+    const MULTISIG_REDEEM_SCRIPT_HASH_HEX: &str = "74d691da1574e6b3c192ecfb52cc8984ee7b6c56";
+
+    // The native P2WPKH example from BIP143, whose second input spends 6 BTC from the key hash below
+    const BIP143_P2WPKH_HEX: &str = "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000049\
+                                     4830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b19\
+                                     4ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d27965\
+                                     5c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b3\
+                                     7df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2\
+                                     f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01c\
+                                     c44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188\
+                                     368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000";
+    const BIP143_P2WPKH_KEY_HASH_HEX: &str = "1d0f172a0ecb48aee1be1f2687d2963ae33f71a1";
+
+    // The P2SH-P2WPKH example from BIP143, spending 10 BTC from the key hash below
+    const BIP143_P2SH_P2WPKH_HEX: &str = "01000000000101db6b1b20aa0fd7b23880be2ecbd4a98130974cf4748fb66092ac4d3ceb1a547701000000\
+                                          1716001479091972186c449eb1ded22b78e40d009bdf0089feffffff02b8b4eb0b000000001976a914a457b6\
+                                          84d7f0d539a46a45bbc043f35b59d0d96388ac0008af2f000000001976a914fd270b1ee6abcaea97fea7ad04\
+                                          02e8bd8ad6d77c88ac02473044022047ac8e878352d3ebbde1c94ce3a10d057c24175747116f8288e5d794d1\
+                                          2d482f0220217f36a485cae903c713331d877c1f64677e3622ad4010726870540656fe9dcb012103ad1d8e89\
+                                          212f0b92c74d23bb710c00662ad1470198ac48c43f7d6f93a2a2687392040000";
+    const BIP143_P2SH_P2WPKH_KEY_HASH_HEX: &str = "79091972186c449eb1ded22b78e40d009bdf0089";
+
+    fn ops(opcodes: &[u8]) -> Vec<Cmd> {
+        opcodes.iter().map(|opcode| Cmd::Op(*opcode)).collect()
+    }
+
+    fn evaluate(script_sig: Vec<Cmd>, script_pubkey: &[u8]) -> Result<bool, ScriptError> {
+        let cmds = script_sig.into_iter().chain(ops(script_pubkey)).collect();
+
+        Script::new(cmds).evaluate([0; 32], None, None, ScriptFlags::CONSENSUS)
+    }
+
+    // Runs the input's script_sig, with one byte of its signature or pubkey push optionally flipped, into the
+    // P2PKH script_pubkey it spends
+    fn evaluate_p2pkh_spend(flip: Option<(usize, usize)>) -> Result<bool, ScriptError> {
+        let tx = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+        let script_pubkey = hex::decode(PREV_SCRIPT_PUBKEY).unwrap();
+        let z = tx.sig_hash(0, &script_pubkey, SighashType::ALL).unwrap();
+
+        let mut script_sig = Script::from_bytes(&tx.inputs[0].script_sig).unwrap();
+        if let Some((cmd, byte)) = flip {
+            if let Cmd::Push { data, .. } = &mut script_sig.cmds[cmd] {
+                data[byte] ^= 0x01;
+            }
+        }
+        let cmds = script_sig.cmds.into_iter().chain(Script::from_bytes(&script_pubkey).unwrap().cmds).collect();
+
+        Script::new(cmds).evaluate(z, None, None, ScriptFlags::CONSENSUS)
+    }
+
+    /*
+        Runs <dummy> <signatures> m <pubkeys> n OP_CHECKMULTISIG against the chapter 8 z, picking signatures and
+        pubkeys by index into the fixtures. Index 2 is a pubkey that signed neither, the generator's.
+     */
+    fn evaluate_multisig(
+        dummy: Vec<u8>,
+        signatures: &[usize],
+        m: u8,
+        pubkeys: &[usize],
+        null_dummy: bool,
+    ) -> Result<bool, ScriptError> {
+        let generator = hex::encode(&S256Point::generator().sec(true));
+        let pubkey_hexes = [MULTISIG_PUBKEY_HEXES[0], MULTISIG_PUBKEY_HEXES[1], &generator];
+
+        let mut cmds = vec![Cmd::push(dummy)];
+        cmds.extend(signatures.iter().map(|index| Cmd::push(hex::decode(MULTISIG_SIGNATURE_HEXES[*index]).unwrap())));
+        cmds.push(Cmd::push(encode_num(m as i64)));
+        cmds.extend(pubkeys.iter().map(|index| Cmd::push(hex::decode(pubkey_hexes[*index]).unwrap())));
+        cmds.extend([Cmd::push(encode_num(pubkeys.len() as i64)), Cmd::Op(OP_CHECKMULTISIG)]);
+
+        let mut machine = Machine::new(vec![], hex::decode(MULTISIG_Z_HEX).unwrap().try_into().unwrap());
+        if null_dummy {
+            machine.flags = ScriptFlags::VERIFY_NULLDUMMY;
+        }
+        Script::new(cmds).run(&mut machine)?;
+
+        Ok(machine.stack.last().is_some_and(|top| cast_to_bool(top)))
+    }
+
+    fn key_hash(hash_hex: &str) -> [u8; 20] {
+        hex::decode(hash_hex).unwrap().try_into().unwrap()
+    }
+
+    // An OP_2 <pubkey 1> <pubkey 2> OP_2 OP_CHECKMULTISIG witness script, with a witness signing z with both keys
+    fn two_of_two_witness(z: &[u8; 32]) -> (Script, Vec<Vec<u8>>) {
+        let private_keys = [7, 11].map(|secret| PrivateKey::new(U256::from_u64(secret)).unwrap());
+        let mut cmds = vec![Cmd::Op(OP_2)];
+        cmds.extend(private_keys.iter().map(|key| Cmd::push(key.point().sec(true))));
+        cmds.extend(ops(&[OP_2, OP_CHECKMULTISIG]));
+        let witness_script = Script::new(cmds);
+
+        let mut witness = vec![vec![]];
+        witness.extend(private_keys.iter().map(|key| [key.sign(z).der(), vec![0x01]].concat()));
+        witness.push(witness_script.to_bytes());
+
+        (witness_script, witness)
+    }
+
+    // Runs <operand> <opcode> OP_DROP OP_1 in a transaction with the given fields, or in none
+    fn evaluate_timelock(operand: i64, opcode: u8, context: Option<(u32, u32, u32)>) -> Result<bool, ScriptError> {
+        let context = context.map(|(locktime, sequence_of_input, version)| TxContext {
+            locktime,
+            sequence_of_input,
+            version,
+        });
+        let cmds = [vec![Cmd::push(encode_num(operand))], ops(&[opcode, OP_DROP, OP_1])].concat();
+
+        Script::new(cmds).evaluate([0; 32], None, context.as_ref(), ScriptFlags::CONSENSUS)
+    }
+
+    // Runs <script_sig pushes> <serialized redeem script> OP_HASH160 <hash> OP_EQUAL
+    fn evaluate_p2sh(
+        z: [u8; 32],
+        pushes: Vec<Cmd>,
+        redeem_script: &Script,
+        hash: &[u8; 20],
+    ) -> Result<bool, ScriptError> {
+        let mut cmds = pushes;
+        cmds.push(Cmd::push(redeem_script.to_bytes()));
+        cmds.extend(Script::p2sh(hash).cmds);
+
+        Script::new(cmds).evaluate(z, None, None, ScriptFlags::CONSENSUS)
+    }
+
+    #[test]
+    fn numbers_encode_minimally_with_a_sign_bit() {
+        let vectors: [(i64, &[u8]); 9] = [
+            (0, &[]),
+            (1, &[0x01]),
+            (-1, &[0x81]),
+            (127, &[0x7f]),
+            (128, &[0x80, 0x00]),
+            (-128, &[0x80, 0x80]),
+            (255, &[0xff, 0x00]),
+            (256, &[0x00, 0x01]),
+            (-2147483647, &[0xff, 0xff, 0xff, 0xff]),
+        ];
+
+        for (number, bytes) in vectors {
+            assert_eq!(encode_num(number), bytes, "{}", number);
+            assert_eq!(decode_num(bytes, 4), Ok(number), "{:02x?}", bytes);
+        }
+        assert_eq!(decode_num(&[0x01, 0x00], 4), Ok(1));
+        assert_eq!(decode_num(&[0x00, 0x80], 4), Ok(0));
+        assert_eq!(decode_num(&[0x01, 0x02, 0x03, 0x04, 0x05], 4), Err(ScriptError::NumberOverflow(5)));
+        assert!(!cast_to_bool(&[]) && !cast_to_bool(&[0x00, 0x00]) && !cast_to_bool(&[0x00, 0x80]));
+        assert!(cast_to_bool(&[0x80, 0x00]) && cast_to_bool(&[0x00, 0x01]));
+    }
+
+    #[test]
+    fn book_addition_puzzle() {
+        // Programming Bitcoin chapter 6: OP_4 unlocks OP_5 OP_ADD OP_9 OP_EQUAL
+        let script_pubkey = [OP_5, OP_ADD, OP_9, OP_EQUAL];
+
+        assert_eq!(evaluate(ops(&[OP_4]), &script_pubkey), Ok(true));
+        assert_eq!(evaluate(ops(&[OP_5]), &script_pubkey), Ok(false));
+    }
+
+    #[test]
+    fn book_multiplication_puzzle_fails_on_the_disabled_op_mul() {
+        // The book's OP_DUP OP_DUP OP_MUL OP_ADD OP_6 OP_EQUAL is solved by 2 but OP_MUL has been disabled since 2010
+        let result = evaluate(ops(&[OP_2]), &[OP_DUP, OP_DUP, OP_MUL, OP_ADD, OP_6, OP_EQUAL]);
+
+        assert_eq!(result, Err(ScriptError::DisabledOpcode(OP_MUL)));
+    }
+
+    #[test]
+    fn x_plus_x_equals_2x() {
+        // Given x and y, checks y = x + x
+        let script_pubkey = [OP_OVER, OP_2, OP_PICK, OP_ADD, OP_NUMEQUAL];
+
+        assert_eq!(evaluate(ops(&[OP_5, OP_10]), &script_pubkey), Ok(true));
+        assert_eq!(evaluate(vec![Cmd::push(vec![0xe8, 0x03]), Cmd::push(vec![0xd0, 0x07])], &script_pubkey), Ok(true));
+        assert_eq!(evaluate(ops(&[OP_5, OP_9]), &script_pubkey), Ok(false));
+    }
+
+    #[test]
+    fn collision_puzzle_is_solved_by_two_encodings_of_one_number() {
+        /*
+            The book's SHA-1 collision bounty, OP_2DUP OP_EQUAL OP_NOT OP_VERIFY OP_SHA1 OP_SWAP OP_SHA1 OP_EQUAL,
+            asks for two different values with the same hash. Comparing the values as numbers instead, two
+            different encodings of the same number are the collision.
+         */
+        let script_pubkey = [OP_2DUP, OP_EQUAL, OP_NOT, OP_VERIFY, OP_NUMEQUAL];
+
+        assert_eq!(evaluate(vec![Cmd::push(vec![0x01]), Cmd::push(vec![0x01, 0x00])], &script_pubkey), Ok(true));
+        assert_eq!(evaluate(vec![Cmd::push(vec![0x01]), Cmd::push(vec![0x02])], &script_pubkey), Ok(false));
+        assert_eq!(
+            evaluate(vec![Cmd::push(vec![0x01]), Cmd::push(vec![0x01])], &script_pubkey),
+            Err(ScriptError::Verify(OP_VERIFY))
+        );
+    }
+
+    #[test]
+    fn non_minimal_encodings_fail_only_under_minimaldata() {
+        let minimal_data = ScriptFlags::CONSENSUS | ScriptFlags::VERIFY_MINIMALDATA;
+        let evaluate_with = |script_sig: &[Cmd], script_pubkey: &[u8], flags| {
+            Script::new([script_sig.to_vec(), ops(script_pubkey)].concat()).evaluate([0; 32], None, None, flags)
+        };
+        // Two bytes pushed with OP_PUSHDATA1, 5 pushed as data rather than with OP_5, and 1 with a needless zero byte
+        let pushdata1 = [Cmd::Push { opcode: OP_PUSHDATA1, data: vec![0x01, 0x02] }];
+        let five = [Cmd::push(vec![0x05])];
+        let padded_one = [Cmd::push(vec![0x01, 0x00])];
+
+        for (script_sig, script_pubkey) in [
+            (&pushdata1[..], &[OP_DROP, OP_1][..]),
+            (&five, &[OP_5, OP_EQUAL]),
+            (&padded_one, &[OP_1ADD, OP_2, OP_NUMEQUAL]),
+        ] {
+            assert_eq!(evaluate_with(script_sig, script_pubkey, ScriptFlags::CONSENSUS), Ok(true));
+            assert_eq!(evaluate_with(script_sig, script_pubkey, minimal_data), Err(ScriptError::MinimalData));
+        }
+
+        let minimal = [Cmd::push(vec![]), Cmd::push(vec![0; 76]), Cmd::push(vec![0x11]), Cmd::push(vec![0x80, 0x00])];
+        assert_eq!(evaluate_with(&minimal, &[OP_1ADD, OP_DROP, OP_2DROP, OP_NOT], minimal_data), Ok(true));
+        let untaken = [vec![Cmd::push(vec![]), Cmd::Op(OP_IF)], pushdata1.to_vec(), ops(&[OP_ENDIF])].concat();
+        assert_eq!(evaluate_with(&untaken, &[OP_1], minimal_data), Ok(true));
+    }
+
+    #[test]
+    fn consensus_flags_are_bitcoin_cores() {
+        let flags = ScriptFlags::VERIFY_P2SH | ScriptFlags::VERIFY_DERSIG;
+
+        assert_eq!(ScriptFlags::CONSENSUS.bits(), 0x020e15);
+        assert!(ScriptFlags::CONSENSUS.contains(flags) && ScriptFlags::CONSENSUS.contains(ScriptFlags::NONE));
+        assert!(!ScriptFlags::CONSENSUS.contains(ScriptFlags::VERIFY_MINIMALDATA));
+        assert_eq!(ScriptFlags::from_bits(flags.bits()), flags);
+        assert_eq!(ScriptFlags::default(), ScriptFlags::NONE);
+    }
+
+    #[test]
+    fn conditionals_pick_a_branch() {
+        // 2 when the first value is true, otherwise -1 unless the second is false too
+        let mut script_pubkey = vec![OP_IF, OP_2, OP_ELSE, OP_NOTIF, OP_RETURN, OP_ENDIF, OP_1NEGATE, OP_ENDIF];
+        script_pubkey.extend([OP_DEPTH, OP_1, OP_NUMEQUALVERIFY]);
+        let returns = |script_sig: Vec<Cmd>, result: u8| {
+            let script_pubkey = [script_pubkey.as_slice(), &[result, OP_NUMEQUAL]].concat();
+            evaluate(script_sig, &script_pubkey)
+        };
+
+        assert_eq!(returns(ops(&[OP_1]), OP_2), Ok(true));
+        assert_eq!(returns(vec![Cmd::push(vec![0x01]), Cmd::push(vec![])], OP_1NEGATE), Ok(true));
+        assert_eq!(returns(vec![Cmd::push(vec![]), Cmd::push(vec![])], OP_1NEGATE), Err(ScriptError::OpReturn));
+    }
+
+    #[test]
+    fn arithmetic_and_comparison() {
+        assert_eq!(evaluate(ops(&[OP_5, OP_2, OP_SUB, OP_1NEGATE, OP_ADD]), &[OP_2, OP_NUMEQUAL]), Ok(true));
+        assert_eq!(evaluate(ops(&[OP_4, OP_2, OP_5, OP_WITHIN]), &[]), Ok(true));
+        assert_eq!(evaluate(ops(&[OP_5, OP_2, OP_5, OP_WITHIN]), &[]), Ok(false));
+        assert_eq!(evaluate(ops(&[OP_16, OP_0NOTEQUAL]), &[OP_1, OP_NUMEQUALVERIFY, OP_1]), Ok(true));
+        assert_eq!(evaluate(ops(&[OP_1, OP_2, OP_ROT]), &[]), Err(ScriptError::InvalidStackOperation(OP_ROT)));
+        assert_eq!(
+            evaluate(vec![Cmd::push(vec![0xff; 5])], &[OP_1, OP_ADD]),
+            Err(ScriptError::NumberOverflow(5))
+        );
+    }
+
+    #[test]
+    fn empty_or_zero_result_is_false() {
+        assert_eq!(evaluate(vec![], &[]), Ok(false));
+        assert_eq!(evaluate(vec![Cmd::push(vec![0x00, 0x80])], &[]), Ok(false));
+        assert_eq!(evaluate(ops(&[OP_1]), &[OP_DROP]), Ok(false));
+        assert_eq!(Script::new(ops(&[OP_1])).evaluate([0; 32], Some(&[]), None, ScriptFlags::CONSENSUS), Ok(true));
+    }
+
+    #[test]
+    fn malformed_scripts_error_instead_of_panicking() {
+        let cases: [(&[u8], ScriptError); 8] = [
+            (&[OP_ADD], ScriptError::InvalidStackOperation(OP_ADD)),
+            (&[OP_SWAP], ScriptError::InvalidStackOperation(OP_SWAP)),
+            (&[OP_1NEGATE, OP_PICK], ScriptError::InvalidStackOperation(OP_PICK)),
+            (&[OP_1, OP_IF], ScriptError::UnbalancedConditional),
+            (&[OP_ENDIF], ScriptError::UnbalancedConditional),
+            (&[OP_ELSE], ScriptError::UnbalancedConditional),
+            (&[OP_RESERVED], ScriptError::BadOpcode(OP_RESERVED)),
+            (&[0xff], ScriptError::BadOpcode(0xff)),
+        ];
+
+        for (script, expected) in cases {
+            assert_eq!(evaluate(vec![], script), Err(expected), "{:02x?}", script);
+        }
+        // A reserved opcode is only bad when executed, but disabled opcodes and OP_VERIF fail in any branch
+        let untaken = |opcode| evaluate(vec![], &[OP_DEPTH, OP_IF, opcode, OP_ENDIF, OP_1]);
+        assert_eq!(untaken(OP_RESERVED), Ok(true));
+        assert_eq!(untaken(OP_MUL), Err(ScriptError::DisabledOpcode(OP_MUL)));
+        assert_eq!(untaken(OP_VERIF), Err(ScriptError::BadOpcode(OP_VERIF)));
+    }
+
+    #[test]
+    fn limits_are_enforced() {
+        let too_many_ops = vec![OP_NOP; MAX_OPS_PER_SCRIPT + 1];
+        let mut deep_stack = vec![OP_1; 999];
+        deep_stack.extend([OP_DUP, OP_DUP]);
+
+        assert_eq!(evaluate(ops(&[OP_1]), &too_many_ops[1..]), Ok(true));
+        assert_eq!(evaluate(ops(&[OP_1]), &too_many_ops), Err(ScriptError::OpCount));
+        assert_eq!(evaluate(vec![], &deep_stack[..1000]), Ok(true));
+        assert_eq!(evaluate(vec![], &deep_stack), Err(ScriptError::StackSize));
+        assert_eq!(evaluate(vec![Cmd::push(vec![0x01; 521])], &[]), Err(ScriptError::PushSize(521)));
+    }
+
+    #[test]
+    fn mainnet_p2pkh_spend_verifies() {
+        assert_eq!(evaluate_p2pkh_spend(None), Ok(true));
+    }
+
+    #[test]
+    fn flipped_signature_byte_fails_the_checksig() {
+        // Byte 10 is inside r, so the signature is still well-formed DER but no longer valid
+        assert_eq!(evaluate_p2pkh_spend(Some((0, 10))), Ok(false));
+        // Byte 0 is the DER sequence tag
+        assert_eq!(evaluate_p2pkh_spend(Some((0, 0))), Err(ScriptError::SignatureDer));
+    }
+
+    #[test]
+    fn flipped_pubkey_byte_fails_the_equalverify() {
+        assert_eq!(evaluate_p2pkh_spend(Some((1, 20))), Err(ScriptError::Verify(OP_EQUALVERIFY)));
+    }
+
+    #[test]
+    fn checksigverify_fails_the_script_on_a_bad_signature() {
+        let tx = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+        let script_sig = Script::from_bytes(&tx.inputs[0].script_sig).unwrap();
+
+        let script = Script::new([script_sig.cmds, ops(&[OP_CHECKSIGVERIFY, OP_1])].concat());
+        let verify = script.evaluate([0; 32], None, None, ScriptFlags::CONSENSUS);
+        let empty = evaluate(vec![Cmd::push(vec![]), Cmd::push(vec![0x02; 33])], &[OP_CHECKSIG, OP_NOT]);
+
+        assert_eq!(verify, Err(ScriptError::Verify(OP_CHECKSIGVERIFY)));
+        assert_eq!(empty, Ok(true));
+    }
+
+    #[test]
+    fn hash_opcodes_replace_the_top_element() {
+        // The well-known digests of the empty string
+        let cases = [
+            (OP_HASH160, "b472a266d0bd89c13706a4132ccfb16f7c3b9fcb"),
+            (OP_SHA256, "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+            (OP_HASH256, "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456"),
+        ];
+
+        for (opcode, digest) in cases {
+            // The digest goes first, as a script ending in OP_HASH160 <20 bytes> OP_EQUAL would be taken for P2SH
+            let script = vec![Cmd::push(hex::decode(digest).unwrap()), Cmd::push(vec![]), Cmd::Op(opcode)];
+
+            assert_eq!(evaluate(script, &[OP_EQUAL]), Ok(true), "{:#04x}", opcode);
+        }
+        assert_eq!(evaluate(vec![], &[OP_HASH160]), Err(ScriptError::InvalidStackOperation(OP_HASH160)));
+        assert_eq!(evaluate(ops(&[OP_1, OP_DUP, OP_2]), &[OP_EQUALVERIFY]), Err(ScriptError::Verify(OP_EQUALVERIFY)));
+    }
+
+    #[test]
+    fn mainnet_two_of_two_multisig_verifies() {
+        assert_eq!(evaluate_multisig(vec![], &[0, 1], 2, &[0, 1], true), Ok(true));
+        assert_eq!(evaluate_multisig(vec![], &[1, 0], 2, &[0, 1], true), Ok(false));
+    }
+
+    #[test]
+    fn one_of_two_matches_either_key() {
+        assert_eq!(evaluate_multisig(vec![], &[0], 1, &[0, 1], true), Ok(true));
+        assert_eq!(evaluate_multisig(vec![], &[1], 1, &[0, 1], true), Ok(true));
+        assert_eq!(evaluate_multisig(vec![], &[1], 1, &[0, 2], true), Ok(false));
+    }
+
+    #[test]
+    fn two_of_three_skips_the_key_that_did_not_sign() {
+        assert_eq!(evaluate_multisig(vec![], &[0, 1], 2, &[0, 2, 1], true), Ok(true));
+        assert_eq!(evaluate_multisig(vec![], &[0, 1], 2, &[2, 0, 1], true), Ok(true));
+        // Once the first signature has used up the last key, the second has nothing left to match
+        assert_eq!(evaluate_multisig(vec![], &[0, 1], 2, &[1, 2, 0], true), Ok(false));
+        assert_eq!(evaluate_multisig(vec![], &[], 0, &[0, 1, 2], true), Ok(true));
+    }
+
+    #[test]
+    fn non_empty_dummy_fails_only_under_null_dummy() {
+        assert_eq!(evaluate_multisig(vec![0x01], &[0, 1], 2, &[0, 1], false), Ok(true));
+        assert_eq!(evaluate_multisig(vec![0x01], &[0, 1], 2, &[0, 1], true), Err(ScriptError::NullDummy));
+    }
+
+    #[test]
+    fn multisig_counts_are_checked() {
+        let too_many_keys = vec![Cmd::push(vec![]), Cmd::Op(OP_0NOTEQUAL), Cmd::push(encode_num(21))];
+        let missing_dummy = vec![Cmd::Op(OP_1), Cmd::push(vec![0x02; 33]), Cmd::Op(OP_1)];
+
+        assert_eq!(evaluate_multisig(vec![], &[0, 1], 3, &[0, 1], true), Err(ScriptError::SigCount(3)));
+        assert_eq!(evaluate(too_many_keys, &[OP_CHECKMULTISIG]), Err(ScriptError::PubkeyCount(21)));
+        assert_eq!(
+            evaluate(missing_dummy.clone(), &[OP_CHECKMULTISIG]),
+            Err(ScriptError::InvalidStackOperation(OP_CHECKMULTISIG))
+        );
+        assert_eq!(
+            evaluate([vec![Cmd::push(vec![]), Cmd::push(vec![])], missing_dummy].concat(), &[OP_CHECKMULTISIGVERIFY]),
+            Err(ScriptError::Verify(OP_CHECKMULTISIGVERIFY))
+        );
+    }
+
+    #[test]
+    fn mainnet_p2sh_multisig_spend_verifies() {
+        let z = hex::decode(MULTISIG_Z_HEX).unwrap().try_into().unwrap();
+        let redeem_script = Script::from_bytes(&hex::decode(MULTISIG_REDEEM_SCRIPT_HEX).unwrap()).unwrap();
+        let hash = hex::decode(MULTISIG_REDEEM_SCRIPT_HASH_HEX).unwrap().try_into().unwrap();
+        let mut pushes = vec![Cmd::push(vec![])];
+        pushes.extend(MULTISIG_SIGNATURE_HEXES.map(|signature| Cmd::push(hex::decode(signature).unwrap())));
+
+        assert_eq!(hash160(&redeem_script.to_bytes()), hash);
+        assert_eq!(evaluate_p2sh(z, pushes.clone(), &redeem_script, &hash), Ok(true));
+        pushes.swap(1, 2);
+        assert_eq!(evaluate_p2sh(z, pushes, &redeem_script, &hash), Ok(false));
+    }
+
+    #[test]
+    fn two_of_three_p2sh_spend_verifies() {
+        let private_keys: Vec<PrivateKey> =
+            (1..=3).map(|secret| PrivateKey::new(U256::from_u64(secret * 1000)).unwrap()).collect();
+        let mut redeem_cmds = vec![Cmd::Op(OP_2)];
+        redeem_cmds.extend(private_keys.iter().map(|key| Cmd::push(key.point().sec(true))));
+        redeem_cmds.extend(ops(&[OP_3, OP_CHECKMULTISIG]));
+        let redeem_script = Script::new(redeem_cmds);
+        let hash = hash160(&redeem_script.to_bytes());
+        let z = sha256(b"two of three");
+        let signature = |key: &PrivateKey| Cmd::push([key.sign(&z).der(), vec![0x01]].concat());
+
+        let first_and_third = vec![Cmd::push(vec![]), signature(&private_keys[0]), signature(&private_keys[2])];
+        let out_of_order = vec![Cmd::push(vec![]), signature(&private_keys[2]), signature(&private_keys[0])];
+
+        assert_eq!(evaluate_p2sh(z, first_and_third, &redeem_script, &hash), Ok(true));
+        assert_eq!(evaluate_p2sh(z, out_of_order, &redeem_script, &hash), Ok(false));
+    }
+
+    #[test]
+    fn redeem_script_only_runs_when_its_hash_matches() {
+        let redeem_script = Script::new(ops(&[OP_RETURN]));
+        let hash = hash160(&redeem_script.to_bytes());
+
+        assert_eq!(evaluate_p2sh([0; 32], vec![], &redeem_script, &[0; 20]), Ok(false));
+        assert_eq!(evaluate_p2sh([0; 32], vec![], &redeem_script, &hash), Err(ScriptError::OpReturn));
+    }
+
+    #[test]
+    fn p2sh_script_sig_must_be_push_only_and_end_in_a_script() {
+        let redeem_script = Script::new(ops(&[OP_1]));
+        let hash = hash160(&redeem_script.to_bytes());
+        // A push of two bytes with only one following it
+        let truncated_push = vec![0x02, 0x05];
+        let p2sh = Script::p2sh(&hash160(&truncated_push));
+        let unparsable = [vec![Cmd::push(truncated_push)], p2sh.cmds].concat();
+
+        assert_eq!(evaluate_p2sh([0; 32], vec![Cmd::Op(OP_1)], &redeem_script, &hash), Ok(true));
+        assert_eq!(evaluate_p2sh([0; 32], ops(&[OP_1, OP_DUP]), &redeem_script, &hash), Err(ScriptError::SigPushOnly));
+        assert_eq!(
+            Script::new(unparsable).evaluate([0; 32], None, None, ScriptFlags::CONSENSUS),
+            Err(ScriptError::InvalidRedeemScript)
+        );
+    }
+
+    #[test]
+    fn spends_run_the_script_sig_and_script_pubkey_separately() {
+        let verify = |script_sig: Vec<Cmd>, script_pubkey: Vec<Cmd>| {
+            let mut machine = Machine::new(vec![], [0; 32]);
+            machine.flags = ScriptFlags::CONSENSUS;
+            machine.verify_spend(&Script::new(script_sig), &Script::new(script_pubkey), &[])
+        };
+        let joined = |script_sig: &[Cmd], script_pubkey: &[Cmd]| {
+            Script::new([script_sig, script_pubkey].concat()).evaluate([0; 32], None, None, ScriptFlags::CONSENSUS)
+        };
+
+        // A hash puzzle that only ends like P2SH, which joining takes for a P2SH spend with OP_DUP in its script_sig
+        let preimage = vec![0x42];
+        let puzzle = [ops(&[OP_DUP, OP_DROP]), Script::p2sh(&hash160(&preimage)).cmds].concat();
+        assert_eq!(verify(vec![Cmd::push(preimage.clone())], puzzle.clone()), Ok(true));
+        assert_eq!(joined(&[Cmd::push(preimage)], &puzzle), Err(ScriptError::SigPushOnly));
+
+        // An OP_IF opened in the script_sig cannot be closed by the script_pubkey
+        assert_eq!(verify(ops(&[OP_1, OP_IF]), ops(&[OP_ENDIF, OP_1])), Err(ScriptError::UnbalancedConditional));
+        assert_eq!(joined(&ops(&[OP_1, OP_IF]), &ops(&[OP_ENDIF, OP_1])), Ok(true));
+
+        // Nor can an item the script_sig moves to the altstack be moved back by the script_pubkey
+        let to_altstack = ops(&[OP_1, OP_TOALTSTACK]);
+        let from_altstack = ops(&[OP_FROMALTSTACK]);
+        assert_eq!(verify(to_altstack.clone(), from_altstack.clone()), Err(ScriptError::InvalidAltstackOperation));
+        assert_eq!(joined(&to_altstack, &from_altstack), Ok(true));
+
+        // Each script has its own opcode limit
+        let nops = ops(&[OP_NOP; 150]);
+        let script_pubkey = [nops.clone(), ops(&[OP_1])].concat();
+        assert_eq!(verify(nops.clone(), script_pubkey.clone()), Ok(true));
+        assert_eq!(joined(&nops, &script_pubkey), Err(ScriptError::OpCount));
+    }
+
+    #[test]
+    fn bip143_p2wpkh_spend_verifies() {
+        let tx = Tx::parse_exact(&hex::decode(BIP143_P2WPKH_HEX).unwrap(), false).unwrap();
+        let key_hash = key_hash(BIP143_P2WPKH_KEY_HASH_HEX);
+        let script_code = Script::p2pkh(&key_hash).to_bytes();
+        let z = tx.sig_hash_bip143(1, &script_code, Amount::from_sat(600_000_000), SighashType::ALL).unwrap();
+        let mut witness = tx.inputs[1].witness.clone();
+
+        assert_eq!(Script::p2wpkh(&key_hash).evaluate(z, Some(&witness), None, ScriptFlags::CONSENSUS), Ok(true));
+        witness[0][10] ^= 0x01;
+        assert_eq!(Script::p2wpkh(&key_hash).evaluate(z, Some(&witness), None, ScriptFlags::CONSENSUS), Ok(false));
+        assert_eq!(
+            Script::p2wpkh(&key_hash).evaluate(z, Some(&witness[..1]), None, ScriptFlags::CONSENSUS),
+            Err(ScriptError::WitnessProgramMismatch)
+        );
+    }
+
+    #[test]
+    fn bip143_p2sh_p2wpkh_spend_verifies() {
+        let tx = Tx::parse_exact(&hex::decode(BIP143_P2SH_P2WPKH_HEX).unwrap(), false).unwrap();
+        let key_hash = key_hash(BIP143_P2SH_P2WPKH_KEY_HASH_HEX);
+        let script_code = Script::p2pkh(&key_hash).to_bytes();
+        let z = tx.sig_hash_bip143(0, &script_code, Amount::from_sat(1_000_000_000), SighashType::ALL).unwrap();
+        let redeem_script = Script::p2wpkh(&key_hash);
+        let script_sig = Script::from_bytes(&tx.inputs[0].script_sig).unwrap();
+        let script_pubkey = Script::p2sh(&hash160(&redeem_script.to_bytes()));
+
+        let spend = Script::new([script_sig.cmds.clone(), script_pubkey.cmds.clone()].concat());
+        let extra_push = Script::new([vec![Cmd::push(vec![])], script_sig.cmds, script_pubkey.cmds.clone()].concat());
+        // The same 22 bytes behind OP_PUSHDATA1, which pushes the same item but is a different script_sig
+        let non_minimal_sig = Script::new(vec![Cmd::Push { opcode: OP_PUSHDATA1, data: redeem_script.to_bytes() }]);
+        let non_minimal = &non_minimal_sig + &script_pubkey;
+        let verify_spend = |script_sig: &Script| {
+            let mut machine = Machine::new(vec![], z);
+            machine.flags = ScriptFlags::CONSENSUS;
+            machine.verify_spend(script_sig, &script_pubkey, &tx.inputs[0].witness)
+        };
+
+        assert_eq!(spend.evaluate(z, Some(&tx.inputs[0].witness), None, ScriptFlags::CONSENSUS), Ok(true));
+        assert_eq!(
+            extra_push.evaluate(z, Some(&tx.inputs[0].witness), None, ScriptFlags::CONSENSUS),
+            Err(ScriptError::WitnessMalleatedP2sh)
+        );
+        assert_eq!(
+            non_minimal.evaluate(z, Some(&tx.inputs[0].witness), None, ScriptFlags::CONSENSUS),
+            Err(ScriptError::WitnessMalleatedP2sh)
+        );
+        assert_eq!(verify_spend(&Script::from_bytes(&tx.inputs[0].script_sig).unwrap()), Ok(true));
+        assert_eq!(verify_spend(&non_minimal_sig), Err(ScriptError::WitnessMalleatedP2sh));
+    }
+
+    #[test]
+    fn two_of_two_p2wsh_spend_verifies() {
+        let z = sha256(b"two of two");
+        let (witness_script, witness) = two_of_two_witness(&z);
+        let script_pubkey = Script::p2wsh(&sha256(&witness_script.to_bytes()));
+        let p2sh = Script::p2sh(&hash160(&script_pubkey.to_bytes()));
+        let wrapped = Script::new([vec![Cmd::push(script_pubkey.to_bytes())], p2sh.cmds].concat());
+
+        assert_eq!(script_pubkey.evaluate(z, Some(&witness), None, ScriptFlags::CONSENSUS), Ok(true));
+        assert_eq!(wrapped.evaluate(z, Some(&witness), None, ScriptFlags::CONSENSUS), Ok(true));
+        assert_eq!(script_pubkey.evaluate(sha256(b"other"), Some(&witness), None, ScriptFlags::CONSENSUS), Ok(false));
+        assert_eq!(
+            script_pubkey.evaluate(z, Some(&[]), None, ScriptFlags::CONSENSUS),
+            Err(ScriptError::WitnessProgramWitnessEmpty)
+        );
+        assert_eq!(
+            script_pubkey.evaluate(z, Some(&[vec![vec![0x01]], witness].concat()), None, ScriptFlags::CONSENSUS),
+            Err(ScriptError::CleanStack)
+        );
+    }
+
+    #[test]
+    fn p2wsh_witness_script_must_hash_to_the_program() {
+        let z = sha256(b"two of two");
+        let (witness_script, mut witness) = two_of_two_witness(&z);
+        let script_pubkey = Script::p2wsh(&sha256(&witness_script.to_bytes()));
+
+        // Swapping the keys gives a script that would still check both signatures, but not the committed one
+        let mut swapped = witness_script.clone();
+        swapped.cmds.swap(1, 2);
+        *witness.last_mut().unwrap() = swapped.to_bytes();
+
+        assert_eq!(
+            script_pubkey.evaluate(z, Some(&witness), None, ScriptFlags::CONSENSUS),
+            Err(ScriptError::WitnessProgramMismatch)
+        );
+    }
+
+    #[test]
+    fn witness_rules_for_other_programs() {
+        let witness = [vec![0x01]];
+        let wrong_length = Script::new(vec![Cmd::push(vec![]), Cmd::push(vec![0; 25])]);
+        let version_2 = Script::new(vec![Cmd::Op(OP_2), Cmd::push(vec![0; 32])]);
+        let legacy = Script::new(ops(&[OP_1]));
+
+        assert_eq!(
+            wrong_length.evaluate([0; 32], Some(&witness), None, ScriptFlags::CONSENSUS),
+            Err(ScriptError::WitnessProgramWrongLength(25))
+        );
+        assert_eq!(
+            version_2.evaluate([0; 32], Some(&witness), None, ScriptFlags::CONSENSUS),
+            Err(ScriptError::UnsupportedWitnessVersion(2))
+        );
+        assert_eq!(
+            legacy.evaluate([0; 32], Some(&witness), None, ScriptFlags::CONSENSUS),
+            Err(ScriptError::WitnessUnexpected)
+        );
+    }
+
+    #[test]
+    fn taproot_key_path_spends_verify() {
+        let internal_key = PrivateKey::new(U256::from_u64(1111)).unwrap();
+        let output_key = tweak_private_key(&internal_key, None).unwrap();
+        let script_pubkey = Script::p2tr(&output_key.point().x().to_be_bytes());
+        let z = [0x17; 32];
+        let signature = output_key.sign_schnorr(&z, &[0; 32]);
+        let with_hash_type = [&signature[..], &[0x83]].concat();
+        let evaluate = |witness: &[Vec<u8>], flags| script_pubkey.evaluate(z, Some(witness), None, flags);
+
+        assert_eq!(evaluate(&[signature.to_vec()], ScriptFlags::CONSENSUS), Ok(true));
+        assert_eq!(evaluate(&[with_hash_type], ScriptFlags::CONSENSUS), Ok(true));
+        assert_eq!(evaluate(&[signature.to_vec(), vec![ANNEX_TAG, 0x01]], ScriptFlags::CONSENSUS), Ok(true));
+        assert_eq!(
+            script_pubkey.evaluate([0x18; 32], Some(&[signature.to_vec()]), None, ScriptFlags::CONSENSUS),
+            Ok(false)
+        );
+
+        // The internal key's own signature does not spend the output its tweak pays to
+        let untweaked = internal_key.sign_schnorr(&z, &[0; 32]);
+        assert_eq!(evaluate(&[untweaked.to_vec()], ScriptFlags::CONSENSUS), Ok(false));
+        assert_eq!(evaluate(&[], ScriptFlags::CONSENSUS), Err(ScriptError::WitnessProgramWitnessEmpty));
+        assert_eq!(
+            evaluate(&[signature.to_vec(), vec![0x51]], ScriptFlags::CONSENSUS),
+            Err(ScriptError::UnsupportedTaprootScriptPath)
+        );
+        assert_eq!(
+            evaluate(&[signature[1..].to_vec()], ScriptFlags::CONSENSUS),
+            Err(ScriptError::SchnorrSignatureSize(63))
+        );
+        assert_eq!(
+            evaluate(&[[&signature[..], &[0x00]].concat()], ScriptFlags::CONSENSUS),
+            Err(ScriptError::SchnorrSignatureHashType(0x00))
+        );
+
+        let pre_taproot = ScriptFlags::from_bits(ScriptFlags::CONSENSUS.bits() & !ScriptFlags::VERIFY_TAPROOT.bits());
+        assert_eq!(evaluate(&[signature.to_vec()], pre_taproot), Err(ScriptError::UnsupportedWitnessVersion(1)));
+    }
+
+    #[test]
+    fn height_locktime_is_satisfied_from_that_height_on() {
+        let cltv = OP_CHECKLOCKTIMEVERIFY;
+
+        assert_eq!(evaluate_timelock(700_000, cltv, Some((700_000, 0, 1))), Ok(true));
+        assert_eq!(evaluate_timelock(700_000, cltv, Some((800_000, SEQUENCE_FINAL - 1, 2))), Ok(true));
+        assert_eq!(evaluate_timelock(0xffff_fffe, cltv, Some((0xffff_ffff, 0, 1))), Ok(true));
+    }
+
+    #[test]
+    fn unmet_locktimes_fail() {
+        let cltv = OP_CHECKLOCKTIMEVERIFY;
+
+        assert_eq!(evaluate_timelock(700_000, cltv, Some((699_999, 0, 1))), Err(ScriptError::UnsatisfiedLocktime));
+        // A final sequence disables the transaction's locktime, so it cannot satisfy the script
+        let final_sequence = Some((700_000, SEQUENCE_FINAL, 1));
+        assert_eq!(evaluate_timelock(700_000, cltv, final_sequence), Err(ScriptError::UnsatisfiedLocktime));
+        assert_eq!(evaluate_timelock(-1, cltv, Some((700_000, 0, 1))), Err(ScriptError::NegativeLocktime));
+        let context = TxContext { locktime: 0, sequence_of_input: 0, version: 1 };
+        assert_eq!(
+            Script::new(ops(&[cltv])).evaluate([0; 32], None, Some(&context), ScriptFlags::CONSENSUS),
+            Err(ScriptError::InvalidStackOperation(cltv))
+        );
+    }
+
+    #[test]
+    fn heights_and_timestamps_do_not_compare() {
+        let cltv = OP_CHECKLOCKTIMEVERIFY;
+
+        // A timestamp requirement against a height locktime, and the other way around, however the numbers compare
+        let time_against_height = evaluate_timelock(500_000_000, cltv, Some((499_999_999, 0, 1)));
+        let height_against_time = evaluate_timelock(100, cltv, Some((1_700_000_000, 0, 1)));
+
+        assert_eq!(time_against_height, Err(ScriptError::UnsatisfiedLocktime));
+        assert_eq!(height_against_time, Err(ScriptError::UnsatisfiedLocktime));
+        assert_eq!(evaluate_timelock(1_600_000_000, cltv, Some((1_700_000_000, 0, 1))), Ok(true));
+    }
+
+    #[test]
+    fn sequence_locks_compare_against_the_bip68_sequence() {
+        let csv = OP_CHECKSEQUENCEVERIFY;
+        let ten_blocks = RelativeLockTime::Blocks(10).to_sequence() as i64;
+        let sequence = |locktime: RelativeLockTime| locktime.to_sequence();
+
+        assert_eq!(evaluate_timelock(ten_blocks, csv, Some((0, sequence(RelativeLockTime::Blocks(10)), 2))), Ok(true));
+        assert_eq!(evaluate_timelock(ten_blocks, csv, Some((0, sequence(RelativeLockTime::Blocks(144)), 2))), Ok(true));
+        for (sequence, version) in [
+            (sequence(RelativeLockTime::Blocks(9)), 2),
+            (sequence(RelativeLockTime::Time(10)), 2),
+            (sequence(RelativeLockTime::Blocks(10)), 1),
+            (SEQUENCE_LOCKTIME_DISABLE_FLAG | 10, 2),
+        ] {
+            let result = evaluate_timelock(ten_blocks, csv, Some((0, sequence, version)));
+
+            assert_eq!(result, Err(ScriptError::UnsatisfiedLocktime), "sequence {:#x}, version {}", sequence, version);
+        }
+
+        // An operand with the disable flag set asks for nothing
+        let disabled = (SEQUENCE_LOCKTIME_DISABLE_FLAG | 10) as i64;
+        assert_eq!(evaluate_timelock(disabled, csv, Some((0, SEQUENCE_FINAL, 1))), Ok(true));
+    }
+
+    #[test]
+    fn timelocks_are_nops_without_their_flags() {
+        for (opcode, flag) in [
+            (OP_CHECKLOCKTIMEVERIFY, ScriptFlags::VERIFY_CLTV),
+            (OP_CHECKSEQUENCEVERIFY, ScriptFlags::VERIFY_CSV),
+        ] {
+            let unsatisfiable = TxContext { locktime: 0, sequence_of_input: SEQUENCE_FINAL, version: 1 };
+            let before_the_soft_fork = ScriptFlags::from_bits(ScriptFlags::CONSENSUS.bits() & !flag.bits());
+            let evaluate_with = |operand: i64, context: Option<&TxContext>, flags| {
+                let cmds = [vec![Cmd::push(encode_num(operand))], ops(&[opcode, OP_DROP, OP_1])].concat();
+                Script::new(cmds).evaluate([0; 32], None, context, flags)
+            };
+
+            assert_eq!(evaluate_with(700_000, Some(&unsatisfiable), before_the_soft_fork), Ok(true));
+            assert_eq!(evaluate_with(-1, None, before_the_soft_fork), Ok(true));
+            assert_eq!(Script::new(ops(&[opcode, OP_1])).evaluate([0; 32], None, None, ScriptFlags::NONE), Ok(true));
+            assert_eq!(evaluate_timelock(700_000, opcode, None), Err(ScriptError::UnsatisfiedLocktime));
+            assert_eq!(evaluate_timelock(-1, opcode, None), Err(ScriptError::NegativeLocktime));
+        }
+    }
+}
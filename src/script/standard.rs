@@ -0,0 +1,707 @@
+use crate::ecc::secp256k1::S256Point;
+use crate::hashing::hash160::hash160;
+use crate::hashing::sha256::sha256;
+use crate::script::interpreter::{
+    decode_num, encode_num, ScriptError, MAX_PUBKEYS_PER_MULTISIG, MAX_SCRIPT_ELEMENT_SIZE,
+};
+use crate::script::opcodes::{
+    OP_0, OP_1, OP_16, OP_CHECKMULTISIG, OP_CHECKSIG, OP_DUP, OP_EQUAL, OP_EQUALVERIFY, OP_HASH160, OP_RETURN,
+};
+use crate::script::{Cmd, Script};
+
+// The most data a standard OP_RETURN output carries, which with OP_RETURN and OP_PUSHDATA1 makes MAX_OP_RETURN_RELAY
+pub const MAX_OP_RETURN_DATA: usize = 80;
+
+// The output types Bitcoin Core's Solver tells apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScriptType {
+    // <SEC pubkey> OP_CHECKSIG
+    P2pk,
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    // A witness program of a version with no rules yet, kept spendable by anyone for future soft forks
+    WitnessUnknown { version: u8 },
+    BareMultisig { m: u8, n: u8 },
+    OpReturn,
+    NonStandard,
+}
+
+// The templates of the standard output types and the scripts that spend them
+impl Script {
+    // OP_DUP OP_HASH160 <hash160 of the pubkey> OP_EQUALVERIFY OP_CHECKSIG
+    pub fn p2pkh(pubkey_hash: &[u8; 20]) -> Self {
+        Self::new(vec![
+            Cmd::Op(OP_DUP),
+            Cmd::Op(OP_HASH160),
+            Cmd::push(pubkey_hash.to_vec()),
+            Cmd::Op(OP_EQUALVERIFY),
+            Cmd::Op(OP_CHECKSIG),
+        ])
+    }
+
+    // The hash has to be pushed with its length as the opcode, as Bitcoin Core matches the template byte for byte
+    pub fn is_p2pkh(&self) -> bool {
+        self.p2pkh_hash().is_some()
+    }
+
+    pub fn p2pkh_hash(&self) -> Option<[u8; 20]> {
+        match self.cmds.as_slice() {
+            [
+                Cmd::Op(OP_DUP),
+                Cmd::Op(OP_HASH160),
+                Cmd::Push { opcode: 20, data },
+                Cmd::Op(OP_EQUALVERIFY),
+                Cmd::Op(OP_CHECKSIG),
+            ] => data.as_slice().try_into().ok(),
+            _ => None,
+        }
+    }
+
+    // <DER signature with the sighash type byte> <SEC pubkey>
+    pub fn p2pkh_unlock(signature: &[u8], pubkey: &[u8]) -> Self {
+        Self::new(vec![Cmd::push(signature.to_vec()), Cmd::push(pubkey.to_vec())])
+    }
+
+    // OP_HASH160 <hash160 of the serialized redeem script> OP_EQUAL
+    pub fn p2sh(redeem_script_hash: &[u8; 20]) -> Self {
+        Self::new(vec![Cmd::Op(OP_HASH160), Cmd::push(redeem_script_hash.to_vec()), Cmd::Op(OP_EQUAL)])
+    }
+
+    pub fn is_p2sh(&self) -> bool {
+        self.redeem_script_hash().is_some()
+    }
+
+    pub fn redeem_script_hash(&self) -> Option<[u8; 20]> {
+        match self.cmds.as_slice() {
+            [Cmd::Op(OP_HASH160), Cmd::Push { opcode: 20, data }, Cmd::Op(OP_EQUAL)] => data.as_slice().try_into().ok(),
+            _ => None,
+        }
+    }
+
+    /*
+        OP_0 <signatures> <serialized redeem script>, spending a P2SH multisig. The OP_0 is the extra element
+        OP_CHECKMULTISIG pops, and the signatures go in the order of the keys they sign for in the redeem script.
+     */
+    pub fn p2sh_multisig_unlock(signatures: &[Vec<u8>], redeem_script: &Script) -> Self {
+        let mut cmds = vec![Cmd::push(vec![])];
+        cmds.extend(signatures.iter().map(|signature| Cmd::push(signature.clone())));
+        cmds.push(Cmd::push(redeem_script.to_bytes()));
+
+        Self::new(cmds)
+    }
+
+    // OP_0 <hash160 of the compressed pubkey>
+    pub fn p2wpkh(pubkey_hash: &[u8; 20]) -> Self {
+        Self::new(vec![Cmd::push(vec![]), Cmd::push(pubkey_hash.to_vec())])
+    }
+
+    // OP_0 <sha256 of the witness script>
+    pub fn p2wsh(witness_script_hash: &[u8; 32]) -> Self {
+        Self::new(vec![Cmd::push(vec![]), Cmd::push(witness_script_hash.to_vec())])
+    }
+
+    // OP_1 <x-only taproot output key>
+    pub fn p2tr(output_key: &[u8; 32]) -> Self {
+        Self::new(vec![Cmd::Op(OP_1), Cmd::push(output_key.to_vec())])
+    }
+
+    pub fn is_p2wpkh(&self) -> bool {
+        matches!(self.witness_program(), Some((0, program)) if program.len() == 20)
+    }
+
+    pub fn is_p2wsh(&self) -> bool {
+        matches!(self.witness_program(), Some((0, program)) if program.len() == 32)
+    }
+
+    /*
+        BIP141: a script that is a version opcode, OP_0 or OP_1 to OP_16, followed by a direct push of 2 to 40
+        bytes is a witness program. Returns the version and the pushed program.
+     */
+    pub fn witness_program(&self) -> Option<(u8, Vec<u8>)> {
+        let (version, program) = match self.cmds.as_slice() {
+            [Cmd::Push { opcode: OP_0, .. }, Cmd::Push { opcode, data }] => (0, (opcode, data)),
+            [Cmd::Op(version @ OP_1..=OP_16), Cmd::Push { opcode, data }] => (version - (OP_1 - 1), (opcode, data)),
+            _ => return None,
+        };
+
+        match program {
+            (opcode, data) if (2..=40).contains(&data.len()) && *opcode as usize == data.len() => {
+                Some((version, data.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /*
+        OP_m <pubkeys> OP_n OP_CHECKMULTISIG with the keys SEC encoded compressed. Sorted puts them in the
+        lexicographic order of their encodings, BIP67's, so cosigners get the same script whatever order they
+        exchanged keys in. Up to the 20 keys OP_CHECKMULTISIG takes are allowed, counts above 16 being pushed as
+        numbers, but only 15 fit the 520-byte push a P2SH spend reveals the script with, which to_p2sh reports.
+     */
+    pub fn multisig(m: u8, pubkeys: &[S256Point], sorted: bool) -> Result<Self, ScriptError> {
+        let n = pubkeys.len();
+        if m == 0 || m as usize > n || n > MAX_PUBKEYS_PER_MULTISIG {
+            return Err(ScriptError::MultisigCounts { m, n });
+        }
+
+        let mut keys: Vec<Vec<u8>> = pubkeys.iter().map(|pubkey| pubkey.sec(true)).collect();
+        if sorted {
+            keys.sort();
+        }
+
+        let mut cmds = vec![multisig_count(m as usize)];
+        cmds.extend(keys.into_iter().map(Cmd::push));
+        cmds.extend([multisig_count(n), Cmd::Op(OP_CHECKMULTISIG)]);
+
+        Ok(Self::new(cmds))
+    }
+
+    // The P2SH output paying to this redeem script, which is only spendable when the script fits in one push
+    pub fn to_p2sh(&self) -> Result<Self, ScriptError> {
+        let redeem_script = self.to_bytes();
+        if redeem_script.len() > MAX_SCRIPT_ELEMENT_SIZE {
+            return Err(ScriptError::PushSize(redeem_script.len()));
+        }
+
+        Ok(Self::p2sh(&hash160(&redeem_script)))
+    }
+
+    // The P2WSH output paying to this witness script
+    pub fn to_p2wsh(&self) -> Self {
+        Self::p2wsh(&sha256(&self.to_bytes()))
+    }
+
+    // OP_RETURN <data>, an output that can never be spent. Empty data gives a bare OP_RETURN.
+    pub fn op_return(data: &[u8]) -> Result<Self, ScriptError> {
+        match data.len() {
+            0 => Ok(Self::new(vec![Cmd::Op(OP_RETURN)])),
+            length if length > MAX_OP_RETURN_DATA => Err(ScriptError::DataCarrierSize(length)),
+            _ => Ok(Self::new(vec![Cmd::Op(OP_RETURN), Cmd::push(data.to_vec())])),
+        }
+    }
+
+    // Any script starting with OP_RETURN fails as soon as it runs, whatever follows
+    pub fn is_op_return(&self) -> bool {
+        self.cmds.first() == Some(&Cmd::Op(OP_RETURN))
+    }
+
+    // The payload of a data carrier: the single push after OP_RETURN, or nothing for a bare OP_RETURN
+    pub fn op_return_data(&self) -> Option<&[u8]> {
+        match self.cmds.as_slice() {
+            [Cmd::Op(OP_RETURN)] => Some(&[]),
+            [Cmd::Op(OP_RETURN), Cmd::Push { data, .. }] => Some(data),
+            _ => None,
+        }
+    }
+
+    /*
+        The type Bitcoin Core's Solver gives this script_pubkey, checking its templates in the same order. Each
+        push has to be the direct one of its size, as Solver compares bytes: a key pushed with OP_PUSHDATA1 is
+        not P2PK. Pubkeys only need the length their first byte announces, 33 for 0x02 and 0x03 and 65 for 0x04,
+        0x06 and 0x07. OP_RETURN followed by pushes is a data carrier whatever its size; the 83-byte limit is
+        relay policy, not part of the template.
+     */
+    pub fn classify(&self) -> ScriptType {
+        if self.is_p2sh() {
+            return ScriptType::P2sh;
+        }
+        if let Some((version, program)) = self.witness_program() {
+            return match (version, program.len()) {
+                (0, 20) => ScriptType::P2wpkh,
+                (0, 32) => ScriptType::P2wsh,
+                (0, _) => ScriptType::NonStandard,
+                (1, 32) => ScriptType::P2tr,
+                (version, _) => ScriptType::WitnessUnknown { version },
+            };
+        }
+        if self.is_op_return() && Script::new(self.cmds[1..].to_vec()).is_push_only() {
+            return ScriptType::OpReturn;
+        }
+        if self.is_p2pkh() {
+            return ScriptType::P2pkh;
+        }
+
+        match self.cmds.as_slice() {
+            [Cmd::Push { opcode, data }, Cmd::Op(OP_CHECKSIG)] if *opcode as usize == data.len() && is_pubkey(data) => {
+                ScriptType::P2pk
+            }
+            [m, keys @ .., n, Cmd::Op(OP_CHECKMULTISIG)] => {
+                let all_pubkeys = keys.iter().all(|key| matches!(key, Cmd::Push { data, .. } if is_pubkey(data)));
+
+                match (read_multisig_count(m), read_multisig_count(n)) {
+                    (Some(m), Some(n)) if all_pubkeys && keys.len() == n as usize && m <= n => {
+                        ScriptType::BareMultisig { m, n }
+                    }
+                    _ => ScriptType::NonStandard,
+                }
+            }
+            _ => ScriptType::NonStandard,
+        }
+    }
+
+    // Whether every command pushes data, counting OP_1NEGATE to OP_16 as pushes the way Bitcoin Core does
+    pub fn is_push_only(&self) -> bool {
+        self.cmds.iter().all(|cmd| match cmd {
+            Cmd::Push { .. } => true,
+            Cmd::Op(opcode) => *opcode <= OP_16,
+        })
+    }
+}
+
+// OP_1 to OP_16 for the multisig counts that have one, a minimal number push for 17 to 20
+fn multisig_count(count: usize) -> Cmd {
+    match count {
+        1..=16 => Cmd::Op(OP_1 - 1 + count as u8),
+        _ => Cmd::push(encode_num(count as i64)),
+    }
+}
+
+/*
+    Bitcoin Core's GetScriptNumber for a count from 1 to 20: OP_1 to OP_16, or a minimally encoded number in
+    the shortest push, which is the form multisig_count gives it.
+ */
+fn read_multisig_count(cmd: &Cmd) -> Option<u8> {
+    let count = match cmd {
+        Cmd::Op(opcode @ OP_1..=OP_16) => (opcode - (OP_1 - 1)) as usize,
+        Cmd::Push { data, .. } => usize::try_from(decode_num(data, 4).ok()?).ok()?,
+        Cmd::Op(_) => return None,
+    };
+
+    match (1..=MAX_PUBKEYS_PER_MULTISIG).contains(&count) && multisig_count(count) == *cmd {
+        true => Some(count as u8),
+        false => None,
+    }
+}
+
+// Bitcoin Core's CPubKey::ValidSize: the length the SEC prefix byte announces, without checking the point
+fn is_pubkey(data: &[u8]) -> bool {
+    match data.first() {
+        Some(0x02 | 0x03) => data.len() == 33,
+        Some(0x04 | 0x06 | 0x07) => data.len() == 65,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ecc::private_key::PrivateKey;
+    use crate::ecc::secp256k1::S256Point;
+    use crate::ecc::u256::U256;
+    use crate::encoding::hex;
+    use crate::hashing::hash160::hash160;
+    use crate::hashing::hash256::Hash256;
+    use crate::hashing::sha256::sha256;
+    use crate::network::Network;
+    use crate::script::interpreter::{ScriptError, ScriptFlags, MAX_PUBKEYS_PER_MULTISIG};
+    use crate::script::opcodes::{
+        OP_1, OP_16, OP_1NEGATE, OP_CHECKMULTISIG, OP_CHECKSIG, OP_EQUAL, OP_NOP, OP_PUSHDATA1, OP_RETURN,
+    };
+    use crate::script::standard::{ScriptType, MAX_OP_RETURN_DATA};
+    use crate::script::{Cmd, Script};
+    use crate::tx::amount::Amount;
+    use crate::tx::fixtures::{PREV_SCRIPT_PUBKEY, TX_HEX};
+    use crate::tx::outpoint::OutPoint;
+    use crate::tx::policy::MAX_OP_RETURN_RELAY;
+    use crate::tx::sighash::SighashType;
+    use crate::tx::transaction::Tx;
+    use crate::tx::tx_in::TxIn;
+    use crate::tx::tx_out::TxOut;
+    use crate::tx::witness::Witness;
+
+    // The Programming Bitcoin chapter 8 P2SH multisig spend: its signature hash, signatures and redeem script
+    const MULTISIG_Z_HEX: &str = "e71bfa115715d6fd33796948126f40a8cdd39f187e4afb03896795189fe1423c";
+    const MULTISIG_SIGNATURE_HEXES: [&str; 2] = [
+        "3045022100dc92655fe37036f47756db8102e0d7d5e28b3beb83a8fef4f5dc0559bddfb94e02205a36d4e4e6c7fcd16658c50783e0\
+         0c341609977aed3ad00937bf4ee942a8993701",
+        "3045022100da6bee3c93766232079a01639d07fa869598749729ae323eab8eef53577d611b02207bef15429dcadce2121ea07f2331\
+         15c6f09034c0be68db99980b9a6c5e75402201",
+    ];
+    const MULTISIG_REDEEM_SCRIPT_HEX: &str = "5221022626e955ea6ea6d98850c994f9107b036b1334f18ca8830bfff1295d21cfdb70\
+                                              2103b287eaf122eea69030a0e9feed096bed8045c8b98bec453e1ffac7fbdbd4bb7152ae";
+
+    const COINBASE_HEX: &str = "010000000001010000000000000000000000000000000000000000000000000000000000000000ffffffff\
+                                0403204e07ffffffff020890814a00000000160014111111111111111111111111111111111111111100000000\
+                                00000000266a24aa21a9edd5232abf74199897d4d7e6b39c255c816a0f698986bf7df236a56ed4a21a756d0120\
+                                000000000000000000000000000000000000000000000000000000000000000000000000";
+
+    #[test]
+    fn p2pkh_template_matches_real_outputs() {
+        let tx = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+        let hashes = ["bc3b654dca7e56b04dca18f2566cdaf02e8d9ada", "1c4bc762dd5423e332166702cb75f40df79fea12"];
+
+        for (output, hash_hex) in tx.outputs.iter().zip(hashes) {
+            let hash: [u8; 20] = hex::decode(hash_hex).unwrap().try_into().unwrap();
+            let script = Script::from_bytes(&output.script_pubkey).unwrap();
+
+            assert_eq!(Script::p2pkh(&hash).to_bytes(), output.script_pubkey);
+            assert!(script.is_p2pkh());
+            assert_eq!(script.p2pkh_hash(), Some(hash));
+        }
+    }
+
+    #[test]
+    fn near_miss_scripts_are_not_p2pkh() {
+        let p2pkh = Script::p2pkh(&[0xab; 20]);
+
+        let mut short_hash = p2pkh.clone();
+        short_hash.cmds[2] = Cmd::push(vec![0xab; 19]);
+        let mut extra_opcode = p2pkh.clone();
+        extra_opcode.cmds.push(Cmd::Op(OP_NOP));
+        let mut wrong_opcode = p2pkh.clone();
+        wrong_opcode.cmds[3] = Cmd::Op(OP_EQUAL);
+        let mut long_push = p2pkh.clone();
+        long_push.cmds[2] = Cmd::Push { opcode: OP_PUSHDATA1, data: vec![0xab; 20] };
+
+        for script in [short_hash, extra_opcode, wrong_opcode, long_push, Script::new(vec![Cmd::Op(OP_CHECKSIG)])] {
+            assert!(!script.is_p2pkh(), "{:?}", script);
+        }
+    }
+
+    #[test]
+    fn p2pkh_unlock_rebuilds_a_real_script_sig() {
+        let tx = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+        let script_pubkey = hex::decode(PREV_SCRIPT_PUBKEY).unwrap();
+        let z = tx.sig_hash(0, &script_pubkey, SighashType::ALL).unwrap();
+        let pubkey = hex::decode("0349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278a").unwrap();
+        let signature = hex::decode(
+            "3045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3\
+             f56100f4d7f67801c31967743a9c8e10615bed01",
+        )
+        .unwrap();
+
+        let script_sig = Script::p2pkh_unlock(&signature, &pubkey);
+
+        assert_eq!(script_sig.to_bytes(), tx.inputs[0].script_sig);
+        assert_eq!(&hex::encode(&hash160(&pubkey)), "a802fc56c704ce87c42d7c92eb75e7896bdc41ae");
+        assert_eq!(
+            (script_sig + Script::from_bytes(&script_pubkey).unwrap()).evaluate(z, None, None, ScriptFlags::CONSENSUS),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn p2sh_multisig_unlock_rebuilds_the_chapter_8_script_sig() {
+        let redeem_script = Script::from_bytes(&hex::decode(MULTISIG_REDEEM_SCRIPT_HEX).unwrap()).unwrap();
+        let signatures: Vec<Vec<u8>> =
+            MULTISIG_SIGNATURE_HEXES.iter().map(|signature| hex::decode(signature).unwrap()).collect();
+        let z: [u8; 32] = hex::decode(MULTISIG_Z_HEX).unwrap().try_into().unwrap();
+
+        let script_sig = Script::p2sh_multisig_unlock(&signatures, &redeem_script);
+
+        let expected = format!(
+            "0048{}48{}47{}",
+            MULTISIG_SIGNATURE_HEXES[0], MULTISIG_SIGNATURE_HEXES[1], MULTISIG_REDEEM_SCRIPT_HEX
+        );
+        assert_eq!(hex::encode(&script_sig.to_bytes()), expected);
+        assert_eq!(
+            (script_sig + redeem_script.to_p2sh().unwrap()).evaluate(z, None, None, ScriptFlags::CONSENSUS),
+            Ok(true)
+        );
+
+        let swapped = Script::p2sh_multisig_unlock(&[signatures[1].clone(), signatures[0].clone()], &redeem_script);
+        assert_eq!(
+            (swapped + redeem_script.to_p2sh().unwrap()).evaluate(z, None, None, ScriptFlags::CONSENSUS),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn p2sh_template_exposes_the_redeem_script_hash() {
+        // The P2SH output of the Programming Bitcoin chapter 8 multisig
+        let script_pubkey = hex::decode("a91474d691da1574e6b3c192ecfb52cc8984ee7b6c5687").unwrap();
+        let hash: [u8; 20] = script_pubkey[2..22].try_into().unwrap();
+
+        let script = Script::from_bytes(&script_pubkey).unwrap();
+
+        assert_eq!(Script::p2sh(&hash), script);
+        assert!(script.is_p2sh() && !script.is_p2pkh());
+        assert_eq!(script.redeem_script_hash(), Some(hash));
+        assert_eq!(Script::p2pkh(&hash).redeem_script_hash(), None);
+        assert!(!Script::new([script.cmds.clone(), vec![Cmd::Op(OP_NOP)]].concat()).is_p2sh());
+    }
+
+    #[test]
+    fn push_only_allows_small_integers_but_no_other_opcodes() {
+        let pushes = Script::new(vec![Cmd::push(vec![]), Cmd::Op(OP_1NEGATE), Cmd::Op(OP_16), Cmd::push(vec![1; 80])]);
+
+        assert!(pushes.is_push_only());
+        assert!(!Script::p2sh(&[0; 20]).is_push_only());
+        assert!(Script::new(vec![]).is_push_only());
+    }
+
+    #[test]
+    fn witness_programs_are_a_version_and_a_direct_push() {
+        // The P2WPKH output of the BIP143 example, and a P2TR output
+        let program = hex::decode("1d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap();
+        let p2wpkh = Script::from_bytes(&[vec![0x00, 0x14], program.clone()].concat()).unwrap();
+        let p2tr = Script::new(vec![Cmd::Op(OP_1), Cmd::push(vec![0x22; 32])]);
+
+        assert_eq!(p2wpkh.witness_program(), Some((0, program)));
+        assert!(p2wpkh.is_p2wpkh() && !p2wpkh.is_p2wsh());
+        assert!(Script::p2wsh(&[0x33; 32]).is_p2wsh());
+        assert_eq!(p2tr.witness_program(), Some((1, vec![0x22; 32])));
+        assert!(!p2tr.is_p2wsh());
+    }
+
+    #[test]
+    fn near_miss_scripts_are_not_witness_programs() {
+        let cases = [
+            Script::new(vec![Cmd::push(vec![]), Cmd::push(vec![0; 1])]),
+            Script::new(vec![Cmd::push(vec![]), Cmd::push(vec![0; 41])]),
+            Script::new(vec![Cmd::Op(OP_1NEGATE), Cmd::push(vec![0; 20])]),
+            Script::new(vec![Cmd::push(vec![]), Cmd::Push { opcode: OP_PUSHDATA1, data: vec![0; 20] }]),
+            Script::new(vec![Cmd::push(vec![0x00]), Cmd::push(vec![0; 20])]),
+            Script::new([Script::p2wpkh(&[0; 20]).cmds, vec![Cmd::Op(OP_NOP)]].concat()),
+        ];
+
+        for script in cases {
+            assert_eq!(script.witness_program(), None, "{:?}", script);
+        }
+    }
+
+    #[test]
+    fn op_return_outputs_round_trip() {
+        let data = b"the times 03/jan/2009";
+
+        let script = Script::op_return(data).unwrap();
+        let reparsed = Script::from_bytes(&script.to_bytes()).unwrap();
+
+        assert_eq!(script.to_bytes()[..2], [OP_RETURN, data.len() as u8]);
+        assert!(reparsed.is_op_return() && !reparsed.is_push_only());
+        assert_eq!(reparsed.op_return_data(), Some(&data[..]));
+        assert_eq!(Script::op_return(&[]).unwrap().cmds, vec![Cmd::Op(OP_RETURN)]);
+        assert_eq!(Script::op_return(&[]).unwrap().op_return_data(), Some(&[][..]));
+    }
+
+    #[test]
+    fn op_return_data_is_limited_to_80_bytes() {
+        let script = Script::op_return(&[0xab; MAX_OP_RETURN_DATA]).unwrap();
+
+        assert_eq!(script.to_bytes().len(), MAX_OP_RETURN_RELAY);
+        assert_eq!(Script::op_return(&[0xab; 81]), Err(ScriptError::DataCarrierSize(81)));
+    }
+
+    #[test]
+    fn op_return_data_is_extracted_from_a_coinbase() {
+        /*
+            The coinbase of the synthetic block in the block tests, not a mainnet transaction. Its second output
+            is the BIP141 witness commitment: OP_RETURN and a push of the aa21a9ed header and the commitment hash.
+         */
+        let tx = Tx::parse_exact(&hex::decode(COINBASE_HEX).unwrap(), false).unwrap();
+
+        let scripts: Vec<Script> =
+            tx.outputs.iter().map(|output| Script::from_bytes(&output.script_pubkey).unwrap()).collect();
+
+        assert_eq!(scripts[0].op_return_data(), None);
+        assert!(scripts[1].is_op_return());
+        assert_eq!(
+            hex::encode(scripts[1].op_return_data().unwrap()),
+            "aa21a9edd5232abf74199897d4d7e6b39c255c816a0f698986bf7df236a56ed4a21a756d"
+        );
+    }
+
+    #[test]
+    fn scripts_after_op_return_are_not_data_carriers() {
+        let two_pushes = Script::new(vec![Cmd::Op(OP_RETURN), Cmd::push(vec![1]), Cmd::push(vec![2])]);
+        let opcode = Script::new(vec![Cmd::Op(OP_RETURN), Cmd::Op(OP_CHECKSIG)]);
+
+        for script in [two_pushes, opcode] {
+            assert!(script.is_op_return());
+            assert_eq!(script.op_return_data(), None);
+        }
+        assert!(!Script::new(vec![Cmd::push(vec![]), Cmd::Op(OP_RETURN)]).is_op_return());
+    }
+
+    #[test]
+    fn op_return_scripts_never_evaluate_to_true() {
+        let script_pubkey = Script::op_return(b"data").unwrap();
+        let with_script_sig = Script::new([vec![Cmd::Op(OP_1)], script_pubkey.cmds.clone()].concat());
+
+        assert_eq!(script_pubkey.evaluate([0; 32], None, None, ScriptFlags::CONSENSUS), Err(ScriptError::OpReturn));
+        assert_eq!(with_script_sig.evaluate([0; 32], None, None, ScriptFlags::CONSENSUS), Err(ScriptError::OpReturn));
+    }
+
+    #[test]
+    fn scripts_classify_like_bitcoin_core_solver() {
+        let key = "0349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278a";
+        let hash = "bc3b654dca7e56b04dca18f2566cdaf02e8d9ada";
+        let cases = [
+            // The genesis coinbase output, and the chapter 5 spender's key paid to directly
+            (
+                "4104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c\
+                 384df7ba0b8d578a4c702b6bf11d5fac",
+                ScriptType::P2pk,
+            ),
+            (&format!("21{}ac", key), ScriptType::P2pk),
+            ("76a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac", ScriptType::P2pkh),
+            ("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac", ScriptType::P2pkh),
+            ("a91474d691da1574e6b3c192ecfb52cc8984ee7b6c5687", ScriptType::P2sh),
+            ("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1", ScriptType::P2wpkh),
+            ("00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262", ScriptType::P2wsh),
+            ("5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c", ScriptType::P2tr),
+            // BIP350's version 2 and version 16 examples
+            ("5210751e76e8199196d454941c45d1b3a323", ScriptType::WitnessUnknown { version: 2 }),
+            (
+                "6028751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6",
+                ScriptType::WitnessUnknown { version: 16 },
+            ),
+            // The Programming Bitcoin chapter 8 redeem script
+            (
+                "5221022626e955ea6ea6d98850c994f9107b036b1334f18ca8830bfff1295d21cfdb702103b287eaf122eea69030a0e9feed\
+                 096bed8045c8b98bec453e1ffac7fbdbd4bb7152ae",
+                ScriptType::BareMultisig { m: 2, n: 2 },
+            ),
+            (&format!("5121{}51ae", key), ScriptType::BareMultisig { m: 1, n: 1 }),
+            // A witness commitment, a bare OP_RETURN and one carrying small integers
+            ("6a24aa21a9edd5232abf74199897d4d7e6b39c255c816a0f698986bf7df236a56ed4a21a756d", ScriptType::OpReturn),
+            ("6a", ScriptType::OpReturn),
+            ("6a5160", ScriptType::OpReturn),
+            // A non-push after OP_RETURN, a hash pushed with OP_PUSHDATA1 and a trailing opcode after P2SH
+            ("6aac", ScriptType::NonStandard),
+            (&format!("76a94c14{}88ac", hash), ScriptType::NonStandard),
+            (&format!("a914{}8787", hash), ScriptType::NonStandard),
+            // A version 0 program of neither length, and a key pushed with OP_PUSHDATA1
+            (&format!("0019{}0102030405", hash), ScriptType::NonStandard),
+            (&format!("4c21{}ac", key), ScriptType::NonStandard),
+            // A key with an unknown prefix, m above n, and one key fewer than n
+            (&format!("2105{}ac", &key[2..]), ScriptType::NonStandard),
+            (&format!("5221{}51ae", key), ScriptType::NonStandard),
+            (&format!("5121{}52ae", key), ScriptType::NonStandard),
+            ("", ScriptType::NonStandard),
+        ];
+
+        for (script_hex, script_type) in cases {
+            let script = Script::from_bytes(&hex::decode(script_hex).unwrap()).unwrap();
+
+            assert_eq!(script.classify(), script_type, "{}", script_hex);
+        }
+    }
+
+    fn parse_keys(sec_hexes: &[&str]) -> Vec<S256Point> {
+        sec_hexes.iter().map(|sec| S256Point::parse_sec(&hex::decode(sec).unwrap()).unwrap()).collect()
+    }
+
+    // BIP67's first two test vectors, with the keys given in reverse of the order the vectors list them in
+    #[test]
+    fn sorted_multisig_matches_bip67_vectors() {
+        let cases = [
+            (
+                vec![
+                    "02ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f8",
+                    "02fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f",
+                ],
+                "522102fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f2102ff12471208c14bd580709cb2358d\
+                 98975247d8765f92bc25eab3b2763ed605f852ae",
+                "39bgKC7RFbpoCRbtD5KEdkYKtNyhpsNa3Z",
+            ),
+            (
+                vec![
+                    "02632b12f4ac5b1d1b72b2a3b508c19172de44f6f46bcee50ba33f3f9291e47ed0",
+                    "027735a29bae7780a9755fae7a1c4374c656ac6a69ea9f3697fda61bb99a4f3e77",
+                    "02e2cc6bd5f45edd43bebe7cb9b675f0ce9ed3efe613b177588290ad188d11b404",
+                ],
+                "522102632b12f4ac5b1d1b72b2a3b508c19172de44f6f46bcee50ba33f3f9291e47ed021027735a29bae7780a9755fae7a1c43\
+                 74c656ac6a69ea9f3697fda61bb99a4f3e772102e2cc6bd5f45edd43bebe7cb9b675f0ce9ed3efe613b177588290ad188d11b4\
+                 0453ae",
+                "3CKHTjBKxCARLzwABMu9yD85kvtm7WnMfH",
+            ),
+        ];
+
+        for (sec_hexes, redeem_script_hex, address) in cases {
+            let mut keys = parse_keys(&sec_hexes);
+            keys.reverse();
+
+            let redeem_script = Script::multisig(2, &keys, true).unwrap();
+            let script_pubkey = redeem_script.to_p2sh().unwrap();
+
+            assert_eq!(hex::encode(&redeem_script.to_bytes()), redeem_script_hex);
+            assert_eq!(script_pubkey.to_address(Network::Mainnet).unwrap().to_string(), address);
+            assert_eq!(Script::multisig(2, &keys, false).unwrap().cmds[1], Cmd::push(keys[0].sec(true)));
+        }
+    }
+
+    #[test]
+    fn multisig_counts_are_limited_to_what_checkmultisig_takes() {
+        let keys: Vec<S256Point> =
+            (1..=21).map(|secret| PrivateKey::new(U256::from_u64(secret)).unwrap().point()).collect();
+
+        assert_eq!(Script::multisig(0, &keys[..3], false), Err(ScriptError::MultisigCounts { m: 0, n: 3 }));
+        assert_eq!(Script::multisig(4, &keys[..3], false), Err(ScriptError::MultisigCounts { m: 4, n: 3 }));
+        assert_eq!(Script::multisig(1, &[], false), Err(ScriptError::MultisigCounts { m: 1, n: 0 }));
+        assert_eq!(Script::multisig(1, &keys, false), Err(ScriptError::MultisigCounts { m: 1, n: 21 }));
+
+        let twenty = Script::multisig(17, &keys[..MAX_PUBKEYS_PER_MULTISIG], false).unwrap();
+        assert_eq!(twenty.cmds[0], Cmd::push(vec![17]));
+        assert_eq!(twenty.cmds[21], Cmd::push(vec![20]));
+        assert_eq!(twenty.classify(), ScriptType::BareMultisig { m: 17, n: 20 });
+        assert_eq!(Script::multisig(16, &keys[..16], false).unwrap().cmds[17], Cmd::Op(OP_16));
+    }
+
+    #[test]
+    fn multisig_counts_above_sixteen_must_be_minimal_pushes() {
+        let keys: Vec<S256Point> =
+            (1..=21).map(|secret| PrivateKey::new(U256::from_u64(secret)).unwrap().point()).collect();
+        let with_counts = |m: Cmd, key_count: usize, n: Cmd| {
+            let mut cmds = vec![m];
+            cmds.extend(keys[..key_count].iter().map(|key| Cmd::push(key.sec(true))));
+            cmds.extend([n, Cmd::Op(OP_CHECKMULTISIG)]);
+            Script::new(cmds).classify()
+        };
+        let padded = Cmd::Push { opcode: 2, data: vec![17, 0] };
+        let pushdata = Cmd::Push { opcode: OP_PUSHDATA1, data: vec![17] };
+
+        assert_eq!(with_counts(Cmd::Op(OP_1), 17, Cmd::push(vec![17])), ScriptType::BareMultisig { m: 1, n: 17 });
+        assert_eq!(with_counts(Cmd::Op(OP_1), 21, Cmd::push(vec![21])), ScriptType::NonStandard);
+        assert_eq!(with_counts(Cmd::Op(OP_1), 17, padded), ScriptType::NonStandard);
+        assert_eq!(with_counts(Cmd::Op(OP_1), 17, pushdata), ScriptType::NonStandard);
+        // A count of 16 or less pushed as data instead of with its opcode
+        assert_eq!(with_counts(Cmd::push(vec![1]), 2, Cmd::Op(OP_1 + 1)), ScriptType::NonStandard);
+    }
+
+    #[test]
+    fn only_fifteen_keys_fit_a_p2sh_redeem_script() {
+        let keys: Vec<S256Point> =
+            (1..=16).map(|secret| PrivateKey::new(U256::from_u64(secret)).unwrap().point()).collect();
+
+        let fifteen = Script::multisig(1, &keys[..15], true).unwrap();
+        let sixteen = Script::multisig(1, &keys, true).unwrap();
+
+        assert!(fifteen.to_p2sh().unwrap().is_p2sh());
+        assert_eq!(sixteen.to_p2sh(), Err(ScriptError::PushSize(547)));
+        assert_eq!(sixteen.to_p2wsh(), Script::p2wsh(&sha256(&sixteen.to_bytes())));
+    }
+
+    #[test]
+    fn constructed_two_of_three_spends_verify() {
+        let private_keys = [3, 5, 7].map(|secret| PrivateKey::new(U256::from_u64(secret)).unwrap());
+        let redeem_script = Script::multisig(2, &private_keys.each_ref().map(|key| key.point()), true).unwrap();
+        let amount = Amount::from_sat(50_000);
+        let inputs = vec![TxIn::new(OutPoint::new(Hash256::from_wire_bytes([0x11; 32]), 0), vec![], 0xffffffff)];
+        let outputs = vec![TxOut::new(Amount::from_sat(40_000), Script::p2wpkh(&[0x22; 20]).to_bytes())];
+        let tx = Tx::new(2, inputs, outputs, 0, false);
+
+        // Signatures go in the order of the keys in the redeem script, which sorting decided
+        let signatures = |z: &[u8; 32], signers: &[usize]| -> Vec<Vec<u8>> {
+            let mut signers: Vec<&PrivateKey> = signers.iter().map(|index| &private_keys[*index]).collect();
+            signers.sort_by_key(|key| key.point().sec(true));
+            signers.iter().map(|key| [key.sign(z).der(), vec![0x01]].concat()).collect()
+        };
+
+        let z = tx.sig_hash(0, &redeem_script.to_bytes(), SighashType::ALL).unwrap();
+        for (signers, valid) in [([0, 2], true), ([1, 2], true), ([0, 0], false)] {
+            let script_sig = Script::p2sh_multisig_unlock(&signatures(&z, &signers), &redeem_script);
+            let spend = script_sig + redeem_script.to_p2sh().unwrap();
+
+            assert_eq!(spend.evaluate(z, None, None, ScriptFlags::CONSENSUS), Ok(valid), "P2SH {:?}", signers);
+        }
+
+        let z = tx.sig_hash_bip143(0, &redeem_script.to_bytes(), amount, SighashType::ALL).unwrap();
+        let witness = Witness::p2wsh(&[vec![vec![]], signatures(&z, &[1, 0])].concat(), &redeem_script).items;
+
+        assert_eq!(redeem_script.to_p2wsh().evaluate(z, Some(&witness), None, ScriptFlags::CONSENSUS), Ok(true));
+        assert_eq!(redeem_script.to_p2wsh().evaluate([0; 32], Some(&witness), None, ScriptFlags::CONSENSUS), Ok(false));
+    }
+}
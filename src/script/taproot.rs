@@ -0,0 +1,513 @@
+use std::fmt::{Display, Formatter};
+use crate::ecc::private_key::PrivateKey;
+use crate::ecc::scalar::Scalar;
+use crate::ecc::secp256k1::{S256Point, N};
+use crate::ecc::u256::U256;
+use crate::encoding::varint::encode_varint;
+use crate::hashing::tagged_hash::{tagged_hash, tagged_hash_engine, TAP_BRANCH, TAP_LEAF, TAP_TWEAK};
+use crate::script::Script;
+
+// The BIP342 leaf version, under which leaf scripts run as tapscript
+pub const TAPSCRIPT_LEAF_VERSION: u8 = 0xc0;
+// A control block's first byte keeps the leaf version in its upper seven bits and the output key's parity in the last
+pub const TAPROOT_LEAF_MASK: u8 = 0xfe;
+// The byte that marks the last witness item as an annex, which a leaf version must not be mistaken for
+pub const ANNEX_TAG: u8 = 0x50;
+pub const TAPROOT_CONTROL_BASE_SIZE: usize = 33;
+pub const TAPROOT_CONTROL_NODE_SIZE: usize = 32;
+pub const TAPROOT_CONTROL_MAX_NODE_COUNT: usize = 128;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaprootError {
+    // An odd leaf version, or the annex tag
+    InvalidLeafVersion(u8),
+    // An x-only key that is not the x coordinate of a point on the curve
+    InvalidInternalKey,
+    // A tweak that is not below the curve order or that cancels the internal key, which no hash is known to give
+    InvalidTweak,
+    LeafNotInTree,
+    // A leaf deeper than the 128 levels a control block has room for
+    MerklePathLength(usize),
+    // A control block that is not 33 bytes followed by up to 128 32-byte hashes
+    ControlBlockSize(usize),
+}
+
+impl Display for TaprootError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaprootError::InvalidLeafVersion(version) => write!(f, "Invalid tapleaf version {:#04x}.", version),
+            TaprootError::InvalidInternalKey => write!(f, "Internal key is not on secp256k1."),
+            TaprootError::InvalidTweak => write!(f, "Taproot tweak does not give a valid output key."),
+            TaprootError::LeafNotInTree => write!(f, "Leaf is not in the script tree."),
+            TaprootError::MerklePathLength(length) => {
+                write!(f, "Merkle path of {} hashes is over the {} limit.", length, TAPROOT_CONTROL_MAX_NODE_COUNT)
+            }
+            TaprootError::ControlBlockSize(size) => write!(f, "Control block of {} bytes has an invalid size.", size),
+        }
+    }
+}
+
+impl std::error::Error for TaprootError {}
+
+// A script together with the version it is to be run under
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TapLeaf {
+    version: u8,
+    script: Script,
+}
+
+impl TapLeaf {
+    pub fn new(version: u8, script: Script) -> Result<Self, TaprootError> {
+        if version & !TAPROOT_LEAF_MASK != 0 || version == ANNEX_TAG {
+            return Err(TaprootError::InvalidLeafVersion(version));
+        }
+
+        Ok(Self { version, script })
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn script(&self) -> &Script {
+        &self.script
+    }
+
+    // TapLeaf tagged hash of the version, then the script with its compact size length prefix
+    pub fn hash(&self) -> [u8; 32] {
+        let script = self.script.to_bytes();
+        let mut engine = tagged_hash_engine(TAP_LEAF);
+        engine.update(&[self.version]);
+        engine.update(&encode_varint(script.len() as u64));
+        engine.update(&script);
+
+        engine.finalize()
+    }
+}
+
+// A binary tree of leaves, each branch committing to its children in either order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TapTree {
+    Leaf(TapLeaf),
+    Branch(Box<TapTree>, Box<TapTree>),
+}
+
+impl TapTree {
+    pub fn branch(left: TapTree, right: TapTree) -> Self {
+        TapTree::Branch(Box::new(left), Box::new(right))
+    }
+
+    // The hash of the root node, which for a single leaf is the leaf's own hash
+    pub fn merkle_root(&self) -> [u8; 32] {
+        match self {
+            TapTree::Leaf(leaf) => leaf.hash(),
+            TapTree::Branch(left, right) => tap_branch_hash(&left.merkle_root(), &right.merkle_root()),
+        }
+    }
+
+    // The hashes of the siblings on the way from the leaf up to the root, nearest first, or None without the leaf
+    pub fn merkle_path(&self, leaf: &TapLeaf) -> Option<Vec<[u8; 32]>> {
+        match self {
+            TapTree::Leaf(own) => (own == leaf).then(Vec::new),
+            TapTree::Branch(left, right) => {
+                let (mut path, sibling) = match left.merkle_path(leaf) {
+                    Some(path) => (path, right),
+                    None => (right.merkle_path(leaf)?, left),
+                };
+                path.push(sibling.merkle_root());
+
+                Some(path)
+            }
+        }
+    }
+
+    // The x-only output key for the internal key committing to this tree, and whether its y is odd
+    pub fn output_key(&self, internal_key: &[u8; 32]) -> Result<([u8; 32], bool), TaprootError> {
+        tweak_public_key(internal_key, Some(&self.merkle_root()))
+    }
+
+    // What a script path spend of the leaf puts after the leaf's script in the witness
+    pub fn control_block(&self, internal_key: &[u8; 32], leaf: &TapLeaf) -> Result<ControlBlock, TaprootError> {
+        let merkle_path = self.merkle_path(leaf).ok_or(TaprootError::LeafNotInTree)?;
+        if merkle_path.len() > TAPROOT_CONTROL_MAX_NODE_COUNT {
+            return Err(TaprootError::MerklePathLength(merkle_path.len()));
+        }
+        let (_, odd_y) = self.output_key(internal_key)?;
+
+        Ok(ControlBlock { leaf_version_parity: leaf.version | odd_y as u8, internal_key: *internal_key, merkle_path })
+    }
+}
+
+// TapBranch tagged hash of the two children, the lesser first
+pub fn tap_branch_hash(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (first, second) = if a <= b { (a, b) } else { (b, a) };
+    let mut engine = tagged_hash_engine(TAP_BRANCH);
+    engine.update(first);
+    engine.update(second);
+
+    engine.finalize()
+}
+
+// TapTweak tagged hash of the internal key, followed by the merkle root when the output has scripts
+pub fn tap_tweak_hash(internal_key: &[u8; 32], merkle_root: Option<&[u8; 32]>) -> [u8; 32] {
+    match merkle_root {
+        Some(merkle_root) => tagged_hash(TAP_TWEAK, &[&internal_key[..], merkle_root].concat()),
+        None => tagged_hash(TAP_TWEAK, internal_key),
+    }
+}
+
+/*
+    BIP341 taproot_tweak_pubkey: Q = P + tG, where P is the point with the internal key as x and an even y, and t
+    is tap_tweak_hash. Gives the x-only Q, which the P2TR output pays to, and whether its y is odd, which a control
+    block records.
+ */
+pub fn tweak_public_key(
+    internal_key: &[u8; 32],
+    merkle_root: Option<&[u8; 32]>,
+) -> Result<([u8; 32], bool), TaprootError> {
+    let internal_point =
+        S256Point::lift_x(U256::from_be_bytes(*internal_key), false).map_err(|_| TaprootError::InvalidInternalKey)?;
+    let tweak = U256::from_be_bytes(tap_tweak_hash(internal_key, merkle_root));
+    if tweak >= N.value() {
+        return Err(TaprootError::InvalidTweak);
+    }
+
+    let output_point = match S256Point::generator().mul(&tweak) {
+        Some(tweak_point) => internal_point.add(&tweak_point).ok_or(TaprootError::InvalidTweak)?,
+        None => internal_point,
+    };
+
+    Ok((output_point.x().to_be_bytes(), output_point.y().is_odd()))
+}
+
+/*
+    BIP341 taproot_tweak_seckey: the secret of the output key tweak_public_key gives for this key's x-only public
+    key, which is what signs a key path spend. The secret is negated first when its point has an odd y, since the
+    x-only internal key stands for the point with an even y.
+ */
+pub fn tweak_private_key(
+    private_key: &PrivateKey,
+    merkle_root: Option<&[u8; 32]>,
+) -> Result<PrivateKey, TaprootError> {
+    let internal_point = private_key.point();
+    let secret = private_key.secret().negate_if(internal_point.y().is_odd());
+    let tweak = Scalar::from_be_bytes(tap_tweak_hash(&internal_point.x().to_be_bytes(), merkle_root))
+        .ok_or(TaprootError::InvalidTweak)?;
+
+    PrivateKey::new((secret + tweak).value()).ok_or(TaprootError::InvalidTweak)
+}
+
+/*
+    The last witness item of a script path spend:
+
+    leaf_version_parity  1 byte, the leaf version with the output key's y parity in the lowest bit
+    internal_key         32 bytes, x-only
+    merkle_path          0 to 128 hashes of 32 bytes, from the leaf's sibling up to the root
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlBlock {
+    pub leaf_version_parity: u8,
+    pub internal_key: [u8; 32],
+    pub merkle_path: Vec<[u8; 32]>,
+}
+
+impl ControlBlock {
+    pub fn parse(bytes: &[u8]) -> Result<Self, TaprootError> {
+        match bytes.len().checked_sub(TAPROOT_CONTROL_BASE_SIZE) {
+            Some(path_size)
+                if path_size.is_multiple_of(TAPROOT_CONTROL_NODE_SIZE)
+                    && path_size / TAPROOT_CONTROL_NODE_SIZE <= TAPROOT_CONTROL_MAX_NODE_COUNT => {}
+            _ => return Err(TaprootError::ControlBlockSize(bytes.len())),
+        }
+
+        Ok(Self {
+            leaf_version_parity: bytes[0],
+            internal_key: bytes[1..TAPROOT_CONTROL_BASE_SIZE].try_into().expect("The internal key is 32 bytes."),
+            merkle_path: bytes[TAPROOT_CONTROL_BASE_SIZE..]
+                .chunks(TAPROOT_CONTROL_NODE_SIZE)
+                .map(|node| node.try_into().expect("Nodes are 32 bytes."))
+                .collect(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let size = TAPROOT_CONTROL_BASE_SIZE + self.merkle_path.len() * TAPROOT_CONTROL_NODE_SIZE;
+        let mut bytes = Vec::with_capacity(size);
+        bytes.push(self.leaf_version_parity);
+        bytes.extend_from_slice(&self.internal_key);
+        for node in &self.merkle_path {
+            bytes.extend_from_slice(node);
+        }
+
+        bytes
+    }
+
+    pub fn leaf_version(&self) -> u8 {
+        self.leaf_version_parity & TAPROOT_LEAF_MASK
+    }
+
+    pub fn output_key_odd_y(&self) -> bool {
+        self.leaf_version_parity & !TAPROOT_LEAF_MASK == 1
+    }
+
+    /*
+        BIP341's script path commitment check: the leaf, run under the version this block gives, hashed up its
+        merkle path to a root that tweaks the internal key into the output key, with the recorded parity.
+     */
+    pub fn verify(&self, output_key: &[u8; 32], leaf: &TapLeaf) -> bool {
+        if leaf.version != self.leaf_version() || self.merkle_path.len() > TAPROOT_CONTROL_MAX_NODE_COUNT {
+            return false;
+        }
+
+        let merkle_root = self.merkle_path.iter().fold(leaf.hash(), |node, sibling| tap_branch_hash(&node, sibling));
+
+        tweak_public_key(&self.internal_key, Some(&merkle_root))
+            .is_ok_and(|(key, odd_y)| &key == output_key && odd_y == self.output_key_odd_y())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ecc::private_key::PrivateKey;
+    use crate::ecc::u256::U256;
+    use crate::encoding::hex;
+    use crate::network::Network;
+    use crate::script::taproot::{
+        tap_tweak_hash, tweak_private_key, tweak_public_key, ControlBlock, TapLeaf, TapTree, TaprootError,
+        TAPSCRIPT_LEAF_VERSION,
+    };
+    use crate::script::{Cmd, Script};
+
+    fn bytes32(hex_str: &str) -> [u8; 32] {
+        hex::decode(hex_str).unwrap().try_into().unwrap()
+    }
+
+    fn leaf(version: u8, script_hex: &str) -> TapLeaf {
+        TapLeaf::new(version, Script::from_bytes(&hex::decode(script_hex).unwrap()).unwrap()).unwrap()
+    }
+
+    fn tree(leaves: &[TapLeaf]) -> TapTree {
+        match leaves {
+            [single] => TapTree::Leaf(single.clone()),
+            [left, right] => TapTree::branch(TapTree::Leaf(left.clone()), TapTree::Leaf(right.clone())),
+            _ => unreachable!(),
+        }
+    }
+
+    // One of BIP341's wallet test vectors with a script tree, and what it expects
+    struct ScriptTreeVector {
+        internal_key: &'static str,
+        tree: TapTree,
+        leaf_hashes: Vec<&'static str>,
+        merkle_root: &'static str,
+        tweak: &'static str,
+        output_key: &'static str,
+        address: &'static str,
+        control_blocks: Vec<&'static str>,
+    }
+
+    fn script_tree_vectors() -> Vec<ScriptTreeVector> {
+        vec![
+            ScriptTreeVector {
+                internal_key: "187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27",
+                tree: tree(&[leaf(0xc0, "20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac")]),
+                leaf_hashes: vec!["5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21"],
+                merkle_root: "5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21",
+                tweak: "cbd8679ba636c1110ea247542cfbd964131a6be84f873f7f3b62a777528ed001",
+                output_key: "147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3",
+                address: "bc1pz37fc4cn9ah8anwm4xqqhvxygjf9rjf2resrw8h8w4tmvcs0863sa2e586",
+                control_blocks: vec!["c1187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27"],
+            },
+            ScriptTreeVector {
+                internal_key: "93478e9488f956df2396be2ce6c5cced75f900dfa18e7dabd2428aae78451820",
+                tree: tree(&[leaf(0xc0, "20b617298552a72ade070667e86ca63b8f5789a9fe8731ef91202a91c9f3459007ac")]),
+                leaf_hashes: vec!["c525714a7f49c28aedbbba78c005931a81c234b2f6c99a73e4d06082adc8bf2b"],
+                merkle_root: "c525714a7f49c28aedbbba78c005931a81c234b2f6c99a73e4d06082adc8bf2b",
+                tweak: "6af9e28dbf9d6aaf027696e2598a5b3d056f5fd2355a7fd5a37a0e5008132d30",
+                output_key: "e4d810fd50586274face62b8a807eb9719cef49c04177cc6b76a9a4251d5450e",
+                address: "bc1punvppl2stp38f7kwv2u2spltjuvuaayuqsthe34hd2dyy5w4g58qqfuag5",
+                control_blocks: vec!["c093478e9488f956df2396be2ce6c5cced75f900dfa18e7dabd2428aae78451820"],
+            },
+            ScriptTreeVector {
+                internal_key: "ee4fe085983462a184015d1f782d6a5f8b9c2b60130aff050ce221ecf3786592",
+                tree: tree(&[
+                    leaf(0xc0, "20387671353e273264c495656e27e39ba899ea8fee3bb69fb2a680e22093447d48ac"),
+                    leaf(0xfa, "06424950333431"),
+                ]),
+                leaf_hashes: vec![
+                    "8ad69ec7cf41c2a4001fd1f738bf1e505ce2277acdcaa63fe4765192497f47a7",
+                    "f224a923cd0021ab202ab139cc56802ddb92dcfc172b9212261a539df79a112a",
+                ],
+                merkle_root: "6c2dc106ab816b73f9d07e3cd1ef2c8c1256f519748e0813e4edd2405d277bef",
+                tweak: "9e0517edc8259bb3359255400b23ca9507f2a91cd1e4250ba068b4eafceba4a9",
+                output_key: "712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5",
+                address: "bc1pwyjywgrd0ffr3tx8laflh6228dj98xkjj8rum0zfpd6h0e930h6saqxrrm",
+                control_blocks: vec![
+                    "c0ee4fe085983462a184015d1f782d6a5f8b9c2b60130aff050ce221ecf3786592f224a923cd0021ab202ab139cc56802d\
+                     db92dcfc172b9212261a539df79a112a",
+                    "faee4fe085983462a184015d1f782d6a5f8b9c2b60130aff050ce221ecf37865928ad69ec7cf41c2a4001fd1f738bf1e50\
+                     5ce2277acdcaa63fe4765192497f47a7",
+                ],
+            },
+            ScriptTreeVector {
+                internal_key: "f9f400803e683727b14f463836e1e78e1c64417638aa066919291a225f0e8dd8",
+                tree: tree(&[
+                    leaf(0xc0, "2044b178d64c32c4a05cc4f4d1407268f764c940d20ce97abfd44db5c3592b72fdac"),
+                    leaf(0xc0, "07546170726f6f74"),
+                ]),
+                leaf_hashes: vec![
+                    "64512fecdb5afa04f98839b50e6f0cb7b1e539bf6f205f67934083cdcc3c8d89",
+                    "2cb2b90daa543b544161530c925f285b06196940d6085ca9474d41dc3822c5cb",
+                ],
+                merkle_root: "ab179431c28d3b68fb798957faf5497d69c883c6fb1e1cd9f81483d87bac90cc",
+                tweak: "639f0281b7ac49e742cd25b7f188657626da1ad169209078e2761cefd91fd65e",
+                output_key: "77e30a5522dd9f894c3f8b8bd4c4b2cf82ca7da8a3ea6a239655c39c050ab220",
+                address: "bc1pwl3s54fzmk0cjnpl3w9af39je7pv5ldg504x5guk2hpecpg2kgsqaqstjq",
+                control_blocks: vec![
+                    "c1f9f400803e683727b14f463836e1e78e1c64417638aa066919291a225f0e8dd82cb2b90daa543b544161530c925f285b\
+                     06196940d6085ca9474d41dc3822c5cb",
+                    "c1f9f400803e683727b14f463836e1e78e1c64417638aa066919291a225f0e8dd864512fecdb5afa04f98839b50e6f0cb7\
+                     b1e539bf6f205f67934083cdcc3c8d89",
+                ],
+            },
+        ]
+    }
+
+    fn leaves(tree: &TapTree) -> Vec<TapLeaf> {
+        match tree {
+            TapTree::Leaf(leaf) => vec![leaf.clone()],
+            TapTree::Branch(left, right) => [leaves(left), leaves(right)].concat(),
+        }
+    }
+
+    #[test]
+    fn script_trees_match_bip341_wallet_vectors() {
+        for vector in script_tree_vectors() {
+            let internal_key = bytes32(vector.internal_key);
+            let merkle_root = vector.tree.merkle_root();
+
+            let (output_key, _) = vector.tree.output_key(&internal_key).unwrap();
+
+            assert_eq!(hex::encode(&merkle_root), vector.merkle_root);
+            assert_eq!(hex::encode(&tap_tweak_hash(&internal_key, Some(&merkle_root))), vector.tweak);
+            assert_eq!(hex::encode(&output_key), vector.output_key);
+            assert_eq!(Script::p2tr(&output_key).to_address(Network::Mainnet).unwrap().to_string(), vector.address);
+
+            let expected = vector.leaf_hashes.into_iter().zip(vector.control_blocks);
+            for (leaf, (leaf_hash, control_block)) in leaves(&vector.tree).iter().zip(expected) {
+                let built = vector.tree.control_block(&internal_key, leaf).unwrap();
+
+                assert_eq!(hex::encode(&leaf.hash()), leaf_hash);
+                assert_eq!(hex::encode(&built.to_bytes()), control_block);
+                assert_eq!(ControlBlock::parse(&hex::decode(control_block).unwrap()), Ok(built.clone()));
+                assert!(built.verify(&output_key, leaf));
+            }
+        }
+    }
+
+    #[test]
+    fn key_path_only_outputs_tweak_without_a_merkle_root() {
+        // BIP341's first wallet vector, and BIP86's first receiving address
+        let cases = [
+            (
+                "d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d",
+                "53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343",
+            ),
+            (
+                "cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115",
+                "a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+            ),
+        ];
+
+        for (internal_key, output_key) in cases {
+            let (tweaked, _) = tweak_public_key(&bytes32(internal_key), None).unwrap();
+
+            assert_eq!(hex::encode(&tweaked), output_key);
+        }
+    }
+
+    #[test]
+    fn tweaked_secrets_give_the_tweaked_output_key() {
+        let merkle_root = [0x42; 32];
+        for secret in 1..=8 {
+            let private_key = PrivateKey::new(U256::from_u64(secret)).unwrap();
+            let internal_key = private_key.point().x().to_be_bytes();
+
+            for root in [None, Some(&merkle_root)] {
+                let (output_key, odd_y) = tweak_public_key(&internal_key, root).unwrap();
+                let tweaked = tweak_private_key(&private_key, root).unwrap();
+
+                assert_eq!(tweaked.point().x().to_be_bytes(), output_key, "secret {}", secret);
+                assert_eq!(tweaked.point().y().is_odd(), odd_y);
+            }
+        }
+    }
+
+    #[test]
+    fn tweaked_secret_matches_bip341_key_path_vector() {
+        // The first input of BIP341's keyPathSpending vector, whose internal key is the first wallet vector's
+        let private_key =
+            PrivateKey::from_hex("6b973d88838f27366ed61c9ad6367663045cb456e28335c109e30717ae0c6baa").unwrap();
+
+        let tweaked = tweak_private_key(&private_key, None).unwrap();
+
+        assert_eq!(
+            hex::encode(&private_key.point().x().to_be_bytes()),
+            "d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d"
+        );
+        assert_eq!(tweaked.expose_secret_hex(), "2405b971772ad26915c8dcdf10f238753a9b837e5f8e6a86fd7c0cce5b7296d9");
+    }
+
+    #[test]
+    fn control_blocks_only_verify_for_their_own_leaf_and_output() {
+        let vector = &script_tree_vectors()[2];
+        let internal_key = bytes32(vector.internal_key);
+        let (output_key, _) = vector.tree.output_key(&internal_key).unwrap();
+        let [first, second] = leaves(&vector.tree).try_into().unwrap();
+        let control_block = vector.tree.control_block(&internal_key, &first).unwrap();
+
+        let mut flipped_parity = control_block.clone();
+        flipped_parity.leaf_version_parity ^= 0x01;
+        let mut short_path = control_block.clone();
+        short_path.merkle_path.clear();
+        let wrong_version = TapLeaf::new(0xc2, first.script().clone()).unwrap();
+
+        assert!(control_block.verify(&output_key, &first));
+        assert!(!control_block.verify(&output_key, &second));
+        assert!(!control_block.verify(&bytes32(vector.merkle_root), &first));
+        assert!(!control_block.verify(&output_key, &wrong_version));
+        assert!(!flipped_parity.verify(&output_key, &first));
+        assert!(!short_path.verify(&output_key, &first));
+    }
+
+    #[test]
+    fn leaves_at_different_depths_get_paths_of_their_depth() {
+        let [a, b, c] = [0x01, 0x02, 0x03].map(|byte| leaf(TAPSCRIPT_LEAF_VERSION, &format!("01{:02x}", byte)));
+        let nested = TapTree::branch(TapTree::Leaf(b.clone()), TapTree::Leaf(c.clone()));
+        let tree = TapTree::branch(TapTree::Leaf(a.clone()), nested.clone());
+        let internal_key = bytes32("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d");
+        let (output_key, _) = tree.output_key(&internal_key).unwrap();
+
+        assert_eq!(tree.merkle_path(&a), Some(vec![nested.merkle_root()]));
+        assert_eq!(tree.merkle_path(&c), Some(vec![b.hash(), a.hash()]));
+        for leaf in [a, b, c] {
+            assert!(tree.control_block(&internal_key, &leaf).unwrap().verify(&output_key, &leaf));
+        }
+    }
+
+    #[test]
+    fn leaf_versions_and_control_block_sizes_are_checked() {
+        let script = Script::new(vec![Cmd::push(vec![])]);
+        let tree = TapTree::Leaf(TapLeaf::new(TAPSCRIPT_LEAF_VERSION, script.clone()).unwrap());
+        let other_leaf = TapLeaf::new(0xc2, script.clone()).unwrap();
+
+        assert_eq!(TapLeaf::new(0xc1, script.clone()), Err(TaprootError::InvalidLeafVersion(0xc1)));
+        assert_eq!(TapLeaf::new(0x50, script), Err(TaprootError::InvalidLeafVersion(0x50)));
+        assert_eq!(tree.control_block(&[0x01; 32], &other_leaf), Err(TaprootError::LeafNotInTree));
+        assert_eq!(tree.output_key(&[0xff; 32]), Err(TaprootError::InvalidInternalKey));
+        for size in [0, 32, 34, 33 + 32 * 129] {
+            assert_eq!(ControlBlock::parse(&vec![0xc0; size]), Err(TaprootError::ControlBlockSize(size)));
+        }
+        assert_eq!(ControlBlock::parse(&[0xc0; 33 + 32 * 128]).unwrap().merkle_path.len(), 128);
+    }
+}
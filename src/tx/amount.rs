@@ -0,0 +1,316 @@
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Mul, Sub, SubAssign};
+use std::str::FromStr;
+use crate::encoding::encodable::{Decodable, Encodable};
+use crate::encoding::parse_error::ParseError;
+
+// Satoshis in one bitcoin
+pub const COIN: u64 = 100_000_000;
+
+const BTC_DECIMALS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AmountError {
+    // The arithmetic does not fit in 64 bits, or went below zero
+    Overflow,
+    TooLarge(Amount),
+    Negative,
+    // More precise than a satoshi
+    TooPrecise,
+    InvalidFormat(String),
+}
+
+impl Display for AmountError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AmountError::Overflow => write!(f, "Amount arithmetic overflowed."),
+            AmountError::TooLarge(amount) => {
+                write!(f, "Amount {} exceeds the maximum of {}.", amount, Amount::MAX_MONEY)
+            }
+            AmountError::Negative => write!(f, "Amount is negative."),
+            AmountError::TooPrecise => write!(f, "Amount has more precision than a satoshi."),
+            AmountError::InvalidFormat(text) => write!(f, "Invalid bitcoin amount '{}'.", text),
+        }
+    }
+}
+
+impl std::error::Error for AmountError {}
+
+/*
+    A number of satoshis. The operators panic on overflow the way u64's do in debug builds; use the checked
+    methods, or sum into a Result, for amounts that are not already known to be in range.
+
+    Amounts given in BTC are checked against MAX_MONEY, but from_sat and decoding accept any u64: whether
+    an amount read off the wire is in range is for the caller to check, as consensus does for outputs and
+    their sum.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(u64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+    pub const ONE_SAT: Amount = Amount(1);
+    pub const ONE_BTC: Amount = Amount(COIN);
+    // 21 million bitcoin, the most any amount or sum of amounts may be
+    pub const MAX_MONEY: Amount = Amount(21_000_000 * COIN);
+
+    pub const fn from_sat(satoshis: u64) -> Self {
+        Self(satoshis)
+    }
+
+    pub const fn to_sat(self) -> u64 {
+        self.0
+    }
+
+    // Rounds to the nearest satoshi, since a float cannot say whether it was meant to be more precise
+    pub fn from_btc(btc: f64) -> Result<Self, AmountError> {
+        if btc.is_nan() {
+            return Err(AmountError::InvalidFormat(btc.to_string()));
+        }
+        if btc < 0.0 {
+            return Err(AmountError::Negative);
+        }
+
+        let satoshis = (btc * COIN as f64).round();
+        if satoshis >= u64::MAX as f64 {
+            return Err(AmountError::Overflow);
+        }
+
+        Self::from_sat(satoshis as u64).within_max_money()
+    }
+
+    // Exact decimal parsing of a bitcoin amount such as "0.00012345"
+    pub fn from_btc_str(btc: &str) -> Result<Self, AmountError> {
+        let invalid = || AmountError::InvalidFormat(btc.to_string());
+        if btc.starts_with('-') {
+            return Err(AmountError::Negative);
+        }
+
+        let (whole, fraction) = btc.split_once('.').unwrap_or((btc, "0"));
+        let all_digits = |part: &str| !part.is_empty() && part.bytes().all(|byte| byte.is_ascii_digit());
+        if !all_digits(whole) || !all_digits(fraction) {
+            return Err(invalid());
+        }
+
+        // Zeros past the last decimal place do not change the value
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.len() > BTC_DECIMALS {
+            return Err(AmountError::TooPrecise);
+        }
+
+        let whole: u64 = whole.parse().map_err(|_| AmountError::Overflow)?;
+        let fraction: u64 = format!("{:0<width$}", fraction, width = BTC_DECIMALS).parse().map_err(|_| invalid())?;
+        let satoshis = whole.checked_mul(COIN).and_then(|whole| whole.checked_add(fraction));
+
+        Self::from_sat(satoshis.ok_or(AmountError::Overflow)?).within_max_money()
+    }
+
+    // Always with 8 decimal places, as Bitcoin Core's RPC reports amounts
+    pub fn to_btc_string(&self) -> String {
+        format!("{}.{:0width$}", self.0 / COIN, self.0 % COIN, width = BTC_DECIMALS)
+    }
+
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    pub fn checked_mul(self, factor: u64) -> Option<Amount> {
+        self.0.checked_mul(factor).map(Self)
+    }
+
+    pub fn within_max_money(self) -> Result<Amount, AmountError> {
+        match self > Self::MAX_MONEY {
+            true => Err(AmountError::TooLarge(self)),
+            false => Ok(self),
+        }
+    }
+}
+
+impl Display for Amount {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} BTC", self.to_btc_string())
+    }
+}
+
+impl FromStr for Amount {
+    type Err = AmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_btc_str(s)
+    }
+}
+
+impl Add for Amount {
+    type Output = Amount;
+
+    fn add(self, other: Amount) -> Amount {
+        self.checked_add(other).expect("amount addition overflowed")
+    }
+}
+
+impl AddAssign for Amount {
+    fn add_assign(&mut self, other: Amount) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Amount {
+    type Output = Amount;
+
+    fn sub(self, other: Amount) -> Amount {
+        self.checked_sub(other).expect("amount subtraction went below zero")
+    }
+}
+
+impl SubAssign for Amount {
+    fn sub_assign(&mut self, other: Amount) {
+        *self = *self - other;
+    }
+}
+
+impl Mul<u64> for Amount {
+    type Output = Amount;
+
+    fn mul(self, factor: u64) -> Amount {
+        self.checked_mul(factor).expect("amount multiplication overflowed")
+    }
+}
+
+// For amounts known to be in range; panics on overflow like Add
+impl Sum for Amount {
+    fn sum<I: Iterator<Item = Amount>>(iter: I) -> Amount {
+        iter.fold(Amount::ZERO, Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Amount> for Amount {
+    fn sum<I: Iterator<Item = &'a Amount>>(iter: I) -> Amount {
+        iter.copied().sum()
+    }
+}
+
+// A total of untrusted amounts, which fails if it leaves 64 bits or the money supply
+impl Sum<Amount> for Result<Amount, AmountError> {
+    fn sum<I: Iterator<Item = Amount>>(iter: I) -> Self {
+        let mut total = Amount::ZERO;
+        for amount in iter {
+            total = total.checked_add(amount).ok_or(AmountError::Overflow)?.within_max_money()?;
+        }
+
+        Ok(total)
+    }
+}
+
+impl<'a> Sum<&'a Amount> for Result<Amount, AmountError> {
+    fn sum<I: Iterator<Item = &'a Amount>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+impl Decodable for Amount {
+    fn parse(reader: &mut impl Read) -> Result<Self, ParseError> {
+        u64::parse(reader).map(Self)
+    }
+}
+
+impl Encodable for Amount {
+    fn serialize(&self, writer: &mut impl Write) -> Result<(), ParseError> {
+        self.0.serialize(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::encodable::{Decodable, Encodable};
+    use crate::tx::amount::{Amount, AmountError, COIN};
+
+    // What the block subsidy schedule issues in total, less than 21 million because subsidies round down
+    const MAX_SUPPLY_SATS: u64 = 2_099_999_997_690_000;
+
+    #[test]
+    fn btc_strings_parse_exactly() {
+        assert_eq!(Amount::from_btc_str("0.00012345"), Ok(Amount::from_sat(12_345)));
+        assert_eq!("1".parse(), Ok(Amount::ONE_BTC));
+        assert_eq!("0.1".parse(), Ok(Amount::from_sat(10_000_000)));
+        assert_eq!("0.000000010".parse(), Ok(Amount::from_sat(1)));
+        assert_eq!(Amount::from_sat(12_345).to_btc_string(), "0.00012345");
+        assert_eq!(Amount::from_sat(10 * COIN).to_string(), "10.00000000 BTC");
+    }
+
+    #[test]
+    fn max_supply_is_within_max_money() {
+        let subsidies = (0..64).map(|halvings| Amount::from_sat(((50 * COIN) >> halvings) * 210_000));
+
+        let subject: Result<Amount, AmountError> = subsidies.sum();
+
+        assert_eq!(subject, Ok(Amount::from_sat(MAX_SUPPLY_SATS)));
+        assert_eq!(Amount::from_btc_str("20999999.9769"), Ok(Amount::from_sat(MAX_SUPPLY_SATS)));
+        assert_eq!(Amount::from_btc(20999999.9769), Ok(Amount::from_sat(MAX_SUPPLY_SATS)));
+        assert_eq!(Amount::from_sat(MAX_SUPPLY_SATS).to_btc_string(), "20999999.97690000");
+        assert_eq!(Amount::from_btc_str("21000000"), Ok(Amount::MAX_MONEY));
+        assert_eq!(
+            Amount::from_btc_str("21000000.00000001"),
+            Err(AmountError::TooLarge(Amount::MAX_MONEY + Amount::ONE_SAT))
+        );
+    }
+
+    #[test]
+    fn sub_satoshi_and_malformed_strings_are_rejected() {
+        assert_eq!(Amount::from_btc_str("0.000000001"), Err(AmountError::TooPrecise));
+        assert_eq!(Amount::from_btc_str("0.123456789"), Err(AmountError::TooPrecise));
+        assert_eq!(Amount::from_btc_str("-1"), Err(AmountError::Negative));
+        assert_eq!(Amount::from_btc_str("99999999999999"), Err(AmountError::Overflow));
+        for text in ["", ".", "1.", ".5", "+1", "1e3", "1.2.3", " 1", "one"] {
+            assert_eq!(Amount::from_btc_str(text), Err(AmountError::InvalidFormat(text.to_string())));
+        }
+    }
+
+    #[test]
+    fn float_btc_rounds_to_the_nearest_satoshi() {
+        // 0.1 + 0.2 is 0.30000000000000004 as a float
+        assert_eq!(Amount::from_btc(0.1 + 0.2), Ok(Amount::from_sat(30_000_000)));
+        assert_eq!(Amount::from_btc(0.00012345), Ok(Amount::from_sat(12_345)));
+        assert_eq!(Amount::from_btc(-0.1), Err(AmountError::Negative));
+        assert!(matches!(Amount::from_btc(f64::NAN), Err(AmountError::InvalidFormat(_))));
+        assert_eq!(Amount::from_btc(f64::INFINITY), Err(AmountError::Overflow));
+    }
+
+    #[test]
+    fn checked_arithmetic() {
+        let max = Amount::from_sat(u64::MAX);
+
+        assert_eq!(Amount::ONE_BTC.checked_add(Amount::ONE_SAT), Some(Amount::from_sat(COIN + 1)));
+        assert_eq!(max.checked_add(Amount::ONE_SAT), None);
+        assert_eq!(Amount::ZERO.checked_sub(Amount::ONE_SAT), None);
+        assert_eq!(Amount::ONE_BTC - Amount::ONE_SAT, Amount::from_sat(COIN - 1));
+        assert_eq!(Amount::ONE_SAT * 294, Amount::from_sat(294));
+    }
+
+    #[test]
+    fn summation_fails_on_overflow_and_past_max_money() {
+        let overflowing = [Amount::ONE_SAT, Amount::from_sat(u64::MAX)];
+        let past_max_money = [Amount::MAX_MONEY, Amount::ONE_SAT, Amount::ONE_SAT];
+
+        assert_eq!(overflowing.iter().sum::<Result<Amount, AmountError>>(), Err(AmountError::Overflow));
+        assert_eq!(
+            past_max_money.iter().sum::<Result<Amount, AmountError>>(),
+            Err(AmountError::TooLarge(Amount::MAX_MONEY + Amount::ONE_SAT))
+        );
+        assert_eq!(past_max_money[..1].iter().sum::<Result<Amount, AmountError>>(), Ok(Amount::MAX_MONEY));
+        assert_eq!(past_max_money.iter().sum::<Amount>(), Amount::MAX_MONEY + Amount::from_sat(2));
+    }
+
+    #[test]
+    fn wire_form_is_eight_bytes_little_endian() {
+        let subject = Amount::from_sat(0x0102030405060708);
+
+        assert_eq!(subject.serialize_to_vec(), vec![8, 7, 6, 5, 4, 3, 2, 1]);
+        assert_eq!(Amount::parse_exact(&subject.serialize_to_vec()), Ok(subject));
+    }
+}
@@ -0,0 +1,413 @@
+use std::fmt::{Display, Formatter};
+use crate::tx::amount::{Amount, AmountError};
+use crate::tx::outpoint::OutPoint;
+use crate::tx::tx_builder::signed_input_weight;
+use crate::tx::tx_out::TxOut;
+
+// How many branches branch and bound explores before giving up, the same bound Bitcoin Core uses
+const BNB_TOTAL_TRIES: usize = 100_000;
+
+// A P2WPKH change output is 31 bytes, and spending it later costs a 68 vbyte input
+const P2WPKH_OUTPUT_WEIGHT: u64 = 31 * 4;
+const P2WPKH_INPUT_WEIGHT: u64 = 272;
+// The dust threshold of a P2WPKH output at the default dust relay fee of 3 sat/vB
+const P2WPKH_DUST_LIMIT: Amount = Amount::from_sat(294);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Utxo {
+    pub outpoint: OutPoint,
+    pub txout: TxOut,
+}
+
+impl Utxo {
+    pub fn new(outpoint: OutPoint, txout: TxOut) -> Self {
+        Self { outpoint, txout }
+    }
+}
+
+/*
+    The target is what the inputs have to pay for besides themselves: the outputs plus the fee for the
+    transaction without any inputs. Each input then has to cover its own marginal cost at the fee rate,
+    so a candidate only counts for its effective value, the amount minus that cost.
+
+    The change fields describe the output added when there is enough left over. By default it is a P2WPKH
+    output, and the long-term fee rate, used to tell whether spending more inputs now is wasteful, is the
+    same as the current one.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelectionParams {
+    pub target: Amount,
+    pub fee_rate: u64,
+    pub long_term_fee_rate: u64,
+    pub change_output_weight: u64,
+    pub change_input_weight: u64,
+    pub dust_limit: Amount,
+}
+
+impl SelectionParams {
+    pub fn new(target: Amount, fee_rate: u64) -> Self {
+        Self {
+            target,
+            fee_rate,
+            long_term_fee_rate: fee_rate,
+            change_output_weight: P2WPKH_OUTPUT_WEIGHT,
+            change_input_weight: P2WPKH_INPUT_WEIGHT,
+            dust_limit: P2WPKH_DUST_LIMIT,
+        }
+    }
+
+    // What adding the change output costs now
+    pub fn change_output_fee(&self) -> Result<Amount, AmountError> {
+        fee_for_weight(self.change_output_weight, self.fee_rate)
+    }
+
+    // What creating the change output and spending it later costs altogether
+    pub fn cost_of_change(&self) -> Result<Amount, AmountError> {
+        let spending_fee = fee_for_weight(self.change_input_weight, self.long_term_fee_rate)?;
+
+        self.change_output_fee()?.checked_add(spending_fee).ok_or(AmountError::Overflow)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    pub selected: Vec<Utxo>,
+    // Zero when the transaction gets no change output
+    pub change: Amount,
+    // The fee the inputs add on top of the one in the target, including any excess too small to be change
+    pub fee: Amount,
+    // The waste metric of the selection, in satoshis
+    pub waste: i64,
+}
+
+impl Selection {
+    pub fn outpoints(&self) -> Vec<OutPoint> {
+        self.selected.iter().map(|utxo| utxo.outpoint).collect()
+    }
+
+    pub fn has_change(&self) -> bool {
+        self.change > Amount::ZERO
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectionError {
+    // The position of a candidate whose script_pubkey type has no size estimate
+    UnsupportedInput(usize),
+    // A fee rate or candidate amounts too large to add up
+    Amount(AmountError),
+    // Effective values: what the candidates are worth after paying for themselves
+    InsufficientFunds { available: Amount, required: Amount },
+}
+
+impl Display for SelectionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SelectionError::UnsupportedInput(index) => {
+                write!(f, "Cannot estimate the size of spending candidate {}.", index)
+            }
+            SelectionError::Amount(error) => write!(f, "Invalid amount: {}", error),
+            SelectionError::InsufficientFunds { available, required } => write!(
+                f,
+                "The candidates are worth {} after fees but {} are required.",
+                available, required
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SelectionError {}
+
+impl From<AmountError> for SelectionError {
+    fn from(error: AmountError) -> Self {
+        SelectionError::Amount(error)
+    }
+}
+
+// A candidate with the costs of spending it worked out
+#[derive(Debug, Clone)]
+struct Candidate {
+    utxo: Utxo,
+    effective_value: Amount,
+    // Spending it now minus spending it at the long-term fee rate
+    waste: i64,
+}
+
+// Branch and bound first, falling back to largest first when no selection avoids change
+pub fn select_coins(candidates: &[Utxo], params: &SelectionParams) -> Result<Selection, SelectionError> {
+    match branch_and_bound(candidates, params)? {
+        Some(selection) => Ok(selection),
+        None => largest_first(candidates, params),
+    }
+}
+
+// Adds candidates from the most valuable down until the target is met
+pub fn largest_first(candidates: &[Utxo], params: &SelectionParams) -> Result<Selection, SelectionError> {
+    let pool = effective_pool(candidates, params)?;
+
+    let mut selected = Vec::new();
+    let mut value = Amount::ZERO;
+    for candidate in pool {
+        if value >= params.target {
+            break;
+        }
+        value = value.checked_add(candidate.effective_value).ok_or(AmountError::Overflow)?;
+        selected.push(candidate);
+    }
+
+    if value < params.target {
+        return Err(SelectionError::InsufficientFunds { available: value, required: params.target });
+    }
+
+    selection_with_change(selected, params)
+}
+
+/*
+    A depth-first search for a set of candidates whose effective value lands between the target and the
+    target plus the cost of change, so that the excess can go to the fee instead of a change output.
+    Among the sets found it keeps the one with the least waste. Returns None when there is no such set
+    within the try limit.
+ */
+pub fn branch_and_bound(candidates: &[Utxo], params: &SelectionParams) -> Result<Option<Selection>, SelectionError> {
+    let pool = effective_pool(candidates, params)?;
+    let target = params.target;
+    let upper_bound = target.checked_add(params.cost_of_change()?).unwrap_or(Amount::from_sat(u64::MAX));
+    // Each extra input costs more now than later, so a branch with more waste than the best will not improve
+    let fee_rate_is_high = params.fee_rate > params.long_term_fee_rate;
+
+    // Every partial sum below is bounded by this total, so once it fits the search cannot overflow
+    let mut available = effective_value(&pool)?;
+    if available < target {
+        return Err(SelectionError::InsufficientFunds { available, required: target });
+    }
+
+    let mut current: Vec<usize> = Vec::new();
+    let mut value = Amount::ZERO;
+    let mut waste: i64 = 0;
+    let mut best: Option<(Vec<usize>, i64)> = None;
+
+    let mut index = 0;
+    for _ in 0..BNB_TOTAL_TRIES {
+        let best_waste = best.as_ref().map_or(i64::MAX, |(_, waste)| *waste);
+
+        let backtrack = if value + available < target
+            || value > upper_bound
+            || (waste > best_waste && fee_rate_is_high)
+        {
+            true
+        } else if value >= target {
+            let total_waste = waste + (value - target).to_sat() as i64;
+            if total_waste <= best_waste {
+                best = Some((current.clone(), total_waste));
+            }
+            true
+        } else {
+            false
+        };
+
+        if backtrack {
+            let Some(&last) = current.last() else {
+                break;
+            };
+            // Put back what was skipped after the last included candidate, then try the branch without it
+            available = available.checked_add(effective_value(&pool[(last + 1)..index])?).ok_or(AmountError::Overflow)?;
+            value -= pool[last].effective_value;
+            waste -= pool[last].waste;
+            current.pop();
+            index = last;
+        } else {
+            let candidate = &pool[index];
+            available -= candidate.effective_value;
+            // Excluding one candidate and including an identical one next is a branch already explored
+            let duplicates_excluded = !current.is_empty()
+                && current.last() != Some(&(index - 1))
+                && candidate.effective_value == pool[index - 1].effective_value
+                && candidate.waste == pool[index - 1].waste;
+            if !duplicates_excluded {
+                current.push(index);
+                value += candidate.effective_value;
+                waste += candidate.waste;
+            }
+        }
+        index += 1;
+    }
+
+    let Some((indices, _)) = best else {
+        return Ok(None);
+    };
+    let selected: Vec<Candidate> = indices.into_iter().map(|index| pool[index].clone()).collect();
+    let excess = effective_value(&selected)? - target;
+
+    finish_selection(selected, excess, Amount::ZERO, excess.to_sat() as i64).map(Some)
+}
+
+// The candidates worth spending, most valuable first
+fn effective_pool(candidates: &[Utxo], params: &SelectionParams) -> Result<Vec<Candidate>, SelectionError> {
+    let mut pool = Vec::new();
+    for (index, utxo) in candidates.iter().enumerate() {
+        let weight = signed_input_weight(&utxo.txout.script_pubkey).ok_or(SelectionError::UnsupportedInput(index))?;
+        let fee = fee_for_weight(weight, params.fee_rate)?;
+        let long_term_fee = fee_for_weight(weight, params.long_term_fee_rate)?;
+
+        if utxo.txout.amount > fee {
+            pool.push(Candidate {
+                utxo: utxo.clone(),
+                effective_value: utxo.txout.amount - fee,
+                waste: fee.to_sat() as i64 - long_term_fee.to_sat() as i64,
+            });
+        }
+    }
+    pool.sort_by_key(|candidate| std::cmp::Reverse(candidate.effective_value));
+
+    Ok(pool)
+}
+
+// Turns the excess into change when it is worth more than dust after paying for the change output
+fn selection_with_change(selected: Vec<Candidate>, params: &SelectionParams) -> Result<Selection, SelectionError> {
+    let excess = effective_value(&selected)? - params.target;
+    let change_output_fee = params.change_output_fee()?;
+
+    if excess > change_output_fee && excess - change_output_fee >= params.dust_limit {
+        let change = excess - change_output_fee;
+        finish_selection(selected, change_output_fee, change, params.cost_of_change()?.to_sat() as i64)
+    } else {
+        finish_selection(selected, excess, Amount::ZERO, excess.to_sat() as i64)
+    }
+}
+
+// extra_fee is paid on top of what the inputs cost, and extra_waste is the waste that is not down to the inputs
+fn finish_selection(
+    selected: Vec<Candidate>,
+    extra_fee: Amount,
+    change: Amount,
+    extra_waste: i64,
+) -> Result<Selection, SelectionError> {
+    let input_fees = selected
+        .iter()
+        .map(|candidate| candidate.utxo.txout.amount - candidate.effective_value)
+        .sum::<Result<Amount, AmountError>>()?;
+    let input_waste: i64 = selected.iter().map(|candidate| candidate.waste).sum();
+
+    Ok(Selection {
+        selected: selected.into_iter().map(|candidate| candidate.utxo).collect(),
+        change,
+        fee: input_fees.checked_add(extra_fee).ok_or(AmountError::Overflow)?,
+        waste: input_waste + extra_waste,
+    })
+}
+
+fn effective_value(candidates: &[Candidate]) -> Result<Amount, AmountError> {
+    candidates.iter().map(|candidate| candidate.effective_value).sum()
+}
+
+fn fee_for_weight(weight: u64, sat_per_vb: u64) -> Result<Amount, AmountError> {
+    Amount::from_sat(weight.div_ceil(4)).checked_mul(sat_per_vb).ok_or(AmountError::Overflow)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hashing::hash256::Hash256;
+    use crate::tx::amount::{Amount, AmountError};
+    use crate::tx::coin_selection::{
+        branch_and_bound, largest_first, select_coins, SelectionError, SelectionParams, Utxo,
+    };
+    use crate::tx::outpoint::OutPoint;
+    use crate::tx::tx_out::TxOut;
+
+    // A P2WPKH input is 68 vbytes once signed
+    const P2WPKH_INPUT_VSIZE: u64 = 68;
+
+    // P2WPKH outputs of distinct transactions, in the order given
+    fn candidates(amounts: &[u64]) -> Vec<Utxo> {
+        amounts
+            .iter()
+            .enumerate()
+            .map(|(index, amount)| {
+                let mut script_pubkey = vec![0x00, 0x14];
+                script_pubkey.extend([index as u8; 20]);
+                let outpoint = OutPoint::new(Hash256::from_wire_bytes([index as u8 + 1; 32]), 0);
+                Utxo::new(outpoint, TxOut::new(Amount::from_sat(*amount), script_pubkey))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn branch_and_bound_finds_the_exact_match_with_the_least_waste() {
+        // Worth 1, 2, 3 and 4 hundred thousand satoshis after paying for themselves at 5 sat/vB
+        let input_fee = P2WPKH_INPUT_VSIZE * 5;
+        let utxos = candidates(&[100_000 + input_fee, 200_000 + input_fee, 300_000 + input_fee, 400_000 + input_fee]);
+        let params = SelectionParams { long_term_fee_rate: 1, ..SelectionParams::new(Amount::from_sat(600_000), 5) };
+
+        let subject = select_coins(&utxos, &params).unwrap();
+
+        // 4 + 2 beats 3 + 2 + 1 because every input costs more now than it would later
+        assert_eq!(subject.outpoints(), vec![utxos[3].outpoint, utxos[1].outpoint]);
+        assert!(!subject.has_change());
+        assert_eq!(subject.fee, Amount::from_sat(2 * input_fee));
+        assert_eq!(subject.waste, 2 * (input_fee - P2WPKH_INPUT_VSIZE) as i64);
+    }
+
+    #[test]
+    fn largest_first_makes_change_when_there_is_no_exact_match() {
+        let utxos = candidates(&[100_000 + P2WPKH_INPUT_VSIZE, 300_000 + P2WPKH_INPUT_VSIZE]);
+        let params = SelectionParams::new(Amount::from_sat(150_000), 1);
+
+        let subject = select_coins(&utxos, &params).unwrap();
+
+        assert_eq!(branch_and_bound(&utxos, &params), Ok(None));
+        assert_eq!(subject.outpoints(), vec![utxos[1].outpoint]);
+        // The change output is 31 vbytes
+        assert_eq!(subject.change, Amount::from_sat(150_000 - 31));
+        assert_eq!(subject.fee, Amount::from_sat(P2WPKH_INPUT_VSIZE + 31));
+        assert_eq!(subject, largest_first(&utxos, &params).unwrap());
+    }
+
+    #[test]
+    fn excess_below_the_dust_limit_goes_to_the_fee() {
+        let utxos = candidates(&[100_200 + P2WPKH_INPUT_VSIZE]);
+        let params = SelectionParams::new(Amount::from_sat(100_000), 1);
+
+        let subject = select_coins(&utxos, &params).unwrap();
+
+        assert_eq!(params.cost_of_change(), Ok(Amount::from_sat(31 + P2WPKH_INPUT_VSIZE)));
+        assert_eq!(subject.change, Amount::ZERO);
+        assert_eq!(subject.fee, Amount::from_sat(P2WPKH_INPUT_VSIZE + 200));
+    }
+
+    #[test]
+    fn selection_is_impossible_when_the_candidates_cannot_pay_for_themselves() {
+        // The last candidate is worth less than the fee for spending it, so it does not count
+        let utxos = candidates(&[50_000, 30_000, 50]);
+        let params = SelectionParams::new(Amount::from_sat(100_000), 2);
+        let expected = SelectionError::InsufficientFunds {
+            available: Amount::from_sat(80_000 - 2 * 2 * P2WPKH_INPUT_VSIZE),
+            required: Amount::from_sat(100_000),
+        };
+
+        assert_eq!(select_coins(&utxos, &params), Err(expected.clone()));
+        assert_eq!(largest_first(&utxos, &params), Err(expected));
+    }
+
+    #[test]
+    fn candidates_of_unknown_size_are_rejected() {
+        let mut utxos = candidates(&[50_000, 30_000]);
+        utxos[1].txout.script_pubkey = vec![0x6a];
+
+        let params = SelectionParams::new(Amount::from_sat(10_000), 1);
+
+        assert_eq!(select_coins(&utxos, &params), Err(SelectionError::UnsupportedInput(1)));
+    }
+
+    #[test]
+    fn overflowing_fees_and_values_are_errors() {
+        let utxos = candidates(&[50_000, 30_000]);
+        let huge = candidates(&[u64::MAX / 2, u64::MAX / 2, u64::MAX / 2]);
+        let expensive = SelectionParams::new(Amount::from_sat(10_000), u64::MAX / 2);
+        let params = SelectionParams::new(Amount::from_sat(10_000), 1);
+
+        assert_eq!(expensive.cost_of_change(), Err(AmountError::Overflow));
+        assert_eq!(select_coins(&utxos, &expensive), Err(SelectionError::Amount(AmountError::Overflow)));
+        assert!(matches!(select_coins(&huge, &params), Err(SelectionError::Amount(AmountError::TooLarge(_)))));
+        assert!(matches!(largest_first(&huge, &params), Err(SelectionError::Amount(_))));
+    }
+}
@@ -0,0 +1,35 @@
+// Transactions the tests of more than one module parse, sign or verify
+
+// The Programming Bitcoin chapter 5 transaction, mainnet, with one P2PKH input and two P2PKH outputs
+pub const TXID: &str = "452c629d67e41baec3ac6f04fe744b4b9617f8f859c63b3002f8684e7a4fee03";
+pub const TX_HEX: &str = "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045\
+                          022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb3\
+                          5d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b81\
+                          38bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566c\
+                          daf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600";
+// The script_pubkey of the output its only input spends
+pub const PREV_SCRIPT_PUBKEY: &str = "76a914a802fc56c704ce87c42d7c92eb75e7896bdc41ae88ac";
+
+// Mainnet ee51510d7bbabe28052038d1deb10c03ec74f06a79e21913c6fcf48d56217c87, which spends four P2PKH outputs
+pub const FOUR_INPUT_TX_HEX: &str = "010000000456919960ac691763688d3d3bcea9ad6ecaf875df5339e148a1fc61c6ed7a069e010000006a473044\
+                                     02204585bcdef85e6b1c6af5c2669d4830ff86e42dd205c0e089bc2a821657e951c002201024a10366077f87d6\
+                                     bce1f7100ad8cfa8a064b39d4e8fe4ea13a7b71aa8180f012102f0da57e85eec2934a82a585ea337ce2f4998b5\
+                                     0ae699dd79f5880e253dafafb7feffffffeb8f51f4038dc17e6313cf831d4f02281c2a468bde0fafd37f1bf882\
+                                     729e7fd3000000006a47304402207899531a52d59a6de200179928ca900254a36b8dff8bb75f5f5d71b1cdc261\
+                                     25022008b422690b8461cb52c3cc30330b23d574351872b7c361e9aae3649071c1a7160121035d5c93d9ac9688\
+                                     1f19ba1f686f15f009ded7c62efe85a872e6a19b43c15a2937feffffff567bf40595119d1bb8a3037c356efd56\
+                                     170b64cbcc160fb028fa10704b45d775000000006a47304402204c7c7818424c7f7911da6cddc59655a70af1cb\
+                                     5eaf17c69dadbfc74ffa0b662f02207599e08bc8023693ad4e9527dc42c34210f7a7d1d1ddfc8492b654a11e76\
+                                     20a0012102158b46fbdff65d0172b7989aec8850aa0dae49abfb84c81ae6e5b251a58ace5cfeffffffd63a5e6c\
+                                     16e620f86f375925b21cabaf736c779f88fd04dcad51d26690f7f345010000006a47304402200633ea0d3314be\
+                                     a0d95b3cd8dadb2ef79ea8331ffe1e61f762c0f6daea0fabde022029f23b3e9c30f080446150b2385202875163\
+                                     5dcee2be669c2a1686a4b5edf304012103ffd6f4a67e94aba353a00882e563ff2722eb4cff0ad6006e86ee20df\
+                                     e7520d55feffffff0251430f00000000001976a914ab0c0b2e98b1ab6dbf67d4750b0a56244948a87988ac005a\
+                                     6202000000001976a9143c82d7df364eb6c75be8c80df2b3eda8db57397088ac46430600";
+
+// A P2WPKH script_pubkey whose key hash is the byte repeated, for outputs nobody has to sign for
+pub fn p2wpkh(key_hash_byte: u8) -> Vec<u8> {
+    let mut script = vec![0x00, 0x14];
+    script.extend([key_hash_byte; 20]);
+    script
+}
@@ -0,0 +1,108 @@
+// nLockTime values below this are block heights, values at or above it are Unix timestamps
+pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+// An input with this sequence does not let the transaction's locktime take effect
+pub const SEQUENCE_FINAL: u32 = 0xffffffff;
+
+// BIP125: a transaction opts in to replacement if any input's sequence is below this
+pub const SEQUENCE_RBF_THRESHOLD: u32 = 0xfffffffe;
+
+/*
+    BIP68 relative locktime bits of nSequence:
+
+    bit 31      set to disable the relative locktime
+    bit 22      set for units of 512 seconds, clear for blocks
+    bits 0-15   the locktime value
+ */
+pub const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+pub const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+pub const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000ffff;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockTime {
+    BlockHeight(u32),
+    Timestamp(u32),
+}
+
+impl LockTime {
+    pub fn from_consensus(value: u32) -> Self {
+        match value < LOCKTIME_THRESHOLD {
+            true => LockTime::BlockHeight(value),
+            false => LockTime::Timestamp(value),
+        }
+    }
+
+    pub fn to_consensus(&self) -> u32 {
+        match self {
+            LockTime::BlockHeight(value) | LockTime::Timestamp(value) => *value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelativeLockTime {
+    Blocks(u16),
+    // In units of 512 seconds
+    Time(u16),
+}
+
+impl RelativeLockTime {
+    // None when the disable flag is set, in which case the sequence carries no relative locktime
+    pub fn from_sequence(sequence: u32) -> Option<Self> {
+        if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return None;
+        }
+
+        let value = (sequence & SEQUENCE_LOCKTIME_MASK) as u16;
+        match sequence & SEQUENCE_LOCKTIME_TYPE_FLAG != 0 {
+            true => Some(RelativeLockTime::Time(value)),
+            false => Some(RelativeLockTime::Blocks(value)),
+        }
+    }
+
+    pub fn to_sequence(&self) -> u32 {
+        match self {
+            RelativeLockTime::Blocks(blocks) => *blocks as u32,
+            RelativeLockTime::Time(units) => SEQUENCE_LOCKTIME_TYPE_FLAG | *units as u32,
+        }
+    }
+
+    pub fn seconds(&self) -> Option<u32> {
+        match self {
+            RelativeLockTime::Blocks(_) => None,
+            RelativeLockTime::Time(units) => Some(*units as u32 * 512),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tx::locktime::{LockTime, RelativeLockTime};
+
+    #[test]
+    fn locktime_threshold_separates_heights_from_timestamps() {
+        assert_eq!(LockTime::from_consensus(0), LockTime::BlockHeight(0));
+        assert_eq!(LockTime::from_consensus(499_999_999), LockTime::BlockHeight(499_999_999));
+        assert_eq!(LockTime::from_consensus(500_000_000), LockTime::Timestamp(500_000_000));
+        assert_eq!(LockTime::Timestamp(1_700_000_000).to_consensus(), 1_700_000_000);
+    }
+
+    #[test]
+    fn bip68_sequences_decode() {
+        assert_eq!(RelativeLockTime::from_sequence(0xffffffff), None);
+        assert_eq!(RelativeLockTime::from_sequence(0x80000090), None);
+        assert_eq!(RelativeLockTime::from_sequence(144), Some(RelativeLockTime::Blocks(144)));
+        assert_eq!(RelativeLockTime::from_sequence(0x00400007), Some(RelativeLockTime::Time(7)));
+        // Bits outside the flags and the value are ignored
+        assert_eq!(RelativeLockTime::from_sequence(0x7fbf0005), Some(RelativeLockTime::Blocks(5)));
+    }
+
+    #[test]
+    fn relative_locktimes_round_trip_through_sequence() {
+        for locktime in [RelativeLockTime::Blocks(0), RelativeLockTime::Blocks(65535), RelativeLockTime::Time(300)] {
+            assert_eq!(RelativeLockTime::from_sequence(locktime.to_sequence()), Some(locktime));
+        }
+        assert_eq!(RelativeLockTime::Time(2).seconds(), Some(1024));
+        assert_eq!(RelativeLockTime::Blocks(2).seconds(), None);
+    }
+}
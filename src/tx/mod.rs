@@ -0,0 +1,17 @@
+pub mod amount;
+pub mod coin_selection;
+#[cfg(test)]
+pub(crate) mod fixtures;
+pub mod locktime;
+pub mod outpoint;
+pub mod policy;
+pub mod psbt;
+pub mod rbf;
+pub mod sighash;
+pub mod signing;
+pub mod transaction;
+pub mod tx_builder;
+pub mod tx_fetcher;
+pub mod tx_in;
+pub mod tx_out;
+pub mod witness;
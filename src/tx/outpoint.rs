@@ -0,0 +1,192 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+use std::str::FromStr;
+use crate::encoding::encodable::{Decodable, Encodable};
+use crate::encoding::parse_error::ParseError;
+use crate::hashing::hash256::{Hash256, Hash256Error};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutPointError {
+    MissingSeparator,
+    Txid(Hash256Error),
+    InvalidVout(String),
+}
+
+impl Display for OutPointError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutPointError::MissingSeparator => write!(f, "Outpoint must be written as txid:vout."),
+            OutPointError::Txid(error) => write!(f, "Invalid outpoint txid: {}", error),
+            OutPointError::InvalidVout(vout) => write!(f, "Invalid output index '{}'.", vout),
+        }
+    }
+}
+
+impl std::error::Error for OutPointError {}
+
+impl From<Hash256Error> for OutPointError {
+    fn from(error: Hash256Error) -> Self {
+        OutPointError::Txid(error)
+    }
+}
+
+/*
+    Wire format:
+
+    txid  32 bytes, in wire order (the reverse of how txids are displayed)
+    vout  4 bytes little-endian
+
+    Ordering follows BIP69: by the txid as it is displayed, then by vout.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct OutPoint {
+    pub txid: Hash256,
+    pub vout: u32,
+}
+
+impl OutPoint {
+    pub fn new(txid: Hash256, vout: u32) -> Self {
+        Self { txid, vout }
+    }
+
+    // What a coinbase input spends: an all-zero txid and index 0xffffffff
+    pub fn null() -> Self {
+        Self::new(Hash256::default(), u32::MAX)
+    }
+
+    pub fn is_null(&self) -> bool {
+        *self == Self::null()
+    }
+}
+
+impl Ord for OutPoint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.txid
+            .to_display_bytes()
+            .cmp(&other.txid.to_display_bytes())
+            .then(self.vout.cmp(&other.vout))
+    }
+}
+
+impl PartialOrd for OutPoint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for OutPoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.txid, self.vout)
+    }
+}
+
+impl FromStr for OutPoint {
+    type Err = OutPointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (txid, vout) = s.split_once(':').ok_or(OutPointError::MissingSeparator)?;
+        // u32's parser would also take a leading '+'
+        if !vout.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(OutPointError::InvalidVout(vout.to_string()));
+        }
+        let vout = vout.parse().map_err(|_| OutPointError::InvalidVout(vout.to_string()))?;
+
+        Ok(Self::new(Hash256::from_str(txid)?, vout))
+    }
+}
+
+impl Decodable for OutPoint {
+    fn parse(reader: &mut impl Read) -> Result<Self, ParseError> {
+        let parse_fields = |reader: &mut _| -> Result<Self, ParseError> {
+            Ok(Self {
+                txid: Hash256::parse(reader)?,
+                vout: u32::parse(reader)?,
+            })
+        };
+
+        parse_fields(reader).map_err(|error| error.in_type("OutPoint"))
+    }
+}
+
+impl Encodable for OutPoint {
+    fn serialize(&self, writer: &mut impl Write) -> Result<(), ParseError> {
+        self.txid.serialize(writer)?;
+        self.vout.serialize(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use crate::encoding::encodable::{Decodable, Encodable};
+    use crate::encoding::hex;
+    use crate::hashing::hash256::Hash256;
+    use crate::tx::outpoint::{OutPoint, OutPointError};
+
+    // The outpoint spent by the Programming Bitcoin chapter 5 transaction
+    const TXID: &str = "d1c789a9c60383bf715f3f6ad9d14b91fe55f3deb369fe5d9280cb1a01793f81";
+
+    #[test]
+    fn textual_form_round_trips() {
+        let text = format!("{}:1", TXID);
+
+        let subject = OutPoint::from_str(&text).unwrap();
+
+        assert_eq!(subject, OutPoint::new(Hash256::from_str(TXID).unwrap(), 1));
+        assert_eq!(subject.to_string(), text);
+    }
+
+    #[test]
+    fn wire_form_is_the_txid_wire_bytes_then_vout() {
+        let subject = OutPoint::new(Hash256::from_str(TXID).unwrap(), 0);
+        let bytes = subject.serialize_to_vec();
+
+        assert_eq!(
+            &hex::encode(&bytes),
+            "813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d100000000"
+        );
+        assert_eq!(OutPoint::parse_exact(&bytes), Ok(subject));
+    }
+
+    #[test]
+    fn null_outpoint_round_trips() {
+        let text = format!("{}:4294967295", "0".repeat(64));
+
+        let subject = OutPoint::from_str(&text).unwrap();
+
+        assert!(subject.is_null());
+        assert_eq!(subject, OutPoint::null());
+        assert_eq!(subject.to_string(), text);
+        assert_eq!(OutPoint::parse_exact(&subject.serialize_to_vec()), Ok(subject));
+        assert!(!OutPoint::new(Hash256::default(), 0).is_null());
+    }
+
+    #[test]
+    fn inputs_sort_per_bip69() {
+        // Displayed txids sort lexicographically even though their wire bytes would order differently
+        let outpoint = |txid: &str, vout| OutPoint::new(Hash256::from_str(txid).unwrap(), vout);
+        let first = outpoint("0e53ec5dfb2cb8a71fec32dc9a634a35b7e24799295ddd5278217822e0b31f57", 0);
+        let second = outpoint("26aa6e6d8b9e49bb0630aac301db6757c02e3619feb4ee0eea81eb1672947024", 1);
+        let third = outpoint("26aa6e6d8b9e49bb0630aac301db6757c02e3619feb4ee0eea81eb1672947024", 2);
+        let fourth = outpoint("f0a130a84912d03c1d284974f563c5949ac13f8342b8112edff52971599e6a45", 0);
+        let mut subject = vec![fourth, third, first, second];
+
+        subject.sort();
+
+        assert_eq!(subject, vec![first, second, third, fourth]);
+        assert!(first.txid.to_wire_bytes() > fourth.txid.to_wire_bytes());
+    }
+
+    #[test]
+    fn malformed_outpoints_are_rejected() {
+        assert_eq!(OutPoint::from_str(TXID), Err(OutPointError::MissingSeparator));
+        assert!(matches!(OutPoint::from_str(&format!("{}:1", &TXID[2..])), Err(OutPointError::Txid(_))));
+        for vout in ["", "-1", "+1", "4294967296", "x"] {
+            assert_eq!(
+                OutPoint::from_str(&format!("{}:{}", TXID, vout)),
+                Err(OutPointError::InvalidVout(vout.to_string()))
+            );
+        }
+    }
+}
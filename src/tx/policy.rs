@@ -0,0 +1,260 @@
+use std::fmt::{Display, Formatter};
+use crate::encoding::encodable::Encodable;
+use crate::script::interpreter::MAX_SCRIPT_SIZE;
+use crate::script::opcodes::OP_RETURN;
+use crate::script::standard::ScriptType;
+use crate::script::Script;
+use crate::tx::amount::Amount;
+use crate::tx::transaction::Tx;
+use crate::tx::tx_out::TxOut;
+
+// Bitcoin Core's relay policy defaults
+pub const DUST_RELAY_FEE_RATE: u64 = 3;
+pub const MAX_STANDARD_TX_WEIGHT: u64 = 400_000;
+pub const MAX_STANDARD_TX_VERSION: u32 = 3;
+pub const MAX_STANDARD_SCRIPT_SIG_SIZE: usize = 1650;
+// OP_RETURN followed by an 80-byte push
+pub const MAX_OP_RETURN_RELAY: usize = 83;
+
+// Each variant carries the position of the offending input or output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NonStandardReason {
+    Version(u32),
+    // The weight of the transaction
+    TxSize(u64),
+    ScriptSigSize(usize),
+    ScriptSigNotPushOnly(usize),
+    ScriptPubKey(usize),
+    BareMultisig(usize),
+    MultiOpReturn,
+    Dust(usize),
+}
+
+impl Display for NonStandardReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NonStandardReason::Version(version) => write!(f, "Version {} is not standard.", version),
+            NonStandardReason::TxSize(weight) => {
+                write!(f, "Weight {} is above the standard {}.", weight, MAX_STANDARD_TX_WEIGHT)
+            }
+            NonStandardReason::ScriptSigSize(index) => {
+                write!(f, "The script_sig of input {} is longer than {} bytes.", index, MAX_STANDARD_SCRIPT_SIG_SIZE)
+            }
+            NonStandardReason::ScriptSigNotPushOnly(index) => {
+                write!(f, "The script_sig of input {} does more than push data.", index)
+            }
+            NonStandardReason::ScriptPubKey(index) => write!(f, "Output {} has a non-standard script_pubkey.", index),
+            NonStandardReason::BareMultisig(index) => write!(f, "Output {} is bare multisig.", index),
+            NonStandardReason::MultiOpReturn => write!(f, "More than one output is OP_RETURN."),
+            NonStandardReason::Dust(index) => write!(f, "Output {} is dust.", index),
+        }
+    }
+}
+
+impl std::error::Error for NonStandardReason {}
+
+impl TxOut {
+    /*
+        What it costs at the relay fee rate to create this output and later spend it, following Bitcoin
+        Core's GetDustThreshold: the serialized output plus a 148-byte input, whose signature data counts
+        a quarter when the output is a witness program. Outputs that can never be spent, starting with
+        OP_RETURN or over MAX_SCRIPT_SIZE, have no threshold.
+     */
+    pub fn dust_threshold(&self, relay_fee_rate: u64) -> Amount {
+        if self.script_pubkey.first() == Some(&OP_RETURN) || self.script_pubkey.len() > MAX_SCRIPT_SIZE {
+            return Amount::ZERO;
+        }
+
+        let script = Script::from_bytes(&self.script_pubkey);
+        let is_witness_program = script.is_ok_and(|script| script.witness_program().is_some());
+        let spending_size = match is_witness_program {
+            true => 32 + 4 + 1 + 107 / 4 + 4,
+            false => 32 + 4 + 1 + 107 + 4,
+        };
+
+        Amount::from_sat((self.serialize_to_vec().len() as u64 + spending_size) * relay_fee_rate)
+    }
+
+    pub fn is_dust(&self, relay_fee_rate: u64) -> bool {
+        self.amount < self.dust_threshold(relay_fee_rate)
+    }
+}
+
+impl Tx {
+    // Bitcoin Core's IsStandardTx with the default policy, which includes not relaying bare multisig
+    pub fn check_standard(&self) -> Result<(), NonStandardReason> {
+        if !(1..=MAX_STANDARD_TX_VERSION).contains(&self.version) {
+            return Err(NonStandardReason::Version(self.version));
+        }
+        let weight = self.weight();
+        if weight > MAX_STANDARD_TX_WEIGHT {
+            return Err(NonStandardReason::TxSize(weight));
+        }
+
+        for (index, input) in self.inputs.iter().enumerate() {
+            if input.script_sig.len() > MAX_STANDARD_SCRIPT_SIG_SIZE {
+                return Err(NonStandardReason::ScriptSigSize(index));
+            }
+            // Like Bitcoin Core, a push that runs past the end does not count as push only
+            if !Script::from_bytes(&input.script_sig).is_ok_and(|script| script.is_push_only()) {
+                return Err(NonStandardReason::ScriptSigNotPushOnly(index));
+            }
+        }
+
+        let mut op_returns = 0;
+        for (index, output) in self.outputs.iter().enumerate() {
+            match output_kind(&output.script_pubkey) {
+                OutputKind::NonStandard => return Err(NonStandardReason::ScriptPubKey(index)),
+                OutputKind::BareMultisig => return Err(NonStandardReason::BareMultisig(index)),
+                OutputKind::OpReturn => op_returns += 1,
+                OutputKind::Standard => {
+                    if output.is_dust(DUST_RELAY_FEE_RATE) {
+                        return Err(NonStandardReason::Dust(index));
+                    }
+                }
+            }
+        }
+        if op_returns > 1 {
+            return Err(NonStandardReason::MultiOpReturn);
+        }
+
+        Ok(())
+    }
+}
+
+enum OutputKind {
+    Standard,
+    BareMultisig,
+    OpReturn,
+    NonStandard,
+}
+
+// The type Bitcoin Core's Solver gives the script, narrowed to what its IsStandard accepts
+fn output_kind(script: &[u8]) -> OutputKind {
+    let Ok(script_type) = Script::from_bytes(script).map(|script| script.classify()) else {
+        return OutputKind::NonStandard;
+    };
+
+    match script_type {
+        ScriptType::NonStandard => OutputKind::NonStandard,
+        ScriptType::OpReturn if script.len() > MAX_OP_RETURN_RELAY => OutputKind::NonStandard,
+        ScriptType::OpReturn => OutputKind::OpReturn,
+        ScriptType::BareMultisig { n: 1..=3, .. } => OutputKind::BareMultisig,
+        ScriptType::BareMultisig { .. } => OutputKind::NonStandard,
+        // Future witness versions are standard so that they can be soft forked in
+        _ => OutputKind::Standard,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hashing::hash256::Hash256;
+    use crate::tx::amount::Amount;
+    use crate::tx::outpoint::OutPoint;
+    use crate::tx::policy::{NonStandardReason, DUST_RELAY_FEE_RATE};
+    use crate::tx::transaction::Tx;
+    use crate::tx::tx_in::TxIn;
+    use crate::tx::tx_out::TxOut;
+
+    fn p2pkh() -> Vec<u8> {
+        let mut script = vec![0x76, 0xa9, 0x14];
+        script.extend([0x01; 20]);
+        script.extend([0x88, 0xac]);
+        script
+    }
+
+    fn p2wpkh() -> Vec<u8> {
+        let mut script = vec![0x00, 0x14];
+        script.extend([0x02; 20]);
+        script
+    }
+
+    fn paying(outputs: Vec<TxOut>) -> Tx {
+        let input = TxIn::new(OutPoint::new(Hash256::from_wire_bytes([0x03; 32]), 0), vec![], 0xfffffffd);
+
+        Tx::new(2, vec![input], outputs, 0, false)
+    }
+
+    #[test]
+    fn classic_dust_thresholds_at_the_default_relay_fee() {
+        let output = |amount, script_pubkey| TxOut::new(Amount::from_sat(amount), script_pubkey);
+
+        assert_eq!(output(0, p2pkh()).dust_threshold(DUST_RELAY_FEE_RATE), Amount::from_sat(546));
+        assert!(output(545, p2pkh()).is_dust(DUST_RELAY_FEE_RATE));
+        assert!(!output(546, p2pkh()).is_dust(DUST_RELAY_FEE_RATE));
+        assert_eq!(output(0, p2wpkh()).dust_threshold(DUST_RELAY_FEE_RATE), Amount::from_sat(294));
+        assert!(output(293, p2wpkh()).is_dust(DUST_RELAY_FEE_RATE));
+        assert!(!output(294, p2wpkh()).is_dust(DUST_RELAY_FEE_RATE));
+        assert!(!output(0, vec![0x6a, 0x01, 0xff]).is_dust(DUST_RELAY_FEE_RATE));
+    }
+
+    #[test]
+    fn ordinary_payment_is_standard() {
+        let subject = paying(vec![
+            TxOut::new(Amount::from_sat(546), p2pkh()),
+            TxOut::new(Amount::from_sat(294), p2wpkh()),
+            TxOut::new(Amount::ZERO, vec![0x6a, 0x01, 0xff]),
+        ]);
+
+        assert_eq!(subject.check_standard(), Ok(()));
+    }
+
+    #[test]
+    fn bare_multisig_output_is_rejected() {
+        // OP_1 <pubkey> <pubkey> OP_2 OP_CHECKMULTISIG
+        let mut multisig = vec![0x51, 0x21, 0x02];
+        multisig.extend([0x04; 32]);
+        multisig.extend([0x21, 0x03]);
+        multisig.extend([0x05; 32]);
+        multisig.extend([0x52, 0xae]);
+        // Claims three keys but has two
+        let unbalanced = [&multisig[..multisig.len() - 2], &[0x53, 0xae]].concat();
+
+        assert_eq!(
+            paying(vec![TxOut::new(Amount::from_sat(294), p2wpkh()), TxOut::new(Amount::from_sat(10_000), multisig)])
+                .check_standard(),
+            Err(NonStandardReason::BareMultisig(1))
+        );
+        assert_eq!(
+            paying(vec![TxOut::new(Amount::from_sat(10_000), unbalanced)]).check_standard(),
+            Err(NonStandardReason::ScriptPubKey(0))
+        );
+    }
+
+    #[test]
+    fn non_standard_transactions_are_rejected() {
+        let mut version_4 = paying(vec![TxOut::new(Amount::from_sat(1_000), p2wpkh())]);
+        version_4.version = 4;
+        let mut not_push_only = paying(vec![TxOut::new(Amount::from_sat(1_000), p2wpkh())]);
+        not_push_only.inputs[0].script_sig = vec![0x51, 0x76];
+        let mut overrunning_push = paying(vec![TxOut::new(Amount::from_sat(1_000), p2wpkh())]);
+        overrunning_push.inputs[0].script_sig = vec![0x4c, 0x05, 0x00];
+        let mut large_script_sig = paying(vec![TxOut::new(Amount::from_sat(1_000), p2wpkh())]);
+        large_script_sig.inputs[0].script_sig = [vec![0x4d, 0x73, 0x06], vec![0; 1651 - 3]].concat();
+        let huge = paying(vec![TxOut::new(Amount::from_sat(1_000), p2wpkh()); 12_000]);
+        let v0_wrong_length = vec![0x00, 0x15, 0x01].into_iter().chain([0x01; 20]).collect();
+
+        assert_eq!(version_4.check_standard(), Err(NonStandardReason::Version(4)));
+        assert_eq!(not_push_only.check_standard(), Err(NonStandardReason::ScriptSigNotPushOnly(0)));
+        assert_eq!(overrunning_push.check_standard(), Err(NonStandardReason::ScriptSigNotPushOnly(0)));
+        assert_eq!(large_script_sig.check_standard(), Err(NonStandardReason::ScriptSigSize(0)));
+        assert_eq!(huge.check_standard(), Err(NonStandardReason::TxSize(huge.weight())));
+        assert_eq!(
+            paying(vec![TxOut::new(Amount::from_sat(1_000), p2wpkh()), TxOut::new(Amount::from_sat(293), p2wpkh())])
+                .check_standard(),
+            Err(NonStandardReason::Dust(1))
+        );
+        assert_eq!(
+            paying(vec![TxOut::new(Amount::ZERO, vec![0x6a]), TxOut::new(Amount::ZERO, vec![0x6a])]).check_standard(),
+            Err(NonStandardReason::MultiOpReturn)
+        );
+        assert_eq!(
+            paying(vec![TxOut::new(Amount::ZERO, [vec![0x6a, 0x4c, 81], vec![0; 81]].concat())]).check_standard(),
+            Err(NonStandardReason::ScriptPubKey(0))
+        );
+        assert_eq!(
+            paying(vec![TxOut::new(Amount::from_sat(1_000), v0_wrong_length)]).check_standard(),
+            Err(NonStandardReason::ScriptPubKey(0))
+        );
+    }
+}
@@ -0,0 +1,876 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+use crate::bip32::derivation_path::{ChildNumber, DerivationPath};
+use crate::ecc::private_key::PrivateKey;
+use crate::encoding::base64::{self, Base64Error};
+use crate::encoding::encodable::{Decodable, Encodable};
+use crate::encoding::hex;
+use crate::encoding::parse_error::ParseError;
+use crate::encoding::varint::{read_var_bytes, write_var_bytes};
+use crate::hashing::hash160::hash160;
+use crate::script::standard::ScriptType;
+use crate::script::Script;
+use crate::tx::sighash::SighashType;
+use crate::tx::signing::{p2pkh_pubkey, KeyProvider};
+use crate::tx::transaction::{parse_witness, serialize_witness, Tx, TxError};
+use crate::tx::tx_out::TxOut;
+
+pub const PSBT_MAGIC: [u8; 5] = *b"psbt\xff";
+
+const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+const PSBT_GLOBAL_VERSION: u8 = 0xfb;
+
+const PSBT_IN_NON_WITNESS_UTXO: u8 = 0x00;
+const PSBT_IN_WITNESS_UTXO: u8 = 0x01;
+const PSBT_IN_PARTIAL_SIG: u8 = 0x02;
+const PSBT_IN_SIGHASH_TYPE: u8 = 0x03;
+const PSBT_IN_REDEEM_SCRIPT: u8 = 0x04;
+const PSBT_IN_WITNESS_SCRIPT: u8 = 0x05;
+const PSBT_IN_BIP32_DERIVATION: u8 = 0x06;
+const PSBT_IN_FINAL_SCRIPTSIG: u8 = 0x07;
+const PSBT_IN_FINAL_SCRIPTWITNESS: u8 = 0x08;
+
+const PSBT_OUT_REDEEM_SCRIPT: u8 = 0x00;
+const PSBT_OUT_WITNESS_SCRIPT: u8 = 0x01;
+const PSBT_OUT_BIP32_DERIVATION: u8 = 0x02;
+
+// Keys are reported in full, type byte included
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PsbtError {
+    Parse(ParseError),
+    Base64(Base64Error),
+    InvalidMagic,
+    MissingUnsignedTx,
+    UnsupportedVersion(u32),
+    DuplicateKey(Vec<u8>),
+    // A known key type with key data it does not allow
+    InvalidKey(Vec<u8>),
+    InvalidValue(Vec<u8>),
+    // The unsigned transaction's input at this position has a script_sig or witness
+    UnsignedTxHasSignature(usize),
+    // The non-witness UTXO of the input at this position is not the transaction it spends from
+    UtxoMismatch(usize),
+    // The redeem script of the input at this position does not hash to the one its P2SH UTXO pays to
+    RedeemScriptMismatch(usize),
+    Tx(TxError),
+}
+
+impl Display for PsbtError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PsbtError::Parse(error) => write!(f, "Invalid PSBT: {}", error),
+            PsbtError::Base64(error) => write!(f, "Invalid PSBT: {}", error),
+            PsbtError::InvalidMagic => write!(f, "Missing the PSBT magic bytes."),
+            PsbtError::MissingUnsignedTx => write!(f, "PSBT has no unsigned transaction."),
+            PsbtError::UnsupportedVersion(version) => write!(f, "PSBT version {} is not supported.", version),
+            PsbtError::DuplicateKey(key) => write!(f, "Duplicate PSBT key {}.", hex::encode(key)),
+            PsbtError::InvalidKey(key) => write!(f, "Invalid PSBT key {}.", hex::encode(key)),
+            PsbtError::InvalidValue(key) => write!(f, "Invalid value for PSBT key {}.", hex::encode(key)),
+            PsbtError::UnsignedTxHasSignature(index) => {
+                write!(f, "Input {} of the unsigned transaction has a script_sig or witness.", index)
+            }
+            PsbtError::UtxoMismatch(index) => {
+                write!(f, "The non-witness UTXO of input {} is not the transaction it spends.", index)
+            }
+            PsbtError::RedeemScriptMismatch(index) => {
+                write!(f, "The redeem script of input {} is not the one its UTXO pays to.", index)
+            }
+            PsbtError::Tx(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for PsbtError {}
+
+impl From<ParseError> for PsbtError {
+    fn from(error: ParseError) -> Self {
+        PsbtError::Parse(error)
+    }
+}
+
+impl From<Base64Error> for PsbtError {
+    fn from(error: Base64Error) -> Self {
+        PsbtError::Base64(error)
+    }
+}
+
+impl From<TxError> for PsbtError {
+    fn from(error: TxError) -> Self {
+        PsbtError::Tx(error)
+    }
+}
+
+// The master key fingerprint and the path from it, the value of a BIP32 derivation entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySource {
+    pub fingerprint: [u8; 4],
+    pub path: DerivationPath,
+}
+
+impl KeySource {
+    fn parse_value(key: &[u8], value: &[u8]) -> Result<Self, PsbtError> {
+        if value.len() < 4 || !value.len().is_multiple_of(4) {
+            return Err(PsbtError::InvalidValue(key.to_vec()));
+        }
+        let (fingerprint, path) = value.split_at(4);
+        let path: Vec<ChildNumber> = path
+            .chunks_exact(4)
+            .map(|index| ChildNumber::from(u32::from_le_bytes([index[0], index[1], index[2], index[3]])))
+            .collect();
+
+        Ok(Self {
+            fingerprint: fingerprint.try_into().expect("Split at 4 bytes."),
+            path: DerivationPath::from(path),
+        })
+    }
+
+    fn to_value(&self) -> Vec<u8> {
+        let mut value = self.fingerprint.to_vec();
+        for child in &self.path {
+            value.extend(child.to_u32().to_le_bytes());
+        }
+
+        value
+    }
+}
+
+// Public keys are kept as their serialized bytes, compressed or not
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PsbtInput {
+    pub non_witness_utxo: Option<Tx>,
+    pub witness_utxo: Option<TxOut>,
+    pub partial_sigs: BTreeMap<Vec<u8>, Vec<u8>>,
+    pub sighash_type: Option<SighashType>,
+    pub redeem_script: Option<Vec<u8>>,
+    pub witness_script: Option<Vec<u8>>,
+    pub bip32_derivation: BTreeMap<Vec<u8>, KeySource>,
+    pub final_script_sig: Option<Vec<u8>>,
+    pub final_script_witness: Option<Vec<Vec<u8>>>,
+    // Entries of any other type, keyed by the full key, so they survive a round trip
+    pub unknown: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PsbtOutput {
+    pub redeem_script: Option<Vec<u8>>,
+    pub witness_script: Option<Vec<u8>>,
+    pub bip32_derivation: BTreeMap<Vec<u8>, KeySource>,
+    pub unknown: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+/*
+    BIP174 partially signed transaction. The wire format is the magic bytes followed by a global map and
+    then one map per input and per output of the unsigned transaction. Each map is a list of key-value
+    pairs ending in a zero byte, where keys and values are length-prefixed and the first byte of a key is
+    its type.
+
+    Known entries are written in type order followed by the unknown ones in key order, which is the
+    canonical form Bitcoin Core produces.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Psbt {
+    pub unsigned_tx: Tx,
+    pub version: Option<u32>,
+    pub unknown: BTreeMap<Vec<u8>, Vec<u8>>,
+    pub inputs: Vec<PsbtInput>,
+    pub outputs: Vec<PsbtOutput>,
+}
+
+impl Psbt {
+    // The creator role: empty maps for a transaction that has no signatures yet
+    pub fn from_unsigned_tx(mut unsigned_tx: Tx) -> Result<Self, PsbtError> {
+        check_unsigned(&unsigned_tx)?;
+        // It is stored without witnesses, so that is how it reads back
+        unsigned_tx.segwit = false;
+
+        Ok(Self {
+            inputs: vec![PsbtInput::default(); unsigned_tx.inputs.len()],
+            outputs: vec![PsbtOutput::default(); unsigned_tx.outputs.len()],
+            unsigned_tx,
+            version: None,
+            unknown: BTreeMap::new(),
+        })
+    }
+
+    // The testnet flag is recorded on the transactions read, as PSBTs do not say which chain they are for
+    pub fn parse(reader: &mut impl Read, testnet: bool) -> Result<Self, PsbtError> {
+        let mut magic = [0; 5];
+        reader.read_exact(&mut magic).map_err(ParseError::from)?;
+        if magic != PSBT_MAGIC {
+            return Err(PsbtError::InvalidMagic);
+        }
+
+        let mut unsigned_tx = None;
+        let mut version = None;
+        let mut unknown = BTreeMap::new();
+        for (key, value) in read_map(reader)? {
+            match (key[0], &key[1..]) {
+                (PSBT_GLOBAL_UNSIGNED_TX, []) => {
+                    let tx = Tx::parse_exact(&value, testnet).map_err(|_| PsbtError::InvalidValue(key))?;
+                    check_unsigned(&tx)?;
+                    unsigned_tx = Some(tx);
+                }
+                (PSBT_GLOBAL_VERSION, []) => match parse_value::<u32>(&key, &value)? {
+                    0 => version = Some(0),
+                    other => return Err(PsbtError::UnsupportedVersion(other)),
+                },
+                (PSBT_GLOBAL_UNSIGNED_TX | PSBT_GLOBAL_VERSION, _) => return Err(PsbtError::InvalidKey(key)),
+                _ => {
+                    unknown.insert(key, value);
+                }
+            }
+        }
+        let unsigned_tx = unsigned_tx.ok_or(PsbtError::MissingUnsignedTx)?;
+
+        let inputs = unsigned_tx
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input)| {
+                let psbt_input = PsbtInput::parse(reader, testnet)?;
+                match &psbt_input.non_witness_utxo {
+                    Some(utxo) if utxo.id() != input.outpoint.txid => Err(PsbtError::UtxoMismatch(index)),
+                    _ => Ok(psbt_input),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        let outputs = unsigned_tx
+            .outputs
+            .iter()
+            .map(|_| PsbtOutput::parse(reader))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            unsigned_tx,
+            version,
+            unknown,
+            inputs,
+            outputs,
+        })
+    }
+
+    // Parses a complete PSBT, rejecting any bytes left over
+    pub fn parse_exact(bytes: &[u8], testnet: bool) -> Result<Self, PsbtError> {
+        let mut reader = bytes;
+        let psbt = Self::parse(&mut reader, testnet)?;
+
+        match reader.len() {
+            0 => Ok(psbt),
+            leftover => Err(PsbtError::Parse(ParseError::TrailingBytes(leftover))),
+        }
+    }
+
+    // Base64 is how PSBTs are usually passed between wallets
+    pub fn from_base64(s: &str, testnet: bool) -> Result<Self, PsbtError> {
+        Self::parse_exact(&base64::decode(s)?, testnet)
+    }
+
+    pub fn to_base64(&self) -> String {
+        base64::encode(&self.serialize_to_vec())
+    }
+
+    /*
+        The signer role. An input spending a P2PKH, P2WPKH or P2SH-P2WPKH output gets a partial signature by the
+        key that output pays to, when the key provider has it. The key is looked up through the input's BIP32
+        derivations first, which is how a master key finds it, and then by the hash the script pays to. The digest
+        is the legacy one for P2PKH and BIP143's over the UTXO's amount for the others, for the input's sighash
+        type or SIGHASH_ALL when it has none.
+
+        Inputs that are finalized, have no UTXO, spend another script type or have no key are skipped and left as
+        they were. A redeem script that is not the one its P2SH output commits to is an error.
+     */
+    pub fn sign(&mut self, keys: &(impl KeyProvider + ?Sized)) -> Result<SignOutcome, PsbtError> {
+        let mut outcome = SignOutcome::default();
+        for index in 0..self.inputs.len() {
+            match self.sign_input(index, keys)? {
+                true => outcome.signed.push(index),
+                false => outcome.skipped.push(index),
+            }
+        }
+
+        Ok(outcome)
+    }
+
+    // Whether the input at this index was signed
+    fn sign_input(&mut self, index: usize, keys: &(impl KeyProvider + ?Sized)) -> Result<bool, PsbtError> {
+        let input = &self.inputs[index];
+        if input.final_script_sig.is_some() || input.final_script_witness.is_some() {
+            return Ok(false);
+        }
+        let Some(utxo) = self.utxo(index) else {
+            return Ok(false);
+        };
+        let Ok(script_pubkey) = Script::from_bytes(&utxo.script_pubkey) else {
+            return Ok(false);
+        };
+
+        // A P2SH output is signed for by its redeem script
+        let script = match (script_pubkey.redeem_script_hash(), &input.redeem_script) {
+            (Some(_), None) => return Ok(false),
+            (Some(hash), Some(redeem_script)) if hash160(redeem_script) != hash => {
+                return Err(PsbtError::RedeemScriptMismatch(index));
+            }
+            (Some(_), Some(redeem_script)) => match Script::from_bytes(redeem_script) {
+                Ok(redeem_script) => redeem_script,
+                Err(_) => return Ok(false),
+            },
+            (None, _) => script_pubkey,
+        };
+
+        let sighash_type = input.sighash_type.unwrap_or(SighashType::ALL);
+        let (private_key, pubkey, z) = match script.classify() {
+            ScriptType::P2pkh => {
+                let pubkey_hash = script.p2pkh_hash().expect("P2PKH scripts have a key hash.");
+                let Some(private_key) = key_for_hash(input, &pubkey_hash, keys) else {
+                    return Ok(false);
+                };
+                let pubkey = p2pkh_pubkey(&private_key, &pubkey_hash).expect("The key was found by its hash.");
+                let z = self.unsigned_tx.sig_hash(index, &script.to_bytes(), sighash_type)?;
+
+                (private_key, pubkey, z)
+            }
+            ScriptType::P2wpkh => {
+                let (_, program) = script.witness_program().expect("P2WPKH scripts are witness programs.");
+                let pubkey_hash = program.try_into().expect("P2WPKH programs are 20 bytes.");
+                let Some(private_key) = key_for_hash(input, &pubkey_hash, keys) else {
+                    return Ok(false);
+                };
+                // BIP143 only takes compressed keys as standard
+                let pubkey = private_key.point().sec(true);
+                if hash160(&pubkey) != pubkey_hash {
+                    return Ok(false);
+                }
+                let script_code = Script::p2pkh(&pubkey_hash).to_bytes();
+                let z = self.unsigned_tx.sig_hash_bip143(index, &script_code, utxo.amount, sighash_type)?;
+
+                (private_key, pubkey, z)
+            }
+            _ => return Ok(false),
+        };
+
+        let mut signature = private_key.sign(&z).der();
+        signature.push(sighash_type.to_u32() as u8);
+        self.inputs[index].partial_sigs.insert(pubkey, signature);
+
+        Ok(true)
+    }
+
+    // The output the input at this index spends, from whichever UTXO field it has
+    fn utxo(&self, index: usize) -> Option<TxOut> {
+        let input = &self.inputs[index];
+        if let Some(witness_utxo) = &input.witness_utxo {
+            return Some(witness_utxo.clone());
+        }
+        let vout = self.unsigned_tx.inputs[index].outpoint.vout as usize;
+
+        input.non_witness_utxo.as_ref()?.outputs.get(vout).cloned()
+    }
+}
+
+// What Psbt::sign did with each input, by index
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SignOutcome {
+    pub signed: Vec<usize>,
+    pub skipped: Vec<usize>,
+}
+
+// A key the input's BIP32 derivations lead to for a pubkey with this hash, or else one the provider has for it
+fn key_for_hash(input: &PsbtInput, pubkey_hash: &[u8; 20], keys: &(impl KeyProvider + ?Sized)) -> Option<PrivateKey> {
+    input
+        .bip32_derivation
+        .iter()
+        .filter(|(pubkey, _)| hash160(pubkey) == *pubkey_hash)
+        .find_map(|(pubkey, source)| keys.key_for_derivation(pubkey, source))
+        .or_else(|| keys.key_for_pubkey_hash(pubkey_hash))
+}
+
+impl Encodable for Psbt {
+    fn serialize(&self, writer: &mut impl Write) -> Result<(), ParseError> {
+        writer.write_all(&PSBT_MAGIC)?;
+
+        let mut unsigned_tx = Vec::new();
+        self.unsigned_tx.serialize_legacy(&mut unsigned_tx)?;
+        write_entry(writer, &[PSBT_GLOBAL_UNSIGNED_TX], &unsigned_tx)?;
+        if let Some(version) = self.version {
+            write_entry(writer, &[PSBT_GLOBAL_VERSION], &version.serialize_to_vec())?;
+        }
+        write_unknown(writer, &self.unknown)?;
+
+        for input in &self.inputs {
+            input.serialize(writer)?;
+        }
+        for output in &self.outputs {
+            output.serialize(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl PsbtInput {
+    fn parse(reader: &mut impl Read, testnet: bool) -> Result<Self, PsbtError> {
+        let mut input = Self::default();
+
+        for (key, value) in read_map(reader)? {
+            match (key[0], &key[1..]) {
+                (PSBT_IN_NON_WITNESS_UTXO, []) => {
+                    let utxo = Tx::parse_exact(&value, testnet).map_err(|_| PsbtError::InvalidValue(key))?;
+                    input.non_witness_utxo = Some(utxo);
+                }
+                (PSBT_IN_WITNESS_UTXO, []) => input.witness_utxo = Some(parse_value(&key, &value)?),
+                (PSBT_IN_PARTIAL_SIG, pubkey) => {
+                    check_pubkey(&key, pubkey)?;
+                    input.partial_sigs.insert(pubkey.to_vec(), value);
+                }
+                (PSBT_IN_SIGHASH_TYPE, []) => {
+                    input.sighash_type = Some(SighashType::from_consensus(parse_value(&key, &value)?));
+                }
+                (PSBT_IN_REDEEM_SCRIPT, []) => input.redeem_script = Some(value),
+                (PSBT_IN_WITNESS_SCRIPT, []) => input.witness_script = Some(value),
+                (PSBT_IN_BIP32_DERIVATION, pubkey) => {
+                    check_pubkey(&key, pubkey)?;
+                    input.bip32_derivation.insert(pubkey.to_vec(), KeySource::parse_value(&key, &value)?);
+                }
+                (PSBT_IN_FINAL_SCRIPTSIG, []) => input.final_script_sig = Some(value),
+                (PSBT_IN_FINAL_SCRIPTWITNESS, []) => {
+                    let mut reader = value.as_slice();
+                    let witness = parse_witness(&mut reader).map_err(|_| PsbtError::InvalidValue(key.clone()))?;
+                    if !reader.is_empty() {
+                        return Err(PsbtError::InvalidValue(key));
+                    }
+                    input.final_script_witness = Some(witness);
+                }
+                (PSBT_IN_NON_WITNESS_UTXO..=PSBT_IN_FINAL_SCRIPTWITNESS, _) => return Err(PsbtError::InvalidKey(key)),
+                _ => {
+                    input.unknown.insert(key, value);
+                }
+            }
+        }
+
+        Ok(input)
+    }
+
+    fn serialize(&self, writer: &mut impl Write) -> Result<(), ParseError> {
+        if let Some(utxo) = &self.non_witness_utxo {
+            write_entry(writer, &[PSBT_IN_NON_WITNESS_UTXO], &utxo.serialize_to_vec())?;
+        }
+        if let Some(utxo) = &self.witness_utxo {
+            write_entry(writer, &[PSBT_IN_WITNESS_UTXO], &utxo.serialize_to_vec())?;
+        }
+        for (pubkey, signature) in &self.partial_sigs {
+            write_entry(writer, &[&[PSBT_IN_PARTIAL_SIG], pubkey.as_slice()].concat(), signature)?;
+        }
+        if let Some(sighash_type) = self.sighash_type {
+            write_entry(writer, &[PSBT_IN_SIGHASH_TYPE], &sighash_type.to_u32().serialize_to_vec())?;
+        }
+        if let Some(script) = &self.redeem_script {
+            write_entry(writer, &[PSBT_IN_REDEEM_SCRIPT], script)?;
+        }
+        if let Some(script) = &self.witness_script {
+            write_entry(writer, &[PSBT_IN_WITNESS_SCRIPT], script)?;
+        }
+        write_derivations(writer, PSBT_IN_BIP32_DERIVATION, &self.bip32_derivation)?;
+        if let Some(script) = &self.final_script_sig {
+            write_entry(writer, &[PSBT_IN_FINAL_SCRIPTSIG], script)?;
+        }
+        if let Some(witness) = &self.final_script_witness {
+            let mut value = Vec::new();
+            serialize_witness(&mut value, witness)?;
+            write_entry(writer, &[PSBT_IN_FINAL_SCRIPTWITNESS], &value)?;
+        }
+
+        write_unknown(writer, &self.unknown)
+    }
+}
+
+impl PsbtOutput {
+    fn parse(reader: &mut impl Read) -> Result<Self, PsbtError> {
+        let mut output = Self::default();
+
+        for (key, value) in read_map(reader)? {
+            match (key[0], &key[1..]) {
+                (PSBT_OUT_REDEEM_SCRIPT, []) => output.redeem_script = Some(value),
+                (PSBT_OUT_WITNESS_SCRIPT, []) => output.witness_script = Some(value),
+                (PSBT_OUT_BIP32_DERIVATION, pubkey) => {
+                    check_pubkey(&key, pubkey)?;
+                    output.bip32_derivation.insert(pubkey.to_vec(), KeySource::parse_value(&key, &value)?);
+                }
+                (PSBT_OUT_REDEEM_SCRIPT..=PSBT_OUT_BIP32_DERIVATION, _) => return Err(PsbtError::InvalidKey(key)),
+                _ => {
+                    output.unknown.insert(key, value);
+                }
+            }
+        }
+
+        Ok(output)
+    }
+
+    fn serialize(&self, writer: &mut impl Write) -> Result<(), ParseError> {
+        if let Some(script) = &self.redeem_script {
+            write_entry(writer, &[PSBT_OUT_REDEEM_SCRIPT], script)?;
+        }
+        if let Some(script) = &self.witness_script {
+            write_entry(writer, &[PSBT_OUT_WITNESS_SCRIPT], script)?;
+        }
+        write_derivations(writer, PSBT_OUT_BIP32_DERIVATION, &self.bip32_derivation)?;
+
+        write_unknown(writer, &self.unknown)
+    }
+}
+
+fn check_unsigned(tx: &Tx) -> Result<(), PsbtError> {
+    match tx.inputs.iter().position(|input| !input.script_sig.is_empty() || !input.witness.is_empty()) {
+        Some(index) => Err(PsbtError::UnsignedTxHasSignature(index)),
+        None => Ok(()),
+    }
+}
+
+type Entries = Vec<(Vec<u8>, Vec<u8>)>;
+
+// Reads key-value pairs up to the zero byte ending the map, in the order they appear
+fn read_map(reader: &mut impl Read) -> Result<Entries, PsbtError> {
+    let mut seen = BTreeSet::new();
+    let mut entries = Vec::new();
+
+    loop {
+        let key = read_var_bytes(reader).map_err(|error| error.in_type("Psbt"))?;
+        if key.is_empty() {
+            return Ok(entries);
+        }
+        let value = read_var_bytes(reader).map_err(|error| error.in_type("Psbt"))?;
+
+        if !seen.insert(key.clone()) {
+            return Err(PsbtError::DuplicateKey(key));
+        }
+        entries.push((key, value));
+    }
+}
+
+fn parse_value<T: Decodable>(key: &[u8], value: &[u8]) -> Result<T, PsbtError> {
+    T::parse_exact(value).map_err(|_| PsbtError::InvalidValue(key.to_vec()))
+}
+
+fn check_pubkey(key: &[u8], pubkey: &[u8]) -> Result<(), PsbtError> {
+    match pubkey {
+        [0x02 | 0x03, ..] if pubkey.len() == 33 => Ok(()),
+        [0x04, ..] if pubkey.len() == 65 => Ok(()),
+        _ => Err(PsbtError::InvalidKey(key.to_vec())),
+    }
+}
+
+fn write_entry(writer: &mut impl Write, key: &[u8], value: &[u8]) -> Result<(), ParseError> {
+    write_var_bytes(writer, key)?;
+    write_var_bytes(writer, value)
+}
+
+fn write_derivations(
+    writer: &mut impl Write,
+    key_type: u8,
+    derivations: &BTreeMap<Vec<u8>, KeySource>,
+) -> Result<(), ParseError> {
+    for (pubkey, source) in derivations {
+        write_entry(writer, &[&[key_type], pubkey.as_slice()].concat(), &source.to_value())?;
+    }
+
+    Ok(())
+}
+
+// Unknown entries come last, and the zero byte closes the map
+fn write_unknown(writer: &mut impl Write, unknown: &BTreeMap<Vec<u8>, Vec<u8>>) -> Result<(), ParseError> {
+    for (key, value) in unknown {
+        write_entry(writer, key, value)?;
+    }
+    writer.write_all(&[0x00])?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use crate::bip32::derivation_path::DerivationPath;
+    use crate::bip32::extended_key::ExtendedPrivateKey;
+    use crate::encoding::encodable::Encodable;
+    use crate::encoding::hex;
+    use crate::encoding::parse_error::ParseError;
+    use crate::encoding::varint::encode_varint;
+    use crate::hashing::hash160::hash160;
+    use crate::script::interpreter::ScriptFlags;
+    use crate::script::{Cmd, Script};
+    use crate::tx::amount::Amount;
+    use crate::tx::psbt::{KeySource, Psbt, PsbtError, SignOutcome};
+    use crate::tx::sighash::SighashType;
+    use crate::tx::transaction::Tx;
+    use crate::tx::tx_out::TxOut;
+
+    // The PSBT the creator produces in BIP174's example, two inputs and two outputs with every map empty
+    const BIP174_CREATOR_HEX: &str = "70736274ff01009a020000000258e87a21b56daf0c23be8e7070456c336f7cbaa5c8757924f545887bb2abdd\
+                                      750000000000ffffffff838d0427d0ec650a68aa46bb0b098aea4422c071b2ca78352a077959d07cea1d01\
+                                      00000000ffffffff0270aaf00800000000160014d85c2b71d0060b09c9886aeb815e50991dda124d00e1f5\
+                                      050000000016001400aea9a2e5f0f876a588df5546e8742d1d87008f000000000000000000";
+    // Where the first input's map and the first output's map start in the creator PSBT
+    const FIRST_INPUT_MAP: usize = 163;
+    const FIRST_OUTPUT_MAP: usize = 165;
+
+    // The native P2WPKH example from BIP143, one P2PK input and one P2WPKH input
+    const BIP143_P2WPKH_HEX: &str = "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000049\
+                                     4830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b19\
+                                     4ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d27965\
+                                     5c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b3\
+                                     7df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2\
+                                     f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01c\
+                                     c44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188\
+                                     368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000";
+
+    const PUBKEY_HEX: &str = "025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357";
+
+    fn creator_bytes() -> Vec<u8> {
+        hex::decode(BIP174_CREATOR_HEX).unwrap()
+    }
+
+    fn entry(key: &[u8], value: &[u8]) -> Vec<u8> {
+        [&encode_varint(key.len() as u64), key, &encode_varint(value.len() as u64), value].concat()
+    }
+
+    // The creator PSBT with extra entries in the map starting at the given offset
+    fn with_entries(offset: usize, entries: &[Vec<u8>]) -> Vec<u8> {
+        let bytes = creator_bytes();
+
+        [&bytes[..offset], &entries.concat(), &bytes[offset..]].concat()
+    }
+
+    // The BIP143 example split into its unsigned transaction and what the signers produced for it
+    fn signed_bip143_psbt() -> Psbt {
+        let mut tx = Tx::parse_exact(&hex::decode(BIP143_P2WPKH_HEX).unwrap(), false).unwrap();
+        let script_sig = std::mem::take(&mut tx.inputs[0].script_sig);
+        let witness = std::mem::take(&mut tx.inputs[1].witness);
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+
+        psbt.version = Some(0);
+        psbt.unknown.insert(vec![0xfc, 0x01], vec![0xab]);
+        psbt.inputs[0].final_script_sig = Some(script_sig);
+        psbt.inputs[1].witness_utxo = Some(TxOut::new(
+            Amount::from_sat(600_000_000),
+            hex::decode("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap(),
+        ));
+        psbt.inputs[1].partial_sigs.insert(witness[1].clone(), witness[0].clone());
+        psbt.inputs[1].sighash_type = Some(SighashType::ALL);
+        psbt.inputs[1].bip32_derivation.insert(
+            hex::decode(PUBKEY_HEX).unwrap(),
+            KeySource {
+                fingerprint: [0xd9, 0x0c, 0x6a, 0x4f],
+                path: DerivationPath::from_str("m/84'/0'/0'/0/1").unwrap(),
+            },
+        );
+        psbt.outputs[1].unknown.insert(vec![0x10, 0x00], vec![0x01, 0x02]);
+
+        psbt
+    }
+
+    #[test]
+    fn bip174_creator_example_round_trips() {
+        let bytes = creator_bytes();
+
+        let subject = Psbt::parse_exact(&bytes, false).unwrap();
+
+        assert_eq!(subject.inputs.len(), 2);
+        assert_eq!(subject.outputs.len(), 2);
+        assert!(subject.inputs.iter().all(|input| input.partial_sigs.is_empty() && input.unknown.is_empty()));
+        assert_eq!(subject.serialize_to_vec(), bytes);
+        assert_eq!(Psbt::from_unsigned_tx(subject.unsigned_tx.clone()).unwrap(), subject);
+        assert_eq!(Psbt::from_base64(&subject.to_base64(), false).unwrap(), subject);
+    }
+
+    #[test]
+    fn populated_psbt_round_trips_with_unknown_keys() {
+        let psbt = signed_bip143_psbt();
+        let bytes = psbt.serialize_to_vec();
+
+        let subject = Psbt::parse_exact(&bytes, false).unwrap();
+
+        assert_eq!(subject, psbt);
+        assert_eq!(subject.serialize_to_vec(), bytes);
+        assert_eq!(subject.inputs[1].partial_sigs[&hex::decode(PUBKEY_HEX).unwrap()].last(), Some(&0x01));
+        assert_eq!(subject.outputs[1].unknown[&vec![0x10, 0x00]], vec![0x01, 0x02]);
+    }
+
+    #[test]
+    fn entries_are_re_serialized_in_canonical_order() {
+        let script_pubkey = hex::decode("0014d85c2b71d0060b09c9886aeb815e50991dda124d").unwrap();
+        let witness_utxo = TxOut::new(Amount::from_sat(5_000), script_pubkey);
+        let sighash = entry(&[0x03], &[0x01, 0x00, 0x00, 0x00]);
+        let utxo = entry(&[0x01], &witness_utxo.serialize_to_vec());
+        let unknown = entry(&[0x20], &[]);
+        let out_of_order = with_entries(FIRST_INPUT_MAP, &[unknown.clone(), sighash.clone(), utxo.clone()]);
+
+        let subject = Psbt::parse_exact(&out_of_order, false).unwrap();
+
+        assert_eq!(subject.inputs[0].witness_utxo, Some(witness_utxo));
+        assert_eq!(subject.serialize_to_vec(), with_entries(FIRST_INPUT_MAP, &[utxo, sighash, unknown]));
+    }
+
+    #[test]
+    fn invalid_psbts_are_rejected() {
+        let bytes = creator_bytes();
+        let pubkey = hex::decode(PUBKEY_HEX).unwrap();
+        let mut extra_global_key_data = bytes.clone();
+        extra_global_key_data.splice(5..7, [0x02, 0x00, 0x01]);
+        let mut signed = Psbt::parse_exact(&bytes, false).unwrap();
+        signed.unsigned_tx.inputs[1].script_sig = vec![0x51];
+        let bip143_tx = hex::decode(BIP143_P2WPKH_HEX).unwrap();
+        let cases: Vec<(Vec<u8>, PsbtError)> = vec![
+            // A network transaction rather than a PSBT
+            (bip143_tx.clone(), PsbtError::InvalidMagic),
+            // The output maps are missing
+            (bytes[..FIRST_OUTPUT_MAP].to_vec(), PsbtError::Parse(ParseError::Truncated("Psbt"))),
+            (signed.serialize_to_vec(), PsbtError::UnsignedTxHasSignature(1)),
+            // Input and output maps but no unsigned transaction
+            (hex::decode("70736274ff000000").unwrap(), PsbtError::MissingUnsignedTx),
+            (
+                with_entries(FIRST_INPUT_MAP, &[entry(&[0x03], &[1, 0, 0, 0]), entry(&[0x03], &[1, 0, 0, 0])]),
+                PsbtError::DuplicateKey(vec![0x03]),
+            ),
+            (extra_global_key_data, PsbtError::InvalidKey(vec![0x00, 0x01])),
+            (with_entries(162, &[entry(&[0xfb], &[1, 0, 0, 0])]), PsbtError::UnsupportedVersion(1)),
+            (
+                with_entries(FIRST_INPUT_MAP, &[entry(&[0x01, 0x00], &[0; 9])]),
+                PsbtError::InvalidKey(vec![0x01, 0x00]),
+            ),
+            (
+                with_entries(FIRST_INPUT_MAP, &[entry(&[&[0x02], &pubkey[..32]].concat(), &[0x30])]),
+                PsbtError::InvalidKey([&[0x02], &pubkey[..32]].concat()),
+            ),
+            (
+                with_entries(FIRST_INPUT_MAP, &[entry(&[0x03], &[1, 0])]),
+                PsbtError::InvalidValue(vec![0x03]),
+            ),
+            (
+                with_entries(FIRST_INPUT_MAP, &[entry(&[0x00], &bip143_tx)]),
+                PsbtError::UtxoMismatch(0),
+            ),
+            (
+                with_entries(FIRST_OUTPUT_MAP, &[entry(&[&[0x02, 0x05], &pubkey[1..]].concat(), &[0; 8])]),
+                PsbtError::InvalidKey([&[0x02, 0x05], &pubkey[1..]].concat()),
+            ),
+            (
+                with_entries(FIRST_OUTPUT_MAP, &[entry(&[&[0x02], pubkey.as_slice()].concat(), &[0; 6])]),
+                PsbtError::InvalidValue([&[0x02], pubkey.as_slice()].concat()),
+            ),
+        ];
+
+        for (index, (bytes, expected)) in cases.into_iter().enumerate() {
+            assert_eq!(Psbt::parse_exact(&bytes, false), Err(expected), "case {}", index);
+        }
+        assert_eq!(
+            Psbt::from_unsigned_tx(signed.unsigned_tx).map(|_| ()),
+            Err(PsbtError::UnsignedTxHasSignature(1))
+        );
+    }
+
+    // The BIP39 seed of "abandon abandon ... about" with no passphrase, which BIP84's and BIP49's vectors start from
+    const ABANDON_SEED_HEX: &str = "5eb00bbddcf069084889a8ab9155568165f5c453ccb85e70811aaed6f6da5fc1\
+                                    9a5ac40b389cd370d086206dec8aa6c43daea6690f20ad3d8d48b2d2ce9e38e4";
+
+    fn abandon_master() -> ExtendedPrivateKey {
+        ExtendedPrivateKey::from_seed(&hex::decode(ABANDON_SEED_HEX).unwrap()).unwrap()
+    }
+
+    /*
+        The creator example after an updater has filled it in: its first input spends a P2WPKH output of the key
+        at m/84'/0'/0'/0/0 and its second a P2SH-P2WPKH output of the key at m/49'/1'/0'/0/0, each with its
+        derivation from the master key.
+     */
+    fn updated_psbt(master: &ExtendedPrivateKey) -> Psbt {
+        let derive = |path: &str| {
+            let path = DerivationPath::from_str(path).unwrap();
+            let pubkey = master.derive_path(&path).unwrap().private_key().point().sec(true);
+
+            (pubkey, KeySource { fingerprint: master.fingerprint(), path })
+        };
+        let (native_pubkey, native_source) = derive("m/84'/0'/0'/0/0");
+        let (nested_pubkey, nested_source) = derive("m/49'/1'/0'/0/0");
+        let redeem_script = Script::p2wpkh(&hash160(&nested_pubkey)).to_bytes();
+        let mut psbt = Psbt::parse_exact(&creator_bytes(), false).unwrap();
+
+        let native_utxo = Script::p2wpkh(&hash160(&native_pubkey)).to_bytes();
+        psbt.inputs[0].witness_utxo = Some(TxOut::new(Amount::from_sat(150_000_000), native_utxo));
+        psbt.inputs[0].bip32_derivation.insert(native_pubkey, native_source);
+        let nested_utxo = Script::p2sh(&hash160(&redeem_script)).to_bytes();
+        psbt.inputs[1].witness_utxo = Some(TxOut::new(Amount::from_sat(100_000_000), nested_utxo));
+        psbt.inputs[1].redeem_script = Some(redeem_script);
+        psbt.inputs[1].bip32_derivation.insert(nested_pubkey, nested_source);
+
+        psbt
+    }
+
+    // Whether the input spends its UTXO once each signed input's one partial signature is put in its witness
+    fn finalized_input_verifies(psbt: &Psbt, index: usize) -> bool {
+        let prevouts: Vec<TxOut> = psbt.inputs.iter().map(|input| input.witness_utxo.clone().unwrap()).collect();
+        let mut tx = psbt.unsigned_tx.clone();
+        for (tx_input, input) in tx.inputs.iter_mut().zip(&psbt.inputs) {
+            let Some((pubkey, signature)) = input.partial_sigs.first_key_value() else {
+                continue;
+            };
+            tx_input.witness = vec![signature.clone(), pubkey.clone()];
+            if let Some(redeem_script) = &input.redeem_script {
+                tx_input.script_sig = Script::new(vec![Cmd::push(redeem_script.clone())]).to_bytes();
+            }
+        }
+
+        tx.verify_input(index, &prevouts, ScriptFlags::CONSENSUS) == Ok(true)
+    }
+
+    #[test]
+    fn master_key_signs_p2wpkh_and_p2sh_p2wpkh_inputs_through_their_derivations() {
+        let master = abandon_master();
+        let mut psbt = updated_psbt(&master);
+
+        let outcome = psbt.sign(&master).unwrap();
+
+        assert_eq!(outcome, SignOutcome { signed: vec![0, 1], skipped: vec![] });
+        assert!(finalized_input_verifies(&psbt, 0));
+        assert!(finalized_input_verifies(&psbt, 1));
+        // BIP84's first receive key, and BIP49's on testnet
+        let signers: Vec<String> =
+            psbt.inputs.iter().map(|input| hex::encode(input.partial_sigs.keys().next().unwrap())).collect();
+        assert_eq!(
+            signers,
+            [
+                "0330d54fd0dd420a6e5f8d3624f5f3482cae350f79d5f0753bf5beef9c2d91af3c",
+                "03a1af804ac108a8a51782198c2d034b28bf90c8803f5a53f76276fa69a4eae77f",
+            ]
+        );
+    }
+
+    #[test]
+    fn plain_keys_sign_the_inputs_whose_hash_they_match() {
+        let master = abandon_master();
+        let native_key = master.derive_path(&DerivationPath::from_str("m/84'/0'/0'/0/0").unwrap()).unwrap();
+        let mut psbt = updated_psbt(&master);
+        psbt.inputs[0].bip32_derivation.clear();
+        psbt.inputs[0].sighash_type = Some(SighashType::SINGLE);
+
+        let outcome = psbt.sign(&[native_key.private_key().clone()][..]).unwrap();
+
+        assert_eq!(outcome, SignOutcome { signed: vec![0], skipped: vec![1] });
+        assert!(psbt.inputs[1].partial_sigs.is_empty());
+        assert_eq!(psbt.inputs[0].partial_sigs.values().next().unwrap().last(), Some(&0x03));
+        assert!(finalized_input_verifies(&psbt, 0));
+    }
+
+    #[test]
+    fn signer_skips_finished_inputs_and_rejects_the_wrong_redeem_script() {
+        let master = abandon_master();
+        let mut psbt = updated_psbt(&master);
+        psbt.inputs[0].final_script_witness = Some(vec![]);
+        psbt.inputs[1].witness_utxo = None;
+
+        assert_eq!(psbt.sign(&master), Ok(SignOutcome { signed: vec![], skipped: vec![0, 1] }));
+
+        let mut psbt = updated_psbt(&master);
+        psbt.inputs[1].redeem_script = Some(Script::p2wpkh(&[0; 20]).to_bytes());
+
+        assert_eq!(psbt.sign(&master), Err(PsbtError::RedeemScriptMismatch(1)));
+    }
+}
@@ -0,0 +1,283 @@
+use std::fmt::{Display, Formatter};
+use crate::tx::amount::{Amount, AmountError};
+use crate::tx::coin_selection::Utxo;
+use crate::tx::transaction::Tx;
+use crate::tx::tx_builder::{BuildError, TxBuilder};
+use crate::tx::tx_out::TxOut;
+
+// Bitcoin Core's default incremental relay fee: a replacement pays this much for its own relay on top
+pub const INCREMENTAL_RELAY_FEE_RATE: u64 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RbfError {
+    // BIP125 rule 1: no input of the original has a sequence below 0xfffffffe
+    NotSignaling,
+    PrevoutCountMismatch { prevout_count: usize, input_count: usize },
+    // The change output the bump is taken from is not one of the original's outputs
+    ChangeIndexOutOfRange { index: usize, output_count: usize },
+    OutputsExceedInputs,
+    // The original already pays at least the new rate, in sat/vB
+    FeeRateTooLow { original_rate: f64 },
+    // BIP125 rule 4: the replacement must pay the original fee plus the incremental relay fee for its own size
+    InsufficientFeeIncrease { required: Amount, offered: Amount },
+    Amount(AmountError),
+    Build(BuildError),
+}
+
+impl Display for RbfError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RbfError::NotSignaling => write!(f, "The original transaction does not signal replaceability."),
+            RbfError::PrevoutCountMismatch { prevout_count, input_count } => {
+                write!(f, "{} prevouts were given for a transaction with {} inputs.", prevout_count, input_count)
+            }
+            RbfError::ChangeIndexOutOfRange { index, output_count } => {
+                write!(f, "Change output {} is out of range for a transaction with {} outputs.", index, output_count)
+            }
+            RbfError::OutputsExceedInputs => write!(f, "The original transaction spends more than its inputs."),
+            RbfError::FeeRateTooLow { original_rate } => {
+                write!(f, "The original transaction already pays {:.2} sat/vB.", original_rate)
+            }
+            RbfError::InsufficientFeeIncrease { required, offered } => {
+                write!(f, "A replacement must pay at least {} but would pay {}.", required, offered)
+            }
+            RbfError::Amount(error) => write!(f, "Invalid amount: {}", error),
+            RbfError::Build(error) => write!(f, "Cannot build the replacement: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for RbfError {}
+
+impl From<AmountError> for RbfError {
+    fn from(error: AmountError) -> Self {
+        RbfError::Amount(error)
+    }
+}
+
+impl From<BuildError> for RbfError {
+    fn from(error: BuildError) -> Self {
+        RbfError::Build(error)
+    }
+}
+
+/*
+    Prepares a BIP125 replacement of a signed transaction paying new_rate sat/vB, taking the higher fee from its
+    last output, which is where TxBuilder puts change. See bump_with_change for an original whose change is
+    elsewhere.
+ */
+pub fn bump(original: &Tx, prevouts: &[TxOut], new_rate: u64, extra_utxos: &[Utxo]) -> Result<TxBuilder, RbfError> {
+    let change_index = original.outputs.len().saturating_sub(1);
+
+    bump_with_change(original, prevouts, change_index, new_rate, extra_utxos)
+}
+
+/*
+    Prepares a BIP125 replacement of a signed transaction paying new_rate sat/vB. The replacement spends the
+    same inputs and pays the same outputs except the one at change_index, which is rebuilt with whatever the
+    higher fee leaves and moved to the end, where TxBuilder puts change. When the change cannot cover the
+    increase, extra_utxos are added largest first until it can. They should be confirmed, since BIP125 does
+    not let a replacement spend new unconfirmed outputs.
+
+    The returned builder is unsigned and finishes into a transaction that signals replaceability and pays
+    both a higher rate and, for its own relay, a higher absolute fee than the original.
+ */
+pub fn bump_with_change(
+    original: &Tx,
+    prevouts: &[TxOut],
+    change_index: usize,
+    new_rate: u64,
+    extra_utxos: &[Utxo],
+) -> Result<TxBuilder, RbfError> {
+    if !original.signals_rbf() {
+        return Err(RbfError::NotSignaling);
+    }
+    if prevouts.len() != original.inputs.len() {
+        let (prevout_count, input_count) = (prevouts.len(), original.inputs.len());
+        return Err(RbfError::PrevoutCountMismatch { prevout_count, input_count });
+    }
+    let output_count = original.outputs.len();
+    let change = original
+        .outputs
+        .get(change_index)
+        .ok_or(RbfError::ChangeIndexOutOfRange { index: change_index, output_count })?;
+    let payments = original.outputs.iter().enumerate().filter(|(index, _)| *index != change_index);
+
+    let mut available = prevouts.iter().map(|prevout| prevout.amount).sum::<Result<Amount, AmountError>>()?;
+    let spent = original.outputs.iter().map(|output| output.amount).sum::<Result<Amount, AmountError>>()?;
+    let original_fee = available.checked_sub(spent).ok_or(RbfError::OutputsExceedInputs)?;
+    let new_fee = Amount::from_sat(new_rate).checked_mul(original.vsize()).ok_or(AmountError::Overflow)?;
+    if new_fee <= original_fee {
+        return Err(RbfError::FeeRateTooLow { original_rate: original.fee_rate(original_fee) });
+    }
+
+    let mut builder = original
+        .inputs
+        .iter()
+        .zip(prevouts)
+        .fold(TxBuilder::new(original.testnet), |builder, (input, prevout)| {
+            builder.add_input(input.outpoint, prevout.clone())
+        });
+    builder = payments
+        .fold(builder, |builder, (_, output)| builder.add_script_output(output.script_pubkey.clone(), output.amount))
+        .add_script_change(change.script_pubkey.clone())
+        .set_locktime(original.locktime)
+        .fee_rate(new_rate)
+        .enable_rbf();
+
+    let mut extras: Vec<&Utxo> = extra_utxos
+        .iter()
+        .filter(|utxo| original.inputs.iter().all(|input| input.outpoint != utxo.outpoint))
+        .collect();
+    extras.sort_by_key(|utxo| std::cmp::Reverse(utxo.txout.amount));
+    let mut extras = extras.into_iter();
+
+    let replacement = loop {
+        match builder.clone().finish() {
+            Ok(replacement) => break replacement,
+            Err(BuildError::InsufficientFunds { .. }) if extras.len() > 0 => {
+                let utxo = extras.next().expect("There are extra UTXOs left.");
+                available = available.checked_add(utxo.txout.amount).ok_or(AmountError::Overflow)?;
+                builder = builder.add_input(utxo.outpoint, utxo.txout.clone());
+            }
+            Err(error) => return Err(error.into()),
+        }
+    };
+
+    let paid = replacement.outputs.iter().map(|output| output.amount).sum::<Result<Amount, AmountError>>()?;
+    let offered = available.checked_sub(paid).ok_or(RbfError::OutputsExceedInputs)?;
+    let relay_fee = Amount::from_sat(INCREMENTAL_RELAY_FEE_RATE)
+        .checked_mul(builder.signed_vsize(replacement)?)
+        .ok_or(AmountError::Overflow)?;
+    let required = original_fee.checked_add(relay_fee).ok_or(AmountError::Overflow)?;
+    if offered < required {
+        return Err(RbfError::InsufficientFeeIncrease { required, offered });
+    }
+
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use crate::hashing::hash256::Hash256;
+    use crate::tx::amount::{Amount, AmountError};
+    use crate::tx::coin_selection::Utxo;
+    use crate::tx::fixtures::{p2wpkh, TXID};
+    use crate::tx::outpoint::OutPoint;
+    use crate::tx::rbf::{bump, bump_with_change, RbfError};
+    use crate::tx::transaction::Tx;
+    use crate::tx::tx_builder::TxBuilder;
+    use crate::tx::tx_out::TxOut;
+
+    fn prevout() -> TxOut {
+        TxOut::new(Amount::from_sat(100_000), p2wpkh(0x01))
+    }
+
+    // 50_000 sat paid out of 100_000 at 10 sat/vB, with a witness of signature size so it is 141 vbytes
+    fn original(builder: TxBuilder) -> Tx {
+        let mut tx = builder
+            .add_input(OutPoint::new(Hash256::from_str(TXID).unwrap(), 1), prevout())
+            .add_script_output(p2wpkh(0x02), Amount::from_sat(50_000))
+            .add_script_change(p2wpkh(0x03))
+            .fee_rate(10)
+            .finish()
+            .unwrap();
+        tx.inputs[0].witness = vec![vec![0x30; 72], vec![0x02; 33]];
+        tx
+    }
+
+    fn output_total(tx: &Tx) -> u64 {
+        tx.outputs.iter().map(|output| output.amount).sum::<Amount>().to_sat()
+    }
+
+    #[test]
+    fn bump_takes_the_fee_from_the_change() {
+        let original = original(TxBuilder::new(false).enable_rbf());
+        assert_eq!((original.vsize(), original.outputs[1].amount.to_sat()), (141, 48_590));
+
+        let replacement = bump(&original, &[prevout()], 20, &[]).unwrap().finish().unwrap();
+
+        assert_eq!(replacement.inputs.len(), 1);
+        assert_eq!(replacement.inputs[0].outpoint, original.inputs[0].outpoint);
+        assert_eq!(replacement.outputs[0], original.outputs[0]);
+        assert_eq!(replacement.outputs[1].script_pubkey, original.outputs[1].script_pubkey);
+        assert_eq!(replacement.outputs[1].amount.to_sat(), 100_000 - 50_000 - 20 * 141);
+        assert!(replacement.signals_rbf());
+    }
+
+    #[test]
+    fn bump_pulls_in_another_input_when_the_change_falls_short() {
+        let original = original(TxBuilder::new(false).enable_rbf());
+        let extra = |vout: u32, sats: u64| {
+            let outpoint = OutPoint::new(Hash256::from_str(TXID).unwrap(), vout);
+            Utxo::new(outpoint, TxOut::new(Amount::from_sat(sats), p2wpkh(0x04)))
+        };
+        let extras = [extra(2, 10_000), extra(3, 200_000)];
+
+        let builder = bump(&original, &[prevout()], 500, &extras).unwrap();
+
+        // The estimate leaves out the change output, which takes 31 bytes
+        let vsize = builder.estimated_vsize().unwrap() + 31;
+        let replacement = builder.finish().unwrap();
+        assert_eq!(replacement.inputs.len(), 2);
+        assert_eq!(replacement.inputs[1].outpoint.vout, 3);
+        assert_eq!(replacement.outputs.len(), 2);
+        assert_eq!(output_total(&replacement), 300_000 - 500 * vsize);
+        assert!(replacement.signals_rbf());
+    }
+
+    #[test]
+    fn bump_is_refused_without_enough_funds() {
+        let original = original(TxBuilder::new(false).enable_rbf());
+
+        let result = bump(&original, &[prevout()], 500, &[]);
+
+        assert!(matches!(result, Err(RbfError::Build(_))));
+    }
+
+    #[test]
+    fn bump_requires_the_original_to_signal_rbf() {
+        let original = original(TxBuilder::new(false));
+
+        let result = bump(&original, &[prevout()], 20, &[]);
+
+        assert_eq!(result.map(|_| ()), Err(RbfError::NotSignaling));
+    }
+
+    #[test]
+    fn bump_must_raise_the_fee_rate() {
+        let original = original(TxBuilder::new(false).enable_rbf());
+
+        let result = bump(&original, &[prevout()], 10, &[]);
+
+        assert_eq!(result.map(|_| ()), Err(RbfError::FeeRateTooLow { original_rate: 10.0 }));
+    }
+
+    #[test]
+    fn bump_takes_the_fee_from_the_given_change_output() {
+        let mut original = original(TxBuilder::new(false).enable_rbf());
+        original.outputs.swap(0, 1);
+
+        let replacement = bump_with_change(&original, &[prevout()], 0, 20, &[]).unwrap().finish().unwrap();
+        let out_of_range = bump_with_change(&original, &[prevout()], 2, 20, &[]);
+
+        assert_eq!(replacement.outputs[0], original.outputs[1]);
+        assert_eq!(replacement.outputs[1].script_pubkey, original.outputs[0].script_pubkey);
+        assert_eq!(replacement.outputs[1].amount.to_sat(), 100_000 - 50_000 - 20 * 141);
+        assert_eq!(out_of_range.map(|_| ()), Err(RbfError::ChangeIndexOutOfRange { index: 2, output_count: 2 }));
+        // bump itself would have cut the payment, now last, instead
+        let last_cut = bump(&original, &[prevout()], 20, &[]).unwrap().finish().unwrap();
+        assert_eq!(last_cut.outputs[1].script_pubkey, original.outputs[1].script_pubkey);
+        assert_eq!(last_cut.outputs[1].amount.to_sat(), 50_000 - (20 - 10) * 141);
+    }
+
+    #[test]
+    fn overflowing_fee_rates_are_errors() {
+        let original = original(TxBuilder::new(false).enable_rbf());
+
+        let result = bump(&original, &[prevout()], u64::MAX / 100, &[]);
+
+        assert_eq!(result.map(|_| ()), Err(RbfError::Amount(AmountError::Overflow)));
+    }
+}
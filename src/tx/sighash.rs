@@ -0,0 +1,1027 @@
+use std::cell::OnceCell;
+use std::fmt::{Display, Formatter};
+use crate::encoding::encodable::Encodable;
+use crate::encoding::varint::write_var_bytes;
+use crate::hashing::hash256::hash256;
+use crate::hashing::sha256::sha256;
+use crate::hashing::tagged_hash::tagged_hash;
+use crate::tx::amount::Amount;
+use crate::tx::transaction::{Tx, TxError};
+use crate::tx::tx_in::TxIn;
+use crate::tx::tx_out::TxOut;
+
+const SIGHASH_ANYONECANPAY: u32 = 0x80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SighashBase {
+    // Commits to every output
+    All,
+    // Commits to no outputs
+    None,
+    // Commits to the output with the same index as the input
+    Single,
+}
+
+/*
+    The hash type a signature commits to, taken from the byte after its DER encoding. The raw value is kept
+    because it is what gets hashed: consensus accepts bytes outside the six standard ones and reads them the
+    way base() and anyone_can_pay() do, so historical transactions can only be verified by hashing them as is.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SighashType(u32);
+
+impl SighashType {
+    // Taproot only: commits like ALL, and the signature leaves out the hash type byte
+    pub const DEFAULT: SighashType = SighashType(0x00);
+    pub const ALL: SighashType = SighashType(0x01);
+    pub const NONE: SighashType = SighashType(0x02);
+    pub const SINGLE: SighashType = SighashType(0x03);
+    pub const ALL_ANYONECANPAY: SighashType = SighashType(0x81);
+    pub const NONE_ANYONECANPAY: SighashType = SighashType(0x82);
+    pub const SINGLE_ANYONECANPAY: SighashType = SighashType(0x83);
+
+    pub fn from_consensus(value: u32) -> Self {
+        SighashType(value)
+    }
+
+    // The last byte of a signature as it appears in a script_sig or witness
+    pub fn from_signature(signature: &[u8]) -> Option<Self> {
+        signature.last().map(|byte| SighashType(*byte as u32))
+    }
+
+    pub fn to_u32(&self) -> u32 {
+        self.0
+    }
+
+    // Bitcoin Core treats any base other than NONE and SINGLE as ALL
+    pub fn base(&self) -> SighashBase {
+        match self.0 & 0x1f {
+            0x02 => SighashBase::None,
+            0x03 => SighashBase::Single,
+            _ => SighashBase::All,
+        }
+    }
+
+    // Commits to the signed input alone, letting others add inputs
+    pub fn anyone_can_pay(&self) -> bool {
+        self.0 & SIGHASH_ANYONECANPAY != 0
+    }
+
+    pub fn is_standard(&self) -> bool {
+        matches!(self.0, 0x01..=0x03 | 0x81..=0x83)
+    }
+}
+
+impl Default for SighashType {
+    fn default() -> Self {
+        SighashType::ALL
+    }
+}
+
+impl Display for SighashType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let base = match self.base() {
+            SighashBase::All => "ALL",
+            SighashBase::None => "NONE",
+            SighashBase::Single => "SINGLE",
+        };
+
+        match (self.is_standard(), self.anyone_can_pay()) {
+            (true, false) => write!(f, "{}", base),
+            (true, true) => write!(f, "{}|ANYONECANPAY", base),
+            (false, _) => write!(f, "{:#x}", self.0),
+        }
+    }
+}
+
+// The digest Bitcoin Core signs for SIGHASH_SINGLE without a matching output: the number 1, little-endian
+const SIGHASH_SINGLE_BUG: [u8; 32] = {
+    let mut one = [0u8; 32];
+    one[0] = 1;
+    one
+};
+
+#[cfg(test)]
+thread_local! {
+    // How many times this thread has hashed a transaction's prevouts, so tests can see midstates being reused
+    static PREVOUT_HASH_COUNT: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+// Single SHA256s of the data every input's digest shares: BIP341 uses them as they are, BIP143 hashes them again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TxMidstates {
+    sha_prevouts: [u8; 32],
+    sha_sequences: [u8; 32],
+    sha_outputs: [u8; 32],
+}
+
+impl TxMidstates {
+    fn new(tx: &Tx) -> Self {
+        #[cfg(test)]
+        PREVOUT_HASH_COUNT.with(|count| count.set(count.get() + 1));
+
+        let mut prevouts = Vec::with_capacity(tx.inputs.len() * 36);
+        let mut sequences = Vec::with_capacity(tx.inputs.len() * 4);
+        for input in &tx.inputs {
+            input.outpoint.serialize(&mut prevouts).expect("Writing to a Vec cannot fail.");
+            sequences.extend_from_slice(&input.sequence.to_le_bytes());
+        }
+
+        let outputs: Vec<u8> = tx.outputs.iter().flat_map(|output| output.serialize_to_vec()).collect();
+
+        Self {
+            sha_prevouts: sha256(&prevouts),
+            sha_sequences: sha256(&sequences),
+            sha_outputs: sha256(&outputs),
+        }
+    }
+}
+
+// The per-transaction parts of the BIP143 digest, the same for every input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bip143Midstates {
+    pub hash_prevouts: [u8; 32],
+    pub hash_sequence: [u8; 32],
+    pub hash_outputs: [u8; 32],
+}
+
+impl Bip143Midstates {
+    pub fn new(tx: &Tx) -> Self {
+        Self::from_tx_midstates(&TxMidstates::new(tx))
+    }
+
+    // hash256 is SHA256 applied twice
+    fn from_tx_midstates(midstates: &TxMidstates) -> Self {
+        Self {
+            hash_prevouts: sha256(&midstates.sha_prevouts),
+            hash_sequence: sha256(&midstates.sha_sequences),
+            hash_outputs: sha256(&midstates.sha_outputs),
+        }
+    }
+}
+
+// The per-transaction parts of the BIP341 message, which also commit to every prevout's amount and script_pubkey
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaprootMidstates {
+    pub sha_prevouts: [u8; 32],
+    pub sha_amounts: [u8; 32],
+    pub sha_script_pubkeys: [u8; 32],
+    pub sha_sequences: [u8; 32],
+    pub sha_outputs: [u8; 32],
+}
+
+impl TaprootMidstates {
+    // The prevouts are those the inputs spend, in input order
+    pub fn new(tx: &Tx, prevouts: &[TxOut]) -> Self {
+        Self::from_tx_midstates(&TxMidstates::new(tx), prevouts)
+    }
+
+    fn from_tx_midstates(midstates: &TxMidstates, prevouts: &[TxOut]) -> Self {
+        let mut amounts = Vec::with_capacity(prevouts.len() * 8);
+        let mut script_pubkeys = Vec::new();
+        for prevout in prevouts {
+            prevout.amount.serialize(&mut amounts).expect("Writing to a Vec cannot fail.");
+            write_var_bytes(&mut script_pubkeys, &prevout.script_pubkey).expect("Writing to a Vec cannot fail.");
+        }
+
+        Self {
+            sha_prevouts: midstates.sha_prevouts,
+            sha_amounts: sha256(&amounts),
+            sha_script_pubkeys: sha256(&script_pubkeys),
+            sha_sequences: midstates.sha_sequences,
+            sha_outputs: midstates.sha_outputs,
+        }
+    }
+}
+
+impl Tx {
+    /*
+        The legacy (pre-segwit) signature hash, returned big-endian as the z that ECDSA signs. The signed input's
+        script_sig is replaced by the prevout's script_pubkey and every other script_sig is emptied. NONE and
+        SINGLE also zero the other inputs' sequences, ANYONECANPAY drops the other inputs, and SINGLE blanks the
+        outputs before the input's own and drops those after it. The 4-byte hash type is appended before hash256.
+
+        SIGHASH_SINGLE on an input with no matching output hashes to 1, a bug Bitcoin Core has to keep. An input
+        index that is out of range altogether gives the same value in Core, but is an error here.
+     */
+    pub fn sig_hash(
+        &self,
+        input_index: usize,
+        script_pubkey: &[u8],
+        sighash_type: SighashType,
+    ) -> Result<[u8; 32], TxError> {
+        if input_index >= self.inputs.len() {
+            return Err(TxError::InputIndexOutOfRange { index: input_index, input_count: self.inputs.len() });
+        }
+
+        let base = sighash_type.base();
+        if base == SighashBase::Single && input_index >= self.outputs.len() {
+            return Ok(SIGHASH_SINGLE_BUG);
+        }
+
+        let inputs = self
+            .inputs
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !sighash_type.anyone_can_pay() || *index == input_index)
+            .map(|(index, input)| match index == input_index {
+                true => TxIn { script_sig: script_pubkey.to_vec(), ..input.clone() },
+                false => {
+                    let sequence = match base {
+                        SighashBase::All => input.sequence,
+                        SighashBase::None | SighashBase::Single => 0,
+                    };
+                    TxIn { script_sig: vec![], sequence, ..input.clone() }
+                }
+            })
+            .collect();
+
+        let outputs = match base {
+            SighashBase::All => self.outputs.clone(),
+            SighashBase::None => vec![],
+            SighashBase::Single => {
+                let mut outputs = vec![TxOut::new(Amount::from_sat(u64::MAX), vec![]); input_index];
+                outputs.push(self.outputs[input_index].clone());
+                outputs
+            }
+        };
+
+        let signing_tx = Tx { inputs, outputs, ..self.clone() };
+
+        let mut preimage = Vec::new();
+        signing_tx.serialize_legacy(&mut preimage).expect("Writing to a Vec cannot fail.");
+        preimage.extend_from_slice(&sighash_type.to_u32().to_le_bytes());
+
+        Ok(hash256(&preimage))
+    }
+
+    pub fn bip143_midstates(&self) -> Bip143Midstates {
+        Bip143Midstates::new(self)
+    }
+
+    /*
+        The BIP143 signature hash for a segwit v0 input, returned big-endian like sig_hash. Unlike the legacy
+        digest it commits to the amount being spent. The script code is given without its length prefix: for
+        P2WPKH it is the P2PKH script of the key hash, for P2WSH the witness script. There is no SINGLE bug here,
+        a missing output simply hashes as zeros.
+     */
+    pub fn sig_hash_bip143(
+        &self,
+        input_index: usize,
+        script_code: &[u8],
+        amount: Amount,
+        sighash_type: SighashType,
+    ) -> Result<[u8; 32], TxError> {
+        self.sig_hash_bip143_with(&self.bip143_midstates(), input_index, script_code, amount, sighash_type)
+    }
+
+    // Reuses midstates computed once, so signing many inputs does not rehash the whole transaction for each
+    pub fn sig_hash_bip143_with(
+        &self,
+        midstates: &Bip143Midstates,
+        input_index: usize,
+        script_code: &[u8],
+        amount: Amount,
+        sighash_type: SighashType,
+    ) -> Result<[u8; 32], TxError> {
+        let input = self.inputs.get(input_index).ok_or(TxError::InputIndexOutOfRange {
+            index: input_index,
+            input_count: self.inputs.len(),
+        })?;
+
+        let base = sighash_type.base();
+        let anyone_can_pay = sighash_type.anyone_can_pay();
+        let hash_prevouts = match anyone_can_pay {
+            true => [0; 32],
+            false => midstates.hash_prevouts,
+        };
+        let hash_sequence = match anyone_can_pay || base != SighashBase::All {
+            true => [0; 32],
+            false => midstates.hash_sequence,
+        };
+        let hash_outputs = match (base, self.outputs.get(input_index)) {
+            (SighashBase::All, _) => midstates.hash_outputs,
+            (SighashBase::Single, Some(output)) => hash256(&output.serialize_to_vec()),
+            _ => [0; 32],
+        };
+
+        let mut preimage = Vec::with_capacity(156 + script_code.len());
+        preimage.extend_from_slice(&self.version.to_le_bytes());
+        preimage.extend_from_slice(&hash_prevouts);
+        preimage.extend_from_slice(&hash_sequence);
+        input.outpoint.serialize(&mut preimage).expect("Writing to a Vec cannot fail.");
+        write_var_bytes(&mut preimage, script_code).expect("Writing to a Vec cannot fail.");
+        amount.serialize(&mut preimage).expect("Writing to a Vec cannot fail.");
+        preimage.extend_from_slice(&input.sequence.to_le_bytes());
+        preimage.extend_from_slice(&hash_outputs);
+        preimage.extend_from_slice(&self.locktime.to_le_bytes());
+        preimage.extend_from_slice(&sighash_type.to_u32().to_le_bytes());
+
+        Ok(hash256(&preimage))
+    }
+
+    /*
+        The BIP341 signature message for a taproot key path spend (ext_flag 0). It commits to the amount and
+        script_pubkey of every prevout, given in input order, unless ANYONECANPAY limits it to the signed input.
+        The annex, if the witness has one, is passed with its 0x50 prefix. SIGHASH_SINGLE without a matching
+        output is an error rather than a bug to reproduce, and only the six standard hash types and DEFAULT are
+        allowed.
+     */
+    pub fn sig_msg_taproot(
+        &self,
+        input_index: usize,
+        prevouts: &[TxOut],
+        sighash_type: SighashType,
+        annex: Option<&[u8]>,
+    ) -> Result<Vec<u8>, TxError> {
+        let midstates = TaprootMidstates::new(self, prevouts);
+
+        self.sig_msg_taproot_with(&midstates, input_index, prevouts, sighash_type, annex)
+    }
+
+    // Reuses midstates computed once for these prevouts, as sig_hash_bip143_with does
+    pub fn sig_msg_taproot_with(
+        &self,
+        midstates: &TaprootMidstates,
+        input_index: usize,
+        prevouts: &[TxOut],
+        sighash_type: SighashType,
+        annex: Option<&[u8]>,
+    ) -> Result<Vec<u8>, TxError> {
+        let input = self.inputs.get(input_index).ok_or(TxError::InputIndexOutOfRange {
+            index: input_index,
+            input_count: self.inputs.len(),
+        })?;
+        if prevouts.len() != self.inputs.len() {
+            return Err(TxError::PrevoutCountMismatch { prevout_count: prevouts.len(), input_count: self.inputs.len() });
+        }
+        if !matches!(sighash_type.to_u32(), 0x00..=0x03 | 0x81..=0x83) {
+            return Err(TxError::InvalidSighashType(sighash_type.to_u32()));
+        }
+        let base = sighash_type.base();
+        let anyone_can_pay = sighash_type.anyone_can_pay();
+        if base == SighashBase::Single && input_index >= self.outputs.len() {
+            return Err(TxError::SingleWithoutOutput(input_index));
+        }
+
+        let mut msg = Vec::with_capacity(206);
+        msg.push(sighash_type.to_u32() as u8);
+        msg.extend_from_slice(&self.version.to_le_bytes());
+        msg.extend_from_slice(&self.locktime.to_le_bytes());
+
+        if !anyone_can_pay {
+            msg.extend_from_slice(&midstates.sha_prevouts);
+            msg.extend_from_slice(&midstates.sha_amounts);
+            msg.extend_from_slice(&midstates.sha_script_pubkeys);
+            msg.extend_from_slice(&midstates.sha_sequences);
+        }
+        if base == SighashBase::All {
+            msg.extend_from_slice(&midstates.sha_outputs);
+        }
+
+        // The spend type's ext_flag is 0 for a key path spend
+        msg.push(annex.is_some() as u8);
+        match anyone_can_pay {
+            true => {
+                let prevout = &prevouts[input_index];
+                input.outpoint.serialize(&mut msg).expect("Writing to a Vec cannot fail.");
+                prevout.amount.serialize(&mut msg).expect("Writing to a Vec cannot fail.");
+                write_var_bytes(&mut msg, &prevout.script_pubkey).expect("Writing to a Vec cannot fail.");
+                msg.extend_from_slice(&input.sequence.to_le_bytes());
+            }
+            false => msg.extend_from_slice(&(input_index as u32).to_le_bytes()),
+        }
+        if let Some(annex) = annex {
+            let mut serialized = Vec::with_capacity(annex.len() + 9);
+            write_var_bytes(&mut serialized, annex).expect("Writing to a Vec cannot fail.");
+            msg.extend_from_slice(&sha256(&serialized));
+        }
+        if base == SighashBase::Single {
+            msg.extend_from_slice(&sha256(&self.outputs[input_index].serialize_to_vec()));
+        }
+
+        Ok(msg)
+    }
+
+    // The digest a taproot key path signature signs: the TapSighash tagged hash of epoch 0 and the message
+    pub fn sig_hash_taproot(
+        &self,
+        input_index: usize,
+        prevouts: &[TxOut],
+        sighash_type: SighashType,
+        annex: Option<&[u8]>,
+    ) -> Result<[u8; 32], TxError> {
+        let msg = self.sig_msg_taproot(input_index, prevouts, sighash_type, annex)?;
+
+        Ok(taproot_digest(&msg))
+    }
+}
+
+fn taproot_digest(msg: &[u8]) -> [u8; 32] {
+    tagged_hash("TapSighash", &[&[0x00], msg].concat())
+}
+
+/*
+    Serves the signature hashes of every input of one transaction, computing the transaction-wide midstates
+    the first time a digest needs them. BIP143 and BIP341 share the hashes of the prevouts, sequences and
+    outputs, so a transaction mixing both kinds of input hashes those once too.
+
+    The taproot midstates also cover the prevouts, so the cache keeps those of the first taproot call and
+    rejects any later call that passes different ones.
+ */
+#[derive(Debug)]
+pub struct SighashCache<'a> {
+    tx: &'a Tx,
+    tx_midstates: OnceCell<TxMidstates>,
+    bip143: OnceCell<Bip143Midstates>,
+    taproot: OnceCell<(Vec<TxOut>, TaprootMidstates)>,
+}
+
+impl<'a> SighashCache<'a> {
+    pub fn new(tx: &'a Tx) -> Self {
+        Self { tx, tx_midstates: OnceCell::new(), bip143: OnceCell::new(), taproot: OnceCell::new() }
+    }
+
+    pub fn tx(&self) -> &'a Tx {
+        self.tx
+    }
+
+    // Legacy digests have nothing to share between inputs, so this is the same as Tx::sig_hash
+    pub fn sig_hash(
+        &self,
+        input_index: usize,
+        script_pubkey: &[u8],
+        sighash_type: SighashType,
+    ) -> Result<[u8; 32], TxError> {
+        self.tx.sig_hash(input_index, script_pubkey, sighash_type)
+    }
+
+    pub fn sig_hash_bip143(
+        &self,
+        input_index: usize,
+        script_code: &[u8],
+        amount: Amount,
+        sighash_type: SighashType,
+    ) -> Result<[u8; 32], TxError> {
+        let midstates = self.bip143.get_or_init(|| Bip143Midstates::from_tx_midstates(self.tx_midstates()));
+
+        self.tx.sig_hash_bip143_with(midstates, input_index, script_code, amount, sighash_type)
+    }
+
+    pub fn sig_hash_taproot(
+        &self,
+        input_index: usize,
+        prevouts: &[TxOut],
+        sighash_type: SighashType,
+        annex: Option<&[u8]>,
+    ) -> Result<[u8; 32], TxError> {
+        // Checked before the midstates are built so a wrong count cannot end up cached
+        let input_count = self.tx.inputs.len();
+        if prevouts.len() != input_count {
+            return Err(TxError::PrevoutCountMismatch { prevout_count: prevouts.len(), input_count });
+        }
+        let (cached_prevouts, midstates) = self.taproot.get_or_init(|| {
+            (prevouts.to_vec(), TaprootMidstates::from_tx_midstates(self.tx_midstates(), prevouts))
+        });
+        if cached_prevouts != prevouts {
+            return Err(TxError::PrevoutsChanged);
+        }
+        let msg = self.tx.sig_msg_taproot_with(midstates, input_index, prevouts, sighash_type, annex)?;
+
+        Ok(taproot_digest(&msg))
+    }
+
+    fn tx_midstates(&self) -> &TxMidstates {
+        self.tx_midstates.get_or_init(|| TxMidstates::new(self.tx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ecc::secp256k1::S256Point;
+    use crate::ecc::signature::Signature;
+    use crate::encoding::hex;
+    use crate::hashing::hash256::Hash256;
+    use crate::tx::amount::{Amount, COIN};
+    use crate::tx::fixtures::{FOUR_INPUT_TX_HEX, TX_HEX};
+    use crate::tx::outpoint::OutPoint;
+    use crate::tx::sighash::{SighashBase, SighashCache, SighashType, TaprootMidstates, PREVOUT_HASH_COUNT};
+    use crate::tx::transaction::{Tx, TxError};
+    use crate::tx::tx_in::TxIn;
+    use crate::tx::tx_out::TxOut;
+
+    // The first case of Bitcoin Core's sighash.json: input 2, an empty script and the raw hash type 1864164639
+    const CORE_SIGHASH_TX_HEX: &str = "907c2bc503ade11cc3b04eb2918b6f547b0630ab569273824748c87ea14b0696526c66ba740200000004\
+                                      ab65ababfd1f9bdd4ef073c7afc4ae00da8a66f429c917a0081ad1e1dabce28d373eab81d8628de80200\
+                                      0000096aab5253ab52000052ad042b5f25efb33beec9f3364e8a9139e8439d9d7e26529c3c30b6c3fd89\
+                                      f8684cfd68ea0200000009ab53526500636a52ab599ac2fe02a526ed0400000000085353005163525151\
+                                      64370e010000000003006300ab2ec229";
+
+    // From Bitcoin Core's tx_valid.json: two P2PK inputs of one key, signed with ALL and with ALL|ANYONECANPAY
+    const CORE_ANYONECANPAY_TX_HEX: &str = "01000000020001000000000000000000000000000000000000000000000000000000000000000000\
+                                           004948304502203a0f5f0e1f2bdbcd04db3061d18f3af70e07f4f467cbc1b8116f267025f5360b02\
+                                           2100c792b6e215afc5afc721a351ec413e714305cb749aae3d7fee76621313418df1010100000000\
+                                           02000000000000000000000000000000000000000000000000000000000000000000004847304402\
+                                           205f7530653eea9b38699e476320ab135b74771e1c48b81a5d041e2ca84b9be7a802200ac8d1f40f\
+                                           b026674fe5a5edd3dea715c27baa9baca51ed45ea750ac9dc0a55e81ffffffff0101000000000000\
+                                           00015100000000";
+
+    // From Bitcoin Core's tx_valid.json: input 1 is signed with SIGHASH_SINGLE but the transaction has one output
+    const CORE_SINGLE_BUG_TX_HEX: &str = "0100000002000200000000000000000000000000000000000000000000000000000000000000000000\
+                                         0151ffffffff0001000000000000000000000000000000000000000000000000000000000000000000\
+                                         006b483045022100c9cdd08798a28af9d1baf44a6c77bcc7e279f47dc487c8c899911bc48feaffcc02\
+                                         20503c5c50ae3998a733263c5c0f7061b483e2b56c4c41b456e7d2f5a78a74c077032102d5c25adb51\
+                                         b61339d2b05315791e21bbe80ea470a49db0135720983c905aace0ffffffff01000000000000000001\
+                                         5100000000";
+
+    // The native P2WPKH example from BIP143, one P2PK input and one P2WPKH input
+    // txid e8151a2af31c368a35053ddd4bdb285a8595c769a3ad83e0fa02314a602d4609
+    const BIP143_P2WPKH_HEX: &str = "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000049\
+                                     4830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b19\
+                                     4ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d27965\
+                                     5c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b3\
+                                     7df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2\
+                                     f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01c\
+                                     c44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188\
+                                     368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000";
+
+    // The P2SH-P2WPKH example from BIP143
+    // txid ef48d9d0f595052e0f8cdcf825f7a5e50b6a388a81f206f3f4846e5ecd7a0c23
+    const BIP143_P2SH_P2WPKH_HEX: &str = "01000000000101db6b1b20aa0fd7b23880be2ecbd4a98130974cf4748fb66092ac4d3ceb1a547701000000\
+                                          1716001479091972186c449eb1ded22b78e40d009bdf0089feffffff02b8b4eb0b000000001976a914a457b6\
+                                          84d7f0d539a46a45bbc043f35b59d0d96388ac0008af2f000000001976a914fd270b1ee6abcaea97fea7ad04\
+                                          02e8bd8ad6d77c88ac02473044022047ac8e878352d3ebbde1c94ce3a10d057c24175747116f8288e5d794d1\
+                                          2d482f0220217f36a485cae903c713331d877c1f64677e3622ad4010726870540656fe9dcb012103ad1d8e89\
+                                          212f0b92c74d23bb710c00662ad1470198ac48c43f7d6f93a2a2687392040000";
+
+    // The 6-of-6 P2SH-P2WSH example from BIP143, signed once with each hash type
+    const BIP143_P2SH_P2WSH_UNSIGNED_HEX: &str = "010000000136641869ca081e70f394c6948e8af409e18b619df2ed74aa106c1ca29787b96e0100000000\
+                                                  ffffffff0200e9a435000000001976a914389ffce9cd9ae88dcc0631e88a821ffdbe9bfe2688acc0832f\
+                                                  05000000001976a9147480a33f950689af511e6e84c138dbbd3c3ee41588ac00000000";
+    const BIP143_SIX_OF_SIX_WITNESS_SCRIPT_HEX: &str = "56210307b8ae49ac90a048e9b53357a2354b3334e9c8bee813ecb98e99a7e07e8c3ba3\
+                                                        2103b28f0c28bfab54554ae8c658ac5c3e0ce6e79ad336331f78c428dd43eea8449b21\
+                                                        034b8113d703413d57761b8b9781957b8c0ac1dfe69f492580ca4195f50376ba4a2103\
+                                                        3400f6afecb833092a9a21cfdf1ed1376e58c5d1f47de74683123987e967a8f42103a6\
+                                                        d48b1131e94ba04d9737d61acdaa1322008af9602b3b14862c07a1789aac162102d8b6\
+                                                        61b0b3302ee2f162b09e07a55ad5dfbe673a9f01d9f0c19617681024306b56ae";
+
+    // The keyPathSpending test from BIP341's wallet vectors: nine inputs, seven of them P2TR, unsigned
+    const BIP341_KEY_PATH_TX_HEX: &str = "02000000097de20cbff686da83a54981d2b9bab3586f4ca7e48f57f5b55963115f3b334e9c01\
+                                         0000000000000000d7b7cab57b1393ace2d064f4d4a2cb8af6def61273e127517d44759b6daf\
+                                         dd990000000000fffffffff8e1f583384333689228c5d28eac13366be082dc57441760d95727\
+                                         5419a418420000000000fffffffff0689180aa63b30cb162a73c6d2a38b7eeda2a83ece74310\
+                                         fda0843ad604853b0100000000feffffffaa5202bdf6d8ccd2ee0f0202afbbb7461d9264a25e\
+                                         5bfd3c5a52ee1239e0ba6c0000000000feffffff956149bdc66faa968eb2be2d2faa29718acb\
+                                         fe3941215893a2a3446d32acd050000000000000000000e664b9773b88c09c32cb70a2a3e4da\
+                                         0ced63b7ba3b22f848531bbb1d5d5f4c94010000000000000000e9aa6b8e6c9de67619e6a392\
+                                         4ae25696bb7b694bb677a632a74ef7eadfd4eabf0000000000ffffffffa778eb6a263dc09046\
+                                         4cd125c466b5a99667720b1c110468831d058aa1b82af10100000000ffffffff0200ca9a3b00\
+                                         0000001976a91406afd46bcdfd22ef94ac122aa11f241244a37ecc88ac807840cb0000000020\
+                                         ac9a87f5594be208f8532db38cff670c450ed2fea8fcdefcc9a663f78bab962b0065cd1d";
+
+    fn bip341_key_path_prevouts() -> Vec<TxOut> {
+        [
+            ("512053a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343", 420_000_000),
+            ("5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3", 462_000_000),
+            ("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac", 294_000_000),
+            ("5120e4d810fd50586274face62b8a807eb9719cef49c04177cc6b76a9a4251d5450e", 504_000_000),
+            ("512091b64d5324723a985170e4dc5a0f84c041804f2cd12660fa5dec09fc21783605", 630_000_000),
+            ("00147dd65592d0ab2fe0d0257d571abf032cd9db93dc", 378_000_000),
+            ("512075169f4001aa68f15bbed28b218df1d0a62cbbcf1188c6665110c293c907b831", 672_000_000),
+            ("5120712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5", 546_000_000),
+            ("512077e30a5522dd9f894c3f8b8bd4c4b2cf82ca7da8a3ea6a239655c39c050ab220", 588_000_000),
+        ]
+        .into_iter()
+        .map(|(script_pubkey, amount)| TxOut::new(Amount::from_sat(amount), hex::decode(script_pubkey).unwrap()))
+        .collect()
+    }
+
+    // Three P2TR inputs worth 100_000, 250_000 and 5_000_000_000 sats, a P2WPKH and a P2TR output, locktime 500_000
+    const TAPROOT_TX_HEX: &str = "0200000003010101010101010101010101010101010101010101010101010101010101010100000000\
+                                  00fdffffff0202020202020202020202020202020202020202020202020202020202020202010000\
+                                  0000fcffffff03030303030303030303030303030303030303030303030303030303030303030200\
+                                  000000fbffffff0280a90300000000001600142121212121212121212121212121212121212121c0\
+                                  aff62901000000225120222222222222222222222222222222222222222222222222222222222222\
+                                  222220a10700";
+
+    fn taproot_prevouts() -> Vec<TxOut> {
+        [100_000, 250_000, 5_000_000_000]
+            .into_iter()
+            .enumerate()
+            .map(|(index, amount)| {
+                TxOut::new(Amount::from_sat(amount), [vec![0x51, 0x20], vec![0x10 + index as u8; 32]].concat())
+            })
+            .collect()
+    }
+
+    fn p2pkh_script_code(pubkey_hash_hex: &str) -> Vec<u8> {
+        hex::decode(&format!("76a914{}88ac", pubkey_hash_hex)).unwrap()
+    }
+
+    // 50 inputs spending distinct P2TR outputs into two, with the prevouts in input order
+    fn many_input_spend() -> (Tx, Vec<TxOut>) {
+        let p2tr = |byte: u8| [vec![0x51, 0x20], vec![byte; 32]].concat();
+        let inputs = (0..50u8)
+            .map(|index| TxIn::new(OutPoint::new(Hash256::from_wire_bytes([index; 32]), 1), vec![], 0xfffffffd))
+            .collect();
+        let outputs = vec![TxOut::new(Amount::ONE_BTC, p2tr(0xaa)), TxOut::new(Amount::from_sat(40_000), p2tr(0xbb))];
+        let prevouts = (0..50u8)
+            .map(|index| TxOut::new(Amount::from_sat(2_100_000 + index as u64), p2tr(index)))
+            .collect();
+
+        (Tx::new(2, inputs, outputs, 0, false), prevouts)
+    }
+
+    fn prevout_hash_count() -> usize {
+        PREVOUT_HASH_COUNT.with(|count| count.get())
+    }
+
+    #[test]
+    fn hash_types_are_read_from_the_signature_byte() {
+        let signature = hex::decode("3044022047ac8e878352d3ebbde1c94ce3a10d057c24175747116f8288e5d794d12d482f\
+                                     0220217f36a485cae903c713331d877c1f64677e3622ad4010726870540656fe9dcb83").unwrap();
+
+        let subject = SighashType::from_signature(&signature).unwrap();
+
+        assert_eq!(subject, SighashType::SINGLE_ANYONECANPAY);
+        assert_eq!(subject.base(), SighashBase::Single);
+        assert!(subject.anyone_can_pay());
+        assert_eq!(SighashType::from_signature(&[]), None);
+    }
+
+    #[test]
+    fn non_standard_hash_types_keep_their_value() {
+        let zero = SighashType::from_consensus(0x00);
+        let high_bits = SighashType::from_consensus(0x43);
+
+        assert_eq!(zero.base(), SighashBase::All);
+        assert!(!zero.is_standard());
+        assert_eq!(high_bits.base(), SighashBase::Single);
+        assert_eq!(high_bits.to_u32(), 0x43);
+        assert_eq!(&SighashType::NONE_ANYONECANPAY.to_string(), "NONE|ANYONECANPAY");
+        assert_eq!(&high_bits.to_string(), "0x43");
+    }
+
+    #[test]
+    fn legacy_sig_hash_reproduces_the_chapter_seven_z() {
+        let subject = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+        let script_pubkey = p2pkh_script_code("a802fc56c704ce87c42d7c92eb75e7896bdc41ae");
+
+        let z = subject.sig_hash(0, &script_pubkey, SighashType::ALL).unwrap();
+
+        assert_eq!(&hex::encode(&z), "27e0c5994dec7824e56dec6b2fcb342eb7cdb0d0957c2fce9882f715e85d81a6");
+    }
+
+    // Whether the signature pushed first in the input's script_sig verifies against its legacy digest
+    fn first_signature_verifies(tx: &Tx, input_index: usize, script_pubkey: &[u8], sec_hex: &str) -> bool {
+        let script_sig = &tx.inputs[input_index].script_sig;
+        let signature = &script_sig[1..1 + script_sig[0] as usize];
+        let (der, hash_type) = signature.split_at(signature.len() - 1);
+        let z = tx.sig_hash(input_index, script_pubkey, SighashType::from_consensus(hash_type[0] as u32)).unwrap();
+
+        let pubkey = S256Point::parse_sec(&hex::decode(sec_hex).unwrap()).unwrap();
+        pubkey.verify(&z, &Signature::parse_der(der).unwrap())
+    }
+
+    #[test]
+    fn legacy_sig_hash_matches_core_vectors() {
+        let sighash_case = Tx::parse_exact(&hex::decode(CORE_SIGHASH_TX_HEX).unwrap(), false).unwrap();
+        let anyone_can_pay = Tx::parse_exact(&hex::decode(CORE_ANYONECANPAY_TX_HEX).unwrap(), false).unwrap();
+        let single_bug = Tx::parse_exact(&hex::decode(CORE_SINGLE_BUG_TX_HEX).unwrap(), false).unwrap();
+        let sec = "035e7f0d4d0841bcd56c39337ed086b1a633ee770c1ffdd94ac552a95ac2ce0efc";
+        let p2pk = hex::decode(&format!("21{}ac", sec)).unwrap();
+
+        let z = sighash_case.sig_hash(2, &[], SighashType::from_consensus(1864164639)).unwrap();
+
+        // sighash.json gives the digest as Core displays a uint256, byte-reversed
+        assert_eq!(&hex::encode(&z), "7e3197893b2cb5da782b138d07ba0eeb4c70314daa5c87f6d5f9f36c7a16af31");
+        assert!(first_signature_verifies(&anyone_can_pay, 0, &p2pk, sec));
+        assert!(first_signature_verifies(&anyone_can_pay, 1, &p2pk, sec));
+        // The signed digest is the constant 1, which no script_pubkey changes
+        assert!(first_signature_verifies(&single_bug, 1, &[], "02d5c25adb51b61339d2b05315791e21bbe80ea470a49db0135720983c905aace0"));
+    }
+
+    #[test]
+    fn none_anyonecanpay_ignores_the_other_inputs_and_every_output() {
+        let original = Tx::parse_exact(&hex::decode(FOUR_INPUT_TX_HEX).unwrap(), false).unwrap();
+        let mut modified = original.clone();
+        modified.inputs.remove(3);
+        modified.inputs[0].sequence = 0;
+        modified.outputs = vec![TxOut::new(Amount::ONE_SAT, vec![0x6a])];
+
+        for sighash_type in [SighashType::NONE_ANYONECANPAY, SighashType::ALL] {
+            let original_z = original.sig_hash(1, &[], sighash_type).unwrap();
+            let modified_z = modified.sig_hash(1, &[], sighash_type).unwrap();
+
+            assert_eq!(original_z == modified_z, sighash_type == SighashType::NONE_ANYONECANPAY);
+        }
+    }
+
+    #[test]
+    fn single_commits_only_to_the_matching_output() {
+        let original = Tx::parse_exact(&hex::decode(FOUR_INPUT_TX_HEX).unwrap(), false).unwrap();
+        let mut other_output_changed = original.clone();
+        other_output_changed.outputs[0].amount += Amount::ONE_SAT;
+        let mut own_output_changed = original.clone();
+        own_output_changed.outputs[1].amount += Amount::ONE_SAT;
+
+        let z = original.sig_hash(1, &[], SighashType::SINGLE).unwrap();
+
+        assert_eq!(other_output_changed.sig_hash(1, &[], SighashType::SINGLE).unwrap(), z);
+        assert_ne!(own_output_changed.sig_hash(1, &[], SighashType::SINGLE).unwrap(), z);
+    }
+
+    #[test]
+    fn legacy_single_without_a_matching_output_hashes_to_one() {
+        let subject = Tx::parse_exact(&hex::decode(FOUR_INPUT_TX_HEX).unwrap(), false).unwrap();
+
+        let z = subject.sig_hash(3, &[], SighashType::SINGLE).unwrap();
+
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        assert_eq!(z, one);
+        assert_eq!(subject.sig_hash(3, &[], SighashType::SINGLE_ANYONECANPAY).unwrap(), one);
+    }
+
+    #[test]
+    fn legacy_sig_hash_rejects_an_out_of_range_input() {
+        let subject = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+
+        let result = subject.sig_hash(1, &[], SighashType::SINGLE);
+
+        assert_eq!(result, Err(TxError::InputIndexOutOfRange { index: 1, input_count: 1 }));
+    }
+
+    #[test]
+    fn native_p2wpkh_vector() {
+        let subject = Tx::parse_exact(&hex::decode(BIP143_P2WPKH_HEX).unwrap(), false).unwrap();
+        let script_code = p2pkh_script_code("1d0f172a0ecb48aee1be1f2687d2963ae33f71a1");
+
+        let midstates = subject.bip143_midstates();
+        let amount = Amount::from_sat(600_000_000);
+        let sig_hash = subject.sig_hash_bip143(1, &script_code, amount, SighashType::ALL).unwrap();
+
+        assert_eq!(
+            &hex::encode(&midstates.hash_prevouts),
+            "96b827c8483d4e9b96712b6713a7b68d6e8003a781feba36c31143470b4efd37"
+        );
+        assert_eq!(
+            &hex::encode(&midstates.hash_sequence),
+            "52b0a642eea2fb7ae638c36f6252b6750293dbe574a806984b8e4d8548339a3b"
+        );
+        assert_eq!(
+            &hex::encode(&midstates.hash_outputs),
+            "863ef3e1a92afbfdb97f31ad0fc7683ee943e9abcf2501590ff8f6551f47e5e5"
+        );
+        assert_eq!(&hex::encode(&sig_hash), "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670");
+    }
+
+    #[test]
+    fn p2sh_p2wpkh_vector() {
+        let subject = Tx::parse_exact(&hex::decode(BIP143_P2SH_P2WPKH_HEX).unwrap(), false).unwrap();
+        let script_code = p2pkh_script_code("79091972186c449eb1ded22b78e40d009bdf0089");
+
+        let midstates = subject.bip143_midstates();
+        let sig_hash = subject.sig_hash_bip143(0, &script_code, Amount::from_sat(10 * COIN), SighashType::ALL).unwrap();
+
+        assert_eq!(
+            &hex::encode(&midstates.hash_prevouts),
+            "b0287b4a252ac05af83d2dcef00ba313af78a3e9c329afa216eb3aa2a7b4613a"
+        );
+        assert_eq!(
+            &hex::encode(&midstates.hash_sequence),
+            "18606b350cd8bf565266bc352f0caddcf01e8fa789dd8a15386327cf8cabe198"
+        );
+        assert_eq!(
+            &hex::encode(&midstates.hash_outputs),
+            "de984f44532e2173ca0d64314fcefe6d30da6f8cf27bafa706da61df8a226c83"
+        );
+        assert_eq!(&hex::encode(&sig_hash), "64f3b0f4dd2bb3aa1ce8566d220cc74dda9df97d8490cc81d89d735c92e59fb6");
+    }
+
+    #[test]
+    fn bip143_six_of_six_vector_covers_every_hash_type() {
+        let subject = Tx::parse_exact(&hex::decode(BIP143_P2SH_P2WSH_UNSIGNED_HEX).unwrap(), false).unwrap();
+        let witness_script = hex::decode(BIP143_SIX_OF_SIX_WITNESS_SCRIPT_HEX).unwrap();
+        let amount = Amount::from_sat(987_654_321);
+        let cases = [
+            (SighashType::ALL, "185c0be5263dce5b4bb50a047973c1b6272bfbd0103a89444597dc40b248ee7c"),
+            (SighashType::NONE, "e9733bc60ea13c95c6527066bb975a2ff29a925e80aa14c213f686cbae5d2f36"),
+            (SighashType::SINGLE, "1e1f1c303dc025bd664acb72e583e933fae4cff9148bf78c157d1e8f78530aea"),
+            (SighashType::ALL_ANYONECANPAY, "2a67f03e63a6a422125878b40b82da593be8d4efaafe88ee528af6e5a9955c6e"),
+            (SighashType::NONE_ANYONECANPAY, "781ba15f3779d5542ce8ecb5c18716733a5ee42a6f51488ec96154934e2c890a"),
+            (SighashType::SINGLE_ANYONECANPAY, "511e8e52ed574121fc1b654970395502128263f62662e076dc6baf05c2e6a99b"),
+        ];
+
+        for (sighash_type, expected) in cases {
+            let sig_hash = subject.sig_hash_bip143(0, &witness_script, amount, sighash_type).unwrap();
+
+            assert_eq!(&hex::encode(&sig_hash), expected, "{}", sighash_type);
+        }
+    }
+
+    #[test]
+    fn bip143_single_without_a_matching_output_hashes_zero_outputs() {
+        let mut subject = Tx::parse_exact(&hex::decode(BIP143_P2SH_P2WSH_UNSIGNED_HEX).unwrap(), false).unwrap();
+        subject.outputs.clear();
+
+        let single = subject.sig_hash_bip143(0, &[], Amount::ZERO, SighashType::SINGLE).unwrap();
+        let none = subject.sig_hash_bip143(0, &[], Amount::ZERO, SighashType::from_consensus(0x02)).unwrap();
+
+        let mut one = [0u8; 32];
+        one[0] = 1;
+        assert_ne!(single, one);
+        assert_ne!(single, none);
+    }
+
+    #[test]
+    fn shared_midstates_give_the_same_digest() {
+        let subject = Tx::parse_exact(&hex::decode(BIP143_P2WPKH_HEX).unwrap(), false).unwrap();
+        let script_code = p2pkh_script_code("1d0f172a0ecb48aee1be1f2687d2963ae33f71a1");
+        let midstates = subject.bip143_midstates();
+        let amount = Amount::from_sat(600_000_000);
+
+        for index in 0..subject.inputs.len() {
+            for sighash_type in [SighashType::ALL, SighashType::SINGLE_ANYONECANPAY] {
+                assert_eq!(
+                    subject.sig_hash_bip143_with(&midstates, index, &script_code, amount, sighash_type),
+                    subject.sig_hash_bip143(index, &script_code, amount, sighash_type)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bip143_out_of_range_input_is_an_error() {
+        let subject = Tx::parse_exact(&hex::decode(BIP143_P2WPKH_HEX).unwrap(), false).unwrap();
+
+        let result = subject.sig_hash_bip143(2, &[], Amount::ZERO, SighashType::ALL);
+
+        assert_eq!(result, Err(TxError::InputIndexOutOfRange { index: 2, input_count: 2 }));
+    }
+
+    #[test]
+    fn taproot_key_path_matches_bip341_vectors() {
+        let subject = Tx::parse_exact(&hex::decode(BIP341_KEY_PATH_TX_HEX).unwrap(), false).unwrap();
+        let prevouts = bip341_key_path_prevouts();
+        let midstates = TaprootMidstates::new(&subject, &prevouts);
+        // The input index, its hash type and the sigHash BIP341 gives for it
+        let expected = [
+            (0, 0x03, "2514a6272f85cfa0f45eb907fcb0d121b808ed37c6ea160a5a9046ed5526d555"),
+            (1, 0x83, "325a644af47e8a5a2591cda0ab0723978537318f10e6a63d4eed783b96a71a4d"),
+            (3, 0x01, "bf013ea93474aa67815b1b6cc441d23b64fa310911d991e713cd34c7f5d46669"),
+            (4, 0x00, "4f900a0bae3f1446fd48490c2958b5a023228f01661cda3496a11da502a7f7ef"),
+            (6, 0x02, "15f25c298eb5cdc7eb1d638dd2d45c97c4c59dcaec6679cfc16ad84f30876b85"),
+            (7, 0x82, "cd292de50313804dabe4685e83f923d2969577191a3e1d2882220dca88cbeb10"),
+            (8, 0x81, "cccb739eca6c13a8a89e6e5cd317ffe55669bbda23f2fd37b0f18755e008edd2"),
+        ];
+
+        assert_eq!(
+            hex::encode(&midstates.sha_prevouts),
+            "e3b33bb4ef3a52ad1fffb555c0d82828eb22737036eaeb02a235d82b909c4c3f"
+        );
+        assert_eq!(
+            hex::encode(&midstates.sha_amounts),
+            "58a6964a4f5f8f0b642ded0a8a553be7622a719da71d1f5befcefcdee8e0fde6"
+        );
+        assert_eq!(
+            hex::encode(&midstates.sha_script_pubkeys),
+            "23ad0f61ad2bca5ba6a7693f50fce988e17c3780bf2b1e720cfbb38fbdd52e21"
+        );
+        assert_eq!(
+            hex::encode(&midstates.sha_sequences),
+            "18959c7221ab5ce9e26c3cd67b22c24f8baa54bac281d8e6b05e400e6c3a957e"
+        );
+        assert_eq!(
+            hex::encode(&midstates.sha_outputs),
+            "a2e6dab7c1f0dcd297c8d61647fd17d821541ea69c3cc37dcbad7f90d4eb4bc5"
+        );
+        assert_eq!(
+            hex::encode(&subject.sig_msg_taproot(7, &prevouts, SighashType::from_consensus(0x82), None).unwrap()),
+            "82020000000065cd1d00e9aa6b8e6c9de67619e6a3924ae25696bb7b694bb677a632a74ef7eadfd4eabf00000000804c8b20000000\
+             00225120712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5ffffffff"
+        );
+        for (index, hash_type, digest) in expected {
+            let sighash_type = SighashType::from_consensus(hash_type);
+
+            let result = subject.sig_hash_taproot(index, &prevouts, sighash_type, None).unwrap();
+
+            assert_eq!(hex::encode(&result), digest, "input {}", index);
+        }
+    }
+
+    #[test]
+    fn taproot_annex_is_committed_to() {
+        let subject = Tx::parse_exact(&hex::decode(TAPROOT_TX_HEX).unwrap(), false).unwrap();
+        let annex = hex::decode("50aabb").unwrap();
+
+        let result = subject.sig_hash_taproot(0, &taproot_prevouts(), SighashType::DEFAULT, Some(&annex)).unwrap();
+
+        assert_eq!(hex::encode(&result), "155bb8c7264585072e3b6325b3b9c438f37ff106935a30c4fdf790fa123c2dff");
+    }
+
+    #[test]
+    fn taproot_message_length_follows_bip341() {
+        let subject = Tx::parse_exact(&hex::decode(TAPROOT_TX_HEX).unwrap(), false).unwrap();
+        let prevouts = taproot_prevouts();
+        let length = |sighash_type, annex: Option<&[u8]>| {
+            subject.sig_msg_taproot(0, &prevouts, sighash_type, annex).unwrap().len()
+        };
+
+        // 174 bytes, less 49 for ANYONECANPAY and 32 for NONE, plus 32 for an annex
+        assert_eq!(length(SighashType::DEFAULT, None), 174);
+        assert_eq!(length(SighashType::SINGLE, None), 174);
+        assert_eq!(length(SighashType::NONE, None), 142);
+        assert_eq!(length(SighashType::ALL_ANYONECANPAY, None), 125);
+        assert_eq!(length(SighashType::NONE_ANYONECANPAY, Some(&[0x50])), 125);
+    }
+
+    #[test]
+    fn taproot_sig_hash_rejects_what_bip341_forbids() {
+        let subject = Tx::parse_exact(&hex::decode(TAPROOT_TX_HEX).unwrap(), false).unwrap();
+        let prevouts = taproot_prevouts();
+
+        assert_eq!(
+            subject.sig_hash_taproot(0, &prevouts, SighashType::from_consensus(0x04), None),
+            Err(TxError::InvalidSighashType(0x04))
+        );
+        assert_eq!(
+            subject.sig_hash_taproot(0, &prevouts[..2], SighashType::DEFAULT, None),
+            Err(TxError::PrevoutCountMismatch { prevout_count: 2, input_count: 3 })
+        );
+        assert_eq!(
+            subject.sig_hash_taproot(2, &prevouts, SighashType::SINGLE, None),
+            Err(TxError::SingleWithoutOutput(2))
+        );
+        assert_eq!(
+            subject.sig_hash_taproot(3, &prevouts, SighashType::DEFAULT, None),
+            Err(TxError::InputIndexOutOfRange { index: 3, input_count: 3 })
+        );
+    }
+
+    #[test]
+    fn cached_digests_match_the_direct_ones() {
+        let (tx, prevouts) = many_input_spend();
+        let script_code = p2pkh_script_code("1d0f172a0ecb48aee1be1f2687d2963ae33f71a1");
+        let cache = SighashCache::new(&tx);
+
+        for (index, prevout) in prevouts.iter().enumerate() {
+            for sighash_type in [SighashType::ALL, SighashType::NONE, SighashType::SINGLE_ANYONECANPAY] {
+                let amount = prevout.amount;
+                assert_eq!(
+                    cache.sig_hash_bip143(index, &script_code, amount, sighash_type),
+                    tx.sig_hash_bip143(index, &script_code, amount, sighash_type)
+                );
+                assert_eq!(
+                    cache.sig_hash(index, &script_code, sighash_type),
+                    tx.sig_hash(index, &script_code, sighash_type)
+                );
+            }
+            for sighash_type in [SighashType::DEFAULT, SighashType::NONE, SighashType::ALL_ANYONECANPAY] {
+                assert_eq!(
+                    cache.sig_hash_taproot(index, &prevouts, sighash_type, None),
+                    tx.sig_hash_taproot(index, &prevouts, sighash_type, None)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cache_hashes_the_prevouts_once() {
+        let (tx, prevouts) = many_input_spend();
+        let script_code = p2pkh_script_code("1d0f172a0ecb48aee1be1f2687d2963ae33f71a1");
+        let cache = SighashCache::new(&tx);
+
+        let before = prevout_hash_count();
+        for (index, prevout) in prevouts.iter().enumerate() {
+            cache.sig_hash_bip143(index, &script_code, prevout.amount, SighashType::ALL).unwrap();
+            cache.sig_hash_taproot(index, &prevouts, SighashType::DEFAULT, None).unwrap();
+        }
+        let cached = prevout_hash_count() - before;
+        for (index, prevout) in prevouts.iter().enumerate() {
+            tx.sig_hash_bip143(index, &script_code, prevout.amount, SighashType::ALL).unwrap();
+        }
+        let direct = prevout_hash_count() - before - cached;
+
+        assert_eq!(cached, 1);
+        assert_eq!(direct, 50);
+    }
+
+    #[test]
+    fn cache_rejects_prevouts_other_than_those_it_hashed() {
+        let (tx, prevouts) = many_input_spend();
+        let cache = SighashCache::new(&tx);
+        let mut changed = prevouts.clone();
+        changed[3].amount = Amount::from_sat(1);
+
+        assert_eq!(
+            cache.sig_hash_taproot(0, &prevouts[..2], SighashType::DEFAULT, None),
+            Err(TxError::PrevoutCountMismatch { prevout_count: 2, input_count: prevouts.len() })
+        );
+        assert_eq!(
+            cache.sig_hash_taproot(0, &prevouts, SighashType::DEFAULT, None),
+            tx.sig_hash_taproot(0, &prevouts, SighashType::DEFAULT, None)
+        );
+        assert_eq!(cache.sig_hash_taproot(1, &changed, SighashType::DEFAULT, None), Err(TxError::PrevoutsChanged));
+    }
+}
@@ -0,0 +1,790 @@
+use std::slice;
+use crate::bip32::extended_key::ExtendedPrivateKey;
+use crate::ecc::private_key::PrivateKey;
+use crate::hashing::hash160::hash160;
+use crate::script::interpreter::{Machine, ScriptFlags, TxChecker, TxContext};
+use crate::script::standard::ScriptType;
+use crate::script::taproot::{tweak_private_key, tweak_public_key};
+use crate::script::Script;
+use crate::tx::amount::Amount;
+use crate::tx::psbt::KeySource;
+use crate::tx::sighash::{SighashCache, SighashType};
+use crate::tx::transaction::{Tx, TxError};
+use crate::tx::tx_fetcher::PrevoutFetcher;
+use crate::tx::tx_out::TxOut;
+
+// Where Tx::sign_all and Psbt::sign look up the keys for the outputs a transaction spends
+pub trait KeyProvider {
+    // The key whose SEC pubkey, compressed or uncompressed, has this hash160, for P2PKH and P2WPKH outputs
+    fn key_for_pubkey_hash(&self, pubkey_hash: &[u8; 20]) -> Option<PrivateKey>;
+
+    // The internal key of a P2TR output with no script tree, whose tweak is this x-only output key
+    fn key_for_output_key(&self, output_key: &[u8; 32]) -> Option<PrivateKey>;
+
+    // The key for a pubkey a PSBT records a BIP32 derivation of, which only a master key can work out
+    fn key_for_derivation(&self, _pubkey: &[u8], _source: &KeySource) -> Option<PrivateKey> {
+        None
+    }
+}
+
+// The first key in the list that matches
+impl KeyProvider for [PrivateKey] {
+    fn key_for_pubkey_hash(&self, pubkey_hash: &[u8; 20]) -> Option<PrivateKey> {
+        self.iter().find(|key| p2pkh_pubkey(key, pubkey_hash).is_some()).cloned()
+    }
+
+    fn key_for_output_key(&self, output_key: &[u8; 32]) -> Option<PrivateKey> {
+        self.iter()
+            .find(|key| {
+                tweak_public_key(&key.point().x().to_be_bytes(), None).is_ok_and(|(tweaked, _)| &tweaked == output_key)
+            })
+            .cloned()
+    }
+}
+
+// A master key has the keys of the derivations that start from its fingerprint, besides its own
+impl KeyProvider for ExtendedPrivateKey {
+    fn key_for_pubkey_hash(&self, pubkey_hash: &[u8; 20]) -> Option<PrivateKey> {
+        slice::from_ref(self.private_key()).key_for_pubkey_hash(pubkey_hash)
+    }
+
+    fn key_for_output_key(&self, output_key: &[u8; 32]) -> Option<PrivateKey> {
+        slice::from_ref(self.private_key()).key_for_output_key(output_key)
+    }
+
+    fn key_for_derivation(&self, pubkey: &[u8], source: &KeySource) -> Option<PrivateKey> {
+        if source.fingerprint != self.fingerprint() {
+            return None;
+        }
+        let derived = self.derive_path(&source.path).ok()?;
+        let point = derived.private_key().point();
+
+        (point.sec(true) == pubkey || point.sec(false) == pubkey).then(|| derived.private_key().clone())
+    }
+}
+
+impl Tx {
+    /*
+        Signs a legacy P2PKH input, replacing its script_sig with <signature> <pubkey>. script_pubkey is that of
+        the output the input spends. The pubkey is SEC encoded compressed or uncompressed, whichever hashes to the
+        one the script pays to.
+     */
+    pub fn sign_input(
+        &mut self,
+        input_index: usize,
+        private_key: &PrivateKey,
+        script_pubkey: &[u8],
+        sighash_type: SighashType,
+    ) -> Result<(), TxError> {
+        let pubkey_hash = Script::from_bytes(script_pubkey)
+            .ok()
+            .and_then(|script| script.p2pkh_hash())
+            .ok_or(TxError::UnsupportedInput(input_index))?;
+        let pubkey = p2pkh_pubkey(private_key, &pubkey_hash).ok_or(TxError::WrongKey(input_index))?;
+
+        self.install_p2pkh_signature(input_index, private_key, &pubkey, script_pubkey, sighash_type)
+    }
+
+    // The signing half of sign_input, once the pubkey has been matched to the script_pubkey
+    fn install_p2pkh_signature(
+        &mut self,
+        input_index: usize,
+        private_key: &PrivateKey,
+        pubkey: &[u8],
+        script_pubkey: &[u8],
+        sighash_type: SighashType,
+    ) -> Result<(), TxError> {
+        let z = self.sig_hash(input_index, script_pubkey, sighash_type)?;
+        let mut signature = private_key.sign(&z).der();
+        signature.push(sighash_type.to_u32() as u8);
+
+        self.inputs[input_index].script_sig = Script::p2pkh_unlock(&signature, pubkey).to_bytes();
+
+        Ok(())
+    }
+
+    /*
+        Signs every input with keys from the key provider, picking how from the script_pubkey of the output each
+        one spends. prevouts are those outputs, one per input and in order.
+
+        P2PKH   legacy digest with SIGHASH_ALL, script_sig <signature> <pubkey>
+        P2WPKH  BIP143 digest with SIGHASH_ALL, witness <signature> <compressed pubkey>
+        P2TR    BIP341 digest with SIGHASH_DEFAULT, witness <Schnorr signature> by the tweaked internal key, for
+                outputs with no script tree
+
+        None of the digests cover another input's script_sig or witness, so every input is signed before any is
+        changed, and an input of another type or without a key leaves the whole transaction as it was. With no
+        randomness source in the crate, the Schnorr signatures use zeros for aux_rand, which BIP340 allows.
+     */
+    pub fn sign_all(
+        &mut self,
+        prevouts: &[TxOut],
+        key_provider: &(impl KeyProvider + ?Sized),
+    ) -> Result<(), TxError> {
+        let input_count = self.inputs.len();
+        if prevouts.len() != input_count {
+            return Err(TxError::PrevoutCountMismatch { prevout_count: prevouts.len(), input_count });
+        }
+
+        let cache = SighashCache::new(self);
+        let mut unlocks = Vec::with_capacity(input_count);
+        for (input_index, prevout) in prevouts.iter().enumerate() {
+            let script_pubkey =
+                Script::from_bytes(&prevout.script_pubkey).map_err(|_| TxError::UnsupportedInput(input_index))?;
+            let program = script_pubkey.witness_program().map(|(_, program)| program).unwrap_or_default();
+
+            let unlock = match script_pubkey.classify() {
+                ScriptType::P2pkh => {
+                    let pubkey_hash = script_pubkey.p2pkh_hash().expect("P2PKH scripts have a key hash.");
+                    let private_key =
+                        key_provider.key_for_pubkey_hash(&pubkey_hash).ok_or(TxError::WrongKey(input_index))?;
+                    let pubkey = p2pkh_pubkey(&private_key, &pubkey_hash).ok_or(TxError::WrongKey(input_index))?;
+                    let z = cache.sig_hash(input_index, &prevout.script_pubkey, SighashType::ALL)?;
+
+                    (Script::p2pkh_unlock(&ecdsa_signature(&private_key, &z), &pubkey).to_bytes(), vec![])
+                }
+                ScriptType::P2wpkh => {
+                    let pubkey_hash = program.try_into().expect("P2WPKH programs are 20 bytes.");
+                    let private_key =
+                        key_provider.key_for_pubkey_hash(&pubkey_hash).ok_or(TxError::WrongKey(input_index))?;
+                    // BIP143 only takes compressed keys as standard
+                    let pubkey = private_key.point().sec(true);
+                    if hash160(&pubkey) != pubkey_hash {
+                        return Err(TxError::WrongKey(input_index));
+                    }
+                    let script_code = Script::p2pkh(&pubkey_hash).to_bytes();
+                    let z = cache.sig_hash_bip143(input_index, &script_code, prevout.amount, SighashType::ALL)?;
+
+                    (vec![], vec![ecdsa_signature(&private_key, &z), pubkey])
+                }
+                ScriptType::P2tr => {
+                    let output_key: [u8; 32] = program.try_into().expect("P2TR programs are 32 bytes.");
+                    let tweaked_key = key_provider
+                        .key_for_output_key(&output_key)
+                        .and_then(|internal_key| tweak_private_key(&internal_key, None).ok())
+                        .filter(|tweaked_key| tweaked_key.point().x().to_be_bytes() == output_key)
+                        .ok_or(TxError::WrongKey(input_index))?;
+                    let z = cache.sig_hash_taproot(input_index, prevouts, SighashType::DEFAULT, None)?;
+
+                    (vec![], vec![tweaked_key.sign_schnorr(&z, &[0; 32]).to_vec()])
+                }
+                _ => return Err(TxError::UnsupportedInput(input_index)),
+            };
+            unlocks.push(unlock);
+        }
+
+        for (input, (script_sig, witness)) in self.inputs.iter_mut().zip(unlocks) {
+            input.script_sig = script_sig;
+            input.witness = witness;
+        }
+
+        Ok(())
+    }
+
+    /*
+        Verifies an input against the output it spends, under the rules the flags pick. prevouts are the outputs
+        every input spends, one per input and in order as sign_all takes them: a taproot digest commits to all of
+        them, while the other digests only need this input's script_pubkey and amount.
+
+        The script_sig and the script_pubkey run one after the other as consensus runs them, followed by the
+        witness of a version 0 or taproot key path program, native or nested in P2SH. Each signature is checked
+        against the digest for its own hash type byte: the legacy one with the script_pubkey or redeem script as
+        the scriptCode, BIP143's for a version 0 witness script and BIP341's for a taproot key. Timelocks are
+        checked against this transaction. Like evaluate, a script that does not parse or fails to run gives false.
+     */
+    pub fn verify_input(&self, input_index: usize, prevouts: &[TxOut], flags: ScriptFlags) -> Result<bool, TxError> {
+        let input_count = self.inputs.len();
+        let input =
+            self.inputs.get(input_index).ok_or(TxError::InputIndexOutOfRange { index: input_index, input_count })?;
+        if prevouts.len() != input_count {
+            return Err(TxError::PrevoutCountMismatch { prevout_count: prevouts.len(), input_count });
+        }
+
+        let scripts = (Script::from_bytes(&input.script_sig), Script::from_bytes(&prevouts[input_index].script_pubkey));
+        let (Ok(script_sig), Ok(script_pubkey)) = scripts else {
+            return Ok(false);
+        };
+
+        let mut machine = Machine::new(vec![], [0; 32]);
+        machine.flags = flags;
+        machine.context = TxContext::new(self, input_index);
+        machine.checker = Some(TxChecker::new(self, input_index, prevouts));
+
+        Ok(machine.verify_spend(&script_sig, &script_pubkey, &input.witness) == Ok(true))
+    }
+
+    /*
+        Verifies the whole transaction under today's consensus rules: the outputs it spends, looked up through the
+        fetcher, have to cover its outputs, and every input has to verify against them. The first input that does
+        not is an error naming it, while outputs worth more than the inputs give false.
+
+        A coinbase spends no outputs, so there is nothing to fetch and its script_sig is not a script to check.
+        What its outputs may claim depends on the block's subsidy and fees, which need the block, so it is true.
+     */
+    pub fn verify(&self, fetcher: &mut impl PrevoutFetcher) -> Result<bool, TxError> {
+        if self.is_coinbase() {
+            return Ok(true);
+        }
+
+        let prevouts = self
+            .inputs
+            .iter()
+            .map(|input| input.prev_output(fetcher, self.testnet))
+            .collect::<Result<Vec<_>, _>>()?;
+        let input_total = prevouts
+            .iter()
+            .try_fold(Amount::ZERO, |total, prevout| total.checked_add(prevout.amount))
+            .ok_or(TxError::AmountOverflow)?;
+        let output_total = self
+            .outputs
+            .iter()
+            .try_fold(Amount::ZERO, |total, output| total.checked_add(output.amount))
+            .ok_or(TxError::AmountOverflow)?;
+        if output_total > input_total {
+            return Ok(false);
+        }
+
+        for input_index in 0..self.inputs.len() {
+            if !self.verify_input(input_index, &prevouts, ScriptFlags::CONSENSUS)? {
+                return Err(TxError::InvalidInput(input_index));
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+// The SEC encoding of the key, compressed or uncompressed, that hashes to the one a P2PKH script pays to
+pub(crate) fn p2pkh_pubkey(private_key: &PrivateKey, pubkey_hash: &[u8; 20]) -> Option<Vec<u8>> {
+    [true, false]
+        .map(|compressed| private_key.point().sec(compressed))
+        .into_iter()
+        .find(|sec| hash160(sec) == *pubkey_hash)
+}
+
+// A DER signature followed by the SIGHASH_ALL byte, as script_sigs and v0 witnesses carry it
+fn ecdsa_signature(private_key: &PrivateKey, z: &[u8; 32]) -> Vec<u8> {
+    let mut signature = private_key.sign(z).der();
+    signature.push(SighashType::ALL.to_u32() as u8);
+
+    signature
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::ecc::private_key::PrivateKey;
+    use crate::ecc::secp256k1::{S256Point, VerifyError};
+    use crate::ecc::signature::DerError;
+    use crate::ecc::u256::U256;
+    use crate::encoding::encodable::Encodable;
+    use crate::encoding::hex;
+    use crate::hashing::hash160::hash160;
+    use crate::hashing::hash256::Hash256;
+    use crate::hashing::sha256::sha256;
+    use crate::script::interpreter::{cast_to_bool, Machine, ScriptError, ScriptFlags};
+    use crate::script::opcodes::{OP_1, OP_CHECKSIG, OP_CODESEPARATOR, OP_DROP, OP_ENDIF, OP_IF};
+    use crate::script::taproot::tweak_public_key;
+    use crate::script::{Cmd, Script};
+    use crate::tx::amount::Amount;
+    use crate::tx::fixtures::{FOUR_INPUT_TX_HEX, PREV_SCRIPT_PUBKEY, TX_HEX};
+    use crate::tx::outpoint::OutPoint;
+    use crate::tx::sighash::SighashType;
+    use crate::tx::signing::{ecdsa_signature, KeyProvider};
+    use crate::tx::transaction::{Tx, TxError};
+    use crate::tx::tx_in::TxIn;
+    use crate::tx::tx_out::TxOut;
+
+    // The Programming Bitcoin chapter 8 2-of-2 P2SH multisig spend, mainnet
+    // 46df1a9484d0a81d03ce0ee543ab6e1a23ed06175c104a178268fad381216c2b, and the hash of the redeem script it spends
+    const P2SH_MULTISIG_HEX: &str = "0100000001868278ed6ddfb6c1ed3ad5f8181eb0c7a385aa0836f01d5e4789e6bd304d87221a000000db004830\
+                                     45022100dc92655fe37036f47756db8102e0d7d5e28b3beb83a8fef4f5dc0559bddfb94e02205a36d4e4e6c7fc\
+                                     d16658c50783e00c341609977aed3ad00937bf4ee942a8993701483045022100da6bee3c93766232079a01639d\
+                                     07fa869598749729ae323eab8eef53577d611b02207bef15429dcadce2121ea07f233115c6f09034c0be68db99\
+                                     980b9a6c5e75402201475221022626e955ea6ea6d98850c994f9107b036b1334f18ca8830bfff1295d21cfdb70\
+                                     2103b287eaf122eea69030a0e9feed096bed8045c8b98bec453e1ffac7fbdbd4bb7152aeffffffff04d3b11400\
+                                     000000001976a914904a49878c0adfc3aa05de7afad2cc15f483a56a88ac7f400900000000001976a914418327\
+                                     e3f3dda4cf5b9089325a4b95abdfa0334088ac722c0c00000000001976a914ba35042cfe9fc66fd35ac2224eeb\
+                                     dafd1028ad2788acdc4ace020000000017a91474d691da1574e6b3c192ecfb52cc8984ee7b6c568700000000";
+    const P2SH_MULTISIG_REDEEM_SCRIPT_HASH_HEX: &str = "74d691da1574e6b3c192ecfb52cc8984ee7b6c56";
+
+    /*
+        Mainnet 23b397edccd3740a74adb603c9756370fafcde9bcc4483eb271ecad09a94dd63, the first standard 1-of-2 bare
+        multisig spend, and the script_pubkey it spends. Its signature's s has the top bit set with no zero byte
+        in front, a negative integer that OpenSSL accepted and strict DER rejects.
+     */
+    const LAX_DER_TX_HEX: &str = "0100000001b14bdcbc3e01bdaad36cc08e81e69c82e1060bc14e518db2b49aa43ad90ba2600000000049004730\
+                                 4402203f16c6f40162ab686621ef3000b04e75418a0c0cb2d8aebeac894ae360ac1e780220ddc15ecdfc3507ac\
+                                 48e1681a33eb60996631bf6bf5bc0a0682c4db743ce7ca2b01ffffffff0140420f00000000001976a914660d4e\
+                                 f3a743e3e696ad990364e555c271ad504b88ac00000000";
+    const LAX_DER_PREV_SCRIPT_PUBKEY: &str = "514104cc71eb30d653c0c3163990c47b976f3fb3f37cccdcbedb169a1dfef58bbfbfaff7d8a473\
+                                             e7e2e6d317b87bafe8bde97e3cf8f065dec022b51d11fcdd0d348ac4410461cbdcc5409fb4b4d4\
+                                             2b51d33381354d80e550078cb532a34bfa2fcfdeb7d76519aecc62770f5b0e4ef8551946d8a540\
+                                             911abe3e7854a26f39f58b25c15342af52ae";
+
+    fn private_key(secret: u64) -> PrivateKey {
+        PrivateKey::new(U256::from_u64(secret)).unwrap()
+    }
+
+    fn p2pkh_for(private_key: &PrivateKey, compressed: bool) -> Vec<u8> {
+        Script::p2pkh(&hash160(&private_key.point().sec(compressed))).to_bytes()
+    }
+
+    fn unsigned_tx() -> Tx {
+        let prev_txid = hex::decode("452c629d67e41baec3ac6f04fe744b4b9617f8f859c63b3002f8684e7a4fee03").unwrap();
+        let prev_txid = Hash256::from_display_bytes(prev_txid.try_into().unwrap());
+        let inputs = vec![TxIn::new(OutPoint::new(prev_txid, 0), vec![], 0xffffffff)];
+        let outputs = vec![TxOut::new(Amount::from_sat(40_000), p2pkh_for(&private_key(2), true))];
+
+        Tx::new(1, inputs, outputs, 0, false)
+    }
+
+    // Outputs for legacy inputs to spend, whose digests do not commit to the amount
+    fn legacy_prevouts(script_pubkeys: &[impl AsRef<[u8]>]) -> Vec<TxOut> {
+        script_pubkeys.iter().map(|script_pubkey| TxOut::new(Amount::ZERO, script_pubkey.as_ref().to_vec())).collect()
+    }
+
+    fn ops(opcodes: &[u8]) -> Vec<Cmd> {
+        opcodes.iter().map(|opcode| Cmd::Op(*opcode)).collect()
+    }
+
+    fn verify(tx: &Tx, script_pubkey: &[u8]) -> bool {
+        tx.verify_input(0, &legacy_prevouts(&[script_pubkey]), ScriptFlags::CONSENSUS).unwrap()
+    }
+
+    #[test]
+    fn signed_p2pkh_inputs_evaluate_to_true() {
+        let private_key = private_key(8675309);
+
+        for compressed in [true, false] {
+            let script_pubkey = p2pkh_for(&private_key, compressed);
+            let mut tx = unsigned_tx();
+
+            tx.sign_input(0, &private_key, &script_pubkey, SighashType::ALL).unwrap();
+
+            assert!(verify(&tx, &script_pubkey), "compressed {}", compressed);
+        }
+    }
+
+    #[test]
+    fn changing_the_signed_transaction_invalidates_it() {
+        let private_key = private_key(8675309);
+        let script_pubkey = p2pkh_for(&private_key, true);
+        let mut tx = unsigned_tx();
+        tx.sign_input(0, &private_key, &script_pubkey, SighashType::ALL).unwrap();
+
+        tx.outputs[0].amount = Amount::from_sat(50_000);
+
+        assert!(!verify(&tx, &script_pubkey));
+    }
+
+    #[test]
+    fn signing_reproduces_the_programming_bitcoin_chapter_7_example() {
+        /*
+            The chapter signs its chapter 5 transaction with the secret 8675309 and prints the result below. That
+            key does not own the output being spent, so sign_input would turn it away before signing; the steps
+            after that check are what the example covers.
+         */
+        let expected = "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006a4730440220\
+                        7db2402a3311a3b845b038885e3dd889c08126a8570f26a844e3e4049c482a11022010178cdca4129eacbeab7c4464\
+                        8bf5ac1f9cac217cd609d216ec2ebc8d242c0a012103935581e52c354cd2f484fe8ed83af7a3097005b2f9c60bff71\
+                        d35bd795f54b67feffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99\
+                        c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600";
+        let private_key = private_key(8675309);
+        let script_pubkey = hex::decode(PREV_SCRIPT_PUBKEY).unwrap();
+        let mut tx = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+
+        let pubkey = private_key.point().sec(true);
+        tx.install_p2pkh_signature(0, &private_key, &pubkey, &script_pubkey, SighashType::ALL).unwrap();
+
+        assert_eq!(hex::encode(&tx.serialize_to_vec()), expected);
+        assert_eq!(tx.sign_input(0, &private_key, &script_pubkey, SighashType::ALL), Err(TxError::WrongKey(0)));
+    }
+
+    #[test]
+    fn only_matching_p2pkh_inputs_can_be_signed() {
+        let mut tx = unsigned_tx();
+
+        let wrong_key = tx.sign_input(0, &private_key(1), &p2pkh_for(&private_key(2), true), SighashType::ALL);
+        let not_p2pkh = tx.sign_input(0, &private_key(1), &[0x51], SighashType::ALL);
+        let out_of_range = tx.sign_input(1, &private_key(2), &p2pkh_for(&private_key(2), true), SighashType::ALL);
+
+        assert_eq!(wrong_key, Err(TxError::WrongKey(0)));
+        assert_eq!(not_p2pkh, Err(TxError::UnsupportedInput(0)));
+        assert_eq!(out_of_range, Err(TxError::InputIndexOutOfRange { index: 1, input_count: 1 }));
+        assert!(tx.inputs[0].script_sig.is_empty());
+    }
+
+    #[test]
+    fn lax_der_signature_verifies_only_without_dersig() {
+        let tx = Tx::parse_exact(&hex::decode(LAX_DER_TX_HEX).unwrap(), false).unwrap();
+        let script_pubkey = hex::decode(LAX_DER_PREV_SCRIPT_PUBKEY).unwrap();
+        let z = tx.sig_hash(0, &script_pubkey, SighashType::ALL).unwrap();
+
+        let prevouts = legacy_prevouts(&[&script_pubkey]);
+
+        let pre_bip66 = ScriptFlags::from_bits(ScriptFlags::CONSENSUS.bits() & !ScriptFlags::VERIFY_DERSIG.bits());
+        assert_eq!(tx.verify_input(0, &prevouts, pre_bip66), Ok(true));
+        assert_eq!(tx.verify_input(0, &prevouts, ScriptFlags::CONSENSUS), Ok(false));
+        let script_sig = Script::from_bytes(&tx.inputs[0].script_sig).unwrap();
+        let combined = script_sig + Script::from_bytes(&script_pubkey).unwrap();
+        assert_eq!(combined.evaluate(z, None, None, ScriptFlags::CONSENSUS), Err(ScriptError::SignatureDer));
+    }
+
+    #[test]
+    fn real_p2pkh_input_verifies() {
+        let tx = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+        let prevouts = legacy_prevouts(&[&hex::decode(PREV_SCRIPT_PUBKEY).unwrap()]);
+        let mut tampered = tx.clone();
+        tampered.locktime += 1;
+
+        assert_eq!(tx.verify_input(0, &prevouts, ScriptFlags::CONSENSUS), Ok(true));
+        assert_eq!(tampered.verify_input(0, &prevouts, ScriptFlags::CONSENSUS), Ok(false));
+        assert_eq!(tx.verify_input(0, &legacy_prevouts(&[&[0x4c]]), ScriptFlags::CONSENSUS), Ok(false));
+        assert_eq!(
+            tx.verify_input(1, &prevouts, ScriptFlags::CONSENSUS),
+            Err(TxError::InputIndexOutOfRange { index: 1, input_count: 1 })
+        );
+        assert_eq!(
+            tx.verify_input(0, &[], ScriptFlags::CONSENSUS),
+            Err(TxError::PrevoutCountMismatch { prevout_count: 0, input_count: 1 })
+        );
+    }
+
+    // The pushes of the input's script_sig, and the digest its signatures sign with SIGHASH_ALL
+    fn pushes_and_z(tx_hex: &str, script_pubkey: &str) -> (Vec<Vec<u8>>, [u8; 32]) {
+        let tx = Tx::parse_exact(&hex::decode(tx_hex).unwrap(), false).unwrap();
+        let z = tx.sig_hash(0, &hex::decode(script_pubkey).unwrap(), SighashType::ALL).unwrap();
+        let script_sig = Script::from_bytes(&tx.inputs[0].script_sig).unwrap();
+        let pushes = script_sig
+            .cmds
+            .into_iter()
+            .filter_map(|cmd| match cmd {
+                Cmd::Push { data, .. } => Some(data),
+                Cmd::Op(_) => None,
+            })
+            .collect();
+
+        (pushes, z)
+    }
+
+    #[test]
+    fn real_signatures_are_held_to_strict_encoding() {
+        let (pushes, z) = pushes_and_z(TX_HEX, PREV_SCRIPT_PUBKEY);
+        let (signature, pubkey) = (&pushes[0], &pushes[1]);
+        let point = S256Point::parse_sec(pubkey).unwrap();
+
+        assert_eq!(point.verify_strict(&z, &signature[..signature.len() - 1]), Ok(()));
+
+        // The negative s of the lax DER spend fails before any key is needed
+        let (pushes, z) = pushes_and_z(LAX_DER_TX_HEX, LAX_DER_PREV_SCRIPT_PUBKEY);
+        let signature = &pushes[1];
+
+        assert_eq!(
+            point.verify_strict(&z, &signature[..signature.len() - 1]),
+            Err(VerifyError::Der(DerError::InvalidInteger { position: 36 }))
+        );
+    }
+
+    // The P2PKH script_pubkey of the pubkey in each script_sig, which is what each input of FOUR_INPUT_TX_HEX spends
+    fn four_input_script_pubkeys(tx: &Tx) -> Vec<Vec<u8>> {
+        tx.inputs
+            .iter()
+            .map(|input| {
+                let script_sig = Script::from_bytes(&input.script_sig).unwrap();
+                let Some(Cmd::Push { data: pubkey, .. }) = script_sig.cmds.last() else { panic!("no pubkey") };
+
+                Script::p2pkh(&hash160(pubkey)).to_bytes()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_input_of_real_p2pkh_transactions_verifies() {
+        let four_inputs = Tx::parse_exact(&hex::decode(FOUR_INPUT_TX_HEX).unwrap(), false).unwrap();
+        let four_prevouts = legacy_prevouts(&four_input_script_pubkeys(&four_inputs));
+        let one_input = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+        let one_prevout = legacy_prevouts(&[&hex::decode(PREV_SCRIPT_PUBKEY).unwrap()]);
+        let cases: Vec<_> = (0..4)
+            .map(|index| (&four_inputs, index, &four_prevouts))
+            .chain([(&one_input, 0, &one_prevout)])
+            .collect();
+
+        assert_eq!(four_inputs.id().to_string(), "ee51510d7bbabe28052038d1deb10c03ec74f06a79e21913c6fcf48d56217c87");
+        for (tx, index, prevouts) in cases {
+            // A byte of r, past the push length and the DER headers
+            let mut corrupted = tx.clone();
+            corrupted.inputs[index].script_sig[10] ^= 1;
+
+            assert_eq!(tx.verify_input(index, prevouts, ScriptFlags::CONSENSUS), Ok(true), "input {}", index);
+            assert_eq!(corrupted.verify_input(index, prevouts, ScriptFlags::CONSENSUS), Ok(false));
+        }
+    }
+
+    #[test]
+    fn real_p2sh_input_verifies_against_its_redeem_script() {
+        let tx = Tx::parse_exact(&hex::decode(P2SH_MULTISIG_HEX).unwrap(), false).unwrap();
+        let redeem_script_hash = hex::decode(P2SH_MULTISIG_REDEEM_SCRIPT_HASH_HEX).unwrap();
+        let prevouts = legacy_prevouts(&[&Script::p2sh(&redeem_script_hash.try_into().unwrap()).to_bytes()]);
+        let mut tampered = tx.clone();
+        tampered.outputs[0].amount = Amount::from_sat(1);
+
+        assert_eq!(tx.verify_input(0, &prevouts, ScriptFlags::CONSENSUS), Ok(true));
+        assert_eq!(tampered.verify_input(0, &prevouts, ScriptFlags::CONSENSUS), Ok(false));
+    }
+
+    #[test]
+    fn legacy_signatures_do_not_sign_over_themselves() {
+        let key = private_key(31);
+        let pubkey = key.point().sec(true);
+        let mut tx = unsigned_tx();
+
+        // The signature is part of the script_pubkey it unlocks, which is only possible as its digest leaves it out
+        let script_code = Script::new(vec![Cmd::Op(OP_DROP), Cmd::push(pubkey), Cmd::Op(OP_CHECKSIG)]);
+        let signature = ecdsa_signature(&key, &tx.sig_hash(0, &script_code.to_bytes(), SighashType::ALL).unwrap());
+        let script_pubkey = Script::new([vec![Cmd::push(signature.clone())], script_code.cmds].concat()).to_bytes();
+        tx.inputs[0].script_sig = Script::new(vec![Cmd::push(signature)]).to_bytes();
+
+        assert!(verify(&tx, &script_pubkey));
+    }
+
+    #[test]
+    fn legacy_signatures_only_cover_the_script_after_an_executed_code_separator() {
+        let key = private_key(32);
+        let signed_part = vec![Cmd::push(key.point().sec(true)), Cmd::Op(OP_CHECKSIG)];
+        let mut tx = unsigned_tx();
+        let z = tx.sig_hash(0, &Script::new(signed_part.clone()).to_bytes(), SighashType::ALL).unwrap();
+        tx.inputs[0].script_sig = Script::new(vec![Cmd::push(ecdsa_signature(&key, &z))]).to_bytes();
+
+        let separated = Script::new([ops(&[OP_1, OP_DROP, OP_CODESEPARATOR]), signed_part.clone()].concat());
+        // In a branch that is not taken the separator does nothing, so the whole script is signed for
+        let skipped = [vec![Cmd::push(vec![])], ops(&[OP_IF, OP_CODESEPARATOR, OP_ENDIF]), signed_part.clone()];
+        let skipped = Script::new(skipped.concat());
+
+        assert!(verify(&tx, &separated.to_bytes()));
+        assert!(!verify(&tx, &skipped.to_bytes()));
+        // The legacy digest leaves every OP_CODESEPARATOR out of what it signs
+        let without_separator = skipped.cmds.iter().filter(|cmd| **cmd != Cmd::Op(OP_CODESEPARATOR)).cloned();
+        let mut signed_whole = tx.clone();
+        let z = tx.sig_hash(0, &Script::new(without_separator.collect()).to_bytes(), SighashType::ALL).unwrap();
+        signed_whole.inputs[0].script_sig = Script::new(vec![Cmd::push(ecdsa_signature(&key, &z))]).to_bytes();
+        assert!(verify(&signed_whole, &skipped.to_bytes()));
+    }
+
+    #[test]
+    fn sighash_single_input_commits_only_to_its_own_output() {
+        let signer = private_key(8675309);
+        let script_pubkey = p2pkh_for(&signer, true);
+        let mut tx = unsigned_tx();
+        tx.inputs.push(TxIn::new(OutPoint::new(Hash256::from_wire_bytes([0x11; 32]), 3), vec![], 0xffffffff));
+        tx.outputs.push(TxOut::new(Amount::from_sat(10_000), p2pkh_for(&private_key(3), true)));
+        tx.sign_input(0, &signer, &script_pubkey, SighashType::SINGLE).unwrap();
+
+        let mut other_output_changed = tx.clone();
+        other_output_changed.outputs[1].amount = Amount::from_sat(5_000);
+        other_output_changed.inputs[1].sequence = 0;
+        let mut own_output_changed = tx.clone();
+        own_output_changed.outputs[0].amount = Amount::from_sat(30_000);
+        let prevouts = legacy_prevouts(&[&script_pubkey, &p2pkh_for(&private_key(4), true)]);
+
+        assert_eq!(tx.verify_input(0, &prevouts, ScriptFlags::CONSENSUS), Ok(true));
+        assert_eq!(other_output_changed.verify_input(0, &prevouts, ScriptFlags::CONSENSUS), Ok(true));
+        assert_eq!(own_output_changed.verify_input(0, &prevouts, ScriptFlags::CONSENSUS), Ok(false));
+    }
+
+    #[test]
+    fn combined_evaluation_matches_running_the_scripts_in_turn() {
+        let tx = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+        let script_pubkey_bytes = hex::decode(PREV_SCRIPT_PUBKEY).unwrap();
+        let z = tx.sig_hash(0, &script_pubkey_bytes, SighashType::ALL).unwrap();
+        let script_sig = Script::from_bytes(&tx.inputs[0].script_sig).unwrap();
+        let script_pubkey = Script::from_bytes(&script_pubkey_bytes).unwrap();
+
+        for z in [z, [0; 32]] {
+            let mut machine = Machine::new(vec![], z);
+            let two_phase = script_sig.run(&mut machine).and_then(|_| script_pubkey.run(&mut machine));
+            let two_phase = two_phase.map(|_| machine.stack.last().is_some_and(|top| cast_to_bool(top)));
+
+            assert_eq!((&script_sig + &script_pubkey).evaluate(z, None, None, ScriptFlags::CONSENSUS), two_phase);
+        }
+    }
+
+    #[test]
+    fn legacy_inputs_with_a_witness_fail_under_segwit_rules() {
+        let mut tx = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+        tx.inputs[0].witness = vec![vec![]];
+        let prevouts = legacy_prevouts(&[&hex::decode(PREV_SCRIPT_PUBKEY).unwrap()]);
+
+        let pre_segwit = ScriptFlags::from_bits(ScriptFlags::CONSENSUS.bits() & !ScriptFlags::VERIFY_WITNESS.bits());
+        assert_eq!(tx.verify_input(0, &prevouts, ScriptFlags::CONSENSUS), Ok(false));
+        assert_eq!(tx.verify_input(0, &prevouts, pre_segwit), Ok(true));
+    }
+
+    // A P2PKH, a P2WPKH and a P2TR output, each paying to its own key, and a transaction spending all three
+    fn mixed_spend() -> (Vec<PrivateKey>, Vec<TxOut>, Tx) {
+        let keys = vec![private_key(11), private_key(12), private_key(13)];
+        let (output_key, _) = tweak_public_key(&keys[2].point().x().to_be_bytes(), None).unwrap();
+        let prevouts = vec![
+            TxOut::new(Amount::from_sat(30_000), p2pkh_for(&keys[0], true)),
+            TxOut::new(Amount::from_sat(40_000), Script::p2wpkh(&hash160(&keys[1].point().sec(true))).to_bytes()),
+            TxOut::new(Amount::from_sat(50_000), Script::p2tr(&output_key).to_bytes()),
+        ];
+
+        let mut tx = unsigned_tx();
+        let outpoint = tx.inputs[0].outpoint;
+        tx.inputs = (0..3).map(|index| TxIn::new(OutPoint::new(outpoint.txid, index), vec![], 0xfffffffd)).collect();
+        tx.outputs[0].amount = Amount::from_sat(110_000);
+
+        (keys, prevouts, tx)
+    }
+
+    #[test]
+    fn sign_all_signs_legacy_segwit_and_taproot_inputs() {
+        let (keys, prevouts, mut tx) = mixed_spend();
+        // In a different order from the inputs, so each key has to be looked up
+        let key_provider = [keys[2].clone(), keys[0].clone(), keys[1].clone()];
+
+        tx.sign_all(&prevouts, &key_provider[..]).unwrap();
+
+        for index in 0..3 {
+            assert_eq!(tx.verify_input(index, &prevouts, ScriptFlags::CONSENSUS), Ok(true), "input {}", index);
+        }
+        assert!(tx.inputs[0].witness.is_empty());
+
+        let p2wpkh = Script::from_bytes(&prevouts[1].script_pubkey).unwrap();
+        let script_code = p2pkh_for(&keys[1], true);
+        let z = tx.sig_hash_bip143(1, &script_code, prevouts[1].amount, SighashType::ALL).unwrap();
+        assert!(tx.inputs[1].script_sig.is_empty());
+        assert_eq!(p2wpkh.evaluate(z, Some(&tx.inputs[1].witness), None, ScriptFlags::CONSENSUS), Ok(true));
+
+        let p2tr = Script::from_bytes(&prevouts[2].script_pubkey).unwrap();
+        let z = tx.sig_hash_taproot(2, &prevouts, SighashType::DEFAULT, None).unwrap();
+        assert_eq!(tx.inputs[2].witness.len(), 1);
+        assert_eq!(p2tr.evaluate(z, Some(&tx.inputs[2].witness), None, ScriptFlags::CONSENSUS), Ok(true));
+
+        // The taproot digest covers every amount spent, so the signature no longer holds once one changes
+        let mut other_amounts = prevouts.clone();
+        other_amounts[0].amount = Amount::from_sat(30_001);
+        let z = tx.sig_hash_taproot(2, &other_amounts, SighashType::DEFAULT, None).unwrap();
+        assert_eq!(p2tr.evaluate(z, Some(&tx.inputs[2].witness), None, ScriptFlags::CONSENSUS), Ok(false));
+        assert_eq!(tx.verify_input(2, &other_amounts, ScriptFlags::CONSENSUS), Ok(false));
+    }
+
+    #[test]
+    fn nested_and_script_hash_witness_inputs_verify_against_their_amounts() {
+        let key = private_key(21);
+        let pubkey = key.point().sec(true);
+        let nested = Script::p2wpkh(&hash160(&pubkey)).to_bytes();
+        let witness_script = Script::new(vec![Cmd::push(pubkey.clone()), Cmd::Op(OP_CHECKSIG)]).to_bytes();
+        let prevouts = vec![
+            TxOut::new(Amount::from_sat(60_000), Script::p2sh(&hash160(&nested)).to_bytes()),
+            TxOut::new(Amount::from_sat(70_000), Script::p2wsh(&sha256(&witness_script)).to_bytes()),
+        ];
+        let mut tx = unsigned_tx();
+        let outpoint = tx.inputs[0].outpoint;
+        tx.inputs = (0..2).map(|index| TxIn::new(OutPoint::new(outpoint.txid, index), vec![], 0xfffffffd)).collect();
+
+        let z = tx.sig_hash_bip143(0, &p2pkh_for(&key, true), prevouts[0].amount, SighashType::ALL).unwrap();
+        tx.inputs[0].script_sig = Script::new(vec![Cmd::push(nested)]).to_bytes();
+        tx.inputs[0].witness = vec![ecdsa_signature(&key, &z), pubkey];
+        let z = tx.sig_hash_bip143(1, &witness_script, prevouts[1].amount, SighashType::ALL).unwrap();
+        tx.inputs[1].witness = vec![ecdsa_signature(&key, &z), witness_script];
+
+        // BIP143 commits to the amount being spent, so the same scripts with other amounts do not verify
+        let other_amounts: Vec<_> =
+            prevouts.iter().map(|prevout| TxOut { amount: Amount::from_sat(1), ..prevout.clone() }).collect();
+        for index in 0..2 {
+            assert_eq!(tx.verify_input(index, &prevouts, ScriptFlags::CONSENSUS), Ok(true), "input {}", index);
+            assert_eq!(tx.verify_input(index, &other_amounts, ScriptFlags::CONSENSUS), Ok(false), "input {}", index);
+        }
+    }
+
+    #[test]
+    fn sign_all_changes_nothing_unless_every_input_can_be_signed() {
+        let (keys, mut prevouts, tx) = mixed_spend();
+
+        let missing_taproot_key = &keys[..2];
+        let mut result = tx.clone();
+        assert_eq!(result.sign_all(&prevouts, missing_taproot_key), Err(TxError::WrongKey(2)));
+        assert_eq!(result, tx);
+        assert_eq!(
+            result.sign_all(&prevouts[..2], &keys[..]),
+            Err(TxError::PrevoutCountMismatch { prevout_count: 2, input_count: 3 })
+        );
+
+        // P2WPKH pays to the compressed key, so the key hashing to it uncompressed is not a match
+        let uncompressed_hash = hash160(&keys[1].point().sec(false));
+        prevouts[1].script_pubkey = Script::p2wpkh(&uncompressed_hash).to_bytes();
+        assert_eq!(keys[..].key_for_pubkey_hash(&uncompressed_hash), Some(keys[1].clone()));
+        assert_eq!(result.sign_all(&prevouts, &keys[..]), Err(TxError::WrongKey(1)));
+
+        prevouts[1].script_pubkey = Script::p2wsh(&[0; 32]).to_bytes();
+        assert_eq!(result.sign_all(&prevouts, &keys[..]), Err(TxError::UnsupportedInput(1)));
+        assert_eq!(result, tx);
+    }
+
+    /*
+        The outputs a transaction's inputs spend, keyed by outpoint for Tx::verify, each worth this much. The
+        fixtures' real prevout amounts are not in the tree; legacy digests do not commit to them, so any amounts
+        that cover the outputs leave the signatures valid.
+     */
+    fn prevouts_by_outpoint(tx: &Tx, script_pubkeys: &[Vec<u8>], amount: u64) -> HashMap<OutPoint, TxOut> {
+        tx.inputs
+            .iter()
+            .zip(script_pubkeys)
+            .map(|(input, script_pubkey)| (input.outpoint, TxOut::new(Amount::from_sat(amount), script_pubkey.clone())))
+            .collect()
+    }
+
+    // The chapter 5 transaction, and the output it spends worth its two outputs and the book's 40000 satoshi fee
+    fn chapter_five_spend() -> (Tx, HashMap<OutPoint, TxOut>) {
+        let tx = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+        let script_pubkeys = [hex::decode(PREV_SCRIPT_PUBKEY).unwrap()];
+        let prevouts = prevouts_by_outpoint(&tx, &script_pubkeys, 32454049 + 10011545 + 40000);
+
+        (tx, prevouts)
+    }
+
+    fn four_input_spend() -> (Tx, HashMap<OutPoint, TxOut>) {
+        let tx = Tx::parse_exact(&hex::decode(FOUR_INPUT_TX_HEX).unwrap(), false).unwrap();
+        // The outputs come to 41,000,273 satoshis
+        let prevouts = prevouts_by_outpoint(&tx, &four_input_script_pubkeys(&tx), 10_300_000);
+
+        (tx, prevouts)
+    }
+
+    #[test]
+    fn real_transactions_verify_as_a_whole() {
+        for (tx, mut prevouts) in [chapter_five_spend(), four_input_spend()] {
+            assert_eq!(tx.verify(&mut prevouts), Ok(true), "{}", tx.id());
+        }
+    }
+
+    #[test]
+    fn verify_names_the_input_with_a_bad_signature() {
+        let (mut tx, mut prevouts) = four_input_spend();
+        // A byte of r, past the push length and the DER headers
+        tx.inputs[1].script_sig[10] ^= 1;
+
+        assert_eq!(tx.verify(&mut prevouts), Err(TxError::InvalidInput(1)));
+    }
+
+    #[test]
+    fn outputs_worth_more_than_the_inputs_do_not_verify() {
+        let (tx, _) = chapter_five_spend();
+        let script_pubkeys = [hex::decode(PREV_SCRIPT_PUBKEY).unwrap()];
+        let mut prevouts = prevouts_by_outpoint(&tx, &script_pubkeys, 32454049 + 10011545 - 1);
+
+        assert_eq!(tx.verify(&mut prevouts), Ok(false));
+    }
+
+    #[test]
+    fn coinbase_verifies_without_looking_up_prevouts() {
+        let coinbase = Tx::new(1, vec![TxIn::new(OutPoint::null(), vec![0x51], 0xffffffff)], vec![], 0, false);
+        let (tx, _) = chapter_five_spend();
+        let mut nothing_known = HashMap::new();
+
+        assert_eq!(coinbase.verify(&mut nothing_known), Ok(true));
+        assert_eq!(tx.verify(&mut nothing_known), Err(TxError::UnknownPrevout(tx.inputs[0].outpoint)));
+    }
+}
@@ -0,0 +1,979 @@
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+use crate::encoding::counting_reader::CountingReader;
+use crate::encoding::encodable::{Decodable, Encodable, VarInt};
+use crate::encoding::parse_error::ParseError;
+use crate::encoding::varint::{read_var_bytes, read_varint, write_var_bytes};
+use crate::hashing::hash256::Hash256;
+use crate::tx::amount::Amount;
+use crate::tx::locktime::LockTime;
+use crate::tx::outpoint::OutPoint;
+use crate::tx::tx_fetcher::{FetchError, PrevoutFetcher};
+use crate::tx::tx_in::TxIn;
+use crate::tx::tx_out::TxOut;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxError {
+    Fetch(FetchError),
+    InputIndexOutOfRange { index: usize, input_count: usize },
+    PrevIndexOutOfRange { txid: Hash256, index: u32, output_count: usize },
+    AmountOverflow,
+    // A hash type taproot signatures cannot use
+    InvalidSighashType(u32),
+    PrevoutCountMismatch { prevout_count: usize, input_count: usize },
+    // A SighashCache was asked for a taproot digest over different prevouts than its midstates cover
+    PrevoutsChanged,
+    // Taproot SIGHASH_SINGLE on an input with no output at the same index
+    SingleWithoutOutput(usize),
+    // An input whose script_pubkey is not a type this crate can sign for
+    UnsupportedInput(usize),
+    // The key given to sign an input is not the one its script_pubkey pays to, or no key for it was found
+    WrongKey(usize),
+    // An output that was looked up but is not known
+    UnknownPrevout(OutPoint),
+    // The index of an input whose scripts do not verify
+    InvalidInput(usize),
+}
+
+impl Display for TxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TxError::Fetch(error) => write!(f, "Could not fetch a previous transaction: {}", error),
+            TxError::InputIndexOutOfRange { index, input_count } => {
+                write!(f, "Input index {} is out of range for a transaction with {} inputs.", index, input_count)
+            }
+            TxError::PrevIndexOutOfRange { txid, index, output_count } => write!(
+                f,
+                "Input spends output {} of {} which only has {} outputs.",
+                index, txid, output_count
+            ),
+            TxError::AmountOverflow => write!(f, "Sum of amounts does not fit in 64 bits."),
+            TxError::InvalidSighashType(value) => write!(f, "Hash type {:#x} is not valid for taproot.", value),
+            TxError::PrevoutCountMismatch { prevout_count, input_count } => {
+                write!(f, "{} prevouts were given for a transaction with {} inputs.", prevout_count, input_count)
+            }
+            TxError::PrevoutsChanged => write!(f, "The prevouts differ from those the cached midstates cover."),
+            TxError::SingleWithoutOutput(index) => {
+                write!(f, "SIGHASH_SINGLE on input {} which has no matching output.", index)
+            }
+            TxError::UnsupportedInput(index) => {
+                write!(f, "Input {} spends a script type that cannot be signed.", index)
+            }
+            TxError::WrongKey(index) => write!(f, "The key does not match the script_pubkey of input {}.", index),
+            TxError::UnknownPrevout(outpoint) => write!(f, "The output {} being spent is not known.", outpoint),
+            TxError::InvalidInput(index) => write!(f, "Input {} does not satisfy the script it spends.", index),
+        }
+    }
+}
+
+impl std::error::Error for TxError {}
+
+impl From<FetchError> for TxError {
+    fn from(error: FetchError) -> Self {
+        TxError::Fetch(error)
+    }
+}
+
+/*
+    Wire format:
+
+    version   4 bytes little-endian
+    marker    0x00, segwit only
+    flag      0x01, segwit only
+    inputs    varint count followed by each TxIn
+    outputs   varint count followed by each TxOut
+    witness   segwit only, for each input a varint item count followed by varint-prefixed items
+    locktime  4 bytes little-endian
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tx {
+    pub version: u32,
+    pub inputs: Vec<TxIn>,
+    pub outputs: Vec<TxOut>,
+    pub locktime: u32,
+    // Not part of the wire format, records which chain the transaction was read from
+    pub testnet: bool,
+    // Whether the transaction was read in the segwit format
+    pub segwit: bool,
+}
+
+impl Tx {
+    pub fn new(version: u32, inputs: Vec<TxIn>, outputs: Vec<TxOut>, locktime: u32, testnet: bool) -> Self {
+        Self {
+            version,
+            inputs,
+            outputs,
+            locktime,
+            testnet,
+            segwit: false,
+        }
+    }
+
+    /*
+        Follows Bitcoin Core in resolving the segwit marker: an empty input list is taken to be the marker, and
+        the next byte is the flag. A zero flag means the transaction really had no inputs, and that byte was its
+        empty output list. This makes a legacy transaction with no inputs and some outputs unparseable, as in Core.
+
+        Reads exactly the transaction's bytes and no further, so transactions can be parsed one after another
+        from a stream such as a block. A truncation reports how many bytes into the transaction it happened.
+     */
+    pub fn parse(reader: &mut impl Read, testnet: bool) -> Result<Self, ParseError> {
+        let mut reader = CountingReader::new(reader);
+        let parse_fields = |reader: &mut _| -> Result<Self, ParseError> {
+            let version = u32::parse(reader)?;
+            let mut inputs: Vec<TxIn> = parse_list(reader)?;
+
+            let mut flag = 0;
+            let outputs = match inputs.is_empty() {
+                true => {
+                    flag = u8::parse(reader)?;
+                    match flag {
+                        0 => vec![],
+                        _ => {
+                            inputs = parse_list(reader)?;
+                            parse_list(reader)?
+                        }
+                    }
+                }
+                false => parse_list(reader)?,
+            };
+
+            let segwit = flag & 1 != 0;
+            if segwit {
+                for input in &mut inputs {
+                    input.witness = parse_witness(reader)?;
+                }
+                if inputs.iter().all(|input| input.witness.is_empty()) {
+                    return Err(ParseError::SuperfluousWitness);
+                }
+            }
+            if flag & !1 != 0 {
+                return Err(ParseError::UnknownSegwitFlag(flag));
+            }
+
+            let locktime = u32::parse(reader)?;
+
+            Ok(Self {
+                segwit,
+                ..Self::new(version, inputs, outputs, locktime, testnet)
+            })
+        };
+
+        parse_fields(&mut reader).map_err(|error| error.in_type("Tx").at_offset(reader.count()))
+    }
+
+    pub fn has_witness(&self) -> bool {
+        self.inputs.iter().any(|input| !input.witness.is_empty())
+    }
+
+    // The stripped serialization, without marker, flag or witnesses
+    pub fn serialize_legacy(&self, writer: &mut impl Write) -> Result<(), ParseError> {
+        self.version.serialize(writer)?;
+        serialize_list(writer, &self.inputs)?;
+        serialize_list(writer, &self.outputs)?;
+        self.locktime.serialize(writer)
+    }
+
+    pub fn serialize_segwit(&self, writer: &mut impl Write) -> Result<(), ParseError> {
+        self.version.serialize(writer)?;
+        writer.write_all(&[0x00, 0x01])?;
+        serialize_list(writer, &self.inputs)?;
+        serialize_list(writer, &self.outputs)?;
+        for input in &self.inputs {
+            serialize_witness(writer, &input.witness)?;
+        }
+        self.locktime.serialize(writer)
+    }
+
+    // The txid, whose Display form is the familiar reversed hex. Witnesses are not part of it.
+    pub fn id(&self) -> Hash256 {
+        let mut bytes = Vec::new();
+        self.serialize_legacy(&mut bytes).expect("Writing to a Vec cannot fail.");
+
+        Hash256::hash(&bytes)
+    }
+
+    // BIP141 witness txid, the same as the txid for transactions without witnesses. A coinbase's is all zeros.
+    pub fn wtxid(&self) -> Hash256 {
+        match self.is_coinbase() {
+            true => Hash256::default(),
+            false => Hash256::hash(&self.serialize_to_vec()),
+        }
+    }
+
+    // BIP141 weight: every byte of the stripped serialization counts four times, witness bytes once
+    pub fn weight(&self) -> u64 {
+        let mut stripped = Vec::new();
+        self.serialize_legacy(&mut stripped).expect("Writing to a Vec cannot fail.");
+
+        stripped.len() as u64 * 3 + self.serialize_to_vec().len() as u64
+    }
+
+    // Virtual size in vbytes, the size fee rates are quoted against
+    pub fn vsize(&self) -> u64 {
+        self.weight().div_ceil(4)
+    }
+
+    // Satoshis per vbyte paid by the given fee
+    pub fn fee_rate(&self, fee: Amount) -> f64 {
+        fee.to_sat() as f64 / self.vsize() as f64
+    }
+
+    pub fn locktime_kind(&self) -> LockTime {
+        LockTime::from_consensus(self.locktime)
+    }
+
+    // A zero locktime imposes nothing, and any other is ignored when every input's sequence is final
+    pub fn is_locktime_enabled(&self) -> bool {
+        self.locktime != 0 && !self.inputs.iter().all(TxIn::is_final)
+    }
+
+    // BIP125 opt-in replace-by-fee
+    pub fn signals_rbf(&self) -> bool {
+        self.inputs.iter().any(TxIn::signals_rbf)
+    }
+
+    // A coinbase has a single input spending the null outpoint
+    pub fn is_coinbase(&self) -> bool {
+        match self.inputs.as_slice() {
+            [input] => input.outpoint.is_null(),
+            _ => false,
+        }
+    }
+
+    /*
+        BIP34 has the coinbase script_sig start with the block height as a minimally encoded script number.
+        Without the block there is no way to tell a pre-BIP34 coinbase from one carrying a height, so pushes
+        longer than 3 bytes are refused: heights only need a 4th byte from block 8,388,608, while early miners
+        routinely started the script with a 4-byte push of nBits, as the genesis coinbase does.
+     */
+    pub fn coinbase_height(&self) -> Option<u64> {
+        if !self.is_coinbase() {
+            return None;
+        }
+
+        let script_sig = &self.inputs[0].script_sig;
+        match *script_sig.first()? {
+            // OP_0 and OP_1 to OP_16, which Bitcoin Core uses for the lowest heights
+            0x00 => Some(0),
+            opcode @ 0x51..=0x60 => Some((opcode - 0x50) as u64),
+            length @ 1..=3 => {
+                let bytes = script_sig.get(1..=length as usize)?;
+                let (&last, rest) = bytes.split_last()?;
+                let negative = last & 0x80 != 0;
+                let non_minimal = last == 0 && rest.last().is_none_or(|byte| byte & 0x80 == 0);
+                if negative || non_minimal {
+                    return None;
+                }
+
+                Some(bytes.iter().rev().fold(0, |height, &byte| (height << 8) | byte as u64))
+            }
+            _ => None,
+        }
+    }
+
+    /*
+        Inputs minus outputs in satoshis. A negative fee means the transaction spends more than it has and is
+        invalid, which is why this is not an Amount.
+     */
+    pub fn fee(&self, fetcher: &mut impl PrevoutFetcher) -> Result<i64, TxError> {
+        let mut input_total = Amount::ZERO;
+        for input in &self.inputs {
+            let prev_output = input.prev_output(fetcher, self.testnet)?;
+            input_total = input_total.checked_add(prev_output.amount).ok_or(TxError::AmountOverflow)?;
+        }
+
+        let output_total = self
+            .outputs
+            .iter()
+            .try_fold(Amount::ZERO, |total, output| total.checked_add(output.amount))
+            .ok_or(TxError::AmountOverflow)?;
+
+        i64::try_from(input_total.to_sat() as i128 - output_total.to_sat() as i128).map_err(|_| TxError::AmountOverflow)
+    }
+
+    // Parses a complete transaction, rejecting any bytes left over
+    pub fn parse_exact(bytes: &[u8], testnet: bool) -> Result<Self, ParseError> {
+        let mut reader = bytes;
+        let tx = Self::parse(&mut reader, testnet)?;
+
+        match reader.len() {
+            0 => Ok(tx),
+            leftover => Err(ParseError::TrailingBytes(leftover)),
+        }
+    }
+}
+
+// Uses the segwit format exactly when some input carries a witness, as Bitcoin Core does
+impl Encodable for Tx {
+    fn serialize(&self, writer: &mut impl Write) -> Result<(), ParseError> {
+        match self.has_witness() {
+            true => self.serialize_segwit(writer),
+            false => self.serialize_legacy(writer),
+        }
+    }
+}
+
+// The count is untrusted, so the list grows as items parse rather than being preallocated
+fn parse_list<T: Decodable>(reader: &mut impl Read) -> Result<Vec<T>, ParseError> {
+    let count = read_varint(reader)?;
+
+    (0..count).map(|_| T::parse(reader)).collect()
+}
+
+pub(crate) fn parse_witness(reader: &mut impl Read) -> Result<Vec<Vec<u8>>, ParseError> {
+    let count = read_varint(reader)?;
+
+    (0..count).map(|_| read_var_bytes(reader)).collect()
+}
+
+pub(crate) fn serialize_witness(writer: &mut impl Write, witness: &[Vec<u8>]) -> Result<(), ParseError> {
+    VarInt(witness.len() as u64).serialize(writer)?;
+    for item in witness {
+        write_var_bytes(writer, item)?;
+    }
+
+    Ok(())
+}
+
+fn serialize_list<T: Encodable>(writer: &mut impl Write, items: &[T]) -> Result<(), ParseError> {
+    VarInt(items.len() as u64).serialize(writer)?;
+    for item in items {
+        item.serialize(writer)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::time::Duration;
+    use crate::encoding::counting_reader::CountingReader;
+    use crate::encoding::encodable::Encodable;
+    use crate::encoding::hex;
+    use crate::encoding::parse_error::ParseError;
+    use crate::hashing::hash256::Hash256;
+    use crate::tx::amount::Amount;
+    use crate::tx::fixtures::{FOUR_INPUT_TX_HEX, TX_HEX};
+    use crate::tx::locktime::LockTime;
+    use crate::tx::outpoint::OutPoint;
+    use crate::tx::transaction::{Tx, TxError};
+    use crate::tx::tx_fetcher::{FetchError, HttpResponse, HttpTransport, TxFetcher};
+    use crate::tx::tx_in::TxIn;
+    use crate::tx::tx_out::TxOut;
+
+    // The genesis block coinbase, one input with a 77-byte coinbase script and one pay-to-pubkey output
+    // 4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b
+    const GENESIS_COINBASE_HEX: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff\
+                                        001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e\
+                                        6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104\
+                                        678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51e\
+                                        c112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+
+    // Block 170, the first payment between people, spending a pay-to-pubkey coinbase
+    // f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16
+    const BLOCK_170_HEX: &str = "0100000001c997a5e56e104102fa209c6a852dd90660a20b2d9c352423edce25857fcd37040000000048473044\
+                                 02204e45e16932b8af514961a1d3a1a25fdf3f4f7732e9d624c6c61548ab5fb8cd410220181522ec8eca07de48\
+                                 60a4acdd12909d831cc56cbbac4622082221a8768d1d0901ffffffff0200ca9a3b00000000434104ae1a62fe09\
+                                 c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414e7aab37397f554a7df5f142c21c1b7303b8a\
+                                 0626f1baded5c72a704f7e6cd84cac00286bee0000000043410411db93e1dcdb8a016b49840f8c53bc1eb68a38\
+                                 2e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3\
+                                 ac00000000";
+
+    // A 2-of-2 P2SH multisig spend with a 219-byte script_sig and four outputs
+    // 46df1a9484d0a81d03ce0ee543ab6e1a23ed06175c104a178268fad381216c2b
+    const P2SH_MULTISIG_HEX: &str = "0100000001868278ed6ddfb6c1ed3ad5f8181eb0c7a385aa0836f01d5e4789e6bd304d87221a000000db004830\
+                                     45022100dc92655fe37036f47756db8102e0d7d5e28b3beb83a8fef4f5dc0559bddfb94e02205a36d4e4e6c7fc\
+                                     d16658c50783e00c341609977aed3ad00937bf4ee942a8993701483045022100da6bee3c93766232079a01639d\
+                                     07fa869598749729ae323eab8eef53577d611b02207bef15429dcadce2121ea07f233115c6f09034c0be68db99\
+                                     980b9a6c5e75402201475221022626e955ea6ea6d98850c994f9107b036b1334f18ca8830bfff1295d21cfdb70\
+                                     2103b287eaf122eea69030a0e9feed096bed8045c8b98bec453e1ffac7fbdbd4bb7152aeffffffff04d3b11400\
+                                     000000001976a914904a49878c0adfc3aa05de7afad2cc15f483a56a88ac7f400900000000001976a914418327\
+                                     e3f3dda4cf5b9089325a4b95abdfa0334088ac722c0c00000000001976a914ba35042cfe9fc66fd35ac2224eeb\
+                                     dafd1028ad2788acdc4ace020000000017a91474d691da1574e6b3c192ecfb52cc8984ee7b6c568700000000";
+
+    #[test]
+    fn chapter_five_transaction_parses() {
+        let bytes = hex::decode(TX_HEX).unwrap();
+
+        let subject = Tx::parse(&mut bytes.as_slice(), false).unwrap();
+
+        assert_eq!(subject.version, 1);
+        assert_eq!(subject.inputs.len(), 1);
+        assert_eq!(subject.inputs[0].script_sig.len(), 0x6b);
+        assert_eq!(subject.inputs[0].sequence, 0xfffffffe);
+        assert_eq!(subject.outputs.len(), 2);
+        assert_eq!(subject.outputs[0].amount, Amount::from_sat(32454049));
+        assert_eq!(subject.outputs[0].script_pubkey.len(), 0x19);
+        assert_eq!(subject.outputs[1].amount, Amount::from_sat(10011545));
+        assert_eq!(subject.outputs[1].script_pubkey.len(), 0x19);
+        assert_eq!(subject.locktime, 410393);
+        assert!(!subject.testnet);
+    }
+
+    #[test]
+    fn id_is_the_reversed_double_sha256_of_the_serialization() {
+        let subject = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+
+        assert_eq!(
+            &subject.id().to_string(),
+            "452c629d67e41baec3ac6f04fe744b4b9617f8f859c63b3002f8684e7a4fee03"
+        );
+    }
+
+    #[test]
+    fn testnet_flag_is_carried_through() {
+        let bytes = hex::decode(TX_HEX).unwrap();
+
+        let subject = Tx::parse_exact(&bytes, true).unwrap();
+
+        assert!(subject.testnet);
+    }
+
+    #[test]
+    fn parse_stops_at_the_end_of_the_transaction() {
+        let mut bytes = hex::decode(TX_HEX).unwrap();
+        bytes.extend_from_slice(&[0xde, 0xad]);
+        let mut reader = bytes.as_slice();
+
+        Tx::parse(&mut reader, false).unwrap();
+
+        assert_eq!(reader, &[0xde, 0xad]);
+    }
+
+    #[test]
+    fn trailing_garbage_is_rejected_by_parse_exact() {
+        let mut bytes = hex::decode(TX_HEX).unwrap();
+        bytes.extend_from_slice(&[0xde, 0xad, 0xbe]);
+
+        let result = Tx::parse_exact(&bytes, false);
+
+        assert_eq!(result, Err(ParseError::TrailingBytes(3)));
+    }
+
+    #[test]
+    fn truncated_transaction_errors_cleanly() {
+        let bytes = hex::decode(TX_HEX).unwrap();
+
+        for length in [0, 3, 4, 5, 50, 150, 160, 200, bytes.len() - 1] {
+            let result = Tx::parse(&mut &bytes[..length], false);
+
+            assert!(
+                matches!(result, Err(ParseError::TruncatedAt { offset, .. }) if offset == length as u64),
+                "length {}: {:?}",
+                length,
+                result
+            );
+        }
+        assert_eq!(Tx::parse(&mut &bytes[..4], false), Err(ParseError::TruncatedAt { type_name: "Tx", offset: 4 }));
+        assert_eq!(
+            Tx::parse(&mut &bytes[..bytes.len() - 1], false),
+            Err(ParseError::TruncatedAt { type_name: "u32", offset: 225 })
+        );
+    }
+
+    #[test]
+    fn consecutive_transactions_parse_from_one_stream() {
+        let transactions = [TX_HEX, BIP143_P2WPKH_HEX, GENESIS_COINBASE_HEX, BIP143_P2SH_P2WPKH_HEX, BLOCK_170_HEX];
+        let encoded: Vec<Vec<u8>> = transactions.iter().map(|tx_hex| hex::decode(tx_hex).unwrap()).collect();
+        let stream = encoded.concat();
+        let mut reader = CountingReader::new(stream.as_slice());
+
+        let mut consumed = 0;
+        for bytes in &encoded {
+            let tx = Tx::parse(&mut reader, false).unwrap();
+
+            consumed += bytes.len() as u64;
+            assert_eq!(reader.count(), consumed);
+            assert_eq!(&tx.serialize_to_vec(), bytes);
+        }
+        assert!(reader.into_inner().is_empty());
+    }
+
+    #[test]
+    fn absurd_input_count_fails_without_allocating() {
+        let bytes = hex::decode("01000000ffffffffffffffffff").unwrap();
+
+        let result = Tx::parse(&mut bytes.as_slice(), false);
+
+        assert_eq!(result, Err(ParseError::TruncatedAt { type_name: "Hash256", offset: 13 }));
+    }
+
+    #[test]
+    fn mainnet_transactions_round_trip_byte_for_byte() {
+        let transactions = [TX_HEX, GENESIS_COINBASE_HEX, BLOCK_170_HEX, FOUR_INPUT_TX_HEX, P2SH_MULTISIG_HEX];
+
+        for tx_hex in transactions {
+            let bytes = hex::decode(tx_hex).unwrap();
+
+            let subject = Tx::parse_exact(&bytes, false).unwrap();
+
+            assert_eq!(subject.serialize_to_vec(), bytes);
+        }
+    }
+
+    #[test]
+    fn round_tripped_transactions_keep_their_shape() {
+        let four_inputs = Tx::parse_exact(&hex::decode(FOUR_INPUT_TX_HEX).unwrap(), false).unwrap();
+        let multisig = Tx::parse_exact(&hex::decode(P2SH_MULTISIG_HEX).unwrap(), false).unwrap();
+
+        assert_eq!(four_inputs.inputs.len(), 4);
+        assert_eq!(four_inputs.outputs.len(), 2);
+        assert_eq!(multisig.inputs[0].script_sig.len(), 0xdb);
+        assert_eq!(multisig.outputs.len(), 4);
+    }
+
+    // A small xorshift generator keeps the property test deterministic and dependency-free
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: u64) -> u64 {
+            self.next() % bound
+        }
+
+        fn bytes(&mut self, length: usize) -> Vec<u8> {
+            (0..length).map(|_| self.next() as u8).collect()
+        }
+    }
+
+    fn random_tx(rng: &mut XorShift) -> Tx {
+        // Lengths straddle the one-byte varint limit so the 0xfd prefix is exercised
+        let script = |rng: &mut XorShift| {
+            let length = match rng.below(4) {
+                0 => 0,
+                1 => rng.below(0xfd),
+                _ => rng.below(600),
+            };
+            rng.bytes(length as usize)
+        };
+
+        // At least one input, since an empty input list reads as the segwit marker
+        let segwit = rng.below(2) == 0;
+        let mut inputs: Vec<TxIn> = (0..1 + rng.below(3))
+            .map(|_| {
+                let txid = Hash256::from_wire_bytes(rng.bytes(32).try_into().unwrap());
+                let outpoint = OutPoint::new(txid, rng.next() as u32);
+                let mut input = TxIn::new(outpoint, script(rng), rng.next() as u32);
+                if segwit {
+                    input.witness = (0..rng.below(3)).map(|_| script(rng)).collect();
+                }
+                input
+            })
+            .collect();
+        if segwit && inputs.iter().all(|input| input.witness.is_empty()) {
+            inputs[0].witness.push(vec![]);
+        }
+        let outputs = (0..rng.below(4))
+            .map(|_| match rng.below(3) {
+                // An OP_RETURN data carrier with no value
+                0 => {
+                    let mut script_pubkey = vec![0x6a, 0x14];
+                    script_pubkey.extend(rng.bytes(20));
+                    TxOut::new(Amount::ZERO, script_pubkey)
+                }
+                _ => TxOut::new(Amount::from_sat(rng.next()), script(rng)),
+            })
+            .collect();
+
+        Tx {
+            segwit,
+            ..Tx::new(rng.next() as u32, inputs, outputs, rng.next() as u32, false)
+        }
+    }
+
+    #[test]
+    fn random_transactions_round_trip() {
+        let mut rng = XorShift(0x2545f4914f6cdd1d);
+
+        for _ in 0..500 {
+            let tx = random_tx(&mut rng);
+
+            let bytes = tx.serialize_to_vec();
+
+            assert_eq!(Tx::parse_exact(&bytes, false), Ok(tx));
+        }
+    }
+
+    fn with_locktime_and_sequences(locktime: u32, sequences: &[u32]) -> Tx {
+        let inputs = sequences
+            .iter()
+            .map(|sequence| TxIn::new(OutPoint::default(), vec![], *sequence))
+            .collect();
+
+        Tx::new(2, inputs, vec![], locktime, false)
+    }
+
+    #[test]
+    fn height_locktime_of_the_chapter_five_transaction() {
+        let subject = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+
+        assert_eq!(subject.locktime_kind(), LockTime::BlockHeight(410393));
+        assert!(subject.is_locktime_enabled());
+        // 0xfffffffe enables the locktime without signaling replaceability
+        assert!(!subject.signals_rbf());
+    }
+
+    #[test]
+    fn timestamp_locktime() {
+        let subject = with_locktime_and_sequences(1_600_000_000, &[0xfffffffd]);
+
+        assert_eq!(subject.locktime_kind(), LockTime::Timestamp(1_600_000_000));
+        assert!(subject.is_locktime_enabled());
+        assert!(subject.signals_rbf());
+    }
+
+    #[test]
+    fn locktime_is_ignored_when_every_sequence_is_final() {
+        let all_final = with_locktime_and_sequences(410393, &[0xffffffff, 0xffffffff]);
+        let one_not_final = with_locktime_and_sequences(410393, &[0xffffffff, 0xfffffffe]);
+        let zero_locktime = with_locktime_and_sequences(0, &[0]);
+
+        assert!(!all_final.is_locktime_enabled());
+        assert!(one_not_final.is_locktime_enabled());
+        assert!(!zero_locktime.is_locktime_enabled());
+    }
+
+    #[test]
+    fn any_input_below_0xfffffffe_signals_rbf() {
+        assert!(!with_locktime_and_sequences(0, &[0xffffffff, 0xfffffffe]).signals_rbf());
+        assert!(with_locktime_and_sequences(0, &[0xffffffff, 0xfffffffd]).signals_rbf());
+        // A BIP68 relative locktime is necessarily below the threshold as well
+        assert!(with_locktime_and_sequences(0, &[144]).signals_rbf());
+    }
+
+    // The coinbase of block 465879 from Programming Bitcoin chapter 9
+    const BLOCK_465879_COINBASE_HEX: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff5e03d71b\
+                                             07254d696e656420627920416e74506f6f6c20626a31312f4542312f4144362f43205914293101fabe6d6d678e\
+                                             2c8c34afc36896e7d9402824ed38e856676ee94bfdb0c6c4bcd8b2e5666a0400000000000000c7270000a5e00e\
+                                             00ffffffff01faf20b58000000001976a914338c84849423992471bffb1a54a8d9b1d69dc28a88ac00000000";
+
+    fn coinbase_with_script_sig(script_sig: Vec<u8>) -> Tx {
+        let input = TxIn::new(OutPoint::null(), script_sig, 0xffffffff);
+
+        Tx::new(1, vec![input], vec![TxOut::new(Amount::ONE_BTC * 50, vec![0x51])], 0, false)
+    }
+
+    #[test]
+    fn coinbase_transactions_are_recognized() {
+        let post_bip34 = Tx::parse_exact(&hex::decode(BLOCK_465879_COINBASE_HEX).unwrap(), false).unwrap();
+        let genesis = Tx::parse_exact(&hex::decode(GENESIS_COINBASE_HEX).unwrap(), false).unwrap();
+        let regular = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+
+        assert!(post_bip34.is_coinbase());
+        assert!(genesis.is_coinbase());
+        assert!(!regular.is_coinbase());
+    }
+
+    #[test]
+    fn bip34_height_is_read_from_the_coinbase() {
+        let subject = Tx::parse_exact(&hex::decode(BLOCK_465879_COINBASE_HEX).unwrap(), false).unwrap();
+
+        assert_eq!(subject.coinbase_height(), Some(465879));
+    }
+
+    #[test]
+    fn transactions_without_a_bip34_height_give_none() {
+        let genesis = Tx::parse_exact(&hex::decode(GENESIS_COINBASE_HEX).unwrap(), false).unwrap();
+        let regular = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+
+        assert_eq!(genesis.coinbase_height(), None);
+        assert_eq!(regular.coinbase_height(), None);
+    }
+
+    #[test]
+    fn small_heights_use_opcodes() {
+        assert_eq!(coinbase_with_script_sig(vec![0x00, 0x01]).coinbase_height(), Some(0));
+        assert_eq!(coinbase_with_script_sig(vec![0x51, 0x00]).coinbase_height(), Some(1));
+        assert_eq!(coinbase_with_script_sig(vec![0x60, 0x00]).coinbase_height(), Some(16));
+        assert_eq!(coinbase_with_script_sig(vec![0x01, 0x11]).coinbase_height(), Some(17));
+    }
+
+    #[test]
+    fn malformed_height_pushes_give_none() {
+        let cases = [
+            vec![],
+            vec![0x03, 0xd7, 0x1b],
+            vec![0x02, 0x80, 0x80],
+            vec![0x02, 0x11, 0x00],
+            vec![0x01, 0x00],
+        ];
+
+        for script_sig in cases {
+            assert_eq!(coinbase_with_script_sig(script_sig.clone()).coinbase_height(), None, "{:?}", script_sig);
+        }
+        assert_eq!(coinbase_with_script_sig(vec![0x02, 0x80, 0x00]).coinbase_height(), Some(128));
+    }
+
+    // The native P2WPKH example from BIP143, one P2PK input and one P2WPKH input
+    // txid e8151a2af31c368a35053ddd4bdb285a8595c769a3ad83e0fa02314a602d4609
+    const BIP143_P2WPKH_HEX: &str = "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000049\
+                                     4830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b19\
+                                     4ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d27965\
+                                     5c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b3\
+                                     7df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2\
+                                     f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01c\
+                                     c44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188\
+                                     368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000";
+
+    #[test]
+    fn segwit_transaction_parses_with_its_witnesses() {
+        let subject = Tx::parse_exact(&hex::decode(BIP143_P2WPKH_HEX).unwrap(), false).unwrap();
+
+        assert!(subject.segwit);
+        assert_eq!(subject.inputs.len(), 2);
+        assert_eq!(subject.inputs[0].script_sig.len(), 0x49);
+        assert!(subject.inputs[0].witness.is_empty());
+        assert!(subject.inputs[1].script_sig.is_empty());
+        assert_eq!(subject.inputs[1].witness.len(), 2);
+        assert_eq!(subject.inputs[1].witness[0].len(), 0x47);
+        assert_eq!(subject.inputs[1].witness[0].last(), Some(&0x01));
+        assert_eq!(
+            &hex::encode(&subject.inputs[1].witness[1]),
+            "025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357"
+        );
+        assert_eq!(subject.outputs.len(), 2);
+        assert_eq!(subject.locktime, 0x11);
+    }
+
+    #[test]
+    fn segwit_transaction_round_trips_and_keeps_its_txid() {
+        let bytes = hex::decode(BIP143_P2WPKH_HEX).unwrap();
+
+        let subject = Tx::parse_exact(&bytes, false).unwrap();
+
+        assert_eq!(subject.serialize_to_vec(), bytes);
+        assert_eq!(
+            &subject.id().to_string(),
+            "e8151a2af31c368a35053ddd4bdb285a8595c769a3ad83e0fa02314a602d4609"
+        );
+    }
+
+    // The P2SH-P2WPKH example from BIP143
+    // txid ef48d9d0f595052e0f8cdcf825f7a5e50b6a388a81f206f3f4846e5ecd7a0c23
+    const BIP143_P2SH_P2WPKH_HEX: &str = "01000000000101db6b1b20aa0fd7b23880be2ecbd4a98130974cf4748fb66092ac4d3ceb1a547701000000\
+                                          1716001479091972186c449eb1ded22b78e40d009bdf0089feffffff02b8b4eb0b000000001976a914a457b6\
+                                          84d7f0d539a46a45bbc043f35b59d0d96388ac0008af2f000000001976a914fd270b1ee6abcaea97fea7ad04\
+                                          02e8bd8ad6d77c88ac02473044022047ac8e878352d3ebbde1c94ce3a10d057c24175747116f8288e5d794d1\
+                                          2d482f0220217f36a485cae903c713331d877c1f64677e3622ad4010726870540656fe9dcb012103ad1d8e89\
+                                          212f0b92c74d23bb710c00662ad1470198ac48c43f7d6f93a2a2687392040000";
+
+    #[test]
+    fn segwit_transactions_round_trip_byte_for_byte() {
+        for tx_hex in [BIP143_P2WPKH_HEX, BIP143_P2SH_P2WPKH_HEX] {
+            let bytes = hex::decode(tx_hex).unwrap();
+            let subject = Tx::parse_exact(&bytes, false).unwrap();
+
+            let mut segwit_bytes = Vec::new();
+            subject.serialize_segwit(&mut segwit_bytes).unwrap();
+
+            assert_eq!(subject.serialize_to_vec(), bytes);
+            assert_eq!(segwit_bytes, bytes);
+        }
+    }
+
+    #[test]
+    fn witness_bearing_transactions_have_a_distinct_wtxid() {
+        let subject = Tx::parse_exact(&hex::decode(BIP143_P2SH_P2WPKH_HEX).unwrap(), false).unwrap();
+
+        assert_eq!(
+            &subject.id().to_string(),
+            "ef48d9d0f595052e0f8cdcf825f7a5e50b6a388a81f206f3f4846e5ecd7a0c23"
+        );
+        assert_eq!(
+            &subject.wtxid().to_string(),
+            "680f483b2bf6c5dcbf111e69e885ba248a41a5e92070cfb0afec3cfc49a9fabb"
+        );
+    }
+
+    #[test]
+    fn legacy_transactions_have_wtxid_equal_to_txid() {
+        let subject = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+
+        assert!(!subject.has_witness());
+        assert_eq!(subject.wtxid(), subject.id());
+    }
+
+    #[test]
+    fn coinbase_wtxid_is_all_zeros() {
+        let subject = Tx::parse_exact(&hex::decode(BLOCK_465879_COINBASE_HEX).unwrap(), false).unwrap();
+
+        assert_eq!(subject.wtxid(), Hash256::default());
+    }
+
+    #[test]
+    fn legacy_weight_is_four_times_the_size() {
+        let subject = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+
+        // Bitcoin Core's getrawtransaction reports size 226, vsize 226 and weight 904
+        assert_eq!(subject.weight(), 904);
+        assert_eq!(subject.vsize(), 226);
+        assert_eq!(subject.fee_rate(Amount::from_sat(40_000)), 40_000.0 / 226.0);
+    }
+
+    #[test]
+    fn witness_bytes_are_discounted() {
+        let native = Tx::parse_exact(&hex::decode(BIP143_P2WPKH_HEX).unwrap(), false).unwrap();
+        let nested = Tx::parse_exact(&hex::decode(BIP143_P2SH_P2WPKH_HEX).unwrap(), false).unwrap();
+
+        // 233 stripped bytes out of 343, and 142 out of 251
+        assert_eq!(native.weight(), 1042);
+        assert_eq!(native.vsize(), 261);
+        assert_eq!(nested.weight(), 677);
+        assert_eq!(nested.vsize(), 170);
+        assert_eq!(nested.fee_rate(Amount::from_sat(1_700)), 10.0);
+    }
+
+    #[test]
+    fn stripping_the_witnesses_gives_the_legacy_serialization() {
+        let mut subject = Tx::parse_exact(&hex::decode(BIP143_P2WPKH_HEX).unwrap(), false).unwrap();
+        let txid = subject.id();
+
+        for input in &mut subject.inputs {
+            input.witness.clear();
+        }
+
+        assert_eq!(Tx::parse_exact(&subject.serialize_to_vec(), false).unwrap().id(), txid);
+        assert_eq!(subject.wtxid(), txid);
+    }
+
+    #[test]
+    fn empty_input_list_is_read_as_the_segwit_marker() {
+        // No inputs and no outputs: the flag byte is really the empty output list
+        let empty = Tx::parse_exact(&hex::decode("01000000000000000000").unwrap(), false).unwrap();
+        // No inputs but an output, which Bitcoin Core also cannot read in the legacy format
+        let ambiguous = Tx::parse_exact(&hex::decode("0100000000010000000000000000015100000000").unwrap(), false);
+
+        assert!(empty.inputs.is_empty() && empty.outputs.is_empty() && !empty.segwit);
+        assert!(ambiguous.is_err());
+    }
+
+    #[test]
+    fn invalid_segwit_markers_are_rejected() {
+        let bytes = hex::decode(BIP143_P2WPKH_HEX).unwrap();
+        let mut unknown_flag = bytes.clone();
+        unknown_flag[5] = 0x03;
+        let mut stripped_witness = hex::decode(TX_HEX).unwrap();
+        stripped_witness.splice(4..4, [0x00, 0x01]);
+        let locktime_at = stripped_witness.len() - 4;
+        stripped_witness.insert(locktime_at, 0x00);
+
+        assert_eq!(Tx::parse_exact(&unknown_flag, false), Err(ParseError::UnknownSegwitFlag(0x03)));
+        assert_eq!(Tx::parse_exact(&stripped_witness, false), Err(ParseError::SuperfluousWitness));
+    }
+
+    // Prevouts are seeded into the fetcher's cache, so any network access is a test failure
+    struct Offline;
+
+    impl HttpTransport for Offline {
+        fn get(&mut self, _url: &str, _timeout: Duration) -> io::Result<HttpResponse> {
+            Err(io::Error::new(io::ErrorKind::NotConnected, "offline"))
+        }
+    }
+
+    fn prevout_tx(amounts: &[u64]) -> Tx {
+        let outputs = amounts.iter().map(|amount| TxOut::new(Amount::from_sat(*amount), vec![0x51])).collect();
+
+        Tx::new(1, vec![], outputs, 0, false)
+    }
+
+    fn spending(prev_tx: &Tx, prev_indices: &[u32], amounts: &[u64]) -> Tx {
+        let inputs = prev_indices
+            .iter()
+            .map(|index| TxIn::new(OutPoint::new(prev_tx.id(), *index), vec![], 0xffffffff))
+            .collect();
+        let outputs = amounts.iter().map(|amount| TxOut::new(Amount::from_sat(*amount), vec![0x51])).collect();
+
+        Tx::new(1, inputs, outputs, 0, false)
+    }
+
+    #[test]
+    fn fee_of_the_chapter_five_transaction() {
+        // The book gives this transaction a 40000 satoshi fee, so its prevout is mocked with that much to spare
+        let prev_tx = prevout_tx(&[32454049 + 10011545 + 40000]);
+        let mut fetcher = TxFetcher::new(Offline);
+        fetcher.insert(prev_tx.clone());
+        let mut subject = Tx::parse_exact(&hex::decode(TX_HEX).unwrap(), false).unwrap();
+        subject.inputs[0].outpoint.txid = prev_tx.id();
+
+        assert_eq!(subject.fee(&mut fetcher), Ok(40000));
+    }
+
+    #[test]
+    fn fee_sums_every_input() {
+        let prev_tx = prevout_tx(&[1000, 2000, 3000]);
+        let mut fetcher = TxFetcher::new(Offline);
+        fetcher.insert(prev_tx.clone());
+
+        let subject = spending(&prev_tx, &[0, 2], &[1500, 2000]);
+
+        assert_eq!(subject.fee(&mut fetcher), Ok(500));
+    }
+
+    #[test]
+    fn zero_fee_transaction() {
+        let prev_tx = prevout_tx(&[5000]);
+        let mut fetcher = TxFetcher::new(Offline);
+        fetcher.insert(prev_tx.clone());
+
+        let subject = spending(&prev_tx, &[0], &[3000, 2000]);
+
+        assert_eq!(subject.fee(&mut fetcher), Ok(0));
+    }
+
+    #[test]
+    fn outputs_exceeding_inputs_give_a_negative_fee() {
+        let prev_tx = prevout_tx(&[5000]);
+        let mut fetcher = TxFetcher::new(Offline);
+        fetcher.insert(prev_tx.clone());
+
+        let subject = spending(&prev_tx, &[0], &[5001]);
+
+        assert_eq!(subject.fee(&mut fetcher), Ok(-1));
+    }
+
+    #[test]
+    fn amounts_past_u64_are_reported() {
+        let prev_tx = prevout_tx(&[u64::MAX, 1]);
+        let mut fetcher = TxFetcher::new(Offline);
+        fetcher.insert(prev_tx.clone());
+
+        let subject = spending(&prev_tx, &[0, 1], &[]);
+
+        assert_eq!(subject.fee(&mut fetcher), Err(TxError::AmountOverflow));
+    }
+
+    #[test]
+    fn input_pointing_past_the_prevout_outputs_is_an_error() {
+        let prev_tx = prevout_tx(&[1000, 2000]);
+        let mut fetcher = TxFetcher::new(Offline);
+        fetcher.insert(prev_tx.clone());
+
+        let subject = spending(&prev_tx, &[2], &[500]);
+
+        assert_eq!(
+            subject.fee(&mut fetcher),
+            Err(TxError::PrevIndexOutOfRange { txid: prev_tx.id(), index: 2, output_count: 2 })
+        );
+    }
+
+    #[test]
+    fn unknown_prevout_surfaces_the_fetch_error() {
+        let prev_tx = prevout_tx(&[1000]);
+        let mut fetcher = TxFetcher::new(Offline);
+
+        let subject = spending(&prev_tx, &[0], &[500]);
+
+        assert_eq!(
+            subject.fee(&mut fetcher),
+            Err(TxError::Fetch(FetchError::Io(io::ErrorKind::NotConnected)))
+        );
+    }
+}
@@ -0,0 +1,487 @@
+use std::fmt::{Display, Formatter};
+use crate::address::Address;
+use crate::encoding::varint::encode_varint;
+use crate::network::Network;
+use crate::script::interpreter::ScriptError;
+use crate::script::Script;
+use crate::tx::amount::{Amount, AmountError};
+use crate::tx::locktime::{SEQUENCE_FINAL, SEQUENCE_RBF_THRESHOLD};
+use crate::tx::outpoint::OutPoint;
+use crate::tx::policy::DUST_RELAY_FEE_RATE;
+use crate::tx::transaction::Tx;
+use crate::tx::tx_in::TxIn;
+use crate::tx::tx_out::TxOut;
+use crate::tx::witness::Witness;
+
+// Sizes of the signatures and keys assumed when estimating an unsigned input: a high-R DER signature
+// with its hash type byte, a compressed public key and a BIP340 signature with the default hash type
+const ECDSA_SIGNATURE_SIZE: usize = 72;
+const COMPRESSED_PUBKEY_SIZE: usize = 33;
+const SCHNORR_SIGNATURE_SIZE: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    NoInputs,
+    NoOutputs,
+    Amount(AmountError),
+    // The prevout's script_pubkey is not a type whose signed size can be estimated
+    UnsupportedInput(usize),
+    InsufficientFunds { available: Amount, required: Amount },
+    // An address of a network other than the one the transaction is for
+    WrongNetwork(Network),
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::NoInputs => write!(f, "Transaction has no inputs."),
+            BuildError::NoOutputs => write!(f, "Transaction has no outputs."),
+            BuildError::Amount(error) => write!(f, "Invalid amount: {}", error),
+            BuildError::UnsupportedInput(index) => {
+                write!(f, "Cannot estimate the signed size of input {}.", index)
+            }
+            BuildError::InsufficientFunds { available, required } => {
+                write!(f, "Inputs provide {} but outputs plus fee need {}.", available, required)
+            }
+            BuildError::WrongNetwork(network) => {
+                write!(f, "The address is for {}, not this transaction's network.", network)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+impl From<AmountError> for BuildError {
+    fn from(error: AmountError) -> Self {
+        BuildError::Amount(error)
+    }
+}
+
+// Assembles an unsigned spend, checking that the inputs pay for the outputs and the fee. Signing is separate.
+#[derive(Debug, Clone)]
+pub struct TxBuilder {
+    inputs: Vec<TxIn>,
+    prevouts: Vec<TxOut>,
+    outputs: Vec<TxOut>,
+    change_script: Option<Vec<u8>>,
+    locktime: u32,
+    sequence: u32,
+    fee_rate: u64,
+    testnet: bool,
+}
+
+impl TxBuilder {
+    pub fn new(testnet: bool) -> Self {
+        Self {
+            inputs: vec![],
+            prevouts: vec![],
+            outputs: vec![],
+            change_script: None,
+            locktime: 0,
+            // Like Bitcoin Core, leave the locktime enforceable without signaling replaceability
+            sequence: SEQUENCE_FINAL - 1,
+            fee_rate: 1,
+            testnet,
+        }
+    }
+
+    // The prevout is needed to know its amount and how large the input will be once signed
+    pub fn add_input(mut self, outpoint: OutPoint, prevout: TxOut) -> Self {
+        self.inputs.push(TxIn::new(outpoint, vec![], self.sequence));
+        self.prevouts.push(prevout);
+        self
+    }
+
+    // A testnet builder takes signet and regtest addresses too, as their transactions are the same
+    pub fn add_output(self, address: &Address, amount: Amount) -> Result<Self, BuildError> {
+        self.check_network(address)?;
+
+        Ok(self.add_script_output(address.script_pubkey().to_bytes(), amount))
+    }
+
+    // For outputs without an address, such as bare multisig, or when copying the outputs of another transaction
+    pub fn add_script_output(mut self, script_pubkey: Vec<u8>, amount: Amount) -> Self {
+        self.outputs.push(TxOut::new(amount, script_pubkey));
+        self
+    }
+
+    // A zero-value OP_RETURN output carrying data, which fails if the data is over MAX_OP_RETURN_DATA bytes
+    pub fn add_data_output(self, data: &[u8]) -> Result<Self, ScriptError> {
+        Ok(self.add_script_output(Script::op_return(data)?.to_bytes(), Amount::ZERO))
+    }
+
+    // Sends whatever is left after the outputs and the fee back to this address, unless it would be dust
+    pub fn add_change(self, address: &Address) -> Result<Self, BuildError> {
+        self.check_network(address)?;
+
+        Ok(self.add_script_change(address.script_pubkey().to_bytes()))
+    }
+
+    // For change to a script_pubkey taken from an existing transaction, as a fee bump does
+    pub fn add_script_change(mut self, script_pubkey: Vec<u8>) -> Self {
+        self.change_script = Some(script_pubkey);
+        self
+    }
+
+    pub fn set_locktime(mut self, locktime: u32) -> Self {
+        self.locktime = locktime;
+        self
+    }
+
+    // BIP125 opt-in replacement for every input
+    pub fn enable_rbf(mut self) -> Self {
+        self.sequence = SEQUENCE_RBF_THRESHOLD - 1;
+        for input in &mut self.inputs {
+            input.sequence = self.sequence;
+        }
+        self
+    }
+
+    pub fn fee_rate(mut self, sat_per_vb: u64) -> Self {
+        self.fee_rate = sat_per_vb;
+        self
+    }
+
+    // The virtual size the transaction will have once every input is signed, not counting any change
+    pub fn estimated_vsize(&self) -> Result<u64, BuildError> {
+        self.signed_vsize(self.unsigned_tx())
+    }
+
+    pub fn required_fee(&self) -> Result<Amount, BuildError> {
+        self.fee_for(self.estimated_vsize()?)
+    }
+
+    /*
+        Without change, whatever the inputs provide beyond the outputs goes to the fee. With change, the fee
+        is worked out for the transaction including the change output, and the change is what remains. The
+        amount is a fixed 8 bytes, so the change does not alter the size it was estimated at. When that leaves
+        nothing or only dust, the change output is dropped and the transaction is re-estimated without it, the
+        remainder going to the fee.
+     */
+    pub fn finish(self) -> Result<Tx, BuildError> {
+        if self.inputs.is_empty() {
+            return Err(BuildError::NoInputs);
+        }
+        if self.outputs.is_empty() {
+            return Err(BuildError::NoOutputs);
+        }
+
+        let available = self.prevouts.iter().map(|prevout| prevout.amount).sum::<Result<Amount, AmountError>>()?;
+        let spent = self.outputs.iter().map(|output| output.amount).sum::<Result<Amount, AmountError>>()?;
+
+        if let Some(change_script) = &self.change_script {
+            let mut with_change = self.unsigned_tx();
+            with_change.outputs.push(TxOut::new(Amount::ZERO, change_script.clone()));
+            let fee = self.fee_for(self.signed_vsize(with_change.clone())?)?;
+
+            let change = spent.checked_add(fee).and_then(|required| available.checked_sub(required));
+            if let Some(change) = change {
+                let change_output = with_change.outputs.last_mut().expect("The change output was just added.");
+                change_output.amount = change;
+                if change > Amount::ZERO && !change_output.is_dust(DUST_RELAY_FEE_RATE) {
+                    return Ok(with_change);
+                }
+            }
+        }
+
+        let required = spent.checked_add(self.required_fee()?).ok_or(AmountError::Overflow)?;
+        if available < required {
+            return Err(BuildError::InsufficientFunds { available, required });
+        }
+
+        Ok(self.unsigned_tx())
+    }
+
+    // A fee rate set high enough can overflow the fee, which is an error rather than a wrapped amount
+    fn fee_for(&self, vsize: u64) -> Result<Amount, BuildError> {
+        Ok(Amount::from_sat(vsize).checked_mul(self.fee_rate).ok_or(AmountError::Overflow)?)
+    }
+
+    fn check_network(&self, address: &Address) -> Result<(), BuildError> {
+        match (address.network() != Network::Mainnet) == self.testnet {
+            true => Ok(()),
+            false => Err(BuildError::WrongNetwork(address.network())),
+        }
+    }
+
+    fn unsigned_tx(&self) -> Tx {
+        Tx::new(2, self.inputs.clone(), self.outputs.clone(), self.locktime, self.testnet)
+    }
+
+    // The size of a transaction spending this builder's inputs, once they are signed
+    pub(crate) fn signed_vsize(&self, mut tx: Tx) -> Result<u64, BuildError> {
+        for (index, (input, prevout)) in tx.inputs.iter_mut().zip(&self.prevouts).enumerate() {
+            let (script_sig, witness) =
+                placeholder_signature(&prevout.script_pubkey).ok_or(BuildError::UnsupportedInput(index))?;
+            input.script_sig = script_sig;
+            input.witness = witness;
+        }
+
+        Ok(tx.vsize())
+    }
+}
+
+// The weight an input spending this script_pubkey will have once signed, for the types estimated_vsize supports
+pub fn signed_input_weight(script_pubkey: &[u8]) -> Option<u64> {
+    let (script_sig, witness) = placeholder_signature(script_pubkey)?;
+
+    let base = 36 + encode_varint(script_sig.len() as u64).len() + script_sig.len() + 4;
+    // A legacy input only adds its empty witness when another input makes the transaction segwit
+    let witness_size: usize = match witness.len() {
+        0 => 0,
+        count => {
+            encode_varint(count as u64).len()
+                + witness.iter().map(|item| encode_varint(item.len() as u64).len() + item.len()).sum::<usize>()
+        }
+    };
+
+    Some(base as u64 * 4 + witness_size as u64)
+}
+
+// A script_sig and witness of the size a signature for this script_pubkey will take, for the types a single key spends
+fn placeholder_signature(script_pubkey: &[u8]) -> Option<(Vec<u8>, Vec<Vec<u8>>)> {
+    if Script::from_bytes(script_pubkey).is_ok_and(|script| script.is_p2pkh()) {
+        let script_sig = Script::p2pkh_unlock(&[0; ECDSA_SIGNATURE_SIZE], &[0; COMPRESSED_PUBKEY_SIZE]);
+        return Some((script_sig.to_bytes(), vec![]));
+    }
+
+    match script_pubkey {
+        // P2WPKH: witness <sig> <pubkey>
+        [0x00, 0x14, ..] if script_pubkey.len() == 22 => {
+            Some((vec![], Witness::p2wpkh(&[0; ECDSA_SIGNATURE_SIZE], &[0; COMPRESSED_PUBKEY_SIZE]).into()))
+        }
+        // P2TR key path: witness <sig>
+        [0x51, 0x20, ..] if script_pubkey.len() == 34 => Some((vec![], vec![vec![0; SCHNORR_SIGNATURE_SIZE]])),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use crate::address::{Address, AddressKind};
+    use crate::encoding::hex;
+    use crate::hashing::hash256::Hash256;
+    use crate::network::Network;
+    use crate::script::interpreter::ScriptError;
+    use crate::tx::amount::{Amount, AmountError};
+    use crate::tx::fixtures::{p2wpkh, TXID};
+    use crate::tx::outpoint::OutPoint;
+    use crate::tx::transaction::Tx;
+    use crate::tx::tx_builder::{signed_input_weight, BuildError, TxBuilder};
+    use crate::tx::tx_out::TxOut;
+
+    fn p2wpkh_address(key_hash_byte: u8) -> Address {
+        Address::new(AddressKind::P2wpkh([key_hash_byte; 20]), Network::Mainnet)
+    }
+
+    fn p2wpkh_spend(change: u64) -> TxBuilder {
+        let prevout = TxOut::new(Amount::from_sat(100_000), p2wpkh(0x01));
+
+        TxBuilder::new(false)
+            .add_input(OutPoint::new(Hash256::from_str(TXID).unwrap(), 1), prevout)
+            .add_output(&p2wpkh_address(0x02), Amount::from_sat(50_000))
+            .unwrap()
+            .add_output(&p2wpkh_address(0x03), Amount::from_sat(change))
+            .unwrap()
+            .fee_rate(10)
+    }
+
+    fn spend_with_change(payment: u64) -> TxBuilder {
+        let prevout = TxOut::new(Amount::from_sat(100_000), p2wpkh(0x01));
+
+        TxBuilder::new(false)
+            .add_input(OutPoint::new(Hash256::from_str(TXID).unwrap(), 1), prevout)
+            .add_output(&p2wpkh_address(0x02), Amount::from_sat(payment))
+            .unwrap()
+            .add_change(&p2wpkh_address(0x03))
+            .unwrap()
+            .fee_rate(10)
+    }
+
+    // The fee a transaction from spend_with_change pays, and its size once signed
+    fn fee_and_vsize(tx: &Tx) -> (u64, u64) {
+        let prevout = TxOut::new(Amount::from_sat(100_000), p2wpkh(0x01));
+        let same_outputs = tx.outputs.iter().fold(
+            TxBuilder::new(false).add_input(OutPoint::default(), prevout),
+            |builder, output| builder.add_script_output(output.script_pubkey.clone(), output.amount),
+        );
+        let fee = 100_000 - tx.outputs.iter().map(|output| output.amount).sum::<Amount>().to_sat();
+
+        (fee, same_outputs.estimated_vsize().unwrap())
+    }
+
+    #[test]
+    fn one_in_two_out_p2wpkh_spend() {
+        // 1 P2WPKH input and 2 P2WPKH outputs come to 141 vbytes, so 1410 satoshis at 10 sat/vB
+        let builder = p2wpkh_spend(100_000 - 50_000 - 1_410);
+        let vsize = builder.estimated_vsize().unwrap();
+
+        let tx = builder.finish().unwrap();
+
+        let fee = 100_000 - tx.outputs.iter().map(|output| output.amount).sum::<Amount>().to_sat();
+        assert_eq!(vsize, 141);
+        assert!(fee.abs_diff(10 * vsize) < vsize, "fee {} for {} vbytes", fee, vsize);
+        assert_eq!(tx.version, 2);
+        assert_eq!(tx.inputs[0].outpoint.vout, 1);
+        assert!(tx.inputs[0].script_sig.is_empty() && tx.inputs[0].witness.is_empty());
+        assert_eq!(&hex::encode(&tx.outputs[1].script_pubkey), "00140303030303030303030303030303030303030303");
+    }
+
+    #[test]
+    fn inputs_must_cover_outputs_plus_fee() {
+        let result = p2wpkh_spend(100_000 - 50_000 - 1_409).finish();
+
+        let expected = BuildError::InsufficientFunds {
+            available: Amount::from_sat(100_000),
+            required: Amount::from_sat(100_001),
+        };
+        assert_eq!(result.map(|_| ()), Err(expected));
+    }
+
+    #[test]
+    fn signed_size_depends_on_the_input_type() {
+        let build = |script_pubkey: Vec<u8>| {
+            TxBuilder::new(false)
+                .add_input(OutPoint::default(), TxOut::new(Amount::from_sat(10_000), script_pubkey))
+                .add_output(&p2wpkh_address(0x02), Amount::from_sat(5_000))
+                .unwrap()
+                .estimated_vsize()
+        };
+        let mut p2pkh = vec![0x76, 0xa9, 0x14];
+        p2pkh.extend([0x01; 20]);
+        p2pkh.extend([0x88, 0xac]);
+        let mut p2tr = vec![0x51, 0x20];
+        p2tr.extend([0x01; 32]);
+
+        assert_eq!(signed_input_weight(&p2pkh), Some(148 * 4));
+        assert_eq!(signed_input_weight(&p2wpkh(0x01)), Some(41 * 4 + 108));
+        // The 68 vbytes wallets budget for a P2WPKH input, give or take a byte of signature length
+        assert!((67..=69).contains(&signed_input_weight(&p2wpkh(0x01)).unwrap().div_ceil(4)));
+        assert_eq!(signed_input_weight(&p2tr), Some(41 * 4 + 66));
+        assert_eq!(signed_input_weight(&[0x51]), None);
+        assert_eq!(build(p2pkh), Ok(189));
+        assert_eq!(build(p2wpkh(0x01)), Ok(110));
+        assert_eq!(build(p2tr), Ok(99));
+        assert_eq!(build(vec![0x51]), Err(BuildError::UnsupportedInput(0)));
+    }
+
+    #[test]
+    fn change_takes_what_the_fee_leaves() {
+        let tx = spend_with_change(50_000).finish().unwrap();
+
+        // The same 141 vbytes as the spend with an explicit change output
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(tx.outputs[1].amount, Amount::from_sat(100_000 - 50_000 - 1_410));
+        assert_eq!(&hex::encode(&tx.outputs[1].script_pubkey), "00140303030303030303030303030303030303030303");
+        assert_eq!(fee_and_vsize(&tx), (1_410, 141));
+    }
+
+    #[test]
+    fn dust_change_is_folded_into_the_fee() {
+        // 290 satoshis of change would be below the 294 satoshi P2WPKH dust threshold
+        let tx = spend_with_change(100_000 - 1_410 - 290).finish().unwrap();
+
+        // Without the change output the transaction is 110 vbytes and overpays by the would-be change
+        assert_eq!(tx.outputs.len(), 1);
+        assert_eq!(fee_and_vsize(&tx), (1_700, 110));
+    }
+
+    #[test]
+    fn change_never_lowers_the_fee_rate_below_the_requested_one() {
+        for payment in (90_000..=98_900).step_by(37) {
+            let tx = spend_with_change(payment).finish().unwrap();
+
+            let (fee, vsize) = fee_and_vsize(&tx);
+            assert!(fee >= 10 * vsize, "payment {}: fee {} for {} vbytes", payment, fee, vsize);
+            assert!(tx.outputs.len() == 1 || tx.outputs[1].amount >= Amount::from_sat(294), "payment {}", payment);
+        }
+    }
+
+    #[test]
+    fn change_cannot_make_up_for_missing_funds() {
+        let result = spend_with_change(99_000).finish();
+
+        let expected = BuildError::InsufficientFunds {
+            available: Amount::from_sat(100_000),
+            required: Amount::from_sat(100_100),
+        };
+        assert_eq!(result.map(|_| ()), Err(expected));
+    }
+
+    #[test]
+    fn rbf_and_locktime_are_applied_to_the_transaction() {
+        let plain = p2wpkh_spend(1_000).set_locktime(800_000).finish().unwrap();
+        let replaceable = p2wpkh_spend(1_000).enable_rbf().finish().unwrap();
+
+        assert_eq!(plain.locktime, 800_000);
+        assert!(plain.is_locktime_enabled());
+        assert!(!plain.signals_rbf());
+        assert!(replaceable.signals_rbf());
+    }
+
+    #[test]
+    fn invalid_amounts_and_empty_transactions_are_rejected() {
+        let prevout = TxOut::new(Amount::from_sat(100_000), p2wpkh(0x01));
+
+        let no_inputs = TxBuilder::new(false).add_output(&p2wpkh_address(0x02), Amount::from_sat(1_000)).unwrap();
+        let no_outputs = TxBuilder::new(false).add_input(OutPoint::default(), prevout.clone()).finish();
+        let too_large = TxBuilder::new(false)
+            .add_input(OutPoint::default(), prevout)
+            .add_output(&p2wpkh_address(0x02), Amount::MAX_MONEY)
+            .unwrap()
+            .add_output(&p2wpkh_address(0x03), Amount::ONE_SAT)
+            .unwrap()
+            .finish();
+
+        assert_eq!(no_inputs.finish().map(|_| ()), Err(BuildError::NoInputs));
+        assert_eq!(no_outputs.map(|_| ()), Err(BuildError::NoOutputs));
+        assert_eq!(
+            too_large.map(|_| ()),
+            Err(BuildError::Amount(AmountError::TooLarge(Amount::MAX_MONEY + Amount::ONE_SAT)))
+        );
+    }
+
+    #[test]
+    fn overflowing_fees_are_rejected() {
+        let builder = p2wpkh_spend(1_000).fee_rate(u64::MAX / 100);
+
+        assert_eq!(builder.required_fee(), Err(BuildError::Amount(AmountError::Overflow)));
+        assert_eq!(builder.finish().map(|_| ()), Err(BuildError::Amount(AmountError::Overflow)));
+        assert_eq!(
+            spend_with_change(50_000).fee_rate(u64::MAX / 100).finish().map(|_| ()),
+            Err(BuildError::Amount(AmountError::Overflow))
+        );
+    }
+
+    #[test]
+    fn data_outputs_carry_no_value() {
+        let tx = spend_with_change(50_000).add_data_output(b"hello").unwrap().finish().unwrap();
+        let too_long = spend_with_change(50_000).add_data_output(&[0; 81]);
+
+        let data_output = &tx.outputs[1];
+        assert_eq!(tx.outputs.len(), 3);
+        assert_eq!(data_output.amount, Amount::ZERO);
+        assert_eq!(&hex::encode(&data_output.script_pubkey), "6a0568656c6c6f");
+        assert_eq!(too_long.map(|_| ()), Err(ScriptError::DataCarrierSize(81)));
+    }
+
+    #[test]
+    fn addresses_must_match_the_network() {
+        let testnet_address = Address::new(AddressKind::P2wpkh([0x02; 20]), Network::Testnet);
+        let regtest_address = Address::new(AddressKind::P2wpkh([0x02; 20]), Network::Regtest);
+
+        let on_mainnet = TxBuilder::new(false).add_output(&testnet_address, Amount::from_sat(1_000));
+        let on_testnet = TxBuilder::new(true).add_output(&p2wpkh_address(0x02), Amount::from_sat(1_000));
+
+        assert_eq!(on_mainnet.map(|_| ()), Err(BuildError::WrongNetwork(Network::Testnet)));
+        assert_eq!(on_testnet.map(|_| ()), Err(BuildError::WrongNetwork(Network::Mainnet)));
+        assert!(TxBuilder::new(true).add_output(&testnet_address, Amount::from_sat(1_000)).is_ok());
+        assert!(TxBuilder::new(true).add_output(&regtest_address, Amount::from_sat(1_000)).is_ok());
+        assert_eq!(
+            TxBuilder::new(false).add_change(&testnet_address).map(|_| ()),
+            Err(BuildError::WrongNetwork(Network::Testnet))
+        );
+    }
+}
@@ -0,0 +1,881 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+use crate::encoding::encodable::Encodable;
+use crate::encoding::hex::{self, HexError};
+use crate::encoding::parse_error::ParseError;
+use crate::hashing::hash256::Hash256;
+use crate::tx::amount::Amount;
+use crate::tx::outpoint::OutPoint;
+use crate::tx::transaction::{Tx, TxError};
+use crate::tx::tx_out::TxOut;
+
+const BLOCKSTREAM_MAINNET_URL: &str = "https://blockstream.info/api";
+const BLOCKSTREAM_TESTNET_URL: &str = "https://blockstream.info/testnet/api";
+const MEMPOOL_SPACE_MAINNET_URL: &str = "https://mempool.space/api";
+const MEMPOOL_SPACE_TESTNET_URL: &str = "https://mempool.space/testnet/api";
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+// How deeply arrays and objects may nest in a response. The UTXO list needs three levels, and the limit keeps
+// a hostile server from exhausting the stack.
+const MAX_JSON_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+// Kept minimal so any HTTP client can be plugged in, and tests can serve canned responses
+pub trait HttpTransport {
+    fn get(&mut self, url: &str, timeout: Duration) -> io::Result<HttpResponse>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Backend {
+    // Any server with the Esplora REST API, such as Blockstream's, at these base URLs
+    Esplora { mainnet_url: String, testnet_url: String },
+    // mempool.space serves the Esplora API under its own base URLs
+    MempoolSpace,
+    // A server that only returns raw transaction hex, at URLs with "{txid}" in place of the transaction id
+    Custom { mainnet_template: String, testnet_template: String },
+}
+
+impl Backend {
+    pub fn blockstream() -> Self {
+        Self::esplora(BLOCKSTREAM_MAINNET_URL, BLOCKSTREAM_TESTNET_URL)
+    }
+
+    pub fn esplora(mainnet_url: &str, testnet_url: &str) -> Self {
+        Backend::Esplora {
+            mainnet_url: mainnet_url.trim_end_matches('/').to_string(),
+            testnet_url: testnet_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn tx_url(&self, txid: &Hash256, testnet: bool) -> String {
+        match (self, self.esplora_url(testnet)) {
+            (Backend::Custom { mainnet_template, testnet_template }, _) => {
+                let template = match testnet {
+                    true => testnet_template,
+                    false => mainnet_template,
+                };
+                template.replace("{txid}", &txid.to_string())
+            }
+            (_, Some(base_url)) => format!("{}/tx/{}/hex", base_url, txid),
+            (_, None) => unreachable!("Every backend but a custom one has an Esplora URL."),
+        }
+    }
+
+    // None when the backend cannot list an address's outputs
+    pub fn utxos_url(&self, address: &str, testnet: bool) -> Option<String> {
+        self.esplora_url(testnet).map(|base_url| format!("{}/address/{}/utxo", base_url, address))
+    }
+
+    fn esplora_url(&self, testnet: bool) -> Option<&str> {
+        match (self, testnet) {
+            (Backend::Esplora { mainnet_url, .. }, false) => Some(mainnet_url),
+            (Backend::Esplora { testnet_url, .. }, true) => Some(testnet_url),
+            (Backend::MempoolSpace, false) => Some(MEMPOOL_SPACE_MAINNET_URL),
+            (Backend::MempoolSpace, true) => Some(MEMPOOL_SPACE_TESTNET_URL),
+            (Backend::Custom { .. }, _) => None,
+        }
+    }
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self::blockstream()
+    }
+}
+
+// An unspent output of an address as the backend reports it, without the script_pubkey
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressUtxo {
+    pub outpoint: OutPoint,
+    pub amount: Amount,
+    pub confirmed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FetchError {
+    Io(io::ErrorKind),
+    Http(u16),
+    Hex(HexError),
+    Parse(ParseError),
+    IdMismatch { requested: Hash256, received: Hash256 },
+    Unsupported,
+    MalformedJson(String),
+}
+
+impl Display for FetchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FetchError::Io(kind) => write!(f, "IO error while fetching transaction: {}.", kind),
+            FetchError::Http(status) => write!(f, "Server responded with HTTP status {}.", status),
+            FetchError::Hex(error) => write!(f, "Server returned invalid hex: {}", error),
+            FetchError::Parse(error) => write!(f, "Server returned an unparseable transaction: {}", error),
+            FetchError::IdMismatch { requested, received } => {
+                write!(f, "Requested transaction {} but the server returned {}.", requested, received)
+            }
+            FetchError::Unsupported => write!(f, "The backend does not support this request."),
+            FetchError::MalformedJson(reason) => write!(f, "Server returned unexpected JSON: {}.", reason),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+impl From<io::Error> for FetchError {
+    fn from(error: io::Error) -> Self {
+        FetchError::Io(error.kind())
+    }
+}
+
+impl From<HexError> for FetchError {
+    fn from(error: HexError) -> Self {
+        FetchError::Hex(error)
+    }
+}
+
+impl From<ParseError> for FetchError {
+    fn from(error: ParseError) -> Self {
+        FetchError::Parse(error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheError {
+    Io(io::ErrorKind),
+    Malformed(String),
+    InvalidEntry(String),
+}
+
+impl Display for CacheError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CacheError::Io(kind) => write!(f, "IO error while accessing the transaction cache: {}.", kind),
+            CacheError::Malformed(reason) => write!(f, "Transaction cache is not valid JSON: {}.", reason),
+            CacheError::InvalidEntry(txid) => write!(f, "Transaction cache entry '{}' is invalid.", txid),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+impl From<io::Error> for CacheError {
+    fn from(error: io::Error) -> Self {
+        CacheError::Io(error.kind())
+    }
+}
+
+// Looks up previous transactions from a block explorer, remembering what it has already fetched
+pub struct TxFetcher<T: HttpTransport> {
+    transport: T,
+    backend: Backend,
+    timeout: Duration,
+    cache: HashMap<Hash256, Tx>,
+}
+
+impl<T: HttpTransport> TxFetcher<T> {
+    pub fn new(transport: T) -> Self {
+        Self::with_backend(transport, Backend::default())
+    }
+
+    // Esplora-compatible servers at these base URLs
+    pub fn with_urls(transport: T, mainnet_url: &str, testnet_url: &str) -> Self {
+        Self::with_backend(transport, Backend::esplora(mainnet_url, testnet_url))
+    }
+
+    pub fn with_backend(transport: T, backend: Backend) -> Self {
+        Self { transport, backend, timeout: DEFAULT_TIMEOUT, cache: HashMap::new() }
+    }
+
+    pub fn backend(&self) -> &Backend {
+        &self.backend
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub fn url_for(&self, txid: &Hash256, testnet: bool) -> String {
+        self.backend.tx_url(txid, testnet)
+    }
+
+    // Adds a transaction obtained some other way, such as one built locally or received from a peer
+    pub fn insert(&mut self, tx: Tx) {
+        self.cache.insert(tx.id(), tx);
+    }
+
+    // `fresh` skips the cache and always asks the server
+    pub fn fetch(&mut self, txid: &Hash256, testnet: bool, fresh: bool) -> Result<Tx, FetchError> {
+        if !fresh {
+            if let Some(tx) = self.cache.get(txid) {
+                return Ok(Tx { testnet, ..tx.clone() });
+            }
+        }
+
+        let body = self.get(&self.url_for(txid, testnet))?;
+        let bytes = hex::decode(body.trim())?;
+        let tx = Tx::parse_exact(&bytes, testnet)?;
+
+        // The server is not trusted to return the transaction that was asked for
+        let received = tx.id();
+        if received != *txid {
+            return Err(FetchError::IdMismatch { requested: *txid, received });
+        }
+
+        self.cache.insert(*txid, tx.clone());
+
+        Ok(tx)
+    }
+
+    // The outputs the backend knows to be unspent at this address, including unconfirmed ones
+    pub fn fetch_utxos(&mut self, address: &str, testnet: bool) -> Result<Vec<AddressUtxo>, FetchError> {
+        let url = self.backend.utxos_url(address, testnet).ok_or(FetchError::Unsupported)?;
+        let body = self.get(&url)?;
+
+        parse_utxos_json(&body)
+    }
+
+    fn get(&mut self, url: &str) -> Result<String, FetchError> {
+        let response = self.transport.get(url, self.timeout)?;
+        if response.status != 200 {
+            return Err(FetchError::Http(response.status));
+        }
+
+        Ok(response.body)
+    }
+
+    // Merges a cache written by dump_cache. Entries are re-verified, so a tampered file cannot inject transactions.
+    pub fn load_cache(&mut self, path: impl AsRef<Path>) -> Result<(), CacheError> {
+        let contents = fs::read_to_string(path)?;
+
+        for (txid_hex, tx_hex) in parse_cache_json(&contents)? {
+            let invalid_entry = || CacheError::InvalidEntry(txid_hex.clone());
+
+            let txid = Hash256::from_str(&txid_hex).map_err(|_| invalid_entry())?;
+            let bytes = hex::decode(&tx_hex).map_err(|_| invalid_entry())?;
+            let tx = Tx::parse_exact(&bytes, false).map_err(|_| invalid_entry())?;
+            if tx.id() != txid {
+                return Err(invalid_entry());
+            }
+
+            self.cache.insert(txid, tx);
+        }
+
+        Ok(())
+    }
+
+    // Writes every cached transaction as a JSON object mapping txid to raw hex, in txid order
+    pub fn dump_cache(&self, path: impl AsRef<Path>) -> Result<(), CacheError> {
+        let entries: BTreeMap<String, String> = self
+            .cache
+            .iter()
+            .map(|(txid, tx)| (txid.to_string(), hex::encode(&tx.serialize_to_vec())))
+            .collect();
+
+        let lines: Vec<String> = entries
+            .iter()
+            .map(|(txid, tx_hex)| format!("  \"{}\": \"{}\"", txid, tx_hex))
+            .collect();
+        let json = match lines.is_empty() {
+            true => "{}\n".to_string(),
+            false => format!("{{\n{}\n}}\n", lines.join(",\n")),
+        };
+
+        fs::write(path, json)?;
+
+        Ok(())
+    }
+}
+
+// Where Tx::fee and Tx::verify look up the outputs a transaction's inputs spend
+pub trait PrevoutFetcher {
+    fn prev_output(&mut self, outpoint: &OutPoint, testnet: bool) -> Result<TxOut, TxError>;
+}
+
+impl<T: HttpTransport> PrevoutFetcher for TxFetcher<T> {
+    fn prev_output(&mut self, outpoint: &OutPoint, testnet: bool) -> Result<TxOut, TxError> {
+        let OutPoint { txid, vout } = *outpoint;
+        let prev_tx = self.fetch(&txid, testnet, false)?;
+        let output_count = prev_tx.outputs.len();
+
+        prev_tx
+            .outputs
+            .into_iter()
+            .nth(vout as usize)
+            .ok_or(TxError::PrevIndexOutOfRange { txid, index: vout, output_count })
+    }
+}
+
+// Outputs already known, such as those a wallet or a PSBT records, without the transactions they are in
+impl PrevoutFetcher for HashMap<OutPoint, TxOut> {
+    fn prev_output(&mut self, outpoint: &OutPoint, _testnet: bool) -> Result<TxOut, TxError> {
+        self.get(outpoint).cloned().ok_or(TxError::UnknownPrevout(*outpoint))
+    }
+}
+
+// Reads the flat string-to-string object that dump_cache writes. Keys and values are hex, so escapes are rejected.
+fn parse_cache_json(json: &str) -> Result<Vec<(String, String)>, CacheError> {
+    let mut chars = json.chars().filter(|character| !character.is_whitespace()).peekable();
+    let mut entries = Vec::new();
+
+    let expect = |chars: &mut std::iter::Peekable<_>, expected: char| match chars.next() {
+        Some(character) if character == expected => Ok(()),
+        Some(character) => Err(CacheError::Malformed(format!("expected '{}', found '{}'", expected, character))),
+        None => Err(CacheError::Malformed(format!("expected '{}', found end of input", expected))),
+    };
+    let read_string = |chars: &mut std::iter::Peekable<_>| -> Result<String, CacheError> {
+        let mut string = String::new();
+        loop {
+            match chars.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => return Err(CacheError::Malformed("unexpected escape sequence".to_string())),
+                Some(character) => string.push(character),
+                None => return Err(CacheError::Malformed("unterminated string".to_string())),
+            }
+        }
+    };
+
+    expect(&mut chars, '{')?;
+    if chars.peek() == Some(&'}') {
+        chars.next();
+    } else {
+        loop {
+            expect(&mut chars, '"')?;
+            let key = read_string(&mut chars)?;
+            expect(&mut chars, ':')?;
+            expect(&mut chars, '"')?;
+            let value = read_string(&mut chars)?;
+            entries.push((key, value));
+
+            match chars.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                Some(character) => {
+                    return Err(CacheError::Malformed(format!("expected ',' or '}}', found '{}'", character)))
+                }
+                None => return Err(CacheError::Malformed("unterminated object".to_string())),
+            }
+        }
+    }
+
+    match chars.next() {
+        None => Ok(entries),
+        Some(character) => Err(CacheError::Malformed(format!("unexpected '{}' after the object", character))),
+    }
+}
+
+// The subset of JSON an explorer's responses need. Numbers are kept as text for the caller to interpret.
+#[derive(Debug, Clone, PartialEq)]
+enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(entries) => entries.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+fn parse_json(json: &str) -> Result<JsonValue, FetchError> {
+    let mut chars = json.chars().peekable();
+    let value = parse_json_value(&mut chars, 0)?;
+    skip_whitespace(&mut chars);
+
+    match chars.next() {
+        None => Ok(value),
+        Some(character) => Err(FetchError::MalformedJson(format!("unexpected '{}' after the value", character))),
+    }
+}
+
+type JsonChars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn skip_whitespace(chars: &mut JsonChars) {
+    while chars.next_if(|character| character.is_whitespace()).is_some() {}
+}
+
+// depth counts the arrays and objects the value is nested in
+fn parse_json_value(chars: &mut JsonChars, depth: usize) -> Result<JsonValue, FetchError> {
+    skip_whitespace(chars);
+    if matches!(chars.peek(), Some('{' | '[')) && depth >= MAX_JSON_DEPTH {
+        return Err(FetchError::MalformedJson(format!("nested deeper than {} levels", MAX_JSON_DEPTH)));
+    }
+
+    match chars.peek().copied() {
+        Some('{') => {
+            chars.next();
+            let mut entries = Vec::new();
+            skip_whitespace(chars);
+            if chars.next_if_eq(&'}').is_some() {
+                return Ok(JsonValue::Object(entries));
+            }
+            loop {
+                skip_whitespace(chars);
+                let key = parse_json_string(chars)?;
+                skip_whitespace(chars);
+                expect_json(chars, ':')?;
+                entries.push((key, parse_json_value(chars, depth + 1)?));
+                skip_whitespace(chars);
+                if chars.next_if_eq(&'}').is_some() {
+                    return Ok(JsonValue::Object(entries));
+                }
+                expect_json(chars, ',')?;
+            }
+        }
+        Some('[') => {
+            chars.next();
+            let mut elements = Vec::new();
+            skip_whitespace(chars);
+            if chars.next_if_eq(&']').is_some() {
+                return Ok(JsonValue::Array(elements));
+            }
+            loop {
+                elements.push(parse_json_value(chars, depth + 1)?);
+                skip_whitespace(chars);
+                if chars.next_if_eq(&']').is_some() {
+                    return Ok(JsonValue::Array(elements));
+                }
+                expect_json(chars, ',')?;
+            }
+        }
+        Some('"') => Ok(JsonValue::String(parse_json_string(chars)?)),
+        Some(character) if character == '-' || character.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(digit) = chars.next_if(|c| matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E')) {
+                number.push(digit);
+            }
+            Ok(JsonValue::Number(number))
+        }
+        Some(_) => {
+            let mut word = String::new();
+            while let Some(letter) = chars.next_if(|character| character.is_ascii_alphabetic()) {
+                word.push(letter);
+            }
+            match word.as_str() {
+                "null" => Ok(JsonValue::Null),
+                "true" => Ok(JsonValue::Bool(true)),
+                "false" => Ok(JsonValue::Bool(false)),
+                _ => Err(FetchError::MalformedJson(format!("unexpected '{}'", word))),
+            }
+        }
+        None => Err(FetchError::MalformedJson("unexpected end of input".to_string())),
+    }
+}
+
+fn expect_json(chars: &mut JsonChars, expected: char) -> Result<(), FetchError> {
+    match chars.next() {
+        Some(character) if character == expected => Ok(()),
+        Some(character) => Err(FetchError::MalformedJson(format!("expected '{}', found '{}'", expected, character))),
+        None => Err(FetchError::MalformedJson(format!("expected '{}', found end of input", expected))),
+    }
+}
+
+// Explorers only put hex and plain words in strings, so escapes other than \" and \\ are not interpreted
+fn parse_json_string(chars: &mut JsonChars) -> Result<String, FetchError> {
+    expect_json(chars, '"')?;
+    let mut string = String::new();
+
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(string),
+            Some('\\') => match chars.next() {
+                Some(escaped @ ('"' | '\\')) => string.push(escaped),
+                _ => return Err(FetchError::MalformedJson("unsupported escape sequence".to_string())),
+            },
+            Some(character) => string.push(character),
+            None => return Err(FetchError::MalformedJson("unterminated string".to_string())),
+        }
+    }
+}
+
+// Reads Esplora's address UTXO list: [{"txid", "vout", "status": {"confirmed", ...}, "value"}, ...]
+fn parse_utxos_json(json: &str) -> Result<Vec<AddressUtxo>, FetchError> {
+    let JsonValue::Array(entries) = parse_json(json)? else {
+        return Err(FetchError::MalformedJson("expected an array of outputs".to_string()));
+    };
+
+    entries
+        .iter()
+        .map(|entry| {
+            let field = |name: &str| entry.get(name).ok_or(FetchError::MalformedJson(format!("missing '{}'", name)));
+            let invalid = |name: &str| FetchError::MalformedJson(format!("invalid '{}'", name));
+            let number = |name: &str| match field(name)? {
+                JsonValue::Number(number) => number.parse::<u64>().map_err(|_| invalid(name)),
+                _ => Err(invalid(name)),
+            };
+
+            let txid = match field("txid")? {
+                JsonValue::String(txid) => Hash256::from_str(txid).map_err(|_| invalid("txid"))?,
+                _ => return Err(invalid("txid")),
+            };
+            let vout = u32::try_from(number("vout")?).map_err(|_| invalid("vout"))?;
+            let confirmed = match field("status")?.get("confirmed") {
+                Some(JsonValue::Bool(confirmed)) => *confirmed,
+                _ => return Err(invalid("status")),
+            };
+
+            let amount = Amount::from_sat(number("value")?).within_max_money().map_err(|_| invalid("value"))?;
+
+            Ok(AddressUtxo { outpoint: OutPoint::new(txid, vout), amount, confirmed })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io;
+    use std::path::PathBuf;
+    use std::str::FromStr;
+    use std::time::Duration;
+    use crate::encoding::hex::HexError;
+    use crate::hashing::hash256::Hash256;
+    use crate::tx::amount::Amount;
+    use crate::tx::fixtures::{TXID, TX_HEX};
+    use crate::tx::outpoint::OutPoint;
+    use crate::tx::tx_fetcher::{
+        AddressUtxo, Backend, CacheError, FetchError, HttpResponse, HttpTransport, TxFetcher, DEFAULT_TIMEOUT,
+    };
+
+    #[derive(Default)]
+    struct MockTransport {
+        responses: HashMap<String, HttpResponse>,
+        requests: Vec<String>,
+        timeouts: Vec<Duration>,
+    }
+
+    impl MockTransport {
+        fn serving(url: &str, status: u16, body: &str) -> Self {
+            let mut transport = Self::default();
+            transport.responses.insert(url.to_string(), HttpResponse { status, body: body.to_string() });
+
+            transport
+        }
+    }
+
+    impl HttpTransport for MockTransport {
+        fn get(&mut self, url: &str, timeout: Duration) -> io::Result<HttpResponse> {
+            self.requests.push(url.to_string());
+            self.timeouts.push(timeout);
+
+            self.responses
+                .get(url)
+                .cloned()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::ConnectionRefused, "no canned response"))
+        }
+    }
+
+    // A cache file unique to the test, removed when dropped
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!("tx_fetcher_{}_{}.json", std::process::id(), name)))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn tx_url(txid: &str) -> String {
+        format!("https://blockstream.info/api/tx/{}/hex", txid)
+    }
+
+    const ADDRESS: &str = "tb1qerzrlxcfu24davlur5sqmgzzgsal6wusda40er";
+
+    // The shape of mempool.space's /address/:address/utxo response, one output confirmed and one in the mempool
+    const UTXOS_JSON: &str = r#"[
+        {
+            "txid": "452c629d67e41baec3ac6f04fe744b4b9617f8f859c63b3002f8684e7a4fee03",
+            "vout": 1,
+            "status": {
+                "confirmed": true,
+                "block_height": 2573451,
+                "block_hash": "000000000000001a9b2a1f6e4d5c5e0a4c3b1e6d8f9c2b7a0e4d1c3b5a79e8f2",
+                "block_time": 1702468137
+            },
+            "value": 100000
+        },
+        {
+            "txid": "ee51510d7bbabe28052038d1deb10c03ec74f06a79e21913c6fcf48d56217c87",
+            "vout": 0,
+            "status": {"confirmed": false},
+            "value": 5461
+        }
+    ]"#;
+
+    #[test]
+    fn fetched_transaction_is_parsed_and_verified() {
+        let transport = MockTransport::serving(&tx_url(TXID), 200, &format!("{}\n", TX_HEX));
+        let mut subject = TxFetcher::new(transport);
+        let txid = Hash256::from_str(TXID).unwrap();
+
+        let tx = subject.fetch(&txid, false, false).unwrap();
+
+        assert_eq!(tx.id(), txid);
+        assert_eq!(tx.outputs.len(), 2);
+        assert_eq!(subject.transport.requests, vec![tx_url(TXID)]);
+    }
+
+    #[test]
+    fn repeated_fetches_are_served_from_the_cache_unless_fresh() {
+        let transport = MockTransport::serving(&tx_url(TXID), 200, TX_HEX);
+        let mut subject = TxFetcher::new(transport);
+        let txid = Hash256::from_str(TXID).unwrap();
+
+        subject.fetch(&txid, false, false).unwrap();
+        subject.fetch(&txid, false, false).unwrap();
+        assert_eq!(subject.transport.requests.len(), 1);
+
+        subject.fetch(&txid, false, true).unwrap();
+        assert_eq!(subject.transport.requests.len(), 2);
+    }
+
+    #[test]
+    fn testnet_requests_use_the_testnet_url() {
+        let transport = MockTransport::default();
+        let mut subject = TxFetcher::with_urls(transport, "http://localhost:3002/", "http://localhost:3001");
+        let txid = Hash256::from_str(TXID).unwrap();
+
+        let _ = subject.fetch(&txid, true, false);
+        let _ = subject.fetch(&txid, false, false);
+
+        assert_eq!(subject.transport.requests, vec![
+            format!("http://localhost:3001/tx/{}/hex", TXID),
+            format!("http://localhost:3002/tx/{}/hex", TXID),
+        ]);
+    }
+
+    #[test]
+    fn transaction_with_a_different_id_is_rejected() {
+        let requested = "ee51510d7bbabe28052038d1deb10c03ec74f06a79e21913c6fcf48d56217c87";
+        let transport = MockTransport::serving(&tx_url(requested), 200, TX_HEX);
+        let mut subject = TxFetcher::new(transport);
+
+        let result = subject.fetch(&Hash256::from_str(requested).unwrap(), false, false);
+
+        assert_eq!(result, Err(FetchError::IdMismatch {
+            requested: Hash256::from_str(requested).unwrap(),
+            received: Hash256::from_str(TXID).unwrap(),
+        }));
+    }
+
+    #[test]
+    fn http_and_transport_errors_are_reported() {
+        let transport = MockTransport::serving(&tx_url(TXID), 404, "Transaction not found");
+        let mut subject = TxFetcher::new(transport);
+        let txid = Hash256::from_str(TXID).unwrap();
+
+        assert_eq!(subject.fetch(&txid, false, false), Err(FetchError::Http(404)));
+        assert_eq!(
+            subject.fetch(&txid, true, false),
+            Err(FetchError::Io(io::ErrorKind::ConnectionRefused))
+        );
+    }
+
+    #[test]
+    fn malformed_responses_are_reported() {
+        let txid = Hash256::from_str(TXID).unwrap();
+
+        let mut not_hex = TxFetcher::new(MockTransport::serving(&tx_url(TXID), 200, "<html>"));
+        let mut truncated = TxFetcher::new(MockTransport::serving(&tx_url(TXID), 200, &TX_HEX[..100]));
+
+        assert_eq!(
+            not_hex.fetch(&txid, false, false),
+            Err(FetchError::Hex(HexError::InvalidCharacter { character: '<', position: 0 }))
+        );
+        assert!(matches!(truncated.fetch(&txid, false, false), Err(FetchError::Parse(_))));
+    }
+
+    #[test]
+    fn dumped_cache_serves_a_new_fetcher_without_the_network() {
+        let cache_file = TempFile::new("reload");
+        let txid = Hash256::from_str(TXID).unwrap();
+        let mut online = TxFetcher::new(MockTransport::serving(&tx_url(TXID), 200, TX_HEX));
+        let expected = online.fetch(&txid, false, false).unwrap();
+        online.dump_cache(&cache_file.0).unwrap();
+
+        let mut offline = TxFetcher::new(MockTransport::default());
+        offline.load_cache(&cache_file.0).unwrap();
+
+        assert_eq!(offline.fetch(&txid, false, false), Ok(expected));
+        assert!(offline.transport.requests.is_empty());
+        assert_eq!(
+            offline.fetch(&txid, false, true),
+            Err(FetchError::Io(io::ErrorKind::ConnectionRefused))
+        );
+    }
+
+    #[test]
+    fn cache_misses_are_fetched_and_written_through() {
+        let cache_file = TempFile::new("write_through");
+        let txid = Hash256::from_str(TXID).unwrap();
+        let mut subject = TxFetcher::new(MockTransport::serving(&tx_url(TXID), 200, TX_HEX));
+        subject.dump_cache(&cache_file.0).unwrap();
+        assert_eq!(fs::read_to_string(&cache_file.0).unwrap(), "{}\n");
+
+        subject.load_cache(&cache_file.0).unwrap();
+        subject.fetch(&txid, false, false).unwrap();
+        subject.dump_cache(&cache_file.0).unwrap();
+
+        assert_eq!(subject.transport.requests.len(), 1);
+        assert_eq!(
+            fs::read_to_string(&cache_file.0).unwrap(),
+            format!("{{\n  \"{}\": \"{}\"\n}}\n", TXID, TX_HEX)
+        );
+    }
+
+    #[test]
+    fn corrupted_cache_files_are_reported() {
+        let cache_file = TempFile::new("corrupted");
+        let mut subject = TxFetcher::new(MockTransport::default());
+        let cases = [
+            ("", CacheError::Malformed("expected '{', found end of input".to_string())),
+            ("{\"abc\": \"def\"", CacheError::Malformed("unterminated object".to_string())),
+            ("{\"abc\" \"def\"}", CacheError::Malformed("expected ':', found '\"'".to_string())),
+            ("{} {}", CacheError::Malformed("unexpected '{' after the object".to_string())),
+            ("{\"abc\": \"def\"}", CacheError::InvalidEntry("abc".to_string())),
+        ];
+
+        for (contents, expected) in cases {
+            fs::write(&cache_file.0, contents).unwrap();
+
+            assert_eq!(subject.load_cache(&cache_file.0), Err(expected));
+        }
+    }
+
+    #[test]
+    fn cache_entry_under_the_wrong_txid_is_rejected() {
+        let cache_file = TempFile::new("wrong_txid");
+        let wrong_txid = "ee51510d7bbabe28052038d1deb10c03ec74f06a79e21913c6fcf48d56217c87";
+        fs::write(&cache_file.0, format!("{{\"{}\": \"{}\"}}", wrong_txid, TX_HEX)).unwrap();
+        let mut subject = TxFetcher::new(MockTransport::default());
+
+        let result = subject.load_cache(&cache_file.0);
+
+        assert_eq!(result, Err(CacheError::InvalidEntry(wrong_txid.to_string())));
+    }
+
+    #[test]
+    fn missing_cache_file_is_an_io_error() {
+        let mut subject = TxFetcher::new(MockTransport::default());
+
+        let result = subject.load_cache(&TempFile::new("missing").0);
+
+        assert_eq!(result, Err(CacheError::Io(io::ErrorKind::NotFound)));
+    }
+
+    #[test]
+    fn urls_depend_on_the_backend_and_network() {
+        let txid = Hash256::from_str(TXID).unwrap();
+        let custom = Backend::Custom {
+            mainnet_template: "http://localhost:8080/raw/{txid}".to_string(),
+            testnet_template: "http://localhost:8080/testnet/raw/{txid}".to_string(),
+        };
+        let cases = [
+            (Backend::blockstream(), false, "https://blockstream.info/api"),
+            (Backend::blockstream(), true, "https://blockstream.info/testnet/api"),
+            (Backend::MempoolSpace, false, "https://mempool.space/api"),
+            (Backend::MempoolSpace, true, "https://mempool.space/testnet/api"),
+            (Backend::esplora("http://localhost:3002/", "http://localhost:3001"), true, "http://localhost:3001"),
+        ];
+
+        for (backend, testnet, base_url) in cases {
+            assert_eq!(backend.tx_url(&txid, testnet), format!("{}/tx/{}/hex", base_url, TXID));
+            assert_eq!(
+                backend.utxos_url(ADDRESS, testnet),
+                Some(format!("{}/address/{}/utxo", base_url, ADDRESS))
+            );
+        }
+        assert_eq!(custom.tx_url(&txid, false), format!("http://localhost:8080/raw/{}", TXID));
+        assert_eq!(custom.tx_url(&txid, true), format!("http://localhost:8080/testnet/raw/{}", TXID));
+        assert_eq!(custom.utxos_url(ADDRESS, true), None);
+        assert_eq!(Backend::default(), Backend::blockstream());
+    }
+
+    #[test]
+    fn requests_carry_the_configured_timeout() {
+        let transport = MockTransport::serving(&tx_url(TXID), 200, TX_HEX);
+        let mut subject = TxFetcher::new(transport);
+        let txid = Hash256::from_str(TXID).unwrap();
+
+        subject.fetch(&txid, false, true).unwrap();
+        subject.set_timeout(Duration::from_secs(5));
+        subject.fetch(&txid, false, true).unwrap();
+
+        assert_eq!(subject.transport.timeouts, vec![DEFAULT_TIMEOUT, Duration::from_secs(5)]);
+    }
+
+    #[test]
+    fn utxos_are_parsed_from_the_mempool_space_response() {
+        let url = format!("https://mempool.space/testnet/api/address/{}/utxo", ADDRESS);
+        let transport = MockTransport::serving(&url, 200, UTXOS_JSON);
+        let mut subject = TxFetcher::with_backend(transport, Backend::MempoolSpace);
+        let outpoint = |txid: &str, vout| OutPoint::new(Hash256::from_str(txid).unwrap(), vout);
+
+        let utxos = subject.fetch_utxos(ADDRESS, true).unwrap();
+
+        assert_eq!(utxos, vec![
+            AddressUtxo { outpoint: outpoint(TXID, 1), amount: Amount::from_sat(100_000), confirmed: true },
+            AddressUtxo {
+                outpoint: outpoint("ee51510d7bbabe28052038d1deb10c03ec74f06a79e21913c6fcf48d56217c87", 0),
+                amount: Amount::from_sat(5461),
+                confirmed: false,
+            },
+        ]);
+        assert_eq!(subject.transport.requests, vec![url]);
+    }
+
+    #[test]
+    fn utxo_lookups_report_unsupported_backends_and_bad_responses() {
+        let custom = Backend::Custom { mainnet_template: "{txid}".to_string(), testnet_template: "{txid}".to_string() };
+        let url = format!("https://blockstream.info/api/address/{}/utxo", ADDRESS);
+        let respond = |body: &str| TxFetcher::new(MockTransport::serving(&url, 200, body)).fetch_utxos(ADDRESS, false);
+
+        let mut unsupported = TxFetcher::with_backend(MockTransport::default(), custom);
+        assert_eq!(unsupported.fetch_utxos(ADDRESS, false), Err(FetchError::Unsupported));
+        assert!(unsupported.transport.requests.is_empty());
+
+        assert_eq!(respond("[]"), Ok(vec![]));
+        assert_eq!(respond("{}"), Err(FetchError::MalformedJson("expected an array of outputs".to_string())));
+        assert_eq!(
+            respond(r#"[{"txid": "00", "vout": 0, "status": {"confirmed": true}, "value": 1}]"#),
+            Err(FetchError::MalformedJson("invalid 'txid'".to_string()))
+        );
+        assert_eq!(
+            respond(&format!(r#"[{{"txid": "{}", "vout": 0, "status": {{}}}}]"#, TXID)),
+            Err(FetchError::MalformedJson("invalid 'status'".to_string()))
+        );
+        assert_eq!(respond("[{"), Err(FetchError::MalformedJson("expected '\"', found end of input".to_string())));
+        // One satoshi more than will ever exist
+        let too_much = r#"{"confirmed": true}, "value": 2100000000000001"#;
+        assert_eq!(
+            respond(&format!(r#"[{{"txid": "{}", "vout": 0, "status": {}}}]"#, TXID, too_much)),
+            Err(FetchError::MalformedJson("invalid 'value'".to_string()))
+        );
+        assert_eq!(respond(&"[".repeat(32)), Err(FetchError::MalformedJson("unexpected end of input".to_string())));
+        assert_eq!(
+            respond(&"[".repeat(100_000)),
+            Err(FetchError::MalformedJson("nested deeper than 32 levels".to_string()))
+        );
+    }
+}
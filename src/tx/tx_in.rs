@@ -0,0 +1,146 @@
+use std::io::{Read, Write};
+use crate::encoding::encodable::{Decodable, Encodable};
+use crate::encoding::parse_error::ParseError;
+use crate::encoding::varint::{read_var_bytes, write_var_bytes};
+use crate::tx::locktime::{RelativeLockTime, SEQUENCE_FINAL, SEQUENCE_RBF_THRESHOLD};
+use crate::tx::outpoint::OutPoint;
+use crate::tx::transaction::TxError;
+use crate::tx::tx_fetcher::PrevoutFetcher;
+use crate::tx::tx_out::TxOut;
+
+/*
+    Wire format:
+
+    outpoint    36 bytes, the txid in wire order followed by the 4-byte little-endian output index
+    script_sig  varint length followed by the script bytes
+    sequence    4 bytes little-endian
+
+    The witness is serialized by the transaction, after all of the outputs.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxIn {
+    pub outpoint: OutPoint,
+    // Kept raw so the input serializes back exactly, whether or not the bytes parse as a Script
+    pub script_sig: Vec<u8>,
+    pub sequence: u32,
+    // Empty for legacy inputs
+    pub witness: Vec<Vec<u8>>,
+}
+
+impl TxIn {
+    pub fn new(outpoint: OutPoint, script_sig: Vec<u8>, sequence: u32) -> Self {
+        Self {
+            outpoint,
+            script_sig,
+            sequence,
+            witness: vec![],
+        }
+    }
+
+    pub fn is_final(&self) -> bool {
+        self.sequence == SEQUENCE_FINAL
+    }
+
+    pub fn signals_rbf(&self) -> bool {
+        self.sequence < SEQUENCE_RBF_THRESHOLD
+    }
+
+    // The BIP68 relative locktime encoded in the sequence. It is only enforced in transactions of version 2 or above.
+    pub fn relative_locktime(&self) -> Option<RelativeLockTime> {
+        RelativeLockTime::from_sequence(self.sequence)
+    }
+
+    // The output this input spends, looked up through the fetcher
+    pub fn prev_output(&self, fetcher: &mut impl PrevoutFetcher, testnet: bool) -> Result<TxOut, TxError> {
+        fetcher.prev_output(&self.outpoint, testnet)
+    }
+}
+
+impl Decodable for TxIn {
+    fn parse(reader: &mut impl Read) -> Result<Self, ParseError> {
+        let parse_fields = |reader: &mut _| -> Result<Self, ParseError> {
+            Ok(Self {
+                outpoint: OutPoint::parse(reader)?,
+                script_sig: read_var_bytes(reader)?,
+                sequence: u32::parse(reader)?,
+                witness: vec![],
+            })
+        };
+
+        parse_fields(reader).map_err(|error| error.in_type("TxIn"))
+    }
+}
+
+impl Encodable for TxIn {
+    fn serialize(&self, writer: &mut impl Write) -> Result<(), ParseError> {
+        self.outpoint.serialize(writer)?;
+        write_var_bytes(writer, &self.script_sig)?;
+        self.sequence.serialize(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::encodable::{Decodable, Encodable};
+    use crate::encoding::hex;
+    use crate::encoding::parse_error::ParseError;
+    use crate::tx::locktime::RelativeLockTime;
+    use crate::tx::outpoint::OutPoint;
+    use crate::tx::tx_in::TxIn;
+
+    // The single input of the Programming Bitcoin chapter 5 transaction (mainnet
+    // 452c629d67e41baec3ac6f04fe744b4b9617f8f859c63b3002f8684e7a4fee03)
+    const TX_IN_HEX: &str = "813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100ed81ff\
+                             192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f561\
+                             00f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e2\
+                             13bf016b278afeffffff";
+
+    #[test]
+    fn input_of_a_mainnet_transaction_parses() {
+        let bytes = hex::decode(TX_IN_HEX).unwrap();
+
+        let subject = TxIn::parse_exact(&bytes).unwrap();
+
+        assert_eq!(
+            subject.outpoint.to_string(),
+            "d1c789a9c60383bf715f3f6ad9d14b91fe55f3deb369fe5d9280cb1a01793f81:0"
+        );
+        assert_eq!(subject.script_sig.len(), 0x6b);
+        assert_eq!(subject.sequence, 0xfffffffe);
+    }
+
+    #[test]
+    fn input_re_serializes_to_identical_bytes() {
+        let bytes = hex::decode(TX_IN_HEX).unwrap();
+
+        let subject = TxIn::parse_exact(&bytes).unwrap();
+
+        assert_eq!(subject.serialize_to_vec(), bytes);
+    }
+
+    #[test]
+    fn truncated_input_errors_cleanly() {
+        let bytes = hex::decode(TX_IN_HEX).unwrap();
+
+        for length in [0, 20, 35, 40, 100, bytes.len() - 1] {
+            let result = TxIn::parse(&mut &bytes[..length]);
+
+            assert!(matches!(result, Err(ParseError::Truncated(_))), "length {}: {:?}", length, result);
+        }
+        assert_eq!(TxIn::parse(&mut &bytes[..36]), Err(ParseError::Truncated("TxIn")));
+    }
+
+    #[test]
+    fn sequence_flags_are_decoded() {
+        let input = |sequence| TxIn::new(OutPoint::default(), vec![], sequence);
+
+        assert!(input(0xffffffff).is_final());
+        assert_eq!(input(0xffffffff).relative_locktime(), None);
+        assert_eq!(input(0x80000010).relative_locktime(), None);
+        assert_eq!(input(0x00000010).relative_locktime(), Some(RelativeLockTime::Blocks(16)));
+        assert_eq!(input(0x00400010).relative_locktime(), Some(RelativeLockTime::Time(16)));
+        assert!(input(0xfffffffd).signals_rbf());
+        assert!(!input(0xfffffffe).signals_rbf());
+        assert!(!input(0xfffffffe).is_final());
+    }
+}
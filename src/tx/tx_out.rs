@@ -0,0 +1,121 @@
+use std::io::{Read, Write};
+use crate::encoding::encodable::{Decodable, Encodable};
+use crate::encoding::parse_error::ParseError;
+use crate::encoding::varint::{read_var_bytes, write_var_bytes};
+use crate::tx::amount::Amount;
+
+/*
+    Wire format:
+
+    amount         8 bytes little-endian, in satoshis
+    script_pubkey  varint length followed by the script bytes
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxOut {
+    pub amount: Amount,
+    // Outputs can lock coins to bytes that do not parse as a script, so these are kept raw; see Script::from_bytes
+    pub script_pubkey: Vec<u8>,
+}
+
+impl TxOut {
+    pub fn new(amount: Amount, script_pubkey: Vec<u8>) -> Self {
+        Self {
+            amount,
+            script_pubkey,
+        }
+    }
+
+    pub fn amount_sats(&self) -> u64 {
+        self.amount.to_sat()
+    }
+}
+
+impl Decodable for TxOut {
+    fn parse(reader: &mut impl Read) -> Result<Self, ParseError> {
+        let parse_fields = |reader: &mut _| -> Result<Self, ParseError> {
+            Ok(Self {
+                amount: Amount::parse(reader)?,
+                script_pubkey: read_var_bytes(reader)?,
+            })
+        };
+
+        parse_fields(reader).map_err(|error| error.in_type("TxOut"))
+    }
+}
+
+impl Encodable for TxOut {
+    fn serialize(&self, writer: &mut impl Write) -> Result<(), ParseError> {
+        self.amount.serialize(writer)?;
+        write_var_bytes(writer, &self.script_pubkey)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::encodable::{Decodable, Encodable};
+    use crate::encoding::hex;
+    use crate::encoding::parse_error::ParseError;
+    use crate::tx::tx_out::TxOut;
+
+    // The two P2PKH outputs of the Programming Bitcoin chapter 5 transaction
+    const P2PKH_OUTPUTS_HEX: &str = "a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac\
+                                     99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac";
+
+    // The outputs of f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16 in block 170,
+    // Satoshi's 10 BTC payment to Hal Finney and 40 BTC of change, both pay-to-pubkey
+    const BLOCK_170_OUTPUTS_HEX: &str = "00ca9a3b00000000434104ae1a62fe09c5f51b13905f07f06b99a2f7159b2225f374cd378d71302fa28414\
+                                         e7aab37397f554a7df5f142c21c1b7303b8a0626f1baded5c72a704f7e6cd84cac\
+                                         00286bee0000000043410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5c\
+                                         b2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac";
+
+    fn parse_all(bytes: &[u8]) -> Vec<TxOut> {
+        let mut reader = bytes;
+        let mut outputs = Vec::new();
+        while !reader.is_empty() {
+            outputs.push(TxOut::parse(&mut reader).unwrap());
+        }
+
+        outputs
+    }
+
+    #[test]
+    fn outputs_of_a_real_transaction_round_trip() {
+        for outputs_hex in [P2PKH_OUTPUTS_HEX, BLOCK_170_OUTPUTS_HEX] {
+            let bytes = hex::decode(outputs_hex).unwrap();
+
+            let outputs = parse_all(&bytes);
+
+            let serialized: Vec<u8> = outputs.iter().flat_map(|output| output.serialize_to_vec()).collect();
+            assert_eq!(serialized, bytes);
+        }
+    }
+
+    #[test]
+    fn p2pkh_output_amounts_and_scripts_parse() {
+        let outputs = parse_all(&hex::decode(P2PKH_OUTPUTS_HEX).unwrap());
+
+        assert_eq!(outputs[0].amount_sats(), 32454049);
+        assert_eq!(outputs[1].amount_sats(), 10011545);
+        assert_eq!(
+            &hex::encode(&outputs[1].script_pubkey),
+            "76a9141c4bc762dd5423e332166702cb75f40df79fea1288ac"
+        );
+    }
+
+    #[test]
+    fn ten_btc_output_of_block_170_parses() {
+        let outputs = parse_all(&hex::decode(BLOCK_170_OUTPUTS_HEX).unwrap());
+
+        assert_eq!(outputs[0].amount_sats(), 10 * 100_000_000);
+        assert_eq!(outputs[1].amount_sats(), 40 * 100_000_000);
+        assert_eq!(outputs[0].script_pubkey.len(), 67);
+    }
+
+    #[test]
+    fn truncated_output_errors_cleanly() {
+        let bytes = hex::decode(P2PKH_OUTPUTS_HEX).unwrap();
+
+        assert_eq!(TxOut::parse(&mut &bytes[..5]), Err(ParseError::Truncated("u64")));
+        assert_eq!(TxOut::parse(&mut &bytes[..20]), Err(ParseError::Truncated("TxOut")));
+    }
+}
@@ -0,0 +1,121 @@
+use crate::script::Script;
+use crate::tx::transaction::serialize_witness;
+
+// An input's witness stack, bottom item first. Inputs hold the bare items, which From gives back.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Witness {
+    pub items: Vec<Vec<u8>>,
+}
+
+impl Witness {
+    pub fn new(items: Vec<Vec<u8>>) -> Self {
+        Self { items }
+    }
+
+    // <DER signature with the sighash type byte> <compressed SEC pubkey>
+    pub fn p2wpkh(signature: &[u8], pubkey: &[u8]) -> Self {
+        Self::new(vec![signature.to_vec(), pubkey.to_vec()])
+    }
+
+    /*
+        The items the witness script runs on followed by the serialized script, whose sha256 the P2WSH program
+        commits to. A multisig witness script needs its empty dummy element as the first item.
+     */
+    pub fn p2wsh(items: &[Vec<u8>], witness_script: &Script) -> Self {
+        let mut items = items.to_vec();
+        items.push(witness_script.to_bytes());
+
+        Self::new(items)
+    }
+
+    // The item count and the length-prefixed items, as the witness is serialized in a segwit transaction
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        serialize_witness(&mut bytes, &self.items).expect("Writing to a Vec cannot fail.");
+
+        bytes
+    }
+}
+
+impl From<Witness> for Vec<Vec<u8>> {
+    fn from(witness: Witness) -> Self {
+        witness.items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ecc::private_key::PrivateKey;
+    use crate::ecc::u256::U256;
+    use crate::encoding::hex;
+    use crate::hashing::hash256::Hash256;
+    use crate::script::interpreter::ScriptFlags;
+    use crate::script::opcodes::OP_1;
+    use crate::script::{Cmd, Script};
+    use crate::tx::amount::Amount;
+    use crate::tx::outpoint::OutPoint;
+    use crate::tx::sighash::SighashType;
+    use crate::tx::transaction::Tx;
+    use crate::tx::tx_in::TxIn;
+    use crate::tx::tx_out::TxOut;
+    use crate::tx::witness::Witness;
+
+    // The native P2WPKH example from BIP143, whose second input spends 6 BTC from the key hash below
+    const BIP143_P2WPKH_HEX: &str = "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000049\
+                                     4830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b19\
+                                     4ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d27965\
+                                     5c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b3\
+                                     7df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2\
+                                     f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01c\
+                                     c44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188\
+                                     368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000";
+    const BIP143_P2WPKH_KEY_HASH_HEX: &str = "1d0f172a0ecb48aee1be1f2687d2963ae33f71a1";
+    const BIP143_P2WPKH_WITNESS_HEX: &str = "0247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a\
+                                             0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee012102\
+                                             5476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357";
+
+    #[test]
+    fn p2wpkh_witness_rebuilds_the_bip143_example() {
+        let tx = Tx::parse_exact(&hex::decode(BIP143_P2WPKH_HEX).unwrap(), false).unwrap();
+        let key_hash: [u8; 20] = hex::decode(BIP143_P2WPKH_KEY_HASH_HEX).unwrap().try_into().unwrap();
+        let script_code = Script::p2pkh(&key_hash).to_bytes();
+        let z = tx.sig_hash_bip143(1, &script_code, Amount::from_sat(600_000_000), SighashType::ALL).unwrap();
+        let [signature, pubkey] = tx.inputs[1].witness.clone().try_into().unwrap();
+
+        let witness = Witness::p2wpkh(&signature, &pubkey);
+
+        assert_eq!(hex::encode(&witness.to_bytes()), BIP143_P2WPKH_WITNESS_HEX);
+        assert_eq!(Script::p2wpkh(&key_hash).evaluate(z, Some(&witness.items), None, ScriptFlags::CONSENSUS), Ok(true));
+        assert_eq!(Vec::from(witness), tx.inputs[1].witness);
+    }
+
+    #[test]
+    fn p2wsh_witness_puts_the_script_last() {
+        let witness = Witness::p2wsh(&[vec![], vec![0x30, 0x01]], &Script::new(vec![Cmd::Op(OP_1)]));
+
+        assert_eq!(hex::encode(&witness.to_bytes()), "03000230010151");
+        assert_eq!(Witness::p2wsh(&[], &Script::new(vec![])).items, vec![vec![]]);
+    }
+
+    #[test]
+    fn constructed_p2wsh_multisig_spend_verifies() {
+        let private_keys = [2, 4].map(|secret| PrivateKey::new(U256::from_u64(secret)).unwrap());
+        let witness_script = Script::multisig(2, &private_keys.each_ref().map(|key| key.point()), false).unwrap();
+        let amount = Amount::from_sat(100_000);
+        let inputs = vec![TxIn::new(OutPoint::new(Hash256::from_wire_bytes([0x33; 32]), 1), vec![], 0xfffffffd)];
+        let outputs = vec![TxOut::new(Amount::from_sat(90_000), Script::p2wpkh(&[0x44; 20]).to_bytes())];
+        let mut tx = Tx::new(2, inputs, outputs, 0, false);
+        let z = tx.sig_hash_bip143(0, &witness_script.to_bytes(), amount, SighashType::ALL).unwrap();
+        let signatures = private_keys.map(|key| [key.sign(&z).der(), vec![SighashType::ALL.to_u32() as u8]].concat());
+
+        let items = [vec![vec![]], signatures.to_vec()].concat();
+        tx.inputs[0].witness = Witness::p2wsh(&items, &witness_script).into();
+
+        let witness = &tx.inputs[0].witness;
+        assert_eq!(witness.len(), 4);
+        assert_eq!(witness[3], witness_script.to_bytes());
+        assert_eq!(witness_script.to_p2wsh().evaluate(z, Some(witness), None, ScriptFlags::CONSENSUS), Ok(true));
+        let swapped = [witness[0].clone(), witness[2].clone(), witness[1].clone(), witness[3].clone()];
+        assert_eq!(witness_script.to_p2wsh().evaluate(z, Some(&swapped), None, ScriptFlags::CONSENSUS), Ok(false));
+    }
+}
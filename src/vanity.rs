@@ -0,0 +1,217 @@
+use std::fmt::{Display, Formatter};
+use crate::ecc::private_key::PrivateKey;
+use crate::ecc::scalar::Scalar;
+use crate::ecc::secp256k1::S256Point;
+use crate::ecc::u256::U256;
+use crate::encoding::base58::is_base58_character;
+use crate::encoding::bech32::is_data_character;
+use crate::network::Network;
+use crate::rng::CryptoRng;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VanityError {
+    // A character that no address of the requested kind can contain
+    InvalidPrefixCharacter(char),
+    // No address matched within this many keys
+    IterationCapReached(u64),
+}
+
+impl Display for VanityError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VanityError::InvalidPrefixCharacter(character) => {
+                write!(f, "'{}' cannot appear in an address of the requested kind.", character)
+            }
+            VanityError::IterationCapReached(cap) => write!(f, "No matching address within {} keys.", cap),
+        }
+    }
+}
+
+impl std::error::Error for VanityError {}
+
+// Whether candidates are P2PKH addresses in base58 or P2WPKH addresses in bech32
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFormat {
+    P2pkh { compressed: bool },
+    P2wpkh,
+}
+
+/*
+    An endless run of keys and their addresses. The first secret is drawn from the generator and each one after
+    is the previous plus one, so a step costs a single point addition instead of a multiplication by G. To search
+    on several threads, give each its own iterator from its own generator.
+ */
+pub struct Candidates {
+    secret: Scalar,
+    point: S256Point,
+    format: AddressFormat,
+    network: Network,
+}
+
+impl Candidates {
+    pub fn new(format: AddressFormat, network: Network, rng: &mut impl CryptoRng) -> Self {
+        let key = random_key(rng);
+
+        Self {
+            secret: key.secret(),
+            point: key.point(),
+            format,
+            network,
+        }
+    }
+}
+
+impl Iterator for Candidates {
+    type Item = (PrivateKey, String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = PrivateKey::from_parts(self.secret, self.point);
+        let address = match self.format {
+            AddressFormat::P2pkh { compressed } => self.point.address(compressed, self.network),
+            AddressFormat::P2wpkh => self.point.p2wpkh_address(self.network),
+        };
+
+        let one = Scalar::new(U256::from_u64(1)).expect("One is below N.");
+        self.secret = self.secret + one;
+        self.point = match self.point.add(&S256Point::generator()) {
+            Some(point) => point,
+            // Stepping from N - 1 reaches zero, so start over from one
+            None => {
+                self.secret = one;
+                S256Point::generator()
+            }
+        };
+
+        Some((key, address))
+    }
+}
+
+/*
+    Looks for an address starting with the prefix. A prefix starting with the network's bech32 prefix, such as
+    "bc1q", asks for P2WPKH addresses and is matched in either case; any other asks for P2PKH addresses, which
+    are case sensitive. A prefix whose first characters no address of the network starts with is not rejected
+    here, so it runs until the cap.
+ */
+pub fn search(
+    prefix: &str,
+    network: Network,
+    compressed: bool,
+    max_iterations: u64,
+    rng: &mut impl CryptoRng,
+) -> Result<(PrivateKey, String), VanityError> {
+    let bech32_prefix = format!("{}1", network.bech32_hrp());
+    if prefix.to_ascii_lowercase().starts_with(&bech32_prefix) {
+        let prefix = prefix.to_ascii_lowercase();
+        if let Some(character) = prefix[bech32_prefix.len()..].chars().find(|&c| !is_data_character(c)) {
+            return Err(VanityError::InvalidPrefixCharacter(character));
+        }
+
+        return search_by(AddressFormat::P2wpkh, network, max_iterations, rng, |address| address.starts_with(&prefix));
+    }
+
+    if let Some(character) = prefix.chars().find(|&c| !is_base58_character(c)) {
+        return Err(VanityError::InvalidPrefixCharacter(character));
+    }
+    let format = AddressFormat::P2pkh { compressed };
+
+    search_by(format, network, max_iterations, rng, |address| address.starts_with(prefix))
+}
+
+// The first of at most max_iterations candidates whose address the predicate accepts
+pub fn search_by(
+    format: AddressFormat,
+    network: Network,
+    max_iterations: u64,
+    rng: &mut impl CryptoRng,
+    mut predicate: impl FnMut(&str) -> bool,
+) -> Result<(PrivateKey, String), VanityError> {
+    Candidates::new(format, network, rng)
+        .take(max_iterations as usize)
+        .find(|(_, address)| predicate(address))
+        .ok_or(VanityError::IterationCapReached(max_iterations))
+}
+
+// Secrets of 32 random bytes, drawing again in the rare case that they are zero or not below N
+fn random_key(rng: &mut impl CryptoRng) -> PrivateKey {
+    loop {
+        let mut bytes = [0; 32];
+        rng.fill_bytes(&mut bytes);
+        if let Some(key) = PrivateKey::new(U256::from_be_bytes(bytes)) {
+            return key;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ecc::private_key::PrivateKey;
+    use crate::network::Network;
+    use crate::rng::SeededRng;
+    use crate::vanity::{search, search_by, AddressFormat, Candidates, VanityError};
+
+    #[test]
+    fn found_keys_produce_the_found_addresses() {
+        for (prefix, compressed) in [("1A", true), ("1z", false)] {
+            let (key, address) = search(prefix, Network::Mainnet, compressed, 100_000, &mut SeededRng::from_u64(1))
+                .unwrap();
+
+            assert!(address.starts_with(prefix));
+            assert_eq!(key.point().address(compressed, Network::Mainnet), address);
+        }
+
+        let (key, address) = search("tb1qq", Network::Testnet, true, 100_000, &mut SeededRng::from_u64(2)).unwrap();
+        assert!(address.starts_with("tb1qq"));
+        assert_eq!(key.point().p2wpkh_address(Network::Testnet), address);
+    }
+
+    #[test]
+    fn the_same_seed_finds_the_same_key() {
+        let first = search("1B", Network::Mainnet, true, 100_000, &mut SeededRng::from_u64(3)).unwrap();
+        let second = search("1B", Network::Mainnet, true, 100_000, &mut SeededRng::from_u64(3)).unwrap();
+
+        assert_eq!(first.0.secret(), second.0.secret());
+        assert_eq!(first.1, second.1);
+    }
+
+    #[test]
+    fn candidates_step_through_consecutive_secrets() {
+        let format = AddressFormat::P2pkh { compressed: true };
+        let candidates: Vec<_> = Candidates::new(format, Network::Testnet, &mut SeededRng::from_u64(4)).take(3).collect();
+
+        for (key, address) in &candidates {
+            // A key built from its secret alone has to agree with the stepped point
+            let derived = PrivateKey::new(key.secret().value()).unwrap();
+            assert_eq!(derived.point(), key.point());
+            assert_eq!(&derived.point().address(true, Network::Testnet), address);
+        }
+        assert_eq!(candidates[1].0.secret() - candidates[0].0.secret(), candidates[2].0.secret() - candidates[1].0.secret());
+    }
+
+    #[test]
+    fn predicates_and_caps() {
+        let format = AddressFormat::P2wpkh;
+        let mut rng = SeededRng::from_u64(5);
+
+        let (_, address) = search_by(format, Network::Mainnet, 1_000, &mut rng, |address| address.ends_with('q')).unwrap();
+        assert!(address.ends_with('q'));
+
+        assert_eq!(
+            search_by(format, Network::Mainnet, 50, &mut rng, |_| false),
+            Err(VanityError::IterationCapReached(50))
+        );
+        // Mainnet P2PKH addresses all start with 1, so this can only run out
+        assert_eq!(
+            search("2", Network::Mainnet, true, 20, &mut rng),
+            Err(VanityError::IterationCapReached(20))
+        );
+    }
+
+    #[test]
+    fn characters_outside_the_alphabet_are_rejected() {
+        let mut rng = SeededRng::from_u64(6);
+
+        assert_eq!(search("10", Network::Mainnet, true, 10, &mut rng), Err(VanityError::InvalidPrefixCharacter('0')));
+        assert_eq!(search("1Il", Network::Mainnet, true, 10, &mut rng), Err(VanityError::InvalidPrefixCharacter('I')));
+        assert_eq!(search("bc1qb", Network::Mainnet, true, 10, &mut rng), Err(VanityError::InvalidPrefixCharacter('b')));
+    }
+}